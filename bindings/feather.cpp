@@ -16,6 +16,12 @@ PYBIND11_MODULE(core, m) {
         .value("CONVERSATION", feather::ContextType::CONVERSATION)
         .export_values();
 
+    // ── Compression ──────────────────────────────────────────────────
+    py::enum_<feather::Compression>(m, "Compression")
+        .value("NONE",     feather::Compression::None)
+        .value("METADATA", feather::Compression::Metadata)
+        .value("ALL",      feather::Compression::All);
+
     // ── Edge ─────────────────────────────────────────────────────────
     py::class_<feather::Edge>(m, "Edge")
         .def(py::init<>())
@@ -287,6 +293,14 @@ PYBIND11_MODULE(core, m) {
         .def("is_quantized", &feather::DB::is_quantized, py::arg("modality"),
              "Whether a modality is persisted with int8 quantization.")
 
+        // -- Compression (file format v12) --
+        .def("set_compression", &feather::DB::set_compression, py::arg("compression"),
+             "Pack the file with zstd from the next save() on: Compression.METADATA "
+             "packs records and content, Compression.ALL every section. A packed "
+             "file is format v12, which older builds cannot open.")
+        .def("compression", &feather::DB::compression,
+             "What save() packs; kept in the file.")
+
         // -- In-RAM int8 quantization (4x less memory) --
         .def("set_int8_ram", &feather::DB::set_int8_ram,
              py::arg("modality"), py::arg("max_abs") = 1.0f,
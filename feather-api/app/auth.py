@@ -0,0 +1,204 @@
+"""Namespace-scoped API keys with per-key usage counters.
+
+Besides the master key (FEATHER_API_KEY), admins can mint keys tied to a set
+of namespaces (exact names or fnmatch patterns like ``team_a_*``; ``*`` = all).
+Every request made with a key is counted per namespace — searches, inserts,
+total requests, bytes in/out — so several internal products can share one
+server with accountability.
+
+Keys live in FEATHER_KEYS_FILE (default ``<FEATHER_DATA_DIR>/_keys.json``).
+Only a SHA-256 of each token is stored; the token itself is shown once, at
+creation. Usage counters are flushed to the same file at most every
+FEATHER_USAGE_SAVE_INTERVAL_S seconds and on shutdown.
+
+Auth is disabled (dev mode) only while there is neither a master key nor any
+minted key. So that /v1/admin always has a way in, the first key minted
+without a master key must be an admin key, and the last admin key cannot be
+revoked while there is no master key.
+"""
+import fnmatch
+import hashlib
+import hmac
+import json
+import os
+import secrets
+import threading
+import time
+from typing import Dict, List, Optional
+
+from .db_manager import DATA_DIR
+
+
+KEYS_FILE = os.getenv("FEATHER_KEYS_FILE", os.path.join(DATA_DIR, "_keys.json"))
+_USAGE_SAVE_INTERVAL_S = float(os.getenv("FEATHER_USAGE_SAVE_INTERVAL_S", "30"))
+
+MASTER_KEY_ID = "master"
+USAGE_FIELDS = ("requests", "searches", "inserts", "bytes_in", "bytes_out")
+
+
+def _hash(token: str) -> str:
+    return hashlib.sha256(token.encode()).hexdigest()
+
+
+def _empty_usage() -> Dict[str, int]:
+    return {f: 0 for f in USAGE_FIELDS}
+
+
+class ApiKey:
+    def __init__(self, key_id: str, name: str, token_hash: str,
+                 namespaces: List[str], admin: bool = False,
+                 created_at: Optional[int] = None,
+                 usage: Optional[Dict[str, Dict[str, int]]] = None,
+                 last_used_at: int = 0):
+        self.id = key_id
+        self.name = name
+        self.token_hash = token_hash
+        self.namespaces = list(namespaces)
+        self.admin = admin
+        self.created_at = created_at or int(time.time())
+        self.usage: Dict[str, Dict[str, int]] = usage or {}
+        self.last_used_at = last_used_at
+
+    def allows(self, namespace: str) -> bool:
+        if self.admin:
+            return True
+        return any(fnmatch.fnmatchcase(namespace, p) for p in self.namespaces)
+
+    def totals(self) -> Dict[str, int]:
+        out = _empty_usage()
+        for counters in self.usage.values():
+            for f in USAGE_FIELDS:
+                out[f] += counters.get(f, 0)
+        return out
+
+    def to_json(self) -> Dict:
+        return {
+            "id": self.id, "name": self.name, "token_hash": self.token_hash,
+            "namespaces": self.namespaces, "admin": self.admin,
+            "created_at": self.created_at, "usage": self.usage,
+            "last_used_at": self.last_used_at,
+        }
+
+    @classmethod
+    def from_json(cls, d: Dict) -> "ApiKey":
+        return cls(d["id"], d.get("name", ""), d["token_hash"], d.get("namespaces", []),
+                   admin=bool(d.get("admin", False)), created_at=d.get("created_at"),
+                   usage=d.get("usage") or {}, last_used_at=d.get("last_used_at", 0))
+
+
+class KeyStore:
+    def __init__(self, path: str = KEYS_FILE, master_key: str = ""):
+        self._path = path
+        self._lock = threading.Lock()
+        self._keys: Dict[str, ApiKey] = {}
+        self._by_hash: Dict[str, ApiKey] = {}
+        self._dirty = False
+        self._last_save = 0.0
+        self.master: Optional[ApiKey] = None
+        if master_key:
+            self.master = ApiKey(MASTER_KEY_ID, "master (FEATHER_API_KEY)", _hash(master_key),
+                                 ["*"], admin=True)
+        self._load()
+
+    def _load(self):
+        try:
+            with open(self._path) as fh:
+                data = json.load(fh)
+        except (OSError, ValueError):
+            return
+        for d in data.get("keys", []):
+            k = ApiKey.from_json(d)
+            self._keys[k.id] = k
+            self._by_hash[k.token_hash] = k
+        if self.master and data.get("master_usage"):
+            self.master.usage = data["master_usage"]
+
+    def _save_locked(self):
+        data = {"keys": [k.to_json() for k in self._keys.values()]}
+        if self.master:
+            data["master_usage"] = self.master.usage
+        tmp = self._path + ".tmp"
+        os.makedirs(os.path.dirname(self._path) or ".", exist_ok=True)
+        with open(tmp, "w") as fh:
+            json.dump(data, fh)
+        os.replace(tmp, self._path)
+        self._dirty = False
+        self._last_save = time.time()
+
+    def save(self):
+        with self._lock:
+            if self._dirty:
+                self._save_locked()
+
+    @property
+    def enabled(self) -> bool:
+        return self.master is not None or bool(self._keys)
+
+    def _admin_besides(self, key: Optional[ApiKey] = None) -> bool:
+        """True if a key other than `key` can reach the admin routes."""
+        return self.master is not None or any(k.admin and k is not key for k in self._keys.values())
+
+    def authenticate(self, token: str) -> Optional[ApiKey]:
+        if not token:
+            return None
+        h = _hash(token)
+        if self.master and hmac.compare_digest(h, self.master.token_hash):
+            return self.master
+        return self._by_hash.get(h)
+
+    def create(self, name: str, namespaces: List[str], admin: bool = False):
+        """Mint a key. Returns (ApiKey, token); the token is not stored.
+        Raises ValueError for a non-admin key while no key is admin."""
+        token = "fk_" + secrets.token_urlsafe(32)
+        with self._lock:
+            if not admin and not self._admin_besides():
+                raise ValueError("there is no admin key yet: the first key minted must be an admin key")
+            key_id = secrets.token_hex(4)
+            while key_id in self._keys or key_id == MASTER_KEY_ID:
+                key_id = secrets.token_hex(4)
+            k = ApiKey(key_id, name, _hash(token), namespaces, admin=admin)
+            self._keys[key_id] = k
+            self._by_hash[k.token_hash] = k
+            self._save_locked()
+        return k, token
+
+    def revoke(self, key_id: str) -> bool:
+        """Revoke a minted key; False if there is none by that id. Raises
+        ValueError for the last admin key while there is no master key."""
+        with self._lock:
+            k = self._keys.get(key_id)
+            if k is None:
+                return False
+            if k.admin and not self._admin_besides(k):
+                raise ValueError("this is the last admin key: mint another before revoking it")
+            del self._keys[key_id]
+            self._by_hash.pop(k.token_hash, None)
+            self._save_locked()
+            return True
+
+    def get(self, key_id: str) -> Optional[ApiKey]:
+        if self.master and key_id == MASTER_KEY_ID:
+            return self.master
+        return self._keys.get(key_id)
+
+    def list(self) -> List[ApiKey]:
+        keys = list(self._keys.values())
+        return ([self.master] if self.master else []) + sorted(keys, key=lambda k: k.created_at)
+
+    def record(self, key: ApiKey, namespace: str, op: str,
+               bytes_in: int = 0, bytes_out: int = 0):
+        """Count one request made with `key` against `namespace` ("" for
+        server-level routes)."""
+        with self._lock:
+            c = key.usage.setdefault(namespace, _empty_usage())
+            c["requests"] = c.get("requests", 0) + 1
+            if op == "search":
+                c["searches"] = c.get("searches", 0) + 1
+            elif op in ("add", "seed", "import", "ingest"):
+                c["inserts"] = c.get("inserts", 0) + 1
+            c["bytes_in"] = c.get("bytes_in", 0) + bytes_in
+            c["bytes_out"] = c.get("bytes_out", 0) + bytes_out
+            key.last_used_at = int(time.time())
+            self._dirty = True
+            if time.time() - self._last_save >= _USAGE_SAVE_INTERVAL_S:
+                self._save_locked()
@@ -114,13 +114,17 @@ class EmbeddingProvider:
         return self.snapshot()
 
     # ── embed dispatch ──────────────────────────────────────────
-    def embed(self, text: str) -> List[float]:
-        """Embed a single string. Raises RuntimeError on misconfig or upstream error."""
+    def embed(self, text: str, model: Optional[str] = None) -> List[float]:
+        """Embed a single string. Raises RuntimeError on misconfig or upstream error.
+
+        `model` overrides the configured model (same provider and credentials)
+        and returns the model's native dim — used by shadow namespaces."""
+        override     = model
         with self._lock:
             provider     = self.provider
-            model        = self.model
+            model        = override or self.model
             base_url     = self.base_url
-            deployment   = self.deployment
+            deployment   = "" if override else self.deployment
             api_version  = self.api_version
             key          = self._api_key
             dim          = self.dim
@@ -145,7 +149,7 @@ class EmbeddingProvider:
             raise RuntimeError(f"Unknown provider: {provider}")
         # safety: pad/truncate to configured dim if shape mismatch (lets users
         # plug in a different model without crashing the whole pipeline)
-        if len(vec) != dim:
+        if len(vec) != dim and not override:
             if len(vec) < dim:
                 vec = vec + [0.0] * (dim - len(vec))
             else:
@@ -0,0 +1,130 @@
+"""Group commit for single-record inserts.
+
+Chatty agents write one memory per turn, so a busy server sees many small
+concurrent `POST /v1/{ns}/vectors` calls. Each one takes the namespace lock,
+appends one WAL entry and inserts one HNSW node. With group commit enabled
+(FEATHER_GROUP_COMMIT_MS > 0) concurrent inserts into the same namespace and
+modality are coalesced: the first request of a window becomes the leader,
+waits up to the configured delay (or until FEATHER_GROUP_COMMIT_MAX records
+are pending), then commits the whole group with one `add_batch` — one lock
+acquisition and a parallel graph build — and wakes every waiting request.
+
+Every request still returns only after its record is committed, so the API
+semantics are unchanged; a lone request just pays up to the delay in latency.
+If a batch fails (e.g. one bad vector), its records are retried one by one so
+only the offending request sees the error.
+"""
+import os
+import threading
+import time
+from concurrent.futures import Future
+from typing import Dict, List, Tuple
+
+import numpy as np
+
+
+GROUP_COMMIT_MS = float(os.getenv("FEATHER_GROUP_COMMIT_MS", "0"))
+GROUP_COMMIT_MAX = int(os.getenv("FEATHER_GROUP_COMMIT_MAX", "256"))
+
+
+class _Group:
+    def __init__(self):
+        self.pending: List[Tuple[int, list, object, Future]] = []
+        self.leader = False
+        self.cond = threading.Condition()
+
+
+class GroupCommitter:
+    def __init__(self, manager, max_delay_ms: float = GROUP_COMMIT_MS,
+                 max_batch: int = GROUP_COMMIT_MAX):
+        self._manager = manager
+        self.max_delay_s = max(0.0, max_delay_ms) / 1000.0
+        self.max_batch = max(1, max_batch)
+        self._groups: Dict[Tuple[str, str], _Group] = {}
+        self._groups_lock = threading.Lock()
+        self._stats_lock = threading.Lock()
+        self._commits = 0
+        self._records = 0
+        self._largest = 0
+
+    @property
+    def enabled(self) -> bool:
+        return self.max_delay_s > 0
+
+    def _group(self, namespace: str, modality: str) -> _Group:
+        with self._groups_lock:
+            return self._groups.setdefault((namespace, modality), _Group())
+
+    def add(self, namespace: str, rec_id: int, vec: list, meta, modality: str = "text"):
+        """Insert one record, possibly as part of a group. Blocks until the
+        record is committed; re-raises the insert's error, if any."""
+        if not self.enabled:
+            db = self._manager.get(namespace)
+            with self._manager.lock(namespace):
+                db.add(id=rec_id, vec=vec, meta=meta, modality=modality)
+            return
+
+        g = self._group(namespace, modality)
+        fut: Future = Future()
+        with g.cond:
+            g.pending.append((rec_id, vec, meta, fut))
+            if len(g.pending) >= self.max_batch:
+                g.cond.notify_all()
+            lead = not g.leader
+            if lead:
+                g.leader = True
+                deadline = time.monotonic() + self.max_delay_s
+                while len(g.pending) < self.max_batch:
+                    left = deadline - time.monotonic()
+                    if left <= 0:
+                        break
+                    g.cond.wait(left)
+                batch, g.pending = g.pending, []
+                g.leader = False
+        if lead:
+            self._commit(namespace, modality, batch)
+        fut.result()
+
+    def _commit(self, namespace: str, modality: str, batch):
+        # Last write wins for an id submitted twice in one window.
+        latest: Dict[int, int] = {}
+        for i, (rec_id, _, _, _) in enumerate(batch):
+            latest[rec_id] = i
+        rows = [batch[i] for i in sorted(latest.values())]
+        db = self._manager.get(namespace)
+        try:
+            with self._manager.lock(namespace):
+                db.add_batch([r[0] for r in rows],
+                             np.asarray([r[1] for r in rows], dtype=np.float32),
+                             [r[2] for r in rows], modality=modality)
+            for _, _, _, fut in batch:
+                fut.set_result(None)
+        except Exception:  # noqa: BLE001 — isolate the bad record(s)
+            failed: Dict[int, BaseException] = {}
+            for rec_id, vec, meta, _ in rows:
+                try:
+                    with self._manager.lock(namespace):
+                        db.add(id=rec_id, vec=vec, meta=meta, modality=modality)
+                except Exception as e:  # noqa: BLE001
+                    failed[rec_id] = e
+            for rec_id, _, _, fut in batch:
+                if rec_id in failed:
+                    fut.set_exception(failed[rec_id])
+                else:
+                    fut.set_result(None)
+        with self._stats_lock:
+            self._commits += 1
+            self._records += len(batch)
+            self._largest = max(self._largest, len(batch))
+
+    def stats(self) -> Dict:
+        with self._stats_lock:
+            return {
+                "enabled": self.enabled,
+                "maxDelayMs": self.max_delay_s * 1000.0,
+                "maxBatch": self.max_batch,
+                "commits": self._commits,
+                "records": self._records,
+                "avgBatch": round(self._records / self._commits, 2) if self._commits else 0.0,
+                "largestBatch": self._largest,
+            }
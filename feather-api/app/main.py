@@ -17,8 +17,24 @@ Endpoints:
   POST /v1/{namespace}/records/{id}/link        — link two records
   POST /v1/{namespace}/save             — flush to disk
 
-Authentication: X-API-Key header (set FEATHER_API_KEY env var).
-If FEATHER_API_KEY is unset, auth is disabled (dev mode).
+  POST   /v1/admin/keys                 — mint a namespace-scoped API key
+  GET    /v1/admin/keys                 — list keys with usage counters
+  GET    /v1/admin/keys/{key_id}        — one key's usage
+  DELETE /v1/admin/keys/{key_id}        — revoke
+
+  PUT    /v1/admin/shadows/{namespace}  — dual-write into a shadow namespace
+  GET    /v1/admin/shadows[/{namespace}] — write counters + sampled query diffs
+  DELETE /v1/admin/shadows/{namespace}  — stop shadowing
+
+OpenAPI: GET /openapi.json (interactive docs at /docs). Every route has a
+typed response model in app/models.py except the free-form dashboard feeds
+(graph, metrics, activity, embedding_models). `python -m app.openapi [out.json]`
+writes the document without starting the server, for client codegen.
+
+Authentication: X-API-Key header — the master key (FEATHER_API_KEY env var)
+or a scoped key minted via /v1/admin/keys (see app/auth.py). With neither
+configured, auth is disabled (dev mode); the first key minted then must be
+an admin key.
 """
 
 import os
@@ -30,15 +46,19 @@ from concurrent.futures import ThreadPoolExecutor
 from contextlib import asynccontextmanager
 from typing import Optional, List, Tuple, Dict
 
-from fastapi import FastAPI, HTTPException, Depends, Header, Request, UploadFile, File, Form
+from fastapi import FastAPI, HTTPException, Depends, Security, Request, UploadFile, File, Form
 from fastapi.responses import JSONResponse, RedirectResponse
+from fastapi.security import APIKeyHeader
 from fastapi.staticfiles import StaticFiles
 
 import feather_db
 from feather_db import Metadata, ContextType, ScoringConfig
 from feather_db.core import SearchFilter
 
+from .auth import KeyStore
 from .db_manager import DBManager
+from .group_commit import GroupCommitter
+from .shadow import ShadowRegistry
 from .metrics import METRICS, classify, namespace_from_path
 from .embedding import EMBEDDING, SUPPORTED_MODELS
 from .models import (
@@ -53,8 +73,14 @@ from .models import (
     TopRecalledItem, OpsTimeseriesResponse, OpsTimeseriesPoint,
     ConnectionInfo, EmbeddingConfig, EmbeddingConfigUpdate,
     ImportRequest, ImportResponse, IngestTextRequest,
+    CreateApiKeyRequest, ApiKeyOut, ApiKeyCreated, KeyUsage,
     HierarchyNode, HierarchyResponse,
-    AutoCompactRequest, QuantizeRequest, IndexStatsResponse,
+    AutoCompactRequest, QuantizeRequest, IndexStatsResponse, ShadowRequest,
+    NamespaceList, NamespaceCreated, NamespaceDeleted, VectorAdded, TextIngested,
+    RecordUpdated, ImportanceUpdated, LinkCreated, LinkRemoved, RecordDeleted,
+    BatchDeleteResponse, PurgeResponse, CompactResponse, SavedResponse, RecordsPage,
+    UploadResponse, SeedResponse, AutoCompactResponse, QuantizeResponse,
+    ApiKeyList, ApiKeyRevoked, ShadowStatus, ShadowList, ShadowStopped,
 )
 import numpy as np
 import json
@@ -73,22 +99,34 @@ logger = logging.getLogger("feather-api")
 # App lifecycle
 # ─────────────────────────────────────────────
 manager: Optional[DBManager] = None
+committer: Optional[GroupCommitter] = None
+shadows: Optional[ShadowRegistry] = None
 
 @asynccontextmanager
 async def lifespan(app: FastAPI):
-    global manager
+    global manager, committer, shadows
     logger.info("Starting Feather DB Cloud API...")
     manager = DBManager()
+    committer = GroupCommitter(manager)
+    shadows = ShadowRegistry(manager, EMBEDDING.embed)
+    if committer.enabled:
+        logger.info(f"Group commit on: max delay {committer.max_delay_s * 1000:g} ms, "
+                    f"max batch {committer.max_batch}")
     logger.info(f"Loaded namespaces: {manager.list_namespaces()}")
     yield
     logger.info("Shutting down — saving all DBs...")
+    shadows.shutdown()   # drain queued shadow writes first
     manager.save_all()
+    KEYS.save()
 
 app = FastAPI(
     title="Feather DB Cloud API",
     description="REST API for Feather DB — embedded vector database with living context.",
     version=feather_db.__version__,
     lifespan=lifespan,
+    # operationId = handler name (e.g. `search`, `batch_delete`), so generated
+    # clients get stable method names instead of FastAPI's path-derived ones.
+    generate_unique_id_function=lambda route: route.name,
 )
 
 
@@ -100,12 +138,21 @@ async def _metrics_middleware(request: Request, call_next):
     path = request.url.path
     if path.startswith("/admin") or path.startswith("/static"):
         return response          # don't count static asset hits
+    op = classify(request.method, path)
+    namespace = namespace_from_path(path)
     METRICS.record(
-        op=classify(request.method, path),
+        op=op,
         latency_ms=dt,
-        namespace=namespace_from_path(path),
+        namespace=namespace,
         status=response.status_code,
     )
+    key = getattr(request.state, "api_key", None)
+    if key is not None:
+        KEYS.record(
+            key, namespace, op,
+            bytes_in=int(request.headers.get("content-length") or 0),
+            bytes_out=int(response.headers.get("content-length") or 0),
+        )
     return response
 
 # ─────────────────────────────────────────────
@@ -150,12 +197,32 @@ def _root():
 # Auth middleware
 # ─────────────────────────────────────────────
 API_KEY = os.getenv("FEATHER_API_KEY", "")
+KEYS = KeyStore(master_key=API_KEY)
+# Declared as a security scheme so it shows up as such in /openapi.json.
+API_KEY_HEADER = APIKeyHeader(name="X-API-Key", auto_error=False)
+
+
+def _is_admin_route(request: Request) -> bool:
+    """Server-wide routes: /v1/admin/* and namespace create/delete."""
+    path = request.url.path
+    if path.startswith("/v1/admin/"):
+        return True
+    return path.startswith("/v1/namespaces") and request.method != "GET"
+
 
-def verify_api_key(x_api_key: str = Header(default="")):
-    if not API_KEY:
+def verify_api_key(request: Request, x_api_key: Optional[str] = Security(API_KEY_HEADER)):
+    if not KEYS.enabled:
         return   # dev mode — no key required
-    if x_api_key != API_KEY:
+    key = KEYS.authenticate(x_api_key or "")
+    if key is None:
         raise HTTPException(status_code=401, detail="Invalid or missing X-API-Key")
+    if _is_admin_route(request) and not key.admin:
+        raise HTTPException(status_code=403, detail="This route needs an admin key")
+    namespace = request.path_params.get("namespace")
+    if namespace and not key.allows(namespace):
+        raise HTTPException(status_code=403,
+                            detail=f"API key '{key.name}' is not scoped to namespace '{namespace}'")
+    request.state.api_key = key   # usage is counted by the metrics middleware
 
 # ─────────────────────────────────────────────
 # Helpers
@@ -309,12 +376,17 @@ def health():
         namespaces_loaded=len(manager.list_namespaces()),
     )
 
-@app.get("/v1/namespaces", tags=["meta"], dependencies=[Depends(verify_api_key)])
-def list_namespaces():
-    return {"namespaces": manager.list_namespaces()}
+@app.get("/v1/namespaces", response_model=NamespaceList, tags=["meta"],
+         dependencies=[Depends(verify_api_key)])
+def list_namespaces(request: Request):
+    names = manager.list_namespaces()
+    key = getattr(request.state, "api_key", None)
+    if key is not None:
+        names = [n for n in names if key.allows(n)]
+    return {"namespaces": names}
 
 
-@app.post("/v1/namespaces", status_code=201, tags=["meta"],
+@app.post("/v1/namespaces", response_model=NamespaceCreated, status_code=201, tags=["meta"],
           dependencies=[Depends(verify_api_key)])
 def create_namespace(req: CreateNamespaceRequest):
     """Create an empty namespace (a new .feather file on disk).
@@ -328,13 +400,14 @@ def create_namespace(req: CreateNamespaceRequest):
     return {"name": req.name, "dim": db.dim(), "created": True}
 
 
-@app.delete("/v1/namespaces/{namespace}", tags=["meta"],
+@app.delete("/v1/namespaces/{namespace}", response_model=NamespaceDeleted, tags=["meta"],
             dependencies=[Depends(verify_api_key)])
 def delete_namespace(namespace: str):
     """Hard-delete a namespace. Drops in-memory state + removes .feather + .wal from disk."""
     if namespace not in manager.list_namespaces():
         raise HTTPException(404, f"Namespace '{namespace}' not found")
     removed = manager.delete(namespace)
+    shadows.forget_namespace(namespace)
     return {"namespace": namespace, "deleted": bool(removed)}
 
 
@@ -521,7 +594,8 @@ def admin_overview():
 MAX_UPLOAD_BYTES = int(os.getenv("FEATHER_MAX_UPLOAD_MB", "256")) * 1024 * 1024
 
 
-@app.post("/v1/admin/upload", tags=["meta"], dependencies=[Depends(verify_api_key)])
+@app.post("/v1/admin/upload", response_model=UploadResponse, tags=["meta"],
+          dependencies=[Depends(verify_api_key)])
 async def upload_feather(
     file: UploadFile = File(...),
     namespace: str = Form(...),
@@ -584,7 +658,8 @@ async def upload_feather(
 # ─────────────────────────────────────────────
 # Routes — vector operations
 # ─────────────────────────────────────────────
-@app.post("/v1/{namespace}/vectors", status_code=201, tags=["vectors"],
+@app.post("/v1/{namespace}/vectors", response_model=VectorAdded, status_code=201,
+          tags=["vectors"],
           dependencies=[Depends(verify_api_key)])
 def add_vector(namespace: str, req: AddVectorRequest):
     db = manager.get(namespace)
@@ -605,8 +680,8 @@ def add_vector(namespace: str, req: AddVectorRequest):
     if not meta.namespace_id:
         meta.namespace_id = namespace
 
-    with manager.lock(namespace):
-        db.add(id=req.id, vec=req.vector, meta=meta, modality=req.modality)
+    committer.add(namespace, req.id, req.vector, meta, modality=req.modality)
+    shadows.mirror_add(namespace, [req.id], [req.vector], [meta], req.modality)
 
     return {"id": req.id, "namespace": namespace, "modality": req.modality}
 
@@ -623,7 +698,15 @@ def search_vectors(namespace: str, req: SearchRequest):
     sc = _build_scoring(req)
     _check_query_dim(db, req.vector, req.modality)
 
+    t0 = time.perf_counter()
     raw = db.search(req.vector, k=req.k, filter=sf, scoring=sc, modality=req.modality)
+    shadow = shadows.sample(namespace)
+    if shadow is not None:
+        shadows.compare(
+            shadow, req.vector, req.text, req.k, [(r.id, r.score) for r in raw],
+            (time.perf_counter() - t0) * 1000,
+            lambda sdb, q: sdb.search(q, k=req.k, filter=sf, scoring=sc, modality=req.modality),
+        )
 
     items = [
         SearchResultItem(id=r.id, score=r.score, metadata=_meta_to_model(r.metadata))
@@ -648,7 +731,7 @@ def get_record(namespace: str, record_id: int):
     return _meta_to_model(meta)
 
 
-@app.put("/v1/{namespace}/records/{record_id}", tags=["records"],
+@app.put("/v1/{namespace}/records/{record_id}", response_model=RecordUpdated, tags=["records"],
          dependencies=[Depends(verify_api_key)])
 def update_record_metadata(namespace: str, record_id: int, req: UpdateMetadataRequest):
     try:
@@ -659,10 +742,12 @@ def update_record_metadata(namespace: str, record_id: int, req: UpdateMetadataRe
     meta = _meta_from_model(req.metadata)
     with manager.lock(namespace):
         db.update_metadata(record_id, meta)
+    shadows.mirror(namespace, lambda sdb: sdb.update_metadata(record_id, meta))
     return {"id": record_id, "updated": True}
 
 
-@app.put("/v1/{namespace}/records/{record_id}/importance", tags=["records"],
+@app.put("/v1/{namespace}/records/{record_id}/importance", response_model=ImportanceUpdated,
+         tags=["records"],
          dependencies=[Depends(verify_api_key)])
 def update_importance(namespace: str, record_id: int, req: UpdateImportanceRequest):
     try:
@@ -672,10 +757,12 @@ def update_importance(namespace: str, record_id: int, req: UpdateImportanceReque
 
     with manager.lock(namespace):
         db.update_importance(record_id, req.importance)
+    shadows.mirror(namespace, lambda sdb: sdb.update_importance(record_id, req.importance))
     return {"id": record_id, "importance": req.importance}
 
 
-@app.post("/v1/{namespace}/records/{record_id}/link", tags=["records"],
+@app.post("/v1/{namespace}/records/{record_id}/link", response_model=LinkCreated,
+          tags=["records"],
           dependencies=[Depends(verify_api_key)])
 def link_records(namespace: str, record_id: int, req: LinkRequest):
     try:
@@ -685,10 +772,12 @@ def link_records(namespace: str, record_id: int, req: LinkRequest):
 
     with manager.lock(namespace):
         db.link(from_id=record_id, to_id=req.to_id)
+    shadows.mirror(namespace, lambda sdb: sdb.link(from_id=record_id, to_id=req.to_id))
     return {"from_id": record_id, "to_id": req.to_id, "linked": True}
 
 
-@app.delete("/v1/{namespace}/records/{record_id}", tags=["records"],
+@app.delete("/v1/{namespace}/records/{record_id}", response_model=RecordDeleted,
+            tags=["records"],
             dependencies=[Depends(verify_api_key)])
 def delete_record(namespace: str, record_id: int):
     try:
@@ -709,10 +798,12 @@ def delete_record(namespace: str, record_id: int):
         # out (rare; mostly for bulk-delete sequences that compact afterwards).
         edges_pruned = _prune_edges_to(db, record_id)
         db.save()
+    shadows.mirror(namespace, lambda sdb: (sdb.forget(record_id), _prune_edges_to(sdb, record_id)))
     return {"id": record_id, "deleted": True, "edges_pruned": edges_pruned}
 
 
-@app.post("/v1/{namespace}/records/batch_delete", tags=["records"],
+@app.post("/v1/{namespace}/records/batch_delete", response_model=BatchDeleteResponse,
+          tags=["records"],
           dependencies=[Depends(verify_api_key)])
 def batch_delete(namespace: str, req: BatchDeleteRequest):
     """Delete many records in ONE pass: take the namespace lock once, forget
@@ -743,6 +834,7 @@ def batch_delete(namespace: str, req: BatchDeleteRequest):
 
     deleted = 0
     not_found = 0
+    forgotten: List[int] = []
     with manager.lock(namespace):
         for rid in ids:
             meta = db.get_metadata(rid)
@@ -751,17 +843,28 @@ def batch_delete(namespace: str, req: BatchDeleteRequest):
                 not_found += 1
                 continue
             db.forget(rid)
+            forgotten.append(rid)
             deleted += 1
         edges_pruned = 0
         if req.cascade and deleted:
             edges_pruned = _prune_edges_to_set(db, ids)
         db.save()   # ← single save for the whole batch
+
+    def _shadow_delete(sdb):
+        for rid in forgotten:
+            if not _is_dead_record(sdb, rid):
+                sdb.forget(rid)
+        if req.cascade and forgotten:
+            _prune_edges_to_set(sdb, ids)
+    if forgotten:
+        shadows.mirror(namespace, _shadow_delete)
     return {"namespace": namespace, "requested": len(ids), "deleted": deleted,
             "not_found": not_found, "edges_pruned": edges_pruned,
             "hint": "run POST /compact to reclaim space" if deleted else None}
 
 
-@app.delete("/v1/{namespace}/records/{from_id}/link/{to_id}", tags=["records"],
+@app.delete("/v1/{namespace}/records/{from_id}/link/{to_id}", response_model=LinkRemoved,
+            tags=["records"],
             dependencies=[Depends(verify_api_key)])
 def unlink_records(namespace: str, from_id: int, to_id: int):
     """Remove a single edge from `from_id` to `to_id`. Returns the number of
@@ -782,10 +885,17 @@ def unlink_records(namespace: str, from_id: int, to_id: int):
             meta.edges = kept
             db.update_metadata(from_id, meta)
             db.save()
+
+        def _shadow_unlink(sdb):
+            smeta = sdb.get_metadata(from_id)
+            if smeta is not None:
+                smeta.edges = [e for e in smeta.edges if e.target_id != to_id]
+                sdb.update_metadata(from_id, smeta)
+        shadows.mirror(namespace, _shadow_unlink)
     return {"from_id": from_id, "to_id": to_id, "removed": removed}
 
 
-@app.post("/v1/{namespace}/purge", tags=["records"],
+@app.post("/v1/{namespace}/purge", response_model=PurgeResponse, tags=["records"],
           dependencies=[Depends(verify_api_key)])
 def purge_namespace(namespace: str, req: PurgeRequest):
     """Hard-delete all records whose metadata.namespace_id matches req.namespace_id.
@@ -799,10 +909,11 @@ def purge_namespace(namespace: str, req: PurgeRequest):
     with manager.lock(namespace):
         removed = db.purge(req.namespace_id)
         db.save()
+    shadows.mirror(namespace, lambda sdb: sdb.purge(req.namespace_id))
     return {"namespace": namespace, "namespace_id": req.namespace_id, "removed": removed}
 
 
-@app.post("/v1/{namespace}/compact", tags=["records"],
+@app.post("/v1/{namespace}/compact", response_model=CompactResponse, tags=["records"],
           dependencies=[Depends(verify_api_key)])
 def compact_namespace(namespace: str, prune_dead_edges: bool = True):
     """Rebuild HNSW indices, physically dropping any soft-deleted records.
@@ -851,7 +962,8 @@ def index_stats(namespace: str):
     )
 
 
-@app.put("/v1/{namespace}/admin/auto_compact", tags=["admin"],
+@app.put("/v1/{namespace}/admin/auto_compact", response_model=AutoCompactResponse,
+         tags=["admin"],
          dependencies=[Depends(verify_api_key)])
 def set_auto_compact(namespace: str, req: AutoCompactRequest):
     """Enable/adjust incremental auto-compaction (rebuild a modality once its
@@ -865,7 +977,8 @@ def set_auto_compact(namespace: str, req: AutoCompactRequest):
     return {"namespace": namespace, "auto_compact_ratio": db.get_auto_compact()}
 
 
-@app.put("/v1/{namespace}/admin/quantize", tags=["admin"],
+@app.put("/v1/{namespace}/admin/quantize", response_model=QuantizeResponse,
+         tags=["admin"],
          dependencies=[Depends(verify_api_key)])
 def set_quantize(namespace: str, req: QuantizeRequest):
     """Toggle on-disk int8 quantization for a modality (~3x smaller .feather).
@@ -881,7 +994,7 @@ def set_quantize(namespace: str, req: QuantizeRequest):
             "quantized": db.is_quantized(req.modality)}
 
 
-@app.get("/v1/{namespace}/records", tags=["records"],
+@app.get("/v1/{namespace}/records", response_model=RecordsPage, tags=["records"],
          dependencies=[Depends(verify_api_key)])
 def list_records(namespace: str, limit: int = 50, after: int = -1, modality: str = "text"):
     """Cursor-based record listing. Returns up to `limit` records with id > `after`.
@@ -955,7 +1068,8 @@ def hybrid_search(namespace: str, req: HybridSearchRequest):
     return SearchResponse(results=items, count=len(items))
 
 
-@app.post("/v1/{namespace}/save", tags=["admin"], dependencies=[Depends(verify_api_key)])
+@app.post("/v1/{namespace}/save", response_model=SavedResponse, tags=["admin"],
+          dependencies=[Depends(verify_api_key)])
 def save_namespace(namespace: str):
     try:
         manager.save(namespace)
@@ -967,7 +1081,8 @@ def save_namespace(namespace: str):
 # ─────────────────────────────────────────────
 # Bulk seeder — generates N records with random vectors
 # ─────────────────────────────────────────────
-@app.post("/v1/{namespace}/seed", tags=["admin"], dependencies=[Depends(verify_api_key)])
+@app.post("/v1/{namespace}/seed", response_model=SeedResponse, tags=["admin"],
+          dependencies=[Depends(verify_api_key)])
 def seed_namespace(namespace: str, req: SeedRequest):
     db = manager.get(namespace)
     rng = np.random.default_rng(req.seed)
@@ -1175,7 +1290,9 @@ def namespace_schema(namespace: str, sample_limit: int = 8):
 
 @app.get("/v1/admin/metrics", tags=["meta"], dependencies=[Depends(verify_api_key)])
 def admin_metrics(window: int = 3600):
-    return METRICS.snapshot(since_seconds=float(window))
+    snap = METRICS.snapshot(since_seconds=float(window))
+    snap["groupCommit"] = committer.stats()
+    return snap
 
 
 @app.get("/v1/admin/activity", tags=["meta"], dependencies=[Depends(verify_api_key)])
@@ -1183,6 +1300,108 @@ def admin_activity(limit: int = 50):
     return {"events": METRICS.activity(limit=limit)}
 
 
+# ─────────────────────────────────────────────
+# Namespace-scoped API keys + per-key usage
+# ─────────────────────────────────────────────
+def _key_to_model(key) -> ApiKeyOut:
+    return ApiKeyOut(
+        id=key.id,
+        name=key.name,
+        namespaces=key.namespaces,
+        admin=key.admin,
+        created_at=key.created_at,
+        last_used_at=key.last_used_at,
+        totals=KeyUsage(**key.totals()),
+        by_namespace={ns: KeyUsage(**c) for ns, c in key.usage.items()},
+    )
+
+
+@app.post("/v1/admin/keys", response_model=ApiKeyCreated, status_code=201,
+          tags=["admin"], dependencies=[Depends(verify_api_key)])
+def create_api_key(req: CreateApiKeyRequest):
+    """Mint a key scoped to `namespaces`. The token is returned only here —
+    the server keeps just its hash."""
+    if not req.admin and not req.namespaces:
+        raise HTTPException(400, "a non-admin key needs at least one namespace")
+    try:
+        key, token = KEYS.create(req.name, req.namespaces, admin=req.admin)
+    except ValueError as e:
+        raise HTTPException(400, str(e))
+    return ApiKeyCreated(**_key_to_model(key).model_dump(), token=token)
+
+
+@app.get("/v1/admin/keys", response_model=ApiKeyList, tags=["admin"],
+         dependencies=[Depends(verify_api_key)])
+def list_api_keys():
+    return {"keys": [_key_to_model(k) for k in KEYS.list()]}
+
+
+@app.get("/v1/admin/keys/{key_id}", response_model=ApiKeyOut,
+         tags=["admin"], dependencies=[Depends(verify_api_key)])
+def get_api_key(key_id: str):
+    key = KEYS.get(key_id)
+    if key is None:
+        raise HTTPException(404, f"API key '{key_id}' not found")
+    return _key_to_model(key)
+
+
+@app.delete("/v1/admin/keys/{key_id}", response_model=ApiKeyRevoked, tags=["admin"],
+            dependencies=[Depends(verify_api_key)])
+def revoke_api_key(key_id: str):
+    if key_id == "master":
+        raise HTTPException(400, "the master key is set via FEATHER_API_KEY and cannot be revoked here")
+    try:
+        revoked = KEYS.revoke(key_id)
+    except ValueError as e:
+        raise HTTPException(400, str(e))
+    if not revoked:
+        raise HTTPException(404, f"API key '{key_id}' not found")
+    return {"id": key_id, "revoked": True}
+
+
+@app.put("/v1/admin/shadows/{namespace}", response_model=ShadowStatus, tags=["admin"],
+         dependencies=[Depends(verify_api_key)])
+def start_shadow(namespace: str, req: ShadowRequest):
+    """Dual-write `namespace` into `target` and diff a sampled fraction of its
+    searches against it (see app/shadow.py). Configure the target first —
+    quantization, auto-compaction — or set `embedding_model` to migrate models.
+    Existing records are copied over in the background unless `backfill` is
+    false."""
+    if namespace not in manager.list_namespaces():
+        raise HTTPException(404, f"Namespace '{namespace}' not found")
+    try:
+        shadow = shadows.start(namespace, req.target, req.sample_rate,
+                               embedding_model=req.embedding_model or "",
+                               backfill=req.backfill)
+    except ValueError as e:
+        raise HTTPException(400, str(e))
+    return shadow.status()
+
+
+@app.get("/v1/admin/shadows", response_model=ShadowList, tags=["admin"],
+         dependencies=[Depends(verify_api_key)])
+def list_shadows():
+    return {"shadows": [s.status() for s in shadows.list()]}
+
+
+@app.get("/v1/admin/shadows/{namespace}", response_model=ShadowStatus, tags=["admin"],
+         dependencies=[Depends(verify_api_key)])
+def get_shadow(namespace: str):
+    shadow = shadows.get(namespace)
+    if shadow is None:
+        raise HTTPException(404, f"Namespace '{namespace}' has no shadow")
+    return shadow.status()
+
+
+@app.delete("/v1/admin/shadows/{namespace}", response_model=ShadowStopped, tags=["admin"],
+            dependencies=[Depends(verify_api_key)])
+def stop_shadow(namespace: str):
+    """Stop dual writes. The target namespace is kept."""
+    if not shadows.stop(namespace):
+        raise HTTPException(404, f"Namespace '{namespace}' has no shadow")
+    return {"namespace": namespace, "stopped": True}
+
+
 # ─────────────────────────────────────────────
 # v0.10.0 — Hawky edition: monitoring, connection info, hierarchy, embedding, import
 # ─────────────────────────────────────────────
@@ -1282,7 +1501,7 @@ def embedding_models():
     return SUPPORTED_MODELS
 
 
-@app.post("/v1/{namespace}/ingest_text", tags=["records"],
+@app.post("/v1/{namespace}/ingest_text", response_model=TextIngested, tags=["records"],
           dependencies=[Depends(verify_api_key)])
 def ingest_text(namespace: str, req: IngestTextRequest):
     """Embed `text` via the configured provider, then ingest as a new record."""
@@ -1315,6 +1534,7 @@ def ingest_text(namespace: str, req: IngestTextRequest):
         db.add(id=rec_id, vec=np.asarray(vec, dtype=np.float32),
                meta=meta, modality=req.modality)
         _throttled_save(namespace, db)   # WAL-durable; throttled full save
+    shadows.mirror_add(namespace, [rec_id], [vec], [meta], req.modality)
     return {"id": rec_id, "namespace": namespace, "embedded": True, "dim": len(vec)}
 
 
@@ -1421,11 +1641,12 @@ def bulk_import(namespace: str, req: ImportRequest):
         # Throttled save instead of a full file rewrite per batch (WAL keeps the
         # data durable in between). Pass flush=true on the final batch to force it.
         _throttled_save(namespace, db, force=req.flush)
+    shadows.mirror_add(namespace, ids, vecs, metas, req.modality)
     return ImportResponse(namespace=namespace, inserted=len(ids),
                           skipped=skipped, embedded=embedded, errors=errors)
 
 
-@app.post("/v1/{namespace}/flush", tags=["records"],
+@app.post("/v1/{namespace}/flush", response_model=SavedResponse, tags=["records"],
           dependencies=[Depends(verify_api_key)])
 def flush_namespace(namespace: str):
     """Force a full save of the namespace now. Call this once after a bulk-import
@@ -90,6 +90,8 @@ def classify(method: str, path: str) -> str:
     if "/hybrid_search" in p: return "search"
     if "/keyword_search" in p:return "search"
     if "/vectors" in p:       return "add"
+    if "/import" in p:        return "import"
+    if "/ingest_text" in p:   return "ingest"
     if "/seed" in p:          return "seed"
     if "/purge" in p:         return "purge"
     if "/compact" in p:       return "compact"
@@ -57,6 +57,9 @@ class SearchRequest(BaseModel):
     scoring_half_life: Optional[float] = None    # days
     scoring_weight: Optional[float] = None       # 0.0–1.0
     scoring_min: Optional[float] = None
+    # The query text. Only used to re-embed the query for a shadow namespace
+    # that runs a different embedding model.
+    text: Optional[str] = None
 
 
 class SearchResultItem(BaseModel):
@@ -233,6 +236,39 @@ class ImportResponse(BaseModel):
     errors: List[str] = []
 
 
+# ── Namespace-scoped API keys + usage ──────────────────────────────
+class CreateApiKeyRequest(BaseModel):
+    name: str = Field(..., min_length=1, description="Who the key is for, e.g. the product name")
+    namespaces: List[str] = Field(
+        default_factory=list,
+        description="Namespaces the key may use; fnmatch patterns allowed ('team_a_*', '*')",
+    )
+    admin: bool = Field(False, description="Admin keys may use every namespace and /v1/admin/*")
+
+
+class KeyUsage(BaseModel):
+    requests: int = 0
+    searches: int = 0
+    inserts: int = 0
+    bytes_in: int = 0
+    bytes_out: int = 0
+
+
+class ApiKeyOut(BaseModel):
+    id: str
+    name: str
+    namespaces: List[str]
+    admin: bool
+    created_at: int
+    last_used_at: int = 0
+    totals: KeyUsage
+    by_namespace: Dict[str, KeyUsage] = Field(default_factory=dict)   # "" = server-level routes
+
+
+class ApiKeyCreated(ApiKeyOut):
+    token: str                          # shown once; only its hash is stored
+
+
 # ── Maintenance / index admin (Phase 7–8 capabilities) ──────────────
 class AutoCompactRequest(BaseModel):
     ratio: float = Field(0.0, ge=0.0, le=1.0,
@@ -244,6 +280,13 @@ class QuantizeRequest(BaseModel):
     on: bool = True
 
 
+class ShadowRequest(BaseModel):
+    target: str                                   # namespace with the new configuration
+    sample_rate: float = Field(0.1, ge=0.0, le=1.0)  # fraction of searches diffed
+    embedding_model: Optional[str] = None         # re-embed writes with this model
+    backfill: bool = True                         # copy existing records first
+
+
 class IndexStatsResponse(BaseModel):
     namespace: str
     record_count: int
@@ -353,3 +396,172 @@ class HealthResponse(BaseModel):
     status: str
     version: str
     namespaces_loaded: int
+
+
+# ── Data-plane responses ────────────────────────────────────────
+# Typed so the OpenAPI document (/openapi.json) describes every response a
+# client gets back, not just the request bodies.
+
+class NamespaceList(BaseModel):
+    namespaces: List[str]
+
+
+class NamespaceCreated(BaseModel):
+    name: str
+    dim: int
+    created: bool
+
+
+class NamespaceDeleted(BaseModel):
+    namespace: str
+    deleted: bool
+
+
+class VectorAdded(BaseModel):
+    id: int
+    namespace: str
+    modality: str
+
+
+class TextIngested(BaseModel):
+    id: int
+    namespace: str
+    embedded: bool
+    dim: int
+
+
+class RecordUpdated(BaseModel):
+    id: int
+    updated: bool
+
+
+class ImportanceUpdated(BaseModel):
+    id: int
+    importance: float
+
+
+class LinkCreated(BaseModel):
+    from_id: int
+    to_id: int
+    linked: bool
+
+
+class LinkRemoved(BaseModel):
+    from_id: int
+    to_id: int
+    removed: int = Field(..., description="Edges removed (one per matching rel_type)")
+
+
+class RecordDeleted(BaseModel):
+    id: int
+    deleted: bool
+    edges_pruned: int
+
+
+class BatchDeleteResponse(BaseModel):
+    namespace: str
+    requested: int
+    deleted: int
+    not_found: int
+    edges_pruned: int
+    hint: Optional[str] = None
+
+
+class PurgeResponse(BaseModel):
+    namespace: str
+    namespace_id: str
+    removed: int
+
+
+class CompactResponse(BaseModel):
+    namespace: str
+    reclaimed: int
+    edges_pruned: int
+
+
+class SavedResponse(BaseModel):
+    namespace: str
+    saved: bool
+
+
+class RecordsPage(BaseModel):
+    results: List[SearchResultItem]
+    count: int
+    next_cursor: int = Field(..., description="Pass as `after` to fetch the next page")
+    has_more: bool
+
+
+class UploadResponse(BaseModel):
+    namespace: str
+    records: int
+    dim: int
+    bytes: int
+    imported: bool
+
+
+class SeedResponse(BaseModel):
+    namespace: str
+    inserted: int
+    first_id: Optional[int] = None
+    last_id: Optional[int] = None
+
+
+class AutoCompactResponse(BaseModel):
+    namespace: str
+    auto_compact_ratio: float
+
+
+class QuantizeResponse(BaseModel):
+    namespace: str
+    modality: str
+    quantized: bool
+
+
+class ApiKeyList(BaseModel):
+    keys: List[ApiKeyOut]
+
+
+class ApiKeyRevoked(BaseModel):
+    id: str
+    revoked: bool
+
+
+class ShadowDiff(BaseModel):
+    ts: int
+    k: int
+    overlap: float
+    top1_match: bool
+    primary_ms: float
+    shadow_ms: float
+    missing: List[int]
+    extra: List[int]
+
+
+class ShadowStatus(BaseModel):
+    source: str
+    target: str
+    sample_rate: float
+    embedding_model: str
+    created_at: int
+    writes: int
+    write_errors: int
+    writes_skipped: int
+    backfilled: int
+    backfill_done: bool
+    queries_sampled: int
+    queries_skipped: int
+    mean_overlap: Optional[float] = None
+    top1_match_rate: Optional[float] = None
+    mean_primary_ms: Optional[float] = None
+    mean_shadow_ms: Optional[float] = None
+    last_error: str
+    recent_diffs: List[ShadowDiff]
+
+
+class ShadowList(BaseModel):
+    shadows: List[ShadowStatus]
+
+
+class ShadowStopped(BaseModel):
+    namespace: str
+    stopped: bool
@@ -0,0 +1,26 @@
+"""Write the API's OpenAPI document without starting the server.
+
+    python -m app.openapi                 # to stdout
+    python -m app.openapi openapi.json    # to a file
+
+The document is the same one served at GET /openapi.json; feed it to a client
+generator (openapi-generator, openapi-typescript, progenitor, ...). Operation
+ids are the handler names, so regenerated clients keep their method names.
+"""
+import json
+import sys
+
+from .main import app
+
+
+def main(argv):
+    doc = json.dumps(app.openapi(), indent=2)
+    if len(argv) > 1:
+        with open(argv[1], "w") as fh:
+            fh.write(doc + "\n")
+    else:
+        print(doc)
+
+
+if __name__ == "__main__":
+    main(sys.argv)
@@ -0,0 +1,308 @@
+"""Shadow namespaces — dual writes and sampled query diffs for migrations.
+
+Moving a namespace to a new configuration (quantization, auto-compaction,
+another embedding model) is risky to do blind. A shadow pairs a live source
+namespace with a target namespace holding the new configuration:
+
+  * every write to the source is replayed on the target, in order, on a
+    background worker (the request never waits for, or fails because of, the
+    shadow). With `embedding_model` set, records are re-embedded from their
+    `content` with that model; vector writes without content are skipped;
+  * a `sample_rate` fraction of searches also runs against the target, and the
+    two result lists are diffed (overlap@k, top-1 match, latency). Every
+    sampled diff is logged on `feather-api.shadow` and the last few are kept
+    for GET /v1/admin/shadows/{namespace}.
+
+Once the numbers look right, point clients at the target (or upload it over
+the source) and stop the shadow. Shadow configs persist in FEATHER_SHADOWS_FILE
+(default ``<FEATHER_DATA_DIR>/_shadows.json``); counters are in-memory.
+"""
+import json
+import logging
+import os
+import random
+import threading
+import time
+from collections import deque
+from concurrent.futures import ThreadPoolExecutor
+from typing import Callable, Dict, List, Optional
+
+import numpy as np
+from feather_db import Metadata
+
+from .db_manager import DATA_DIR
+
+
+SHADOWS_FILE = os.getenv("FEATHER_SHADOWS_FILE", os.path.join(DATA_DIR, "_shadows.json"))
+_RECENT_DIFFS = 50
+_BACKFILL_BATCH = 1000
+
+logger = logging.getLogger("feather-api.shadow")
+
+
+def _is_dead(meta) -> bool:
+    return meta is None or meta.source == "_forgotten" or meta.get_attribute("_deleted") == "true"
+
+
+class Shadow:
+    def __init__(self, source: str, target: str, sample_rate: float,
+                 embedding_model: str = "", created_at: Optional[int] = None):
+        self.source = source
+        self.target = target
+        self.sample_rate = sample_rate
+        self.embedding_model = embedding_model
+        self.created_at = created_at or int(time.time())
+        self.writes = 0
+        self.write_errors = 0
+        self.writes_skipped = 0
+        self.backfilled = 0
+        self.backfill_done = False
+        self.queries_sampled = 0
+        self.queries_skipped = 0
+        self.overlap_sum = 0.0
+        self.top1_matches = 0
+        self.primary_ms_sum = 0.0
+        self.shadow_ms_sum = 0.0
+        self.last_error = ""
+        self.recent: deque = deque(maxlen=_RECENT_DIFFS)
+
+    def to_json(self) -> Dict:
+        return {"source": self.source, "target": self.target,
+                "sample_rate": self.sample_rate,
+                "embedding_model": self.embedding_model,
+                "created_at": self.created_at}
+
+    @classmethod
+    def from_json(cls, d: Dict) -> "Shadow":
+        s = cls(d["source"], d["target"], float(d.get("sample_rate", 0.0)),
+                d.get("embedding_model", ""), d.get("created_at"))
+        s.backfill_done = True   # backfill is a one-off at start; don't redo on restart
+        return s
+
+    def status(self) -> Dict:
+        n = self.queries_sampled
+        return {
+            **self.to_json(),
+            "writes": self.writes,
+            "write_errors": self.write_errors,
+            "writes_skipped": self.writes_skipped,
+            "backfilled": self.backfilled,
+            "backfill_done": self.backfill_done,
+            "queries_sampled": n,
+            "queries_skipped": self.queries_skipped,
+            "mean_overlap": round(self.overlap_sum / n, 4) if n else None,
+            "top1_match_rate": round(self.top1_matches / n, 4) if n else None,
+            "mean_primary_ms": round(self.primary_ms_sum / n, 2) if n else None,
+            "mean_shadow_ms": round(self.shadow_ms_sum / n, 2) if n else None,
+            "last_error": self.last_error,
+            "recent_diffs": list(self.recent),
+        }
+
+
+class ShadowRegistry:
+    """`embed(text, model)` returns a vector; injected so this module stays
+    independent of the embedding provider."""
+
+    def __init__(self, manager, embed: Callable[[str, str], List[float]],
+                 path: str = SHADOWS_FILE):
+        self._manager = manager
+        self._embed = embed
+        self._path = path
+        self._lock = threading.Lock()
+        self._shadows: Dict[str, Shadow] = {}
+        # One worker keeps the target's writes in the source's order.
+        self._worker = ThreadPoolExecutor(max_workers=1, thread_name_prefix="shadow")
+        self._load()
+
+    # ── config ──────────────────────────────────────────────────
+    def _load(self):
+        try:
+            with open(self._path) as fh:
+                data = json.load(fh)
+        except (OSError, ValueError):
+            return
+        for d in data.get("shadows", []):
+            s = Shadow.from_json(d)
+            self._shadows[s.source] = s
+
+    def _save_locked(self):
+        tmp = self._path + ".tmp"
+        os.makedirs(os.path.dirname(self._path) or ".", exist_ok=True)
+        with open(tmp, "w") as fh:
+            json.dump({"shadows": [s.to_json() for s in self._shadows.values()]}, fh)
+        os.replace(tmp, self._path)
+
+    def get(self, source: str) -> Optional[Shadow]:
+        return self._shadows.get(source)
+
+    def list(self) -> List[Shadow]:
+        return sorted(self._shadows.values(), key=lambda s: s.created_at)
+
+    def targets(self) -> List[str]:
+        return [s.target for s in self._shadows.values()]
+
+    def start(self, source: str, target: str, sample_rate: float,
+              embedding_model: str = "", backfill: bool = True) -> Shadow:
+        """Start shadowing `source` into `target` (created if missing).
+        Raises ValueError for a pairing that would loop."""
+        with self._lock:
+            if source == target:
+                raise ValueError("a namespace cannot shadow itself")
+            if target in self._shadows or source in self.targets():
+                raise ValueError("shadows cannot be chained")
+            if any(s.target == target and s.source != source for s in self._shadows.values()):
+                raise ValueError(f"'{target}' already shadows another namespace")
+            s = Shadow(source, target, sample_rate, embedding_model)
+            s.backfill_done = not backfill
+            self._shadows[source] = s
+            self._save_locked()
+        self._manager.get(target)
+        if backfill:
+            self._worker.submit(self._backfill, s)
+        return s
+
+    def stop(self, source: str) -> bool:
+        with self._lock:
+            if self._shadows.pop(source, None) is None:
+                return False
+            self._save_locked()
+            return True
+
+    def forget_namespace(self, namespace: str):
+        """Drop every shadow `namespace` takes part in (it was deleted)."""
+        with self._lock:
+            gone = [k for k, s in self._shadows.items()
+                    if namespace in (s.source, s.target)]
+            for k in gone:
+                del self._shadows[k]
+            if gone:
+                self._save_locked()
+
+    def shutdown(self):
+        self._worker.shutdown(wait=True)
+
+    # ── writes ──────────────────────────────────────────────────
+    def _apply(self, s: Shadow, fn: Callable):
+        try:
+            db = self._manager.get(s.target)
+            with self._manager.lock(s.target):
+                fn(db)
+            s.writes += 1
+        except Exception as e:  # noqa: BLE001 — the shadow never fails the source
+            s.write_errors += 1
+            s.last_error = f"{type(e).__name__}: {e}"
+            logger.warning(f"shadow {s.source}->{s.target}: write failed: {s.last_error}")
+
+    def mirror(self, source: str, fn: Callable):
+        """Replay a write that needs no vector (`fn(target_db)`) on the
+        source's shadow, if any."""
+        s = self._shadows.get(source)
+        if s is not None:
+            self._worker.submit(self._apply, s, fn)
+
+    def mirror_add(self, source: str, ids: List[int], vecs, metas: List[Metadata],
+                   modality: str = "text"):
+        """Replay inserts on the source's shadow, re-embedding them when the
+        shadow uses another model."""
+        s = self._shadows.get(source)
+        if s is None or not ids:
+            return
+        vecs = np.asarray(vecs, dtype=np.float32).copy()
+        self._worker.submit(self._add, s, list(ids), vecs, list(metas), modality)
+
+    def _add(self, s: Shadow, ids, vecs, metas, modality):
+        if s.embedding_model:
+            keep = [i for i, m in enumerate(metas) if m is not None and m.content.strip()]
+            s.writes_skipped += len(ids) - len(keep)
+            if not keep:
+                return
+            try:
+                vecs = np.asarray([self._embed(metas[i].content, s.embedding_model) for i in keep],
+                                  dtype=np.float32)
+            except Exception as e:  # noqa: BLE001
+                s.write_errors += 1
+                s.last_error = f"embed: {e}"
+                logger.warning(f"shadow {s.source}->{s.target}: embed failed: {e}")
+                return
+            ids = [ids[i] for i in keep]
+            metas = [metas[i] for i in keep]
+        self._apply(s, lambda db: db.add_batch(ids, vecs, metas, modality=modality))
+
+    def _backfill(self, s: Shadow):
+        """Copy what the source held when the shadow started."""
+        try:
+            src = self._manager.get(s.source, create=False)
+            try:
+                modalities = list(src.modality_names()) or ["text"]
+            except AttributeError:
+                modalities = ["text"]
+            for modality in modalities:
+                rows = []
+                for rec_id in src.get_all_ids(modality=modality):
+                    meta = src.get_metadata(rec_id)
+                    if _is_dead(meta):
+                        continue
+                    vec = src.get_vector(rec_id, modality)
+                    if len(vec):
+                        rows.append((rec_id, vec, meta))
+                for i in range(0, len(rows), _BACKFILL_BATCH):
+                    chunk = rows[i:i + _BACKFILL_BATCH]
+                    self._add(s, [r[0] for r in chunk], np.asarray([r[1] for r in chunk]),
+                              [r[2] for r in chunk], modality)
+                    s.backfilled += len(chunk)
+        except Exception as e:  # noqa: BLE001
+            s.last_error = f"backfill: {e}"
+            logger.warning(f"shadow {s.source}->{s.target}: backfill failed: {e}")
+        s.backfill_done = True
+        logger.info(f"shadow {s.source}->{s.target}: backfilled {s.backfilled} records")
+
+    # ── queries ─────────────────────────────────────────────────
+    def sample(self, source: str) -> Optional[Shadow]:
+        """The source's shadow if this query is picked for comparison."""
+        s = self._shadows.get(source)
+        if s is None or s.sample_rate <= 0 or random.random() >= s.sample_rate:
+            return None
+        return s
+
+    def compare(self, s: Shadow, vector, text: Optional[str], k: int,
+                primary: List[tuple], primary_ms: float, search: Callable):
+        """Queue a diff of `primary` ([(id, score)]) against the shadow.
+        `search(db, vector)` reruns the query and returns core results."""
+        self._worker.submit(self._compare, s, list(vector), text, k, primary,
+                            primary_ms, search)
+
+    def _compare(self, s: Shadow, vector, text, k, primary, primary_ms, search):
+        try:
+            if s.embedding_model:
+                if not text:
+                    s.queries_skipped += 1   # a query vector can't cross models
+                    return
+                vector = self._embed(text, s.embedding_model)
+            db = self._manager.get(s.target, create=False)
+            t0 = time.perf_counter()
+            shadow = [(r.id, r.score) for r in search(db, np.asarray(vector, dtype=np.float32))]
+            shadow_ms = (time.perf_counter() - t0) * 1000
+        except Exception as e:  # noqa: BLE001
+            s.queries_skipped += 1
+            s.last_error = f"query: {e}"
+            return
+
+        p_ids = [i for i, _ in primary]
+        s_ids = [i for i, _ in shadow]
+        common = set(p_ids) & set(s_ids)
+        overlap = len(common) / max(len(p_ids), len(s_ids), 1)
+        top1 = (p_ids[:1] == s_ids[:1])
+        diff = {
+            "ts": int(time.time()), "k": k,
+            "overlap": round(overlap, 4), "top1_match": top1,
+            "primary_ms": round(primary_ms, 2), "shadow_ms": round(shadow_ms, 2),
+            "missing": [i for i in p_ids if i not in common][:10],
+            "extra": [i for i in s_ids if i not in common][:10],
+        }
+        s.queries_sampled += 1
+        s.overlap_sum += overlap
+        s.top1_matches += int(top1)
+        s.primary_ms_sum += primary_ms
+        s.shadow_ms_sum += shadow_ms
+        s.recent.append(diff)
+        logger.info(f"shadow diff {s.source}->{s.target}: {json.dumps(diff)}")
@@ -0,0 +1,35 @@
+/* Build: cargo build --release && cc examples/quickstart.c -Iinclude \
+ *        -Ltarget/release -lfeather_v1 -o quickstart
+ * Run:   LD_LIBRARY_PATH=target/release ./quickstart demo.feather */
+#include <stdio.h>
+#include "feather_v1.h"
+
+int main(int argc, char **argv) {
+    const char *path = argc > 1 ? argv[1] : "demo.feather";
+    FeatherV1Db *db = feather_v1_open(path, 4);
+    if (!db) {
+        fprintf(stderr, "open: %s\n", feather_v1_last_error());
+        return 1;
+    }
+    float a[4] = {1, 0, 0, 0}, b[4] = {0, 1, 0, 0};
+    if (feather_v1_add(db, 1, a, 4, NULL, "{\"content\": \"deploy failed: OOM\", \"source\": \"ci\"}") != 0
+        || feather_v1_add(db, 2, b, 4, NULL, "{\"content\": \"lunch on friday\"}") != 0) {
+        fprintf(stderr, "add: %s\n", feather_v1_last_error());
+        return 1;
+    }
+
+    uint64_t ids[2];
+    float scores[2];
+    int64_t n = feather_v1_search(db, a, 4, 2, NULL, "source = 'ci'", ids, scores);
+    if (n < 0) {
+        fprintf(stderr, "search: %s\n", feather_v1_last_error());
+        return 1;
+    }
+    for (int64_t i = 0; i < n; i++) {
+        char *record = feather_v1_get_json(db, ids[i]);
+        printf("%llu %.3f %s\n", (unsigned long long)ids[i], scores[i], record ? record : "?");
+        feather_v1_free_string(record);
+    }
+    feather_v1_close(db);
+    return 0;
+}
@@ -0,0 +1,85 @@
+#ifndef FEATHER_V1_H
+#define FEATHER_V1_H
+
+/* Generated with cbindgen from feather-capi; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Version of the API this library implements.
+#define FEATHER_V1_ABI_VERSION 1
+
+// An open store.
+typedef struct FeatherV1Db FeatherV1Db;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// `FEATHER_V1_ABI_VERSION` of the loaded library; callers built against a
+// later header should check it before using newer functions.
+uint32_t feather_v1_abi_version(void);
+
+// The reason for the calling thread's last failure, or "" if none. Valid
+// until the next failing call on the same thread; do not free.
+const char *feather_v1_last_error(void);
+
+// Open the store at `path`, creating it if need be; `dim` is needed only
+// to create one. Returns NULL on failure.
+FeatherV1Db *feather_v1_open(const char *path, size_t dim);
+
+// Checkpoint and close the store. NULL is a no-op.
+void feather_v1_close(FeatherV1Db *db);
+
+// Checkpoint the file. Returns 0, or -1.
+int feather_v1_save(const FeatherV1Db *db);
+
+// Vector dimension of `modality` (NULL for "text"), or the open-time
+// `dim` while it has none. Returns 0 if `db` is NULL.
+size_t feather_v1_dim(const FeatherV1Db *db, const char *modality);
+
+// Add (or replace) record `id` with `len` floats in `modality` (NULL for
+// "text"). `metadata_json` (may be NULL) is a JSON object with the fields
+// of a `feather export` record's metadata, e.g. `{"content": "...",
+// "importance": 0.8}`; the timestamp defaults to now. The store's
+// duplicate-id policy and dedup mode apply. Returns 0, or -1.
+int feather_v1_add(const FeatherV1Db *db,
+                   uint64_t id,
+                   const float *vec,
+                   size_t len,
+                   const char *modality,
+                   const char *metadata_json);
+
+// Search `modality` (NULL for "text") for the `k` records nearest the
+// `len` floats of `query`, keeping only those matching `filter` (NULL for
+// all; as `feather search --filter` takes it). Writes ids and scores,
+// best first, to `ids` and `scores`, each room for `k`. Returns the
+// number of hits, or -1.
+int64_t feather_v1_search(const FeatherV1Db *db,
+                          const float *query,
+                          size_t len,
+                          size_t k,
+                          const char *modality,
+                          const char *filter,
+                          uint64_t *ids,
+                          float *scores);
+
+// Record `id` as a JSON object, as `feather export` writes it; NULL if
+// there is no live record `id`. Free the string with
+// `feather_v1_free_string`.
+char *feather_v1_get_json(const FeatherV1Db *db, uint64_t id);
+
+// Forget record `id`; a no-op for an unknown id. Returns 0, or -1.
+int feather_v1_forget(const FeatherV1Db *db, uint64_t id);
+
+// Free a string returned by this library. NULL is a no-op.
+void feather_v1_free_string(char *s);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* FEATHER_V1_H */
@@ -0,0 +1,210 @@
+//! The stable C API of Feather, version 1: `include/feather_v1.h`.
+//!
+//! Other languages should bind to these `feather_v1_*` symbols rather than
+//! to the internal C++ ones, which change with the core. Within version 1
+//! functions are only ever added; a breaking change would come as
+//! `feather_v2_*` beside them.
+//!
+//! Conventions, for every function:
+//!
+//! - Pointers are borrowed for the call only, and must be valid for it:
+//!   a `FeatherV1Db` from `feather_v1_open` not yet closed, NUL-terminated
+//!   UTF-8 strings, arrays of the stated length. Optional arguments may be
+//!   NULL.
+//! - Failure is -1 (or NULL), with the reason in `feather_v1_last_error`.
+//! - A `FeatherV1Db` is used from one thread at a time.
+//! - Strings the library returns are freed with `feather_v1_free_string`.
+
+#![allow(clippy::missing_safety_doc)]
+
+use feather_db_cli::{decay, Filter, Metadata, OpenOptions, SearchOptions, DB};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+
+/// Version of the API this library implements.
+pub const FEATHER_V1_ABI_VERSION: u32 = 1;
+
+/// An open store.
+pub struct FeatherV1Db(DB);
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn set_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).expect("no NULs");
+    LAST_ERROR.with(|e| *e.borrow_mut() = message);
+}
+
+// Run `f`, turning an error or a panic into `failed` and the last error.
+fn guard<T>(failed: T, f: impl FnOnce() -> anyhow::Result<T>) -> T {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(e)) => {
+            set_error(format!("{:#}", e));
+            failed
+        }
+        Err(_) => {
+            set_error("internal error (panic)".to_string());
+            failed
+        }
+    }
+}
+
+unsafe fn db<'a>(db: *const FeatherV1Db) -> anyhow::Result<&'a DB> {
+    db.as_ref().map(|db| &db.0).ok_or_else(|| anyhow::anyhow!("db is NULL"))
+}
+
+unsafe fn opt_str<'a>(s: *const c_char, what: &str) -> anyhow::Result<Option<&'a str>> {
+    if s.is_null() { return Ok(None); }
+    CStr::from_ptr(s).to_str().map(Some).map_err(|_| anyhow::anyhow!("{} is not UTF-8", what))
+}
+
+unsafe fn slice<'a, T>(ptr: *const T, len: usize, what: &str) -> anyhow::Result<&'a [T]> {
+    if len == 0 { return Ok(&[]); }
+    anyhow::ensure!(!ptr.is_null(), "{} is NULL", what);
+    Ok(std::slice::from_raw_parts(ptr, len))
+}
+
+/// `FEATHER_V1_ABI_VERSION` of the loaded library; callers built against a
+/// later header should check it before using newer functions.
+#[no_mangle]
+pub extern "C" fn feather_v1_abi_version() -> u32 {
+    FEATHER_V1_ABI_VERSION
+}
+
+/// The reason for the calling thread's last failure, or "" if none. Valid
+/// until the next failing call on the same thread; do not free.
+#[no_mangle]
+pub extern "C" fn feather_v1_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ptr())
+}
+
+/// Open the store at `path`, creating it if need be; `dim` is needed only
+/// to create one. Returns NULL on failure.
+#[no_mangle]
+pub unsafe extern "C" fn feather_v1_open(path: *const c_char, dim: usize) -> *mut FeatherV1Db {
+    guard(std::ptr::null_mut(), || {
+        let path = opt_str(path, "path")?.ok_or_else(|| anyhow::anyhow!("path is NULL"))?;
+        let db = OpenOptions::new().dim(dim).create(true).open(Path::new(path))?;
+        db.expire()?;
+        Ok(Box::into_raw(Box::new(FeatherV1Db(db))))
+    })
+}
+
+/// Checkpoint and close the store. NULL is a no-op.
+#[no_mangle]
+pub unsafe extern "C" fn feather_v1_close(db: *mut FeatherV1Db) {
+    if !db.is_null() {
+        drop(Box::from_raw(db));
+    }
+}
+
+/// Checkpoint the file. Returns 0, or -1.
+#[no_mangle]
+pub unsafe extern "C" fn feather_v1_save(db: *const FeatherV1Db) -> c_int {
+    guard(-1, || {
+        self::db(db)?.save()?;
+        Ok(0)
+    })
+}
+
+/// Vector dimension of `modality` (NULL for "text"), or the open-time
+/// `dim` while it has none. Returns 0 if `db` is NULL.
+#[no_mangle]
+pub unsafe extern "C" fn feather_v1_dim(db: *const FeatherV1Db, modality: *const c_char) -> usize {
+    guard(0, || {
+        let modality = opt_str(modality, "modality")?.unwrap_or("text");
+        Ok(self::db(db)?.dim(modality))
+    })
+}
+
+/// Add (or replace) record `id` with `len` floats in `modality` (NULL for
+/// "text"). `metadata_json` (may be NULL) is a JSON object with the fields
+/// of a `feather export` record's metadata, e.g. `{"content": "...",
+/// "importance": 0.8}`; the timestamp defaults to now. The store's
+/// duplicate-id policy and dedup mode apply. Returns 0, or -1.
+#[no_mangle]
+pub unsafe extern "C" fn feather_v1_add(db: *const FeatherV1Db, id: u64, vec: *const f32, len: usize,
+                                        modality: *const c_char, metadata_json: *const c_char) -> c_int {
+    guard(-1, || {
+        let db = self::db(db)?;
+        let vec = slice(vec, len, "vec")?;
+        let modality = opt_str(modality, "modality")?.unwrap_or("text");
+        let meta = match opt_str(metadata_json, "metadata_json")? {
+            None => Metadata { timestamp: decay::now(), ..Metadata::default() },
+            Some(json) => {
+                let value: serde_json::Value = serde_json::from_str(json)
+                    .map_err(|e| anyhow::anyhow!("metadata_json: {}", e))?;
+                anyhow::ensure!(value.is_object(), "metadata_json must be a JSON object");
+                let stamped = value.get("timestamp").is_some();
+                let mut meta: Metadata = serde_json::from_value(value)
+                    .map_err(|e| anyhow::anyhow!("metadata_json: {}", e))?;
+                if !stamped { meta.timestamp = decay::now(); }
+                meta
+            }
+        };
+        db.add_with_metadata(id, vec, &meta, modality)?;
+        Ok(0)
+    })
+}
+
+/// Search `modality` (NULL for "text") for the `k` records nearest the
+/// `len` floats of `query`, keeping only those matching `filter` (NULL for
+/// all; as `feather search --filter` takes it). Writes ids and scores,
+/// best first, to `ids` and `scores`, each room for `k`. Returns the
+/// number of hits, or -1.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn feather_v1_search(db: *const FeatherV1Db, query: *const f32, len: usize, k: usize,
+                                           modality: *const c_char, filter: *const c_char,
+                                           ids: *mut u64, scores: *mut f32) -> i64 {
+    guard(-1, || {
+        let db = self::db(db)?;
+        let query = slice(query, len, "query")?;
+        let modality = opt_str(modality, "modality")?.unwrap_or("text");
+        let options = SearchOptions {
+            filter: opt_str(filter, "filter")?.map(Filter::parse).transpose()?,
+            ..SearchOptions::default()
+        };
+        anyhow::ensure!(k == 0 || (!ids.is_null() && !scores.is_null()), "ids or scores is NULL");
+        let hits = db.search_with_options(query, k, modality, &options)?;
+        for (i, (id, score)) in hits.iter().enumerate() {
+            *ids.add(i) = *id;
+            *scores.add(i) = *score;
+        }
+        Ok(hits.len() as i64)
+    })
+}
+
+/// Record `id` as a JSON object, as `feather export` writes it; NULL if
+/// there is no live record `id`. Free the string with
+/// `feather_v1_free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn feather_v1_get_json(db: *const FeatherV1Db, id: u64) -> *mut c_char {
+    guard(std::ptr::null_mut(), || {
+        let record = self::db(db)?.record(id)
+            .filter(|r| !r.metadata.is_forgotten())
+            .ok_or_else(|| anyhow::anyhow!("no record {}", id))?;
+        Ok(CString::new(serde_json::to_string(&record)?)?.into_raw())
+    })
+}
+
+/// Forget record `id`; a no-op for an unknown id. Returns 0, or -1.
+#[no_mangle]
+pub unsafe extern "C" fn feather_v1_forget(db: *const FeatherV1Db, id: u64) -> c_int {
+    guard(-1, || {
+        self::db(db)?.forget(id)?;
+        Ok(0)
+    })
+}
+
+/// Free a string returned by this library. NULL is a no-op.
+#[no_mangle]
+pub unsafe extern "C" fn feather_v1_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
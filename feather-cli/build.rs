@@ -1,5 +1,20 @@
 fn main() {
-    cc::Build::new()
+    // cc emits rerun-if-env-changed lines, which disables cargo's default
+    // "rerun on any package change" — track the vendored core explicitly.
+    println!("cargo:rerun-if-changed=cpp");
+    // With the `zstd` feature the core packs files with libzstd: the
+    // system's, or the one under ZSTD_DIR (its include/ and lib/).
+    let zstd = std::env::var_os("CARGO_FEATURE_ZSTD").is_some();
+    let mut build = cc::Build::new();
+    if zstd {
+        println!("cargo:rerun-if-env-changed=ZSTD_DIR");
+        if let Some(dir) = std::env::var_os("ZSTD_DIR").map(std::path::PathBuf::from) {
+            build.include(dir.join("include"));
+            println!("cargo:rustc-link-search=native={}", dir.join("lib").display());
+        }
+        build.define("FEATHER_ZSTD", None);
+    }
+    build
         .cpp(true)
         .std("c++17")
         .file("cpp/src/feather_core.cpp")
@@ -8,4 +23,7 @@ fn main() {
         .file("cpp/src/scoring.cpp")
         .include("cpp/include")
         .compile("feather");
+    if zstd {
+        println!("cargo:rustc-link-lib=zstd");
+    }
 }
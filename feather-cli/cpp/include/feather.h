@@ -15,14 +15,25 @@
 #include <thread>
 #include <atomic>
 #include <cstdio>
+#include <functional>
 #include "hnswlib.h"
 #include "metadata.h"
 #include "filter.h"
 #include "scoring.h"
+#include "packed.h"
 #include <optional>
+#include <map>
+#include <set>
+
 
 namespace feather {
 
+// ── Sparse vector: (dimension, weight) pairs sorted by dimension ──
+using SparseVector = std::vector<std::pair<uint32_t, float>>;
+
+// ── Progress of a long operation: called with (done, total) units ──
+using ProgressFn = std::function<void(size_t done, size_t total)>;
+
 // ── Reverse-index entry: who points to a given node ──────────────
 struct IncomingEdge {
     uint64_t    source_id;
@@ -30,19 +41,28 @@ struct IncomingEdge {
     float       weight;
 };
 
+// What save() packs (file format v12); see DB::compression_.
+enum class Compression : uint8_t { None = 0, Metadata = 1, All = 2 };
+
 class DB {
 private:
+
     struct ModalityIndex {
         std::unique_ptr<hnswlib::HierarchicalNSW<float>> index;
         std::unique_ptr<hnswlib::SpaceInterface<float>> space;  // L2Space or Int8L2Space
         size_t dim;
         bool  int8  = false;   // in-RAM int8 storage (4x smaller)
         float scale = 0.0f;    // global quant scale when int8 (= max_abs / 127)
+        bool  binary = false;  // 1 bit per dim, Hamming distance (HammingSpace)
+        bool  shuffled = false;  // built on several threads: the graph depends on their timing
     };
 
     std::unordered_map<std::string, ModalityIndex> modality_indices_;
     std::string path_;
     std::string wal_path_;
+    // WAL entries of the open transaction, written as one TXN entry on commit
+    bool in_txn_ = false;
+    mutable std::string txn_buf_;
     size_t default_dim_ = 768;   // dim reported before any modality index exists
     std::unordered_map<uint64_t, Metadata> metadata_store_;
 
@@ -60,6 +80,21 @@ private:
     std::unordered_map<std::string, std::unordered_set<uint64_t>> ns_index_;     // namespace_id → ids
     std::unordered_map<std::string, std::unordered_set<uint64_t>> entity_index_; // entity_id    → ids
     std::unordered_map<std::string, std::unordered_set<uint64_t>> attr_index_;   // "key\x1fval" → ids
+    // Live records carrying ARCHIVED_ATTRIBUTE. While it is empty, a search
+    // leaving them out needs no filter (see has_archived).
+    std::unordered_set<uint64_t> archived_;
+
+    // Optional secondary indexes, switched on per file (property "indexes",
+    // a comma-separated list of "source" / "timestamp"). Off by default: they
+    // cost memory on every record but only pay off for selective filters.
+    std::unordered_map<std::string, std::unordered_set<uint64_t>> source_index_; // source → ids
+    std::set<std::pair<int64_t, uint64_t>> time_index_;                          // (timestamp, id), ordered
+    bool index_source_ = false;
+    bool index_time_   = false;
+    static constexpr const char* INDEXES_PROPERTY = "indexes";
+    // A timestamp range matching more records than this is left to the HNSW
+    // traversal: an exact scan over that many vectors costs more than it saves.
+    static constexpr size_t TIME_INDEX_MAX_CANDIDATES = 20000;
 
     // ── Auto-compaction ──────────────────────────────────────────────
     // When a modality index's deleted/total ratio crosses this threshold after
@@ -67,26 +102,70 @@ private:
     // 0.0 disables it (default) — compaction stays manual via compact().
     float auto_compact_ratio_ = 0.0f;
 
+    // ── Seed ─────────────────────────────────────────────────────────
+    // Seed of the level draws of every HNSW index built from now on. Once
+    // set (set_seed), indexes are also built on one thread, so the same
+    // vectors inserted in the same order give the same graph, run after run.
+    static constexpr uint64_t DEFAULT_SEED = 100;   // hnswlib's own
+    std::optional<uint64_t> seed_;
+
     // ── On-disk int8 quantization ────────────────────────────────────
     // Modalities whose vectors are persisted as int8 + per-vector scale (file
     // format v7) — ~4x smaller on disk and faster to load. The in-memory HNSW
     // index stays float32; vectors are dequantized on load. Opt-in per modality.
     std::unordered_set<std::string> quantized_modalities_;
 
+    // ── Compression ──────────────────────────────────────────────────
+    // What save() packs with zstd (file format v12, see packed.h): the
+    // metadata section — records and their content, most of a text-heavy
+    // file — or everything after the properties, vectors too (archival:
+    // smallest, slowest to load). Loading restores the file's setting. An
+    // unpacked store is saved as v11, which older builds still read.
+    Compression compression_ = Compression::None;
+
     // ── In-RAM int8 quantization ─────────────────────────────────────
     // Modalities whose HNSW index stores int8[dim] vectors (4x less RAM) under a
     // global scale = max_abs/127. Must be configured before the modality's index
     // is created (i.e. before the first add). Persisted in file format v8.
     std::unordered_map<std::string, float> int8_ram_scale_;
 
+    // ── Database properties ──────────────────────────────────────────
+    // Free-form key → binary value pairs persisted in the file header (format
+    // v10). Wrappers use them for per-DB settings that must travel with the
+    // file (e.g. a projection applied to every vector). Take effect on save().
+    std::map<std::string, std::string> properties_;
+
+    // ── Binary vectors ───────────────────────────────────────────────
+    // With properties_[METRIC_PROPERTY] == "hamming" every index stores one
+    // bit per dimension (set where the component is > 0) and ranks by Hamming
+    // distance. Store-wide, and fixed before the first vector (set_binary);
+    // being a property it is read at load before any index is created.
+    static constexpr const char* METRIC_PROPERTY = "metric";
+    bool binary_store() const {
+        auto it = properties_.find(METRIC_PROPERTY);
+        return it != properties_.end() && it->second == "hamming";
+    }
+
     // ── BM25 Inverted Index ──────────────────────────────────────────
     struct PostingEntry { uint64_t doc_id; uint32_t term_freq; };
     std::unordered_map<std::string, std::vector<PostingEntry>> bm25_index_;
     std::unordered_map<uint64_t, uint32_t> doc_lengths_;
+    uint64_t total_dl_ = 0;    // sum of doc_lengths_, kept so avg_dl_ is O(1)
     double avg_dl_ = 0.0;
     static constexpr float BM25_K1 = 1.2f;
     static constexpr float BM25_B  = 0.75f;
 
+    // ── Sparse vectors ───────────────────────────────────────────────
+    // Named sets of sparse vectors (e.g. SPLADE term weights), each with an
+    // inverted index scored by dot product. Persisted after the modality
+    // indices (file format v11). Forgetting or purging a record drops its
+    // sparse vectors.
+    struct SparseIndex {
+        std::unordered_map<uint64_t, SparseVector> vectors;
+        std::unordered_map<uint32_t, std::vector<std::pair<uint64_t, float>>> postings;  // dim → (id, weight)
+    };
+    std::unordered_map<std::string, SparseIndex> sparse_indices_;
+
     // ── WAL op codes ─────────────────────────────────────────────────
     enum class WalOp : uint8_t {
         ADD    = 0x01,
@@ -94,6 +173,9 @@ private:
         UIMP   = 0x03,
         LINK   = 0x04,
         FORGET = 0x05,
+        SPARSE = 0x06,
+        UNLINK = 0x07,
+        TXN    = 0x08,   // a committed transaction: its entries, as one payload
     };
 
     // ── Helpers ─────────────────────────────────────────────────────
@@ -110,6 +192,13 @@ private:
     // double as needed, so RAM tracks the real working set, not the worst case.
     static constexpr size_t INITIAL_MAX_ELEMENTS = 4096;
 
+    // An empty HNSW index over `space`, its levels drawn from seed_.
+    std::unique_ptr<hnswlib::HierarchicalNSW<float>> make_index(hnswlib::SpaceInterface<float>* space,
+                                                                size_t max_elements) const {
+        return std::make_unique<hnswlib::HierarchicalNSW<float>>(
+            space, max_elements, 16, 200, seed_.value_or(DEFAULT_SEED));
+    }
+
     // Ensure the index can hold at least `target` elements. NOT thread-safe
     // (resizeIndex reallocs every backing buffer) — call before any add, and
     // before parallel_add for the full batch size, never from inside it.
@@ -125,18 +214,43 @@ private:
             auto cfg = int8_ram_scale_.find(modality);
             bool int8 = cfg != int8_ram_scale_.end();
             float scale = int8 ? cfg->second : 0.0f;
-            std::unique_ptr<hnswlib::SpaceInterface<float>> space;
-            if (int8) space = std::make_unique<hnswlib::Int8L2Space>(dim, scale);
-            else      space = std::make_unique<hnswlib::L2Space>(dim);
-            auto index = std::make_unique<hnswlib::HierarchicalNSW<float>>(
-                space.get(), INITIAL_MAX_ELEMENTS, 16, 200);
+            bool binary = binary_store();
+            auto space = make_space(dim, int8, scale, binary);
+            auto index = make_index(space.get(), INITIAL_MAX_ELEMENTS);
             index->setEf(DEFAULT_EF);
-            modality_indices_[modality] = {std::move(index), std::move(space), dim, int8, scale};
+            modality_indices_[modality] = {std::move(index), std::move(space), dim, int8, scale, binary};
             return modality_indices_[modality];
         }
         return it->second;
     }
 
+    // The space an index of `dim` stores its vectors in.
+    static std::unique_ptr<hnswlib::SpaceInterface<float>> make_space(size_t dim, bool int8, float scale,
+                                                                      bool binary) {
+        if (binary) return std::make_unique<hnswlib::HammingSpace>(dim);
+        if (int8)   return std::make_unique<hnswlib::Int8L2Space>(dim, scale);
+        return std::make_unique<hnswlib::L2Space>(dim);
+    }
+
+    // Pack a float vector into HammingSpace words: bit i set where v[i] > 0.
+    static void pack_bits(const float* v, size_t dim, uint64_t* out) {
+        std::fill(out, out + (dim + 63) / 64, 0);
+        for (size_t i = 0; i < dim; ++i)
+            if (v[i] > 0.0f) out[i / 64] |= uint64_t{1} << (i % 64);
+    }
+
+    // Unpack HammingSpace words (possibly unaligned) to 0.0/1.0 floats.
+    static std::vector<float> unpack_bits(const char* raw, size_t dim) {
+        std::vector<float> out(dim);
+        for (size_t w = 0; w < (dim + 63) / 64; ++w) {
+            uint64_t word;
+            std::memcpy(&word, raw + w * sizeof(uint64_t), sizeof(uint64_t));
+            for (size_t i = w * 64; i < std::min(dim, w * 64 + 64); ++i)
+                out[i] = (word >> (i % 64)) & 1 ? 1.0f : 0.0f;
+        }
+        return out;
+    }
+
     // Build the int8 storage payload for one vector under a global scale.
     static void quantize_global(const float* v, size_t dim, float scale, int8_t* out) {
         float inv = (scale > 0.0f) ? 1.0f / scale : 0.0f;
@@ -151,7 +265,12 @@ private:
     // Insert a float vector into a modality index, quantizing to int8 first if
     // the modality is in-RAM int8. Centralises the float-vs-int8 store decision.
     static void add_point(ModalityIndex& m_idx, uint64_t id, const float* vec) {
-        if (m_idx.int8) {
+        if (m_idx.binary) {
+            static thread_local std::vector<uint64_t> bits;
+            bits.resize((m_idx.dim + 63) / 64);
+            pack_bits(vec, m_idx.dim, bits.data());
+            m_idx.index->addPoint(bits.data(), id);
+        } else if (m_idx.int8) {
             // Reusable per-thread buffer — a fresh std::vector per call across the
             // parallel insert pool churns the allocator and inflates RSS by ~MBs.
             static thread_local std::vector<int8_t> q;
@@ -166,6 +285,12 @@ private:
     // Encode a query in the modality's storage format (int8 blob or float bytes)
     // so it can be passed straight to searchKnn / the distance function.
     static std::vector<char> encode_query(const ModalityIndex& m_idx, const float* q) {
+        if (m_idx.binary) {
+            std::vector<uint64_t> bits((m_idx.dim + 63) / 64);
+            pack_bits(q, m_idx.dim, bits.data());
+            const char* p = reinterpret_cast<const char*>(bits.data());
+            return std::vector<char>(p, p + bits.size() * sizeof(uint64_t));
+        }
         if (m_idx.int8) {
             std::vector<char> blob(m_idx.dim);
             quantize_global(q, m_idx.dim, m_idx.scale,
@@ -179,6 +304,7 @@ private:
     // Read a stored vector back as float32 (dequantizing if the modality is int8).
     static std::vector<float> read_vector_internal(const ModalityIndex& m_idx, size_t internal_id) {
         const char* raw = m_idx.index->getDataByInternalId(internal_id);
+        if (m_idx.binary) return unpack_bits(raw, m_idx.dim);
         std::vector<float> out(m_idx.dim);
         if (m_idx.int8) {
             const int8_t* q = reinterpret_cast<const int8_t*>(raw);
@@ -192,6 +318,10 @@ private:
 
     // Read a stored vector back as float32 by external id. Throws if absent.
     static std::vector<float> read_vector_label(const ModalityIndex& m_idx, uint64_t id) {
+        if (m_idx.binary) {
+            auto words = m_idx.index->template getDataByLabel<uint64_t>(id);  // ceil(dim/64) words
+            return unpack_bits(reinterpret_cast<const char*>(words.data()), m_idx.dim);
+        }
         if (m_idx.int8) {
             auto q = m_idx.index->template getDataByLabel<int8_t>(id);  // dim int8s
             std::vector<float> out(q.size());
@@ -237,31 +367,50 @@ private:
     // so the graph is built concurrently. Caller must guarantee exclusive
     // structural access (no concurrent resize) — true at load and batch-ingest,
     // and we never exceed max_elements here so no resize is triggered.
-    static void parallel_add(ModalityIndex& m_idx,
-                             std::vector<std::pair<uint64_t, std::vector<float>>>& items) {
+    // `progress`, if set, is called on the calling thread as points go in.
+    // Seeded (seed_), the points go in one by one, in order.
+    void parallel_add(ModalityIndex& m_idx,
+                      std::vector<std::pair<uint64_t, std::vector<float>>>& items,
+                      const ProgressFn& progress = nullptr) const {
         const size_t n = items.size();
         if (n == 0) return;
+        constexpr size_t PROGRESS_EVERY = 1024;
         unsigned hw = std::thread::hardware_concurrency();
         size_t nthreads = std::min<size_t>(hw ? hw : 4, n);
         if (const char* env = std::getenv("FEATHER_LOAD_THREADS")) {
             long v = std::atol(env);                // override / cap thread count
             if (v >= 1) nthreads = std::min<size_t>(static_cast<size_t>(v), n);
         }
+        if (seed_) nthreads = 1;
         if (nthreads <= 1 || n < 256) {           // small sets: serial is faster
-            for (auto& [id, v] : items) add_point(m_idx, id, v.data());
+            for (size_t i = 0; i < n; ++i) {
+                add_point(m_idx, items[i].first, items[i].second.data());
+                if (progress && (i + 1) % PROGRESS_EVERY == 0) progress(i + 1, n);
+            }
+            if (progress) progress(n, n);
             return;
         }
-        std::atomic<size_t> next{0};
+        m_idx.shuffled = true;
+        std::atomic<size_t> next{0}, added{0};
+        auto work = [&]() {
+            size_t i;
+            while ((i = next.fetch_add(1)) < n) {
+                add_point(m_idx, items[i].first, items[i].second.data());
+                added.fetch_add(1);
+            }
+        };
+        // the calling thread works too, reporting between its own points
         std::vector<std::thread> pool;
-        pool.reserve(nthreads);
-        for (size_t t = 0; t < nthreads; ++t) {
-            pool.emplace_back([&]() {
-                size_t i;
-                while ((i = next.fetch_add(1)) < n)
-                    add_point(m_idx, items[i].first, items[i].second.data());
-            });
+        pool.reserve(nthreads - 1);
+        for (size_t t = 1; t < nthreads; ++t) pool.emplace_back(work);
+        size_t i, reported = 0;
+        while ((i = next.fetch_add(1)) < n) {
+            add_point(m_idx, items[i].first, items[i].second.data());
+            size_t done = added.fetch_add(1) + 1;
+            if (progress && done >= reported + PROGRESS_EVERY) progress(reported = done, n);
         }
         for (auto& th : pool) th.join();
+        if (progress) progress(n, n);
     }
 
     // ── Secondary index helpers ──────────────────────────────────────
@@ -281,6 +430,9 @@ private:
         if (!m.namespace_id.empty()) ns_index_[m.namespace_id].insert(id);
         if (!m.entity_id.empty())    entity_index_[m.entity_id].insert(id);
         for (const auto& [k, v] : m.attributes) attr_index_[attr_key(k, v)].insert(id);
+        if (m.attributes.count(ARCHIVED_ATTRIBUTE)) archived_.insert(id);
+        if (index_source_) source_index_[m.source].insert(id);
+        if (index_time_)   time_index_.insert({m.timestamp, id});
     }
 
     void deindex_meta(uint64_t id, const Metadata& m) {
@@ -294,20 +446,31 @@ private:
         if (!m.namespace_id.empty()) drop(ns_index_, m.namespace_id);
         if (!m.entity_id.empty())    drop(entity_index_, m.entity_id);
         for (const auto& [k, v] : m.attributes) drop(attr_index_, attr_key(k, v));
+        archived_.erase(id);
+        if (index_source_) drop(source_index_, m.source);
+        if (index_time_)   time_index_.erase({m.timestamp, id});
     }
 
     void build_secondary_indexes() {
+        auto enabled = properties_.find(INDEXES_PROPERTY);
+        std::string list = enabled == properties_.end() ? "" : "," + enabled->second + ",";
+        index_source_ = list.find(",source,") != std::string::npos;
+        index_time_   = list.find(",timestamp,") != std::string::npos;
         ns_index_.clear();
         entity_index_.clear();
         attr_index_.clear();
+        archived_.clear();
+        source_index_.clear();
+        time_index_.clear();
         for (const auto& [id, meta] : metadata_store_) {
             if (is_dead_meta(meta)) continue;   // candidate sets are live-only
             index_meta(id, meta);
         }
     }
 
-    // Candidate ids for a filter's INDEXED fields (namespace/entity/attributes),
-    // computed as the intersection of the relevant secondary-index sets.
+    // Candidate ids for a filter's INDEXED fields (namespace/entity/attributes,
+    // plus source and timestamp range when those indexes are on), computed as
+    // the intersection of the relevant secondary-index sets.
     // Sets `indexed` = true if the filter constrained at least one indexed field
     // (so the caller knows the result is an authoritative candidate set rather
     // than "no constraint"). An empty return with indexed=true means the filter
@@ -329,6 +492,25 @@ private:
         if (f.attributes_match)
             for (const auto& [k, v] : *f.attributes_match)
                 if (!pick(attr_index_, attr_key(k, v)))              return {};
+        if (f.source && index_source_ && !pick(source_index_, *f.source)) return {};
+
+        std::unordered_set<uint64_t> in_range;
+        if ((f.timestamp_after || f.timestamp_before) && index_time_) {
+            auto lo = time_index_.lower_bound({f.timestamp_after.value_or(INT64_MIN), 0});
+            auto hi = f.timestamp_before
+                ? time_index_.upper_bound({*f.timestamp_before, UINT64_MAX})
+                : time_index_.end();
+            bool selective = true;
+            for (auto it = lo; it != hi && selective; ++it) {
+                in_range.insert(it->second);
+                selective = in_range.size() <= TIME_INDEX_MAX_CANDIDATES;
+            }
+            if (selective) {
+                indexed = true;
+                if (in_range.empty()) return {};
+                sets.push_back(&in_range);
+            }
+        }
 
         if (!indexed) return {};                 // no indexed constraint at all
 
@@ -345,13 +527,91 @@ private:
         return result;
     }
 
+    // Exact squared L2 distances from `q` to the live `cand` records of this
+    // modality that pass `f` (which may constrain non-indexed fields too).
+    std::vector<std::pair<uint64_t, float>>
+    exact_distances(const ModalityIndex& m_idx, const std::vector<float>& q,
+                    const std::unordered_set<uint64_t>& cand, const SearchFilter& f) const {
+        std::vector<std::pair<uint64_t, float>> out;
+        out.reserve(cand.size());
+        for (uint64_t id : cand) {
+            auto it = metadata_store_.find(id);
+            if (it == metadata_store_.end() || is_dead_meta(it->second)) continue;
+            if (!f.matches(it->second)) continue;         // non-indexed predicates
+            std::vector<float> vec;
+            try { vec = read_vector_label(m_idx, id); }   // float (deq if int8)
+            catch (...) { continue; }                     // not in this modality
+            if (vec.size() != m_idx.dim) continue;
+            float dist = 0.0f;                            // exact L2 in float space
+            for (size_t d = 0; d < m_idx.dim; ++d) {
+                float diff = q[d] - vec[d];
+                dist += diff * diff;
+            }
+            out.emplace_back(id, dist);
+        }
+        return out;
+    }
+
+    // Truncated-dimension kNN for Matryoshka-style embeddings (caller holds
+    // mutex_): every live record `f` accepts is measured on the first `dims`
+    // components only, straight from index storage, and the `shortlist`
+    // nearest are then measured in full with the index's own distance.
+    // Returns (id, squared L2), nearest first, at most k.
+    std::vector<std::pair<uint64_t, float>>
+    truncated_knn(const ModalityIndex& m_idx, const std::vector<float>& q, size_t k, size_t dims,
+                  size_t shortlist, const SearchFilter& f) const {
+        std::vector<std::pair<float, hnswlib::tableint>> first;
+        size_t n = m_idx.index->cur_element_count;
+        for (size_t i = 0; i < n; ++i) {
+            auto internal = static_cast<hnswlib::tableint>(i);
+            if (m_idx.index->isMarkedDeleted(internal)) continue;
+            auto it = metadata_store_.find(m_idx.index->getExternalLabel(internal));
+            if (it == metadata_store_.end() || is_dead_meta(it->second) || !f.matches(it->second)) continue;
+            const char* raw = m_idx.index->getDataByInternalId(internal);
+            float dist = 0.0f;
+            if (m_idx.int8) {
+                const int8_t* v = reinterpret_cast<const int8_t*>(raw);
+                for (size_t d = 0; d < dims; ++d) {
+                    float diff = q[d] - static_cast<float>(v[d]) * m_idx.scale;
+                    dist += diff * diff;
+                }
+            } else {
+                const float* v = reinterpret_cast<const float*>(raw);
+                for (size_t d = 0; d < dims; ++d) {
+                    float diff = q[d] - v[d];
+                    dist += diff * diff;
+                }
+            }
+            first.emplace_back(dist, internal);
+        }
+        size_t keep = std::min(std::max(shortlist, k), first.size());
+        std::partial_sort(first.begin(), first.begin() + keep, first.end());
+
+        auto qbytes = encode_query(m_idx, q.data());
+        auto dist_fn = m_idx.space->get_dist_func();
+        void* param = m_idx.space->get_dist_func_param();
+        std::vector<std::pair<uint64_t, float>> out;
+        out.reserve(keep);
+        for (size_t j = 0; j < keep; ++j) {
+            auto internal = first[j].second;
+            out.emplace_back(m_idx.index->getExternalLabel(internal),
+                             dist_fn(qbytes.data(), m_idx.index->getDataByInternalId(internal), param));
+        }
+        std::sort(out.begin(), out.end(), [](const auto& a, const auto& b) {
+            return a.second < b.second || (a.second == b.second && a.first < b.first);
+        });
+        if (out.size() > k) out.resize(k);
+        return out;
+    }
+
     // ── Compaction (lock-free core) ──────────────────────────────────
     // Rebuild every modality index keeping only records that are present AND
     // live in metadata_store_. This reclaims the space held by markDelete'd
     // vectors (forget/expire) and orphaned index elements (purge erased their
     // metadata but left the vector marked-deleted in the graph). Caller MUST
     // hold mutex_. Returns the number of dead metadata records removed.
-    size_t compact_nolock() {
+    // `progress`, if set, counts index elements visited out of all of them.
+    size_t compact_nolock(const ProgressFn& progress = nullptr) {
         std::unordered_set<uint64_t> dead;
         for (const auto& [id, meta] : metadata_store_)
             if (is_dead_meta(meta)) dead.insert(id);
@@ -364,6 +624,8 @@ private:
                 if (m_idx.index->getDeletedCount() > 0) { work = true; break; }
         if (!work) return 0;
 
+        size_t total = 0, visited = 0;
+        for (auto& [name, m_idx] : modality_indices_) total += m_idx.index->cur_element_count;
         for (auto& [name, m_idx] : modality_indices_) {
             size_t n = m_idx.index->cur_element_count;
             std::vector<std::pair<uint64_t, std::vector<float>>> survivors;
@@ -375,17 +637,19 @@ private:
                 if (is_dead_meta(mit->second))      continue;  // forgotten / _deleted
                 survivors.push_back({id, read_vector_internal(m_idx, i)});  // float (deq if int8)
             }
-            // Rebuild preserving the storage type (float L2 or int8).
-            std::unique_ptr<hnswlib::SpaceInterface<float>> space;
-            if (m_idx.int8) space = std::make_unique<hnswlib::Int8L2Space>(m_idx.dim, m_idx.scale);
-            else            space = std::make_unique<hnswlib::L2Space>(m_idx.dim);
-            auto new_index = std::make_unique<hnswlib::HierarchicalNSW<float>>(
-                space.get(), std::max(INITIAL_MAX_ELEMENTS, survivors.size()), 16, 200);
+            // Rebuild preserving the storage type (float L2, int8 or binary).
+            auto space = make_space(m_idx.dim, m_idx.int8, m_idx.scale, m_idx.binary);
+            auto new_index = make_index(space.get(), std::max(INITIAL_MAX_ELEMENTS, survivors.size()));
             new_index->setEf(DEFAULT_EF);
             m_idx.index = std::move(new_index);
             m_idx.space = std::move(space);
-            for (const auto& [id, vec] : survivors)
+            m_idx.shuffled = false;
+            for (const auto& [id, vec] : survivors) {
                 add_point(m_idx, id, vec.data());
+                if (progress && ++visited % 1024 == 0) progress(visited, total);
+            }
+            visited += n - survivors.size();   // dead ones, skipped
+            if (progress) progress(visited, total);
         }
 
         for (uint64_t id : dead) metadata_store_.erase(id);
@@ -409,6 +673,152 @@ private:
         }
     }
 
+    // ── Edge helpers (caller holds mutex_) ───────────────────────────
+    // Remove from_id's edges to to_id (of `rel_type`, or any type if empty),
+    // keeping the reverse index in step. Returns the number removed.
+    size_t unlink_nolock(uint64_t from_id, uint64_t to_id, const std::string& rel_type) {
+        auto it = metadata_store_.find(from_id);
+        if (it == metadata_store_.end()) return 0;
+        auto matches = [&](uint64_t target, const std::string& type) {
+            return target == to_id && (rel_type.empty() || type == rel_type);
+        };
+        auto& edges = it->second.edges;
+        size_t before = edges.size();
+        edges.erase(std::remove_if(edges.begin(), edges.end(),
+                                   [&](const Edge& e) { return matches(e.target_id, e.rel_type); }),
+                    edges.end());
+        size_t removed = before - edges.size();
+        auto rit = reverse_index_.find(to_id);
+        if (rit != reverse_index_.end()) {
+            auto& incoming = rit->second;
+            incoming.erase(std::remove_if(incoming.begin(), incoming.end(),
+                               [&](const IncomingEdge& ie) {
+                                   return ie.source_id == from_id && matches(to_id, ie.rel_type);
+                               }),
+                           incoming.end());
+            if (incoming.empty()) reverse_index_.erase(rit);
+        }
+        return removed;
+    }
+
+    // Remove every edge from or to `id`, so a deleted record leaves no
+    // dangling links behind.
+    void drop_incident_edges_nolock(uint64_t id) {
+        auto rit = reverse_index_.find(id);
+        if (rit != reverse_index_.end()) {
+            for (const auto& ie : rit->second) {
+                auto sit = metadata_store_.find(ie.source_id);
+                if (sit == metadata_store_.end()) continue;
+                auto& edges = sit->second.edges;
+                edges.erase(std::remove_if(edges.begin(), edges.end(),
+                                           [id](const Edge& e) { return e.target_id == id; }),
+                            edges.end());
+            }
+            reverse_index_.erase(rit);
+        }
+        auto mit = metadata_store_.find(id);
+        if (mit == metadata_store_.end()) return;
+        for (const auto& e : mit->second.edges) {
+            auto t = reverse_index_.find(e.target_id);
+            if (t == reverse_index_.end()) continue;
+            auto& incoming = t->second;
+            incoming.erase(std::remove_if(incoming.begin(), incoming.end(),
+                                          [id](const IncomingEdge& ie) { return ie.source_id == id; }),
+                           incoming.end());
+            if (incoming.empty()) reverse_index_.erase(t);
+        }
+        mit->second.edges.clear();
+    }
+
+    // Move `id`'s entries in the reverse index from the edges it had,
+    // `before`, to the ones it has now.
+    void reindex_edges_nolock(uint64_t id, const std::vector<Edge>& before) {
+        for (const auto& e : before) {
+            auto t = reverse_index_.find(e.target_id);
+            if (t == reverse_index_.end()) continue;
+            auto& incoming = t->second;
+            incoming.erase(std::remove_if(incoming.begin(), incoming.end(),
+                                          [id](const IncomingEdge& ie) { return ie.source_id == id; }),
+                           incoming.end());
+            if (incoming.empty()) reverse_index_.erase(t);
+        }
+        for (const auto& e : metadata_store_[id].edges)
+            reverse_index_[e.target_id].push_back({id, e.rel_type, e.weight});
+    }
+
+    // ── Sparse helpers (caller holds mutex_) ─────────────────────────
+    // Sort by dimension, summing repeated dimensions and dropping zeros.
+    static SparseVector normalize_sparse(SparseVector v) {
+        std::sort(v.begin(), v.end(),
+                  [](const auto& a, const auto& b) { return a.first < b.first; });
+        SparseVector out;
+        out.reserve(v.size());
+        for (const auto& [dim, w] : v) {
+            if (!out.empty() && out.back().first == dim) out.back().second += w;
+            else out.push_back({dim, w});
+        }
+        out.erase(std::remove_if(out.begin(), out.end(),
+                                 [](const auto& p) { return p.second == 0.0f; }),
+                  out.end());
+        return out;
+    }
+
+    static void erase_sparse(SparseIndex& s, uint64_t id) {
+        auto it = s.vectors.find(id);
+        if (it == s.vectors.end()) return;
+        for (const auto& [dim, _] : it->second) {
+            auto p = s.postings.find(dim);
+            if (p == s.postings.end()) continue;
+            auto& list = p->second;
+            list.erase(std::remove_if(list.begin(), list.end(),
+                                      [id](const auto& e) { return e.first == id; }),
+                       list.end());
+            if (list.empty()) s.postings.erase(p);
+        }
+        s.vectors.erase(it);
+    }
+
+    void forget_sparse_nolock(uint64_t id) {
+        for (auto it = sparse_indices_.begin(); it != sparse_indices_.end();) {
+            erase_sparse(it->second, id);
+            it = it->second.vectors.empty() ? sparse_indices_.erase(it) : std::next(it);
+        }
+    }
+
+    // `v` must be normalized; empty removes the record's vector.
+    void set_sparse_nolock(uint64_t id, const std::string& name, SparseVector v) {
+        auto& s = sparse_indices_[name];
+        erase_sparse(s, id);
+        if (!v.empty()) {
+            for (const auto& [dim, w] : v) s.postings[dim].push_back({id, w});
+            s.vectors[id] = std::move(v);
+        }
+        if (s.vectors.empty()) sparse_indices_.erase(name);
+    }
+
+    // Drop the sparse vectors of records that are not live. Returns the
+    // number dropped.
+    size_t prune_sparse_nolock() {
+        size_t dropped = 0;
+        for (auto it = sparse_indices_.begin(); it != sparse_indices_.end();) {
+            auto& s = it->second;
+            size_t before = s.vectors.size();
+            for (auto v = s.vectors.begin(); v != s.vectors.end();) {
+                auto mit = metadata_store_.find(v->first);
+                bool live = mit != metadata_store_.end() && !is_dead_meta(mit->second);
+                v = live ? std::next(v) : s.vectors.erase(v);
+            }
+            if (s.vectors.size() != before) {
+                dropped += before - s.vectors.size();
+                s.postings.clear();
+                for (const auto& [id, vec] : s.vectors)
+                    for (const auto& [dim, w] : vec) s.postings[dim].push_back({id, w});
+            }
+            it = s.vectors.empty() ? sparse_indices_.erase(it) : std::next(it);
+        }
+        return dropped;
+    }
+
     static const std::unordered_set<std::string>& stop_words() {
         static const std::unordered_set<std::string> sw = {
             "a","an","the","and","or","but","in","on","at","to","for",
@@ -447,6 +857,7 @@ private:
         // Remove old posting entries for this doc (handles updates)
         auto old_it = doc_lengths_.find(id);
         if (old_it != doc_lengths_.end()) {
+            total_dl_ -= old_it->second;
             for (auto& [term, postings] : bm25_index_) {
                 postings.erase(
                     std::remove_if(postings.begin(), postings.end(),
@@ -464,16 +875,16 @@ private:
         for (const auto& [term, freq] : tf)
             bm25_index_[term].push_back({id, freq});
 
-        // Recompute avg_dl
-        double total = 0.0;
-        for (const auto& [_, len] : doc_lengths_) total += len;
-        avg_dl_ = doc_lengths_.empty() ? 1.0 : total / static_cast<double>(doc_lengths_.size());
+        total_dl_ += tokens.size();
+        avg_dl_ = static_cast<double>(total_dl_) / static_cast<double>(doc_lengths_.size());
     }
 
     void rebuild_bm25_index() {
         bm25_index_.clear();
         doc_lengths_.clear();
+        total_dl_ = 0;
         avg_dl_ = 0.0;
+
         for (const auto& [id, meta] : metadata_store_)
             add_to_bm25_index(id, meta.content);
     }
@@ -488,27 +899,51 @@ private:
     }
 
     // ── WAL helpers ──────────────────────────────────────────────────
+    static std::string wal_entry(WalOp op, uint64_t id, const std::string& payload) {
+        std::string entry;
+        auto op_b = static_cast<uint8_t>(op);
+        uint32_t plen = static_cast<uint32_t>(payload.size());
+        entry.append(reinterpret_cast<const char*>(&op_b), 1);
+        entry.append(reinterpret_cast<const char*>(&id),   8);
+        entry.append(reinterpret_cast<const char*>(&plen), 4);
+        entry += payload;
+        return entry;
+    }
+
     void wal_append(WalOp op, uint64_t id, const std::string& payload) {
-        if (wal_path_.empty()) return;
+        wal_append_entries(wal_entry(op, id, payload));
+    }
+
+    // Append entries framed by wal_entry, in one write.
+    void wal_append_entries(const std::string& entries) {
+        if (wal_path_.empty() || entries.empty()) return;
+        if (in_txn_) {
+            txn_buf_ += entries;
+            return;
+        }
         std::ofstream wf(wal_path_, std::ios::binary | std::ios::app);
         if (!wf) return;
-        auto op_b = static_cast<uint8_t>(op);
-        uint32_t plen = static_cast<uint32_t>(payload.size());
-        wf.write(reinterpret_cast<const char*>(&op_b), 1);
-        wf.write(reinterpret_cast<const char*>(&id),   8);
-        wf.write(reinterpret_cast<const char*>(&plen), 4);
-        if (plen > 0) wf.write(payload.data(), plen);
+        wf.write(entries.data(), entries.size());
     }
 
     void wal_clear() const {
         if (!wal_path_.empty()) std::remove(wal_path_.c_str());
+        txn_buf_.clear();   // a save mid-transaction has its changes on disk
     }
 
     void replay_wal() {
         if (wal_path_.empty()) return;
         std::ifstream wf(wal_path_, std::ios::binary);
         if (!wf) return;
+        replay_entries(wf);
+        build_reverse_index();
+        build_secondary_indexes();
+        rebuild_bm25_index();
+    }
 
+    // Apply WAL entries from `wf` up to its end or a torn entry, which is
+    // dropped; a torn TXN entry drops the whole transaction.
+    void replay_entries(std::istream& wf) {
         while (true) {
             uint8_t op_b; uint64_t id; uint32_t plen;
             if (!wf.read(reinterpret_cast<char*>(&op_b), 1)) break;
@@ -520,7 +955,11 @@ private:
             std::istringstream ss(payload);
             auto op = static_cast<WalOp>(op_b);
 
-            if (op == WalOp::ADD) {
+            if (op == WalOp::TXN) {
+                std::istringstream txn(payload);
+                replay_entries(txn);
+
+            } else if (op == WalOp::ADD) {
                 uint16_t mod_len = 0;
                 ss.read(reinterpret_cast<char*>(&mod_len), 2);
                 std::string modality(mod_len, '\0');
@@ -576,11 +1015,36 @@ private:
                     it->second.importance = 0.0f;
                     it->second.ttl        = 0;
                 }
+                drop_incident_edges_nolock(id);
+                forget_sparse_nolock(id);
+
+            } else if (op == WalOp::UNLINK) {
+                uint64_t to_id = 0;
+                ss.read(reinterpret_cast<char*>(&to_id), 8);
+                uint8_t rel_len = 0;
+                ss.read(reinterpret_cast<char*>(&rel_len), 1);
+                std::string rel_type(rel_len, '\0');
+                if (rel_len > 0) ss.read(&rel_type[0], rel_len);
+                unlink_nolock(id, to_id, rel_type);
+
+            } else if (op == WalOp::SPARSE) {
+                uint16_t name_len = 0;
+                ss.read(reinterpret_cast<char*>(&name_len), 2);
+                std::string name(name_len, '\0');
+                if (name_len > 0) ss.read(&name[0], name_len);
+                uint32_t nnz = 0;
+                ss.read(reinterpret_cast<char*>(&nnz), 4);
+                SparseVector v;
+                for (uint32_t i = 0; i < nnz && ss; ++i) {
+                    uint32_t dim = 0;
+                    float w = 0.0f;
+                    ss.read(reinterpret_cast<char*>(&dim), 4);
+                    ss.read(reinterpret_cast<char*>(&w), 4);
+                    v.push_back({dim, w});
+                }
+                set_sparse_nolock(id, name, normalize_sparse(std::move(v)));
             }
         }
-        build_reverse_index();
-        build_secondary_indexes();
-        rebuild_bm25_index();
     }
 
     static std::string escape_json(const std::string& s) {
@@ -609,16 +1073,30 @@ private:
     // ── Persistence ─────────────────────────────────────────────────
 
     void save_vectors() const {
+        if (path_.empty()) return;   // in-memory store: nothing to write
         // Atomic save: write to .tmp, then rename — prevents corruption on crash
         std::string tmp_path = path_ + ".tmp";
         std::ofstream f(tmp_path, std::ios::binary);
         if (!f) throw std::runtime_error("Cannot save to temp file: " + tmp_path);
 
         uint32_t magic   = 0x46454154; // "FEAT"
-        uint32_t version = 9;          // v7: on-disk int8; v8: in-RAM int8 flag+scale; v9: persisted HNSW graph
+        // v7: on-disk int8; v8: in-RAM int8 flag+scale; v9: persisted HNSW graph; v10: properties; v11: sparse vectors; v12: compression
+        uint32_t version = compression_ == Compression::None ? 11 : 12;
         f.write((char*)&magic,   4);
         f.write((char*)&version, 4);
 
+        // v10: properties section (key u16-len-prefixed, value u32-len-prefixed)
+        uint32_t prop_count = static_cast<uint32_t>(properties_.size());
+        f.write((char*)&prop_count, 4);
+        for (const auto& [key, val] : properties_) {
+            uint16_t key_len = static_cast<uint16_t>(key.size());
+            uint32_t val_len = static_cast<uint32_t>(val.size());
+            f.write((char*)&key_len, 2);
+            f.write(key.data(), key_len);
+            f.write((char*)&val_len, 4);
+            f.write(val.data(), val_len);
+        }
+
         // Build the set of valid IDs — exclude _forgotten and _deleted.
         // This makes forget()/purge() actually persist across save+reload.
         auto is_dead = [](const Metadata& m) -> bool {
@@ -632,31 +1110,45 @@ private:
             if (!is_dead(meta)) valid_ids.insert(id);
         }
 
+        // v12: how the rest is packed, then the sections, some or all of
+        // them through `packed` (see `compression_`)
+        std::optional<packed::Writer> packer;
+        std::ostream packed(nullptr);
+        if (compression_ != Compression::None) {
+            uint8_t codec = static_cast<uint8_t>(compression_);
+            f.write((char*)&codec, 1);
+            packed.rdbuf(&packer.emplace(f));
+        }
+        std::ostream& meta_out = compression_ == Compression::None ? static_cast<std::ostream&>(f) : packed;
+
         // Metadata section — only write live records
         uint32_t meta_count = static_cast<uint32_t>(valid_ids.size());
-        f.write((char*)&meta_count, 4);
+        meta_out.write((char*)&meta_count, 4);
         for (const auto& [id, meta] : metadata_store_) {
             if (!valid_ids.count(id)) continue;
-            f.write((char*)&id, 8);
-            meta.serialize(f);
+            meta_out.write((char*)&id, 8);
+            meta.serialize(meta_out);
         }
 
+        if (compression_ == Compression::Metadata) packer->finish();
+        std::ostream& out = compression_ == Compression::All ? static_cast<std::ostream&>(packed) : f;
+
         // Modality indices section — only write vectors whose ID is live
         uint32_t modal_count = static_cast<uint32_t>(modality_indices_.size());
-        f.write((char*)&modal_count, 4);
+        out.write((char*)&modal_count, 4);
         for (const auto& [name, m_idx] : modality_indices_) {
             uint16_t name_len = static_cast<uint16_t>(name.size());
-            f.write((char*)&name_len, 2);
-            f.write(name.data(), name_len);
+            out.write((char*)&name_len, 2);
+            out.write(name.data(), name_len);
             uint32_t dim32 = static_cast<uint32_t>(m_idx.dim);
-            f.write((char*)&dim32, 4);
+            out.write((char*)&dim32, 4);
 
             uint8_t quant = quantized_modalities_.count(name) ? 1 : 0;
-            f.write((char*)&quant, 1);
+            out.write((char*)&quant, 1);
             // v8: persist in-RAM int8 mode + its global scale so reload restores it
             uint8_t int8ram = m_idx.int8 ? 1 : 0;
-            f.write((char*)&int8ram, 1);
-            if (int8ram) f.write((char*)&m_idx.scale, 4);
+            out.write((char*)&int8ram, 1);
+            if (int8ram) out.write((char*)&m_idx.scale, 4);
 
             size_t total = m_idx.index->cur_element_count;
             uint32_t live_count = 0;
@@ -672,29 +1164,53 @@ private:
             // nodes to filter) and the modality isn't on-disk-quantized (which
             // re-encodes vectors to int8, incompatible with the graph blob).
             uint8_t persist_graph = (live_count == total && !quant) ? 1 : 0;
-            f.write((char*)&persist_graph, 1);
+            out.write((char*)&persist_graph, 1);
             if (persist_graph) {
-                m_idx.index->saveIndexStream(f);
+                m_idx.index->saveIndexStream(out);
                 continue;
             }
 
-            f.write((char*)&live_count, 4);
+            out.write((char*)&live_count, 4);
             std::vector<int8_t> qbuf(quant ? m_idx.dim : 0);
             for (size_t i = 0; i < total; ++i) {
                 uint64_t id = m_idx.index->getExternalLabel(i);
                 if (!valid_ids.count(id)) continue;
                 // dequantize int8 nodes to float; on-disk quant is independent
                 std::vector<float> data = read_vector_internal(m_idx, i);
-                f.write((char*)&id, 8);
+                out.write((char*)&id, 8);
                 if (quant) {
                     float scale = quantize_vec(data.data(), m_idx.dim, qbuf.data());
-                    f.write((char*)&scale, 4);
-                    f.write((char*)qbuf.data(), m_idx.dim);   // dim bytes
+                    out.write((char*)&scale, 4);
+                    out.write((char*)qbuf.data(), m_idx.dim);   // dim bytes
                 } else {
-                    f.write((char*)data.data(), m_idx.dim * sizeof(float));
+                    out.write((char*)data.data(), m_idx.dim * sizeof(float));
+                }
+            }
+        }
+
+        // v11: sparse vectors section — only vectors whose ID is live
+        uint32_t sparse_count = static_cast<uint32_t>(sparse_indices_.size());
+        out.write((char*)&sparse_count, 4);
+        for (const auto& [name, s] : sparse_indices_) {
+            uint16_t name_len = static_cast<uint16_t>(name.size());
+            out.write((char*)&name_len, 2);
+            out.write(name.data(), name_len);
+            uint32_t live_count = 0;
+            for (const auto& [id, _] : s.vectors)
+                if (valid_ids.count(id)) live_count++;
+            out.write((char*)&live_count, 4);
+            for (const auto& [id, vec] : s.vectors) {
+                if (!valid_ids.count(id)) continue;
+                uint32_t nnz = static_cast<uint32_t>(vec.size());
+                out.write((char*)&id, 8);
+                out.write((char*)&nnz, 4);
+                for (const auto& [dim, w] : vec) {
+                    out.write((char*)&dim, 4);
+                    out.write((char*)&w, 4);
                 }
             }
         }
+        if (compression_ == Compression::All) packer->finish();
         f.close();
         // Atomic rename: tmp → real path (POSIX atomic)
         if (std::rename(tmp_path.c_str(), path_.c_str()) != 0)
@@ -727,33 +1243,66 @@ private:
                 metadata_store_[id] = std::move(meta);
             }
         } else if (version >= 3) {
+            if (version >= 10) {
+                uint32_t prop_count = 0;
+                f.read((char*)&prop_count, 4);
+                for (uint32_t i = 0; i < prop_count && f; ++i) {
+                    uint16_t key_len = 0;
+                    uint32_t val_len = 0;
+                    f.read((char*)&key_len, 2);
+                    std::string key(key_len, '\0');
+                    f.read(&key[0], key_len);
+                    f.read((char*)&val_len, 4);
+                    if (val_len > (1u << 30))
+                        throw std::runtime_error("corrupt .feather: implausible property size "
+                                                 + std::to_string(val_len));
+                    std::string val;
+                    val.resize(val_len);
+                    f.read(&val[0], val_len);
+                    properties_[key] = std::move(val);
+                }
+            }
+            // v12: the sections may come packed (see `compression_`)
+            uint8_t codec = 0;
+            if (version >= 12) {
+                f.read((char*)&codec, 1);
+                if (codec > static_cast<uint8_t>(Compression::All))
+                    throw std::runtime_error("corrupt .feather: unknown compression " + std::to_string(codec));
+                compression_ = static_cast<Compression>(codec);
+            }
+            std::optional<packed::Reader> unpacker;
+            std::istream packed(nullptr);
+            if (compression_ != Compression::None) packed.rdbuf(&unpacker.emplace(f));
+            std::istream& meta_in = compression_ == Compression::None ? static_cast<std::istream&>(f) : packed;
             // v3/v4/v5: separate metadata section then modality indices
             uint32_t meta_count;
-            f.read((char*)&meta_count, 4);
+            meta_in.read((char*)&meta_count, 4);
             for (uint32_t i = 0; i < meta_count; ++i) {
                 uint64_t id;
-                f.read((char*)&id, 8);
-                metadata_store_[id] = Metadata::deserialize(f);
+                meta_in.read((char*)&id, 8);
+                metadata_store_[id] = Metadata::deserialize(meta_in);
             }
+            if (compression_ == Compression::Metadata) unpacker->finish();
+            std::istream& in = compression_ == Compression::All ? static_cast<std::istream&>(packed) : f;
             uint32_t modal_count;
-            f.read((char*)&modal_count, 4);
+            in.read((char*)&modal_count, 4);
             for (uint32_t m = 0; m < modal_count; ++m) {
                 uint16_t name_len;
-                f.read((char*)&name_len, 2);
+                in.read((char*)&name_len, 2);
                 std::string name(name_len, ' ');
-                f.read(&name[0], name_len);
+                in.read(&name[0], name_len);
                 uint32_t dim32, element_count;
-                f.read((char*)&dim32, 4);
+                in.read((char*)&dim32, 4);
                 uint8_t quant = 0;
-                if (version >= 7) f.read((char*)&quant, 1);
+                if (version >= 7) in.read((char*)&quant, 1);
                 uint8_t int8ram = 0;
                 float   int8scale = 0.0f;
                 if (version >= 8) {
-                    f.read((char*)&int8ram, 1);
-                    if (int8ram) f.read((char*)&int8scale, 4);
+                    in.read((char*)&int8ram, 1);
+                    if (int8ram) in.read((char*)&int8scale, 4);
                 }
                 uint8_t persist_graph = 0;
-                if (version >= 9) f.read((char*)&persist_graph, 1);
+                if (version >= 9) in.read((char*)&persist_graph, 1);
                 // configure int8-RAM BEFORE the index is created so it is built
                 // as an int8 index; vectors below are re-quantized via add_point.
                 if (int8ram) int8_ram_scale_[name] = int8scale;
@@ -763,35 +1312,64 @@ private:
                 if (persist_graph) {
                     // v9: restore the prebuilt HNSW graph verbatim — no rebuild.
                     // The blob carries the base layer (vectors) + link lists; the
-                    // space matches (Int8L2Space if int8ram, else L2Space).
-                    m_idx.index->loadIndexStream(f, m_idx.space.get(), 0);
+                    // space matches (Int8L2Space if int8ram, HammingSpace in a
+                    // binary store, else L2Space).
+                    m_idx.index->loadIndexStream(in, m_idx.space.get(), 0);
                     m_idx.index->setEf(DEFAULT_EF);
                     continue;
                 }
 
                 // Read all vectors serially (sequential I/O), then build the
                 // HNSW graph in parallel — graph construction dominates load.
-                f.read((char*)&element_count, 4);
+                in.read((char*)&element_count, 4);
                 std::vector<std::pair<uint64_t, std::vector<float>>> items;
                 items.reserve(element_count);
                 std::vector<int8_t> qbuf(quant ? dim32 : 0);
                 for (uint32_t i = 0; i < element_count; ++i) {
                     uint64_t id;
-                    f.read((char*)&id, 8);
+                    in.read((char*)&id, 8);
                     std::vector<float> vec(dim32);
                     if (quant) {
                         float scale = 1.0f;
-                        f.read((char*)&scale, 4);
-                        f.read((char*)qbuf.data(), dim32);   // dim bytes
+                        in.read((char*)&scale, 4);
+                        in.read((char*)qbuf.data(), dim32);   // dim bytes
                         dequantize_vec(qbuf.data(), dim32, scale, vec.data());
                     } else {
-                        f.read((char*)vec.data(), dim32 * sizeof(float));
+                        in.read((char*)vec.data(), dim32 * sizeof(float));
                     }
                     items.emplace_back(id, std::move(vec));
                 }
                 reserve(m_idx, m_idx.index->getCurrentElementCount() + items.size());
                 parallel_add(m_idx, items);
             }
+            if (version >= 11) {
+                uint32_t sparse_count = 0;
+                in.read((char*)&sparse_count, 4);
+                for (uint32_t s = 0; s < sparse_count && in; ++s) {
+                    uint16_t name_len = 0;
+                    in.read((char*)&name_len, 2);
+                    std::string name(name_len, '\0');
+                    in.read(&name[0], name_len);
+                    uint32_t count = 0;
+                    in.read((char*)&count, 4);
+                    for (uint32_t i = 0; i < count && in; ++i) {
+                        uint64_t id = 0;
+                        uint32_t nnz = 0;
+                        in.read((char*)&id, 8);
+                        in.read((char*)&nnz, 4);
+                        if (nnz > (1u << 24))
+                            throw std::runtime_error("corrupt .feather: implausible sparse vector size "
+                                                     + std::to_string(nnz));
+                        SparseVector v(nnz);
+                        for (auto& [dim, w] : v) {
+                            in.read((char*)&dim, 4);
+                            in.read((char*)&w, 4);
+                        }
+                        set_sparse_nolock(id, name, std::move(v));
+                    }
+                }
+            }
+            if (compression_ == Compression::All) unpacker->finish();
         }
 
         build_reverse_index();
@@ -810,7 +1388,15 @@ public:
         db->path_        = path;
         db->wal_path_    = path + ".wal";
         db->default_dim_ = default_dim;
-        db->load_vectors();
+        try {
+            db->load_vectors();
+        } catch (...) {
+            // a file that fails to load must not be saved over, half-read,
+            // as the store is destroyed
+            db->path_.clear();
+            db->wal_path_.clear();
+            throw;
+        }
         // Intentionally do NOT pre-create the "text" index. An empty HNSW index
         // preallocates ~70MB (1M-element link locks etc.); pre-creating it forced
         // set_int8_ram()/set_quantized() to build a *second* index, doubling RAM.
@@ -819,6 +1405,110 @@ public:
         return db;
     }
 
+    // Ephemeral store with no backing file (and no WAL): save() is a no-op
+    // until persist_to() gives it a path.
+    static std::unique_ptr<DB> in_memory(size_t default_dim = 768) {
+        auto db = std::make_unique<DB>();
+        db->default_dim_ = default_dim;
+        return db;
+    }
+
+    // Write the full state to `path` and keep it as the backing file from now
+    // on (later writes are WAL-logged there). Works for file-backed stores
+    // too, as a "save as".
+    void persist_to(const std::string& path) {
+        std::lock_guard<std::mutex> lock(mutex_);
+        if (path.empty()) throw std::runtime_error("persist_to: empty path");
+        std::string old_path = path_, old_wal = wal_path_;
+        path_     = path;
+        wal_path_ = path + ".wal";
+        try {
+            save_vectors();   // also drops any stale WAL at the new path
+        } catch (...) {
+            path_ = old_path; wal_path_ = old_wal;
+            throw;
+        }
+    }
+
+    bool is_in_memory() const { return path_.empty(); }
+
+    // How save() packs the file; takes effect on the next save.
+    void set_compression(Compression compression) {
+        std::lock_guard<std::mutex> lock(mutex_);
+        compression_ = compression;
+    }
+
+    Compression compression() const {
+        std::lock_guard<std::mutex> lock(mutex_);
+        return compression_;
+    }
+
+    // The backing file; empty for an in-memory or detached store.
+    const std::string& path() const { return path_; }
+
+
+    // Transactions. Between begin_txn() and commit_txn() changes apply in
+    // memory as usual but their WAL entries are held back; commit writes
+    // them as one TXN entry, which replay applies whole or, torn by a
+    // crash, not at all. rollback_txn() drops them unwritten: the caller
+    // must not have applied anything it cannot take back in memory.
+    void begin_txn() {
+        std::lock_guard<std::mutex> lock(mutex_);
+        if (in_txn_) throw std::runtime_error("a transaction is already open");
+        in_txn_ = true;
+        txn_buf_.clear();
+    }
+
+    void commit_txn() {
+        std::lock_guard<std::mutex> lock(mutex_);
+        if (!in_txn_) throw std::runtime_error("no transaction is open");
+        in_txn_ = false;
+        std::string entries;
+        entries.swap(txn_buf_);
+        if (entries.empty() || wal_path_.empty()) return;
+        std::ofstream wf(wal_path_, std::ios::binary | std::ios::app);
+        if (!wf) throw std::runtime_error("Cannot append to WAL: " + wal_path_);
+        std::string entry = wal_entry(WalOp::TXN, 0, entries);
+        wf.write(entry.data(), entry.size());
+        wf.flush();
+        if (!wf) throw std::runtime_error("Cannot append to WAL: " + wal_path_);
+    }
+
+    void rollback_txn() {
+        std::lock_guard<std::mutex> lock(mutex_);
+        in_txn_ = false;
+        txn_buf_.clear();
+    }
+
+    // Apply WAL entries another store logged (replication), logging them
+    // to this store's WAL as they are. A torn last entry is dropped.
+    void apply_wal(const std::string& entries) {
+        std::lock_guard<std::mutex> lock(mutex_);
+        if (!wal_path_.empty()) {
+            std::ofstream wf(wal_path_, std::ios::binary | std::ios::app);
+            wf.write(entries.data(), entries.size());
+            wf.flush();
+            if (!wf) throw std::runtime_error("Cannot append to WAL: " + wal_path_);
+        }
+        std::istringstream in(entries);
+        replay_entries(in);
+        build_reverse_index();
+        build_secondary_indexes();
+        rebuild_bm25_index();
+    }
+
+
+    // Drop the backing file (and WAL) without touching it: the store keeps
+
+    // its loaded state but never writes again. Used for read-only snapshots.
+    void detach() {
+        std::lock_guard<std::mutex> lock(mutex_);
+        path_.clear();
+        wal_path_.clear();
+    }
+
+
+
     // ─────────────────────────────────────────────────────────────────
     // Ingestion
     // ─────────────────────────────────────────────────────────────────
@@ -847,8 +1537,10 @@ public:
         add_point(m_idx, id, vec.data());
 
         auto it = metadata_store_.find(id);
+        std::vector<Edge> before;
         if (it != metadata_store_.end()) {
             deindex_meta(id, it->second);   // drop stale secondary-index entries
+            before = it->second.edges;
             Metadata combined = meta;
             if (combined.edges.empty() && !it->second.edges.empty())
                 combined.edges = it->second.edges;
@@ -856,6 +1548,7 @@ public:
         } else {
             metadata_store_[id] = meta;
         }
+        reindex_edges_nolock(id, before);
         if (!is_dead_meta(metadata_store_[id])) index_meta(id, metadata_store_[id]);
         add_to_bm25_index(id, meta.content);
     }
@@ -897,8 +1590,10 @@ public:
                 wal_append(WalOp::ADD, ids[i], ws.str());
             }
             auto it = metadata_store_.find(ids[i]);
+            std::vector<Edge> before;
             if (it != metadata_store_.end()) {
                 deindex_meta(ids[i], it->second);
+                before = it->second.edges;
                 Metadata combined = meta;
                 if (combined.edges.empty() && !it->second.edges.empty())
                     combined.edges = it->second.edges;
@@ -906,7 +1601,9 @@ public:
             } else {
                 metadata_store_[ids[i]] = meta;
             }
+            reindex_edges_nolock(ids[i], before);
             if (!is_dead_meta(metadata_store_[ids[i]])) index_meta(ids[i], metadata_store_[ids[i]]);
+
             add_to_bm25_index(ids[i], meta.content);
             items.emplace_back(ids[i], vecs[i]);
         }
@@ -950,6 +1647,22 @@ public:
         reverse_index_[to_id].push_back({from_id, rel_type, weight});
     }
 
+    // Remove from_id's edges to to_id of `rel_type` (any type if empty).
+    // Returns the number of edges removed.
+    size_t unlink(uint64_t from_id, uint64_t to_id, const std::string& rel_type = "") {
+        std::lock_guard<std::mutex> lock(mutex_);
+        size_t removed = unlink_nolock(from_id, to_id, rel_type);
+        if (removed > 0) {
+            std::ostringstream ws;
+            ws.write(reinterpret_cast<const char*>(&to_id), 8);
+            auto rel_len = static_cast<uint8_t>(std::min(rel_type.size(), size_t(255)));
+            ws.write(reinterpret_cast<const char*>(&rel_len), 1);
+            ws.write(rel_type.data(), rel_len);
+            wal_append(WalOp::UNLINK, from_id, ws.str());
+        }
+        return removed;
+    }
+
     // ─────────────────────────────────────────────────────────────────
     // Graph: query edges
     // ─────────────────────────────────────────────────────────────────
@@ -1221,7 +1934,31 @@ public:
         add_to_bm25_index(id, meta.content);
     }
 
+    // Give those of `ids` whose source is `from` the source `to`, in place:
+    // one WAL entry per record, and no index but the source index touched.
+    // Returns the ids renamed.
+    std::vector<uint64_t> rename_source(const std::vector<uint64_t>& ids, const std::string& from,
+                                        const std::string& to) {
+        std::lock_guard<std::mutex> lock(mutex_);
+        std::vector<uint64_t> renamed;
+        std::string wal;
+        for (uint64_t id : ids) {
+            auto it = metadata_store_.find(id);
+            if (it == metadata_store_.end() || it->second.source != from || is_dead_meta(it->second)) continue;
+            deindex_meta(id, it->second);
+            it->second.source = to;
+            index_meta(id, it->second);
+            std::ostringstream ws;
+            it->second.serialize(ws);
+            wal += wal_entry(WalOp::UPDATE, id, ws.str());
+            renamed.push_back(id);
+        }
+        wal_append_entries(wal);
+        return renamed;
+    }
+
     void update_importance(uint64_t id, float importance) {
+
         std::lock_guard<std::mutex> lock(mutex_);
         // WAL
         {
@@ -1261,6 +1998,27 @@ public:
         return ids;
     }
 
+    // Every id that has metadata, regardless of which modality holds its
+    // vector(s). Records browsing/counting should use this so a DB whose
+    // vectors live under a non-"text" modality still lists its records.
+    std::vector<uint64_t> all_ids() const {
+        std::lock_guard<std::mutex> lock(mutex_);
+        std::vector<uint64_t> ids;
+        ids.reserve(metadata_store_.size());
+        for (const auto& [id, _] : metadata_store_) ids.push_back(id);
+        return ids;
+    }
+
+    // The actual modality index names present in this DB (e.g. "text",
+    // "visual", or whatever an external pipeline named them).
+    std::vector<std::string> modality_names() const {
+        std::lock_guard<std::mutex> lock(mutex_);
+        std::vector<std::string> names;
+        names.reserve(modality_indices_.size());
+        for (const auto& [name, _] : modality_indices_) names.push_back(name);
+        return names;
+    }
+
     // ─────────────────────────────────────────────────────────────────
     // Secondary-index queries — O(matches), LIVE records only.
     // Back the API's namespace/entity/attribute scans and feed feature A's
@@ -1305,6 +2063,12 @@ public:
     // ─────────────────────────────────────────────────────────────────
     // Search
     // ─────────────────────────────────────────────────────────────────
+    // Whether any live record is archived.
+    bool has_archived() const {
+        std::lock_guard<std::mutex> lock(mutex_);
+        return !archived_.empty();
+    }
+
     struct SearchResult {
         uint64_t id;
         float    score;
@@ -1334,19 +2098,8 @@ public:
                 double now_ts = static_cast<double>(std::time(nullptr));
                 std::vector<SearchResult> results;
                 results.reserve(cand.size());
-                for (uint64_t id : cand) {
+                for (auto [id, dist] : exact_distances(m_idx, q, cand, *filter)) {
                     auto it = metadata_store_.find(id);
-                    if (it == metadata_store_.end() || is_dead_meta(it->second)) continue;
-                    if (!filter->matches(it->second)) continue;   // non-indexed predicates
-                    std::vector<float> vec;
-                    try { vec = read_vector_label(m_idx, id); }   // float (deq if int8)
-                    catch (...) { continue; }                     // not in this modality
-                    if (vec.size() != m_idx.dim) continue;
-                    float dist = 0.0f;                            // exact L2 in float space
-                    for (size_t d = 0; d < m_idx.dim; ++d) {
-                        float diff = q[d] - vec[d];
-                        dist += diff * diff;
-                    }
                     touch_nolock(id);
                     float score = scoring
                         ? Scorer::calculate_score(dist, it->second, *scoring, now_ts)
@@ -1401,12 +2154,177 @@ public:
         return results;
     }
 
+    // What a search planner needs to know of `f` over `modality`'s vectors.
+    struct FilterStats {
+        size_t vectors = 0;             // live vectors in the modality
+        bool   indexed = false;         // an index narrows f (knn() would pre-filter)
+        size_t index_rows = 0;          // records the indexes narrow it to, estimated
+        size_t sampled = 0;             // live vectors sampled
+        std::vector<uint64_t> matched;  // the sampled ids f accepts
+    };
+
+    // Selectivity of `f` over `modality`: the index sets it picks, combined
+    // as if independent (their shares of all records multiplied), and up to
+    // `sample` live vectors at an even stride through the index, checked
+    // against every term of f.
+    FilterStats filter_stats(const std::string& modality, const SearchFilter& f, size_t sample) const {
+        std::lock_guard<std::mutex> lock(mutex_);
+        FilterStats stats;
+        auto m_it = modality_indices_.find(modality);
+        if (m_it == modality_indices_.end()) return stats;
+        const auto& m_idx = m_it->second;
+
+        double records = static_cast<double>(std::max<size_t>(metadata_store_.size(), 1));
+        double share = 1.0;
+        auto pick = [&](const std::unordered_map<std::string, std::unordered_set<uint64_t>>& idx,
+                        const std::string& key) {
+            stats.indexed = true;
+            auto it = idx.find(key);
+            share *= it == idx.end() ? 0.0 : it->second.size() / records;
+        };
+        if (f.namespace_id) pick(ns_index_, *f.namespace_id);
+        if (f.entity_id)    pick(entity_index_, *f.entity_id);
+        if (f.attributes_match)
+            for (const auto& [k, v] : *f.attributes_match) pick(attr_index_, attr_key(k, v));
+        if (f.source && index_source_) pick(source_index_, *f.source);
+        if ((f.timestamp_after || f.timestamp_before) && index_time_) {
+            auto lo = time_index_.lower_bound({f.timestamp_after.value_or(INT64_MIN), 0});
+            auto hi = f.timestamp_before
+                ? time_index_.upper_bound({*f.timestamp_before, UINT64_MAX})
+                : time_index_.end();
+            size_t in_range = 0;
+            for (auto it = lo; it != hi && in_range <= TIME_INDEX_MAX_CANDIDATES; ++it) ++in_range;
+            if (in_range <= TIME_INDEX_MAX_CANDIDATES) {   // as candidates_for_filter
+                stats.indexed = true;
+                share *= in_range / records;
+            }
+        }
+        if (stats.indexed) stats.index_rows = static_cast<size_t>(std::llround(share * records));
+
+        size_t n = m_idx.index->cur_element_count;
+        stats.vectors = n - m_idx.index->getDeletedCount();
+        size_t draws = std::min(sample, n);
+        for (size_t j = 0; j < draws; ++j) {
+            auto internal = static_cast<hnswlib::tableint>(j * n / draws);
+            if (m_idx.index->isMarkedDeleted(internal)) continue;
+            uint64_t id = m_idx.index->getExternalLabel(internal);
+            auto it = metadata_store_.find(id);
+            if (it == metadata_store_.end() || is_dead_meta(it->second)) continue;
+            ++stats.sampled;
+            if (f.matches(it->second)) stats.matched.push_back(id);
+        }
+        return stats;
+    }
+
+    // Raw k-nearest-neighbour lookup: (id, squared L2 distance), nearest first.
+    // Unlike search() it neither scores nor touches the hits, so analytics
+    // passes (outlier / duplicate scans) don't inflate recall counts. A
+    // filter is applied during the HNSW traversal, like search()'s. With
+    // `exact`, every live record is measured instead: slower, but the true
+    // nearest neighbours rather than the graph's approximation. With a
+    // `truncate_dim`, every live record is measured on that many leading
+    // components and the `shortlist` nearest re-measured in full (see
+    // truncated_knn). With `post_filter`, a filter an index narrows is still
+    // applied during the traversal rather than by measuring the index's
+    // candidates.
+    std::vector<std::pair<uint64_t, float>> knn(const std::vector<float>& q, size_t k,
+                                                const std::string& modality = "text",
+                                                const SearchFilter* filter = nullptr,
+                                                bool exact = false, size_t truncate_dim = 0,
+                                                size_t shortlist = 0, bool post_filter = false) const {
+        std::lock_guard<std::mutex> lock(mutex_);
+        auto m_it = modality_indices_.find(modality);
+        if (m_it == modality_indices_.end()) return {};
+        const auto& m_idx = m_it->second;
+        if (q.size() != m_idx.dim)
+            throw std::runtime_error("Dimension mismatch for modality " + modality);
+        SearchFilter everything;
+        if (!filter && exact) filter = &everything;
+        if (truncate_dim > 0) {
+            if (truncate_dim > m_idx.dim)
+                throw std::runtime_error("truncate_dim " + std::to_string(truncate_dim) + " exceeds modality "
+                                         + modality + "'s dim " + std::to_string(m_idx.dim));
+            if (m_idx.binary)
+                throw std::runtime_error("modality " + modality + " is stored as bits, which cannot be truncated");
+            return truncated_knn(m_idx, q, k, truncate_dim, shortlist, filter ? *filter : everything);
+        }
+
+
+        // Pre-filtered exact path, as in search(); with `exact`, over all
+        // records when no index narrows them.
+        if (filter && (exact || !post_filter)) {
+            bool indexed = false;
+
+            auto cand = candidates_for_filter(*filter, indexed);
+            if (exact && !indexed)
+                for (const auto& [id, meta] : metadata_store_) cand.insert(id);
+            if (indexed || exact) {
+
+                auto out = exact_distances(m_idx, q, cand, *filter);
+                std::sort(out.begin(), out.end(),
+                          [](const auto& a, const auto& b) { return a.second < b.second; });
+                if (out.size() > k) out.resize(k);
+                return out;
+            }
+        }
+
+
+        struct FilterWrapper : public hnswlib::BaseFilterFunctor {
+            const SearchFilter& filter_;
+            const std::unordered_map<uint64_t, Metadata>& store_;
+            FilterWrapper(const SearchFilter& f,
+                          const std::unordered_map<uint64_t, Metadata>& s)
+                : filter_(f), store_(s) {}
+            bool operator()(hnswlib::labeltype id) override {
+                auto it = store_.find(id);
+                return it != store_.end() && filter_.matches(it->second);
+            }
+        };
+
+        auto qbytes = encode_query(m_idx, q.data());
+        std::optional<FilterWrapper> hnsw_filter;
+        if (filter) hnsw_filter.emplace(*filter, metadata_store_);
+        auto res = m_idx.index->searchKnn(qbytes.data(), k,
+                                          hnsw_filter ? &*hnsw_filter : nullptr);
+
+        std::vector<std::pair<uint64_t, float>> out(res.size());
+        for (size_t i = res.size(); i-- > 0; res.pop())
+            out[i] = {res.top().second, res.top().first};
+        return out;
+    }
+
     // ─────────────────────────────────────────────────────────────────
     // BM25 keyword search
     // ─────────────────────────────────────────────────────────────────
+
     std::vector<SearchResult> keyword_search(const std::string& query, size_t k = 10,
                                              const SearchFilter* filter = nullptr) {
         std::lock_guard<std::mutex> lock(mutex_);
+        std::vector<SearchResult> results;
+        for (const auto& [id, sc] : bm25_nolock(query, k, filter)) {
+            touch_nolock(id);
+            auto mit = metadata_store_.find(id);
+            Metadata meta = (mit != metadata_store_.end()) ? mit->second : Metadata();
+            results.push_back({id, sc, std::move(meta)});
+        }
+        return results;
+    }
+
+    // Raw BM25 lookup: (id, score), best first. Like knn() it does not touch
+    // the hits, so callers fusing it with other rankings decide what counts
+    // as recalled.
+    std::vector<std::pair<uint64_t, float>> bm25(const std::string& query, size_t k,
+                                                 bool exclude_archived = false) const {
+        std::lock_guard<std::mutex> lock(mutex_);
+        SearchFilter unarchived;
+        unarchived.exclude_archived = true;
+        return bm25_nolock(query, k, exclude_archived ? &unarchived : nullptr);
+    }
+
+    // BM25 ranking of the live records matching `filter` (lock held by the
+    // caller).
+    std::vector<std::pair<uint64_t, float>>
+    bm25_nolock(const std::string& query, size_t k, const SearchFilter* filter) const {
         auto terms = tokenize(query);
         if (terms.empty() || doc_lengths_.empty()) return {};
 
@@ -1430,11 +2348,9 @@ public:
                 (static_cast<double>(n_t) + 0.5) + 1.0);
 
             for (const auto& p : postings) {
-                if (filter) {
-                    auto mit = metadata_store_.find(p.doc_id);
-                    if (mit == metadata_store_.end() || !filter->matches(mit->second))
-                        continue;
-                }
+                auto mit = metadata_store_.find(p.doc_id);
+                if (mit == metadata_store_.end() || is_dead_meta(mit->second)) continue;
+                if (filter && !filter->matches(mit->second)) continue;
                 auto dl_it = doc_lengths_.find(p.doc_id);
                 uint32_t dl = (dl_it != doc_lengths_.end()) ? dl_it->second : 1;
                 double tf_norm =
@@ -1452,15 +2368,10 @@ public:
         std::sort(ranked.begin(), ranked.end(), std::greater<std::pair<float,uint64_t>>());
         if (ranked.size() > k) ranked.resize(k);
 
-        std::vector<SearchResult> results;
-        results.reserve(ranked.size());
-        for (const auto& [sc, id] : ranked) {
-            touch_nolock(id);
-            auto mit = metadata_store_.find(id);
-            Metadata meta = (mit != metadata_store_.end()) ? mit->second : Metadata();
-            results.push_back({id, sc, std::move(meta)});
-        }
-        return results;
+        std::vector<std::pair<uint64_t, float>> out;
+        out.reserve(ranked.size());
+        for (const auto& [sc, id] : ranked) out.emplace_back(id, sc);
+        return out;
     }
 
     // ─────────────────────────────────────────────────────────────────
@@ -1511,41 +2422,10 @@ public:
 
         // ── Inline BM25 search (no re-lock) ──────────────────────────
         std::vector<SearchResult> kw_results;
-        {
-            auto terms = tokenize(query);
-            if (!terms.empty() && !doc_lengths_.empty()) {
-                size_t N = doc_lengths_.size();
-                double avdl = avg_dl_ > 0.0 ? avg_dl_ : 1.0;
-                std::unordered_set<std::string> uterms(terms.begin(), terms.end());
-                std::unordered_map<uint64_t, float> scores;
-                for (const auto& term : uterms) {
-                    auto it = bm25_index_.find(term);
-                    if (it == bm25_index_.end()) continue;
-                    size_t n_t = it->second.size();
-                    double idf = std::log((static_cast<double>(N)-n_t+0.5)/(n_t+0.5)+1.0);
-                    for (const auto& p : it->second) {
-                        if (filter) {
-                            auto mit = metadata_store_.find(p.doc_id);
-                            if (mit==metadata_store_.end()||!filter->matches(mit->second)) continue;
-                        }
-                        auto dl_it = doc_lengths_.find(p.doc_id);
-                        uint32_t dl = dl_it!=doc_lengths_.end() ? dl_it->second : 1;
-                        double tf_norm = (p.term_freq*(BM25_K1+1.0)) /
-                            (p.term_freq + BM25_K1*(1.0-BM25_B+BM25_B*dl/avdl));
-                        scores[p.doc_id] += static_cast<float>(idf * tf_norm);
-                    }
-                }
-                std::vector<std::pair<float,uint64_t>> ranked;
-                ranked.reserve(scores.size());
-                for (const auto& [id, sc] : scores) ranked.push_back({sc, id});
-                std::sort(ranked.begin(), ranked.end(), std::greater<std::pair<float,uint64_t>>());
-                if (ranked.size() > candidates) ranked.resize(candidates);
-                for (const auto& [sc, id] : ranked) {
-                    auto mit = metadata_store_.find(id);
-                    Metadata meta = (mit != metadata_store_.end()) ? mit->second : Metadata();
-                    kw_results.push_back({id, sc, std::move(meta)});
-                }
-            }
+        for (const auto& [id, sc] : bm25_nolock(query, candidates, filter)) {
+            auto mit = metadata_store_.find(id);
+            Metadata meta = (mit != metadata_store_.end()) ? mit->second : Metadata();
+            kw_results.push_back({id, sc, std::move(meta)});
         }
 
         // ── RRF merge ────────────────────────────────────────────────
@@ -1571,12 +2451,95 @@ public:
         return results;
     }
 
+    // ─────────────────────────────────────────────────────────────────
+    // Sparse vectors
+    // ─────────────────────────────────────────────────────────────────
+
+    // Attach a sparse vector to an existing record under `name`, replacing
+    // the one it had there; an empty vector removes it. Returns false if
+    // `id` has no metadata.
+    bool set_sparse(uint64_t id, const std::string& name, const SparseVector& v) {
+        std::lock_guard<std::mutex> lock(mutex_);
+        if (!metadata_store_.count(id)) return false;
+        SparseVector norm = normalize_sparse(v);
+        // WAL
+        {
+            std::ostringstream ws;
+            uint16_t name_len = static_cast<uint16_t>(name.size());
+            uint32_t nnz = static_cast<uint32_t>(norm.size());
+            ws.write(reinterpret_cast<const char*>(&name_len), 2);
+            ws.write(name.data(), name_len);
+            ws.write(reinterpret_cast<const char*>(&nnz), 4);
+            for (const auto& [dim, w] : norm) {
+                ws.write(reinterpret_cast<const char*>(&dim), 4);
+                ws.write(reinterpret_cast<const char*>(&w), 4);
+            }
+            wal_append(WalOp::SPARSE, id, ws.str());
+        }
+        set_sparse_nolock(id, name, std::move(norm));
+        return true;
+    }
+
+    // The live record's sparse vector under `name` (empty if none).
+    SparseVector get_sparse(uint64_t id, const std::string& name) const {
+        std::lock_guard<std::mutex> lock(mutex_);
+        auto mit = metadata_store_.find(id);
+        if (mit == metadata_store_.end() || is_dead_meta(mit->second)) return {};
+        auto it = sparse_indices_.find(name);
+        if (it == sparse_indices_.end()) return {};
+        auto v = it->second.vectors.find(id);
+        return v != it->second.vectors.end() ? v->second : SparseVector();
+    }
+
+    // Live records with the highest dot product between their sparse vector
+    // under `name` and `query`, as (id, score), best first. Records sharing
+    // no dimension with the query are not returned, nor archived records
+    // with `exclude_archived`. Like knn() it does not touch the hits.
+    std::vector<std::pair<uint64_t, float>>
+    sparse_search(const std::string& name, const SparseVector& query, size_t k,
+                  bool exclude_archived = false) const {
+        std::lock_guard<std::mutex> lock(mutex_);
+        auto it = sparse_indices_.find(name);
+        if (it == sparse_indices_.end()) return {};
+        std::unordered_map<uint64_t, float> scores;
+        for (const auto& [dim, qw] : normalize_sparse(query)) {
+            auto p = it->second.postings.find(dim);
+            if (p == it->second.postings.end()) continue;
+            for (const auto& [id, w] : p->second) scores[id] += qw * w;
+        }
+        std::vector<std::pair<uint64_t, float>> hits;
+        hits.reserve(scores.size());
+        for (const auto& [id, score] : scores) {
+            auto mit = metadata_store_.find(id);
+            if (mit == metadata_store_.end() || is_dead_meta(mit->second)) continue;
+            if (exclude_archived && archived_.count(id)) continue;
+            hits.emplace_back(id, score);
+        }
+        size_t n = std::min(k, hits.size());
+        std::partial_sort(hits.begin(), hits.begin() + n, hits.end(),
+                          [](const auto& a, const auto& b) {
+                              return a.second != b.second ? a.second > b.second : a.first < b.first;
+                          });
+        hits.resize(n);
+        return hits;
+    }
+
+    // Names of the sparse vector sets present in this DB.
+    std::vector<std::string> sparse_names() const {
+        std::lock_guard<std::mutex> lock(mutex_);
+        std::vector<std::string> names;
+        names.reserve(sparse_indices_.size());
+        for (const auto& [name, _] : sparse_indices_) names.push_back(name);
+        return names;
+    }
+
     // ─────────────────────────────────────────────────────────────────
     // Memory lifecycle: forget / purge / expire
     // ─────────────────────────────────────────────────────────────────
 
+
     // Soft-delete: mark-deleted in HNSW (exits search), blank content,
-    // set importance=0. The node shell remains so graph edges stay traversable.
+    // set importance=0, and drop every edge from or to the record.
     void forget(uint64_t id) {
         std::lock_guard<std::mutex> lock(mutex_);
         wal_append(WalOp::FORGET, id, "");
@@ -1591,6 +2554,8 @@ public:
             it->second.importance = 0.0f;
             it->second.ttl        = 0;
         }
+        drop_incident_edges_nolock(id);
+        forget_sparse_nolock(id);
         maybe_auto_compact_nolock();
     }
 
@@ -1626,6 +2591,7 @@ public:
         }
         // Remove reverse index entries for purged target keys
         for (uint64_t id : to_purge) reverse_index_.erase(id);
+        prune_sparse_nolock();
 
         // Prune edges in surviving nodes that pointed to purged targets
         for (auto& [id, meta] : metadata_store_) {
@@ -1665,7 +2631,11 @@ public:
                 it->second.importance = 0.0f;
                 it->second.ttl        = 0;
             }
+            drop_incident_edges_nolock(id);
+            forget_sparse_nolock(id);
             ++count;
+
+
         }
         maybe_auto_compact_nolock();
         return count;
@@ -1674,9 +2644,9 @@ public:
     // ─────────────────────────────────────────────────────────────────
     // 7d: compact() — rebuild HNSW indices without soft-deleted records
     // ─────────────────────────────────────────────────────────────────
-    size_t compact() {
+    size_t compact(const ProgressFn& progress = nullptr) {
         std::lock_guard<std::mutex> lock(mutex_);
-        return compact_nolock();
+        return compact_nolock(progress);
     }
 
     // Configure auto-compaction. ratio in (0,1] triggers a rebuild of a modality
@@ -1691,6 +2661,174 @@ public:
         return auto_compact_ratio_;
     }
 
+    // The largest share of any modality index's elements that are marked
+    // deleted: what compaction would reclaim, as auto-compaction measures it.
+    float dead_share() const {
+        std::lock_guard<std::mutex> lock(mutex_);
+        float share = 0.0f;
+        for (const auto& [name, m_idx] : modality_indices_) {
+            size_t total = m_idx.index->getCurrentElementCount();
+            if (total == 0) continue;
+            share = std::max(share, static_cast<float>(m_idx.index->getDeletedCount())
+                                    / static_cast<float>(total));
+        }
+        return share;
+    }
+
+    // WAL entries that turn `base` into this store, record by record: an
+    // ADD per modality whose vector is new or changed (carrying the
+    // record), an UPDATE for a record whose metadata alone changed, a
+    // SPARSE per new or changed sparse vector, and a FORGET for each record
+    // live in `base` and not here. Properties are not in the WAL; see
+    // `properties_blob`. Returns the entries and how many there are.
+    std::pair<std::string, size_t> delta_from(const DB& base) const {
+        std::scoped_lock lock(mutex_, base.mutex_);
+        std::string out;
+        size_t count = 0;
+        auto vector_of = [](const DB& db, const std::string& modality, uint64_t id, std::vector<float>& vec) {
+            auto it = db.modality_indices_.find(modality);
+            if (it == db.modality_indices_.end()) return false;
+            try { vec = read_vector_label(it->second, id); } catch (...) { return false; }
+            return true;
+        };
+        auto live = [](const DB& db, uint64_t id) {
+            auto it = db.metadata_store_.find(id);
+            return it != db.metadata_store_.end() && !is_dead_meta(it->second) ? &it->second : nullptr;
+        };
+        for (const auto& [id, meta] : metadata_store_) {
+            if (is_dead_meta(meta)) continue;
+            const Metadata* was = live(base, id);
+            std::ostringstream ms;
+            meta.serialize(ms);
+            std::string serialized = ms.str();
+            bool changed = true;
+            if (was) {
+                std::ostringstream bs;
+                was->serialize(bs);
+                changed = bs.str() != serialized;
+            }
+            bool added = false;
+            for (const auto& [name, m_idx] : modality_indices_) {
+                std::vector<float> vec, old;
+                if (!vector_of(*this, name, id, vec)) continue;
+                if (was && vector_of(base, name, id, old) && old == vec) continue;
+                std::ostringstream ws;
+                uint16_t mod_len = static_cast<uint16_t>(name.size());
+                uint32_t dim32 = static_cast<uint32_t>(vec.size());
+                ws.write(reinterpret_cast<const char*>(&mod_len), 2);
+                ws.write(name.data(), mod_len);
+                ws.write(reinterpret_cast<const char*>(&dim32), 4);
+                ws.write(reinterpret_cast<const char*>(vec.data()), vec.size() * 4);
+                ws << serialized;
+                out += wal_entry(WalOp::ADD, id, ws.str());
+                ++count;
+                added = true;
+            }
+            if (!added && changed) {
+                out += wal_entry(WalOp::UPDATE, id, serialized);
+                ++count;
+            }
+        }
+        for (const auto& [name, s_idx] : sparse_indices_) {
+            auto theirs = base.sparse_indices_.find(name);
+            for (const auto& [id, vec] : s_idx.vectors) {
+                if (!live(*this, id)) continue;
+                if (live(base, id) && theirs != base.sparse_indices_.end()) {
+                    auto it = theirs->second.vectors.find(id);
+                    if (it != theirs->second.vectors.end() && it->second == vec) continue;
+                }
+                std::ostringstream ws;
+                uint16_t name_len = static_cast<uint16_t>(name.size());
+                uint32_t nnz = static_cast<uint32_t>(vec.size());
+                ws.write(reinterpret_cast<const char*>(&name_len), 2);
+                ws.write(name.data(), name_len);
+                ws.write(reinterpret_cast<const char*>(&nnz), 4);
+                for (const auto& [dim, w] : vec) {
+                    ws.write(reinterpret_cast<const char*>(&dim), 4);
+                    ws.write(reinterpret_cast<const char*>(&w), 4);
+                }
+                out += wal_entry(WalOp::SPARSE, id, ws.str());
+                ++count;
+            }
+        }
+        for (const auto& [id, meta] : base.metadata_store_) {
+            if (is_dead_meta(meta) || live(*this, id)) continue;
+            out += wal_entry(WalOp::FORGET, id, "");
+            ++count;
+        }
+        return {out, count};
+    }
+
+    // FNV-1a over the live records, by id: their ids and metadata, not
+    // their vectors. Equal stores digest equal, whatever their WAL holds.
+    uint64_t digest() const {
+        std::lock_guard<std::mutex> lock(mutex_);
+        std::vector<uint64_t> ids;
+        ids.reserve(metadata_store_.size());
+        for (const auto& [id, meta] : metadata_store_)
+            if (!is_dead_meta(meta)) ids.push_back(id);
+        std::sort(ids.begin(), ids.end());
+        uint64_t hash = 0xcbf29ce484222325ULL;
+        auto mix = [&hash](const char* p, size_t n) {
+            for (size_t i = 0; i < n; ++i) {
+                hash ^= static_cast<uint8_t>(p[i]);
+                hash *= 0x100000001b3ULL;
+            }
+        };
+        for (uint64_t id : ids) {
+            std::ostringstream ms;
+            metadata_store_.at(id).serialize(ms);
+            std::string serialized = ms.str();
+            mix(reinterpret_cast<const char*>(&id), 8);
+            mix(serialized.data(), serialized.size());
+        }
+        return hash;
+    }
+
+    // Every property, laid out as the file's properties section.
+    std::string properties_blob() const {
+        std::lock_guard<std::mutex> lock(mutex_);
+        std::string out;
+        uint32_t count = static_cast<uint32_t>(properties_.size());
+        out.append(reinterpret_cast<const char*>(&count), 4);
+        for (const auto& [key, val] : properties_) {
+            uint16_t key_len = static_cast<uint16_t>(key.size());
+            uint32_t val_len = static_cast<uint32_t>(val.size());
+            out.append(reinterpret_cast<const char*>(&key_len), 2);
+            out += key;
+            out.append(reinterpret_cast<const char*>(&val_len), 4);
+            out += val;
+        }
+        return out;
+    }
+
+    // Replace every property with those of a `properties_blob`.
+    void set_properties_blob(const std::string& blob) {
+        std::map<std::string, std::string> properties;
+        size_t at = 0;
+        auto take = [&](void* out, size_t n) {
+            if (blob.size() - at < n) throw std::runtime_error("truncated properties");
+            std::memcpy(out, blob.data() + at, n);
+            at += n;
+        };
+        uint32_t count = 0;
+        take(&count, 4);
+        for (uint32_t i = 0; i < count; ++i) {
+            uint16_t key_len = 0;
+            uint32_t val_len = 0;
+            take(&key_len, 2);
+            std::string key(key_len, '\0');
+            take(key.data(), key_len);
+            take(&val_len, 4);
+            std::string val(val_len, '\0');
+            take(val.data(), val_len);
+            properties[std::move(key)] = std::move(val);
+        }
+        std::lock_guard<std::mutex> lock(mutex_);
+        properties_ = std::move(properties);
+    }
+
+
     // Persist a modality's vectors as int8 + per-vector scale (file format v7):
     // ~4x smaller on disk, dequantized to float32 on load. Takes effect on the
     // next save(). The in-memory index is unchanged. Opt-in; default off.
@@ -1717,6 +2855,8 @@ public:
             throw std::runtime_error(
                 "set_int8_ram('" + modality + "') must be called before any "
                 "vectors are added to that modality");
+        if (binary_store())
+            throw std::runtime_error("set_int8_ram('" + modality + "'): the store holds binary vectors");
         if (max_abs <= 0.0f) max_abs = 1.0f;
         float scale = max_abs / 127.0f;
         int8_ram_scale_[modality] = scale;
@@ -1724,8 +2864,7 @@ public:
         if (it != modality_indices_.end()) {
             size_t dim = it->second.dim;
             auto space = std::make_unique<hnswlib::Int8L2Space>(dim, scale);
-            auto index = std::make_unique<hnswlib::HierarchicalNSW<float>>(
-                space.get(), INITIAL_MAX_ELEMENTS, 16, 200);
+            auto index = make_index(space.get(), INITIAL_MAX_ELEMENTS);
             index->setEf(DEFAULT_EF);
             it->second = {std::move(index), std::move(space), dim, true, scale};
         }
@@ -1735,6 +2874,31 @@ public:
         return int8_ram_scale_.count(modality) > 0;
     }
 
+    // Store every vector as bits and rank by Hamming distance (see
+    // binary_store). Must be called before any vector is added; open()'s
+    // empty "text" index is rebuilt in place. Persisted as a property.
+    void set_binary() {
+        std::lock_guard<std::mutex> lock(mutex_);
+        if (binary_store()) return;
+        if (!int8_ram_scale_.empty())
+            throw std::runtime_error("set_binary: the store has in-RAM int8 modalities");
+        for (const auto& [name, m_idx] : modality_indices_)
+            if (m_idx.index->getCurrentElementCount() > 0)
+                throw std::runtime_error("set_binary must be called before any vectors are added "
+                                         "(modality " + name + " has some)");
+        properties_[METRIC_PROPERTY] = "hamming";
+        for (auto& [name, m_idx] : modality_indices_) {
+            auto space = make_space(m_idx.dim, false, 0.0f, true);
+            auto index = make_index(space.get(), INITIAL_MAX_ELEMENTS);
+            index->setEf(DEFAULT_EF);
+            m_idx = {std::move(index), std::move(space), m_idx.dim, false, 0.0f, true};
+        }
+    }
+    bool is_binary() const {
+        std::lock_guard<std::mutex> lock(mutex_);
+        return binary_store();
+    }
+
     // ─────────────────────────────────────────────────────────────────
     // Persistence & info
     // ─────────────────────────────────────────────────────────────────
@@ -1755,6 +2919,40 @@ public:
         return default_dim_;   // modality not created yet → report the open() default
     }
 
+    // Create the (empty) index of `modality` with dimension `dim` ahead of
+    // its first vector, so every add is checked against it. No-op if it
+    // exists with that dim; throws if it exists with another.
+    void register_modality(const std::string& modality, size_t dim) {
+        std::lock_guard<std::mutex> lock(mutex_);
+        if (dim == 0) throw std::runtime_error("modality " + modality + ": dim must be positive");
+        auto it = modality_indices_.find(modality);
+        if (it != modality_indices_.end()) {
+            if (it->second.dim != dim)
+                throw std::runtime_error("modality " + modality + " already has dim "
+                                         + std::to_string(it->second.dim));
+            return;
+        }
+        get_or_create_index(modality, dim);
+    }
+
+    // Write `modality`'s HNSW graph to `path` as an hnswlib index file
+    // (hnswlib's own layout), the labels being the ids. Forgotten records
+    // stay in it; callers mark them deleted. Throws for an int8 or binary
+    // index, whose vectors hnswlib's float spaces cannot read.
+    void save_hnsw(const std::string& modality, const std::string& path) const {
+        std::lock_guard<std::mutex> lock(mutex_);
+        auto it = modality_indices_.find(modality);
+        if (it == modality_indices_.end()) throw std::runtime_error("no modality " + modality);
+        if (it->second.int8)
+            throw std::runtime_error("modality " + modality + " is stored as int8; hnswlib needs float32 vectors");
+        if (it->second.binary)
+            throw std::runtime_error("modality " + modality + " is stored as bits; hnswlib needs float32 vectors");
+
+        it->second.index->saveIndex(path);
+    }
+
+
+
     size_t size() const {
         std::lock_guard<std::mutex> lock(mutex_);
         return metadata_store_.size();
@@ -1765,7 +2963,44 @@ public:
     // ─────────────────────────────────────────────────────────────────
     // Higher ef = better recall, slower search. Default is DEFAULT_EF (50).
     // Pass modality = "" (default) to apply to all modalities.
+    // Build every index from `seed`, on one thread, from now on. An index
+    // built on several threads is rebuilt so, its vectors in id order; the
+    // others, loaded as saved or built one vector at a time, draw their
+    // next levels from `seed`. The same file, writes and seed then give the
+    // same graphs, and a search the same hits, on every run. Keeps each
+    // index's ef and deletions.
+    void set_seed(uint64_t seed) {
+        std::lock_guard<std::mutex> lock(mutex_);
+        seed_ = seed;
+        for (auto& [name, m_idx] : modality_indices_) {
+            if (!m_idx.shuffled) {
+                m_idx.index->level_generator_.seed(seed);
+                m_idx.index->update_probability_generator_.seed(seed + 1);
+                continue;
+            }
+            size_t n = m_idx.index->cur_element_count;
+            std::vector<std::pair<uint64_t, std::vector<float>>> items;
+            std::vector<uint64_t> deleted;
+            items.reserve(n);
+            for (size_t i = 0; i < n; ++i) {
+                uint64_t id = m_idx.index->getExternalLabel(i);
+                if (m_idx.index->isMarkedDeleted(static_cast<hnswlib::tableint>(i))) deleted.push_back(id);
+                items.push_back({id, read_vector_internal(m_idx, i)});
+            }
+            std::sort(items.begin(), items.end(),
+                      [](const auto& a, const auto& b) { return a.first < b.first; });
+            size_t ef = m_idx.index->ef_;
+            m_idx.index = make_index(m_idx.space.get(), std::max(INITIAL_MAX_ELEMENTS, n));
+            m_idx.index->setEf(ef);
+            m_idx.shuffled = false;
+            parallel_add(m_idx, items);
+            for (uint64_t id : deleted) m_idx.index->markDelete(id);
+
+        }
+    }
+
     void set_ef(size_t ef, const std::string& modality = "") {
+
         std::lock_guard<std::mutex> lock(mutex_);
         if (modality.empty()) {
             for (auto& [_name, mi] : modality_indices_) {
@@ -1786,6 +3021,131 @@ public:
             throw std::runtime_error("unknown modality: " + modality);
         return it->second.index->ef_;
     }
+
+    // ─────────────────────────────────────────────────────────────────
+    // Warm-up: read every page of the indexes and records once
+    // ─────────────────────────────────────────────────────────────────
+    // After a long idle spell the OS may have paged the loaded store out, and
+    // the first searches then fault it back in one page at a time. Reading it
+    // up front pays that in one pass. Returns the bytes read.
+    size_t warm() const {
+        std::lock_guard<std::mutex> lock(mutex_);
+        constexpr size_t page = 4096;
+        unsigned char sink = 0;
+        size_t bytes = 0;
+        auto touch = [&](const char* p, size_t len) {
+            for (size_t off = 0; off < len; off += page) sink ^= static_cast<unsigned char>(p[off]);
+            if (len > 0) sink ^= static_cast<unsigned char>(p[len - 1]);
+            bytes += len;
+        };
+        for (const auto& [_name, mi] : modality_indices_) {
+            const auto& index = *mi.index;
+            size_t n = index.cur_element_count;
+            touch(index.data_level0_memory_, n * index.size_data_per_element_);
+            for (size_t i = 0; i < n; ++i) {
+                if (index.element_levels_[i] > 0)
+                    touch(index.linkLists_[i], index.size_links_per_element_ * index.element_levels_[i]);
+            }
+        }
+        for (const auto& [_id, meta] : metadata_store_) {
+            touch(meta.content.data(), meta.content.size());
+            touch(reinterpret_cast<const char*>(meta.edges.data()), meta.edges.size() * sizeof(Edge));
+            bytes += sizeof(meta);
+        }
+        // keep the reads from being optimized away
+        static std::atomic<unsigned char> observed;
+        observed.store(sink, std::memory_order_relaxed);
+
+        return bytes;
+    }
+
+
+    // ─────────────────────────────────────────────────────────────────
+    // Properties: per-DB key/value settings persisted in the header (v10)
+    // ─────────────────────────────────────────────────────────────────
+    void set_property(const std::string& key, const std::string& value) {
+        std::lock_guard<std::mutex> lock(mutex_);
+        properties_[key] = value;
+    }
+
+    std::optional<std::string> get_property(const std::string& key) const {
+        std::lock_guard<std::mutex> lock(mutex_);
+        auto it = properties_.find(key);
+        if (it == properties_.end()) return std::nullopt;
+        return it->second;
+    }
+
+    bool remove_property(const std::string& key) {
+        std::lock_guard<std::mutex> lock(mutex_);
+        return properties_.erase(key) > 0;
+    }
+
+    // Switch the optional index on `field` ("source" or "timestamp") on or
+    // off. The choice is kept in the "indexes" property, so it persists on
+    // save(); switching on builds the index from the current records.
+    void set_secondary_index(const std::string& field, bool enabled) {
+        if (field != "source" && field != "timestamp")
+            throw std::invalid_argument("no optional index on field '" + field + "'");
+        std::lock_guard<std::mutex> lock(mutex_);
+        (field == "source" ? index_source_ : index_time_) = enabled;
+        std::string list;
+        if (index_source_) list = "source";
+        if (index_time_)   list += list.empty() ? "timestamp" : ",timestamp";
+        if (list.empty()) properties_.erase(INDEXES_PROPERTY);
+        else              properties_[INDEXES_PROPERTY] = list;
+        build_secondary_indexes();
+    }
+
+
+    // ─────────────────────────────────────────────────────────────────
+    // Reprojection: replace every vector v of a modality with M·v + bias
+    // ─────────────────────────────────────────────────────────────────
+    // `matrix` is out_dim × dim (row-major); `bias` is empty or out_dim long. The
+    // modality's index is rebuilt at out_dim (float storage — an int8-RAM
+    // scale no longer fits the projected range), then the DB is checkpointed:
+    // WAL entries logged at the old dimension can't be replayed onto the new
+    // index. Returns the number of vectors reprojected. `progress`, if set,
+    // counts vectors added to the new index.
+    size_t reproject(const std::string& modality, const std::vector<float>& matrix,
+                     const std::vector<float>& bias, size_t out_dim,
+                     const ProgressFn& progress = nullptr) {
+        std::lock_guard<std::mutex> lock(mutex_);
+        auto it = modality_indices_.find(modality);
+        if (it == modality_indices_.end()) return 0;
+        size_t in_dim = it->second.dim;
+        if (out_dim == 0 || matrix.size() != out_dim * in_dim)
+            throw std::runtime_error("reproject: matrix is not out_dim x " + std::to_string(in_dim));
+        if (!bias.empty() && bias.size() != out_dim)
+            throw std::runtime_error("reproject: bias length != " + std::to_string(out_dim));
+
+        auto& old_idx = it->second;
+        size_t n = old_idx.index->cur_element_count;
+        std::vector<std::pair<uint64_t, std::vector<float>>> items;
+        items.reserve(n);
+        for (size_t i = 0; i < n; ++i) {
+            if (old_idx.index->isMarkedDeleted(static_cast<hnswlib::tableint>(i))) continue;
+            std::vector<float> v = read_vector_internal(old_idx, i);
+            std::vector<float> out(out_dim, 0.0f);
+            for (size_t r = 0; r < out_dim; ++r) {
+                const float* row = matrix.data() + r * in_dim;
+                float acc = bias.empty() ? 0.0f : bias[r];
+                for (size_t d = 0; d < in_dim; ++d) acc += row[d] * v[d];
+                out[r] = acc;
+            }
+
+            items.emplace_back(old_idx.index->getExternalLabel(i), std::move(out));
+        }
+
+        modality_indices_.erase(it);
+        int8_ram_scale_.erase(modality);
+        auto& m_idx = get_or_create_index(modality, out_dim);
+        reserve(m_idx, items.size());
+        parallel_add(m_idx, items, progress);
+        save_vectors();
+        return items.size();
+
+    }
+
 };
 
 } // namespace feather
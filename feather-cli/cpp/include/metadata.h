@@ -56,6 +56,10 @@ struct Metadata {
     static Metadata deserialize(std::istream& is);
 };
 
+// Attribute marking an archived record: kept, but left out of searches
+// that ask for that (SearchFilter::exclude_archived).
+inline constexpr const char* ARCHIVED_ATTRIBUTE = "_archived";
+
 struct ContextRecord {
     uint64_t id;
     Metadata metadata;
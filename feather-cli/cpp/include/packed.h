@@ -0,0 +1,209 @@
+#pragma once
+#ifdef FEATHER_ZSTD
+#include <zstd.h>
+#endif
+#include <algorithm>
+#include <cstdint>
+#include <istream>
+#include <ostream>
+#include <stdexcept>
+#include <streambuf>
+#include <string>
+#include <vector>
+
+// Packed sections of a .feather file (format v12): a little-endian u64
+// unpacked length, a u64 packed length, then one zstd frame. Writer and
+// Reader are stream buffers that pack and unpack a chunk at a time, so a
+// section is never held in memory whole, however large the file.
+//
+// Built without FEATHER_ZSTD (the Rust crate's `zstd` feature, off by
+// default), there is no codec: stores are saved unpacked, and a packed one
+// fails to open with a message saying why.
+
+namespace feather {
+namespace packed {
+
+// Why a build without the codec cannot pack or unpack.
+constexpr const char* UNAVAILABLE =
+    "this build has no zstd to pack or unpack .feather files with (rebuild with the `zstd` feature)";
+
+#ifdef FEATHER_ZSTD
+
+// Whether this build can pack and unpack sections at all.
+constexpr bool AVAILABLE = true;
+
+constexpr int LEVEL = ZSTD_CLEVEL_DEFAULT;
+
+// zstd's densest block, RLE, takes 4 bytes for 128 KiB.
+constexpr uint64_t MAX_RATIO = 32768;
+
+// Packs what is written to it into `out`, from where it stands. The
+// lengths ahead of the frame are filled in by finish(), so `out` must be
+// seekable (a file).
+class Writer : public std::streambuf {
+public:
+    explicit Writer(std::ostream& out)
+        : out_(out), cctx_(ZSTD_createCCtx()), raw_(ZSTD_CStreamInSize()), packed_(ZSTD_CStreamOutSize()) {
+        if (!cctx_) throw std::runtime_error("zstd: cannot allocate a compression context");
+        ZSTD_CCtx_setParameter(cctx_, ZSTD_c_compressionLevel, LEVEL);
+        start_ = out_.tellp();
+        uint64_t lengths[2] = {0, 0};
+        out_.write((char*)lengths, sizeof lengths);
+        setp(raw_.data(), raw_.data() + raw_.size());
+    }
+
+    ~Writer() override { ZSTD_freeCCtx(cctx_); }
+
+    Writer(const Writer&) = delete;
+    Writer& operator=(const Writer&) = delete;
+
+    // End the frame and fill in the lengths, leaving `out` past the section.
+    void finish() {
+        pack(ZSTD_e_end);
+        std::streampos end = out_.tellp();
+        uint64_t lengths[2] = {raw_len_, static_cast<uint64_t>(end - start_) - sizeof lengths};
+        out_.seekp(start_);
+        out_.write((char*)lengths, sizeof lengths);
+        out_.seekp(end);
+        if (!error_.empty()) throw std::runtime_error("cannot pack a section: " + error_);
+        if (!out_) throw std::runtime_error("cannot write a packed section");
+    }
+
+protected:
+    int_type overflow(int_type c) override {
+        if (!pack(ZSTD_e_continue)) return traits_type::eof();
+        if (!traits_type::eq_int_type(c, traits_type::eof())) {
+            *pptr() = traits_type::to_char_type(c);
+            pbump(1);
+        }
+        return traits_type::not_eof(c);
+    }
+
+private:
+    // Pack what is buffered; ZSTD_e_end also ends the frame. Errors are kept
+    // for finish(): a stream buffer that throws only sets the stream's badbit.
+    bool pack(ZSTD_EndDirective mode) {
+        if (!error_.empty()) return false;
+        ZSTD_inBuffer in{pbase(), static_cast<size_t>(pptr() - pbase()), 0};
+        raw_len_ += in.size;
+        for (;;) {
+            ZSTD_outBuffer out{packed_.data(), packed_.size(), 0};
+            size_t left = ZSTD_compressStream2(cctx_, &out, &in, mode);
+            if (ZSTD_isError(left)) {
+                error_ = ZSTD_getErrorName(left);
+                return false;
+            }
+            out_.write(packed_.data(), out.pos);
+            if (mode == ZSTD_e_end ? left == 0 : in.pos == in.size) break;
+        }
+        setp(raw_.data(), raw_.data() + raw_.size());
+        return true;
+    }
+
+    std::ostream& out_;
+    ZSTD_CCtx* cctx_;
+    std::vector<char> raw_, packed_;
+    std::streampos start_;
+    uint64_t raw_len_ = 0;
+    std::string error_;
+};
+
+// Unpacks the section `in` stands at, reading no further than its end.
+class Reader : public std::streambuf {
+public:
+    explicit Reader(std::istream& in)
+        : in_(in), dctx_(ZSTD_createDCtx()), packed_(ZSTD_DStreamInSize()), raw_(ZSTD_DStreamOutSize()) {
+        if (!dctx_) throw std::runtime_error("zstd: cannot allocate a decompression context");
+        in_.read((char*)&raw_len_, 8);
+        in_.read((char*)&packed_len_, 8);
+        std::streampos cur = in_.tellg();
+        in_.seekg(0, std::ios::end);
+        std::streamoff remaining = (in_.tellg() >= cur) ? (in_.tellg() - cur) : -1;
+        in_.seekg(cur);
+        if (!in_ || remaining < 0 || packed_len_ > (uint64_t)remaining || raw_len_ / MAX_RATIO > packed_len_)
+            throw std::runtime_error("corrupt .feather: implausible compressed section size");
+        end_ = cur + static_cast<std::streamoff>(packed_len_);
+        setg(raw_.data(), raw_.data(), raw_.data());
+    }
+
+    ~Reader() override { ZSTD_freeDCtx(dctx_); }
+
+    Reader(const Reader&) = delete;
+    Reader& operator=(const Reader&) = delete;
+
+    // Check the section was read to its end, and leave `in` past it.
+    void finish() {
+        if (error_.empty() && (gptr() != egptr() || underflow() != traits_type::eof()))
+            error_ = "unread bytes after its end";
+        if (error_.empty() && (unpacked_ != raw_len_ || !frame_done_))
+            error_ = "it ends early";
+        if (!error_.empty()) throw std::runtime_error("corrupt .feather: packed section: " + error_);
+        in_.seekg(end_);
+    }
+
+protected:
+    int_type underflow() override {
+        if (gptr() < egptr()) return traits_type::to_int_type(*gptr());
+        while (error_.empty()) {
+            if (in_buf_.pos == in_buf_.size) {
+                if (consumed_ == packed_len_) break;
+                size_t n = static_cast<size_t>(std::min<uint64_t>(packed_len_ - consumed_, packed_.size()));
+                in_.read(packed_.data(), n);
+                if (static_cast<size_t>(in_.gcount()) != n) {
+                    error_ = "the file ends inside it";
+                    break;
+                }
+                consumed_ += n;
+                in_buf_ = ZSTD_inBuffer{packed_.data(), n, 0};
+            }
+            ZSTD_outBuffer out{raw_.data(), raw_.size(), 0};
+            size_t left = ZSTD_decompressStream(dctx_, &out, &in_buf_);
+            if (ZSTD_isError(left)) {
+                error_ = ZSTD_getErrorName(left);
+                break;
+            }
+            frame_done_ = left == 0;
+            unpacked_ += out.pos;
+            if (unpacked_ > raw_len_) {
+                error_ = "it unpacks past its length";
+                break;
+            }
+            if (out.pos > 0) {
+                setg(raw_.data(), raw_.data(), raw_.data() + out.pos);
+                return traits_type::to_int_type(*gptr());
+            }
+        }
+        return traits_type::eof();
+    }
+
+private:
+    std::istream& in_;
+    ZSTD_DCtx* dctx_;
+    std::vector<char> packed_, raw_;
+    ZSTD_inBuffer in_buf_{nullptr, 0, 0};
+    uint64_t raw_len_ = 0, packed_len_ = 0, consumed_ = 0, unpacked_ = 0;
+    std::streampos end_;
+    bool frame_done_ = false;
+    std::string error_;
+};
+
+#else
+
+constexpr bool AVAILABLE = false;
+
+class Writer : public std::streambuf {
+public:
+    explicit Writer(std::ostream&) { throw std::runtime_error(UNAVAILABLE); }
+    void finish() {}
+};
+
+class Reader : public std::streambuf {
+public:
+    explicit Reader(std::istream&) { throw std::runtime_error(UNAVAILABLE); }
+    void finish() {}
+};
+
+#endif
+
+} // namespace packed
+} // namespace feather
@@ -1,15 +1,179 @@
 #include "../include/feather.h"
 #include <vector>
 #include <memory>
+#include <cstring>
+
+// Last error raised by a fallible wrapper on this thread (empty if none).
+// Exceptions must not cross the C ABI, so wrappers catch and record here.
+static thread_local std::string g_last_error;
+
+// C view of feather::Metadata. Strings are NUL-terminated; attributes and
+// edges are parallel arrays. Mirrored by `RawMetadata` in feather-cli/src/metadata.rs.
+struct FeatherMetadata {
+    int64_t     timestamp;
+    float       importance;
+    uint8_t     context_type;
+    uint32_t    recall_count;
+    uint64_t    last_recalled_at;
+    int64_t     ttl;
+    float       confidence;
+    const char* source;
+    const char* content;
+    const char* tags_json;
+    const char* namespace_id;
+    const char* entity_id;
+    const char* const* attr_keys;
+    const char* const* attr_values;
+    size_t      attr_count;
+    const uint64_t*    edge_targets;
+    const char* const* edge_types;
+    const float*       edge_weights;
+    size_t      edge_count;
+};
+
+// Owns the storage a FeatherMetadata handed to the caller points into.
+// `view` is the first member so the public pointer casts back to the holder.
+struct MetadataHolder {
+    FeatherMetadata          view;
+    feather::Metadata        meta;
+    std::vector<const char*> keys, values, types;
+    std::vector<uint64_t>    targets;
+    std::vector<float>       weights;
+};
+
+static feather::Metadata from_c(const FeatherMetadata* m) {
+    feather::Metadata meta;
+    meta.timestamp        = m->timestamp;
+    meta.importance       = m->importance;
+    meta.type             = static_cast<feather::ContextType>(m->context_type);
+    meta.recall_count     = m->recall_count;
+    meta.last_recalled_at = m->last_recalled_at;
+    meta.ttl              = m->ttl;
+    meta.confidence       = m->confidence;
+    if (m->source)       meta.source       = m->source;
+    if (m->content)      meta.content      = m->content;
+    if (m->tags_json)    meta.tags_json    = m->tags_json;
+    if (m->namespace_id) meta.namespace_id = m->namespace_id;
+    if (m->entity_id)    meta.entity_id    = m->entity_id;
+    for (size_t i = 0; i < m->attr_count; ++i)
+        meta.attributes[m->attr_keys[i]] = m->attr_values[i];
+    for (size_t i = 0; i < m->edge_count; ++i)
+        meta.edges.emplace_back(m->edge_targets[i], m->edge_types[i], m->edge_weights[i]);
+    return meta;
+}
+
+// The filter a search's index-side terms make, and whether there are any.
+static bool prefilter(feather::SearchFilter& filter, const int64_t* time_range, const char* source,
+                      const char* const* attributes, size_t attribute_count, int include_archived) {
+    if (time_range) {
+        filter.timestamp_after = time_range[0];
+        filter.timestamp_before = time_range[1];
+    }
+    if (source) filter.source = source;
+    if (attribute_count > 0) {
+        filter.attributes_match.emplace();
+        for (size_t i = 0; i < attribute_count; ++i)
+            (*filter.attributes_match)[attributes[2 * i]] = attributes[2 * i + 1];
+    }
+    filter.exclude_archived = !include_archived;
+    return time_range || source || attribute_count > 0 || filter.exclude_archived;
+}
+
+// Progress callback of long operations: `ctx` as passed in, then units done
+// and the total. Mirrored by `ProgressCb` in feather-cli/src/progress.rs.
+typedef void (*feather_progress_cb)(void* ctx, size_t done, size_t total);
+
+static feather::ProgressFn progress_fn(feather_progress_cb cb, void* ctx) {
+    if (!cb) return nullptr;
+    return [cb, ctx](size_t done, size_t total) { cb(ctx, done, total); };
+}
 
 extern "C" {
+    const char* feather_last_error() {
+        return g_last_error.c_str();
+    }
+
     void* feather_open(const char* path, size_t dim) {
         try {
             auto db = feather::DB::open(path, dim);
             return new std::unique_ptr<feather::DB>(std::move(db));
+        } catch (const std::exception& e) {
+            g_last_error = e.what();
+            return nullptr;
+        } catch (...) {
+            g_last_error = "unknown error";
+            return nullptr;
+        }
+    }
+
+    void* feather_open_in_memory(size_t dim) {
+        try {
+            return new std::unique_ptr<feather::DB>(feather::DB::in_memory(dim));
         } catch (...) { return nullptr; }
     }
 
+    // Write the store to `path` and make it the backing file. Returns 0, or
+    // -1 (see feather_last_error).
+    int feather_persist_to(void* db_ptr, const char* path) {
+        if (!db_ptr || !path) return -1;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        try {
+            db->persist_to(path);
+            return 0;
+        } catch (const std::exception& e) {
+            g_last_error = e.what();
+            return -1;
+        }
+    }
+
+    int feather_is_in_memory(void* db_ptr) {
+        if (!db_ptr) return 0;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        return db->is_in_memory() ? 1 : 0;
+    }
+
+    // How save() packs the file: 0 nothing, 1 the metadata section, 2 all
+    // of it. -1 (see feather_last_error) on an unknown value.
+    int feather_set_compression(void* db_ptr, int compression) {
+        if (!db_ptr) return -1;
+        if (compression < 0 || compression > static_cast<int>(feather::Compression::All)) {
+            g_last_error = "unknown compression " + std::to_string(compression);
+            return -1;
+        }
+        if (compression != 0 && !feather::packed::AVAILABLE) {
+            g_last_error = feather::packed::UNAVAILABLE;
+            return -1;
+        }
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        db->set_compression(static_cast<feather::Compression>(compression));
+        return 0;
+    }
+
+    int feather_compression(void* db_ptr) {
+        if (!db_ptr) return 0;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        return static_cast<int>(db->compression());
+    }
+
+
+    // The backing file path, copied into up to `cap` bytes (not
+    // NUL-terminated). Returns its length; 0 for an in-memory store.
+    size_t feather_path(void* db_ptr, char* out, size_t cap) {
+        if (!db_ptr) return 0;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        const auto& path = db->path();
+        if (out) std::memcpy(out, path.data(), std::min(cap, path.size()));
+        return path.size();
+    }
+
+    // Make the store read-only in effect: it forgets its file and WAL, so
+    // neither later writes nor close/save touch the disk.
+    void feather_detach(void* db_ptr) {
+        if (!db_ptr) return;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        db->detach();
+    }
+
     void feather_add(void* db_ptr, uint64_t id, const float* vec, size_t len) {
         if (!db_ptr) return;
         auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
@@ -39,6 +203,22 @@ extern "C" {
         db->link(from_id, to_id);
     }
 
+    // Typed, weighted edge; a no-op if `from_id` already has an edge of this
+    // type to `to_id`.
+    void feather_link_typed(void* db_ptr, uint64_t from_id, uint64_t to_id,
+                            const char* rel_type, float weight) {
+        if (!db_ptr || !rel_type) return;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        db->link(from_id, to_id, rel_type, weight);
+    }
+
+    // rel_type NULL removes edges of every type.
+    size_t feather_unlink(void* db_ptr, uint64_t from_id, uint64_t to_id, const char* rel_type) {
+        if (!db_ptr) return 0;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        return db->unlink(from_id, to_id, rel_type ? rel_type : "");
+    }
+
     void feather_touch(void* db_ptr, uint64_t id) {
         if (!db_ptr) return;
         auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
@@ -50,7 +230,10 @@ extern "C" {
         if (!db_ptr) return;
         auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
         std::string mod = modality ? modality : "text";
-        auto results = db->search(std::vector<float>(query, query + len), k, nullptr, nullptr, mod);
+        feather::SearchFilter unarchived;
+        unarchived.exclude_archived = true;
+        auto results = db->search(std::vector<float>(query, query + len), k,
+                                  db->has_archived() ? &unarchived : nullptr, nullptr, mod);
         for (size_t i = 0; i < results.size() && i < k; ++i) {
             out_ids[i] = results[i].id;
             out_dists[i] = results[i].score;
@@ -64,6 +247,7 @@ extern "C" {
         auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
         
         feather::SearchFilter filter;
+        filter.exclude_archived = true;
         if (type_filter != 255) { // 255 = no filter
             filter.types = std::vector<feather::ContextType>{static_cast<feather::ContextType>(type_filter)};
         }
@@ -107,4 +291,582 @@ extern "C" {
         auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
         return db->forget_expired();
     }
+
+    // Phase 7: rebuild indices without soft-deleted records. `cb` may be null.
+    size_t feather_compact(void* db_ptr, feather_progress_cb cb, void* ctx) {
+        if (!db_ptr) return 0;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        return db->compact(progress_fn(cb, ctx));
+    }
+
+    // Largest share of soft-deleted elements in any modality index.
+    float feather_dead_share(void* db_ptr) {
+        if (!db_ptr) return 0.0f;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        return db->dead_share();
+    }
+
+    size_t feather_dim(void* db_ptr, const char* modality) {
+        if (!db_ptr) return 0;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        return db->dim(modality ? modality : "text");
+    }
+
+    // Returns 0, or -1 if the modality exists with another dim (see
+    // feather_last_error).
+    int feather_save_hnsw(void* db_ptr, const char* modality, const char* path) {
+        if (!db_ptr || !modality || !path) return -1;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        try {
+            db->save_hnsw(modality, path);
+            return 0;
+        } catch (const std::exception& e) {
+            g_last_error = e.what();
+            return -1;
+        }
+    }
+
+    int feather_register_modality(void* db_ptr, const char* modality, size_t dim) {
+
+        if (!db_ptr || !modality) return -1;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        try {
+            db->register_modality(modality, dim);
+            return 0;
+        } catch (const std::exception& e) {
+            g_last_error = e.what();
+            return -1;
+        }
+    }
+
+
+    // Fills up to `cap` ids and returns the total count, so callers can size
+    // the buffer with a first call passing cap = 0.
+    size_t feather_get_all_ids(void* db_ptr, const char* modality, uint64_t* out, size_t cap) {
+        if (!db_ptr) return 0;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        auto ids = db->get_all_ids(modality ? modality : "text");
+        for (size_t i = 0; i < ids.size() && i < cap; ++i) out[i] = ids[i];
+        return ids.size();
+    }
+
+    // Copies up to `cap` floats and returns the vector length (0 if absent).
+    size_t feather_get_vector(void* db_ptr, uint64_t id, const char* modality,
+                              float* out, size_t cap) {
+        if (!db_ptr) return 0;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        auto vec = db->get_vector(id, modality ? modality : "text");
+        for (size_t i = 0; i < vec.size() && i < cap; ++i) out[i] = vec[i];
+        return vec.size();
+    }
+
+    // Properties (file format v10)
+    void feather_set_property(void* db_ptr, const char* key, const char* val, size_t len) {
+        if (!db_ptr || !key) return;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        db->set_property(key, std::string(val ? val : "", val ? len : 0));
+    }
+
+    // Copies up to `cap` bytes; returns the value length, or -1 if unset.
+    int64_t feather_get_property(void* db_ptr, const char* key, char* out, size_t cap) {
+        if (!db_ptr || !key) return -1;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        auto val = db->get_property(key);
+        if (!val) return -1;
+        if (out) std::memcpy(out, val->data(), std::min(cap, val->size()));
+        return static_cast<int64_t>(val->size());
+    }
+
+    int feather_remove_property(void* db_ptr, const char* key) {
+        if (!db_ptr || !key) return 0;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        return db->remove_property(key) ? 1 : 0;
+    }
+
+    // Returns the number of vectors reprojected, or -1 (see feather_last_error).
+    // `cb` may be null.
+    int64_t feather_reproject(void* db_ptr, const char* modality, const float* matrix,
+                              const float* bias, size_t in_dim, size_t out_dim,
+                              feather_progress_cb cb, void* ctx) {
+        if (!db_ptr || !matrix) return -1;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        try {
+            std::vector<float> m(matrix, matrix + in_dim * out_dim);
+            std::vector<float> b = bias ? std::vector<float>(bias, bias + out_dim)
+                                        : std::vector<float>();
+            return static_cast<int64_t>(db->reproject(modality ? modality : "text", m, b, out_dim,
+                                                      progress_fn(cb, ctx)));
+
+        } catch (const std::exception& e) {
+            g_last_error = e.what();
+            return -1;
+        }
+    }
+
+    // Raw kNN without scoring/touching. Returns the hit count, or -1 on error.
+    // `time_range` (nullable) is an inclusive [after, before] timestamp window;
+    // `source` (nullable) an exact source to match; `attributes` holds
+    // `attribute_count` key, value pairs, flattened, that must all match.
+    // Archived records are left out unless `include_archived`. With `exact`,
+    // every record is measured rather than the HNSW graph traversed. A
+    // nonzero `truncate_dim` measures every record on that many leading
+    // components and re-measures the `shortlist` nearest in full.
+    int64_t feather_knn(void* db_ptr, const float* query, size_t len, size_t k,
+                        const char* modality, const int64_t* time_range, const char* source,
+                        const char* const* attributes, size_t attribute_count, int include_archived,
+                        int exact, size_t truncate_dim, size_t shortlist, int post_filter,
+                        uint64_t* out_ids, float* out_dists) {
+        if (!db_ptr) return -1;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        try {
+            feather::SearchFilter filter;
+            // with no archived records there are none to leave out
+            bool filtered = prefilter(filter, time_range, source, attributes, attribute_count,
+                                      include_archived || !db->has_archived());
+            auto hits = db->knn(std::vector<float>(query, query + len), k,
+                                modality ? modality : "text", filtered ? &filter : nullptr, exact != 0,
+                                truncate_dim, shortlist, post_filter != 0);
+            for (size_t i = 0; i < hits.size() && i < k; ++i) {
+                out_ids[i] = hits[i].first;
+                out_dists[i] = hits[i].second;
+            }
+            return static_cast<int64_t>(std::min(hits.size(), k));
+        } catch (const std::exception& e) {
+            g_last_error = e.what();
+            return -1;
+        }
+    }
+
+    // Statistics of a search filter over `modality` for the planner: writes
+    // [vectors, indexed, index rows, sampled] to `out_stats` and up to `cap`
+    // of the sampled ids the filter accepts to `out_ids`. Returns how many
+    // ids, or -1 on error.
+    int64_t feather_filter_stats(void* db_ptr, const char* modality, const int64_t* time_range,
+                                 const char* source, const char* const* attributes, size_t attribute_count,
+                                 int include_archived, size_t sample, uint64_t* out_stats,
+                                 uint64_t* out_ids, size_t cap) {
+        if (!db_ptr) return -1;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        try {
+            feather::SearchFilter filter;
+            prefilter(filter, time_range, source, attributes, attribute_count, include_archived);
+            auto stats = db->filter_stats(modality ? modality : "text", filter, sample);
+            out_stats[0] = stats.vectors;
+            out_stats[1] = stats.indexed ? 1 : 0;
+            out_stats[2] = stats.index_rows;
+            out_stats[3] = stats.sampled;
+            size_t n = std::min(stats.matched.size(), cap);
+            std::copy(stats.matched.begin(), stats.matched.begin() + n, out_ids);
+            return static_cast<int64_t>(n);
+        } catch (const std::exception& e) {
+            g_last_error = e.what();
+            return -1;
+        }
+    }
+
+    // BM25 ranking of `query` over record content, without touching; archived
+
+    // records only with `include_archived`. Returns the hit count, or -1 on
+    // error.
+    int64_t feather_bm25(void* db_ptr, const char* query, size_t k, int include_archived,
+                         uint64_t* out_ids, float* out_scores) {
+        if (!db_ptr || !query) return -1;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        try {
+            auto hits = db->bm25(query, k, include_archived == 0);
+            for (size_t i = 0; i < hits.size() && i < k; ++i) {
+                out_ids[i] = hits[i].first;
+                out_scores[i] = hits[i].second;
+            }
+            return static_cast<int64_t>(std::min(hits.size(), k));
+        } catch (const std::exception& e) {
+            g_last_error = e.what();
+            return -1;
+        }
+    }
+
+    // Switch the optional index on `field` ("source" / "timestamp") on or off.
+    // Returns 0, or -1 on an unknown field.
+    int feather_set_index(void* db_ptr, const char* field, int enabled) {
+        if (!db_ptr || !field) return -1;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        try {
+            db->set_secondary_index(field, enabled != 0);
+            return 0;
+        } catch (const std::exception& e) {
+            g_last_error = e.what();
+            return -1;
+        }
+    }
+
+    // Read every page of the loaded indexes and records once (see
+    // DB::warm). Returns the bytes read, or -1 (see feather_last_error).
+    int64_t feather_warm(void* db_ptr) {
+        if (!db_ptr) return -1;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        try {
+            return static_cast<int64_t>(db->warm());
+        } catch (const std::exception& e) {
+            g_last_error = e.what();
+            return -1;
+        }
+    }
+
+    // HNSW search beam width (ef) of `modality`, or of every modality if it
+    // is NULL or empty. Not persisted. Returns 0, or -1 (see feather_last_error).
+
+    int feather_set_ef(void* db_ptr, size_t ef, const char* modality) {
+        if (!db_ptr) return -1;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        try {
+            db->set_ef(ef, modality ? modality : "");
+            return 0;
+        } catch (const std::exception& e) {
+            g_last_error = e.what();
+            return -1;
+        }
+    }
+
+    // Build every index from `seed`, on one thread, rebuilding the loaded
+    // ones. Not persisted. Returns 0, or -1 (see feather_last_error).
+    int feather_set_seed(void* db_ptr, uint64_t seed) {
+        if (!db_ptr) return -1;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        try {
+            db->set_seed(seed);
+            return 0;
+        } catch (const std::exception& e) {
+            g_last_error = e.what();
+            return -1;
+        }
+    }
+
+    // Store vectors as bits, ranked by Hamming distance; before any vector
+    // is added. 0, or -1 (see feather_last_error).
+    int feather_set_binary(void* db_ptr) {
+        if (!db_ptr) return -1;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        try {
+            db->set_binary();
+            return 0;
+        } catch (const std::exception& e) {
+            g_last_error = e.what();
+            return -1;
+        }
+    }
+
+
+    // Transactions: WAL entries between begin and commit are written as one,
+
+    // applied whole or not at all on replay. begin/commit return 0, or -1
+    // (see feather_last_error); rollback drops the held entries.
+    int feather_begin(void* db_ptr) {
+        if (!db_ptr) return -1;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        try {
+            db->begin_txn();
+            return 0;
+        } catch (const std::exception& e) {
+            g_last_error = e.what();
+            return -1;
+        }
+    }
+
+    int feather_commit(void* db_ptr) {
+        if (!db_ptr) return -1;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        try {
+            db->commit_txn();
+            return 0;
+        } catch (const std::exception& e) {
+            g_last_error = e.what();
+            return -1;
+        }
+    }
+
+    void feather_rollback(void* db_ptr) {
+        if (!db_ptr) return;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        db->rollback_txn();
+    }
+
+    // Apply `len` bytes of WAL entries shipped from a primary store.
+    int feather_apply_wal(void* db_ptr, const char* data, size_t len) {
+        if (!db_ptr || (!data && len > 0)) return -1;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        try {
+            db->apply_wal(std::string(data, len));
+            return 0;
+        } catch (const std::exception& e) {
+            g_last_error = e.what();
+            return -1;
+        }
+    }
+
+    // Append the WAL entries that turn `base_ptr` into `db_ptr` to the file
+    // at `path`. Returns how many, or -1 with the reason in
+    // feather_last_error.
+    int64_t feather_delta(void* base_ptr, void* db_ptr, const char* path) {
+        if (!base_ptr || !db_ptr || !path) return -1;
+        auto& base = *static_cast<std::unique_ptr<feather::DB>*>(base_ptr);
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        try {
+            auto [entries, count] = db->delta_from(*base);
+            std::ofstream out(path, std::ios::binary | std::ios::app);
+            out.write(entries.data(), entries.size());
+            out.flush();
+            if (!out) throw std::runtime_error(std::string("cannot write ") + path);
+            return static_cast<int64_t>(count);
+        } catch (const std::exception& e) {
+            g_last_error = e.what();
+            return -1;
+        }
+    }
+
+    uint64_t feather_digest(void* db_ptr) {
+        if (!db_ptr) return 0;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        return db->digest();
+    }
+
+    // Copies up to `cap` bytes of the properties blob into `out`; returns
+    // its full length.
+    size_t feather_properties(void* db_ptr, char* out, size_t cap) {
+        if (!db_ptr) return 0;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        std::string blob = db->properties_blob();
+        if (out && cap > 0) std::memcpy(out, blob.data(), std::min(cap, blob.size()));
+        return blob.size();
+    }
+
+    int feather_set_properties(void* db_ptr, const char* data, size_t len) {
+        if (!db_ptr || (!data && len > 0)) return -1;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        try {
+            db->set_properties_blob(std::string(data, len));
+            return 0;
+        } catch (const std::exception& e) {
+            g_last_error = e.what();
+            return -1;
+        }
+    }
+
+
+
+    // Sparse vectors (file format v11). Sets (nnz = 0: removes) the sparse
+
+
+    // vector of `id` under `name`. Returns 1, or 0 if the id is unknown.
+    int feather_set_sparse(void* db_ptr, uint64_t id, const char* name,
+                           const uint32_t* dims, const float* weights, size_t nnz) {
+        if (!db_ptr || !name) return 0;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        feather::SparseVector v;
+        v.reserve(nnz);
+        for (size_t i = 0; i < nnz; ++i) v.push_back({dims[i], weights[i]});
+        return db->set_sparse(id, name, v) ? 1 : 0;
+    }
+
+    // Copies up to `cap` pairs and returns the vector's length (0 if absent).
+    size_t feather_get_sparse(void* db_ptr, uint64_t id, const char* name,
+                              uint32_t* out_dims, float* out_weights, size_t cap) {
+        if (!db_ptr || !name) return 0;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        auto v = db->get_sparse(id, name);
+        for (size_t i = 0; i < v.size() && i < cap; ++i) {
+            out_dims[i] = v[i].first;
+            out_weights[i] = v[i].second;
+        }
+        return v.size();
+    }
+
+    // Dot-product ranking of the sparse vectors under `name`, without
+    // touching; archived records only with `include_archived`. Returns the
+    // hit count, or -1 on error.
+    int64_t feather_sparse_search(void* db_ptr, const char* name, const uint32_t* dims,
+                                  const float* weights, size_t nnz, size_t k, int include_archived,
+                                  uint64_t* out_ids, float* out_scores) {
+        if (!db_ptr || !name) return -1;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        try {
+            feather::SparseVector query;
+            query.reserve(nnz);
+            for (size_t i = 0; i < nnz; ++i) query.push_back({dims[i], weights[i]});
+            auto hits = db->sparse_search(name, query, k, include_archived == 0);
+            for (size_t i = 0; i < hits.size() && i < k; ++i) {
+                out_ids[i] = hits[i].first;
+                out_scores[i] = hits[i].second;
+            }
+            return static_cast<int64_t>(std::min(hits.size(), k));
+        } catch (const std::exception& e) {
+            g_last_error = e.what();
+            return -1;
+        }
+    }
+
+    // NUL-separated sparse vector set names; same contract as
+    // feather_modality_names.
+    size_t feather_sparse_names(void* db_ptr, char* out, size_t cap) {
+        if (!db_ptr) return 0;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        std::string packed;
+        for (const auto& name : db->sparse_names()) {
+            packed += name;
+            packed.push_back('\0');
+        }
+        if (out) std::memcpy(out, packed.data(), std::min(cap, packed.size()));
+        return packed.size();
+    }
+
+    // Ids of the records holding an edge to `id` (from the reverse index),
+    // each once. Fills up to `cap` and returns the total count.
+    size_t feather_get_incoming(void* db_ptr, uint64_t id, uint64_t* out, size_t cap) {
+        if (!db_ptr) return 0;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        std::vector<uint64_t> sources;
+        for (const auto& e : db->get_incoming(id)) sources.push_back(e.source_id);
+        std::sort(sources.begin(), sources.end());
+        sources.erase(std::unique(sources.begin(), sources.end()), sources.end());
+        for (size_t i = 0; i < sources.size() && i < cap; ++i) out[i] = sources[i];
+        return sources.size();
+    }
+
+    // Set one attribute on an existing record. Returns 0 if the id is unknown.
+    int feather_set_attribute(void* db_ptr, uint64_t id, const char* key, const char* value) {
+        if (!db_ptr || !key || !value) return 0;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        auto meta = db->get_metadata(id);
+        if (!meta) return 0;
+        meta->attributes[key] = value;
+        db->update_metadata(id, *meta);
+        return 1;
+    }
+
+    // Full metadata for `id`, or NULL if unknown. Free with feather_metadata_free.
+    const FeatherMetadata* feather_get_metadata(void* db_ptr, uint64_t id) {
+        if (!db_ptr) return nullptr;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        auto meta = db->get_metadata(id);
+        if (!meta) return nullptr;
+        auto* h = new MetadataHolder();
+        h->meta = std::move(*meta);
+        const auto& m = h->meta;
+        for (const auto& [k, v] : m.attributes) {
+            h->keys.push_back(k.c_str());
+            h->values.push_back(v.c_str());
+        }
+        for (const auto& e : m.edges) {
+            h->targets.push_back(e.target_id);
+            h->types.push_back(e.rel_type.c_str());
+            h->weights.push_back(e.weight);
+        }
+        h->view = FeatherMetadata{
+            m.timestamp, m.importance, static_cast<uint8_t>(m.type),
+            m.recall_count, m.last_recalled_at, m.ttl, m.confidence,
+            m.source.c_str(), m.content.c_str(), m.tags_json.c_str(),
+            m.namespace_id.c_str(), m.entity_id.c_str(),
+            h->keys.data(), h->values.data(), h->keys.size(),
+            h->targets.data(), h->types.data(), h->weights.data(), h->targets.size(),
+        };
+        return &h->view;
+    }
+
+    void feather_metadata_free(const FeatherMetadata* meta) {
+        delete reinterpret_cast<const MetadataHolder*>(meta);
+    }
+
+    // Replace a record's metadata (edges included) without touching vectors.
+    void feather_put_metadata(void* db_ptr, uint64_t id, const FeatherMetadata* meta) {
+        if (!db_ptr || !meta) return;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        db->update_metadata(id, from_c(meta));
+    }
+
+    // add() with a full metadata record. Returns 0, or -1 (see feather_last_error).
+    int feather_add_with_metadata(void* db_ptr, uint64_t id, const float* vec, size_t len,
+                                  const FeatherMetadata* meta, const char* modality) {
+        if (!db_ptr || !meta) return -1;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        try {
+            db->add(id, std::vector<float>(vec, vec + len), from_c(meta),
+                    modality ? modality : "text");
+            return 0;
+        } catch (const std::exception& e) {
+            g_last_error = e.what();
+            return -1;
+        }
+    }
+
+    // add_batch(): `n` records of `dim` floats each, laid out row-major in
+    // `vecs`, with one metadata pointer per record. The HNSW graph is built in
+    // parallel. Returns 0, or -1 (see feather_last_error).
+    int feather_add_batch(void* db_ptr, const uint64_t* ids, const float* vecs, size_t n, size_t dim,
+                          const FeatherMetadata* const* metas, const char* modality) {
+        if (!db_ptr || (n > 0 && (!ids || !vecs || !metas))) return -1;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        try {
+            std::vector<uint64_t> id_vec(ids, ids + n);
+            std::vector<std::vector<float>> vv;
+            std::vector<feather::Metadata> mv;
+            vv.reserve(n);
+            mv.reserve(n);
+            for (size_t i = 0; i < n; ++i) {
+                vv.emplace_back(vecs + i * dim, vecs + (i + 1) * dim);
+                mv.push_back(from_c(metas[i]));
+            }
+            db->add_batch(id_vec, vv, mv, modality ? modality : "text");
+            return 0;
+        } catch (const std::exception& e) {
+            g_last_error = e.what();
+            return -1;
+        }
+    }
+
+    // Every id with metadata, across all modalities. Same sizing protocol
+    // as feather_get_all_ids.
+    size_t feather_all_ids(void* db_ptr, uint64_t* out, size_t cap) {
+        if (!db_ptr) return 0;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        auto ids = db->all_ids();
+        for (size_t i = 0; i < ids.size() && i < cap; ++i) out[i] = ids[i];
+        return ids.size();
+    }
+
+    // Give those of the `n` ids whose source is `from` the source `to`.
+    // Writes the renamed ids to `out` (room for `n` suffices); returns how
+    // many were renamed.
+    size_t feather_rename_source(void* db_ptr, const uint64_t* ids, size_t n, const char* from, const char* to,
+                                 uint64_t* out, size_t cap) {
+        if (!db_ptr || !from || !to) return 0;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        auto renamed = db->rename_source(std::vector<uint64_t>(ids, ids + n), from, to);
+        for (size_t i = 0; i < renamed.size() && i < cap; ++i) out[i] = renamed[i];
+        return renamed.size();
+    }
+
+    // Ids of the live records whose attribute `key` is `value`, from the
+
+    // attribute index. Same sizing protocol as feather_get_all_ids.
+    size_t feather_ids_with_attribute(void* db_ptr, const char* key, const char* value, uint64_t* out, size_t cap) {
+        if (!db_ptr || !key || !value) return 0;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        auto ids = db->ids_with_attribute(key, value);
+        for (size_t i = 0; i < ids.size() && i < cap; ++i) out[i] = ids[i];
+        return ids.size();
+    }
+
+    // Modality names, NUL-separated, copied into up to `cap` bytes. Returns
+
+    // the total byte length.
+    size_t feather_modality_names(void* db_ptr, char* out, size_t cap) {
+        if (!db_ptr) return 0;
+        auto& db = *static_cast<std::unique_ptr<feather::DB>*>(db_ptr);
+        std::string packed;
+        for (const auto& name : db->modality_names()) {
+            packed += name;
+            packed.push_back('\0');
+        }
+        if (out) std::memcpy(out, packed.data(), std::min(cap, packed.size()));
+        return packed.size();
+    }
 }
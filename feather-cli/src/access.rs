@@ -0,0 +1,192 @@
+//! Access control labels, so one store can hold the memories of several
+//! users without leaking them across (`feather add --owner`, `feather serve
+//! --access-file`).
+//!
+//! A record may carry an owner label (`Metadata::owner`), e.g. a user or
+//! tenant id, and a `Visibility`: private, the default, or public. A
+//! caller's `Access` is the labels it acts for. It sees the records owned by
+//! one of them, the public ones, and those with no owner at all, the
+//! knowledge the store shares with everyone; it may change or delete only
+//! those it owns. `SearchOptions::access` keeps a search to what a caller
+//! sees, checked on every candidate like the filter.
+//!
+//! `feather serve --access-file` maps bearer tokens to labels (see
+//! `Tokens`), and then answers only requests carrying one; the keys of
+//! `--api-key-file` are tokens that see everything. A request sees
+//! and writes through its token's labels: rows it adds are owned by the
+//! token's first label unless they name another of its labels, and a record
+//! it may not see is answered as if it did not exist. Both labels are kept
+//! in attributes (`_owner`, `_visibility`), so they travel with exports,
+//! merges and forks like any other.
+
+use crate::Metadata;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+/// Who may see a record with an owner.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Visibility {
+    /// Only callers acting for its owner.
+    #[default]
+    Private,
+    /// Every caller.
+    Public,
+}
+
+impl Visibility {
+    pub fn parse(name: &str) -> anyhow::Result<Self> {
+        match name {
+            "private" => Ok(Visibility::Private),
+            "public" => Ok(Visibility::Public),
+            _ => anyhow::bail!("unknown visibility '{}' (private, public)", name),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Visibility::Private => "private",
+            Visibility::Public => "public",
+        }
+    }
+}
+
+impl fmt::Display for Visibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(self.name()) }
+}
+
+/// What a caller may see and change.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Access {
+    /// Everything, as the store's own handle.
+    All,
+    /// What these labels own, plus what is public or has no owner; the
+    /// first label owns the records the caller adds.
+    Labels(Vec<String>),
+}
+
+impl Access {
+    /// Whether the caller may see a record with metadata `meta`.
+    pub fn sees(&self, meta: &Metadata) -> bool {
+        match (self, meta.owner()) {
+            (Access::All, _) | (_, None) => true,
+            (Access::Labels(labels), Some(owner)) => {
+                meta.visibility() == Visibility::Public || labels.iter().any(|l| l == owner)
+            }
+        }
+    }
+
+    /// Whether the caller may change or delete a record with metadata `meta`.
+    pub fn owns(&self, meta: &Metadata) -> bool {
+        match self {
+            Access::All => true,
+            Access::Labels(labels) => meta.owner().is_some_and(|owner| labels.iter().any(|l| l == owner)),
+        }
+    }
+
+    /// Give `meta`, about to be written by the caller, the caller's first
+    /// label as its owner if it has none; fails if it names an owner the
+    /// caller does not act for.
+    pub fn claim(&self, meta: &mut Metadata) -> anyhow::Result<()> {
+        let Access::Labels(labels) = self else { return Ok(()) };
+        match meta.owner() {
+            Some(owner) => anyhow::ensure!(labels.iter().any(|l| l == owner), "cannot write records owned by '{}'", owner),
+            None => meta.set_owner(labels.first().map(String::as_str)),
+        }
+        Ok(())
+    }
+}
+
+/// Bearer tokens and the access each grants: an access file's, API keys,
+/// or both.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Tokens {
+    tokens: HashMap<String, Access>,
+}
+
+impl Tokens {
+    /// Read an access file: one `TOKEN LABEL[,LABEL...]` per line; blank
+    /// lines and lines starting with `#` are skipped.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|e| anyhow::anyhow!("cannot read {:?}: {}", path, e))?;
+        Self::parse(&text).map_err(|e| anyhow::anyhow!("{:?}: {:#}", path, e))
+    }
+
+    /// Read an API key file: one key per line, each granting `Access::All`;
+    /// blank lines and lines starting with `#` are skipped.
+    pub fn load_api_keys(path: &Path) -> anyhow::Result<Self> {
+        let mut tokens = Tokens::default();
+        for key in read_api_keys(path)? {
+            tokens.insert(&key, Access::All);
+        }
+        Ok(tokens)
+    }
+
+    /// The tokens of an API key file and an access file, each if given;
+    /// None if neither is, when a server lets every request in.
+    pub fn load_files(api_keys: Option<&Path>, access: Option<&Path>) -> anyhow::Result<Option<Self>> {
+        let mut tokens = match api_keys {
+            Some(path) => Self::load_api_keys(path)?,
+            None if access.is_none() => return Ok(None),
+            None => Tokens::default(),
+        };
+        if let Some(path) = access {
+            tokens.merge(Self::load(path)?)?;
+        }
+        Ok(Some(tokens))
+    }
+
+    /// `load`, from the file's text.
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut tokens = Tokens::default();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+            let (token, labels) = line.split_once(char::is_whitespace)
+                .ok_or_else(|| anyhow::anyhow!("line {}: expected TOKEN LABEL[,LABEL...]", n + 1))?;
+            let labels: Vec<String> = labels.split(',').map(str::trim).filter(|l| !l.is_empty()).map(String::from).collect();
+            anyhow::ensure!(!labels.is_empty(), "line {}: token without labels", n + 1);
+            anyhow::ensure!(!tokens.tokens.contains_key(token), "line {}: token listed twice", n + 1);
+            tokens.insert(token, Access::Labels(labels));
+        }
+        Ok(tokens)
+    }
+
+    /// Add the tokens of `other`; fails if one is in both.
+    pub fn merge(&mut self, other: Tokens) -> anyhow::Result<()> {
+        for (token, access) in other.tokens {
+            anyhow::ensure!(!self.tokens.contains_key(&token), "a token is listed twice");
+            self.tokens.insert(token, access);
+        }
+        Ok(())
+    }
+
+    /// Grant `access` to `token`, replacing what it had.
+    pub fn insert(&mut self, token: &str, access: Access) {
+        self.tokens.insert(token.to_string(), access);
+    }
+
+    /// The access `token` grants, if it is one.
+    pub fn get(&self, token: &str) -> Option<&Access> {
+        self.tokens.get(token)
+    }
+
+    pub fn len(&self) -> usize { self.tokens.len() }
+
+    pub fn is_empty(&self) -> bool { self.tokens.is_empty() }
+}
+
+// The keys of an API key file, in file order (see `Tokens::load_api_keys`).
+pub(crate) fn read_api_keys(path: &Path) -> anyhow::Result<Vec<String>> {
+    let text = std::fs::read_to_string(path).map_err(|e| anyhow::anyhow!("cannot read {:?}: {}", path, e))?;
+    let mut keys: Vec<String> = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let key = line.trim();
+        if key.is_empty() || key.starts_with('#') { continue; }
+        anyhow::ensure!(!key.contains(char::is_whitespace), "{:?}: line {}: keys have no spaces", path, n + 1);
+        anyhow::ensure!(!keys.iter().any(|k| k == key), "{:?}: line {}: key listed twice", path, n + 1);
+        keys.push(key.to_string());
+    }
+    anyhow::ensure!(!keys.is_empty(), "{:?} lists no keys", path);
+    Ok(keys)
+}
@@ -0,0 +1,47 @@
+//! Whole-store analytics built on raw kNN lookups (no recall-count side
+//! effects): outlier detection.
+
+use crate::DB;
+
+/// A record whose neighbourhood is unusually far away.
+#[derive(Clone, Debug)]
+pub struct Outlier {
+    pub id: u64,
+    /// Mean L2 distance to the record's `k` nearest neighbours.
+    pub knn_distance: f32,
+    /// Standard deviations above the store-wide mean kNN distance.
+    pub z_score: f32,
+}
+
+/// Flag records of `modality` whose mean distance to their `k` nearest
+/// neighbours lies more than `threshold` standard deviations above the
+/// store-wide mean — typically bad embeddings, the wrong modality, or
+/// corrupted inputs. Sorted most anomalous first.
+pub fn outliers(db: &DB, modality: &str, k: usize, threshold: f32) -> anyhow::Result<Vec<Outlier>> {
+    anyhow::ensure!(k > 0, "k must be at least 1");
+    let mut scored = Vec::new();
+    for id in db.ids(modality) {
+        let Some(vec) = db.get_vector(id, modality) else { continue };
+        let hits = db.knn(&vec, k + 1, modality)?;
+        let dists: Vec<f32> = hits.iter()
+            .filter(|(hit, _)| *hit != id)
+            .take(k)
+            .map(|(_, d)| d.max(0.0).sqrt())
+            .collect();
+        if dists.is_empty() { continue; }
+        scored.push((id, dists.iter().sum::<f32>() / dists.len() as f32));
+    }
+    if scored.len() < 2 { return Ok(Vec::new()); }
+
+    let n = scored.len() as f32;
+    let mean = scored.iter().map(|(_, d)| d).sum::<f32>() / n;
+    let std = (scored.iter().map(|(_, d)| (d - mean).powi(2)).sum::<f32>() / n).sqrt();
+    if std <= f32::EPSILON { return Ok(Vec::new()); }
+
+    let mut out: Vec<Outlier> = scored.into_iter()
+        .map(|(id, d)| Outlier { id, knn_distance: d, z_score: (d - mean) / std })
+        .filter(|o| o.z_score > threshold)
+        .collect();
+    out.sort_by(|a, b| b.z_score.total_cmp(&a.z_score));
+    Ok(out)
+}
@@ -0,0 +1,311 @@
+//! Index files of other vector libraries (`IndexFile`, `DB::export_index`,
+//! `feather import-index`, `feather export-index`): hnswlib's `saveIndex`
+//! files and FAISS's `write_index` files, so a pipeline built on either can
+//! move its vectors to feather, or back, without embedding them again.
+//!
+//! hnswlib: every element not marked deleted is read, its label as the id.
+//! The file does not say which space it was built for, so its data is read
+//! as float32 vectors (an `l2`, `ip` or `cosine` index). Writing saves the
+//! modality's own HNSW graph, which hnswlib loads ready to search as an
+//! `l2` index; forgotten records stay in it marked deleted, as hnswlib's
+//! `mark_deleted` leaves them. A sharded store or a fork has no single
+//! graph to save.
+//!
+//! FAISS: flat indexes (`IndexFlatL2`, `IndexFlatIP`) and the vectors of an
+//! `IndexHNSWFlat` are read, each optionally inside an `IndexIDMap` or
+//! `IndexIDMap2` giving the ids; without one, vectors are numbered from 0.
+//! Compressed indexes (IVF, PQ, ...) do not keep the vectors, and are not
+//! read. Writing makes an `IndexIDMap` around an `IndexFlatL2`, which
+//! searches exactly.
+//!
+//! Feather ranks by L2 distance, which ranks vectors from an inner-product
+//! or cosine index the same way only if they are unit length (see
+//! `normalize`). Metadata travels in a sidecar file, one row per id, as
+//! `feather import` reads rows (JSONL or CSV); `export_index` leaves it to
+//! the caller, and `feather export-index --meta` writes it as JSONL.
+
+use crate::{c_char, c_str, c_void, last_error, Record, DB};
+use std::collections::HashMap;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+extern "C" {
+    fn feather_save_hnsw(db: *mut c_void, modality: *const c_char, path: *const c_char) -> i32;
+}
+
+/// A vector index file format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexFormat {
+    Hnswlib,
+    Faiss,
+}
+
+/// The vectors of an index file.
+#[derive(Clone, Debug, Default)]
+pub struct IndexFile {
+    pub ids: Vec<u64>,
+    /// `dim` floats per id, in the order of `ids`.
+    pub vectors: Vec<f32>,
+    pub dim: usize,
+    /// Whether the index ranks by inner product; only FAISS files say.
+    pub inner_product: bool,
+}
+
+impl IndexFile {
+    pub fn read(path: &Path, format: IndexFormat) -> anyhow::Result<Self> {
+        let read = match format {
+            IndexFormat::Hnswlib => std::fs::read(path).map_err(anyhow::Error::from).and_then(|bytes| read_hnswlib(&bytes)),
+            IndexFormat::Faiss => std::fs::File::open(path).map_err(anyhow::Error::from)
+                .and_then(|file| read_faiss(&mut BufReader::new(file))),
+        };
+        read.map_err(|e| anyhow::anyhow!("{:?}: {:#}", path, e))
+    }
+
+    /// One record per vector, in `modality`, with the metadata of the row
+    /// of `meta` that has its id, if any, and `timestamp` if that has none.
+    /// Every row must name an id the index has, once, and carry no vectors.
+    pub fn records<M>(self, modality: &str, meta: Option<M>, timestamp: i64) -> anyhow::Result<Vec<Record>>
+    where
+        M: IntoIterator<Item = anyhow::Result<Record>>,
+    {
+        let mut rows: HashMap<u64, Record> = HashMap::new();
+        for row in meta.into_iter().flatten() {
+            let row = row?;
+            anyhow::ensure!(row.vectors.is_empty(), "record {}: vectors come from the index, not the metadata", row.id);
+            let id = row.id;
+            anyhow::ensure!(rows.insert(id, row).is_none(), "metadata: duplicate id {}", id);
+        }
+        let mut records = Vec::with_capacity(self.ids.len());
+        for (&id, vector) in self.ids.iter().zip(self.vectors.chunks_exact(self.dim.max(1))) {
+            let mut record = rows.remove(&id).unwrap_or_else(|| Record { id, ..Record::default() });
+            if record.metadata.timestamp == 0 { record.metadata.timestamp = timestamp; }
+            record.vectors.insert(modality.to_string(), vector.to_vec());
+            records.push(record);
+        }
+        if let Some(id) = rows.keys().min() {
+            anyhow::bail!("metadata for id {}, which the index does not have", id);
+        }
+        Ok(records)
+    }
+}
+
+impl DB {
+    /// Write the live records' vectors in `modality` to `path` as an index
+    /// file of `format` (see the module docs); returns their ids.
+    pub fn export_index(&self, path: &Path, format: IndexFormat, modality: &str) -> anyhow::Result<Vec<u64>> {
+        anyhow::ensure!(self.modalities().iter().any(|m| m == modality), "no modality '{}'", modality);
+        match format {
+            IndexFormat::Hnswlib => self.write_hnswlib(path, modality),
+            IndexFormat::Faiss => self.write_faiss(path, modality),
+        }
+    }
+
+    fn write_hnswlib(&self, path: &Path, modality: &str) -> anyhow::Result<Vec<u64>> {
+        self.unsharded("exported to hnswlib")?;
+        anyhow::ensure!(self.handle.fork.is_none(), "a fork cannot be exported to hnswlib; use FAISS");
+        let name = self.mname(Some(modality)).expect("named");
+        let c_path = c_str(path.to_str().ok_or_else(|| anyhow::anyhow!("path is not UTF-8: {:?}", path))?)?;
+        if unsafe { feather_save_hnsw(self.ptr, c_str(&name)?.as_ptr(), c_path.as_ptr()) } != 0 {
+            return Err(last_error());
+        }
+        // the core labels elements with internal ids, and keeps forgotten ones
+        let mut bytes = std::fs::read(path).map_err(|e| anyhow::anyhow!("{:?}: {}", path, e))?;
+        let layout = HnswlibLayout::parse(&bytes)?;
+        let mut ids = Vec::new();
+        for i in 0..layout.count {
+            let element = layout.element_mut(&mut bytes, i);
+            let label = u64::from_le_bytes(element[layout.label..layout.label + 8].try_into().expect("8 bytes"));
+            // an element with no record left gets a label no record can have
+            let id = self.xid(label);
+            element[layout.label..layout.label + 8].copy_from_slice(&id.unwrap_or(u64::MAX - i as u64).to_le_bytes());
+            match id.filter(|&id| self.get_metadata(id).is_some_and(|m| !m.is_forgotten())) {
+                Some(id) if element[layout.links + 2] & DELETE_MARK == 0 => ids.push(id),
+                _ => element[layout.links + 2] |= DELETE_MARK,
+            }
+        }
+        std::fs::write(path, bytes).map_err(|e| anyhow::anyhow!("{:?}: {}", path, e))?;
+        Ok(ids)
+    }
+
+    fn write_faiss(&self, path: &Path, modality: &str) -> anyhow::Result<Vec<u64>> {
+        let dim = self.dim(modality);
+        let mut ids = Vec::new();
+        let mut vectors = Vec::new();
+        for id in self.ids(modality) {
+            if self.get_metadata(id).is_none_or(|m| m.is_forgotten()) { continue; }
+            let Some(vector) = self.get_vector(id, modality) else { continue };
+            anyhow::ensure!(i64::try_from(id).is_ok(), "id {} does not fit a FAISS id", id);
+            ids.push(id);
+            vectors.extend(vector);
+        }
+        let file = std::fs::File::create(path).map_err(|e| anyhow::anyhow!("{:?}: {}", path, e))?;
+        let mut out = BufWriter::new(file);
+        out.write_all(b"IxMp")?;
+        faiss_header(&mut out, dim, ids.len())?;
+        out.write_all(b"IxF2")?;
+        faiss_header(&mut out, dim, ids.len())?;
+        out.write_all(&(vectors.len() as u64).to_le_bytes())?;
+        for x in &vectors { out.write_all(&x.to_le_bytes())?; }
+        out.write_all(&(ids.len() as u64).to_le_bytes())?;
+        for &id in &ids { out.write_all(&(id as i64).to_le_bytes())?; }
+        out.flush()?;
+        Ok(ids)
+    }
+}
+
+/// Write the metadata of records `ids` to `out` as JSONL, one object per
+/// record with its `id`, for `IndexFile::records` to read back.
+pub fn write_metadata(db: &DB, ids: &[u64], out: &mut impl Write) -> anyhow::Result<()> {
+    for &id in ids {
+        let Some(metadata) = db.get_metadata(id) else { continue };
+        let mut row = serde_json::to_value(Record { id, metadata, ..Record::default() })?;
+        if let Some(row) = row.as_object_mut() { row.remove("vectors"); }
+        serde_json::to_writer(&mut *out, &row)?;
+        out.write_all(b"\n")?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+// Set in the third byte of an element's level-0 link list header.
+const DELETE_MARK: u8 = 0x01;
+
+// Where things are in an hnswlib file: a fixed header, then `count`
+// elements of `size` bytes — link list header at `links`, vector at
+// `data`, label at `label` — then the upper-level link lists.
+struct HnswlibLayout {
+    count: usize,
+    size: usize,
+    links: usize,
+    data: usize,
+    label: usize,
+}
+
+impl HnswlibLayout {
+    // offsetLevel0, max_elements, cur_element_count, size_data_per_element,
+    // label_offset, offsetData (u64); maxlevel, enterpoint (u32); maxM,
+    // maxM0, M (u64); mult (f64); ef_construction (u64)
+    const HEADER: usize = 6 * 8 + 2 * 4 + 5 * 8;
+
+    fn parse(bytes: &[u8]) -> anyhow::Result<Self> {
+        anyhow::ensure!(bytes.len() >= Self::HEADER, "not an hnswlib index: too short");
+        let field = |i: usize| u64::from_le_bytes(bytes[i * 8..i * 8 + 8].try_into().expect("8 bytes")) as usize;
+        let layout = HnswlibLayout { links: field(0), count: field(2), size: field(3), label: field(4), data: field(5) };
+        anyhow::ensure!(layout.links + 4 <= layout.data && layout.data < layout.label
+                        && (layout.label - layout.data).is_multiple_of(4) && layout.label + 8 <= layout.size,
+                        "not an hnswlib index: bad element layout");
+        let elements = layout.count.checked_mul(layout.size).and_then(|n| n.checked_add(Self::HEADER));
+        anyhow::ensure!(elements.is_some_and(|n| n <= bytes.len()), "hnswlib index cut short");
+        Ok(layout)
+    }
+
+    fn element<'a>(&self, bytes: &'a [u8], i: usize) -> &'a [u8] {
+        &bytes[Self::HEADER + i * self.size..][..self.size]
+    }
+
+    fn element_mut<'a>(&self, bytes: &'a mut [u8], i: usize) -> &'a mut [u8] {
+        &mut bytes[Self::HEADER + i * self.size..][..self.size]
+    }
+}
+
+pub(crate) fn read_hnswlib(bytes: &[u8]) -> anyhow::Result<IndexFile> {
+    let layout = HnswlibLayout::parse(bytes)?;
+    let mut file = IndexFile { dim: (layout.label - layout.data) / 4, ..IndexFile::default() };
+    for i in 0..layout.count {
+        let element = layout.element(bytes, i);
+        if element[layout.links + 2] & DELETE_MARK != 0 { continue; }
+        file.ids.push(u64::from_le_bytes(element[layout.label..layout.label + 8].try_into().expect("8 bytes")));
+        file.vectors.extend(element[layout.data..layout.label].chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().expect("4 bytes"))));
+    }
+    Ok(file)
+}
+
+// FAISS's index header: d, ntotal, two unused fields, is_trained,
+// metric_type (1 = L2).
+fn faiss_header(out: &mut impl Write, dim: usize, n: usize) -> std::io::Result<()> {
+    out.write_all(&(dim as i32).to_le_bytes())?;
+    out.write_all(&(n as i64).to_le_bytes())?;
+    out.write_all(&(1i64 << 20).to_le_bytes())?;
+    out.write_all(&(1i64 << 20).to_le_bytes())?;
+    out.write_all(&[1])?;
+    out.write_all(&1i32.to_le_bytes())
+}
+
+fn read_faiss(input: &mut impl Read) -> anyhow::Result<IndexFile> {
+    let mut fourcc = [0u8; 4];
+    input.read_exact(&mut fourcc)?;
+    let (dim, n, inner_product) = read_faiss_header(input)?;
+    match &fourcc {
+        b"IxF2" | b"IxFI" | b"IxFl" => {
+            let floats = read_u64(input)? as usize;
+            anyhow::ensure!(Some(floats) == dim.checked_mul(n), "flat index holds {} floats, not {} x {}", floats, n, dim);
+            let mut bytes = vec![0u8; floats * 4];
+            input.read_exact(&mut bytes)?;
+            Ok(IndexFile {
+                ids: (0..n as u64).collect(),
+                vectors: bytes.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().expect("4 bytes"))).collect(),
+                dim,
+                inner_product,
+            })
+        }
+        b"IxMp" | b"IxM2" => {
+            let mut file = read_faiss(input)?;
+            let count = read_u64(input)? as usize;
+            anyhow::ensure!(count == file.ids.len(), "id map holds {} ids for {} vectors", count, file.ids.len());
+            for id in &mut file.ids {
+                let raw = read_i64(input)?;
+                *id = u64::try_from(raw).map_err(|_| anyhow::anyhow!("negative id {}", raw))?;
+            }
+            Ok(file)
+        }
+        b"IHNf" => {
+            // the graph — assign_probas, cum_nneighbor_per_level, levels,
+            // offsets, neighbors — then five ints, then the vectors' storage
+            for width in [8, 4, 4, 8, 4] { skip_vec(input, width)?; }
+            let mut ints = [0u8; 5 * 4];
+            input.read_exact(&mut ints)?;
+            read_faiss(input)
+        }
+        other => anyhow::bail!("unsupported FAISS index type {:?}: flat and HNSW-flat indexes keep their vectors; \
+                                compressed ones do not", String::from_utf8_lossy(other)),
+    }
+}
+
+// (d, ntotal, whether the metric is inner product)
+fn read_faiss_header(input: &mut impl Read) -> anyhow::Result<(usize, usize, bool)> {
+    let dim = read_i32(input)?;
+    let n = read_i64(input)?;
+    read_i64(input)?;
+    read_i64(input)?;
+    let mut is_trained = [0u8; 1];
+    input.read_exact(&mut is_trained)?;
+    let metric = read_i32(input)?;
+    if metric > 1 { read_i32(input)?; }   // metric_arg
+    anyhow::ensure!(dim > 0 && n >= 0, "not a FAISS index: d = {}, ntotal = {}", dim, n);
+    Ok((dim as usize, n as usize, metric == 0))
+}
+
+fn skip_vec(input: &mut impl Read, width: u64) -> anyhow::Result<()> {
+    let len = read_u64(input)?;
+    let bytes = len.checked_mul(width).ok_or_else(|| anyhow::anyhow!("not a FAISS index"))?;
+    let skipped = std::io::copy(&mut input.take(bytes), &mut std::io::sink())?;
+    anyhow::ensure!(skipped == bytes, "FAISS index cut short");
+    Ok(())
+}
+
+fn read_u64(input: &mut impl Read) -> std::io::Result<u64> {
+    let mut b = [0u8; 8];
+    input.read_exact(&mut b)?;
+    Ok(u64::from_le_bytes(b))
+}
+
+fn read_i64(input: &mut impl Read) -> std::io::Result<i64> {
+    read_u64(input).map(|v| v as i64)
+}
+
+fn read_i32(input: &mut impl Read) -> std::io::Result<i32> {
+    let mut b = [0u8; 4];
+    input.read_exact(&mut b)?;
+    Ok(i32::from_le_bytes(b))
+}
@@ -0,0 +1,49 @@
+//! Taking records out of search without losing them (`feather archive`,
+//! `feather restore`, `feather search --include-archived`).
+//!
+//! `forget` is final: it drops a record's content and vectors. Archiving
+//! only hides it. An archived record keeps everything, stays in `get`,
+//! exports, lineage and the graph, but searches here leave it out of
+//! vector, keyword and sparse results unless
+//! `SearchOptions::include_archived` asks for it. `restore` puts it back.
+//! The C++ core only leaves archived records out when asked
+//! (`SearchFilter::exclude_archived`), so its other bindings are unchanged.
+//!
+//! The time of archiving is kept in the `_archived` attribute
+//! (`Metadata::archived_at`), so it travels with exports, merges and forks
+//! like any other, and a filter can ask for it as `archived_at`.
+
+use crate::metadata::ARCHIVED_ATTRIBUTE;
+use crate::{decay, DB};
+
+impl DB {
+    /// Archive record `id`; returns false if it already was.
+    pub fn archive(&self, id: u64) -> anyhow::Result<bool> {
+        let meta = self.get_metadata(id).filter(|m| !m.is_forgotten())
+            .ok_or_else(|| anyhow::anyhow!("no record {}", id))?;
+        if meta.is_archived() { return Ok(false); }
+        self.set_attribute(id, ARCHIVED_ATTRIBUTE, &decay::now().to_string())
+    }
+
+    /// Bring archived record `id` back into search; returns false if it
+    /// was not archived.
+    pub fn restore(&self, id: u64) -> anyhow::Result<bool> {
+        let mut meta = self.get_metadata(id).filter(|m| !m.is_forgotten())
+            .ok_or_else(|| anyhow::anyhow!("no record {}", id))?;
+        if meta.attributes.remove(ARCHIVED_ATTRIBUTE).is_none() { return Ok(false); }
+        self.put_metadata(id, &meta)?;
+        Ok(true)
+    }
+
+    /// Ids of the archived records, oldest archived first.
+    pub fn archived(&self) -> Vec<u64> {
+        let mut archived: Vec<(i64, u64)> = self.all_ids().into_iter()
+            .filter_map(|id| {
+                let meta = self.get_metadata(id).filter(|m| !m.is_forgotten())?;
+                Some((meta.archived_at()?, id))
+            })
+            .collect();
+        archived.sort_unstable();
+        archived.into_iter().map(|(_, id)| id).collect()
+    }
+}
@@ -0,0 +1,235 @@
+//! An append-only log of the changes made to a store (`feather history`).
+//!
+//! With auditing on (`DB::set_audit`), every add, update, delete, link and
+//! unlink is appended as one JSON line to `<store>.audit`, beside the store:
+//! when, what, which record of which collection, and the actor the writer
+//! named itself (`OpenOptions::actor`, `feather --actor`), if it did. Lines
+//! are only ever appended, so the log still shows how the store evolved
+//! after records are overwritten, forgotten or vacuumed — which write led
+//! an agent astray, and who made it.
+//!
+//! The setting is kept in the file's properties, so every later writer
+//! follows it. Entries are written as a handle makes each change, not on
+//! `save()`; a transaction's are held back until it commits. Budget
+//! evictions are logged as deletes; recalls and expiry are not logged. An
+//! in-memory store has no log until `persist_to` gives it a path, and a
+//! read-only handle writes none but can read it.
+
+use crate::trace::{Level, Span};
+use crate::{collection, decay, Handle, DB};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// Property set while auditing is on.
+pub(crate) const PROPERTY_KEY: &str = "audit";
+
+/// The audit log of the store at `path`.
+pub fn log_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".audit");
+    PathBuf::from(name)
+}
+
+/// The kind of change an entry records.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Op {
+    /// A record written with a vector, new or replacing one.
+    Add,
+    /// A record's metadata, attributes or vectors changed in place.
+    Update,
+    /// A record forgotten.
+    Delete,
+    Link,
+    Unlink,
+}
+
+impl Op {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Op::Add => "add",
+            Op::Update => "update",
+            Op::Delete => "delete",
+            Op::Link => "link",
+            Op::Unlink => "unlink",
+        }
+    }
+}
+
+/// One change, as the log holds it and subscribers receive it (see
+/// `feed`).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// When, in Unix seconds.
+    pub at: i64,
+    pub op: Op,
+    /// The record changed; for a link or unlink, the one holding it.
+    pub id: u64,
+    /// The collection `id` is local to; None for the default one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+    /// The record linked to or unlinked from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<u64>,
+    /// The link's type; None for an untyped link, or an unlink of every
+    /// type.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rel_type: Option<String>,
+    /// Who made the change, as the writer named itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+}
+
+// A handle's end of the log.
+#[derive(Default)]
+pub(crate) struct Log {
+    // where the store's log is; None in memory
+    path: Option<PathBuf>,
+    // open for appending while auditing is on
+    file: Option<File>,
+    actor: Option<String>,
+    // entries of the transaction being applied, published when it commits
+    held: Option<Vec<Entry>>,
+}
+
+impl Log {
+    fn append(&mut self, entries: &[Entry]) {
+        let Some(file) = &mut self.file else { return };
+        let text: String = entries.iter()
+            .map(|entry| serde_json::to_string(entry).expect("entries serialize") + "\n")
+            .collect();
+        if let Err(e) = file.write_all(text.as_bytes()) {
+            // the change itself was made; losing its entry must not undo it
+            Span::new(Level::Warn, "feather::audit").record_str("error", &e.to_string());
+        }
+    }
+}
+
+impl Handle {
+    // Log `op` on record `iid` (internal ids; `target` for links) if
+    // auditing is on, and tell the subscribers (see `feed`).
+    pub(crate) fn changed(&self, op: Op, iid: u64, target: Option<u64>, rel_type: Option<&str>) {
+        self.auto_save();
+        let mut log = self.audit.borrow_mut();
+        if log.file.is_none() && self.subscribers.borrow().is_empty() { return; }
+        let index = iid >> collection::ID_BITS;
+        let collection = self.collections.borrow().iter()
+            .find(|(_, &i)| index != 0 && i as u64 == index)
+            .map(|(name, _)| name.clone());
+        let local = |iid: u64| if collection.is_some() { iid & collection::MAX_ID } else { iid };
+        let entry = Entry {
+            at: decay::now(),
+            op,
+            id: local(iid),
+            target: target.map(local),
+            rel_type: rel_type.map(str::to_string),
+            actor: log.actor.clone(),
+            collection,
+        };
+        match &mut log.held {
+            Some(held) => held.push(entry),
+            None => {
+                log.append(std::slice::from_ref(&entry));
+                self.subscribers.borrow_mut().send(&entry);
+            }
+        }
+    }
+
+    // Hold entries back until `release`, which publishes them if `commit`.
+    pub(crate) fn hold_changes(&self) {
+        self.audit.borrow_mut().held = Some(Vec::new());
+    }
+
+    // Whether a transaction is holding entries back.
+    pub(crate) fn holds_changes(&self) -> bool { self.audit.borrow().held.is_some() }
+
+    pub(crate) fn release_changes(&self, commit: bool) {
+        let mut log = self.audit.borrow_mut();
+        let held = log.held.take().unwrap_or_default();
+        if !commit { return; }
+        log.append(&held);
+        let mut subscribers = self.subscribers.borrow_mut();
+        for entry in &held {
+            subscribers.send(entry);
+        }
+    }
+}
+
+impl DB {
+    /// Whether changes to this file are logged (see the module docs).
+    pub fn audits(&self) -> bool {
+        self.property(PROPERTY_KEY).is_some()
+    }
+
+    /// Turn the audit log on or off for the whole file (all collections);
+    /// the setting persists on `save()`. Turning it off leaves the log
+    /// as it is.
+    pub fn set_audit(&self, on: bool) -> anyhow::Result<()> {
+        self.writable()?;
+        let mut log = self.handle.audit.borrow_mut();
+        if !on {
+            log.file = None;
+            self.remove_property(PROPERTY_KEY)?;
+            return Ok(());
+        }
+        let path = log.path.clone()
+            .ok_or_else(|| anyhow::anyhow!("an in-memory store has no audit log; persist it first"))?;
+        log.file = Some(open_log(&path)?);
+        self.set_property(PROPERTY_KEY, b"1")?;
+        Ok(())
+    }
+
+    /// The actor this handle's changes are logged under, if named.
+    pub fn actor(&self) -> Option<String> {
+        self.handle.audit.borrow().actor.clone()
+    }
+
+    /// Name who makes this handle's changes, e.g. an agent or a user, in
+    /// the audit log; for every handle on this file.
+    pub fn set_actor(&self, actor: Option<&str>) {
+        self.handle.audit.borrow_mut().actor = actor.map(str::to_string);
+    }
+
+    /// The logged changes of this collection, oldest first: all of them,
+    /// or with `id` the ones to that record, links to it included. Empty
+    /// if nothing was ever logged.
+    pub fn history(&self, id: Option<u64>) -> anyhow::Result<Vec<Entry>> {
+        let Some(path) = self.handle.audit.borrow().path.clone() else { return Ok(Vec::new()) };
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => anyhow::bail!("cannot read {:?}: {}", path, e),
+        };
+        let lines: Vec<String> = BufReader::new(file).lines().collect::<std::io::Result<_>>()?;
+        let mut entries = Vec::new();
+        for (n, line) in lines.iter().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+            let entry: Entry = match serde_json::from_str(line) {
+                Ok(entry) => entry,
+                // a line cut short by a crash
+                Err(_) if n + 1 == lines.len() => break,
+                Err(e) => anyhow::bail!("{:?} line {}: {}", path, n + 1, e),
+            };
+            if entry.collection.as_deref() != self.collection_name() { continue; }
+            if id.is_none_or(|id| entry.id == id || entry.target == Some(id)) { entries.push(entry); }
+        }
+        Ok(entries)
+    }
+
+    // Tie the log to the store at `path`, and open it if the file audits
+    // and this handle may write.
+    pub(crate) fn attach_audit(&self, path: &Path) -> anyhow::Result<()> {
+        let path = log_path(path);
+        let file = if self.audits() && !self.is_read_only() { Some(open_log(&path)?) } else { None };
+        let mut log = self.handle.audit.borrow_mut();
+        log.path = Some(path);
+        log.file = file;
+        Ok(())
+    }
+}
+
+fn open_log(path: &Path) -> anyhow::Result<File> {
+    File::options().create(true).append(true).open(path)
+        .map_err(|e| anyhow::anyhow!("cannot open audit log {:?}: {}", path, e))
+}
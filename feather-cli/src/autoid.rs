@@ -0,0 +1,53 @@
+//! Ids the store picks (`DB::add_auto`, `DB::allocate_id`, `feather add`
+//! without an id).
+//!
+//! Each collection keeps a counter in the DB properties, the next id to hand
+//! out. A new counter starts above the largest id stored, and ids taken
+//! since by explicit inserts are stepped over, so callers can mix their own
+//! ids with allocated ones. Ids are never handed out twice, even once the
+//! record holding one has been deleted. Like any property, the counter is
+//! persisted on the next `save()`.
+
+use crate::{collection, Metadata, DB};
+
+/// Property key holding the next id of the default collection; named
+/// collections use `next_id::<collection>`.
+pub(crate) const PROPERTY_KEY: &str = "next_id";
+
+fn property_key(db: &DB) -> String {
+    match db.collection_name() {
+        Some(name) => format!("{}{}{}", PROPERTY_KEY, collection::MODALITY_SEP, name),
+        None => PROPERTY_KEY.to_string(),
+    }
+}
+
+impl DB {
+    /// Reserve an id no record holds or has held since the counter began,
+    /// and advance the counter past it. Ids start at 1.
+    pub fn allocate_id(&self) -> anyhow::Result<u64> {
+        self.writable()?;
+        let key = property_key(self);
+        let stored = self.property(&key)
+            .and_then(|raw| Some(u64::from_le_bytes(raw.as_slice().try_into().ok()?)));
+        let mut id = stored.unwrap_or_else(|| self.all_ids().into_iter().max().map_or(1, |max| max + 1)).max(1);
+        while self.get_metadata(id).is_some() {
+            id += 1;
+        }
+        self.set_property(&key, &(id + 1).to_le_bytes())?;
+        Ok(id)
+    }
+
+    /// Insert a record in the `text` modality under a newly allocated id,
+    /// and return the id. Under the dedup mode, a record already stored
+    /// under another id is not inserted again (or is merged there), and
+    /// that id is returned instead. Fails with `DimensionMismatch` if `vec`
+    /// does not fit the modality.
+    pub fn add_auto(&self, vec: &[f32], meta: &Metadata) -> anyhow::Result<u64> {
+        let id = self.allocate_id()?;
+        if let Some(existing) = self.deduplicate(id, Some(("text", vec)), meta)? {
+            return Ok(existing);
+        }
+        self.write_record(id, vec, meta, "text")?;
+        Ok(id)
+    }
+}
@@ -0,0 +1,127 @@
+//! Saving as changes come (`OpenOptions::auto_save`), and making the WAL
+//! durable without a save (`DB::flush`).
+//!
+//! Every change reaches the WAL as it is made, and the next open replays
+//! it; a save checkpoints the file and empties the WAL. Until then the WAL
+//! grows with every change, a reopen replays all of it, and settings kept
+//! in the properties (the id counter, drift, a fork's tombstones) are only
+//! in memory. Saving after every add costs a full write of the file each
+//! time. An `AutoSave` policy saves every `every` changes, or once
+//! `interval` has passed since the last save; the interval is checked as
+//! changes come, so an idle handle is not saved until its next change.
+//! Auto-saves checkpoint only: evicting down to the budget is left to an
+//! explicit `save()`. No auto-save happens while a transaction commits.
+//! Dropping the last handle on a file saves it whatever the policy, as the
+//! core checkpoints on close.
+//!
+//! The WAL is written through the OS's cache, so a crash of the machine,
+//! rather than of the process, can lose its tail. `flush` syncs it to
+//! disk: cheaper than a save, and every change made so far survives.
+
+use crate::{c_char, c_void, Handle, DB};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+extern "C" {
+    fn feather_path(db: *mut c_void, out: *mut c_char, cap: usize) -> usize;
+}
+
+/// When a handle saves on its own; the default never does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AutoSave {
+    /// Save after this many changes; 0 for no limit.
+    pub every: usize,
+    /// Save on the first change this long after the last save.
+    pub interval: Option<Duration>,
+}
+
+impl AutoSave {
+    /// Save every `n` changes.
+    pub fn every(n: usize) -> Self {
+        AutoSave { every: n, interval: None }
+    }
+
+    /// Save on the first change `interval` after the last save.
+    pub fn interval(interval: Duration) -> Self {
+        AutoSave { every: 0, interval: Some(interval) }
+    }
+
+    fn is_off(&self) -> bool { self.every == 0 && self.interval.is_none() }
+}
+
+// A handle's policy, and the changes made since it last saved.
+pub(crate) struct State {
+    policy: AutoSave,
+    pending: usize,
+    saved_at: Instant,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State { policy: AutoSave::default(), pending: 0, saved_at: Instant::now() }
+    }
+}
+
+impl Handle {
+    // Count a change, and save if the policy says it is time.
+    pub(crate) fn auto_save(&self) {
+        let due = {
+            let mut state = self.autosave.borrow_mut();
+            state.pending += 1;
+            if state.policy.is_off() { return; }
+            (state.policy.every > 0 && state.pending >= state.policy.every)
+                || state.policy.interval.is_some_and(|t| state.saved_at.elapsed() >= t)
+        };
+        if due && !self.holds_changes() {
+            self.checkpoint();
+        }
+    }
+
+    // Write the properties and every core to the file, and keep a
+    // snapshot of it if asked to (see `snapshots`).
+    pub(crate) fn checkpoint(&self) {
+        self.flush_properties();
+        for &core in self.cores() {
+            unsafe { crate::feather_save(core) }
+        }
+        self.snapshot_saved();
+        let mut state = self.autosave.borrow_mut();
+        state.pending = 0;
+        state.saved_at = Instant::now();
+    }
+}
+
+impl DB {
+    /// The auto-save policy of this file's handles.
+    pub fn auto_save(&self) -> AutoSave { self.handle.autosave.borrow().policy }
+
+    /// Change the auto-save policy for every handle on this file
+    /// (collections included). Not persisted.
+    pub fn set_auto_save(&self, policy: AutoSave) {
+        self.handle.autosave.borrow_mut().policy = policy;
+    }
+
+    /// Changes made since the last save.
+    pub fn unsaved_changes(&self) -> usize { self.handle.autosave.borrow().pending }
+
+    /// Sync the WAL to disk, so every change made so far survives a crash
+    /// of the machine; see the module docs. A no-op in memory, when
+    /// read-only, and when nothing is left unsaved.
+    pub fn flush(&self) -> anyhow::Result<()> {
+        if self.is_read_only() { return Ok(()); }
+        for &core in self.handle.cores() {
+            let n = unsafe { feather_path(core, std::ptr::null_mut(), 0) };
+            if n == 0 { continue; }
+            let mut buf = vec![0u8; n];
+            unsafe { feather_path(core, buf.as_mut_ptr().cast(), n) };
+            let mut wal = PathBuf::from(String::from_utf8(buf)?).into_os_string();
+            wal.push(".wal");
+            match std::fs::OpenOptions::new().append(true).open(&wal) {
+                Ok(file) => file.sync_all().map_err(|e| anyhow::anyhow!("cannot sync {:?}: {}", wal, e))?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => anyhow::bail!("cannot open {:?}: {}", wal, e),
+            }
+        }
+        Ok(())
+    }
+}
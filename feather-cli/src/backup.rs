@@ -0,0 +1,292 @@
+//! Backups, whole or as what changed since an earlier one, and restoring
+//! them (`feather backup`, `feather restore --from`).
+//!
+//! `backup` checkpoints the store and copies its file: a full backup is a
+//! plain store file, which opens like any other. `backup_since` writes only
+//! what changed since an earlier backup: it checkpoints the store, compares
+//! it with the earlier state record by record, and writes the WAL entries
+//! that turn one into the other — a record added or changed, with its
+//! vectors if they changed, a sparse vector set, a record forgotten — after
+//! a header naming both states. A delta is about the size of what changed,
+//! however large the store. The properties (collections, indexes, settings)
+//! are not in the WAL and travel whole in the header.
+//!
+//! Each backup's state is kept beside the store as
+//! `<store>.backups/<id>.feather`, hard-linked like a snapshot (see
+//! `snapshots`), its id the Unix second it was taken, or one past the last
+//! backup's if that is no earlier (two in a second, or a clock set back);
+//! a delta is taken since one of these, or since a snapshot. Kept are the last full
+//! backup's state and the newest, so deltas can be taken each since the
+//! one before (incremental) or each since the full backup (differential);
+//! a full backup starts over.
+//!
+//! `restore` copies a base, a full backup or a snapshot, to a new path and
+//! replays the deltas on it in order, as the core replays its WAL. A delta
+//! carries digests of the records it starts from and ends at (ids and
+//! metadata), so one applied to the wrong base, or out of order, fails
+//! before it changes anything, and one that does not rebuild its end state
+//! fails after. A restore that fails leaves nothing behind.
+//!
+//! A sharded store is backed up shard by shard. A fork's backup holds what
+//! the fork changed; its base is backed up on its own.
+
+use crate::lock::{FileLock, LockMode};
+use crate::{c_char, c_void, snapshots, CString, OpenOptions, DB};
+use std::path::{Path, PathBuf};
+
+/// What a delta file starts with.
+pub const MAGIC: &[u8; 8] = b"FTHRDLT1";
+
+// magic, from, to, two digests, the properties' length
+const HEADER: usize = 8 + 8 + 8 + 8 + 8 + 4;
+
+extern "C" {
+    fn feather_delta(base: *mut c_void, db: *mut c_void, path: *const c_char) -> i64;
+    fn feather_digest(db: *mut c_void) -> u64;
+    fn feather_properties(db: *mut c_void, out: *mut c_char, cap: usize) -> usize;
+    fn feather_set_properties(db: *mut c_void, data: *const c_char, len: usize) -> i32;
+    fn feather_apply_wal(db: *mut c_void, data: *const c_char, len: usize) -> i32;
+}
+
+/// What a backup wrote.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize)]
+pub struct BackupReport {
+    /// The id of the state the backup holds, to take a later one since.
+    pub id: i64,
+    /// The state a delta starts from; None for a full backup.
+    pub since: Option<i64>,
+    /// WAL entries in a delta.
+    pub entries: usize,
+    pub bytes: u64,
+}
+
+/// A delta's header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Delta {
+    /// The state it starts from.
+    pub from: i64,
+    /// The state it ends at.
+    pub to: i64,
+    from_digest: u64,
+    to_digest: u64,
+    properties: Vec<u8>,
+}
+
+impl Delta {
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER + self.properties.len());
+        out.extend_from_slice(MAGIC);
+        out.extend(self.from.to_le_bytes());
+        out.extend(self.to.to_le_bytes());
+        out.extend(self.from_digest.to_le_bytes());
+        out.extend(self.to_digest.to_le_bytes());
+        out.extend((self.properties.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.properties);
+        out
+    }
+
+    // The header of `bytes`, and the WAL entries after it.
+    fn decode(bytes: &[u8]) -> anyhow::Result<(Delta, &[u8])> {
+        anyhow::ensure!(bytes.len() >= HEADER && bytes.starts_with(MAGIC), "not a delta written by `feather backup --incremental`");
+        let word = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().expect("8 bytes"));
+        let len = u32::from_le_bytes(bytes[HEADER - 4..HEADER].try_into().expect("4 bytes")) as usize;
+        anyhow::ensure!(bytes.len() - HEADER >= len, "truncated delta");
+        let delta = Delta {
+            from: word(8) as i64,
+            to: word(16) as i64,
+            from_digest: word(24),
+            to_digest: word(32),
+            properties: bytes[HEADER..HEADER + len].to_vec(),
+        };
+        Ok((delta, &bytes[HEADER + len..]))
+    }
+
+    /// The header of the delta at `path`.
+    pub fn read(path: &Path) -> anyhow::Result<Delta> {
+        let bytes = std::fs::read(path).map_err(|e| anyhow::anyhow!("cannot read {:?}: {}", path, e))?;
+        Delta::decode(&bytes).map(|(delta, _)| delta).map_err(|e| anyhow::anyhow!("{:?}: {}", path, e))
+    }
+}
+
+/// The directory holding the states of the backups of the store at `path`.
+pub fn dir(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".backups");
+    PathBuf::from(name)
+}
+
+/// The backup states kept of the store at `path`, as (id, file), oldest
+/// first.
+pub fn list(path: &Path) -> anyhow::Result<Vec<(i64, PathBuf)>> {
+    let dir = dir(path);
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => anyhow::bail!("cannot read {:?}: {}", dir, e),
+    };
+    let mut kept = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_none_or(|e| e != "feather") { continue; }
+        let Some(id) = path.file_stem().and_then(|s| s.to_str()).and_then(|s| s.parse::<i64>().ok()) else { continue };
+        kept.push((id, path));
+    }
+    kept.sort();
+    Ok(kept)
+}
+
+/// Copy the store at `path`, checkpointed, to `out` (see the module docs).
+pub fn backup(path: &Path, out: &Path) -> anyhow::Result<BackupReport> {
+    let db = open(path)?;
+    let id = checkpoint(&db, path, None)?;
+    let tmp = partial(out);
+    std::fs::copy(path, &tmp).map_err(|e| anyhow::anyhow!("cannot write {:?}: {}", tmp, e))?;
+    std::fs::rename(&tmp, out).map_err(|e| anyhow::anyhow!("cannot write {:?}: {}", out, e))?;
+    Ok(BackupReport { id, since: None, entries: 0, bytes: size(out) })
+}
+
+/// Write to `out` what changed in the store at `path` since the backup, or
+/// snapshot, `since` (see the module docs).
+pub fn backup_since(path: &Path, since: i64, out: &Path) -> anyhow::Result<BackupReport> {
+    let db = open(path)?;
+    let Some((_, base_path)) = list(path)?.into_iter().chain(snapshots::list(path)?).find(|(id, _)| *id == since) else {
+        let kept: Vec<String> = list(path)?.iter().map(|(id, _)| id.to_string()).collect();
+        anyhow::bail!("{:?} keeps no backup or snapshot {} (backups kept: {}); take a full backup", path, since,
+                      if kept.is_empty() { "none".to_string() } else { kept.join(", ") });
+    };
+    // loaded first: the checkpoint may replace a snapshot of this second
+    let base = Raw::open(&base_path, true)?;
+    let to = checkpoint(&db, path, Some(since))?;
+    let core = db.handle.ptr;
+    let delta = Delta {
+        from: since,
+        to,
+        from_digest: unsafe { feather_digest(base.0) },
+        to_digest: unsafe { feather_digest(core) },
+        properties: properties(core),
+    };
+    let tmp = partial(out);
+    let written = (|| {
+        std::fs::write(&tmp, delta.encode())?;
+        let c_tmp = CString::new(tmp.to_str().ok_or_else(|| anyhow::anyhow!("path is not UTF-8: {:?}", tmp))?)?;
+        let entries = unsafe { feather_delta(base.0, core, c_tmp.as_ptr()) };
+        if entries < 0 { return Err(crate::last_error()); }
+        std::fs::rename(&tmp, out)?;
+        Ok(entries as usize)
+    })();
+    let entries = written.inspect_err(|_| { let _ = std::fs::remove_file(&tmp); })
+        .map_err(|e| anyhow::anyhow!("cannot write {:?}: {:#}", out, e))?;
+    Ok(BackupReport { id: to, since: Some(since), entries, bytes: size(out) })
+}
+
+/// Rebuild a store at `out`, which must not exist yet, from `base` and
+/// `deltas` in order (see the module docs). Returns the id of the state
+/// the result holds, the last delta's; None without deltas.
+pub fn restore(base: &Path, deltas: &[PathBuf], out: &Path) -> anyhow::Result<Option<i64>> {
+    anyhow::ensure!(!out.exists(), "{:?} exists already; restore to a new path", out);
+    anyhow::ensure!(base.is_file(), "no backup at {:?}", base);
+    let _lock = FileLock::acquire(out, LockMode::Exclusive)?;
+    let restored = (|| {
+        std::fs::copy(base, out).map_err(|e| anyhow::anyhow!("cannot write {:?}: {}", out, e))?;
+        let db = Raw::open(out, false)?;
+        let mut at: Option<i64> = None;
+        for path in deltas {
+            let bytes = std::fs::read(path).map_err(|e| anyhow::anyhow!("cannot read {:?}: {}", path, e))?;
+            let (delta, entries) = Delta::decode(&bytes).map_err(|e| anyhow::anyhow!("{:?}: {}", path, e))?;
+            anyhow::ensure!(unsafe { feather_digest(db.0) } == delta.from_digest,
+                            "{:?} was taken since {}, which is not what {} holds; give the backups in order, from the one the first delta was taken since",
+                            path, delta.from, at.map_or_else(|| format!("{:?}", base), |at| at.to_string()));
+            if unsafe { feather_set_properties(db.0, delta.properties.as_ptr().cast(), delta.properties.len()) } != 0
+                || unsafe { feather_apply_wal(db.0, entries.as_ptr().cast(), entries.len()) } != 0 {
+                return Err(crate::last_error());
+            }
+            anyhow::ensure!(unsafe { feather_digest(db.0) } == delta.to_digest,
+                            "{:?} did not rebuild the state {}; the delta is damaged", path, delta.to);
+            at = Some(delta.to);
+        }
+        unsafe { crate::feather_save(db.0) };
+        Ok(at)
+    })();
+    if restored.is_err() {
+        for leftover in [out.to_path_buf(), wal(out), crate::lock::lock_path(out)] {
+            let _ = std::fs::remove_file(leftover);
+        }
+    }
+    restored
+}
+
+// A core opened on its own, without the wrapper, closed when dropped;
+// `detached`, it never writes to its file.
+struct Raw(*mut c_void);
+
+impl Raw {
+    fn open(path: &Path, detached: bool) -> anyhow::Result<Raw> {
+        let c_path = CString::new(path.to_str().ok_or_else(|| anyhow::anyhow!("path is not UTF-8: {:?}", path))?)?;
+        let ptr = unsafe { crate::feather_open(c_path.as_ptr(), 0) };
+        anyhow::ensure!(!ptr.is_null(), "cannot open {:?}: {}", path, crate::last_error());
+        if detached { unsafe { crate::feather_detach(ptr) }; }
+        Ok(Raw(ptr))
+    }
+}
+
+impl Drop for Raw {
+    fn drop(&mut self) {
+        unsafe { crate::feather_close(self.0) }
+    }
+}
+
+fn open(path: &Path) -> anyhow::Result<DB> {
+    anyhow::ensure!(!path.is_dir(), "a sharded store is backed up shard file by shard file");
+    let db = OpenOptions::new().open(path)?;
+    db.writable()?;
+    Ok(db)
+}
+
+// Checkpoint `db` and keep the state as a backup's, returning its id; a
+// delta since `since`, or else a full backup.
+fn checkpoint(db: &DB, path: &Path, since: Option<i64>) -> anyhow::Result<i64> {
+    let dir = dir(path);
+    std::fs::create_dir_all(&dir).map_err(|e| anyhow::anyhow!("cannot create {:?}: {}", dir, e))?;
+    let kept = list(path)?;
+    // ids are seconds, but one taken twice would stand for two states
+    let now = crate::decay::now();
+    let id = kept.last().map_or(now, |&(last, _)| now.max(last + 1));
+    db.handle.checkpoint();
+    let state = dir.join(format!("{}.feather", id));
+    if std::fs::hard_link(path, &state).is_err() {
+        std::fs::copy(path, &state).map_err(|e| anyhow::anyhow!("cannot keep {:?}: {}", state, e))?;
+    }
+    // a full backup starts over; a delta keeps the full backup's state,
+    // the oldest, for the deltas after it to be taken since too
+    let keep = if since.is_some() { kept.first().map(|(id, _)| *id) } else { None };
+    for (old, file) in kept {
+        if Some(old) != keep {
+            std::fs::remove_file(&file).map_err(|e| anyhow::anyhow!("cannot remove {:?}: {}", file, e))?;
+        }
+    }
+    Ok(id)
+}
+
+fn properties(core: *mut c_void) -> Vec<u8> {
+    let n = unsafe { feather_properties(core, std::ptr::null_mut(), 0) };
+    let mut buf = vec![0u8; n];
+    unsafe { feather_properties(core, buf.as_mut_ptr().cast(), n) };
+    buf
+}
+
+// Where `out` is written before it is renamed into place.
+fn partial(out: &Path) -> PathBuf {
+    let mut name = out.as_os_str().to_owned();
+    name.push(".partial");
+    PathBuf::from(name)
+}
+
+fn wal(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".wal");
+    PathBuf::from(name)
+}
+
+fn size(path: &Path) -> u64 {
+    std::fs::metadata(path).map_or(0, |m| m.len())
+}
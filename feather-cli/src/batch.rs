@@ -0,0 +1,98 @@
+//! Adding many vectors to a store in one call (`feather add-batch`).
+//!
+//! The vectors come as a 2-D array, one per row, or as a directory of 1-D
+//! `<id>.npy` files. Ids come from a parallel 1-D array, from the metadata,
+//! or count up from a first id; metadata comes from a JSONL file whose i-th
+//! line describes row i, in any row shape `feather import` reads. The rows
+//! become `Record`s for `import::import`, so the duplicate-id policy and the
+//! dedup mode apply as for an import.
+
+use crate::{import, vectors, Record};
+use ndarray::{Array1, Array2, ArrayView2};
+use serde_json::{Map, Value};
+use std::io::BufRead;
+use std::path::Path;
+
+/// Ids from a 1-D `.npy` array of unsigned or signed 64- or 32-bit integers.
+pub fn read_ids(path: &Path) -> anyhow::Result<Vec<u64>> {
+    if let Ok(ids) = ndarray_npy::read_npy::<_, Array1<u64>>(path) {
+        return Ok(ids.to_vec());
+    }
+    let signed: Vec<i64> = match ndarray_npy::read_npy::<_, Array1<i64>>(path) {
+        Ok(ids) => ids.to_vec(),
+        Err(_) => ndarray_npy::read_npy::<_, Array1<i32>>(path)
+            .map_err(|e| anyhow::anyhow!("{:?}: expected a 1-D integer array: {}", path, e))?
+            .iter().map(|&id| i64::from(id)).collect(),
+    };
+    signed.into_iter()
+        .map(|id| u64::try_from(id).map_err(|_| anyhow::anyhow!("{:?}: negative id {}", path, id)))
+        .collect()
+}
+
+/// The vectors of a directory of `<id>.npy` files, one 1-D array each, as
+/// (ids, one row per id) in increasing id order. Other files are ignored.
+pub fn read_dir(dir: &Path) -> anyhow::Result<(Vec<u64>, Array2<f32>)> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir).map_err(|e| anyhow::anyhow!("{:?}: {}", dir, e))? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|e| e != "npy") { continue; }
+        let id = path.file_stem().and_then(|s| s.to_str()).and_then(|s| s.parse::<u64>().ok())
+            .ok_or_else(|| anyhow::anyhow!("{:?}: name the files <id>.npy", path))?;
+        files.push((id, path));
+    }
+    anyhow::ensure!(!files.is_empty(), "{:?} has no .npy files", dir);
+    files.sort();
+    let mut ids = Vec::with_capacity(files.len());
+    let mut data = Vec::new();
+    let mut dim = None;
+    for (id, path) in files {
+        let vector = vectors::read_vector(&path)?;
+        let expected = *dim.get_or_insert(vector.len());
+        anyhow::ensure!(vector.len() == expected, "{:?} has {} dims, the others {}", path, vector.len(), expected);
+        ids.push(id);
+        data.extend(vector);
+    }
+    let rows = ids.len();
+    Ok((ids, Array2::from_shape_vec((rows, dim.unwrap_or(0)), data)?))
+}
+
+/// One record per row of `vectors`, in `modality`. Row i takes its metadata
+/// from line i of `meta` (JSONL, no vectors of its own) and its id from
+/// `ids[i]`, else from that line's `id`, else `first_id + i`. Rows without a
+/// timestamp get `timestamp`.
+pub fn records<R: BufRead>(vectors: ArrayView2<f32>, ids: Option<&[u64]>, meta: Option<R>, first_id: u64,
+                           modality: &str, timestamp: i64) -> anyhow::Result<Vec<Record>> {
+    let rows = vectors.nrows();
+    if let Some(ids) = ids {
+        anyhow::ensure!(ids.len() == rows, "{} ids for {} vectors", ids.len(), rows);
+    }
+    let mut objects: Vec<Map<String, Value>> = Vec::with_capacity(rows);
+    if let Some(meta) = meta {
+        for (i, line) in meta.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() { continue; }
+            objects.push(serde_json::from_str(&line).map_err(|e| anyhow::anyhow!("meta line {}: {}", i + 1, e))?);
+        }
+        anyhow::ensure!(objects.len() == rows, "{} metadata rows for {} vectors", objects.len(), rows);
+    } else {
+        objects.resize(rows, Map::new());
+    }
+    let mut records = Vec::with_capacity(rows);
+    for (i, (mut obj, row)) in objects.into_iter().zip(vectors.rows()).enumerate() {
+        let id = match (ids, obj.get("id")) {
+            (Some(ids), Some(given)) if given.as_u64() != Some(ids[i]) => {
+                anyhow::bail!("meta row {}: id {} but the ids array says {}", i + 1, given, ids[i])
+            }
+            (Some(ids), _) => ids[i],
+            (None, Some(given)) => given.as_u64().ok_or_else(|| anyhow::anyhow!("meta row {}: bad id {}", i + 1, given))?,
+            (None, None) => first_id + i as u64,
+        };
+        obj.insert("id".into(), id.into());
+        obj.entry("timestamp").or_insert(timestamp.into());
+        let mut record = import::record_from_json(obj, modality).map_err(|e| anyhow::anyhow!("meta row {}: {}", i + 1, e))?;
+        anyhow::ensure!(record.vectors.is_empty(), "meta row {}: vectors come from the array, not the metadata", i + 1);
+        record.vectors.insert(modality.to_string(), row.to_vec());
+        records.push(record);
+    }
+    Ok(records)
+}
@@ -0,0 +1,134 @@
+//! Measuring a store on a given workload (`feather bench`).
+//!
+//! `run` inserts a set of vectors into an empty store, then answers a set of
+//! queries with the HNSW index once per search beam width (`ef`), timing
+//! each query and checking its hits against an exact brute-force scan. The
+//! report gives insert throughput, latency percentiles and recall@k per
+//! `ef`, which is what tuning `ef` trades between.
+
+use crate::{Metadata, DB};
+use ndarray::{Array2, ArrayView2};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+/// Vectors `feather bench` inserts and queries it runs unless told otherwise.
+pub const DEFAULT_COUNT: usize = 10_000;
+pub const DEFAULT_QUERIES: usize = 100;
+
+#[derive(Clone, Debug, Default)]
+pub struct BenchReport {
+    pub inserted: usize,
+    pub insert_time: Duration,
+    pub queries: usize,
+    pub k: usize,
+    /// Mean time of one exact brute-force query, for comparison.
+    pub brute_force: Duration,
+    /// One run over the queries per `ef`, in the order given.
+    pub runs: Vec<EfRun>,
+}
+
+impl BenchReport {
+    pub fn inserts_per_sec(&self) -> f64 {
+        self.inserted as f64 / self.insert_time.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// The queries answered at one `ef`.
+#[derive(Clone, Debug, Default)]
+pub struct EfRun {
+    pub ef: usize,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub mean: Duration,
+    pub qps: f64,
+    /// Mean fraction of the exact k nearest neighbours returned.
+    pub recall: f64,
+}
+
+/// `n` vectors of `dim` values uniform in [-0.5, 0.5), the same for the
+/// same `seed`.
+pub fn random_vectors(n: usize, dim: usize, seed: u64) -> Array2<f32> {
+    // xorshift never leaves zero, so keep the state off it
+    let mut state = seed ^ 0x9E37_79B9_7F4A_7C15;
+    if state == 0 { state = 1; }
+    Array2::from_shape_fn((n, dim), |_| {
+        state ^= state << 13; state ^= state >> 7; state ^= state << 17;
+        (state >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+    })
+}
+
+/// Insert `data` (ids 1..=n) into the empty `db` in batches of `batch_size`,
+/// then answer each row of `queries` for its `k` nearest neighbours once per
+/// entry of `efs`.
+pub fn run(db: &DB, data: ArrayView2<f32>, queries: ArrayView2<f32>, k: usize, efs: &[usize],
+           batch_size: usize) -> anyhow::Result<BenchReport> {
+    anyhow::ensure!(data.nrows() > 0, "no vectors to insert");
+    anyhow::ensure!(queries.nrows() > 0, "no queries");
+    anyhow::ensure!(queries.ncols() == data.ncols(), "queries have dim {}, vectors {}", queries.ncols(), data.ncols());
+    anyhow::ensure!(k > 0, "k must be positive");
+    anyhow::ensure!(efs.iter().all(|&ef| ef > 0), "ef must be positive");
+    let mut report = BenchReport { inserted: data.nrows(), queries: queries.nrows(), k, ..Default::default() };
+
+    let start = Instant::now();
+    for (chunk, rows) in data.axis_chunks_iter(ndarray::Axis(0), batch_size.max(1)).enumerate() {
+        let first = (chunk * batch_size.max(1)) as u64 + 1;
+        let ids: Vec<u64> = (first..first + rows.nrows() as u64).collect();
+        let vecs: Vec<Vec<f32>> = rows.rows().into_iter().map(|r| r.to_vec()).collect();
+        db.add_batch(&ids, &vecs, &vec![Metadata::default(); ids.len()], "text")?;
+    }
+    report.insert_time = start.elapsed();
+
+    let start = Instant::now();
+    let truth: Vec<HashSet<u64>> = queries.rows().into_iter().map(|q| exact(data, &q.to_vec(), k)).collect();
+    report.brute_force = start.elapsed() / queries.nrows() as u32;
+
+    for &ef in efs {
+        report.runs.push(measure(db, queries, &truth, k, ef, "text")?);
+    }
+    Ok(report)
+}
+
+// Answer each row of `queries` from the HNSW index of `modality` at beam
+// width `ef`, timing it and checking its hits against `truth`, the exact
+// neighbours of the same row.
+pub(crate) fn measure(db: &DB, queries: ArrayView2<f32>, truth: &[HashSet<u64>], k: usize, ef: usize,
+                      modality: &str) -> anyhow::Result<EfRun> {
+    db.set_ef(ef, Some(modality))?;
+    let mut times = Vec::with_capacity(queries.nrows());
+    let mut found = 0;
+    for (query, truth) in queries.rows().into_iter().zip(truth) {
+        let query = query.to_vec();
+        let start = Instant::now();
+        let hits = db.knn(&query, k, modality)?;
+        times.push(start.elapsed());
+        found += hits.iter().filter(|(id, _)| truth.contains(id)).count();
+    }
+    let total: Duration = times.iter().sum();
+    times.sort();
+    Ok(EfRun {
+        ef,
+        p50: percentile(&times, 50.0),
+        p95: percentile(&times, 95.0),
+        p99: percentile(&times, 99.0),
+        mean: total / times.len() as u32,
+        qps: times.len() as f64 / total.as_secs_f64().max(f64::EPSILON),
+        recall: found as f64 / truth.iter().map(|t| t.len()).sum::<usize>().max(1) as f64,
+    })
+}
+
+// Ids (1-based row numbers) of the k rows of `data` nearest `query` in L2.
+fn exact(data: ArrayView2<f32>, query: &[f32], k: usize) -> HashSet<u64> {
+    let mut dists: Vec<(f32, u64)> = data.rows().into_iter().enumerate()
+        .map(|(i, row)| (row.iter().zip(query).map(|(a, b)| (a - b) * (a - b)).sum(), i as u64 + 1))
+        .collect();
+    let k = k.min(dists.len());
+    dists.select_nth_unstable_by(k - 1, |a, b| a.0.total_cmp(&b.0));
+    dists[..k].iter().map(|&(_, id)| id).collect()
+}
+
+// Nearest-rank percentile of sorted, non-empty `times`.
+pub(crate) fn percentile(times: &[Duration], p: f64) -> Duration {
+    let rank = ((p / 100.0) * times.len() as f64).ceil() as usize;
+    times[rank.clamp(1, times.len()) - 1]
+}
@@ -0,0 +1,165 @@
+//! Building a new store from bulk files in one pass (`feather bootstrap`).
+//!
+//! The inputs are the shape data usually arrives in: a 2-D array with one
+//! vector per row, optionally a CSV whose i-th row describes the i-th vector,
+//! and optionally a CSV of links between records. Records and their edges
+//! are assembled up front and inserted in large `add_batch` calls, so the
+//! index is built by the core's parallel loader (`FEATHER_LOAD_THREADS` caps
+//! its workers). `check` then verifies what was written.
+
+use crate::{graph, Edge, Link, ProgressFn, Record, DB};
+use ndarray::ArrayView2;
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+
+/// Records inserted per `add_batch` call unless told otherwise; larger than
+/// the import default since the whole input is known to be well-formed.
+pub const DEFAULT_BATCH_SIZE: usize = 10_000;
+
+/// Vectors looked up by `check` to confirm the index finds them.
+const LOOKUP_SAMPLES: usize = 100;
+const LOOKUP_K: usize = 10;
+
+/// Links CSV with a header row: `from` and `to` (alias `source` / `target`)
+/// ids, then optional `rel_type` (default `related_to`) and `weight`
+/// (default 1.0) columns.
+pub fn read_links<R: Read>(input: R) -> anyhow::Result<Vec<Link>> {
+    let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(input);
+    let headers: Vec<String> = reader.headers()?.iter().map(|h| h.trim().to_string()).collect();
+    let column = |names: &[&str]| headers.iter().position(|h| names.contains(&h.as_str()));
+    let from = column(&["from", "source"]).ok_or_else(|| anyhow::anyhow!("links: missing `from` column"))?;
+    let to = column(&["to", "target"]).ok_or_else(|| anyhow::anyhow!("links: missing `to` column"))?;
+    let (rel_type, weight) = (column(&["rel_type"]), column(&["weight"]));
+
+    let mut links = Vec::new();
+    for (i, row) in reader.records().enumerate() {
+        let parsed = row.map_err(anyhow::Error::from).and_then(|row| {
+            let field = |c: Option<usize>| c.and_then(|c| row.get(c)).map(str::trim).filter(|s| !s.is_empty());
+            let id = |c: usize, name: &str| -> anyhow::Result<u64> {
+                let raw = field(Some(c)).ok_or_else(|| anyhow::anyhow!("missing `{}`", name))?;
+                raw.parse().map_err(|e| anyhow::anyhow!("`{}`: {}", raw, e))
+            };
+            Ok(Link {
+                from: id(from, "from")?,
+                to: id(to, "to")?,
+                rel_type: field(rel_type).unwrap_or(graph::DEFAULT_REL_TYPE).to_string(),
+                weight: field(weight).map(str::parse).transpose()?.unwrap_or(1.0),
+            })
+        });
+        links.push(parsed.map_err(|e| anyhow::anyhow!("links row {}: {}", i + 2, e))?);
+    }
+    Ok(links)
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BootstrapReport {
+    pub records: usize,
+    /// Edges attached (duplicate links counted once).
+    pub links: usize,
+    pub batches: usize,
+    pub check: CheckReport,
+}
+
+/// What `check` found.
+#[derive(Clone, Debug, Default)]
+pub struct CheckReport {
+    /// Records expected.
+    pub records: usize,
+    /// Expected ids with no vector in the modality.
+    pub missing: Vec<u64>,
+    /// `(from, to)` edges whose target has no record.
+    pub dangling: Vec<(u64, u64)>,
+    /// Vectors looked up in the index.
+    pub lookups: usize,
+    /// Ids a lookup of their own vector did not return.
+    pub misses: Vec<u64>,
+}
+
+impl CheckReport {
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.dangling.is_empty() && self.misses.is_empty()
+    }
+}
+
+/// Fill the empty `db` with one record per row of `vectors` under
+/// `modality`. Row i takes its id and metadata from the i-th item of `meta`,
+/// else id i + 1 and empty metadata; `meta` must hold exactly one item per
+/// vector and no vectors of its own. Every link must join two of these
+/// records. `progress` is as for `import`. Runs `check` at the end; does
+/// not save.
+pub fn bootstrap<M>(db: &DB, vectors: ArrayView2<f32>, meta: Option<M>, links: &[Link], modality: &str,
+                    batch_size: usize, progress: Option<&mut ProgressFn>) -> anyhow::Result<BootstrapReport>
+where
+    M: IntoIterator<Item = anyhow::Result<Record>>,
+{
+    anyhow::ensure!(db.all_ids().is_empty(), "bootstrap needs an empty store; use `feather import` to add to one");
+    let rows = vectors.nrows();
+    let mut records: Vec<Record> = match meta {
+        None => (1..=rows as u64).map(|id| Record { id, ..Record::default() }).collect(),
+        Some(meta) => {
+            let records = meta.into_iter().collect::<anyhow::Result<Vec<Record>>>()?;
+            anyhow::ensure!(records.len() == rows, "{} metadata rows for {} vectors", records.len(), rows);
+            if let Some(r) = records.iter().find(|r| !r.vectors.is_empty()) {
+                anyhow::bail!("record {}: vectors come from the array, not the metadata", r.id);
+            }
+            records
+        }
+    };
+
+    let mut index = HashMap::with_capacity(rows);
+    for (i, r) in records.iter().enumerate() {
+        if index.insert(r.id, i).is_some() {
+            anyhow::bail!("duplicate id {}", r.id);
+        }
+    }
+    let mut attached = 0;
+    for (i, link) in links.iter().enumerate() {
+        let Some(&from) = index.get(&link.from) else {
+            anyhow::bail!("link {} ({} -> {}): no record {}", i + 1, link.from, link.to, link.from);
+        };
+        anyhow::ensure!(index.contains_key(&link.to), "link {} ({} -> {}): no record {}", i + 1, link.from, link.to, link.to);
+        let edges = &mut records[from].metadata.edges;
+        if !edges.iter().any(|e| e.target == link.to && e.rel_type == link.rel_type) {
+            edges.push(Edge { target: link.to, rel_type: link.rel_type.clone(), weight: link.weight });
+            attached += 1;
+        }
+    }
+
+    let ids: Vec<u64> = records.iter().map(|r| r.id).collect();
+    for (r, row) in records.iter_mut().zip(vectors.rows()) {
+        r.vectors.insert(modality.to_string(), row.to_vec());
+    }
+    let imported = crate::import::import(db, records.into_iter().map(Ok), batch_size, progress)?;
+    Ok(BootstrapReport {
+        records: imported.records,
+        links: attached,
+        batches: imported.batches,
+        check: check(db, &ids, vectors, modality)?,
+    })
+}
+
+/// Verify a store holds `ids`, row i of `vectors` being the vector of
+/// `ids[i]` in `modality`: every id has a vector, no edge of theirs dangles,
+/// and a sample of the vectors finds its own record through the index.
+pub fn check(db: &DB, ids: &[u64], vectors: ArrayView2<f32>, modality: &str) -> anyhow::Result<CheckReport> {
+    let mut report = CheckReport { records: ids.len(), ..CheckReport::default() };
+    let indexed: HashSet<u64> = db.ids(modality).into_iter().collect();
+    let known: HashSet<u64> = db.all_ids().into_iter().collect();
+    for &id in ids {
+        if !indexed.contains(&id) { report.missing.push(id); }
+        for edge in db.get_metadata(id).map(|m| m.edges).unwrap_or_default() {
+            if !known.contains(&edge.target) { report.dangling.push((id, edge.target)); }
+        }
+    }
+    let step = ids.len().div_ceil(LOOKUP_SAMPLES).max(1);
+    for (i, &id) in ids.iter().enumerate().step_by(step) {
+        let hits = db.knn(&vectors.row(i).to_vec(), LOOKUP_K, modality)?;
+        report.lookups += 1;
+        // a full page of exact duplicates can crowd the record itself out
+        let crowded = hits.len() == LOOKUP_K && hits.last().is_some_and(|&(_, d)| d == 0.0);
+        if !hits.iter().any(|&(hit, _)| hit == id) && !crowded {
+            report.misses.push(id);
+        }
+    }
+    Ok(report)
+}
@@ -0,0 +1,199 @@
+//! A memory budget: caps on the records, or bytes, a file keeps (`feather
+//! budget`).
+//!
+//! An agent writing unattended grows its memory without bound. With a
+//! budget set, every `save()` first checks the live records against it and,
+//! while either cap is exceeded, forgets the record least worth keeping.
+//! A record's retention is
+//!
+//! ```text
+//! importance × 0.5^(idle / RETENTION_HALF_LIFE) × (1 + ln(1 + recall_count))
+//! ```
+//!
+//! `idle` being the time since its last activity, its timestamp or last
+//! recall: low importance, old age and rare retrieval all push a record out
+//! first. Ties go to the lower id.
+//!
+//! Bytes count the live data — vectors, content, source, tags, attributes
+//! and links — not the file: evicted records leave search at once and
+//! `compact()` reclaims their space. The budget covers the whole file,
+//! every collection included, and is kept in its properties. Enforcing it
+//! reads every record, so each save of a budgeted file costs a pass over
+//! them.
+
+use crate::decay::{self, Decay};
+use crate::trace::{Level, Span};
+use crate::{Metadata, DB};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Property key holding the budget, in its text form.
+pub(crate) const PROPERTY_KEY: &str = "budget";
+
+/// Seconds of inactivity that halve a record's retention: 30 days.
+pub const RETENTION_HALF_LIFE: f64 = 30.0 * 86_400.0;
+
+// Bytes of a record's fixed-size fields, and of a link besides its type.
+const RECORD_BYTES: u64 = 64;
+const EDGE_BYTES: u64 = 12;
+
+/// Caps on a file's live records; at least one is set.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Budget {
+    pub max_records: Option<usize>,
+    pub max_bytes: Option<u64>,
+}
+
+impl Budget {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(self.max_records.is_some() || self.max_bytes.is_some(),
+                        "a budget needs max_records or max_bytes");
+        anyhow::ensure!(self.max_records != Some(0) && self.max_bytes != Some(0), "budget caps must be positive");
+        Ok(())
+    }
+
+    fn exceeded_by(&self, usage: &Usage) -> bool {
+        self.max_records.is_some_and(|max| usage.records > max) || self.max_bytes.is_some_and(|max| usage.bytes > max)
+    }
+}
+
+impl fmt::Display for Budget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut caps = Vec::new();
+        if let Some(max) = self.max_records { caps.push(format!("max_records={}", max)); }
+        if let Some(max) = self.max_bytes { caps.push(format!("max_bytes={}", max)); }
+        f.write_str(&caps.join(","))
+    }
+}
+
+impl FromStr for Budget {
+    type Err = String;
+
+    /// `max_records=N` and/or `max_bytes=SIZE`, separated by commas.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut budget = Budget::default();
+        for pair in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (name, value) = pair.split_once('=').ok_or_else(|| format!("expected NAME=VALUE, got `{}`", pair))?;
+            match name.trim() {
+                "max_records" => budget.max_records = Some(value.trim().parse().map_err(|_| format!("bad record count `{}`", value))?),
+                "max_bytes" => budget.max_bytes = Some(parse_bytes(value)?),
+                other => return Err(format!("unknown cap `{}` (expected max_records or max_bytes)", other)),
+            }
+        }
+        budget.validate().map_err(|e| e.to_string())?;
+        Ok(budget)
+    }
+}
+
+/// A byte count such as `500000`, `64K`, `200MB` or `2G` (powers of 1000).
+pub fn parse_bytes(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let digits = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+    let (num, unit) = s.split_at(digits);
+    let scale = match unit.trim().to_ascii_uppercase().trim_end_matches('B') {
+        "" => 1.0,
+        "K" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        "T" => 1e12,
+        _ => return Err(format!("unknown unit in `{}` (expected K, M, G or T)", s)),
+    };
+    let num: f64 = num.parse().map_err(|_| format!("bad size `{}`", s))?;
+    Ok((num * scale) as u64)
+}
+
+/// What a file's live records take up.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub records: usize,
+    pub bytes: u64,
+}
+
+/// How much a record is worth keeping at `now` (see the module docs).
+pub fn retention(meta: &Metadata, now: i64) -> f32 {
+    let decay = Decay { half_life: RETENTION_HALF_LIFE, floor: 0.0 };
+    decay.importance(meta, 0, now) * (1.0 + (meta.recall_count as f32).ln_1p())
+}
+
+impl DB {
+    /// The file's budget, if one is set.
+    pub fn budget(&self) -> Option<Budget> {
+        let raw = self.property(PROPERTY_KEY)?;
+        String::from_utf8_lossy(&raw).parse().ok()
+    }
+
+    /// Cap the file's live records (see the module docs), or with None lift
+    /// the caps; persists on `save()`, which enforces the new budget.
+    pub fn set_budget(&self, budget: Option<Budget>) -> anyhow::Result<()> {
+        self.writable()?;
+        match budget {
+            Some(budget) => {
+                budget.validate()?;
+                self.set_property(PROPERTY_KEY, budget.to_string().as_bytes())?;
+            }
+            None => { self.remove_property(PROPERTY_KEY)?; }
+        }
+        Ok(())
+    }
+
+    /// The live records of the whole file and the bytes the budget counts.
+    pub fn usage(&self) -> Usage {
+        self.live().iter().fold(Usage::default(), |usage, (_, _, bytes)| Usage {
+            records: usage.records + 1,
+            bytes: usage.bytes + bytes,
+        })
+    }
+
+    /// Forget the records least worth keeping until the file is within its
+    /// budget; `save()` does this first. Returns the number evicted: none
+    /// without a budget or on a read-only handle.
+    pub fn enforce_budget(&self) -> usize {
+        if self.is_read_only() { return 0; }
+        let Some(budget) = self.budget() else { return 0 };
+        let mut records = self.live();
+        let mut usage = Usage {
+            records: records.len(),
+            bytes: records.iter().map(|(_, _, bytes)| bytes).sum(),
+        };
+        if !budget.exceeded_by(&usage) { return 0; }
+        let mut span = Span::new(Level::Info, "feather::evict");
+        records.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        let mut evicted = 0;
+        for (id, _, bytes) in records {
+            if !budget.exceeded_by(&usage) { break; }
+            self.handle.forget(id);
+            usage.records -= 1;
+            usage.bytes -= bytes;
+            evicted += 1;
+        }
+        span.record("evicted", evicted).record("records", usage.records).record("bytes", usage.bytes);
+        evicted
+    }
+
+    // Every live record of the file as (internal id, retention, bytes).
+    fn live(&self) -> Vec<(u64, f32, u64)> {
+        let mut vector_bytes: HashMap<u64, u64> = HashMap::new();
+        for modality in self.handle.modalities() {
+            let bytes = 4 * self.handle.dim(Some(&modality)) as u64;
+            for id in self.handle.ids(Some(&modality)) {
+                *vector_bytes.entry(id).or_default() += bytes;
+            }
+        }
+        let now = decay::now();
+        self.handle.all_ids().into_iter()
+            .filter_map(|id| {
+                let meta = self.handle.meta(id).filter(|m| !m.is_forgotten())?;
+                let bytes = record_bytes(&meta) + vector_bytes.get(&id).copied().unwrap_or(0);
+                Some((id, retention(&meta, now), bytes))
+            })
+            .collect()
+    }
+}
+
+fn record_bytes(meta: &Metadata) -> u64 {
+    let text = meta.source.len() + meta.content.len() + meta.tags_json.len() + meta.namespace_id.len()
+        + meta.entity_id.len() + meta.attributes.iter().map(|(k, v)| k.len() + v.len()).sum::<usize>();
+    let links: u64 = meta.edges.iter().map(|e| EDGE_BYTES + e.rel_type.len() as u64).sum();
+    RECORD_BYTES + text as u64 + links
+}
@@ -0,0 +1,64 @@
+//! Query vectors made from stored records (`DB::centroid`, `DB::combine`,
+//! `feather search --like` / `--vector-expr`).
+//!
+//! "Find memories like these three" searches by the centroid of the three
+//! records' vectors; an analogy such as "12 - 7 + 30" searches by a signed
+//! sum of them. Both read the vectors as stored, so in a file that
+//! normalizes or projects, they are already in the index's space, and the
+//! search scales the result like any other query.
+
+use crate::DB;
+
+impl DB {
+    /// The mean of the vectors records `ids` hold in `modality`. Fails if
+    /// `ids` is empty or a record has no vector there.
+    pub fn centroid(&self, ids: &[u64], modality: &str) -> anyhow::Result<Vec<f32>> {
+        anyhow::ensure!(!ids.is_empty(), "a centroid needs at least one record");
+        let weight = 1.0 / ids.len() as f32;
+        let terms: Vec<(f32, u64)> = ids.iter().map(|&id| (weight, id)).collect();
+        self.combine(&terms, modality)
+    }
+
+    /// The sum of the vectors records hold in `modality`, each scaled by its
+    /// weight: `[(1.0, a), (-1.0, b), (1.0, c)]` is `a - b + c`. Fails if
+    /// `terms` is empty or a record has no vector there.
+    pub fn combine(&self, terms: &[(f32, u64)], modality: &str) -> anyhow::Result<Vec<f32>> {
+        anyhow::ensure!(!terms.is_empty(), "a combination needs at least one record");
+        let mut sum: Vec<f32> = Vec::new();
+        for &(weight, id) in terms {
+            let v = self.get_vector(id, modality)
+                .filter(|_| self.get_metadata(id).is_some_and(|m| !m.is_forgotten()))
+                .ok_or_else(|| anyhow::anyhow!("record {} has no '{}' vector", id, modality))?;
+            if sum.is_empty() { sum = vec![0.0; v.len()]; }
+            anyhow::ensure!(v.len() == sum.len(), "record {}: dim {} differs from dim {}", id, v.len(), sum.len());
+            for (s, x) in sum.iter_mut().zip(&v) {
+                *s += weight * x;
+            }
+        }
+        Ok(sum)
+    }
+}
+
+/// The terms of an expression like `12 - 7 + 30` or `0.5*12 + 0.5*30`:
+/// record ids joined by `+` and `-`, each optionally scaled by
+/// `<weight>*`, as `(weight, id)` for `DB::combine`.
+pub fn parse_terms(expr: &str) -> anyhow::Result<Vec<(f32, u64)>> {
+    let bad = || anyhow::anyhow!("expected ids joined by + and -, e.g. \"12 - 7 + 30\", got {:?}", expr);
+    let mut terms = Vec::new();
+    for (i, term) in expr.replace('-', "+-").split('+').enumerate() {
+        let term = term.trim();
+        // "-12 + 3" leaves an empty term before the first sign
+        if term.is_empty() && i == 0 { continue; }
+        let (sign, term) = match term.strip_prefix('-') {
+            Some(rest) => (-1.0, rest.trim()),
+            None => (1.0, term),
+        };
+        let (weight, id) = match term.split_once('*') {
+            Some((w, id)) => (w.trim().parse::<f32>().map_err(|_| bad())?, id.trim()),
+            None => (1.0, term),
+        };
+        terms.push((sign * weight, id.parse::<u64>().map_err(|_| bad())?));
+    }
+    if terms.is_empty() { return Err(bad()); }
+    Ok(terms)
+}
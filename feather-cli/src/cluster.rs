@@ -0,0 +1,143 @@
+//! k-means over stored vectors (`DB::cluster`, `feather cluster`).
+//!
+//! Groups the live records of a modality into `k` clusters, seeded by
+//! k-means++ from a fixed seed, or the store's (see `seed`), so the same
+//! store always clusters the same way, then refined by Lloyd rounds until no record changes cluster. The
+//! clusters are labelled by size, largest first, and each record's label is
+//! written to its `cluster` attribute, where a search filter such as
+//! `attr.cluster = '3'` can find it; a record whose label did not change is
+//! not rewritten. Records without a vector in the modality keep whatever
+//! label they had.
+
+use crate::DB;
+use serde::Serialize;
+
+/// Attribute holding a record's cluster label.
+pub const CLUSTER_ATTRIBUTE: &str = "cluster";
+
+/// Default Lloyd rounds; most stores settle well before.
+pub const DEFAULT_ITERATIONS: usize = 25;
+
+/// One cluster of records.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Cluster {
+    /// Position by size, from 0 for the largest cluster.
+    pub label: usize,
+    /// The records in the cluster, nearest the centroid first.
+    pub members: Vec<u64>,
+    /// Mean L2 distance of the members to the centroid.
+    pub spread: f32,
+}
+
+impl DB {
+    /// Cluster the live records of `modality` into at most `k` clusters
+    /// (fewer if there are fewer records, or a cluster ends up empty) in
+    /// at most `iterations` Lloyd rounds, and label them (see the module
+    /// docs). With `dry_run`, only find the clusters.
+    pub fn cluster(&self, modality: &str, k: usize, iterations: usize, dry_run: bool) -> anyhow::Result<Vec<Cluster>> {
+        anyhow::ensure!(k > 0, "k must be at least 1");
+        if !dry_run { self.writable()?; }
+        let mut ids = Vec::new();
+        let mut vectors: Vec<Vec<f32>> = Vec::new();
+        // in id order, not the index's, which a parallel load shuffles
+        let mut all = self.ids(modality);
+        all.sort_unstable();
+        for id in all {
+            if self.get_metadata(id).is_none_or(|m| m.is_forgotten()) { continue; }
+            let Some(v) = self.get_vector(id, modality) else { continue };
+            if let Some(first) = vectors.first() {
+                anyhow::ensure!(v.len() == first.len(), "record {}: dim {} differs from dim {}", id, v.len(), first.len());
+            }
+            ids.push(id);
+            vectors.push(v);
+        }
+        if vectors.is_empty() { return Ok(Vec::new()); }
+
+        let mut centroids = seed(&vectors, k.min(vectors.len()), self.seed().unwrap_or(0));
+        let mut assignment = vec![usize::MAX; vectors.len()];
+        for _ in 0..iterations.max(1) {
+            let mut moved = false;
+            for (v, a) in vectors.iter().zip(assignment.iter_mut()) {
+                let nearest = nearest(&centroids, v);
+                if *a != nearest { *a = nearest; moved = true; }
+            }
+            if !moved { break; }
+            let mut sums = vec![vec![0.0f32; vectors[0].len()]; centroids.len()];
+            let mut counts = vec![0usize; centroids.len()];
+            for (v, &a) in vectors.iter().zip(&assignment) {
+                counts[a] += 1;
+                for (s, x) in sums[a].iter_mut().zip(v) { *s += x; }
+            }
+            // an emptied cluster keeps its centroid
+            for ((c, s), &n) in centroids.iter_mut().zip(sums).zip(&counts) {
+                if n > 0 { *c = s.into_iter().map(|x| x / n as f32).collect(); }
+            }
+        }
+
+        let mut groups: Vec<Vec<(f32, u64)>> = vec![Vec::new(); centroids.len()];
+        for ((&id, v), &a) in ids.iter().zip(&vectors).zip(&assignment) {
+            groups[a].push((distance2(&centroids[a], v).sqrt(), id));
+        }
+        groups.retain(|g| !g.is_empty());
+        groups.sort_by_key(|g| std::cmp::Reverse(g.len()));
+        let mut clusters = Vec::with_capacity(groups.len());
+        for (label, mut group) in groups.into_iter().enumerate() {
+            group.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+            let spread = group.iter().map(|(d, _)| d).sum::<f32>() / group.len() as f32;
+            let members: Vec<u64> = group.into_iter().map(|(_, id)| id).collect();
+            if !dry_run {
+                let value = label.to_string();
+                for &id in &members {
+                    let current = self.get_metadata(id).and_then(|m| m.attributes.get(CLUSTER_ATTRIBUTE).cloned());
+                    if current.as_deref() != Some(value.as_str()) {
+                        self.set_attribute(id, CLUSTER_ATTRIBUTE, &value)?;
+                    }
+                }
+            }
+            clusters.push(Cluster { label, members, spread });
+        }
+        Ok(clusters)
+    }
+}
+
+// k-means++: the first centroid is a pseudo-random vector, each further one
+// is drawn with probability proportional to its squared distance from the
+// nearest centroid so far; the draws follow `from`.
+fn seed(vectors: &[Vec<f32>], k: usize, from: u64) -> Vec<Vec<f32>> {
+    // xorshift never leaves zero, so keep the state off it
+    let mut state = from ^ 0x9E37_79B9_7F4A_7C15;
+    if state == 0 { state = 1; }
+    let mut next = || {
+        state ^= state << 13; state ^= state >> 7; state ^= state << 17;
+        (state >> 11) as f64 / (1u64 << 53) as f64
+    };
+    let mut centroids = vec![vectors[(next() * vectors.len() as f64) as usize].clone()];
+    let mut nearest2: Vec<f32> = vectors.iter().map(|v| distance2(&centroids[0], v)).collect();
+    while centroids.len() < k {
+        let total: f64 = nearest2.iter().map(|&d| d as f64).sum();
+        // every vector sits on a centroid: no distinct one left to add
+        if total <= 0.0 { break; }
+        let mut target = next() * total;
+        let mut pick = nearest2.iter().rposition(|&d| d > 0.0).expect("total > 0");
+        for (i, &d) in nearest2.iter().enumerate() {
+            target -= d as f64;
+            if target < 0.0 && d > 0.0 { pick = i; break; }
+        }
+        let c = vectors[pick].clone();
+        for (n, v) in nearest2.iter_mut().zip(vectors) { *n = n.min(distance2(&c, v)); }
+        centroids.push(c);
+    }
+    centroids
+}
+
+// The index of the centroid nearest `v`.
+fn nearest(centroids: &[Vec<f32>], v: &[f32]) -> usize {
+    centroids.iter().enumerate()
+        .map(|(i, c)| (i, distance2(c, v)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .expect("at least one centroid").0
+}
+
+fn distance2(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum()
+}
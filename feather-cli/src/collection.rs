@@ -0,0 +1,80 @@
+//! Named collections: independent id spaces inside one database file.
+//!
+//! A collection is registered under a 16-bit index. Its records are stored
+//! under internal ids `index << 48 | id` and its vectors in modality indexes
+//! named `<collection>::<modality>`, so searches never cross collections. A
+//! `DB` handle scoped to a collection (`DB::collection`) translates both on
+//! the way in and out; the unscoped handle sees everything that belongs to no
+//! registered collection. The registry lives in the DB properties.
+
+use std::collections::BTreeMap;
+
+/// Property key holding the collection registry.
+pub(crate) const PROPERTY_KEY: &str = "collections";
+
+/// Bits of the internal id left to the collection-local id.
+pub const ID_BITS: u32 = 48;
+
+/// Largest id usable inside a named collection.
+pub const MAX_ID: u64 = (1 << ID_BITS) - 1;
+
+/// Separator between the collection and modality names of a scoped index.
+pub(crate) const MODALITY_SEP: &str = "::";
+
+/// Which collection a `DB` handle is looking at.
+#[derive(Clone, Debug)]
+pub(crate) struct Scope {
+    pub name: String,
+    pub index: u16,
+}
+
+impl Scope {
+    pub fn internal_id(&self, id: u64) -> anyhow::Result<u64> {
+        anyhow::ensure!(id <= MAX_ID, "id {} is too large for collection '{}' (max {})", id, self.name, MAX_ID);
+        Ok((self.index as u64) << ID_BITS | id)
+    }
+
+    pub fn external_id(&self, internal: u64) -> Option<u64> {
+        (internal >> ID_BITS == self.index as u64).then_some(internal & MAX_ID)
+    }
+
+    pub fn modality(&self, modality: &str) -> String {
+        format!("{}{}{}", self.name, MODALITY_SEP, modality)
+    }
+}
+
+pub(crate) fn validate_name(name: &str) -> anyhow::Result<()> {
+    anyhow::ensure!(!name.is_empty() && name.len() <= 64
+                    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'),
+                    "invalid collection name {:?}: use 1-64 of [A-Za-z0-9_-]", name);
+    Ok(())
+}
+
+pub(crate) fn encode(map: &BTreeMap<String, u16>) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend((map.len() as u32).to_le_bytes());
+    for (name, index) in map {
+        out.extend((name.len() as u16).to_le_bytes());
+        out.extend(name.as_bytes());
+        out.extend(index.to_le_bytes());
+    }
+    out
+}
+
+pub(crate) fn decode(mut bytes: &[u8]) -> Option<BTreeMap<String, u16>> {
+    fn take<'a>(b: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+        if b.len() < n { return None; }
+        let (head, tail) = b.split_at(n);
+        *b = tail;
+        Some(head)
+    }
+    let count = u32::from_le_bytes(take(&mut bytes, 4)?.try_into().ok()?);
+    let mut map = BTreeMap::new();
+    for _ in 0..count {
+        let len = u16::from_le_bytes(take(&mut bytes, 2)?.try_into().ok()?) as usize;
+        let name = String::from_utf8(take(&mut bytes, len)?.to_vec()).ok()?;
+        let index = u16::from_le_bytes(take(&mut bytes, 2)?.try_into().ok()?);
+        map.insert(name, index);
+    }
+    Some(map)
+}
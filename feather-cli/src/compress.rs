@@ -0,0 +1,96 @@
+//! Compression of the store file (`OpenOptions::compression`).
+//!
+//! A text-heavy store is mostly `content` strings. The core can pack the
+//! metadata section of the file — records with their content, tags and
+//! attributes — or, for archival, every section after the properties,
+//! vectors and graphs included. It uses zstd, a chunk at a time, so a
+//! section is never held in memory whole. Packing costs time on every save
+//! and open; the WAL is never packed.
+//!
+//! The setting is chosen when a store is created and kept in the file
+//! (format v12), so every later save follows it; `DB::set_compression`
+//! changes it from the next save on. An unpacked store is saved as v11,
+//! which builds older than packing still open.
+//!
+//! The codec is there only with the `zstd` feature, which links libzstd.
+//! Without it, asking to pack fails and a packed file does not open.
+
+use crate::*;
+use std::str::FromStr;
+
+extern "C" {
+    fn feather_set_compression(db: *mut c_void, compression: i32) -> i32;
+    fn feather_compression(db: *mut c_void) -> i32;
+}
+
+/// What of the file is packed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// Nothing: fastest to save and open.
+    #[default]
+    None,
+    /// The metadata section: records, content and attributes.
+    Metadata,
+    /// Everything after the properties, vectors too: smallest, slowest to
+    /// open.
+    All,
+}
+
+impl Compression {
+    pub fn name(self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Metadata => "metadata",
+            Compression::All => "all",
+        }
+    }
+
+    // The core's (and the file's) code.
+    pub(crate) fn code(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Metadata => 1,
+            Compression::All => 2,
+        }
+    }
+
+    pub(crate) fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Metadata),
+            2 => Some(Compression::All),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Compression::None),
+            "metadata" => Ok(Compression::Metadata),
+            "all" => Ok(Compression::All),
+            other => Err(format!("unknown compression '{}' (expected none, metadata or all)", other)),
+        }
+    }
+}
+
+impl DB {
+    /// What saves of this store pack.
+    pub fn compression(&self) -> Compression {
+        let code = unsafe { feather_compression(self.ptr) };
+        u8::try_from(code).ok().and_then(Compression::from_code).unwrap_or_default()
+    }
+
+    /// Pack the file as `compression` says from the next `save()` on, for
+    /// every collection and shard.
+    pub fn set_compression(&self, compression: Compression) -> anyhow::Result<()> {
+        self.writable()?;
+        for &core in self.handle.cores() {
+            if unsafe { feather_set_compression(core, compression.code() as i32) } != 0 { return Err(last_error()); }
+        }
+        Ok(())
+    }
+}
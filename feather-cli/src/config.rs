@@ -0,0 +1,277 @@
+//! Defaults for everyday CLI use, from `~/.config/feather/config.toml`
+//! (under `$XDG_CONFIG_HOME` if set) and the `FEATHER_DB` variable.
+//!
+//! ```toml
+//! db = "~/memory.feather"        # database for commands not given one
+//! k = 10                         # hits `feather search` returns
+//! metric = "cosine"              # for new files: "l2", "cosine" (--normalize) or "hamming"
+//! embed_model = "potion-base-8M" # as --embed-model
+//! embed_api = "https://api.openai.com/v1"
+//!
+//! [databases."~/work.feather"]   # per-file defaults, over the ones above
+//! k = 20
+//! embed_model = "text-embedding-3-small"
+//! ```
+//!
+//! `FEATHER_DB` takes precedence over `db`; flags on the command line over
+//! everything. The file takes a subset of TOML: tables, `key = value`
+//! with strings and integers, and `#` comments.
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Variable naming the default database; wins over `db` in the file.
+pub const DB_VAR: &str = "FEATHER_DB";
+
+/// How new files compare vectors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Metric {
+    /// Euclidean distance, the index's own.
+    L2,
+    /// Cosine similarity: vectors and queries are unit-normalized.
+    Cosine,
+    /// Hamming distance between binary vectors, stored a bit per dimension
+    /// (see `hamming`).
+    Hamming,
+}
+
+impl Metric {
+    /// As `FromStr` reads it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::L2 => "l2",
+            Metric::Cosine => "cosine",
+            Metric::Hamming => "hamming",
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "l2" => Ok(Metric::L2),
+            "cosine" => Ok(Metric::Cosine),
+            "hamming" => Ok(Metric::Hamming),
+            other => Err(format!("unknown metric '{}' (expected l2, cosine or hamming)", other)),
+        }
+    }
+}
+
+/// Settings that apply to every database, or to one.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Defaults {
+    pub k: Option<usize>,
+    pub metric: Option<Metric>,
+    pub embed_model: Option<String>,
+    pub embed_api: Option<String>,
+}
+
+impl Defaults {
+    // `self` with the settings `other` has taking over.
+    fn overlay(&self, other: &Defaults) -> Defaults {
+        Defaults {
+            k: other.k.or(self.k),
+            metric: other.metric.or(self.metric),
+            embed_model: other.embed_model.clone().or_else(|| self.embed_model.clone()),
+            embed_api: other.embed_api.clone().or_else(|| self.embed_api.clone()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    /// Database for commands not given one.
+    pub db: Option<PathBuf>,
+    pub defaults: Defaults,
+    /// Per-file defaults, by path.
+    pub databases: Vec<(PathBuf, Defaults)>,
+}
+
+impl Config {
+    /// Where the config file lives, if there is a home to put it in.
+    pub fn path() -> Option<PathBuf> {
+        let base = match std::env::var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("feather").join("config.toml"))
+    }
+
+    /// The config file, or no settings if there is none.
+    pub fn load() -> anyhow::Result<Config> {
+        let Some(path) = Config::path() else { return Ok(Config::default()) };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => Config::parse(&text).map_err(|e| anyhow::anyhow!("{:?}: {}", path, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => anyhow::bail!("{:?}: {}", path, e),
+        }
+    }
+
+    pub fn parse(text: &str) -> anyhow::Result<Config> {
+        let mut config = Config::default();
+        // None: the top level; Some(i): config.databases[i]
+        let mut table: Option<usize> = None;
+        for (n, line) in text.lines().enumerate() {
+            let at = |e: String| anyhow::anyhow!("line {}: {}", n + 1, e);
+            let line = strip_comment(line).trim();
+            if line.is_empty() { continue; }
+            if let Some(header) = line.strip_prefix('[') {
+                let header = header.strip_suffix(']').ok_or_else(|| at("unclosed table header".into()))?;
+                let path = match key_path(header).map_err(at)?.as_slice() {
+                    [databases, path] if databases == "databases" => expand(path),
+                    _ => return Err(at(format!("unknown table [{}]; use [databases.\"PATH\"]", header.trim()))),
+                };
+                config.databases.push((path, Defaults::default()));
+                table = Some(config.databases.len() - 1);
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or_else(|| at("expected `key = value`".into()))?;
+            let (key, value) = (key.trim(), parse_value(value.trim()).map_err(at)?);
+            let defaults = match table {
+                None if key == "db" => {
+                    config.db = Some(expand(&value.string(key).map_err(at)?));
+                    continue;
+                }
+                None => &mut config.defaults,
+                Some(i) => &mut config.databases[i].1,
+            };
+            match key {
+                "k" => {
+                    let k = value.integer(key).map_err(at)?;
+                    defaults.k = Some(usize::try_from(k).ok().filter(|&k| k > 0).ok_or_else(|| at("k must be positive".into()))?);
+                }
+                "metric" => defaults.metric = Some(value.string(key).map_err(at)?.parse().map_err(at)?),
+                "embed_model" => defaults.embed_model = Some(value.string(key).map_err(at)?),
+                "embed_api" => defaults.embed_api = Some(value.string(key).map_err(at)?),
+                other => return Err(at(format!("unknown setting `{}`", other))),
+            }
+        }
+        Ok(config)
+    }
+
+    /// The database for commands not given one: `FEATHER_DB`, else `db`.
+    pub fn default_db(&self) -> Option<PathBuf> {
+        std::env::var_os(DB_VAR).filter(|v| !v.is_empty()).map(PathBuf::from).or_else(|| self.db.clone())
+    }
+
+    /// The settings for the database at `db`: its table's over the top
+    /// level's.
+    pub fn for_db(&self, db: Option<&Path>) -> Defaults {
+        let table = db.and_then(|db| self.databases.iter().rev().find(|(path, _)| same_file(path, db)));
+        match table {
+            Some((_, defaults)) => self.defaults.overlay(defaults),
+            None => self.defaults.clone(),
+        }
+    }
+}
+
+// Whether two paths name one file, which need not exist yet.
+fn same_file(a: &Path, b: &Path) -> bool {
+    let resolve = |p: &Path| std::fs::canonicalize(p).or_else(|_| std::path::absolute(p)).ok();
+    resolve(a).is_some_and(|a| Some(a) == resolve(b))
+}
+
+// `~/x` under the home directory.
+fn expand(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+enum TomlValue {
+    String(String),
+    Integer(i64),
+}
+
+impl TomlValue {
+    fn string(self, key: &str) -> Result<String, String> {
+        match self {
+            TomlValue::String(s) => Ok(s),
+            _ => Err(format!("`{}` takes a string", key)),
+        }
+    }
+
+    fn integer(self, key: &str) -> Result<i64, String> {
+        match self {
+            TomlValue::Integer(i) => Ok(i),
+            _ => Err(format!("`{}` takes an integer", key)),
+        }
+    }
+}
+
+fn parse_value(raw: &str) -> Result<TomlValue, String> {
+    if raw.starts_with('"') || raw.starts_with('\'') {
+        let (s, rest) = quoted(raw)?;
+        return if rest.trim().is_empty() { Ok(TomlValue::String(s)) } else { Err(format!("unexpected `{}` after the string", rest.trim())) };
+    }
+    raw.replace('_', "").parse().map(TomlValue::Integer)
+        .map_err(|_| format!("cannot read value `{}` (strings need quotes)", raw))
+}
+
+// A "basic" (escapes) or 'literal' string at the start of `s`, and what
+// follows it.
+fn quoted(s: &str) -> Result<(String, &str), String> {
+    let quote = s.chars().next().ok_or("expected a string")?;
+    let mut out = String::new();
+    let mut chars = s.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c == quote => return Ok((out, &s[i + 1..])),
+            '\\' if quote == '"' => match chars.next().map(|(_, c)| c) {
+                Some('"') => out.push('"'),
+                Some('\\') => out.push('\\'),
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                other => return Err(format!("unsupported escape \\{}", other.map(String::from).unwrap_or_default())),
+            },
+            c => out.push(c),
+        }
+    }
+    Err("unclosed string".into())
+}
+
+// The parts of a dotted key like `databases."~/x.feather"`.
+fn key_path(mut s: &str) -> Result<Vec<String>, String> {
+    let mut parts = Vec::new();
+    loop {
+        s = s.trim_start();
+        let part = if s.starts_with('"') || s.starts_with('\'') {
+            let (part, rest) = quoted(s)?;
+            s = rest;
+            part
+        } else {
+            let end = s.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-')).unwrap_or(s.len());
+            if end == 0 { return Err(format!("bad key `{}`", s)); }
+            let (part, rest) = s.split_at(end);
+            s = rest;
+            part.to_string()
+        };
+        parts.push(part);
+        s = s.trim_start();
+        match s.strip_prefix('.') {
+            Some(rest) => s = rest,
+            None if s.is_empty() => return Ok(parts),
+            None => return Err(format!("bad key near `{}`", s)),
+        }
+    }
+}
+
+// `line` up to a `#` that is not inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => { escaped = true; continue; }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
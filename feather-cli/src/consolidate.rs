@@ -0,0 +1,154 @@
+//! Folding clusters of near-duplicate memories into one record (`feather
+//! consolidate`).
+//!
+//! A long-running agent stores the same fact many times in slightly
+//! different words. `consolidate` groups live records whose vectors in a
+//! modality have a cosine similarity of at least `threshold` with the
+//! cluster's leader — the most important record not yet grouped — and
+//! writes each cluster of two or more as one new record:
+//!
+//! - its vector is the mean of the members' vectors;
+//! - its content is the leader's, or what a `Summarizer` makes of all of
+//!   the members;
+//! - it keeps the highest importance and confidence, the latest timestamp
+//!   and the recall counts summed; source, type, tags, namespace and entity
+//!   come from the leader, and attributes and links from every member;
+//! - it is `derived_from` each member (see `lineage`).
+//!
+//! The members stay, marked with the `superseded_by` attribute naming the
+//! new record, so lineage can still show them; a search filter such as
+//! `attr.superseded_by = ''` leaves them out, and `forget` drops them.
+//! Records already superseded, and archived ones, are not consolidated.
+
+use crate::lineage::DERIVED_FROM;
+use crate::metadata::VERSION_ATTRIBUTE;
+use crate::{Edge, Metadata, DB};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Attribute naming the record a consolidated member was folded into.
+pub const SUPERSEDED_BY: &str = "superseded_by";
+
+/// Default cosine similarity at which records count as near duplicates.
+pub const DEFAULT_THRESHOLD: f32 = 0.95;
+
+// Nearest neighbours of a leader looked at for its cluster.
+const CANDIDATES: usize = 32;
+
+/// Writes the content of a consolidated record from its members', leader
+/// first; a closure will do.
+pub trait Summarizer {
+    fn summarize(&self, members: &[Metadata]) -> anyhow::Result<String>;
+}
+
+impl<F> Summarizer for F
+where
+    F: Fn(&[Metadata]) -> anyhow::Result<String>,
+{
+    fn summarize(&self, members: &[Metadata]) -> anyhow::Result<String> {
+        self(members)
+    }
+}
+
+/// One cluster folded into a new record.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Consolidation {
+    /// The new record.
+    pub id: u64,
+    /// The records folded into it, leader first.
+    pub members: Vec<u64>,
+}
+
+impl DB {
+    /// Fold the clusters of near-duplicate records in `modality` into new
+    /// records (see the module docs), numbered on from the highest id. With
+    /// `dry_run`, only find the clusters and the ids they would get.
+    pub fn consolidate(&self, modality: &str, threshold: f32, summarizer: Option<&dyn Summarizer>,
+                       dry_run: bool) -> anyhow::Result<Vec<Consolidation>> {
+        anyhow::ensure!(threshold > 0.0 && threshold <= 1.0, "threshold must be in (0, 1]");
+        if !dry_run { self.writable()?; }
+        let mut records: Vec<(u64, Metadata)> = self.ids(modality).into_iter()
+            .filter_map(|id| Some((id, self.get_metadata(id)?)))
+            .filter(|(_, meta)| !meta.is_forgotten() && !meta.is_archived() && !meta.attributes.contains_key(SUPERSEDED_BY))
+            .collect();
+        records.sort_by(|(a, x), (b, y)| y.importance.total_cmp(&x.importance).then(a.cmp(b)));
+        let eligible: HashSet<u64> = records.iter().map(|&(id, _)| id).collect();
+
+        let mut grouped = HashSet::new();
+        let mut clusters: Vec<Vec<u64>> = Vec::new();
+        for (leader, _) in &records {
+            if grouped.contains(leader) { continue; }
+            let Some(vector) = self.get_vector(*leader, modality) else { continue };
+            let mut members = vec![*leader];
+            for (other, _) in self.knn(&vector, CANDIDATES, modality)? {
+                if other == *leader || grouped.contains(&other) || !eligible.contains(&other) { continue; }
+                let Some(theirs) = self.get_vector(other, modality) else { continue };
+                if cosine(&vector, &theirs) >= threshold { members.push(other); }
+            }
+            if members.len() > 1 {
+                grouped.extend(members.iter().copied());
+                clusters.push(members);
+            }
+        }
+
+        let first = self.all_ids().into_iter().max().map_or(0, |max| max + 1);
+        let mut done = Vec::with_capacity(clusters.len());
+        for (id, members) in (first..).zip(clusters) {
+            if !dry_run { self.fold(id, &members, modality, summarizer)?; }
+            done.push(Consolidation { id, members });
+        }
+        Ok(done)
+    }
+
+    // Write record `id` from `members` (leader first) and mark them
+    // superseded by it.
+    fn fold(&self, id: u64, members: &[u64], modality: &str, summarizer: Option<&dyn Summarizer>) -> anyhow::Result<()> {
+        let metas: Vec<Metadata> = members.iter()
+            .map(|&m| self.get_metadata(m).ok_or_else(|| anyhow::anyhow!("no record {}", m)))
+            .collect::<anyhow::Result<_>>()?;
+        let vectors: Vec<Vec<f32>> = members.iter().filter_map(|&m| self.get_vector(m, modality)).collect();
+        let mut mean = vec![0.0f32; vectors[0].len()];
+        for v in &vectors {
+            for (acc, x) in mean.iter_mut().zip(v) { *acc += x / vectors.len() as f32; }
+        }
+
+        let leader = &metas[0];
+        let mut meta = Metadata {
+            content: match summarizer {
+                Some(summarizer) => summarizer.summarize(&metas)?,
+                None => leader.content.clone(),
+            },
+            importance: metas.iter().map(|m| m.importance).fold(f32::MIN, f32::max),
+            confidence: metas.iter().map(|m| m.confidence).fold(f32::MIN, f32::max),
+            timestamp: metas.iter().map(|m| m.timestamp).max().unwrap_or(0),
+            recall_count: metas.iter().map(|m| m.recall_count).fold(0, u32::saturating_add),
+            last_recalled_at: metas.iter().map(|m| m.last_recalled_at).max().unwrap_or(0),
+            attributes: Default::default(),
+            edges: Vec::new(),
+            ..leader.clone()
+        };
+        for member in &metas {
+            for (key, value) in &member.attributes {
+                if key != VERSION_ATTRIBUTE { meta.attributes.entry(key.clone()).or_insert_with(|| value.clone()); }
+            }
+            for edge in member.edges.iter().filter(|e| !members.contains(&e.target)) {
+                if !meta.edges.iter().any(|e| e.target == edge.target && e.rel_type == edge.rel_type) {
+                    meta.edges.push(edge.clone());
+                }
+            }
+        }
+        meta.edges.extend(members.iter().map(|&m| Edge { target: m, rel_type: DERIVED_FROM.to_string(), weight: 1.0 }));
+
+        self.write_record(id, &mean, &meta, modality)?;
+        for &member in members {
+            self.set_attribute(member, SUPERSEDED_BY, &id.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norms = a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norms == 0.0 { 0.0 } else { dot / norms }
+}
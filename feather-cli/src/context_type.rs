@@ -0,0 +1,182 @@
+//! Record kinds (`Metadata::context_type`) and the names they go by.
+//!
+//! The core stores a record's kind as one byte. Four kinds are built in;
+//! every other code is a `Custom` kind, which a file can give a name in its
+//! registry (`DB::register_context_type`, `feather context-types --add`) so
+//! that `--context-type decision` works like `--context-type episodic`.
+//!
+//! The built-in codes line up with the core's own enum: semantic memories
+//! are its FACT (0) and episodic ones its EVENT (2). Records written by the
+//! Python bindings as PREFERENCE (1) or CONVERSATION (3) read back as
+//! `Custom(1)` and `Custom(3)`.
+
+use crate::DB;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Property holding the registry (`name=code` pairs, comma-separated).
+pub(crate) const PROPERTY_KEY: &str = "context_types";
+
+/// Code the core reads as "any kind" in a type filter; no kind has it.
+pub(crate) const ANY_CODE: u8 = 255;
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(from = "u8", into = "u8")]
+pub enum ContextType {
+    /// Facts and knowledge; the default.
+    #[default]
+    Semantic,
+    /// Things that happened.
+    Episodic,
+    /// How to do things.
+    Procedural,
+    /// What a tool returned.
+    ToolOutput,
+    /// Any other code; see the registry for its name.
+    Custom(u8),
+}
+
+impl ContextType {
+    pub const BUILTIN: [ContextType; 4] =
+        [ContextType::Semantic, ContextType::Episodic, ContextType::Procedural, ContextType::ToolOutput];
+
+    pub fn code(self) -> u8 {
+        match self {
+            ContextType::Semantic => 0,
+            ContextType::Episodic => 2,
+            ContextType::Procedural => 4,
+            ContextType::ToolOutput => 5,
+            ContextType::Custom(code) => code,
+        }
+    }
+
+    /// The name of a built-in kind; `None` for `Custom`.
+    pub fn builtin_name(self) -> Option<&'static str> {
+        match ContextType::from(self.code()) {
+            ContextType::Semantic => Some("semantic"),
+            ContextType::Episodic => Some("episodic"),
+            ContextType::Procedural => Some("procedural"),
+            ContextType::ToolOutput => Some("tool_output"),
+            ContextType::Custom(_) => None,
+        }
+    }
+
+    fn builtin(name: &str) -> Option<ContextType> {
+        ContextType::BUILTIN.into_iter().find(|t| t.builtin_name() == Some(name))
+    }
+}
+
+impl From<u8> for ContextType {
+    fn from(code: u8) -> Self {
+        match code {
+            0 => ContextType::Semantic,
+            2 => ContextType::Episodic,
+            4 => ContextType::Procedural,
+            5 => ContextType::ToolOutput,
+            code => ContextType::Custom(code),
+        }
+    }
+}
+
+impl From<ContextType> for u8 {
+    fn from(t: ContextType) -> u8 { t.code() }
+}
+
+// `Custom(0)` is `Semantic`: kinds are equal when their codes are.
+impl PartialEq for ContextType {
+    fn eq(&self, other: &Self) -> bool { self.code() == other.code() }
+}
+
+impl Eq for ContextType {}
+
+impl std::hash::Hash for ContextType {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) { self.code().hash(state) }
+}
+
+/// The built-in name, else the code.
+impl fmt::Display for ContextType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.builtin_name() {
+            Some(name) => f.write_str(name),
+            None => write!(f, "{}", self.code()),
+        }
+    }
+}
+
+impl DB {
+    /// Name the custom kind `code` for the whole file (all collections);
+    /// persists on `save()`. Names are case-insensitive and may not be a
+    /// built-in name or a number; each code takes one name. Registering
+    /// the same pair again is a no-op.
+    pub fn register_context_type(&self, name: &str, code: u8) -> anyhow::Result<ContextType> {
+        let name = name.trim().to_ascii_lowercase();
+        anyhow::ensure!(!name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'),
+                        "context type names are letters, digits, `_` and `-`");
+        anyhow::ensure!(name.parse::<u8>().is_err(), "context type name `{}` is a number", name);
+        anyhow::ensure!(ContextType::builtin(&name).is_none(), "`{}` is a built-in context type", name);
+        let kind = ContextType::from(code);
+        if let Some(builtin) = kind.builtin_name() {
+            anyhow::bail!("code {} is the built-in context type `{}`", code, builtin);
+        }
+        anyhow::ensure!(code != ANY_CODE, "code {} is reserved", ANY_CODE);
+        let mut registry = self.context_type_registry();
+        for (other, t) in &registry {
+            if *other == name && *t == kind { return Ok(kind); }
+            anyhow::ensure!(*other != name, "`{}` is already context type {}", name, t.code());
+            anyhow::ensure!(*t != kind, "context type {} is already named `{}`", code, other);
+        }
+        self.writable()?;
+        registry.push((name, kind));
+        let encoded: Vec<String> = registry.iter().map(|(n, t)| format!("{}={}", n, t.code())).collect();
+        self.set_property(PROPERTY_KEY, encoded.join(",").as_bytes())?;
+        Ok(kind)
+    }
+
+    /// The kind called `name` (built-in or registered, any case), or given
+    /// as its code.
+    pub fn context_type(&self, name: &str) -> anyhow::Result<ContextType> {
+        let name = name.trim().to_ascii_lowercase();
+        if let Ok(code) = name.parse::<u8>() {
+            anyhow::ensure!(code != ANY_CODE, "code {} is reserved", ANY_CODE);
+            return Ok(ContextType::from(code));
+        }
+        if let Some(t) = ContextType::builtin(&name) {
+            return Ok(t);
+        }
+        let types = self.context_types();
+        types.iter().find(|(n, _)| *n == name).map(|&(_, t)| t).ok_or_else(|| {
+            let names: Vec<&str> = types.iter().map(|(n, _)| n.as_str()).collect();
+            anyhow::anyhow!("unknown context type `{}` (known: {})", name, names.join(", "))
+        })
+    }
+
+    /// The name of `kind`: built-in, registered, or else its code.
+    pub fn context_type_name(&self, kind: ContextType) -> String {
+        if let Some(name) = kind.builtin_name() {
+            return name.to_string();
+        }
+        self.context_type_registry().into_iter()
+            .find(|&(_, t)| t == kind)
+            .map_or_else(|| kind.code().to_string(), |(name, _)| name)
+    }
+
+    /// Every named kind: the built-in ones, then the registered ones by code.
+    pub fn context_types(&self) -> Vec<(String, ContextType)> {
+        let mut registered = self.context_type_registry();
+        registered.sort_by_key(|&(_, t)| t.code());
+        ContextType::BUILTIN.into_iter()
+            .map(|t| (t.to_string(), t))
+            .chain(registered)
+            .collect()
+    }
+
+    fn context_type_registry(&self) -> Vec<(String, ContextType)> {
+        let Some(raw) = self.property(PROPERTY_KEY) else { return Vec::new() };
+        String::from_utf8_lossy(&raw).split(',')
+            .filter_map(|pair| {
+                let (name, code) = pair.split_once('=')?;
+                Some((name.to_string(), ContextType::from(code.parse::<u8>().ok()?)))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,196 @@
+//! Importance decay: memories fade unless they are used.
+//!
+//! A record's importance halves every `half_life` seconds of inactivity,
+//! counted from its last activity — its `timestamp` or `last_recalled_at`,
+//! whichever is later. The decay is either applied lazily at scoring time
+//! (`DB::search_decayed`) or baked into the stored importance by `apply`
+//! (`feather decay`). Each collection remembers when it was last baked, in
+//! the DB properties, so lazy scoring only adds the decay accrued since.
+
+use crate::search::CANDIDATE_FACTOR;
+use crate::{collection, Metadata, DB};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Property key holding the last `apply` time of the default collection;
+/// named collections use `decay::<collection>`.
+pub(crate) const PROPERTY_KEY: &str = "decay";
+
+/// Exponential decay of importance over inactivity.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decay {
+    /// Seconds of inactivity that halve a record's importance.
+    pub half_life: f64,
+    /// Importance never decays below this (records already below it keep theirs).
+    pub floor: f32,
+}
+
+impl Decay {
+    pub fn new(half_life: f64, floor: f32) -> anyhow::Result<Self> {
+        anyhow::ensure!(half_life > 0.0 && half_life.is_finite(), "half-life must be positive");
+        anyhow::ensure!((0.0..=1.0).contains(&floor), "floor must be within 0..=1");
+        Ok(Decay { half_life, floor })
+    }
+
+    /// Multiplier for `elapsed` seconds of inactivity.
+    pub fn factor(&self, elapsed: f64) -> f32 {
+        0.5f64.powf(elapsed.max(0.0) / self.half_life) as f32
+    }
+
+    /// `meta`'s importance at `now`, decaying from its last activity or from
+    /// `since` (the last time decay was baked in), whichever is later. A
+    /// record with no time reference at all does not decay.
+    pub fn importance(&self, meta: &Metadata, since: i64, now: i64) -> f32 {
+        let active = meta.timestamp.max(meta.last_recalled_at as i64).max(since);
+        if active <= 0 { return meta.importance; }
+        let decayed = meta.importance * self.factor((now - active) as f64);
+        decayed.max(self.floor.min(meta.importance))
+    }
+}
+
+/// Parse a duration such as `30d`, `12h`, `90m`, `45s`, `2w` or a bare
+/// number of seconds. Returns seconds.
+pub fn parse_duration(s: &str) -> anyhow::Result<f64> {
+    let s = s.trim();
+    let (num, unit) = match s.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let scale = match unit {
+        "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3_600.0,
+        "d" => 86_400.0,
+        "w" => 604_800.0,
+        _ => anyhow::bail!("unknown duration unit {:?} in {:?} (use s, m, h, d or w)", unit, s),
+    };
+    let n: f64 = num.trim().parse().map_err(|_| anyhow::anyhow!("invalid duration {:?}", s))?;
+    anyhow::ensure!(n >= 0.0 && n.is_finite(), "invalid duration {:?}", s);
+    Ok(n * scale)
+}
+
+/// Parse a point in time as Unix seconds: a bare integer is a Unix
+/// timestamp, `YYYY-MM-DD` is that day's midnight UTC, and a duration such as
+/// `7d` means that long before `now`.
+pub fn parse_time(s: &str, now: i64) -> anyhow::Result<i64> {
+    let s = s.trim();
+    if let Ok(ts) = s.parse::<i64>() {
+        return Ok(ts);
+    }
+    if let Some(day) = parse_date(s) {
+        return Ok(day);
+    }
+    if s.ends_with(|c: char| c.is_ascii_alphabetic()) {
+        return Ok(now - parse_duration(s)? as i64);
+    }
+    anyhow::bail!("invalid time {:?} (use a Unix timestamp, YYYY-MM-DD or a duration ago such as 7d)", s)
+}
+
+// Midnight UTC of a `YYYY-MM-DD` date, in Unix seconds.
+fn parse_date(s: &str) -> Option<i64> {
+    let mut parts = s.splitn(3, '-');
+    let y: i64 = parts.next()?.parse().ok()?;
+    let m: i64 = parts.next()?.parse().ok()?;
+    let d: i64 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) { return None; }
+    // days since the epoch, proleptic Gregorian (Howard Hinnant's days_from_civil)
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    Some((era * 146_097 + doe - 719_468) * 86_400)
+}
+
+/// `ts` (Unix seconds) as `YYYY-MM-DD HH:MM` UTC.
+pub fn format_time(ts: i64) -> String {
+    let (days, secs) = (ts.div_euclid(86_400), ts.rem_euclid(86_400));
+    // inverse of parse_date (Howard Hinnant's civil_from_days)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", y, m, d, secs / 3600, secs % 3600 / 60)
+}
+
+/// Current Unix time in seconds.
+pub fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+fn property_key(db: &DB) -> String {
+    match db.collection_name() {
+        Some(name) => format!("{}{}{}", PROPERTY_KEY, collection::MODALITY_SEP, name),
+        None => PROPERTY_KEY.to_string(),
+    }
+}
+
+/// When decay was last baked into `db`'s importances (0 = never).
+pub fn last_applied(db: &DB) -> i64 {
+    db.property(&property_key(db))
+        .and_then(|raw| Some(i64::from_le_bytes(raw.as_slice().try_into().ok()?)))
+        .unwrap_or(0)
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DecayReport {
+    /// Live records examined.
+    pub records: usize,
+    /// Records whose stored importance went down.
+    pub decayed: usize,
+}
+
+/// Bake the decay accrued up to `now` into the stored importance of every
+/// live record of `db`. Running it repeatedly compounds correctly: each run
+/// only applies the decay since the previous one. With `dry_run` nothing is
+/// written.
+pub fn apply(db: &DB, decay: &Decay, now: i64, dry_run: bool) -> anyhow::Result<DecayReport> {
+    let since = last_applied(db);
+    let mut report = DecayReport::default();
+    for id in db.all_ids() {
+        let Some(mut meta) = db.get_metadata(id) else { continue };
+        if meta.is_forgotten() { continue; }
+        report.records += 1;
+        let importance = decay.importance(&meta, since, now);
+        if importance < meta.importance {
+            report.decayed += 1;
+            if !dry_run {
+                meta.importance = importance;
+                db.put_metadata(id, &meta)?;
+            }
+        }
+    }
+    if !dry_run {
+        db.set_property(&property_key(db), &now.to_le_bytes())?;
+    }
+    Ok(report)
+}
+
+impl DB {
+    /// Nearest records to `query` ranked by similarity × decayed importance,
+    /// best first, as `(id, score)`. Like `search`, the returned hits count as
+    /// recalled (which restarts their decay) and the query feeds drift stats.
+    pub fn search_decayed(&self, query: &[f32], k: usize, modality: &str,
+                          decay: &Decay) -> anyhow::Result<Vec<(u64, f32)>> {
+        let internal = self.mname(Some(modality)).expect("named");
+        let projected = self.project(Some(&internal), query);
+        self.check_dim(Some(&internal), &projected)?;
+        self.observe_query(Some(&internal), &projected);
+        let (since, now) = (last_applied(self), now());
+        let mut hits: Vec<(u64, f32)> = self.knn(query, k.saturating_mul(CANDIDATE_FACTOR), modality)?
+            .into_iter()
+            .filter_map(|(id, dist)| {
+                let meta = self.get_metadata(id).filter(|m| !m.is_forgotten())?;
+                Some((id, decay.importance(&meta, since, now) / (1.0 + dist)))
+            })
+            .collect();
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1));
+        hits.truncate(k);
+        self.recalled(hits.iter().map(|&(id, _)| id));
+        Ok(hits)
+    }
+}
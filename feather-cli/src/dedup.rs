@@ -0,0 +1,145 @@
+//! Skipping inserts that repeat what the store already holds.
+//!
+//! Agents tend to store the same fact again and again, each time under a
+//! fresh id. With a dedup mode set (`OpenOptions::dedup`,
+//! `DB::set_dedup`), an insert whose content equals that of a live record,
+//! or whose vector lies within `epsilon` of one, is not written: it is
+//! dropped (`OnMatch::Skip`) or folded into the existing record
+//! (`OnMatch::Merge`), and `insert` reports the existing id.
+//!
+//! Content is matched through an in-memory index of content hashes, built
+//! on the first insert that needs it. A record never duplicates itself;
+//! whether an insert may replace its own id is up to the duplicate-id
+//! policy, which is applied first.
+
+use crate::{Handle, Metadata, DB};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// How to recognise a duplicate.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Dedup {
+    #[default]
+    Off,
+    /// The same non-empty `content`, byte for byte.
+    Content,
+    /// A vector in the same modality at most `epsilon` away (L2).
+    Vector { epsilon: f32 },
+}
+
+/// What becomes of an insert found to be a duplicate.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnMatch {
+    /// Drop it; the existing record is untouched.
+    #[default]
+    Skip,
+    /// Fold its metadata into the existing record: the higher importance
+    /// and confidence, the later timestamp, and any attributes and edges
+    /// the record lacks.
+    Merge,
+}
+
+// Candidate nearest neighbours looked at in `Dedup::Vector` mode; the
+// nearest may be the record being replaced or a forgotten one.
+const VECTOR_CANDIDATES: usize = 8;
+
+fn content_hash(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl DB {
+    /// The dedup mode of this file's handles.
+    pub fn dedup(&self) -> (Dedup, OnMatch) { self.handle.dedup.get() }
+
+    /// Change the dedup mode for every handle on this file (collections
+    /// included). Not persisted.
+    pub fn set_dedup(&self, dedup: Dedup, on_match: OnMatch) -> anyhow::Result<()> {
+        if let Dedup::Vector { epsilon } = dedup {
+            anyhow::ensure!(epsilon >= 0.0, "dedup epsilon must not be negative");
+        }
+        self.handle.dedup.set((dedup, on_match));
+        Ok(())
+    }
+
+    /// The live record other than `id` that a record with `meta`, and
+    /// `vector` in the named modality, duplicates under the dedup mode.
+    pub fn find_duplicate(&self, id: u64, vector: Option<(&str, &[f32])>,
+                          meta: &Metadata) -> anyhow::Result<Option<u64>> {
+        let is_other = |other: u64| other != id && self.get_metadata(other).is_some_and(|m| !m.is_forgotten());
+        match self.dedup().0 {
+            Dedup::Off => Ok(None),
+            Dedup::Content => {
+                if meta.content.is_empty() { return Ok(None); }
+                let internal = self.handle.with_content_index(|index| {
+                    index.get(&content_hash(&meta.content)).cloned().unwrap_or_default()
+                });
+                Ok(internal.into_iter()
+                    .filter_map(|i| self.xid(i))
+                    .find(|&other| is_other(other)
+                        && self.get_metadata(other).is_some_and(|m| m.content == meta.content)))
+            }
+            Dedup::Vector { epsilon } => {
+                let Some((modality, vector)) = vector else { return Ok(None) };
+                if !self.modalities().iter().any(|m| m == modality) { return Ok(None); }
+                Ok(self.knn(vector, VECTOR_CANDIDATES, modality)?
+                    .into_iter()
+                    .find(|&(other, d)| d <= epsilon * epsilon && is_other(other))
+                    .map(|(other, _)| other))
+            }
+        }
+    }
+
+    // `find_duplicate`, folding `meta` into the duplicate under
+    // `OnMatch::Merge`. None means the insert goes ahead.
+    pub(crate) fn deduplicate(&self, id: u64, vector: Option<(&str, &[f32])>,
+                              meta: &Metadata) -> anyhow::Result<Option<u64>> {
+        let Some(existing) = self.find_duplicate(id, vector, meta)? else { return Ok(None) };
+        if self.dedup().1 == OnMatch::Merge {
+            let mut merged = self.get_metadata(existing).expect("live");
+            merged.importance = merged.importance.max(meta.importance);
+            merged.confidence = merged.confidence.max(meta.confidence);
+            merged.timestamp = merged.timestamp.max(meta.timestamp);
+            for (key, value) in &meta.attributes {
+                merged.attributes.entry(key.clone()).or_insert_with(|| value.clone());
+            }
+            for edge in &meta.edges {
+                if !merged.edges.iter().any(|e| e.target == edge.target && e.rel_type == edge.rel_type) {
+                    merged.edges.push(edge.clone());
+                }
+            }
+            self.put_metadata(existing, &merged)?;
+        }
+        Ok(Some(existing))
+    }
+}
+
+impl Handle {
+    // Run `f` on the content-hash index (hash → internal ids), building it
+    // first if need be. Entries may be stale; callers compare the content.
+    pub(crate) fn with_content_index<T>(&self, f: impl FnOnce(&HashMap<u64, Vec<u64>>) -> T) -> T {
+        let mut index = self.content_index.borrow_mut();
+        let index = index.get_or_insert_with(|| {
+            let mut index: HashMap<u64, Vec<u64>> = HashMap::new();
+            for id in self.all_ids() {
+                let Some(meta) = self.meta(id) else { continue };
+                if !meta.content.is_empty() && !meta.is_forgotten() {
+                    index.entry(content_hash(&meta.content)).or_default().push(id);
+                }
+            }
+            index
+        });
+        f(index)
+    }
+
+    // Keep the content-hash index, if built, in step with a write of
+    // `content` to the record with internal id `id`.
+    pub(crate) fn note_content(&self, id: u64, content: &str) {
+        if content.is_empty() { return; }
+        if let Some(index) = self.content_index.borrow_mut().as_mut() {
+            let ids = index.entry(content_hash(content)).or_default();
+            if !ids.contains(&id) { ids.push(id); }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Answering many queries at once (`DB::search_batch`, `feather
+//! search-batch`), on the CPU or a GPU (`SearchOptions::device`).
+//!
+//! For analytics-scale work — thousands of queries against millions of
+//! vectors — one index search per query wastes what the queries share. A
+//! batch is instead answered by an exact scan: the options' record checks
+//! run once per record rather than once per hit, and every query is then
+//! measured against every admitted vector, a block of each at a time, so
+//! the hits are the true nearest neighbours. On the CPU the queries of a
+//! block are spread over the available cores. Built with `--features
+//! experimental-gpu`, `Device::Gpu` measures them on a CUDA GPU instead, as
+//! one matrix product per block; without the feature or a usable GPU it
+//! falls back to the CPU (`gpu_available` says which).
+//!
+//! Hits score as a search's do, `1 / (1 + d)` times the recency factor;
+//! `min_score` and `offset` apply. Options a scan cannot score — keywords,
+//! a sparse query, graph boost, MMR, a reranker, a scoring policy and linked
+//! modalities — as well as `truncate_dim` and `max_per_source` rank each
+//! query as `search_with_options` would instead, on the CPU. Either way the
+//! hits are not counted as recalls and the queries do not feed drift stats.
+
+#[cfg(all(feature = "experimental-gpu", target_os = "linux"))]
+mod cuda;
+
+use crate::rerank::Query;
+use crate::{decay, SearchOptions, DB};
+use ndarray::ArrayView2;
+
+/// Queries measured together.
+const QUERY_BLOCK: usize = 256;
+
+/// Vectors measured against a block of queries at a time.
+const VECTOR_BLOCK: usize = 65_536;
+
+/// Where `DB::search_batch` measures queries against vectors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Device {
+    #[default]
+    Cpu,
+    /// A CUDA GPU (`--features experimental-gpu`), else the CPU.
+    Gpu,
+}
+
+/// Whether `Device::Gpu` runs on a GPU: the build has the `experimental-gpu`
+/// feature and the CUDA runtime, cuBLAS and a device were found.
+pub fn gpu_available() -> bool {
+    #[cfg(all(feature = "experimental-gpu", target_os = "linux"))]
+    return cuda::gpu().is_some();
+    #[cfg(not(all(feature = "experimental-gpu", target_os = "linux")))]
+    false
+}
+
+impl DB {
+    /// The `k` best hits for each row of `queries` in `modality`, as
+    /// `(id, score)`, best first (see the module docs).
+    pub fn search_batch(&self, queries: ArrayView2<f32>, k: usize, modality: &str,
+                        options: &SearchOptions) -> anyhow::Result<Vec<Vec<(u64, f32)>>> {
+        options.validate()?;
+        anyhow::ensure!(self.modalities().iter().any(|m| m == modality), "no vectors in modality '{}'", modality);
+        if self.ranks_each(options) {
+            return queries.rows().into_iter()
+                .map(|q| self.ranked(Query { vector: &q.to_vec(), text: options.text.as_deref() }, k, modality, options, None))
+                .collect();
+        }
+        if queries.nrows() == 0 { return Ok(Vec::new()); }
+        let internal = self.mname(Some(modality)).expect("named");
+        let mut flat = Vec::new();
+        for q in queries.rows() {
+            let q = q.to_vec();
+            let projected = self.project(Some(&internal), &q);
+            self.check_dim(Some(&internal), &projected)?;
+            flat.extend_from_slice(&projected);
+        }
+        let dim = flat.len() / queries.nrows();
+
+        let now = decay::now();
+        let mut ids = self.ids(modality);
+        ids.sort_unstable();
+        let (mut admitted, mut recency, mut vectors) = (Vec::new(), Vec::new(), Vec::new());
+        for id in ids {
+            let Some(timestamp) = self.admitted(id, options) else { continue };
+            let Some(v) = self.get_vector(id, modality) else { continue };
+            anyhow::ensure!(v.len() == dim, "record {}: dim {} differs from the queries' {}", id, v.len(), dim);
+            admitted.push(id);
+            recency.push(options.recency(timestamp, now));
+            vectors.extend(v);
+        }
+
+        let want = k.saturating_add(options.offset);
+        // per query, the best (score, index into `admitted`) so far
+        let mut best: Vec<Vec<(f32, usize)>> = vec![Vec::new(); queries.nrows()];
+        if want > 0 {
+            for (c, block) in vectors.chunks(VECTOR_BLOCK * dim).enumerate() {
+                let first = c * VECTOR_BLOCK;
+                let n = block.len() / dim;
+                self.measure(options, &flat, block, dim, &mut |query, dists| {
+                    for (i, row) in dists.chunks(n).enumerate() {
+                        let best = &mut best[query + i];
+                        best.extend(row.iter().enumerate()
+                            .map(|(j, &d)| (recency[first + j] / (1.0 + d), first + j)));
+                        keep_best(best, want);
+                    }
+                })?;
+            }
+        }
+        Ok(best.into_iter().map(|mut best| {
+            best.sort_by(|a, b| b.0.total_cmp(&a.0).then(admitted[a.1].cmp(&admitted[b.1])));
+            let mut hits: Vec<(u64, f32)> = best.into_iter()
+                .map(|(score, i)| (admitted[i], score))
+                .filter(|&(_, score)| options.min_score.is_none_or(|min| score >= min))
+                .take(want)
+                .collect();
+            hits.drain(..options.offset.min(hits.len()));
+            hits
+        }).collect())
+    }
+
+    // Whether `options` need each query ranked on its own.
+    fn ranks_each(&self, options: &SearchOptions) -> bool {
+        let policy = options.scoring.is_some()
+            || (options.recency_weight == 0.0 && options.graph_boost == 0.0 && self.scoring_policy().is_some());
+        options.text.is_some() || options.sparse.is_some() || options.graph_boost > 0.0 || options.mmr_lambda.is_some()
+            || options.reranker.is_some() || !options.linked_modalities.is_empty() || policy
+            || options.truncate_dim.is_some() || options.max_per_source.is_some()
+    }
+
+    // Squared L2 distances from each query of `queries` to each vector of
+    // `vectors`, handed to `sink` a block of queries at a time: the index of
+    // the block's first query, then a row per query.
+    fn measure(&self, options: &SearchOptions, queries: &[f32], vectors: &[f32], dim: usize,
+               sink: &mut dyn FnMut(usize, &[f32])) -> anyhow::Result<()> {
+        #[cfg(all(feature = "experimental-gpu", target_os = "linux"))]
+        if options.device == Device::Gpu {
+            if let Some(gpu) = cuda::gpu() {
+                let gpu = gpu.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                return gpu.distances(queries, vectors, dim, QUERY_BLOCK, sink);
+            }
+        }
+        #[cfg(not(all(feature = "experimental-gpu", target_os = "linux")))]
+        let _ = options;
+        for (b, block) in queries.chunks(QUERY_BLOCK * dim).enumerate() {
+            sink(b * QUERY_BLOCK, &cpu_distances(block, vectors, dim));
+        }
+        Ok(())
+    }
+}
+
+// Squared L2 distances from each query to each vector, a row per query,
+// the queries shared out among the cores.
+fn cpu_distances(queries: &[f32], vectors: &[f32], dim: usize) -> Vec<f32> {
+    let n = vectors.len() / dim;
+    let mut out = vec![0.0; queries.len() / dim * n];
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let rows = (queries.len() / dim).div_ceil(threads).max(1);
+    std::thread::scope(|scope| {
+        for (qs, out) in queries.chunks(rows * dim).zip(out.chunks_mut(rows * n)) {
+            scope.spawn(move || {
+                for (q, out) in qs.chunks(dim).zip(out.chunks_mut(n)) {
+                    for (v, d) in vectors.chunks(dim).zip(out) {
+                        *d = q.iter().zip(v).map(|(a, b)| (a - b) * (a - b)).sum();
+                    }
+                }
+            });
+        }
+    });
+    out
+}
+
+// Trim `best` to its `want` highest scores once it holds twice that.
+fn keep_best(best: &mut Vec<(f32, usize)>, want: usize) {
+    if best.len() < want.saturating_mul(2) { return; }
+    best.select_nth_unstable_by(want - 1, |a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+    best.truncate(want);
+}
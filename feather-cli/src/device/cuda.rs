@@ -0,0 +1,163 @@
+//! Batch distances on a CUDA GPU (`--features experimental-gpu`).
+//!
+//! The CUDA runtime and cuBLAS are loaded the first time a batch asks for
+//! the GPU rather than linked, so a build with the feature still runs where
+//! they are missing; `gpu` then finds no GPU and batches run on the CPU.
+//! Distances come from one matrix product per block of queries, as
+//! `|q|² + |v|² - 2 q·v`, so they can differ from the CPU's in the last
+//! bits and close hits may swap places.
+
+use std::ffi::{c_void, CStr};
+use std::sync::{Mutex, OnceLock};
+
+// cudaMemcpyKind
+const HOST_TO_DEVICE: i32 = 1;
+const DEVICE_TO_HOST: i32 = 2;
+
+// cublasOperation_t
+const OP_N: i32 = 0;
+const OP_T: i32 = 1;
+
+type Sgemm = unsafe extern "C" fn(*mut c_void, i32, i32, i32, i32, i32, *const f32, *const f32, i32,
+                                  *const f32, i32, *const f32, *mut f32, i32) -> i32;
+
+// The entry points used, each returning 0 on success.
+struct Api {
+    get_device_count: unsafe extern "C" fn(*mut i32) -> i32,
+    malloc: unsafe extern "C" fn(*mut *mut c_void, usize) -> i32,
+    free: unsafe extern "C" fn(*mut c_void) -> i32,
+    memcpy: unsafe extern "C" fn(*mut c_void, *const c_void, usize, i32) -> i32,
+    create: unsafe extern "C" fn(*mut *mut c_void) -> i32,
+    sgemm: Sgemm,
+}
+
+/// A cuBLAS handle on the first device. Kept for the life of the process.
+pub(super) struct Gpu {
+    api: Api,
+    handle: *mut c_void,
+}
+
+// The handle is only used under the mutex `gpu` hands out.
+unsafe impl Send for Gpu {}
+
+/// The GPU, if the libraries load and there is a device; looked for once.
+pub(super) fn gpu() -> Option<&'static Mutex<Gpu>> {
+    static GPU: OnceLock<Option<Mutex<Gpu>>> = OnceLock::new();
+    GPU.get_or_init(|| Gpu::open().ok().map(Mutex::new)).as_ref()
+}
+
+// The first of `names` that loads.
+fn load(names: &[&CStr]) -> anyhow::Result<*mut c_void> {
+    for name in names {
+        let lib = unsafe { libc::dlopen(name.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if !lib.is_null() { return Ok(lib); }
+    }
+    anyhow::bail!("cannot load {:?}", names[0])
+}
+
+// The function `name` of `lib`, as a `T`.
+unsafe fn symbol<T: Copy>(lib: *mut c_void, name: &CStr) -> anyhow::Result<T> {
+    let sym = libc::dlsym(lib, name.as_ptr());
+    anyhow::ensure!(!sym.is_null(), "no {:?}", name);
+    Ok(std::mem::transmute_copy(&sym))
+}
+
+fn check(status: i32, what: &str) -> anyhow::Result<()> {
+    anyhow::ensure!(status == 0, "{} failed with status {}", what, status);
+    Ok(())
+}
+
+// Device memory, freed on drop.
+struct Buffer<'a> {
+    gpu: &'a Gpu,
+    ptr: *mut c_void,
+}
+
+impl Drop for Buffer<'_> {
+    fn drop(&mut self) {
+        unsafe { (self.gpu.api.free)(self.ptr) };
+    }
+}
+
+impl Gpu {
+    fn open() -> anyhow::Result<Gpu> {
+        let cudart = load(&[c"libcudart.so", c"libcudart.so.12", c"libcudart.so.11.0"])?;
+        let cublas = load(&[c"libcublas.so", c"libcublas.so.12", c"libcublas.so.11"])?;
+        let api = unsafe {
+            Api {
+                get_device_count: symbol(cudart, c"cudaGetDeviceCount")?,
+                malloc: symbol(cudart, c"cudaMalloc")?,
+                free: symbol(cudart, c"cudaFree")?,
+                memcpy: symbol(cudart, c"cudaMemcpy")?,
+                create: symbol(cublas, c"cublasCreate_v2")?,
+                sgemm: symbol(cublas, c"cublasSgemm_v2")?,
+            }
+        };
+        let mut devices = 0;
+        check(unsafe { (api.get_device_count)(&mut devices) }, "cudaGetDeviceCount")?;
+        anyhow::ensure!(devices > 0, "no CUDA device");
+        let mut handle = std::ptr::null_mut();
+        check(unsafe { (api.create)(&mut handle) }, "cublasCreate")?;
+        Ok(Gpu { api, handle })
+    }
+
+    fn alloc(&self, floats: usize) -> anyhow::Result<Buffer<'_>> {
+        let bytes = floats.checked_mul(4).ok_or_else(|| anyhow::anyhow!("{} floats overflow a device buffer", floats))?;
+        let mut ptr = std::ptr::null_mut();
+        check(unsafe { (self.api.malloc)(&mut ptr, bytes) }, "cudaMalloc")?;
+        Ok(Buffer { gpu: self, ptr })
+    }
+
+    fn upload(&self, values: &[f32]) -> anyhow::Result<Buffer<'_>> {
+        let buffer = self.alloc(values.len())?;
+        let status = unsafe { (self.api.memcpy)(buffer.ptr, values.as_ptr().cast(), size_of_val(values), HOST_TO_DEVICE) };
+        check(status, "cudaMemcpy")?;
+        Ok(buffer)
+    }
+
+    /// Squared L2 distances from each query to each vector, handed to
+    /// `sink` `block` queries at a time as for `DB::measure`. The vectors
+    /// stay on the device for every block.
+    pub(super) fn distances(&self, queries: &[f32], vectors: &[f32], dim: usize, block: usize,
+                            sink: &mut dyn FnMut(usize, &[f32])) -> anyhow::Result<()> {
+        anyhow::ensure!(dim > 0 && block > 0, "nothing to measure with dim {} and block {}", dim, block);
+        let n = vectors.len() / dim;
+        if n == 0 || queries.is_empty() { return Ok(()); }
+        // no more rows of products than there are queries
+        let block = block.min(queries.len() / dim);
+        let floats = block.checked_mul(n)
+            .ok_or_else(|| anyhow::anyhow!("{} queries by {} vectors overflow a block", block, n))?;
+        let (rows_n, k) = (i32::try_from(n)?, i32::try_from(dim)?);
+        let norms = |values: &[f32]| -> Vec<f32> {
+            values.chunks(dim).map(|v| v.iter().map(|x| x * x).sum()).collect()
+        };
+        let vector_norms = norms(vectors);
+        let on_device = self.upload(vectors)?;
+        let products = self.alloc(floats)?;
+        let mut out = vec![0.0f32; floats];
+        for (b, qs) in queries.chunks(block * dim).enumerate() {
+            let m = qs.len() / dim;
+            let q = self.upload(qs)?;
+            // column-major, (V Qᵀ) is n×m: row-major, the m×n products Q Vᵀ
+            let (alpha, beta) = (1.0f32, 0.0f32);
+            let status = unsafe {
+                (self.api.sgemm)(self.handle, OP_T, OP_N, rows_n, i32::try_from(m)?, k, &alpha,
+                                 on_device.ptr.cast(), k, q.ptr.cast(), k, &beta,
+                                 products.ptr.cast(), rows_n)
+            };
+            check(status, "cublasSgemm")?;
+            let rows = &mut out[..m * n];
+            let status = unsafe {
+                (self.api.memcpy)(rows.as_mut_ptr().cast(), products.ptr, size_of_val(rows), DEVICE_TO_HOST)
+            };
+            check(status, "cudaMemcpy")?;
+            for (row, q) in rows.chunks_mut(n).zip(norms(qs)) {
+                for (d, v) in row.iter_mut().zip(&vector_norms) {
+                    *d = (q + v - 2.0 * *d).max(0.0);
+                }
+            }
+            sink(b * block, rows);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,298 @@
+//! Checking that a store is fit to use (`feather doctor`), and that a
+//! server is (`/healthz`, `/readyz`).
+//!
+//! `diagnose` looks at a store from outside, without opening it, and makes
+//! one `Finding` per check, saying what to do about anything not `Ok`:
+//!
+//! - permissions: the file, every shard file of a sharded store, can be
+//!   read and written, and so can its directory, as a save renames a new
+//!   copy over the file;
+//! - lock: whether another process has the store open, a writer (every
+//!   open waits for it) or readers (writers wait for them), and its pid;
+//! - disk: the filesystem has room for a save, which writes the whole file
+//!   anew beside the old one; less than twice that is a warning;
+//! - WAL: one larger than the file it follows makes every open replay it;
+//!   a checkpoint empties it;
+//! - index: what `fsck::check` finds;
+//! - config: the config file parses (see `config`), and the database it
+//!   names exists.
+//!
+//! A server answers `GET /healthz` as soon as it has read the request, on
+//! the connection's thread, so even while the store is busy: `{"status":
+//! "ok"}` says only that the process is up and taking connections. `GET
+//! /readyz` is answered in turn with the other requests, with the checks of
+//! `readiness`: room for a save, and no stop asked for (see `shutdown`). It
+//! replies 200 `{"ready": true, "checks": {...}}`, or 503 once one fails,
+//! so a load balancer drains a server before it stops. Neither takes a
+//! token or counts against a rate limit, so probes need no credentials;
+//! neither names a path.
+
+use crate::config::Config;
+use crate::lock::lock_path;
+use crate::{c_char, c_void, fsck, shard, shutdown, DB};
+use serde::Serialize;
+use std::fmt;
+use std::fs::{File, TryLockError};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Problems of `fsck::check` a finding lists before it counts the rest.
+pub const SHOWN_PROBLEMS: usize = 3;
+
+extern "C" {
+    fn feather_path(db: *mut c_void, out: *mut c_char, cap: usize) -> usize;
+}
+
+/// How a check came out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    /// Usable, but something should be done.
+    Warn,
+    /// The store cannot be used as it is.
+    Fail,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Status::Ok => "ok",
+            Status::Warn => "warn",
+            Status::Fail => "fail",
+        })
+    }
+}
+
+/// What one check found.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Finding {
+    pub check: &'static str,
+    pub status: Status,
+    pub detail: String,
+    /// What to do about it; None when it is `Ok`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl Finding {
+    fn ok(check: &'static str, detail: impl Into<String>) -> Self {
+        Finding { check, status: Status::Ok, detail: detail.into(), hint: None }
+    }
+
+    fn warn(check: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Finding { check, status: Status::Warn, detail: detail.into(), hint: Some(hint.into()) }
+    }
+
+    fn fail(check: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Finding { check, status: Status::Fail, detail: detail.into(), hint: Some(hint.into()) }
+    }
+}
+
+/// The worst status among `findings`; `Ok` if there are none.
+pub fn worst(findings: &[Finding]) -> Status {
+    findings.iter().map(|f| f.status).max().unwrap_or(Status::Ok)
+}
+
+/// Check the store at `path` (see the module docs).
+pub fn diagnose(path: &Path) -> Vec<Finding> {
+    if !path.exists() {
+        return vec![
+            Finding::fail("file", format!("no database at {:?}", path), "check the path, or create one with `feather new`"),
+            config(),
+        ];
+    }
+    let files = match path.is_dir() {
+        true => shard::files(path),
+        false => vec![path.to_path_buf()],
+    };
+    if files.is_empty() {
+        return vec![
+            Finding::fail("file", format!("{:?} is a directory without shard files", path), "give the path of a store"),
+            config(),
+        ];
+    }
+    vec![permissions(&files), lock(path), disk(&files), wal(&files), index(path), config()]
+}
+
+/// Whether a server on `files` can take requests: room to save them, and
+/// no stop asked for.
+pub fn readiness(files: &[PathBuf]) -> Vec<Finding> {
+    let stopping = match shutdown::requested() {
+        true => Finding::fail("shutdown", "stopping", "wait for it to restart"),
+        false => Finding::ok("shutdown", "running"),
+    };
+    vec![disk(files), stopping]
+}
+
+impl DB {
+    /// `doctor::readiness` of the files of this store; a store in memory
+    /// has none.
+    pub fn readiness(&self) -> Vec<Finding> {
+        let files: Vec<PathBuf> = self.handle.cores().iter().filter_map(|&core| {
+            let n = unsafe { feather_path(core, std::ptr::null_mut(), 0) };
+            if n == 0 { return None; }
+            let mut buf = vec![0u8; n];
+            unsafe { feather_path(core, buf.as_mut_ptr().cast(), n) };
+            String::from_utf8(buf).ok().map(PathBuf::from)
+        }).collect();
+        readiness(&files)
+    }
+}
+
+fn permissions(files: &[PathBuf]) -> Finding {
+    for file in files {
+        if !accessible(file, true) {
+            let hint = match accessible(file, false) {
+                true => "make it writable (chmod u+w), or open it with --read-only",
+                false => "make it readable and writable by this user (chmod u+rw)",
+            };
+            return Finding::fail("permissions", format!("{:?} cannot be written", file), hint);
+        }
+    }
+    let dir = match files[0].parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    if !accessible(dir, true) {
+        return Finding::fail("permissions", format!("{:?} cannot be written; saves replace the file there", dir),
+                             "make the directory writable (chmod u+w), or open the store with --read-only");
+    }
+    Finding::ok("permissions", format!("{} file(s) and their directory are readable and writable", files.len()))
+}
+
+fn lock(path: &Path) -> Finding {
+    let lock = lock_path(path);
+    let mut file = match File::open(&lock) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Finding::ok("lock", "not locked"),
+        Err(e) => return Finding::fail("lock", format!("cannot open {:?}: {}", lock, e), "make it readable (chmod u+r)"),
+    };
+    let mut pid = String::new();
+    let _ = file.read_to_string(&mut pid);
+    let holder = match pid.trim().parse::<u32>() {
+        Ok(pid) => format!("pid {}", pid),
+        Err(_) => "another process".to_string(),
+    };
+    // taken and dropped on a handle of our own, leaving the holder's pid
+    match file.try_lock() {
+        Ok(()) => Finding::ok("lock", "not locked"),
+        Err(TryLockError::WouldBlock) => match file.try_lock_shared() {
+            Ok(()) => Finding::warn("lock", format!("open read-only by {} (and maybe others)", holder),
+                                    "writers wait until it closes; reads can go ahead with --read-only"),
+            Err(_) => Finding::warn("lock", format!("open for writing by {}", holder),
+                                    "every other open fails until it closes; stop it, or send it the work"),
+        },
+        Err(TryLockError::Error(e)) => Finding::fail("lock", format!("cannot lock {:?}: {}", lock, e),
+                                                     "the filesystem may not support locks; move the store"),
+    }
+}
+
+fn disk(files: &[PathBuf]) -> Finding {
+    // the largest file, WAL included, is the most one save writes at once
+    let need = files.iter().map(|f| size(f) + size(&wal_path(f))).max().unwrap_or(0);
+    let Some(free) = files.iter().filter_map(|f| available(f)).min() else {
+        return Finding::ok("disk", "free space unknown here");
+    };
+    let detail = format!("{} free, a save writes up to {}", mb(free), mb(need));
+    match free {
+        free if free < need => Finding::fail("disk", detail, "free some space, or move the store; saves fail until then"),
+        free if free < 2 * need => Finding::warn("disk", detail, "free some space: the store cannot grow much before saves fail"),
+        _ => Finding::ok("disk", detail),
+    }
+}
+
+fn wal(files: &[PathBuf]) -> Finding {
+    let (wal, file) = files.iter().fold((0, 0), |(w, f), file| (w + size(&wal_path(file)), f + size(file)));
+    let detail = format!("WAL {} beside {} of file", mb(wal), mb(file));
+    match wal > file.max(1 << 20) {
+        true => Finding::warn("wal", detail, "every open replays it: checkpoint with `feather save` or `feather maintain`"),
+        false => Finding::ok("wal", detail),
+    }
+}
+
+fn index(path: &Path) -> Finding {
+    let reports = match path.is_dir() {
+        true => fsck::check_shards(path),
+        false => fsck::check(path).map(|report| vec![(path.to_path_buf(), report)]),
+    };
+    let reports = match reports {
+        Ok(reports) => reports,
+        Err(e) => return Finding::fail("index", format!("cannot read the store: {:#}", e), "see `feather fsck`"),
+    };
+    let problems: Vec<&fsck::Problem> = reports.iter().flat_map(|(_, r)| &r.problems).collect();
+    let records: usize = reports.iter().map(|(_, r)| r.records).sum();
+    if problems.is_empty() {
+        return Finding::ok("index", format!("{} records, no problems", records));
+    }
+    if problems.iter().all(|p| matches!(p, fsck::Problem::OldFormat { .. })) {
+        return Finding::warn("index", problems[0].to_string(), "saving rewrites it in the current format");
+    }
+    let mut detail: Vec<String> = problems.iter().take(SHOWN_PROBLEMS).map(|p| p.to_string()).collect();
+    if problems.len() > SHOWN_PROBLEMS {
+        detail.push(format!("{} more", problems.len() - SHOWN_PROBLEMS));
+    }
+    let hint = match problems.iter().all(|p| p.is_repairable()) {
+        true => "`feather fsck --repair` fixes them",
+        false => "the file is damaged: restore it from a snapshot or backup; `feather fsck` has the details",
+    };
+    Finding::fail("index", detail.join("; "), hint)
+}
+
+fn config() -> Finding {
+    let Some(path) = Config::path() else { return Finding::ok("config", "no home directory, so no config file") };
+    if !path.exists() {
+        return Finding::ok("config", "no config file; the defaults apply");
+    }
+    match Config::load() {
+        Err(e) => Finding::fail("config", format!("{:#}", e), format!("fix or remove {:?}", path)),
+        Ok(config) => match config.default_db() {
+            Some(db) if !db.exists() => Finding::warn("config", format!("the default database {:?} does not exist", db),
+                                                      "create it with `feather new`, or change `db` or FEATHER_DB"),
+            _ => Finding::ok("config", format!("{:?} is valid", path)),
+        },
+    }
+}
+
+fn size(path: &Path) -> u64 {
+    std::fs::metadata(path).map_or(0, |m| m.len())
+}
+
+fn wal_path(path: &Path) -> PathBuf {
+    let mut wal = path.as_os_str().to_owned();
+    wal.push(".wal");
+    PathBuf::from(wal)
+}
+
+fn mb(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / 1e6)
+}
+
+// Whether this process may read `path`, and write it if `write`.
+#[cfg(unix)]
+fn accessible(path: &Path, write: bool) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    let Ok(c_path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else { return false };
+    let mode = if write { libc::R_OK | libc::W_OK } else { libc::R_OK };
+    unsafe { libc::access(c_path.as_ptr(), mode) == 0 }
+}
+
+#[cfg(not(unix))]
+fn accessible(path: &Path, write: bool) -> bool {
+    std::fs::metadata(path).is_ok_and(|m| !write || !m.permissions().readonly())
+}
+
+// Bytes free to this process on the filesystem holding `path`.
+#[cfg(unix)]
+fn available(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 { return None; }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn available(_path: &Path) -> Option<u64> {
+    None
+}
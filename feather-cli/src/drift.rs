@@ -0,0 +1,152 @@
+//! Drift monitoring: compares the distribution of incoming query vectors
+//! with the stored vectors of the same modality.
+//!
+//! Every search folds its (projected) query into a per-modality running
+//! accumulator persisted in the DB properties, so the comparison spans CLI
+//! invocations. `reset_drift` starts a new observation window.
+
+use crate::DB;
+use std::collections::HashMap;
+
+/// Property key holding every modality's query accumulator.
+pub(crate) const PROPERTY_KEY: &str = "drift";
+
+/// Relative centroid shift above which a modality is reported as drifted.
+pub const DEFAULT_THRESHOLD: f32 = 0.25;
+
+/// Queries needed before a drift verdict is meaningful.
+pub const MIN_QUERIES: u64 = 10;
+
+/// Running first/second moments of a stream of vectors.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DistributionStats {
+    pub count: u64,
+    sum: Vec<f64>,
+    norm_sum: f64,
+    norm_sq_sum: f64,
+}
+
+impl DistributionStats {
+    pub fn observe(&mut self, v: &[f32]) {
+        if self.sum.len() != v.len() {
+            // dimension changed (e.g. after redim): restart the window
+            *self = DistributionStats { sum: vec![0.0; v.len()], ..Default::default() };
+        }
+        for (s, x) in self.sum.iter_mut().zip(v) { *s += *x as f64; }
+        let norm = v.iter().map(|x| (*x as f64).powi(2)).sum::<f64>().sqrt();
+        self.norm_sum += norm;
+        self.norm_sq_sum += norm * norm;
+        self.count += 1;
+    }
+
+    pub fn dim(&self) -> usize { self.sum.len() }
+
+    pub fn centroid(&self) -> Vec<f32> {
+        let n = self.count.max(1) as f64;
+        self.sum.iter().map(|s| (s / n) as f32).collect()
+    }
+
+    pub fn norm_mean(&self) -> f32 {
+        (self.norm_sum / self.count.max(1) as f64) as f32
+    }
+
+    pub fn norm_std(&self) -> f32 {
+        let n = self.count.max(1) as f64;
+        let mean = self.norm_sum / n;
+        ((self.norm_sq_sum / n - mean * mean).max(0.0)).sqrt() as f32
+    }
+}
+
+/// Query distribution compared against the stored one.
+#[derive(Clone, Debug)]
+pub struct DriftReport {
+    pub modality: String,
+    pub queries: u64,
+    pub stored: u64,
+    /// ‖query centroid − stored centroid‖ / mean stored norm.
+    pub centroid_shift: f32,
+    pub query_norm_mean: f32,
+    pub query_norm_std: f32,
+    pub stored_norm_mean: f32,
+    pub stored_norm_std: f32,
+}
+
+impl DriftReport {
+    /// Whether the query traffic has moved far enough from the stored data to
+    /// hurt recall: the centroid shifted by more than `threshold`, or the mean
+    /// query norm sits over 3 stored standard deviations away. Needs at least
+    /// `MIN_QUERIES` observed queries.
+    pub fn drifted(&self, threshold: f32) -> bool {
+        if self.queries < MIN_QUERIES || self.stored == 0 { return false; }
+        let norm_off = (self.query_norm_mean - self.stored_norm_mean).abs();
+        self.centroid_shift > threshold || norm_off > 3.0 * self.stored_norm_std.max(f32::EPSILON)
+    }
+}
+
+/// Compare the queries observed for `modality` with its stored vectors, or
+/// None if no query has been observed since the last reset.
+pub fn report(db: &DB, modality: &str) -> Option<DriftReport> {
+    let queries = db.query_stats(modality)?;
+    let mut stored = DistributionStats::default();
+    for id in db.ids(modality) {
+        if let Some(v) = db.get_vector(id, modality) { stored.observe(&v); }
+    }
+    let shift = if stored.dim() == queries.dim() {
+        let dist = stored.centroid().iter().zip(queries.centroid())
+            .map(|(a, b)| (a - b).powi(2))
+            .sum::<f32>()
+            .sqrt();
+        dist / stored.norm_mean().max(f32::EPSILON)
+    } else {
+        f32::INFINITY
+    };
+    Some(DriftReport {
+        modality: modality.to_string(),
+        queries: queries.count,
+        stored: stored.count,
+        centroid_shift: shift,
+        query_norm_mean: queries.norm_mean(),
+        query_norm_std: queries.norm_std(),
+        stored_norm_mean: stored.norm_mean(),
+        stored_norm_std: stored.norm_std(),
+    })
+}
+
+pub(crate) fn encode(map: &HashMap<String, DistributionStats>) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend((map.len() as u32).to_le_bytes());
+    for (name, s) in map {
+        out.extend((name.len() as u16).to_le_bytes());
+        out.extend(name.as_bytes());
+        out.extend(s.count.to_le_bytes());
+        out.extend(s.norm_sum.to_le_bytes());
+        out.extend(s.norm_sq_sum.to_le_bytes());
+        out.extend((s.sum.len() as u32).to_le_bytes());
+        for x in &s.sum { out.extend(x.to_le_bytes()); }
+    }
+    out
+}
+
+pub(crate) fn decode(mut bytes: &[u8]) -> Option<HashMap<String, DistributionStats>> {
+    fn take<'a>(b: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+        if b.len() < n { return None; }
+        let (head, tail) = b.split_at(n);
+        *b = tail;
+        Some(head)
+    }
+    fn f64_at(b: &mut &[u8]) -> Option<f64> { Some(f64::from_le_bytes(take(b, 8)?.try_into().ok()?)) }
+
+    let count = u32::from_le_bytes(take(&mut bytes, 4)?.try_into().ok()?);
+    let mut map = HashMap::new();
+    for _ in 0..count {
+        let name_len = u16::from_le_bytes(take(&mut bytes, 2)?.try_into().ok()?) as usize;
+        let name = String::from_utf8(take(&mut bytes, name_len)?.to_vec()).ok()?;
+        let n = u64::from_le_bytes(take(&mut bytes, 8)?.try_into().ok()?);
+        let norm_sum = f64_at(&mut bytes)?;
+        let norm_sq_sum = f64_at(&mut bytes)?;
+        let dim = u32::from_le_bytes(take(&mut bytes, 4)?.try_into().ok()?) as usize;
+        let sum = (0..dim).map(|_| f64_at(&mut bytes)).collect::<Option<Vec<_>>>()?;
+        map.insert(name, DistributionStats { count: n, sum, norm_sum, norm_sq_sum });
+    }
+    Some(map)
+}
@@ -0,0 +1,84 @@
+//! Reporting near-duplicate records already in the store (`DB::dupes`,
+//! `feather dupes`).
+//!
+//! The dedup mode (see `dedup`) keeps new duplicates out; this finds the
+//! ones stored before it was set, or under another mode. Two live records
+//! are duplicates if they have the same non-empty content, or if their
+//! vectors in a modality have a cosine similarity of at least `threshold`.
+//! Going from the oldest record on, each record not yet in a group leads a
+//! new one, of the ungrouped records that duplicate it; so a chain of
+//! records, each a little further from the first, does not end up as one
+//! group. A group lists its oldest record first, the copy worth keeping,
+//! then the newer ones, which `feather dupes --delete` forgets.
+
+use crate::{Metadata, DB};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// Default cosine similarity at which records count as duplicates.
+pub const DEFAULT_THRESHOLD: f32 = 0.98;
+
+// Nearest neighbours of a group's oldest record looked at for its copies.
+const CANDIDATES: usize = 32;
+
+/// A group of records that duplicate one another.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Duplicates {
+    /// The records, oldest first (by timestamp, then id).
+    pub members: Vec<u64>,
+    /// Whether every member has the same content.
+    pub same_content: bool,
+}
+
+impl Duplicates {
+    /// The members after the oldest one.
+    pub fn newer(&self) -> &[u64] { &self.members[1..] }
+}
+
+impl DB {
+    /// The groups of live records that duplicate one another by content,
+    /// or by their vectors in `modality` at `threshold` cosine similarity
+    /// (see the module docs), ordered by their oldest record.
+    pub fn dupes(&self, modality: &str, threshold: f32) -> anyhow::Result<Vec<Duplicates>> {
+        anyhow::ensure!(threshold > 0.0 && threshold <= 1.0, "threshold must be in (0, 1]");
+        let mut records: Vec<(u64, Metadata)> = self.all_ids().into_iter()
+            .filter_map(|id| Some((id, self.get_metadata(id)?)))
+            .filter(|(_, meta)| !meta.is_forgotten())
+            .collect();
+        records.sort_by_key(|(id, meta)| (meta.timestamp, *id));
+        let rank: HashMap<u64, usize> = records.iter().enumerate().map(|(i, &(id, _))| (id, i)).collect();
+        let mut by_content: HashMap<&str, Vec<u64>> = HashMap::new();
+        for (id, meta) in records.iter().filter(|(_, meta)| !meta.content.is_empty()) {
+            by_content.entry(meta.content.as_str()).or_default().push(*id);
+        }
+
+        let mut grouped = HashSet::new();
+        let mut found = Vec::new();
+        for (oldest, meta) in &records {
+            if grouped.contains(oldest) { continue; }
+            let mut members = vec![*oldest];
+            if let Some(same) = by_content.get(meta.content.as_str()) {
+                members.extend(same.iter().filter(|&&id| id != *oldest && !grouped.contains(&id)));
+            }
+            if let Some(vector) = self.get_vector(*oldest, modality) {
+                for (other, _) in self.knn(&vector, CANDIDATES, modality)? {
+                    if members.contains(&other) || grouped.contains(&other) || !rank.contains_key(&other) { continue; }
+                    let Some(theirs) = self.get_vector(other, modality) else { continue };
+                    if cosine(&vector, &theirs) >= threshold { members.push(other); }
+                }
+            }
+            if members.len() < 2 { continue; }
+            members.sort_by_key(|id| rank[id]);
+            grouped.extend(members.iter().copied());
+            let same_content = members.iter().all(|id| records[rank[id]].1.content == meta.content);
+            found.push(Duplicates { members, same_content });
+        }
+        Ok(found)
+    }
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norms = a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norms == 0.0 { 0.0 } else { dot / norms }
+}
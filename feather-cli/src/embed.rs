@@ -0,0 +1,88 @@
+//! Raw text in place of vectors, through a pluggable embedding model.
+//!
+//! The store keeps and searches vectors; an `EmbeddingProvider` turns text
+//! into them. Set one on a store (`DB::set_embedder`) and `add_text` and
+//! `search_text` take text, embedding it on the way in. Any model or
+//! service that embeds a batch of strings can be wired in, a closure
+//! included, without touching the store:
+//!
+//! ```ignore
+//! db.set_embedder(move |texts: &[&str]| client.embed(texts));
+//! db.add_text(7, "the deploy failed because of OOM")?;
+//! let hits = db.search_text("why did deploys fail?", 5, "text", &SearchOptions::default())?;
+//! ```
+
+#[cfg(feature = "local-embed")]
+pub mod local;
+pub mod remote;
+
+use crate::rerank::Query;
+use crate::{Inserted, SearchOptions, DB};
+use std::rc::Rc;
+
+/// Turns text into vectors.
+pub trait EmbeddingProvider {
+    /// One vector per text, in order. Every vector must have the dimension
+    /// of the modality it is stored in or searched against.
+    fn embed(&self, texts: &[&str]) -> anyhow::Result<Vec<Vec<f32>>>;
+}
+
+impl<F> EmbeddingProvider for F
+where
+    F: Fn(&[&str]) -> anyhow::Result<Vec<Vec<f32>>>,
+{
+    fn embed(&self, texts: &[&str]) -> anyhow::Result<Vec<Vec<f32>>> {
+        self(texts)
+    }
+}
+
+impl EmbeddingProvider for Box<dyn EmbeddingProvider> {
+    fn embed(&self, texts: &[&str]) -> anyhow::Result<Vec<Vec<f32>>> {
+        (**self).embed(texts)
+    }
+}
+
+impl DB {
+    /// Use `embedder` for every handle on this file (collections included).
+    /// Not persisted.
+    pub fn set_embedder(&self, embedder: impl EmbeddingProvider + 'static) {
+        self.handle.embedder.replace(Some(Rc::new(embedder)));
+    }
+
+    /// The embedder set on this file, if any.
+    pub fn embedder(&self) -> Option<Rc<dyn EmbeddingProvider>> {
+        self.handle.embedder.borrow().clone()
+    }
+
+    /// Embed `texts` with this file's embedder. Fails if none is set, or if
+    /// it does not return one vector per text.
+    pub fn embed(&self, texts: &[&str]) -> anyhow::Result<Vec<Vec<f32>>> {
+        let embedder = self.embedder().ok_or_else(|| anyhow::anyhow!("no embedder set; see DB::set_embedder"))?;
+        let vectors = embedder.embed(texts)?;
+        anyhow::ensure!(vectors.len() == texts.len(),
+                        "embedder returned {} vectors for {} texts", vectors.len(), texts.len());
+        Ok(vectors)
+    }
+
+    /// Insert record `id` with `text` as its content and its embedding in
+    /// the `text` modality, as `insert(..).content(text).execute()` would.
+    pub fn add_text(&self, id: u64, text: &str) -> anyhow::Result<Inserted> {
+        Ok(self.add_texts(&[(id, text)])?.remove(0))
+    }
+
+    /// `add_text` for many records, embedded in one call.
+    pub fn add_texts(&self, records: &[(u64, &str)]) -> anyhow::Result<Vec<Inserted>> {
+        let texts: Vec<&str> = records.iter().map(|&(_, text)| text).collect();
+        let vectors = self.embed(&texts)?;
+        records.iter().zip(&vectors)
+            .map(|(&(id, text), vector)| self.insert(id, vector).content(text).execute())
+            .collect()
+    }
+
+    /// `search_with_options` for the embedding of `text`.
+    pub fn search_text(&self, text: &str, k: usize, modality: &str,
+                       options: &SearchOptions) -> anyhow::Result<Vec<(u64, f32)>> {
+        let query = self.embed(&[text])?.remove(0);
+        self.search_query(Query { vector: &query, text: Some(text) }, k, modality, options)
+    }
+}
@@ -0,0 +1,156 @@
+//! A local embedding model, run in-process (`--features local-embed`).
+//!
+//! `StaticEmbedder` loads a static embedding model in the Model2Vec layout
+//! (e.g. `minishlab/potion-base-8M`): a directory with `tokenizer.json`
+//! (a WordPiece tokenizer), `model.safetensors` (one row per token) and,
+//! optionally, `config.json`. A text embeds as the mean of its tokens'
+//! rows, unit-normalized unless the config says otherwise. There is no
+//! neural network to run, so it needs no runtime and embeds thousands of
+//! texts a second on one core.
+
+use super::EmbeddingProvider;
+use crate::vectors;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Tensor holding the token embeddings in a Model2Vec `model.safetensors`.
+pub const EMBEDDINGS_TENSOR: &str = "embeddings";
+
+// Words longer than this (in chars) become the unknown token, as in BERT.
+const MAX_WORD_CHARS: usize = 100;
+
+pub struct StaticEmbedder {
+    vocab: HashMap<String, usize>,
+    unk: Option<usize>,
+    prefix: String,
+    lowercase: bool,
+    normalize: bool,
+    dim: usize,
+    // row-major, one row of `dim` per token id
+    embeddings: Vec<f32>,
+}
+
+impl StaticEmbedder {
+    /// Load the model in directory `dir`.
+    pub fn load(dir: &Path) -> anyhow::Result<Self> {
+        let read = |name: &str| {
+            std::fs::read(dir.join(name)).map_err(|e| anyhow::anyhow!("{:?}: {}", dir.join(name), e))
+        };
+        let tokenizer: Value = serde_json::from_slice(&read("tokenizer.json")?)
+            .map_err(|e| anyhow::anyhow!("tokenizer.json: {}", e))?;
+        let model = &tokenizer["model"];
+        anyhow::ensure!(model["type"] == "WordPiece",
+                        "tokenizer.json: only WordPiece tokenizers are supported, not {}", model["type"]);
+        let vocab: HashMap<String, usize> = serde_json::from_value(model["vocab"].clone())
+            .map_err(|e| anyhow::anyhow!("tokenizer.json: bad vocab: {}", e))?;
+        let unk = model["unk_token"].as_str().and_then(|t| vocab.get(t).copied());
+        let prefix = model["continuing_subword_prefix"].as_str().unwrap_or("##").to_string();
+        let lowercase = tokenizer["normalizer"]["lowercase"].as_bool().unwrap_or(true);
+        let normalize = match std::fs::read(dir.join("config.json")) {
+            Ok(raw) => serde_json::from_slice::<Value>(&raw)
+                .map_err(|e| anyhow::anyhow!("config.json: {}", e))?["normalize"]
+                .as_bool()
+                .unwrap_or(true),
+            Err(_) => true,
+        };
+        let (rows, dim, embeddings) = read_embeddings(&read("model.safetensors")?)
+            .map_err(|e| anyhow::anyhow!("model.safetensors: {}", e))?;
+        anyhow::ensure!(vocab.values().all(|&id| id < rows),
+                        "tokenizer.json has token ids past the {} rows of model.safetensors", rows);
+        Ok(StaticEmbedder { vocab, unk, prefix, lowercase, normalize, dim, embeddings })
+    }
+
+    /// Dimension of the vectors this model produces.
+    pub fn dim(&self) -> usize { self.dim }
+
+    fn embed_one(&self, text: &str) -> Vec<f32> {
+        let mut sum = vec![0.0f32; self.dim];
+        let mut n = 0;
+        for id in self.tokenize(text) {
+            let row = &self.embeddings[id * self.dim..(id + 1) * self.dim];
+            sum.iter_mut().zip(row).for_each(|(s, x)| *s += x);
+            n += 1;
+        }
+        if n > 0 {
+            sum.iter_mut().for_each(|s| *s /= n as f32);
+        }
+        if self.normalize {
+            let norm = sum.iter().map(|x| x * x).sum::<f32>().sqrt();
+            if norm > 0.0 { sum.iter_mut().for_each(|s| *s /= norm); }
+        }
+        sum
+    }
+
+    // Token ids of `text`, unknown words dropped (they carry no meaning of
+    // their own in a static model).
+    fn tokenize(&self, text: &str) -> Vec<usize> {
+        let text = if self.lowercase { text.to_lowercase() } else { text.to_string() };
+        let mut ids = Vec::new();
+        for word in words(&text) {
+            ids.extend(self.word_pieces(word).into_iter().filter(|&id| Some(id) != self.unk));
+        }
+        ids
+    }
+
+    // Greedy longest-match-first WordPiece; the unknown token if the word
+    // cannot be covered.
+    fn word_pieces(&self, word: &str) -> Vec<usize> {
+        let chars: Vec<char> = word.chars().collect();
+        if chars.len() > MAX_WORD_CHARS { return self.unk.into_iter().collect(); }
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        while start < chars.len() {
+            let mut end = chars.len();
+            let piece = loop {
+                if end == start { return self.unk.into_iter().collect(); }
+                let mut candidate: String = chars[start..end].iter().collect();
+                if start > 0 { candidate.insert_str(0, &self.prefix); }
+                if let Some(&id) = self.vocab.get(&candidate) { break id; }
+                end -= 1;
+            };
+            pieces.push(piece);
+            start = end;
+        }
+        pieces
+    }
+}
+
+impl EmbeddingProvider for StaticEmbedder {
+    fn embed(&self, texts: &[&str]) -> anyhow::Result<Vec<Vec<f32>>> {
+        Ok(texts.iter().map(|text| self.embed_one(text)).collect())
+    }
+}
+
+// BERT pre-tokenization: split on whitespace, and make every punctuation or
+// symbol character a word of its own.
+fn words(text: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    for chunk in text.split_whitespace() {
+        let mut start = 0;
+        for (i, c) in chunk.char_indices() {
+            if !c.is_alphanumeric() {
+                if start < i { words.push(&chunk[start..i]); }
+                words.push(&chunk[i..i + c.len_utf8()]);
+                start = i + c.len_utf8();
+            }
+        }
+        if start < chunk.len() { words.push(&chunk[start..]); }
+    }
+    words
+}
+
+// The token embedding matrix of a safetensors file: (rows, dim, data).
+fn read_embeddings(raw: &[u8]) -> anyhow::Result<(usize, usize, Vec<f32>)> {
+    let names = vectors::tensor_names(raw)?;
+    let name = match names.len() {
+        _ if names.iter().any(|n| n == EMBEDDINGS_TENSOR) => Some(EMBEDDINGS_TENSOR),
+        1 => None,
+        _ => anyhow::bail!("no tensor named '{}'", EMBEDDINGS_TENSOR),
+    };
+    let (shape, values) = vectors::read_safetensors(raw, name)?;
+    let &[rows, dim] = shape.as_slice() else {
+        anyhow::bail!("embeddings must be 2-D, not {:?}", shape)
+    };
+    Ok((rows, dim, values))
+}
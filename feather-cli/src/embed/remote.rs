@@ -0,0 +1,108 @@
+//! Embedding through an OpenAI-compatible HTTP API (`--embed-api`).
+//!
+//! `ApiEmbedder` posts `{"model", "input": [texts]}` to `<base>/embeddings`
+//! and reads the vectors back from `data[].embedding`, which is what
+//! OpenAI, Azure-style proxies, Ollama, vLLM, LM Studio and llama.cpp's
+//! server all speak. Requests go through the `curl` binary, so HTTPS and
+//! proxies work without a TLS stack in feather; the request, API key
+//! included, is passed on curl's stdin, never on its command line.
+
+use super::EmbeddingProvider;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Environment variables read for the API key, first set one wins.
+pub const KEY_VARS: [&str; 2] = ["FEATHER_EMBED_API_KEY", "OPENAI_API_KEY"];
+
+/// Most texts sent in one request.
+pub const MAX_BATCH: usize = 256;
+
+pub struct ApiEmbedder {
+    url: String,
+    model: String,
+    key: Option<String>,
+}
+
+impl ApiEmbedder {
+    /// An embedder for `model` behind the API at `base` (e.g.
+    /// `https://api.openai.com/v1`), with the key from `KEY_VARS` if set.
+    pub fn new(base: &str, model: &str) -> Self {
+        let key = KEY_VARS.iter().find_map(|var| std::env::var(var).ok().filter(|k| !k.is_empty()));
+        ApiEmbedder {
+            url: format!("{}/embeddings", base.trim_end_matches('/')),
+            model: model.to_string(),
+            key,
+        }
+    }
+
+    fn request(&self, texts: &[&str]) -> anyhow::Result<Vec<Vec<f32>>> {
+        let body = json!({ "model": self.model, "input": texts }).to_string();
+        let mut config = format!("url = {}\nrequest = POST\nheader = \"Content-Type: application/json\"\n",
+                                 quote(&self.url));
+        if let Some(key) = &self.key {
+            config += &format!("header = {}\n", quote(&format!("Authorization: Bearer {}", key)));
+        }
+        config += &format!("data-binary = {}\n", quote(&body));
+        let mut curl = Command::new("curl")
+            .args(["--silent", "--show-error", "--config", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("--embed-api needs curl on the PATH: {}", e))?;
+        curl.stdin.take().expect("piped").write_all(config.as_bytes())?;
+        let out = curl.wait_with_output()?;
+        anyhow::ensure!(out.status.success(), "{}: {}", self.url, String::from_utf8_lossy(&out.stderr).trim());
+        let reply: Value = serde_json::from_slice(&out.stdout)
+            .map_err(|_| anyhow::anyhow!("{}: reply is not JSON: {}", self.url,
+                                         String::from_utf8_lossy(&out.stdout).chars().take(200).collect::<String>()))?;
+        if let Some(error) = reply.get("error") {
+            let message = error.get("message").and_then(Value::as_str).map_or_else(|| error.to_string(), str::to_string);
+            anyhow::bail!("{}: {}", self.url, message);
+        }
+        let data = reply["data"].as_array()
+            .ok_or_else(|| anyhow::anyhow!("{}: reply has no `data` array", self.url))?;
+        let mut vectors = vec![None; texts.len()];
+        for (i, item) in data.iter().enumerate() {
+            let index = item["index"].as_u64().map_or(i, |index| index as usize);
+            let vector: Vec<f32> = serde_json::from_value(item["embedding"].clone())
+                .map_err(|_| anyhow::anyhow!("{}: `embedding` {} is not an array of numbers", self.url, index))?;
+            let slot = vectors.get_mut(index)
+                .ok_or_else(|| anyhow::anyhow!("{}: embedding index {} out of range", self.url, index))?;
+            *slot = Some(vector);
+        }
+        vectors.into_iter()
+            .enumerate()
+            .map(|(i, v)| v.ok_or_else(|| anyhow::anyhow!("{}: no embedding for input {}", self.url, i)))
+            .collect()
+    }
+}
+
+impl EmbeddingProvider for ApiEmbedder {
+    fn embed(&self, texts: &[&str]) -> anyhow::Result<Vec<Vec<f32>>> {
+        let mut vectors = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(MAX_BATCH) {
+            vectors.extend(self.request(batch)?);
+        }
+        Ok(vectors)
+    }
+}
+
+// A double-quoted curl config value.
+pub(crate) fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,118 @@
+//! Errors callers may want to tell apart from the rest. They travel inside
+//! `anyhow::Error`; recover one with `err.downcast_ref::<DimensionMismatch>()`.
+
+use std::fmt;
+
+/// A vector or query whose length differs from the stored dimension of the
+/// modality it was meant for (after any projection of that modality).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DimensionMismatch {
+    pub expected: usize,
+    pub got: usize,
+}
+
+impl fmt::Display for DimensionMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "dimension mismatch: expected {} values, got {}", self.expected, self.got)
+    }
+}
+
+impl std::error::Error for DimensionMismatch {}
+
+/// An insert of an id that already has a live record, refused under
+/// `OnDuplicate::Error`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DuplicateId {
+    pub id: u64,
+}
+
+impl fmt::Display for DuplicateId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "record {} already exists", self.id)
+    }
+}
+
+impl std::error::Error for DuplicateId {}
+
+/// A store another handle holds: a writer, or readers when a writer asked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Locked {
+    pub path: std::path::PathBuf,
+    /// The process that last took the lock, if it left its pid.
+    pub pid: Option<u32>,
+}
+
+impl fmt::Display for Locked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.pid {
+            Some(pid) => write!(f, "database {:?} is locked by pid {}", self.path, pid),
+            None => write!(f, "database {:?} is locked by another process", self.path),
+        }
+    }
+}
+
+impl std::error::Error for Locked {}
+
+/// A change attempted through a store opened with
+/// `OpenOptions::read_only(true)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReadOnly;
+
+impl fmt::Display for ReadOnly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "database is open read-only")
+    }
+}
+
+impl std::error::Error for ReadOnly {}
+
+/// A change attempted through a handle a transaction failed part-way on
+/// (see `txn`): it holds changes that never reached the file, so it writes
+/// nothing more.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Poisoned;
+
+impl fmt::Display for Poisoned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a transaction failed part-way on this handle; reopen the store")
+    }
+}
+
+impl std::error::Error for Poisoned {}
+
+/// A compare-and-swap write (`put_metadata_if`, `forget_if`) of a record
+/// that is no longer at the version the writer read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VersionConflict {
+    pub id: u64,
+    pub expected: u64,
+    /// The record's version now; None once it is gone.
+    pub actual: Option<u64>,
+}
+
+impl fmt::Display for VersionConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.actual {
+            Some(actual) => write!(f, "record {} is at version {}, not {}", self.id, actual, self.expected),
+            None => write!(f, "record {} no longer exists (expected version {})", self.id, self.expected),
+        }
+    }
+}
+
+impl std::error::Error for VersionConflict {}
+
+/// Vectors or queries declared to come from an embedding model other than
+/// the one the store records (see `model`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModelMismatch {
+    pub expected: String,
+    pub got: String,
+}
+
+impl fmt::Display for ModelMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "model mismatch: the store holds vectors from '{}', not '{}'", self.expected, self.got)
+    }
+}
+
+impl std::error::Error for ModelMismatch {}
@@ -0,0 +1,82 @@
+//! Measuring search accuracy on a store's own data (`feather eval`).
+//!
+//! `run` answers a set of query vectors from a store's HNSW index, once per
+//! search beam width (`ef`), timing each query and checking its hits
+//! against the true k nearest neighbours. Those come from a ground-truth
+//! file — the ids of each query's nearest records, computed on the original
+//! vectors, so the report also counts what a projection (`feather redim`)
+//! loses — or, without one, from an exact scan of the vectors the store
+//! holds, which measures the index alone. Searches made this way are not
+//! counted as recalls.
+
+use crate::bench::{self, EfRun};
+use crate::DB;
+use ndarray::ArrayView2;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug, Default)]
+pub struct EvalReport {
+    pub queries: usize,
+    pub k: usize,
+    /// Vectors scanned for the exact neighbours, and the mean time of one
+    /// such query; None with ground truth given.
+    pub brute_force: Option<(usize, Duration)>,
+    /// One run over the queries per `ef`, in the order given.
+    pub runs: Vec<EfRun>,
+}
+
+/// Answer each row of `queries` for its `k` nearest neighbours in
+/// `modality` once per entry of `efs`; `truth`, if given, holds a row of
+/// neighbour ids per query, nearest first, of which the first `k` count
+/// (None entries are padding). `modality` is left searching at the last
+/// `ef`.
+pub fn run(db: &DB, queries: ArrayView2<f32>, truth: Option<&[Vec<Option<u64>>]>, k: usize,
+           modality: &str, efs: &[usize]) -> anyhow::Result<EvalReport> {
+    anyhow::ensure!(queries.nrows() > 0, "no queries");
+    anyhow::ensure!(k > 0, "k must be positive");
+    anyhow::ensure!(efs.iter().all(|&ef| ef > 0), "ef must be positive");
+    anyhow::ensure!(db.modalities().iter().any(|m| m == modality), "no vectors in modality '{}'", modality);
+    let mut report = EvalReport { queries: queries.nrows(), k, ..Default::default() };
+
+    let truth: Vec<HashSet<u64>> = match truth {
+        Some(truth) => {
+            anyhow::ensure!(truth.len() == queries.nrows(), "ground truth has {} rows for {} queries",
+                            truth.len(), queries.nrows());
+            if let Some(row) = truth.iter().find(|row| row.len() < k) {
+                anyhow::bail!("ground truth holds {} neighbours per query; k is {}", row.len(), k);
+            }
+            truth.iter().map(|row| row[..k].iter().flatten().copied().collect()).collect()
+        }
+        None => {
+            let ids = db.ids(modality);
+            let vectors: Vec<(u64, Vec<f32>)> = ids.into_iter()
+                .filter_map(|id| Some((id, db.get_vector(id, modality)?)))
+                .collect();
+            let start = Instant::now();
+            let truth: Vec<HashSet<u64>> = queries.rows().into_iter()
+                .map(|q| exact(db, &vectors, &q.to_vec(), k, modality))
+                .collect();
+            report.brute_force = Some((vectors.len(), start.elapsed() / queries.nrows() as u32));
+            truth
+        }
+    };
+
+    for &ef in efs {
+        report.runs.push(bench::measure(db, queries, &truth, k, ef, modality)?);
+    }
+    Ok(report)
+}
+
+// Ids of the k `vectors` nearest `query` in L2, the query entering the
+// modality as a search's would.
+fn exact(db: &DB, vectors: &[(u64, Vec<f32>)], query: &[f32], k: usize, modality: &str) -> HashSet<u64> {
+    let query = db.project(db.mname(Some(modality)).as_deref(), query);
+    let mut dists: Vec<(f32, u64)> = vectors.iter()
+        .map(|(id, v)| (v.iter().zip(query.iter()).map(|(a, b)| (a - b) * (a - b)).sum(), *id))
+        .collect();
+    let k = k.min(dists.len());
+    if k == 0 { return HashSet::new(); }
+    dists.select_nth_unstable_by(k - 1, |a, b| a.0.total_cmp(&b.0));
+    dists[..k].iter().map(|&(_, id)| id).collect()
+}
@@ -0,0 +1,168 @@
+//! Why a search ranked what it did (`DB::explain_search`, `feather search
+//! --explain`).
+//!
+//! An explained search ranks exactly as `search_with_options` does and
+//! returns each hit's score broken into the signals behind it: the vector
+//! distance and similarity, the keyword and sparse matches, the fused
+//! relevance, recency, the importance and usage, graph activation and a
+//! reranker's verdict. It also lists the candidates the filters turned
+//! away and why, and how the search found its candidates (see `planner`).
+//! It has no side effects: hits are not counted as recalled and the query
+//! does not feed drift stats.
+//!
+//! Under the default scoring a hit's score is `relevance × recency +
+//! graph`; under a scoring policy, the policy's weighted mean of
+//! relevance (its similarity signal), importance, recency, usage and
+//! graph. A reranker's score, or MMR's pick order, then overrides either.
+
+use crate::rerank::Query;
+use crate::scoring;
+use crate::{QueryPlan, SearchOptions, DB};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+
+/// The signals behind one hit's score.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct HitExplanation {
+    pub id: u64,
+    /// The score the search gave it.
+    pub score: f32,
+    /// Squared L2 distance from the query to its vector in the searched
+    /// modality; None without one.
+    pub distance: Option<f32>,
+    /// `1 / (1 + distance)`, 0 without a vector.
+    pub similarity: f32,
+    /// BM25 score over the best one, with `SearchOptions::text`.
+    pub keyword: Option<f32>,
+    /// Sparse dot product over the best one, with `SearchOptions::sparse`.
+    pub sparse: Option<f32>,
+    /// Similarity fused with the keyword and sparse matches.
+    pub relevance: f32,
+    /// Recency: the multiplier on relevance, or under a scoring policy the
+    /// recency signal.
+    pub recency: f32,
+    /// The record's importance; it counts only under a scoring policy.
+    pub importance: f32,
+    /// The usage signal of its recall count (`scoring::usage`); it counts
+    /// only under a scoring policy.
+    pub usage: f32,
+    /// Activation received along links: added to the score, or under a
+    /// scoring policy the graph proximity signal.
+    pub graph: f32,
+    /// The reranker's score, if one ran.
+    pub reranker: Option<f32>,
+    /// Whether it joined as a record linked to a hit
+    /// (`SearchOptions::linked_modalities`) rather than on its own score.
+    pub linked: bool,
+}
+
+/// Why a candidate was dropped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rejection {
+    /// It is forgotten.
+    Forgotten,
+    /// Its timestamp is outside `SearchOptions::time_range`.
+    TimeRange,
+    /// Its importance is outside `SearchOptions::importance_range`.
+    Importance,
+    /// It is outside `SearchOptions::session`, or in
+    /// `SearchOptions::exclude_session`.
+    Session,
+    /// It is archived and `SearchOptions::include_archived` is not set.
+    Archived,
+    /// It is one of `SearchOptions::exclude_ids`, or from one of its
+    /// `exclude_sources` or `exclude_types`.
+    Excluded,
+    /// It does not match `SearchOptions::filter`.
+    Filter,
+    /// `SearchOptions::access` does not see it.
+    Access,
+    /// It scored below `SearchOptions::min_score`.
+    MinScore,
+    /// Better hits from its source fill `SearchOptions::max_per_source`.
+    PerSource,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Rejection::Forgotten => "forgotten",
+            Rejection::TimeRange => "outside the time range",
+            Rejection::Importance => "outside the importance range",
+            Rejection::Session => "outside the session scope",
+            Rejection::Archived => "archived",
+            Rejection::Excluded => "excluded",
+            Rejection::Filter => "does not match the filter",
+            Rejection::Access => "not visible to the caller",
+            Rejection::MinScore => "below the minimum score",
+            Rejection::PerSource => "over the per-source limit",
+        })
+    }
+}
+
+/// An explained search.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Explanation {
+    /// The hits, in order.
+    pub hits: Vec<HitExplanation>,
+    /// Candidates dropped, and why, by id.
+    pub rejected: Vec<(u64, Rejection)>,
+    /// How the candidates were found.
+    pub plan: QueryPlan,
+}
+
+// What a search notes as it ranks (`DB::ranked`).
+#[derive(Default)]
+pub(crate) struct Trace {
+    hits: HashMap<u64, HitExplanation>,
+    rejected: Vec<(u64, Rejection)>,
+    pub(crate) plan: QueryPlan,
+}
+
+impl Trace {
+    // Forget a pass over the candidates, before the next.
+    pub(crate) fn clear(&mut self) {
+        self.hits.clear();
+        self.rejected.clear();
+    }
+
+    pub(crate) fn note(&mut self, hit: HitExplanation) {
+        self.hits.insert(hit.id, hit);
+    }
+
+    pub(crate) fn reject(&mut self, id: u64, reason: Rejection) {
+        self.rejected.push((id, reason));
+    }
+
+    // The signals of `id`, blank for a record first met through a link.
+    pub(crate) fn entry(&mut self, id: u64) -> &mut HitExplanation {
+        self.hits.entry(id).or_insert_with(|| HitExplanation { id, recency: 1.0, ..HitExplanation::default() })
+    }
+}
+
+impl DB {
+    /// Run `search_with_options` and explain its hits (see the module docs).
+    pub fn explain_search(&self, query: &[f32], k: usize, modality: &str,
+                          options: &SearchOptions) -> anyhow::Result<Explanation> {
+        let mut trace = Trace::default();
+        let query = Query { vector: query, text: options.text.as_deref() };
+        let hits = self.ranked(query, k, modality, options, Some(&mut trace))?;
+        let hits = hits.into_iter()
+            .map(|(id, score)| {
+                let mut hit = trace.hits.remove(&id).unwrap_or_default();
+                hit.id = id;
+                hit.score = score;
+                if let Some(meta) = self.get_metadata(id) {
+                    hit.importance = meta.importance;
+                    hit.usage = scoring::usage(meta.recall_count);
+                }
+                hit
+            })
+            .collect();
+        let mut rejected = trace.rejected;
+        rejected.sort_by_key(|&(id, _)| id);
+        Ok(Explanation { hits, rejected, plan: trace.plan })
+    }
+}
@@ -0,0 +1,265 @@
+//! Dumping records for analytics pipelines (`feather export`).
+//!
+//! JSONL is always available; Arrow IPC and Parquet need the `arrow` /
+//! `parquet` cargo features. Columnar formats hold one nullable
+//! `vector_<modality>` list column per modality, the scalar metadata fields,
+//! and `attributes` / `edges` as JSON strings; sparse vectors are exported
+//! to JSONL only. Each record carries the links it starts from in `edges`;
+//! `write_links` lists them all on their own, as the links CSV `feather
+//! import --links` and `feather bootstrap` read.
+
+use crate::{Link, Record, DB};
+use std::io::Write;
+
+/// A sink for exported records.
+pub trait RecordWriter {
+    fn write(&mut self, record: &Record) -> anyhow::Result<()>;
+    /// Flush buffered rows and finalize the output (footers etc.).
+    fn finish(&mut self) -> anyhow::Result<()>;
+}
+
+/// One JSON object per line: `{"id":..,"vectors":{"text":[..]},"timestamp":..,..}`.
+pub struct JsonlWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> JsonlWriter<W> {
+    pub fn new(out: W) -> Self { JsonlWriter { out } }
+}
+
+impl<W: Write> RecordWriter for JsonlWriter<W> {
+    fn write(&mut self, record: &Record) -> anyhow::Result<()> {
+        serde_json::to_writer(&mut self.out, record)?;
+        self.out.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Write every live record of `db` (sorted by id) to `writer`, then finish
+/// it; forgotten records are skipped. Returns the number of records written.
+pub fn export(db: &DB, writer: &mut dyn RecordWriter) -> anyhow::Result<usize> {
+    let mut ids = db.all_ids();
+    ids.sort_unstable();
+    let mut n = 0;
+    for id in ids {
+        if let Some(record) = db.record(id).filter(|r| !r.metadata.is_forgotten()) {
+            writer.write(&record)?;
+            n += 1;
+        }
+    }
+    writer.finish()?;
+    Ok(n)
+}
+
+/// Write every link between live records of `db` to `out` as CSV, with a
+/// `from,to,rel_type,weight` header, by `from` id and then in the order the
+/// record holds them. Returns the number of links written.
+pub fn write_links<W: Write>(db: &DB, out: W) -> anyhow::Result<usize> {
+    let live = |id: u64| db.get_metadata(id).filter(|m| !m.is_forgotten());
+    let mut ids = db.all_ids();
+    ids.sort_unstable();
+    let mut writer = csv::Writer::from_writer(out);
+    let mut n = 0;
+    for id in ids {
+        let Some(meta) = live(id) else { continue };
+        for edge in meta.edges.into_iter().filter(|e| live(e.target).is_some()) {
+            writer.serialize(Link { from: id, to: edge.target, rel_type: edge.rel_type, weight: edge.weight })?;
+            n += 1;
+        }
+    }
+    if n == 0 {
+        writer.write_record(["from", "to", "rel_type", "weight"])?;
+    }
+    writer.flush()?;
+    Ok(n)
+}
+
+#[cfg(feature = "arrow")]
+pub use columnar::ArrowWriter;
+#[cfg(feature = "parquet")]
+pub use columnar::ParquetWriter;
+
+#[cfg(feature = "arrow")]
+pub mod columnar {
+    use super::RecordWriter;
+    use crate::Record;
+    use arrow::array::{
+        ArrayRef, Float32Array, Float32Builder, Int64Array, ListBuilder, StringArray, UInt32Array,
+        UInt64Array, UInt8Array,
+    };
+    use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+    use arrow::record_batch::RecordBatch;
+    use std::io::Write;
+    use std::sync::Arc;
+
+    /// Rows buffered per record batch.
+    const BATCH_ROWS: usize = 8192;
+
+    /// The export schema for a store with the given modalities.
+    pub fn schema(modalities: &[String]) -> SchemaRef {
+        let mut fields = vec![Field::new("id", DataType::UInt64, false)];
+        for m in modalities {
+            let item = Arc::new(Field::new("item", DataType::Float32, true));
+            fields.push(Field::new(format!("vector_{}", m), DataType::List(item), true));
+        }
+        fields.extend([
+            Field::new("timestamp", DataType::Int64, false),
+            Field::new("importance", DataType::Float32, false),
+            Field::new("context_type", DataType::UInt8, false),
+            Field::new("source", DataType::Utf8, false),
+            Field::new("content", DataType::Utf8, false),
+            Field::new("tags_json", DataType::Utf8, false),
+            Field::new("recall_count", DataType::UInt32, false),
+            Field::new("last_recalled_at", DataType::UInt64, false),
+            Field::new("namespace_id", DataType::Utf8, false),
+            Field::new("entity_id", DataType::Utf8, false),
+            Field::new("attributes", DataType::Utf8, false),
+            Field::new("edges", DataType::Utf8, false),
+            Field::new("ttl", DataType::Int64, false),
+            Field::new("confidence", DataType::Float32, false),
+        ]);
+        Arc::new(Schema::new(fields))
+    }
+
+    /// Build one record batch over `rows` in `schema(modalities)` layout.
+    pub fn to_batch(schema: &SchemaRef, modalities: &[String], rows: &[Record]) -> anyhow::Result<RecordBatch> {
+        let strings = |f: fn(&Record) -> &str| -> ArrayRef {
+            Arc::new(StringArray::from(rows.iter().map(f).collect::<Vec<_>>()))
+        };
+        let mut columns: Vec<ArrayRef> = vec![Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.id)))];
+        for m in modalities {
+            let mut list = ListBuilder::new(Float32Builder::new());
+            for r in rows {
+                match r.vectors.get(m) {
+                    Some(v) => {
+                        list.values().append_slice(v);
+                        list.append(true);
+                    }
+                    None => list.append(false),
+                }
+            }
+            columns.push(Arc::new(list.finish()));
+        }
+        let attributes: Vec<String> = rows.iter()
+            .map(|r| serde_json::to_string(&r.metadata.attributes))
+            .collect::<Result<_, _>>()?;
+        let edges: Vec<String> = rows.iter()
+            .map(|r| serde_json::to_string(&r.metadata.edges))
+            .collect::<Result<_, _>>()?;
+        columns.extend([
+            Arc::new(Int64Array::from_iter_values(rows.iter().map(|r| r.metadata.timestamp))) as ArrayRef,
+            Arc::new(Float32Array::from_iter_values(rows.iter().map(|r| r.metadata.importance))),
+            Arc::new(UInt8Array::from_iter_values(rows.iter().map(|r| r.metadata.context_type.code()))),
+            strings(|r| &r.metadata.source),
+            strings(|r| &r.metadata.content),
+            strings(|r| &r.metadata.tags_json),
+            Arc::new(UInt32Array::from_iter_values(rows.iter().map(|r| r.metadata.recall_count))),
+            Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.metadata.last_recalled_at))),
+            strings(|r| &r.metadata.namespace_id),
+            strings(|r| &r.metadata.entity_id),
+            Arc::new(StringArray::from(attributes)),
+            Arc::new(StringArray::from(edges)),
+            Arc::new(Int64Array::from_iter_values(rows.iter().map(|r| r.metadata.ttl))),
+            Arc::new(Float32Array::from_iter_values(rows.iter().map(|r| r.metadata.confidence))),
+        ]);
+        Ok(RecordBatch::try_new(schema.clone(), columns)?)
+    }
+
+    // Buffers rows and hands full batches to a format-specific sink.
+    struct Batcher {
+        schema: SchemaRef,
+        modalities: Vec<String>,
+        rows: Vec<Record>,
+    }
+
+    impl Batcher {
+        fn new(modalities: Vec<String>) -> Self {
+            Batcher { schema: schema(&modalities), modalities, rows: Vec::new() }
+        }
+
+        fn push(&mut self, record: &Record) -> anyhow::Result<Option<RecordBatch>> {
+            self.rows.push(record.clone());
+            if self.rows.len() < BATCH_ROWS { return Ok(None); }
+            self.take().map(Some)
+        }
+
+        fn take(&mut self) -> anyhow::Result<RecordBatch> {
+            let batch = to_batch(&self.schema, &self.modalities, &self.rows)?;
+            self.rows.clear();
+            Ok(batch)
+        }
+    }
+
+    /// Arrow IPC file writer.
+    pub struct ArrowWriter<W: Write> {
+        batcher: Batcher,
+        out: arrow::ipc::writer::FileWriter<W>,
+    }
+
+    impl<W: Write> ArrowWriter<W> {
+        pub fn new(out: W, modalities: Vec<String>) -> anyhow::Result<Self> {
+            let batcher = Batcher::new(modalities);
+            let out = arrow::ipc::writer::FileWriter::try_new(out, &batcher.schema)?;
+            Ok(ArrowWriter { batcher, out })
+        }
+    }
+
+    impl<W: Write> RecordWriter for ArrowWriter<W> {
+        fn write(&mut self, record: &Record) -> anyhow::Result<()> {
+            if let Some(batch) = self.batcher.push(record)? { self.out.write(&batch)?; }
+            Ok(())
+        }
+
+        fn finish(&mut self) -> anyhow::Result<()> {
+            if !self.batcher.rows.is_empty() {
+                let batch = self.batcher.take()?;
+                self.out.write(&batch)?;
+            }
+            self.out.finish()?;
+            Ok(())
+        }
+    }
+
+    /// Parquet file writer (snappy-compressed).
+    #[cfg(feature = "parquet")]
+    pub struct ParquetWriter<W: Write + Send> {
+        batcher: Batcher,
+        out: Option<parquet::arrow::ArrowWriter<W>>,
+    }
+
+    #[cfg(feature = "parquet")]
+    impl<W: Write + Send> ParquetWriter<W> {
+        pub fn new(out: W, modalities: Vec<String>) -> anyhow::Result<Self> {
+            let batcher = Batcher::new(modalities);
+            let props = parquet::file::properties::WriterProperties::builder()
+                .set_compression(parquet::basic::Compression::SNAPPY)
+                .build();
+            let out = parquet::arrow::ArrowWriter::try_new(out, batcher.schema.clone(), Some(props))?;
+            Ok(ParquetWriter { batcher, out: Some(out) })
+        }
+    }
+
+    #[cfg(feature = "parquet")]
+    impl<W: Write + Send> RecordWriter for ParquetWriter<W> {
+        fn write(&mut self, record: &Record) -> anyhow::Result<()> {
+            if let Some(batch) = self.batcher.push(record)? {
+                self.out.as_mut().ok_or_else(|| anyhow::anyhow!("writer already finished"))?.write(&batch)?;
+            }
+            Ok(())
+        }
+
+        fn finish(&mut self) -> anyhow::Result<()> {
+            let mut out = self.out.take().ok_or_else(|| anyhow::anyhow!("writer already finished"))?;
+            if !self.batcher.rows.is_empty() {
+                out.write(&self.batcher.take()?)?;
+            }
+            out.close()?;
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! A feed of the changes made to a store (`DB::subscribe`).
+//!
+//! A cache, a UI or a secondary indexer that mirrors the store would
+//! otherwise have to poll it. A subscription is a channel on which every
+//! add, update, delete, link and unlink made through the file's handles
+//! arrives as an `audit::Entry`, as the audit log records it but whether or
+//! not auditing is on. The receiver is `Send`, so it can be drained on
+//! another thread while this one writes:
+//!
+//! ```ignore
+//! let changes = db.subscribe();
+//! std::thread::spawn(move || for change in changes { invalidate(change.id) });
+//! ```
+//!
+//! A subscription on a collection handle sees that collection's changes
+//! only. A transaction's changes arrive when it commits, and none of a
+//! rolled-back one. Changes made by other processes, and the core's own
+//! expiry, are not seen. Dropping the receiver ends the subscription.
+
+use crate::audit::Entry;
+use crate::DB;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+// The open subscriptions of a file's handles.
+#[derive(Default)]
+pub(crate) struct Subscribers {
+    // each with the collection it keeps to; None for the default one
+    senders: Vec<(Option<String>, Sender<Entry>)>,
+}
+
+impl Subscribers {
+    pub(crate) fn is_empty(&self) -> bool { self.senders.is_empty() }
+
+    // Hand `entry` to the subscribers of its collection, dropping those
+    // whose receiver is gone.
+    pub(crate) fn send(&mut self, entry: &Entry) {
+        self.senders.retain(|(collection, sender)| {
+            *collection != entry.collection || sender.send(entry.clone()).is_ok()
+        });
+    }
+}
+
+impl DB {
+    /// A channel of the changes made to this collection from now on (see
+    /// the module docs).
+    pub fn subscribe(&self) -> Receiver<Entry> {
+        let (sender, receiver) = mpsc::channel();
+        self.handle.subscribers.borrow_mut().senders.push((self.collection_name().map(str::to_string), sender));
+        receiver
+    }
+}
@@ -0,0 +1,466 @@
+//! Metadata filter expressions, e.g.
+//! `context_type in (1, 2) and source != 'slack' and importance > 0.5`.
+//!
+//! Grammar (keywords are case-insensitive):
+//!
+//! ```text
+//! expr       := and ("or" and)*
+//! and        := unary ("and" unary)*
+//! unary      := "not" unary | "(" expr ")" | comparison
+//! comparison := field op value
+//!             | field ["not"] "in" "(" value ("," value)* ")"
+//!             | field "contains" string
+//! op         := "=" | "==" | "!=" | "<" | "<=" | ">" | ">="
+//! ```
+//!
+//! Fields are the metadata columns (`timestamp`, `importance`,
+//! `context_type` or `type`, `source`, `content`, `tags`, `recall_count`,
+//! `last_recalled_at`, `namespace_id`, `entity_id`, `session_id` or
+//! `session`, `archived_at` (0 unless archived), `ttl`, `confidence`), `attr.<key>` for attributes and
+//! `meta.<path>` for a (dotted) path into the record's JSON object. Strings are quoted with `'` or `"`; `true` and
+//! `false` are booleans. A missing attribute reads as the empty string;
+//! comparing an attribute with a number only matches if the attribute parses
+//! as one. A missing JSON path matches no comparison, and `contains` on a
+//! JSON array tests its elements.
+
+use crate::index::Prefilter;
+use crate::metadata::SESSION_ATTRIBUTE;
+use crate::Metadata;
+use std::cmp::Ordering;
+
+/// A parsed filter expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Compare(Field, Op, Value),
+    In(Field, Vec<Value>),
+    Contains(Field, String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Field {
+    Timestamp,
+    Importance,
+    ContextType,
+    Source,
+    Content,
+    Tags,
+    RecallCount,
+    LastRecalledAt,
+    NamespaceId,
+    EntityId,
+    Session,
+    ArchivedAt,
+    Ttl,
+    Confidence,
+    Attribute(String),
+    Json(Vec<String>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+}
+
+impl Filter {
+    pub fn parse(input: &str) -> anyhow::Result<Filter> {
+        let mut parser = Parser { tokens: tokenize(input)?, pos: 0 };
+        let filter = parser.expr()?;
+        match parser.peek() {
+            None => Ok(filter),
+            Some(token) => anyhow::bail!("unexpected {} in filter", token),
+        }
+    }
+
+    pub fn matches(&self, meta: &Metadata) -> bool {
+        match self {
+            Filter::And(a, b) => a.matches(meta) && b.matches(meta),
+            Filter::Or(a, b) => a.matches(meta) || b.matches(meta),
+            Filter::Not(f) => !f.matches(meta),
+            Filter::Compare(field, op, value) => compare(field, meta, value).is_some_and(|o| op.holds(o)),
+            Filter::In(field, values) => values.iter().any(|v| compare(field, meta, v) == Some(Ordering::Equal)),
+            Filter::Contains(Field::Json(path), needle) => match json_at(meta, path) {
+                Some(serde_json::Value::Array(items)) => items.iter().any(|v| v.as_str() == Some(needle)),
+                Some(serde_json::Value::String(s)) => s.contains(needle.as_str()),
+                _ => false,
+            },
+            Filter::Contains(field, needle) => match field.get(meta) {
+                Some(Value::Text(s)) => s.contains(needle.as_str()),
+                _ => false,
+            },
+        }
+    }
+
+    // Tighten `prefilter` with what every match must satisfy: the
+    // `source = '…'`, `session = '…'`, `attr.<key> = '…'` and timestamp
+    // comparisons joined by top-level `and`s. An empty attribute value also
+    // matches records without the attribute, which no index lists.
+    pub(crate) fn narrow(&self, prefilter: &mut Prefilter) {
+        match self {
+            Filter::And(a, b) => {
+                a.narrow(prefilter);
+                b.narrow(prefilter);
+            }
+            Filter::Compare(Field::Source, Op::Eq, Value::Text(source)) => {
+                prefilter.source.get_or_insert_with(|| source.clone());
+            }
+            Filter::Compare(Field::Session, Op::Eq, Value::Text(session)) if !session.is_empty() => {
+                prefilter.attributes.entry(SESSION_ATTRIBUTE.to_string()).or_insert_with(|| session.clone());
+            }
+            Filter::Compare(Field::Attribute(key), Op::Eq, Value::Text(value)) if !value.is_empty() => {
+                prefilter.attributes.entry(key.clone()).or_insert_with(|| value.clone());
+            }
+            Filter::Compare(Field::Timestamp, op, Value::Number(n)) if *op != Op::Ne => {
+                let (mut after, mut before) = prefilter.time_range.unwrap_or((i64::MIN, i64::MAX));
+                if matches!(op, Op::Eq | Op::Ge) { after = after.max(n.ceil() as i64); }
+                if matches!(op, Op::Gt) { after = after.max((n.floor() as i64).saturating_add(1)); }
+                if matches!(op, Op::Eq | Op::Le) { before = before.min(n.floor() as i64); }
+                if matches!(op, Op::Lt) { before = before.min((n.ceil() as i64).saturating_sub(1)); }
+                prefilter.time_range = Some((after, before));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl std::str::FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> { Filter::parse(s) }
+}
+
+impl Field {
+    fn parse(name: &str) -> anyhow::Result<Field> {
+        if let Some(key) = name.strip_prefix("attr.").or_else(|| name.strip_prefix("attributes.")) {
+            anyhow::ensure!(!key.is_empty(), "attribute name missing in {:?}", name);
+            return Ok(Field::Attribute(key.to_string()));
+        }
+        if let Some(path) = name.strip_prefix("meta.") {
+            let path: Vec<String> = path.split('.').map(str::to_string).collect();
+            anyhow::ensure!(path.iter().all(|p| !p.is_empty()), "invalid JSON path in {:?}", name);
+            return Ok(Field::Json(path));
+        }
+        Ok(match name.to_ascii_lowercase().as_str() {
+            "timestamp" => Field::Timestamp,
+            "importance" => Field::Importance,
+            "context_type" | "type" => Field::ContextType,
+            "source" => Field::Source,
+            "content" => Field::Content,
+            "tags" | "tags_json" => Field::Tags,
+            "recall_count" => Field::RecallCount,
+            "last_recalled_at" => Field::LastRecalledAt,
+            "namespace_id" | "namespace" => Field::NamespaceId,
+            "entity_id" | "entity" => Field::EntityId,
+            "session_id" | "session" => Field::Session,
+            "archived_at" => Field::ArchivedAt,
+            "ttl" => Field::Ttl,
+            "confidence" => Field::Confidence,
+            _ => anyhow::bail!("unknown filter field {:?} (use a metadata field, attr.<key> or meta.<path>)", name),
+        })
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Field::Timestamp => "timestamp".into(),
+            Field::Importance => "importance".into(),
+            Field::ContextType => "context_type".into(),
+            Field::Source => "source".into(),
+            Field::Content => "content".into(),
+            Field::Tags => "tags".into(),
+            Field::RecallCount => "recall_count".into(),
+            Field::LastRecalledAt => "last_recalled_at".into(),
+            Field::NamespaceId => "namespace_id".into(),
+            Field::EntityId => "entity_id".into(),
+            Field::Session => "session_id".into(),
+            Field::ArchivedAt => "archived_at".into(),
+            Field::Ttl => "ttl".into(),
+            Field::Confidence => "confidence".into(),
+            Field::Attribute(key) => format!("attr.{}", key),
+            Field::Json(path) => format!("meta.{}", path.join(".")),
+        }
+    }
+
+    fn is_text(&self) -> bool {
+        matches!(self, Field::Source | Field::Content | Field::Tags | Field::NamespaceId | Field::EntityId | Field::Session)
+    }
+
+    fn get(&self, meta: &Metadata) -> Option<Value> {
+        Some(match self {
+            Field::Timestamp => Value::Number(meta.timestamp as f64),
+            Field::Importance => Value::Number(meta.importance as f64),
+            Field::ContextType => Value::Number(meta.context_type.code() as f64),
+            Field::Source => Value::Text(meta.source.clone()),
+            Field::Content => Value::Text(meta.content.clone()),
+            Field::Tags => Value::Text(meta.tags_json.clone()),
+            Field::RecallCount => Value::Number(meta.recall_count as f64),
+            Field::LastRecalledAt => Value::Number(meta.last_recalled_at as f64),
+            Field::NamespaceId => Value::Text(meta.namespace_id.clone()),
+            Field::EntityId => Value::Text(meta.entity_id.clone()),
+            Field::Session => Value::Text(meta.session().unwrap_or_default().to_string()),
+            Field::ArchivedAt => Value::Number(meta.archived_at().unwrap_or(0) as f64),
+            Field::Ttl => Value::Number(meta.ttl as f64),
+            Field::Confidence => Value::Number(meta.confidence as f64),
+            Field::Attribute(key) => Value::Text(meta.attributes.get(key).cloned().unwrap_or_default()),
+            Field::Json(path) => match json_at(meta, path)? {
+                serde_json::Value::Number(n) => Value::Number(n.as_f64()?),
+                serde_json::Value::String(s) => Value::Text(s),
+                serde_json::Value::Bool(b) => Value::Bool(b),
+                _ => return None,
+            },
+        })
+    }
+
+    // Reject comparisons that can never match, such as `source > 5`.
+    fn check(&self, value: &Value) -> anyhow::Result<()> {
+        match (self, value) {
+            (Field::Attribute(_) | Field::Json(_), _) => Ok(()),
+            (f, Value::Bool(_)) => anyhow::bail!("{} is not a boolean field", f.name()),
+            (f, Value::Number(_)) if f.is_text() => anyhow::bail!("{} is a text field; quote the value", f.name()),
+            (f, Value::Text(_)) if !f.is_text() => anyhow::bail!("{} is a numeric field", f.name()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Op {
+    fn holds(self, order: Ordering) -> bool {
+        match self {
+            Op::Eq => order.is_eq(),
+            Op::Ne => order.is_ne(),
+            Op::Lt => order.is_lt(),
+            Op::Le => order.is_le(),
+            Op::Gt => order.is_gt(),
+            Op::Ge => order.is_ge(),
+        }
+    }
+}
+
+// `field` of `meta` against `value`; None if they cannot be compared.
+fn compare(field: &Field, meta: &Metadata, value: &Value) -> Option<Ordering> {
+    match (field.get(meta)?, value) {
+        // f32 columns compare at f32 precision, so `importance >= 0.7` holds for 0.7
+        (Value::Number(a), Value::Number(b)) if matches!(field, Field::Importance | Field::Confidence) => {
+            a.partial_cmp(&(*b as f32 as f64))
+        }
+        (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+        (Value::Text(a), Value::Text(b)) => Some(a.as_str().cmp(b)),
+        (Value::Text(a), Value::Number(b)) => a.trim().parse::<f64>().ok()?.partial_cmp(b),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (Value::Text(a), Value::Bool(b)) => a.parse::<bool>().ok().map(|a| a.cmp(b)),
+        _ => None,
+    }
+}
+
+// The value at `path` in `meta`'s JSON object.
+fn json_at(meta: &Metadata, path: &[String]) -> Option<serde_json::Value> {
+    let mut value = serde_json::Value::Object(meta.json()?);
+    for key in path {
+        value = match value {
+            serde_json::Value::Object(mut map) => map.remove(key)?,
+            serde_json::Value::Array(mut items) => {
+                let i: usize = key.parse().ok()?;
+                (i < items.len()).then(|| items.swap_remove(i))?
+            }
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Text(String),
+    Number(f64),
+    Op(Op),
+    Open,
+    Close,
+    Comma,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Word(w) => write!(f, "{:?}", w),
+            Token::Text(s) => write!(f, "'{}'", s),
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Op(op) => write!(f, "'{}'", match op {
+                Op::Eq => "=", Op::Ne => "!=", Op::Lt => "<", Op::Le => "<=", Op::Gt => ">", Op::Ge => ">=",
+            }),
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+            Token::Comma => write!(f, "','"),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => { chars.next(); }
+            '(' => { chars.next(); tokens.push(Token::Open); }
+            ')' => { chars.next(); tokens.push(Token::Close); }
+            ',' => { chars.next(); tokens.push(Token::Comma); }
+            '\'' | '"' => {
+                chars.next();
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some(ch) => s.push(ch),
+                        None => anyhow::bail!("unterminated string in filter"),
+                    }
+                }
+                tokens.push(Token::Text(s));
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let eq = chars.next_if_eq(&'=').is_some();
+                tokens.push(Token::Op(match (c, eq) {
+                    ('=', _) => Op::Eq,
+                    ('!', true) => Op::Ne,
+                    ('<', false) => Op::Lt,
+                    ('<', true) => Op::Le,
+                    ('>', false) => Op::Gt,
+                    ('>', true) => Op::Ge,
+                    _ => anyhow::bail!("expected != in filter"),
+                }));
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let mut s = String::new();
+                while let Some(ch) = chars.next_if(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '+' | '.')) {
+                    s.push(ch);
+                }
+                let n = s.parse().map_err(|_| anyhow::anyhow!("invalid number {:?} in filter", s))?;
+                tokens.push(Token::Number(n));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut s = String::new();
+                while let Some(ch) = chars.next_if(|ch| ch.is_alphanumeric() || matches!(ch, '_' | '.' | '-')) {
+                    s.push(ch);
+                }
+                tokens.push(Token::Word(s));
+            }
+            _ => anyhow::bail!("unexpected {:?} in filter", c),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> { self.tokens.get(self.pos) }
+
+    fn next(&mut self) -> anyhow::Result<Token> {
+        let token = self.tokens.get(self.pos).cloned()
+            .ok_or_else(|| anyhow::anyhow!("filter ends unexpectedly"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    // Consume the keyword `kw` if it comes next.
+    fn keyword(&mut self, kw: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(kw));
+        if found { self.pos += 1; }
+        found
+    }
+
+    fn expect(&mut self, want: Token) -> anyhow::Result<()> {
+        let token = self.next()?;
+        anyhow::ensure!(token == want, "expected {} in filter, found {}", want, token);
+        Ok(())
+    }
+
+    fn expr(&mut self) -> anyhow::Result<Filter> {
+        let mut filter = self.and()?;
+        while self.keyword("or") {
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> anyhow::Result<Filter> {
+        let mut filter = self.unary()?;
+        while self.keyword("and") {
+            filter = Filter::And(Box::new(filter), Box::new(self.unary()?));
+        }
+        Ok(filter)
+    }
+
+    fn unary(&mut self) -> anyhow::Result<Filter> {
+        if self.keyword("not") {
+            return Ok(Filter::Not(Box::new(self.unary()?)));
+        }
+        if self.peek() == Some(&Token::Open) {
+            self.pos += 1;
+            let filter = self.expr()?;
+            self.expect(Token::Close)?;
+            return Ok(filter);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> anyhow::Result<Filter> {
+        let field = match self.next()? {
+            Token::Word(name) => Field::parse(&name)?,
+            token => anyhow::bail!("expected a field name in filter, found {}", token),
+        };
+        let negated = self.keyword("not");
+        if self.keyword("in") {
+            self.expect(Token::Open)?;
+            let mut values = vec![self.value(&field)?];
+            while self.peek() == Some(&Token::Comma) {
+                self.pos += 1;
+                values.push(self.value(&field)?);
+            }
+            self.expect(Token::Close)?;
+            let filter = Filter::In(field, values);
+            return Ok(if negated { Filter::Not(Box::new(filter)) } else { filter });
+        }
+        anyhow::ensure!(!negated, "expected `in` after `not` in filter");
+        if self.keyword("contains") {
+            return match self.next()? {
+                Token::Text(s) => Ok(Filter::Contains(field, s)),
+                token => anyhow::bail!("`contains` takes a quoted string, found {}", token),
+            };
+        }
+        let op = match self.next()? {
+            Token::Op(op) => op,
+            token => anyhow::bail!("expected a comparison after {}, found {}", field.name(), token),
+        };
+        let value = self.value(&field)?;
+        Ok(Filter::Compare(field, op, value))
+    }
+
+    fn value(&mut self, field: &Field) -> anyhow::Result<Value> {
+        let value = match self.next()? {
+            Token::Number(n) => Value::Number(n),
+            Token::Text(s) => Value::Text(s),
+            Token::Word(w) if w.eq_ignore_ascii_case("true") => Value::Bool(true),
+            Token::Word(w) if w.eq_ignore_ascii_case("false") => Value::Bool(false),
+            token => anyhow::bail!("expected a value in filter, found {}", token),
+        };
+        field.check(&value)?;
+        Ok(value)
+    }
+}
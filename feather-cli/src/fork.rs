@@ -0,0 +1,479 @@
+//! Copy-on-write forks: branch a store without copying it.
+//!
+//! `DB::fork` freezes the store's file as a snapshot next to the fork,
+//! `<fork>.base`, by hard-linking it. Nothing is copied: the core only ever
+//! saves by writing a new file and renaming it over the old one, so the
+//! original keeps evolving while the linked snapshot never changes. The fork
+//! itself is a new, small file that holds only its own writes. Reads go
+//! through both — the fork's records win, and base records the fork forgot are
+//! masked — and the snapshot is opened read-only, so it stays shared by every
+//! fork taken at that point. The snapshot's name and the tombstones of
+//! forgotten base records live in the fork's properties. Forks of forks chain.
+//!
+//! Keep `<fork>.base` alongside the fork file: the fork finds it by name in
+//! its own directory, so the two can be moved or copied together, and it
+//! refuses to open without it. To flatten a fork into a standalone store,
+//! export it and import the result into a fresh file.
+
+use crate::*;
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// Property key holding the base snapshot path and the tombstones.
+pub(crate) const PROPERTY_KEY: &str = "fork";
+
+// Wrapper state a new fork starts with, copied from its source.
+const INHERITED: [&str; 5] = [
+    projection::PROPERTY_KEY, drift::PROPERTY_KEY, collection::PROPERTY_KEY, decay::PROPERTY_KEY,
+    model::PROPERTY_KEY,
+];
+
+extern "C" {
+    fn feather_path(db: *mut c_void, out: *mut c_char, cap: usize) -> usize;
+}
+
+/// The read-only snapshot under a fork.
+pub(crate) struct Base {
+    pub handle: Rc<Handle>,
+    // as recorded: relative to the fork's directory, or absolute in forks
+    // taken before that
+    path: String,
+    resolved: PathBuf,
+    // base records forgotten by the fork after it dropped their metadata
+    tombstones: RefCell<HashSet<u64>>,
+    pub dirty: Cell<bool>,
+}
+
+impl Base {
+    // The base recorded in `raw`, of the fork whose file is `fork`.
+    pub fn open(raw: &[u8], dim: usize, fork: Option<&Path>) -> anyhow::Result<Base> {
+        let (path, tombstones) = decode(raw).ok_or_else(|| anyhow::anyhow!("the '{}' property does not decode", PROPERTY_KEY))?;
+        let resolved = resolve(fork.unwrap_or(Path::new("")), &path);
+        anyhow::ensure!(resolved.is_file(), "the fork's base snapshot {:?} is missing; keep it beside the fork file", resolved);
+        let c_path = c_str(resolved.to_str().ok_or_else(|| anyhow::anyhow!("path is not UTF-8: {:?}", resolved))?)?;
+        let ptr = unsafe { feather_open(c_path.as_ptr(), dim) };
+        anyhow::ensure!(!ptr.is_null(), "cannot open the fork's base snapshot {:?}", resolved);
+        unsafe { feather_detach(ptr) };
+        let handle = Handle::new(ptr).map_err(|e| anyhow::anyhow!("the fork's base snapshot {:?}: {:#}", resolved, e))?;
+        Ok(Base {
+            handle: Rc::new(handle),
+            path,
+            resolved,
+            tombstones: RefCell::new(tombstones),
+            dirty: Cell::new(false),
+        })
+    }
+
+    // Search beam width, as set on the fork; the base may lack the modality.
+    pub fn set_ef(&self, ef: usize, modality: Option<&str>) {
+        let _ = self.handle.set_ef(ef, modality);
+    }
+
+    pub fn set_seed(&self, seed: u64) -> anyhow::Result<()> {
+        self.handle.set_seed(seed)
+    }
+
+    pub fn warm(&self) -> anyhow::Result<u64> {
+        self.handle.warm()
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        encode(&self.path, &self.tombstones.borrow())
+    }
+}
+
+/// Where the base recorded as `base` is, for the fork at `fork`.
+pub(crate) fn resolve(fork: &Path, base: &str) -> PathBuf {
+    let base = Path::new(base);
+    match fork.parent() {
+        Some(dir) if base.is_relative() => dir.join(base),
+        _ => base.to_path_buf(),
+    }
+}
+
+fn encode(path: &str, tombstones: &HashSet<u64>) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend((path.len() as u32).to_le_bytes());
+    out.extend(path.as_bytes());
+    out.extend((tombstones.len() as u64).to_le_bytes());
+    for id in tombstones {
+        out.extend(id.to_le_bytes());
+    }
+    out
+}
+
+pub(crate) fn decode(bytes: &[u8]) -> Option<(String, HashSet<u64>)> {
+    let len = u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?) as usize;
+    let path = String::from_utf8(bytes.get(4..4 + len)?.to_vec()).ok()?;
+    let rest = bytes.get(4 + len..)?;
+    let count = u64::from_le_bytes(rest.get(..8)?.try_into().ok()?) as usize;
+    let ids = rest.get(8..)?;
+    if ids.len() != count.checked_mul(8)? { return None; }
+    let tombstones = ids.chunks_exact(8).map(|c| u64::from_le_bytes(c.try_into().unwrap())).collect();
+    Some((path, tombstones))
+}
+
+// Overlay-aware versions of the `Handle` primitives: on an ordinary store
+// they are the primitives; on a fork they merge in the base.
+impl Handle {
+    // Whether the fork hides the base's record `id`, entirely or (with a
+    // modality) because the fork holds its own vector there.
+    fn masks(&self, base: &Base, id: u64, modality: Option<&str>) -> bool {
+        base.tombstones.borrow().contains(&id)
+            || self.own_meta(id).is_some_and(|m| m.is_forgotten())
+            || modality.is_some_and(|m| self.own_vector(id, Some(m)).is_some())
+    }
+
+    // Whether the fork archived base record `id` (see `archive`).
+    fn archived_here(&self, id: u64) -> bool {
+        self.own_meta(id).is_some_and(|m| m.is_archived())
+    }
+
+    pub(crate) fn all_ids(&self) -> Vec<u64> {
+        let mut ids = self.own_all_ids();
+        if let Some(base) = &self.fork {
+            let own: HashSet<u64> = ids.iter().copied().collect();
+            let tombstones = base.tombstones.borrow();
+            ids.extend(base.handle.all_ids().into_iter()
+                .filter(|id| !own.contains(id) && !tombstones.contains(id)));
+        }
+        ids
+    }
+
+    // Candidates only: a base record the fork has since rewritten is listed
+    // under its old attribute value.
+    pub(crate) fn ids_with_attribute(&self, key: &str, value: &str) -> Vec<u64> {
+        let mut ids = self.own_ids_with_attribute(key, value);
+        if let Some(base) = &self.fork {
+            let own: HashSet<u64> = ids.iter().copied().collect();
+            ids.extend(base.handle.ids_with_attribute(key, value).into_iter().filter(|id| !own.contains(id)));
+        }
+        ids
+    }
+
+    pub(crate) fn modalities(&self) -> Vec<String> {
+        let mut names = self.own_modalities();
+        if let Some(base) = &self.fork {
+            for name in base.handle.modalities() {
+                if !names.contains(&name) { names.push(name); }
+            }
+        }
+        names
+    }
+
+    pub(crate) fn meta(&self, id: u64) -> Option<Metadata> {
+        match &self.fork {
+            Some(base) => self.own_meta(id).or_else(|| {
+                (!base.tombstones.borrow().contains(&id)).then(|| base.handle.meta(id)).flatten()
+            }),
+            None => self.own_meta(id),
+        }
+    }
+
+    // Records with an edge to `id`; a base record the fork has its own copy
+    // of counts by the fork's edges.
+    pub(crate) fn incoming(&self, id: u64) -> Vec<u64> {
+        let mut ids = self.own_incoming(id);
+        if let Some(base) = &self.fork {
+            let own: HashSet<u64> = ids.iter().copied().collect();
+            ids.extend(base.handle.incoming(id).into_iter()
+                .filter(|&src| !own.contains(&src) && !self.masks(base, src, None) && self.own_meta(src).is_none()));
+        }
+        ids
+    }
+
+    pub(crate) fn vector(&self, id: u64, modality: Option<&str>) -> Option<Vec<f32>> {
+        let own = self.own_vector(id, modality);
+        match &self.fork {
+            Some(base) if own.is_none() && !self.masks(base, id, None) => base.handle.vector(id, modality),
+            _ => own,
+        }
+    }
+
+    pub(crate) fn dim(&self, modality: Option<&str>) -> usize {
+        match &self.fork {
+            Some(base) if !self.own_modalities().iter().any(|m| m == modality.unwrap_or("text")) => {
+                base.handle.dim(modality)
+            }
+            _ => self.own_dim(modality),
+        }
+    }
+
+    pub(crate) fn ids(&self, modality: Option<&str>) -> Vec<u64> {
+        let mut ids = self.own_ids(modality);
+        if let Some(base) = &self.fork {
+            let m = modality.unwrap_or("text");
+            let own: HashSet<u64> = ids.iter().copied().collect();
+            ids.extend(base.handle.ids(modality).into_iter()
+                .filter(|&id| !own.contains(&id) && !self.masks(base, id, Some(m))));
+        }
+        ids
+    }
+
+    // The nearest `k` hits of `knn` (called with growing fetch sizes) that
+    // `keep` accepts.
+    fn knn_where(k: usize, knn: impl Fn(usize) -> anyhow::Result<Vec<(u64, f32)>>,
+                 keep: impl Fn(u64) -> bool) -> Vec<(u64, f32)> {
+        let mut fetch = k;
+        loop {
+            let Ok(hits) = knn(fetch) else { return Vec::new() };
+            let exhausted = hits.len() < fetch;
+            let mut kept: Vec<(u64, f32)> = hits.into_iter().filter(|&(id, _)| keep(id)).collect();
+            if kept.len() >= k || exhausted {
+                kept.truncate(k);
+                return kept;
+            }
+            fetch = fetch.saturating_mul(2);
+        }
+    }
+
+    // The base's nearest records in `modality` that the fork does not mask
+    // and `keep` accepts.
+    fn base_knn(&self, base: &Base, query: &[f32], k: usize, modality: &str,
+                prefilter: &Prefilter, keep: impl Fn(u64) -> bool) -> Vec<(u64, f32)> {
+        Self::knn_where(k, |fetch| base.handle.knn(query, fetch, modality, prefilter),
+                        |id| !self.masks(base, id, Some(modality)) && keep(id))
+    }
+
+    pub(crate) fn search(&self, query: &[f32], k: usize, modality: Option<&str>,
+                         type_filter: Option<u8>, source_filter: Option<&str>) -> Vec<(u64, f32)> {
+        if self.fork.is_none() && !self.is_sharded() {
+            return self.own_search(query, k, modality, type_filter, source_filter);
+        }
+        // Both sides (or every shard) are searched unscored and merged,
+        // scoring like the core does; only the hits that make the cut count
+        // as recalled.
+        let modality = modality.unwrap_or("text");
+        let type_filter = type_filter.filter(|&t| t != context_type::ANY_CODE);
+        let source_filter = source_filter.filter(|s| !s.is_empty());
+        let keep = |id| match self.meta(id) {
+            Some(m) if m.is_archived() => false,
+            Some(m) => type_filter.is_none_or(|t| m.context_type.code() == t)
+                && source_filter.is_none_or(|s| m.source == s),
+            None => false,
+        };
+        let unfiltered = Prefilter::default();
+        let mut hits = Self::knn_where(k, |fetch| self.own_knn(query, fetch, modality, &unfiltered), keep);
+        if let Some(base) = &self.fork {
+            hits.extend(self.base_knn(base, query, k, modality, &unfiltered, keep));
+            hits.sort_by(|a, b| a.1.total_cmp(&b.1));
+            hits.truncate(k);
+        }
+        // recalls of base records are counted in the fork, like any write
+        for &(id, _) in &hits {
+            self.copy_up(id);
+            unsafe { feather_touch(self.core(id), id) };
+        }
+        let mut out: Vec<(u64, f32)> = hits.into_iter().map(|(id, dist)| (id, 1.0 / (1.0 + dist))).collect();
+        out.resize(k, (0, 0.0));
+        out
+    }
+
+    pub(crate) fn knn(&self, query: &[f32], k: usize, modality: &str,
+                      prefilter: &Prefilter) -> anyhow::Result<Vec<(u64, f32)>> {
+        let mut hits = self.own_knn(query, k, modality, prefilter)?;
+        if let Some(base) = &self.fork {
+            // the base does not know which of its records the fork archived
+            hits.extend(self.base_knn(base, query, k, modality, prefilter,
+                                      |id| prefilter.include_archived || !self.archived_here(id)));
+            hits.sort_by(|a, b| a.1.total_cmp(&b.1));
+            hits.truncate(k);
+        }
+        Ok(hits)
+    }
+
+    // BM25 hits of both sides; a base record the fork has its own copy of is
+    // ranked by the fork's content.
+    pub(crate) fn bm25(&self, text: &str, k: usize, include_archived: bool) -> anyhow::Result<Vec<(u64, f32)>> {
+        let mut hits = self.own_bm25(text, k, include_archived)?;
+        if let Some(base) = &self.fork {
+            hits.extend(Self::knn_where(k, |fetch| base.handle.bm25(text, fetch, include_archived),
+                                        |id| !self.masks(base, id, None) && self.own_meta(id).is_none()));
+            hits.sort_by(|a, b| b.1.total_cmp(&a.1));
+            hits.truncate(k);
+        }
+        Ok(hits)
+    }
+
+    pub(crate) fn sparse_names(&self) -> Vec<String> {
+        let mut names = self.own_sparse_names();
+        if let Some(base) = &self.fork {
+            for name in base.handle.sparse_names() {
+                if !names.contains(&name) { names.push(name); }
+            }
+        }
+        names
+    }
+
+    pub(crate) fn sparse(&self, id: u64, name: &str) -> Option<SparseVector> {
+        let own = self.own_sparse(id, name);
+        match &self.fork {
+            Some(base) if own.is_none() && !self.masks(base, id, None) => base.handle.sparse(id, name),
+            _ => own,
+        }
+    }
+
+    // Sparse hits of both sides; a base record the fork holds its own sparse
+    // vector for is ranked by that one.
+    pub(crate) fn sparse_knn(&self, query: &SparseVector, k: usize, name: &str,
+                             include_archived: bool) -> anyhow::Result<Vec<(u64, f32)>> {
+        let mut hits = self.own_sparse_knn(query, k, name, include_archived)?;
+        if let Some(base) = &self.fork {
+            hits.extend(Self::knn_where(k, |fetch| base.handle.sparse_knn(query, fetch, name, include_archived),
+                                        |id| !self.masks(base, id, None) && self.own_sparse(id, name).is_none()
+                                            && (include_archived || !self.archived_here(id))));
+            hits.sort_by(|a, b| b.1.total_cmp(&a.1));
+            hits.truncate(k);
+        }
+        Ok(hits)
+    }
+
+    // Give the fork its own copy of a base record's metadata before it is
+    // modified in place. No-op on an ordinary store.
+    pub(crate) fn copy_up(&self, id: u64) {
+        let Some(base) = &self.fork else { return };
+        if base.tombstones.borrow().contains(&id) || self.own_meta(id).is_some() { return; }
+        let Some(meta) = base.handle.meta(id) else { return };
+        if let Ok(c_meta) = CMetadata::new(&meta) {
+            unsafe { feather_put_metadata(self.ptr, id, c_meta.raw()) };
+        }
+    }
+
+    pub(crate) fn forget(&self, id: u64) {
+        self.copy_up(id);
+        // the core drops the edges pointing at `id` from the records it
+        // holds, so bring the base's linking records over first
+        let incoming = self.incoming(id);
+        for &src in &incoming {
+            self.copy_up(src);
+        }
+        let core = self.core(id);
+        unsafe { feather_forget(core, id) }
+        // ...and so do the other shards of a sharded store
+        for src in incoming.into_iter().filter(|&src| self.core(src) != core) {
+            unsafe { feather_unlink(self.core(src), src, id, std::ptr::null()) };
+        }
+        self.changed(audit::Op::Delete, id, None, None);
+    }
+
+    pub(crate) fn expire(&self) -> usize {
+        let mut expired: usize = self.cores().iter().map(|&core| unsafe { feather_forget_expired(core) }).sum();
+        if let Some(base) = &self.fork {
+            let now = decay::now();
+            for id in base.handle.all_ids() {
+                if base.tombstones.borrow().contains(&id) || self.own_meta(id).is_some() { continue; }
+                if base.handle.meta(id).is_some_and(|m| m.ttl > 0 && now > m.timestamp + m.ttl) {
+                    self.forget(id);
+                    expired += 1;
+                }
+            }
+        }
+        expired
+    }
+
+    pub(crate) fn compact(&self, progress: Option<&mut ProgressFn>) -> usize {
+        if let Some(base) = &self.fork {
+            // compaction drops forgotten metadata, which is what masks the
+            // base's copy; keep the mask as a tombstone
+            let mut tombstones = base.tombstones.borrow_mut();
+            for id in self.own_all_ids() {
+                if !tombstones.contains(&id)
+                    && self.own_meta(id).is_some_and(|m| m.is_forgotten())
+                    && base.handle.meta(id).is_some() {
+                    tombstones.insert(id);
+                    base.dirty.set(true);
+                }
+            }
+        }
+        let mut reporter = Reporter::new("compact", progress);
+        let (cb, ctx) = reporter.as_c();
+        self.cores().iter().map(|&core| unsafe { feather_compact(core, cb, ctx) }).sum()
+    }
+
+    // The backing file, if any.
+    pub(crate) fn path(&self) -> Option<String> {
+        let n = unsafe { feather_path(self.ptr, std::ptr::null_mut(), 0) };
+        if n == 0 { return None; }
+        let mut buf = vec![0u8; n];
+        unsafe { feather_path(self.ptr, buf.as_mut_ptr().cast(), n) };
+        String::from_utf8(buf).ok()
+    }
+}
+
+impl DB {
+    /// Branch this store into a new file at `path`, in about the time of a
+    /// `save()` whatever the store's size: the current state is frozen as a
+    /// shared read-only snapshot (`<path>.base`, a hard link) and the fork
+    /// only stores what changes from there. Writes to either side never show
+    /// in the other. Returns a handle on the fork, scoped like this one.
+    pub fn fork(&self, path: &Path) -> anyhow::Result<DB> {
+        self.writable()?;
+        self.unsharded("forked")?;
+        let source = self.handle.path()
+            .ok_or_else(|| anyhow::anyhow!("an in-memory store cannot be forked; persist_to() it first"))?;
+        anyhow::ensure!(!path.exists(), "{:?} already exists", path);
+        let path = std::path::absolute(path)?;
+        let fork_path = path.to_str().ok_or_else(|| anyhow::anyhow!("path is not UTF-8: {:?}", path))?;
+        let base_path = format!("{}.base", fork_path);
+        anyhow::ensure!(!Path::new(&base_path).exists(), "{:?} already exists", base_path);
+
+        self.save()?;
+        if std::fs::hard_link(&source, &base_path).is_err() {
+            // e.g. across filesystems: fall back to a real copy
+            std::fs::copy(&source, &base_path)
+                .map_err(|e| anyhow::anyhow!("cannot snapshot {:?}: {}", source, e))?;
+        }
+
+        let c_path = c_str(fork_path)?;
+        let lock = lock::FileLock::acquire(&path, lock::LockMode::Exclusive)?;
+        let ptr = unsafe { feather_open(c_path.as_ptr(), self.handle.dim(None)) };
+        anyhow::ensure!(!ptr.is_null(), "cannot create {:?}", path);
+        if self.handle.binary.get() {
+            hamming::set_binary(ptr)?;
+        }
+        let collections = self.handle.collections.borrow().keys()
+            .map(|name| format!("{}{}{}", decay::PROPERTY_KEY, collection::MODALITY_SEP, name))
+            .collect::<Vec<_>>();
+        for key in INHERITED.iter().copied().chain(collections.iter().map(String::as_str)) {
+            if let (Some(value), Ok(c_key)) = (self.handle.property(key), CString::new(key)) {
+                unsafe { feather_set_property(ptr, c_key.as_ptr(), value.as_ptr().cast(), value.len()) };
+            }
+        }
+        // recorded by name, so the pair can be moved together
+        let base_name = Path::new(&base_path).file_name().and_then(|n| n.to_str()).expect("a UTF-8 file name");
+        let fork = encode(base_name, &HashSet::new());
+        let c_key = c_str(PROPERTY_KEY)?;
+        unsafe {
+            feather_set_property(ptr, c_key.as_ptr(), fork.as_ptr().cast(), fork.len());
+            feather_save(ptr);
+        }
+        let db = DB::wrap(ptr).map_err(|e| anyhow::anyhow!("cannot open the fork {:?}: {:#}", path, e))?;
+        db.handle.lock.replace(Some(lock));
+        db.set_compression(self.compression())?;
+        Ok(DB { scope: self.scope.clone(), ..db })
+    }
+
+    /// The snapshot this store is a fork of, if it is one.
+    pub fn fork_base(&self) -> Option<PathBuf> {
+        self.handle.fork.as_ref().map(|b| b.resolved.clone())
+    }
+
+    // The snapshot under this fork as a read-only DB, scoped like this handle.
+    pub(crate) fn snapshot(&self) -> Option<DB> {
+        let base = self.handle.fork.as_ref()?;
+        Some(DB { ptr: base.handle.ptr, handle: Rc::clone(&base.handle), scope: self.scope.clone() })
+    }
+
+    // Ids this fork wrote or forgot since it was taken (including records
+    // only copied up to count a recall), sorted.
+    pub(crate) fn changed_ids(&self) -> Vec<u64> {
+        let mut ids: Vec<u64> = self.handle.own_all_ids();
+        if let Some(base) = &self.handle.fork {
+            ids.extend(base.tombstones.borrow().iter().copied());
+        }
+        let mut ids: Vec<u64> = ids.into_iter().filter_map(|id| self.xid(id)).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+}
@@ -0,0 +1,815 @@
+//! Checking a database file for damage (`feather fsck`).
+//!
+//! `check` reads the file and its WAL byte by byte, the way the core loads
+//! them, without loading the store. The format carries no checksums, so
+//! damage shows as a section that does not parse to the length it declares
+//! or as values no writer produces. It reports:
+//!
+//! - a header other than `FEAT`, a format newer than this build, wrapper
+//!   properties (collections, projections, fork) that do not decode, a
+//!   packed section that does not unpack;
+//! - HNSW graphs whose links point at nodes that do not exist;
+//! - vectors with no metadata record, ids stored twice in one modality, and
+//!   NaN or infinite values;
+//! - links to records that do not exist;
+//! - WAL entries replay would mishandle: a torn last entry, an unknown op,
+//!   a vector whose dimension is not its modality's.
+//!
+//! The WAL is applied to what the file holds before ids are cross-checked,
+//! as on load. A fork's links may point into its base snapshot, which is
+//! read too. The shards of a sharded store are checked one by one
+//! (`check_shards`), a link into another shard counting as sound.
+//!
+//! `repair` fixes what it can. It rewrites the WAL without its bad entries
+//! and broken graphs as plain vectors (the core rebuilds a graph on load),
+//! then opens the store, forgets the broken records, removes the dangling
+//! links and saves, which also drops vectors without a record. A damaged
+//! file is left alone, and so is a broken graph inside a packed body
+//! (`Compression::All`), which counts as damage.
+
+use crate::lock::{FileLock, LockMode};
+use crate::{collection, fork, hamming, projection, shard, Compression, OpenOptions};
+use std::collections::{BTreeMap, HashMap, HashSet};
+#[cfg(feature = "zstd")]
+use std::ffi::c_void;
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+const MAGIC: u32 = 0x4645_4154;
+
+/// Newest file format this build reads (and the one it writes for a packed
+/// store).
+pub const FORMAT_VERSION: u32 = 12;
+
+/// Oldest file format `check` reads through; an unpacked store is still
+/// written in it.
+pub const OLDEST_CHECKED: u32 = 11;
+
+// Bounds the core applies on load.
+const MAX_DIM: u32 = 1 << 20;
+const MAX_PROPERTY: u32 = 1 << 30;
+const MAX_SPARSE_NNZ: u32 = 1 << 24;
+const MAX_PACK_RATIO: u64 = 32768;
+
+#[cfg(feature = "zstd")]
+extern "C" {
+    fn ZSTD_decompress(dst: *mut c_void, dst_capacity: usize, src: *const c_void, src_size: usize) -> usize;
+    fn ZSTD_isError(code: usize) -> u32;
+}
+
+// WAL ops, as the core numbers them.
+const WAL_ADD: u8 = 0x01;
+const WAL_UPDATE: u8 = 0x02;
+const WAL_UIMP: u8 = 0x03;
+const WAL_LINK: u8 = 0x04;
+const WAL_FORGET: u8 = 0x05;
+const WAL_SPARSE: u8 = 0x06;
+const WAL_UNLINK: u8 = 0x07;
+const WAL_TXN: u8 = 0x08;
+const WAL_HEADER: usize = 13;
+
+/// Something `check` found wrong. `index` names a modality, or `sparse:NAME`
+/// for a set of sparse vectors.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Problem {
+    /// The file cannot be read past `offset`; nothing after it was checked.
+    Damaged { offset: usize, reason: String },
+    /// Written in an older format, which this check does not read; saving
+    /// rewrites it in the current one.
+    OldFormat { version: u32 },
+    /// A modality's HNSW graph is malformed.
+    BadGraph { index: String, reason: String },
+    /// A vector whose id has no record.
+    Orphan { index: String, id: u64 },
+    /// An id with more than one vector in an index.
+    Duplicate { index: String, id: u64 },
+    /// A vector holding NaN or infinity.
+    NonFinite { index: String, id: u64 },
+    /// A link to an id with no record.
+    DanglingLink { from: u64, to: u64, rel_type: String },
+    /// A WAL entry replay skips or cannot complete; `offset` is where it
+    /// starts in the WAL.
+    BadWalEntry { offset: usize, reason: String },
+}
+
+impl Problem {
+    /// Whether `repair` fixes it.
+    pub fn is_repairable(&self) -> bool {
+        !matches!(self, Problem::Damaged { .. })
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Damaged { offset, reason } => write!(f, "damaged at byte {}: {}", offset, reason),
+            Problem::OldFormat { version } => write!(f, "format v{} predates this check (v{} and later)", version, OLDEST_CHECKED),
+            Problem::BadGraph { index, reason } => write!(f, "index '{}': HNSW graph {}", index, reason),
+            Problem::Orphan { index, id } => write!(f, "index '{}': vector {} has no record", index, id),
+            Problem::Duplicate { index, id } => write!(f, "index '{}': id {} is stored more than once", index, id),
+            Problem::NonFinite { index, id } => write!(f, "index '{}': vector {} holds NaN or infinity", index, id),
+            Problem::DanglingLink { from, to, rel_type } => write!(f, "link {} -> {} ({}): no record {}", from, to, rel_type, to),
+            Problem::BadWalEntry { offset, reason } => write!(f, "WAL byte {}: {}", offset, reason),
+        }
+    }
+}
+
+/// What `check` read and found.
+#[derive(Clone, Debug, Default)]
+pub struct FsckReport {
+    pub version: u32,
+    /// What the file's sections are packed with.
+    pub compression: Compression,
+    /// Records in the file, WAL applied.
+    pub records: usize,
+    /// `(modality, dim, vectors)` for each dense index, WAL applied.
+    pub modalities: Vec<(String, usize, usize)>,
+    pub wal_entries: usize,
+    pub problems: Vec<Problem>,
+}
+
+impl FsckReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Check the file at `path` and its WAL (see the module docs).
+pub fn check(path: &Path) -> anyhow::Result<FsckReport> {
+    check_with(path, &HashSet::new())
+}
+
+// `check`, counting the records in `elsewhere` as existing.
+fn check_with(path: &Path, elsewhere: &HashSet<u64>) -> anyhow::Result<FsckReport> {
+    let scan = scan_with(path, elsewhere)?;
+    let modalities = scan.dims.iter()
+        .map(|(name, &dim)| (name.clone(), dim, scan.vectors.get(name).map_or(0, HashSet::len)))
+        .collect();
+    Ok(FsckReport {
+        version: scan.version,
+        compression: scan.compression,
+        records: scan.records.len(),
+        modalities,
+        wal_entries: scan.wal_entries,
+        problems: scan.problems,
+    })
+}
+
+/// Fix what `check` finds at `path`, except damage (see the module docs),
+/// and check again. Fails, changing nothing, if the file is damaged.
+pub fn repair(path: &Path) -> anyhow::Result<FsckReport> {
+    repair_with(path, &HashSet::new())
+}
+
+fn repair_with(path: &Path, elsewhere: &HashSet<u64>) -> anyhow::Result<FsckReport> {
+    let scan = scan_with(path, elsewhere)?;
+    if let Some(damage) = scan.problems.iter().find(|p| !p.is_repairable()) {
+        anyhow::bail!("{:?} is {}; --repair cannot fix that, restore it from a copy", path, damage);
+    }
+    if !scan.bad_wal.is_empty() {
+        let wal = wal_path(path);
+        let raw = std::fs::read(&wal)?;
+        let mut kept = Vec::with_capacity(raw.len());
+        let mut from = 0;
+        for range in &scan.bad_wal {
+            kept.extend_from_slice(&raw[from..range.start]);
+            from = range.end;
+        }
+        kept.extend_from_slice(&raw[from.min(raw.len())..]);
+        replace(&wal, &kept)?;
+    }
+    if !scan.bad_graphs.is_empty() {
+        let raw = std::fs::read(path)?;
+        let mut fixed = Vec::with_capacity(raw.len());
+        let mut from = 0;
+        for graph in &scan.bad_graphs {
+            fixed.extend_from_slice(&raw[from..graph.span.start]);
+            fixed.push(0);   // no persisted graph: vectors follow
+            fixed.extend((graph.elements.len() as u32).to_le_bytes());
+            for (id, data) in &graph.elements {
+                fixed.extend(id.to_le_bytes());
+                let bytes = &raw[data.clone()];
+                match graph.storage {
+                    Storage::Float => fixed.extend_from_slice(bytes),
+                    Storage::Int8(scale) => bytes.iter().for_each(|&q| fixed.extend((q as i8 as f32 * scale).to_le_bytes())),
+                    Storage::Bits => (0..graph.dim).for_each(|i| {
+                        let bit = bytes[i / 8] >> (i % 8) & 1;
+                        fixed.extend(f32::from(bit).to_le_bytes());
+                    }),
+                }
+            }
+            from = graph.span.end;
+        }
+        fixed.extend_from_slice(&raw[from..]);
+        replace(path, &fixed)?;
+    }
+
+    let db = OpenOptions::new().open(path)?;
+    for problem in &scan.problems {
+        match problem {
+            Problem::Duplicate { id, .. } | Problem::NonFinite { id, .. } => db.forget(*id)?,
+            Problem::DanglingLink { from, to, rel_type } => { db.unlink_type(*from, *to, rel_type)?; }
+            _ => {}
+        }
+    }
+    db.save()?;
+    drop(db);
+    check_with(path, elsewhere)
+}
+
+/// `check` each shard file of the sharded store in `dir`, in order.
+pub fn check_shards(dir: &Path) -> anyhow::Result<Vec<(PathBuf, FsckReport)>> {
+    let (files, ids) = shard_ids(dir)?;
+    files.into_iter().map(|file| Ok((file.clone(), check_with(&file, &ids)?))).collect()
+}
+
+/// `repair` each shard file of the sharded store in `dir` that has
+/// problems, holding the store's lock; returns every shard's report.
+pub fn repair_shards(dir: &Path) -> anyhow::Result<Vec<(PathBuf, FsckReport)>> {
+    let _lock = FileLock::acquire(dir, LockMode::Exclusive)?;
+    let (files, ids) = shard_ids(dir)?;
+    files.into_iter()
+        .map(|file| {
+            let report = check_with(&file, &ids)?;
+            let report = if report.is_ok() { report } else { repair_with(&file, &ids)? };
+            Ok((file, report))
+        })
+        .collect()
+}
+
+// The shard files in `dir`, and every record id they hold between them.
+fn shard_ids(dir: &Path) -> anyhow::Result<(Vec<PathBuf>, HashSet<u64>)> {
+    let files = shard::files(dir);
+    anyhow::ensure!(!files.is_empty(), "no sharded store in {:?}", dir);
+    let mut ids = HashSet::new();
+    for file in &files {
+        ids.extend(scan(file)?.records.into_keys());
+    }
+    Ok((files, ids))
+}
+
+fn wal_path(path: &Path) -> PathBuf {
+    let mut wal = path.as_os_str().to_owned();
+    wal.push(".wal");
+    PathBuf::from(wal)
+}
+
+// Write `bytes` over `path` by way of a temporary file and a rename.
+fn replace(path: &Path, bytes: &[u8]) -> anyhow::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".fsck");
+    std::fs::write(&tmp, bytes)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+// Everything `scan` learns: the report's contents, plus where the things
+// `repair` rewrites are.
+#[derive(Default)]
+struct Scan {
+    version: u32,
+    compression: Compression,
+    problems: Vec<Problem>,
+    // live record id -> its edges as (target, rel_type)
+    records: HashMap<u64, Vec<(u64, String)>>,
+    vectors: BTreeMap<String, HashSet<u64>>,
+    dims: BTreeMap<String, usize>,
+    // the store keeps its vectors as bits (see `hamming`)
+    binary: bool,
+    wal_entries: usize,
+    bad_wal: Vec<Range<usize>>,
+    bad_graphs: Vec<Graph>,
+}
+
+// How a persisted graph holds each vector.
+#[derive(Clone, Copy)]
+enum Storage {
+    Float,
+    // in-RAM int8 under one scale
+    Int8(f32),
+    // a bit per dimension, in 64-bit words
+    Bits,
+}
+
+// A persisted HNSW graph that needs rewriting as plain vectors.
+struct Graph {
+    // from its persisted-graph flag to its end
+    span: Range<usize>,
+    dim: usize,
+    storage: Storage,
+    // (id, byte range of the vector)
+    elements: Vec<(u64, Range<usize>)>,
+}
+
+fn scan(path: &Path) -> anyhow::Result<Scan> {
+    scan_with(path, &HashSet::new())
+}
+
+// `scan`, links to the records in `elsewhere` (held by other shards)
+// counting as sound.
+fn scan_with(path: &Path, elsewhere: &HashSet<u64>) -> anyhow::Result<Scan> {
+    let raw = std::fs::read(path).map_err(|e| anyhow::anyhow!("{:?}: {}", path, e))?;
+    let mut scan = Scan::default();
+    let mut base = None;
+    let mut r = Reader { buf: &raw, pos: 0 };
+    let read = read_file(&mut r, &mut scan, &mut base);
+    if scan.compression != Compression::None && !cfg!(feature = "zstd") {
+        anyhow::bail!("{:?} is packed ({}), and this build has no zstd to unpack it with \
+                       (rebuild with the `zstd` feature)", path, scan.compression.name());
+    }
+    if let Err(reason) = read {
+        scan.problems.push(Problem::Damaged { offset: r.pos, reason });
+        return Ok(scan);
+    }
+    if scan.problems.iter().any(|p| matches!(p, Problem::OldFormat { .. })) { return Ok(scan); }
+    match std::fs::read(wal_path(path)) {
+        Ok(wal) => read_wal(&wal, &mut scan),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => anyhow::bail!("{:?}: {}", wal_path(path), e),
+    }
+
+    let mut known: HashSet<u64> = scan.records.keys().copied().collect();
+    known.extend(elsewhere);
+    if let Some((base_path, tombstones)) = base {
+        let base_path = fork::resolve(path, &base_path);
+        match scan_base(&base_path) {
+            Ok(ids) => known.extend(ids.into_iter().filter(|id| !tombstones.contains(id))),
+            Err(e) => {
+                scan.problems.push(Problem::Damaged { offset: 0, reason: format!("fork base {:?}: {:#}", base_path, e) });
+                return Ok(scan);
+            }
+        }
+    }
+    let mut problems = Vec::new();
+    for (index, ids) in &scan.vectors {
+        let mut orphans: Vec<u64> = ids.iter().filter(|id| !scan.records.contains_key(id)).copied().collect();
+        orphans.sort_unstable();
+        problems.extend(orphans.into_iter().map(|id| Problem::Orphan { index: index.clone(), id }));
+    }
+    let mut from_ids: Vec<&u64> = scan.records.keys().collect();
+    from_ids.sort_unstable();
+    for &from in from_ids {
+        for (to, rel_type) in &scan.records[&from] {
+            if !known.contains(to) {
+                problems.push(Problem::DanglingLink { from, to: *to, rel_type: rel_type.clone() });
+            }
+        }
+    }
+    scan.problems.extend(problems);
+    Ok(scan)
+}
+
+// Live record ids of a fork's base snapshot, and of its bases in turn.
+fn scan_base(path: &Path) -> anyhow::Result<Vec<u64>> {
+    let scan = scan(path)?;
+    if let Some(problem) = scan.problems.iter().find(|p| !p.is_repairable() || matches!(p, Problem::OldFormat { .. })) {
+        anyhow::bail!("{}", problem);
+    }
+    Ok(scan.records.into_keys().collect())
+}
+
+fn read_file(r: &mut Reader, scan: &mut Scan, base: &mut Option<(String, HashSet<u64>)>) -> Result<(), String> {
+    if r.u32().map_err(|_| "too short for a header".to_string())? != MAGIC {
+        r.pos = 0;
+        return Err("not a feather file (no FEAT header)".into());
+    }
+    scan.version = r.u32()?;
+    if scan.version > FORMAT_VERSION {
+        return Err(format!("format v{} is newer than this build reads (v{})", scan.version, FORMAT_VERSION));
+    }
+    if scan.version < OLDEST_CHECKED {
+        scan.problems.push(Problem::OldFormat { version: scan.version });
+        return Ok(());
+    }
+
+    for _ in 0..r.u32()? {
+        let key = r.string16()?;
+        let len = r.u32()?;
+        if len > MAX_PROPERTY { return Err(format!("property '{}' of implausible size {}", key, len)); }
+        let value = r.take(len as usize)?;
+        if key == hamming::PROPERTY_KEY { scan.binary = hamming::is_hamming(value); }
+        let decodes = match key.as_str() {
+            collection::PROPERTY_KEY => collection::decode(value).is_some(),
+            projection::PROPERTY_KEY => projection::decode(value).is_some(),
+            fork::PROPERTY_KEY => {
+                *base = fork::decode(value);
+                base.is_some()
+            }
+            _ => true,
+        };
+        if !decodes { return Err(format!("property '{}' does not decode", key)); }
+    }
+
+    let code = if scan.version >= 12 { r.u8()? } else { 0 };
+    scan.compression = Compression::from_code(code).ok_or_else(|| format!("unknown compression {}", code))?;
+    match scan.compression {
+        Compression::None => {
+            read_records(r, scan)?;
+            read_indexes(r, scan)?;
+        }
+        Compression::Metadata => {
+            let meta = unpack(r)?;
+            read_packed(&meta, "metadata", |m| read_records(m, scan))?;
+            read_indexes(r, scan)?;
+        }
+        Compression::All => {
+            let body = unpack(r)?;
+            read_packed(&body, "body", |b| {
+                read_records(b, scan)?;
+                read_indexes(b, scan)
+            })?;
+            // graph spans are offsets into the unpacked body, which repair
+            // cannot rewrite in place
+            if let Some(graph) = scan.problems.iter().find(|p| matches!(p, Problem::BadGraph { .. })) {
+                return Err(format!("packed body: {}", graph));
+            }
+        }
+    }
+
+    if r.remaining() > 0 {
+        return Err(format!("{} bytes after the last section", r.remaining()));
+    }
+    Ok(())
+}
+
+// A packed section: its unpacked and packed lengths, then a zstd frame
+// (the core's `packed.h`).
+fn unpack(r: &mut Reader) -> Result<Vec<u8>, String> {
+    let raw_len = r.u64()?;
+    let packed_len = r.u64()?;
+    if packed_len > r.remaining() as u64 || raw_len / MAX_PACK_RATIO > packed_len {
+        return Err(format!("packed section of implausible size {} -> {}", packed_len, raw_len));
+    }
+    let packed = r.take(packed_len as usize)?;
+    decode(packed, raw_len as usize)
+}
+
+#[cfg(feature = "zstd")]
+fn decode(packed: &[u8], raw_len: usize) -> Result<Vec<u8>, String> {
+    let mut raw = vec![0u8; raw_len];
+    let len = unsafe { ZSTD_decompress(raw.as_mut_ptr().cast(), raw.len(), packed.as_ptr().cast(), packed.len()) };
+    if unsafe { ZSTD_isError(len) } != 0 || len != raw.len() {
+        return Err("packed section does not decode".to_string());
+    }
+    Ok(raw)
+}
+
+// without the codec, `scan_with` refuses the file
+#[cfg(not(feature = "zstd"))]
+fn decode(_: &[u8], _: usize) -> Result<Vec<u8>, String> {
+    Err("this build has no zstd".to_string())
+}
+
+// Read the unpacked bytes of a section with `read`, which must use all of
+// them.
+fn read_packed(bytes: &[u8], what: &str, read: impl FnOnce(&mut Reader) -> Result<(), String>)
+               -> Result<(), String> {
+    let mut r = Reader { buf: bytes, pos: 0 };
+    read(&mut r).map_err(|e| format!("packed {} at byte {}: {}", what, r.pos, e))?;
+    if r.remaining() > 0 {
+        return Err(format!("packed {}: {} bytes after its end", what, r.remaining()));
+    }
+    Ok(())
+}
+
+fn read_records(r: &mut Reader, scan: &mut Scan) -> Result<(), String> {
+    for _ in 0..r.u32()? {
+        let id = r.u64()?;
+        let meta = read_meta(r)?;
+        if !meta.dead { scan.records.insert(id, meta.edges); }
+    }
+    Ok(())
+}
+
+// The dense indexes, then the sparse ones.
+fn read_indexes(r: &mut Reader, scan: &mut Scan) -> Result<(), String> {
+    for _ in 0..r.u32()? {
+        let name = r.string16()?;
+        let dim = r.u32()?;
+        if dim == 0 || dim > MAX_DIM { return Err(format!("modality '{}' of implausible dim {}", name, dim)); }
+        let dim = dim as usize;
+        scan.dims.insert(name.clone(), dim);
+        let quant = r.u8()? != 0;
+        let int8_scale = if r.u8()? != 0 { Some(r.f32()?) } else { None };
+        let storage = match int8_scale {
+            _ if scan.binary => Storage::Bits,
+            Some(scale) => Storage::Int8(scale),
+            None => Storage::Float,
+        };
+        let graph_at = r.pos;
+        if r.u8()? != 0 {
+            read_graph(r, scan, &name, dim, storage, graph_at)?;
+            continue;
+        }
+        for _ in 0..r.u32()? {
+            let id = r.u64()?;
+            let finite = if quant {
+                let scale = r.f32()?;
+                r.take(dim)?;
+                scale.is_finite()
+            } else {
+                floats(r.take(dim * 4)?).all(f32::is_finite)
+            };
+            add_vector(scan, &name, id, finite);
+        }
+    }
+
+    for _ in 0..r.u32()? {
+        let name = format!("sparse:{}", r.string16()?);
+        for _ in 0..r.u32()? {
+            let id = r.u64()?;
+            let nnz = r.u32()?;
+            if nnz > MAX_SPARSE_NNZ { return Err(format!("sparse vector {} of implausible size {}", id, nnz)); }
+            let pairs = r.take(nnz as usize * 8)?;
+            let finite = pairs.chunks_exact(8).all(|p| f32::from_le_bytes(p[4..].try_into().unwrap()).is_finite());
+            add_vector(scan, &name, id, finite);
+        }
+    }
+    Ok(())
+}
+
+fn add_vector(scan: &mut Scan, index: &str, id: u64, finite: bool) {
+    if !scan.vectors.entry(index.to_string()).or_default().insert(id) {
+        scan.problems.push(Problem::Duplicate { index: index.to_string(), id });
+    }
+    if !finite {
+        scan.problems.push(Problem::NonFinite { index: index.to_string(), id });
+    }
+}
+
+// A graph as hnswlib's saveIndexStream writes it: a header, every
+// element's level-0 block (links, vector, label), then each element's
+// upper-level links.
+fn read_graph(r: &mut Reader, scan: &mut Scan, name: &str, dim: usize, storage: Storage,
+              start: usize) -> Result<(), String> {
+    let offset_level0 = r.u64()?;
+    let _max_elements = r.u64()?;
+    let count = r.u64()? as usize;
+    let element_size = r.u64()? as usize;
+    let label_offset = r.u64()? as usize;
+    let data_offset = r.u64()? as usize;
+    let max_level = r.u32()? as i32;
+    let entry_point = r.u32()? as usize;
+    let max_m = r.u64()? as usize;
+    let max_m0 = r.u64()? as usize;
+    let _m = r.u64()?;
+    let _mult = r.f64()?;
+    let _ef_construction = r.u64()?;
+
+    let data_size = match storage {
+        Storage::Float => dim * 4,
+        Storage::Int8(_) => dim,
+        Storage::Bits => dim.div_ceil(64) * 8,
+    };
+    let layout_ok = offset_level0 == 0
+        && max_m0.checked_mul(4).and_then(|b| b.checked_add(4)) == Some(data_offset)
+        && data_offset.checked_add(data_size) == Some(label_offset)
+        && label_offset.checked_add(8) == Some(element_size);
+    if !layout_ok {
+        return Err(format!("modality '{}': HNSW element layout does not fit dim {}", name, dim));
+    }
+    let level0_at = r.pos;
+    let level0 = r.take(count.checked_mul(element_size).ok_or("implausible HNSW element count")?)?;
+
+    let mut faults = Vec::new();
+    let mut elements = Vec::with_capacity(count);
+    for (i, element) in level0.chunks_exact(element_size).enumerate() {
+        let links = u16::from_le_bytes([element[0], element[1]]) as usize;
+        if links > max_m0 {
+            faults.push(format!("node {} has {} level-0 links, more than {}", i, links, max_m0));
+        } else if let Some(bad) = u32s(&element[4..4 + links * 4]).find(|&n| n as usize >= count) {
+            faults.push(format!("node {} links to node {} of {}", i, bad, count));
+        }
+        let id = u64::from_le_bytes(element[label_offset..label_offset + 8].try_into().unwrap());
+        let data = &element[data_offset..label_offset];
+        let finite = match storage {
+            Storage::Float => floats(data).all(f32::is_finite),
+            Storage::Int8(scale) => scale.is_finite(),
+            Storage::Bits => true,
+        };
+        add_vector(scan, name, id, finite);
+        let at = level0_at + i * element_size + data_offset;
+        elements.push((id, at..at + data_size));
+    }
+    if count > 0 && entry_point >= count {
+        faults.push(format!("enters at node {} of {}", entry_point, count));
+    }
+
+    let list_size = max_m * 4 + 4;
+    for i in 0..count {
+        let size = r.u32()? as usize;
+        let lists = r.take(size)?;
+        if !size.is_multiple_of(list_size) || size / list_size > max_level.max(0) as usize {
+            faults.push(format!("node {} has upper-level links of {} bytes", i, size));
+            continue;
+        }
+        for list in lists.chunks_exact(list_size) {
+            let links = u16::from_le_bytes([list[0], list[1]]) as usize;
+            if links > max_m || u32s(&list[4..4 + links * 4]).any(|n| n as usize >= count) {
+                faults.push(format!("node {} has a malformed upper-level link list", i));
+                break;
+            }
+        }
+    }
+
+    if let Some(first) = faults.first() {
+        let reason = match faults.len() {
+            1 => first.clone(),
+            n => format!("{} (and {} more faults)", first, n - 1),
+        };
+        scan.problems.push(Problem::BadGraph { index: name.to_string(), reason });
+        scan.bad_graphs.push(Graph { span: start..r.pos, dim, storage, elements });
+    }
+    Ok(())
+}
+
+// The parts of a record `scan` needs.
+struct Meta {
+    dead: bool,
+    edges: Vec<(u64, String)>,
+}
+
+// A record's metadata as `Metadata::serialize` writes it.
+fn read_meta(r: &mut Reader) -> Result<Meta, String> {
+    r.take(8 + 4 + 1)?;   // timestamp, importance, type
+    let source = r.string16()?;
+    let len = r.u32()? as usize;
+    r.take(len)?;   // content
+    r.string16()?;   // tags JSON
+    let mut edges = Vec::new();
+    for _ in 0..r.u16()? {
+        edges.push((r.u64()?, "related_to".to_string()));   // legacy plain links
+    }
+    r.take(4 + 8)?;   // recall count, last recalled
+    r.string16()?;   // namespace
+    r.string16()?;   // entity
+    let mut deleted = false;
+    for _ in 0..r.u16()? {
+        let key = r.string16()?;
+        let len = r.u32()? as usize;
+        let value = r.take(len)?;
+        deleted |= key == "_deleted" && value == b"true";
+    }
+    for _ in 0..r.u16()? {
+        let target = r.u64()?;
+        let len = r.u8()? as usize;
+        let rel_type = String::from_utf8_lossy(r.take(len)?).into_owned();
+        r.take(4)?;   // weight
+        edges.push((target, rel_type));
+    }
+    r.take(8 + 4)?;   // ttl, confidence
+    Ok(Meta { dead: deleted || source == "_forgotten", edges })
+}
+
+// Replay the WAL onto what `scan` read from the file, noting bad entries.
+fn read_wal(raw: &[u8], scan: &mut Scan) {
+    let mut r = Reader { buf: raw, pos: 0 };
+    while r.remaining() > 0 {
+        let start = r.pos;
+        if r.remaining() < WAL_HEADER {
+            bad_wal(scan, start..raw.len(), format!("torn last entry ({} bytes)", raw.len() - start));
+            return;
+        }
+        let (op, id, len) = (r.u8().unwrap(), r.u64().unwrap(), r.u32().unwrap() as usize);
+        let Ok(payload) = r.take(len) else {
+            bad_wal(scan, start..raw.len(), format!("torn last entry ({} bytes)", raw.len() - start));
+            return;
+        };
+        scan.wal_entries += 1;
+        if let Err(reason) = replay(op, id, &mut Reader { buf: payload, pos: 0 }, scan) {
+            bad_wal(scan, start..r.pos, reason);
+        }
+    }
+}
+
+fn bad_wal(scan: &mut Scan, range: Range<usize>, reason: String) {
+    scan.problems.push(Problem::BadWalEntry { offset: range.start, reason });
+    scan.bad_wal.push(range);
+}
+
+// Apply one WAL entry the way the core's replay does.
+fn replay(op: u8, id: u64, p: &mut Reader, scan: &mut Scan) -> Result<(), String> {
+    match op {
+        WAL_ADD => {
+            let modality = p.string16().map_err(short("add", id))?;
+            let dim = p.u32().map_err(short("add", id))? as usize;
+            let vector = p.take(dim * 4).map_err(short("add", id))?;
+            let meta = read_meta(p).map_err(short("add", id))?;
+            let expected = *scan.dims.entry(modality.clone()).or_insert(dim);
+            if dim != expected {
+                return Err(format!("adds {} with {} dims to '{}' of dim {}", id, dim, modality, expected));
+            }
+            let finite = floats(vector).all(f32::is_finite);
+            scan.vectors.entry(modality.clone()).or_default().insert(id);
+            if !finite { scan.problems.push(Problem::NonFinite { index: modality, id }); }
+            set_record(scan, id, meta);
+        }
+        WAL_UPDATE => {
+            let meta = read_meta(p).map_err(short("update", id))?;
+            set_record(scan, id, meta);
+        }
+        WAL_UIMP => { p.f32().map_err(short("importance", id))?; }
+        WAL_LINK => {
+            let to = p.u64().map_err(short("link", id))?;
+            let len = p.u8().map_err(short("link", id))? as usize;
+            let rel_type = String::from_utf8_lossy(p.take(len).map_err(short("link", id))?).into_owned();
+            p.f32().map_err(short("link", id))?;
+            if let Some(edges) = scan.records.get_mut(&id) {
+                if !edges.iter().any(|(t, r)| *t == to && *r == rel_type) { edges.push((to, rel_type)); }
+            }
+        }
+        WAL_FORGET => {
+            scan.records.remove(&id);
+            for ids in scan.vectors.values_mut() { ids.remove(&id); }
+            for edges in scan.records.values_mut() { edges.retain(|(to, _)| *to != id); }
+        }
+        WAL_SPARSE => {
+            let name = format!("sparse:{}", p.string16().map_err(short("sparse", id))?);
+            let nnz = p.u32().map_err(short("sparse", id))? as usize;
+            let pairs = p.take(nnz * 8).map_err(short("sparse", id))?;
+            scan.vectors.entry(name.clone()).or_default().insert(id);
+            if !pairs.chunks_exact(8).all(|c| f32::from_le_bytes(c[4..].try_into().unwrap()).is_finite()) {
+                scan.problems.push(Problem::NonFinite { index: name, id });
+            }
+        }
+        WAL_UNLINK => {
+            let to = p.u64().map_err(short("unlink", id))?;
+            let len = p.u8().map_err(short("unlink", id))? as usize;
+            let rel_type = String::from_utf8_lossy(p.take(len).map_err(short("unlink", id))?).into_owned();
+            if let Some(edges) = scan.records.get_mut(&id) {
+                edges.retain(|(t, r)| *t != to || !(rel_type.is_empty() || *r == rel_type));
+            }
+        }
+        WAL_TXN => {
+            // the entries of a committed transaction, which replay applies
+            // whole: an entry torn inside it would be a bad file, not a crash
+            while p.remaining() > 0 {
+                let (op, id) = (p.u8().map_err(short("transaction", id))?, p.u64().map_err(short("transaction", id))?);
+                let len = p.u32().map_err(short("transaction", id))? as usize;
+                let payload = p.take(len).map_err(short("transaction", id))?;
+                replay(op, id, &mut Reader { buf: payload, pos: 0 }, scan).map_err(|e| format!("in a transaction: {}", e))?;
+            }
+        }
+        other => return Err(format!("unknown op 0x{:02x}", other)),
+    }
+    Ok(())
+}
+
+fn short(what: &'static str, id: u64) -> impl Fn(String) -> String {
+    move |_| format!("short {} entry for {}", what, id)
+}
+
+fn set_record(scan: &mut Scan, id: u64, meta: Meta) {
+    if meta.dead {
+        scan.records.remove(&id);
+    } else {
+        scan.records.insert(id, meta.edges);
+    }
+}
+
+fn floats(bytes: &[u8]) -> impl Iterator<Item = f32> + '_ {
+    bytes.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn u32s(bytes: &[u8]) -> impl Iterator<Item = u32> + '_ {
+    bytes.chunks_exact(4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+// Little-endian reads that fail, rather than run past the end, on a
+// truncated buffer.
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn remaining(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if n > self.remaining() {
+            return Err(format!("truncated: {} bytes needed, {} left", n, self.remaining()));
+        }
+        self.pos += n;
+        Ok(&self.buf[self.pos - n..self.pos])
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, String> { Ok(self.array::<1>()?[0]) }
+    fn u16(&mut self) -> Result<u16, String> { Ok(u16::from_le_bytes(self.array()?)) }
+    fn u32(&mut self) -> Result<u32, String> { Ok(u32::from_le_bytes(self.array()?)) }
+    fn u64(&mut self) -> Result<u64, String> { Ok(u64::from_le_bytes(self.array()?)) }
+    fn f32(&mut self) -> Result<f32, String> { Ok(f32::from_le_bytes(self.array()?)) }
+    fn f64(&mut self) -> Result<f64, String> { Ok(f64::from_le_bytes(self.array()?)) }
+
+    // A string with a u16 length prefix.
+    fn string16(&mut self) -> Result<String, String> {
+        let len = self.u16()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+}
@@ -0,0 +1,61 @@
+//! One ranking from several queries (`DB::search_fused`, `feather search
+//! --fuse`).
+//!
+//! An agent often asks the same thing several ways: reformulations of a
+//! question, HyDE-style hypothetical answers. Each
+//! query is ranked on its own, as `search_with_options` would rank it, to
+//! `CANDIDATE_FACTOR` times the hits asked for, and the rankings are fused
+//! by reciprocal rank fusion: a record's score is
+//!
+//! ```text
+//! Σ over the rankings it is in of 1 / (RRF_K + rank)
+//! ```
+//!
+//! ranks counting from 1. Only ranks matter, so queries whose similarity
+//! scores live on different scales fuse fairly, and a record several
+//! queries agree on beats one that a single query ranks first. The fused
+//! list has each record once; ties go to the lower id.
+
+use crate::rerank::Query;
+use crate::search::CANDIDATE_FACTOR;
+use crate::{SearchOptions, DB};
+use std::collections::HashMap;
+
+/// Rank offset of reciprocal rank fusion: the larger, the less the top few
+/// ranks of each list dominate.
+pub const RRF_K: f32 = 60.0;
+
+impl DB {
+    /// The records `queries` rank best together, fused by reciprocal rank
+    /// fusion (see the module docs), as `(id, fused score)`.
+    pub fn search_fused(&self, queries: &[&[f32]], k: usize, modality: &str) -> anyhow::Result<Vec<(u64, f32)>> {
+        self.search_fused_with_options(queries, k, modality, &SearchOptions::default())
+    }
+
+    /// `search_fused`, each query ranked under `options`; `options.offset`
+    /// pages the fused list. The hits count as recalled and every query
+    /// feeds drift stats.
+    pub fn search_fused_with_options(&self, queries: &[&[f32]], k: usize, modality: &str,
+                                     options: &SearchOptions) -> anyhow::Result<Vec<(u64, f32)>> {
+        anyhow::ensure!(!queries.is_empty(), "no queries to fuse");
+        let depth = k.saturating_add(options.offset).saturating_mul(CANDIDATE_FACTOR);
+        let each = SearchOptions { offset: 0, ..options.clone() };
+        let mut fused: HashMap<u64, f32> = HashMap::new();
+        for query in queries {
+            let ranking = self.ranked(Query { vector: query, text: options.text.as_deref() }, depth, modality, &each, None)?;
+            for (rank, (id, _)) in ranking.into_iter().enumerate() {
+                *fused.entry(id).or_default() += 1.0 / (RRF_K + rank as f32 + 1.0);
+            }
+        }
+        let internal = self.mname(Some(modality)).expect("named");
+        for query in queries {
+            self.observe_query(Some(&internal), &self.project(Some(&internal), query));
+        }
+        let mut hits: Vec<(u64, f32)> = fused.into_iter().collect();
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        let hits = self.cap_per_source(hits, options, None);
+        let hits: Vec<(u64, f32)> = hits.into_iter().skip(options.offset).take(k).collect();
+        self.recalled(hits.iter().map(|&(id, _)| id));
+        Ok(hits)
+    }
+}
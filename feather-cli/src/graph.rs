@@ -0,0 +1,202 @@
+//! The association graph: typed, weighted links between records
+//! (`DB::link_with`, `feather link`; `DB::link_batch_with`, `feather link
+//! --file`; `DB::unlink`, `feather unlink`), read back as the links of a record and
+//! the records within a few hops of it (`DB::links`, `DB::neighbors`,
+//! `feather links`).
+//!
+//! A link is stored as an edge on the record it starts from, but both
+//! directions are walked: a record's neighbours are the records it links to
+//! and the records linking to it, the latter found through the core's
+//! reverse index. Forgetting a record drops every link from or to it, so
+//! the graph never points at a deleted record.
+
+use crate::*;
+use serde::Serialize;
+use std::collections::VecDeque;
+
+/// Edge type of a link made without one.
+pub const DEFAULT_REL_TYPE: &str = "related_to";
+
+/// Longest edge type the core's log keeps, in bytes.
+const MAX_REL_TYPE_LEN: usize = 255;
+
+/// A directed, typed link between two records; also one row of a links
+/// CSV (`feather bootstrap`, `feather import --links`, `export::write_links`).
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Link {
+    pub from: u64,
+    pub to: u64,
+    pub rel_type: String,
+    pub weight: f32,
+}
+
+impl Link {
+    /// The end of the link that is not `id` (`id` itself for a self-link).
+    pub fn other(&self, id: u64) -> u64 {
+        if self.from == id { self.to } else { self.from }
+    }
+}
+
+/// A record reached by `DB::neighbors`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Neighbor {
+    pub id: u64,
+    /// Hops from the start of the walk, from 1.
+    pub depth: usize,
+    /// The link it was first reached by; the other end is one hop nearer
+    /// the start.
+    pub via: Link,
+}
+
+// Reject an edge type the core's log cannot keep, or a weight that is not
+// a positive number.
+pub(crate) fn check_edge(rel_type: &str, weight: f32) -> anyhow::Result<()> {
+    anyhow::ensure!(!rel_type.is_empty() && rel_type.len() <= MAX_REL_TYPE_LEN,
+                    "edge type must be 1 to {} bytes", MAX_REL_TYPE_LEN);
+    anyhow::ensure!(weight.is_finite() && weight > 0.0, "edge weight must be positive");
+    Ok(())
+}
+
+impl DB {
+    /// Link `from` to `to` with an edge type (e.g. "caused_by", "follows",
+    /// "refines") and a positive weight; both records must exist. Linking
+    /// the pair again under the same type updates the weight.
+    pub fn link_with(&self, from: u64, to: u64, rel_type: &str, weight: f32) -> anyhow::Result<()> {
+        self.writable()?;
+        check_edge(rel_type, weight)?;
+        for id in [from, to] {
+            anyhow::ensure!(self.is_live(id), "no record {}", id);
+        }
+        self.put_link(from, to, rel_type, weight)?;
+        Ok(())
+    }
+
+    /// Link each `(from, to)` pair as `link` would, with the default edge
+    /// type and weight 1. See `link_batch_with`.
+    pub fn link_batch(&self, pairs: &[(u64, u64)]) -> anyhow::Result<usize> {
+        let links: Vec<Link> = pairs.iter()
+            .map(|&(from, to)| Link { from, to, rel_type: DEFAULT_REL_TYPE.to_string(), weight: 1.0 })
+            .collect();
+        self.link_batch_with(&links)
+    }
+
+    /// Make every link of `links` as `link_with` would, in order, a later
+    /// link of the same pair and type setting its weight. All are checked
+    /// before any is made, so a bad one (a missing record, an edge type or
+    /// weight `link_with` refuses) fails the call with nothing linked.
+    /// Returns how many links were made or reweighted.
+    pub fn link_batch_with(&self, links: &[Link]) -> anyhow::Result<usize> {
+        self.writable()?;
+        let mut live = HashSet::new();
+        for (i, link) in links.iter().enumerate() {
+            let fail = |e: anyhow::Error| anyhow::anyhow!("link {} ({} -> {}): {}", i + 1, link.from, link.to, e);
+            check_edge(&link.rel_type, link.weight).map_err(fail)?;
+            for id in [link.from, link.to] {
+                if live.contains(&id) { continue; }
+                if !self.is_live(id) { return Err(fail(anyhow::anyhow!("no record {}", id))); }
+                live.insert(id);
+            }
+        }
+        let mut made = 0;
+        for link in links {
+            if self.put_link(link.from, link.to, &link.rel_type, link.weight)? { made += 1; }
+        }
+        Ok(made)
+    }
+
+    // Whether `id` has a record that is not forgotten.
+    fn is_live(&self, id: u64) -> bool {
+        self.get_metadata(id).is_some_and(|m| !m.is_forgotten())
+    }
+
+    // Link two live records, or set the weight of their link of this type.
+    // Returns whether anything changed.
+    fn put_link(&self, from: u64, to: u64, rel_type: &str, weight: f32) -> anyhow::Result<bool> {
+        let mut meta = self.get_metadata(from).ok_or_else(|| anyhow::anyhow!("no record {}", from))?;
+        if let Some(edge) = meta.edges.iter_mut().find(|e| e.target == to && e.rel_type == rel_type) {
+            if edge.weight == weight { return Ok(false); }
+            edge.weight = weight;
+            self.put_metadata(from, &meta)?;
+            return Ok(true);
+        }
+        let (from, to) = (self.iid(from)?, self.iid(to)?);
+        let c_type = c_str(rel_type)?;
+        self.handle.copy_up(from);
+        unsafe { feather_link_typed(self.handle.core(from), from, to, c_type.as_ptr(), weight) };
+        self.stamp(from, meta.version());
+        self.changed(audit::Op::Link, from, Some(to), Some(rel_type));
+        Ok(true)
+    }
+
+    /// Remove the links from `from` to `to`, of every type. Returns how many
+    /// were removed (0 if there were none).
+    pub fn unlink(&self, from: u64, to: u64) -> anyhow::Result<usize> {
+        self.unlink_edges(from, to, None)
+    }
+
+    /// Remove the link from `from` to `to` of type `rel_type`. Returns
+    /// whether there was one.
+    pub fn unlink_type(&self, from: u64, to: u64, rel_type: &str) -> anyhow::Result<bool> {
+        Ok(self.unlink_edges(from, to, Some(rel_type))? > 0)
+    }
+
+    fn unlink_edges(&self, from: u64, to: u64, rel_type: Option<&str>) -> anyhow::Result<usize> {
+        self.writable()?;
+        let Some(meta) = self.get_metadata(from) else { return Ok(0) };
+        if !meta.edges.iter().any(|e| e.target == to && rel_type.is_none_or(|t| e.rel_type == t)) {
+            return Ok(0);
+        }
+        let (from, to) = (self.iid(from)?, self.iid(to)?);
+        let c_type = rel_type.map(c_str).transpose()?;
+        self.handle.copy_up(from);
+        let removed = unsafe { feather_unlink(self.handle.core(from), from, to, c_type.as_ref().map_or(std::ptr::null(), |t| t.as_ptr())) };
+        self.stamp(from, meta.version());
+        self.changed(audit::Op::Unlink, from, Some(to), rel_type);
+        Ok(removed)
+    }
+
+    /// Links between the live record `id` and other live records: the ones
+    /// it holds (`from == id`) first, then the ones pointing at it. Empty if
+    /// the record does not exist.
+    pub fn links(&self, id: u64) -> Vec<Link> {
+        let live = |id: u64| self.get_metadata(id).filter(|m| !m.is_forgotten());
+        let Some(meta) = live(id) else { return Vec::new() };
+        let mut links: Vec<Link> = meta.edges.into_iter()
+            .filter(|e| live(e.target).is_some())
+            .map(|e| Link { from: id, to: e.target, rel_type: e.rel_type, weight: e.weight })
+            .collect();
+        let Ok(internal) = self.iid(id) else { return links };
+        let mut sources: Vec<u64> = self.handle.incoming(internal).into_iter()
+            .filter_map(|src| self.xid(src))
+            .filter(|&src| src != id)
+            .collect();
+        sources.sort_unstable();
+        sources.dedup();
+        for src in sources {
+            let Some(meta) = live(src) else { continue };
+            links.extend(meta.edges.into_iter()
+                .filter(|e| e.target == id)
+                .map(|e| Link { from: src, to: id, rel_type: e.rel_type, weight: e.weight }));
+        }
+        links
+    }
+
+    /// The live records within `depth` hops of `id`, following links in
+    /// either direction, nearest first. Each is listed once, reached by a
+    /// shortest path; `id` itself is not listed.
+    pub fn neighbors(&self, id: u64, depth: usize) -> Vec<Neighbor> {
+        let mut seen = HashSet::from([id]);
+        let mut queue = VecDeque::from([(id, 0)]);
+        let mut out = Vec::new();
+        while let Some((node, hops)) = queue.pop_front() {
+            if hops == depth { continue; }
+            for link in self.links(node) {
+                let next = link.other(node);
+                if !seen.insert(next) { continue; }
+                queue.push_back((next, hops + 1));
+                out.push(Neighbor { id: next, depth: hops + 1, via: link });
+            }
+        }
+        out
+    }
+}
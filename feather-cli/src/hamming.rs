@@ -0,0 +1,68 @@
+//! Binary embeddings compared by Hamming distance (`Metric::Hamming`,
+//! `feather new --metric hamming`).
+//!
+//! In a hamming store every index keeps one bit per dimension, set where
+//! the component is above zero, and ranks by the number of bits that
+//! differ, counted with popcount: a 1024-dimension vector takes 128 bytes
+//! rather than 4 KiB. Vectors may be given as 0/1 or as raw float or ±1
+//! embeddings to be binarized; either way they are stored, returned and
+//! compared as 0.0/1.0, whose squared L2 distance is the Hamming distance,
+//! so hits score `1 / (1 + bits differing)` here as on every other path.
+//!
+//! The metric is a property of the store, all collections and shards, and
+//! is chosen when it is created: it cannot change once a vector is in,
+//! and cannot be combined with normalization.
+
+use crate::config::Metric;
+use crate::*;
+
+/// Property the core reads to build its indexes as bits.
+pub(crate) const PROPERTY_KEY: &str = "metric";
+
+/// The property's value in a hamming store.
+const HAMMING: &[u8] = b"hamming";
+
+extern "C" {
+    fn feather_set_binary(db: *mut c_void) -> i32;
+}
+
+/// `vec` as 0.0/1.0: 1.0 where a component is above zero.
+pub(crate) fn binarize(vec: &[f32]) -> Vec<f32> {
+    vec.iter().map(|&x| if x > 0.0 { 1.0 } else { 0.0 }).collect()
+}
+
+/// Whether a property value marks a hamming store.
+pub(crate) fn is_hamming(value: &[u8]) -> bool { value == HAMMING }
+
+/// Make the core `core` store bits; it must hold no vectors yet.
+pub(crate) fn set_binary(core: *mut c_void) -> anyhow::Result<()> {
+    if unsafe { feather_set_binary(core) } != 0 { return Err(last_error()); }
+    Ok(())
+}
+
+impl DB {
+    /// How this store compares vectors.
+    pub fn metric(&self) -> Metric {
+        if self.handle.binary.get() {
+            Metric::Hamming
+        } else if self.normalizes() {
+            Metric::Cosine
+        } else {
+            Metric::L2
+        }
+    }
+
+    /// Store vectors as bits and rank by Hamming distance from now on, in
+    /// every collection and shard. Only before the first vector is added.
+    pub fn set_hamming(&self) -> anyhow::Result<()> {
+        if self.handle.binary.get() { return Ok(()); }
+        self.writable()?;
+        anyhow::ensure!(self.handle.fork.is_none(), "a fork compares vectors as its base does");
+        anyhow::ensure!(!self.normalizes(), "a normalizing store cannot compare by Hamming distance");
+        for &core in self.handle.cores() {
+            set_binary(core)?;
+        }
+        self.handle.binary.set(true);
+        Ok(())
+    }
+}
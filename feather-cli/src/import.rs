@@ -0,0 +1,517 @@
+//! Bulk loading records from other stores (`feather import`).
+//!
+//! Every reader yields `Record`s. Besides the layout `feather export` writes,
+//! rows may carry a bare `vector` (alias `embedding` / `values`) that lands in
+//! the default modality, `vector_<modality>` columns, a `sparse_values`
+//! object (`{"indices": [..], "values": [..]}`) that lands in the default
+//! sparse set, a `session_id` (see `session`), an `owner` and `visibility`
+//! (see `access`), and a `metadata` (alias `payload`) object as produced by
+//! other vector databases; keys in it that are not feather metadata fields
+//! or one of those three become string attributes.
+
+use crate::metadata::{OWNER_ATTRIBUTE, SESSION_ATTRIBUTE, VISIBILITY_ATTRIBUTE};
+use crate::progress::Reporter;
+use crate::{sparse, Dedup, Visibility, Metadata, ProgressFn, Record, SparseVector, DB};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::io::{BufRead, Read};
+use std::sync::mpsc;
+
+/// Records inserted per `add_batch` call unless told otherwise.
+pub const DEFAULT_BATCH_SIZE: usize = 1000;
+
+/// Batches `import_pipelined` reads ahead of the one being inserted.
+pub const READ_AHEAD: usize = 2;
+
+pub(crate) const VECTOR_KEYS: [&str; 3] = ["vector", "embedding", "values"];
+const METADATA_KEYS: [&str; 2] = ["metadata", "payload"];
+/// Row keys stored as the attribute of the same meaning.
+const ATTRIBUTE_KEYS: [(&str, &str); 3] =
+    [("session_id", SESSION_ATTRIBUTE), ("owner", OWNER_ATTRIBUTE), ("visibility", VISIBILITY_ATTRIBUTE)];
+const INT_FIELDS: [&str; 6] = ["id", "timestamp", "context_type", "recall_count", "last_recalled_at", "ttl"];
+const FLOAT_FIELDS: [&str; 2] = ["importance", "confidence"];
+const STRING_FIELDS: [&str; 5] = ["source", "content", "tags_json", "namespace_id", "entity_id"];
+const JSON_FIELDS: [&str; 3] = ["vectors", "attributes", "edges"];
+/// Other spellings accepted for a metadata field.
+const ALIASES: [(&str, &str); 1] = [("ttl_seconds", "ttl")];
+
+fn canonical(key: &str) -> &str {
+    ALIASES.iter().find(|(alias, _)| *alias == key).map_or(key, |(_, field)| field)
+}
+
+fn is_field(key: &str) -> bool {
+    let key = canonical(key);
+    INT_FIELDS.contains(&key) || FLOAT_FIELDS.contains(&key)
+        || STRING_FIELDS.contains(&key) || JSON_FIELDS.contains(&key)
+}
+
+/// Turn one loosely-shaped row into a `Record`.
+pub fn record_from_json(mut obj: Map<String, Value>, default_modality: &str) -> anyhow::Result<Record> {
+    let mut vectors: BTreeMap<String, Value> = match obj.remove("vectors") {
+        Some(Value::Object(m)) => m.into_iter().collect(),
+        Some(Value::Null) | None => BTreeMap::new(),
+        Some(other) => anyhow::bail!("`vectors` must be an object, got {}", other),
+    };
+    for key in VECTOR_KEYS {
+        if let Some(v) = obj.remove(key) {
+            vectors.entry(default_modality.to_string()).or_insert(v);
+        }
+    }
+    let columns: Vec<String> = obj.keys().filter(|k| k.starts_with("vector_")).cloned().collect();
+    for key in columns {
+        let v = obj.remove(&key).expect("listed");
+        if !v.is_null() { vectors.insert(key["vector_".len()..].to_string(), v); }
+    }
+
+    let mut sparse: BTreeMap<String, Value> = match obj.remove("sparse") {
+        // a bare sparse vector rather than a name → vector map
+        Some(Value::Object(m)) if m.contains_key("indices") => {
+            BTreeMap::from([(sparse::DEFAULT_NAME.to_string(), Value::Object(m))])
+        }
+        Some(Value::Object(m)) => m.into_iter().collect(),
+        Some(Value::Null) | None => BTreeMap::new(),
+        Some(other) => anyhow::bail!("`sparse` must be an object, got {}", other),
+    };
+    if let Some(v) = obj.remove("sparse_values") {
+        sparse.entry(sparse::DEFAULT_NAME.to_string()).or_insert(v);
+    }
+
+    let mut attributes = match obj.remove("attributes") {
+        Some(Value::Object(m)) => m,
+        Some(Value::Null) | None => Map::new(),
+        Some(other) => anyhow::bail!("`attributes` must be an object, got {}", other),
+    };
+    for key in METADATA_KEYS {
+        let Some(Value::Object(extra)) = obj.remove(key) else { continue };
+        for (k, v) in extra {
+            if is_field(&k) || ATTRIBUTE_KEYS.iter().any(|(key, _)| *key == k) {
+                obj.entry(k).or_insert(v);
+            } else {
+                attributes.entry(k).or_insert(v);
+            }
+        }
+    }
+    for (key, attribute) in ATTRIBUTE_KEYS {
+        if let Some(value) = obj.remove(key).filter(|v| !v.is_null()) {
+            attributes.insert(attribute.to_string(), value);
+        }
+    }
+    let attributes: Map<String, Value> = attributes.into_iter()
+        .map(|(k, v)| {
+            let s = match v { Value::String(s) => s, other => other.to_string() };
+            (k, Value::String(s))
+        })
+        .collect();
+    obj.insert("attributes".into(), Value::Object(attributes));
+    for (alias, field) in ALIASES {
+        if let Some(v) = obj.remove(alias) { obj.entry(field).or_insert(v); }
+    }
+    obj.retain(|k, v| is_field(k) && !v.is_null());
+    anyhow::ensure!(obj.contains_key("id"), "missing `id`");
+
+    let mut record: Record = serde_json::from_value(Value::Object(obj))?;
+    for (modality, v) in vectors {
+        let v: Vec<f32> = serde_json::from_value(v)
+            .map_err(|e| anyhow::anyhow!("vector for modality '{}': {}", modality, e))?;
+        record.vectors.insert(modality, v);
+    }
+    for (name, v) in sparse {
+        if v.is_null() { continue; }
+        let v: SparseVector = serde_json::from_value(v)
+            .map_err(|e| anyhow::anyhow!("sparse vector '{}': {}", name, e))?;
+        record.sparse.insert(name, v);
+    }
+    if let Some(visibility) = record.metadata.attributes.get(VISIBILITY_ATTRIBUTE) {
+        Visibility::parse(visibility)?;
+    }
+    Ok(record)
+}
+
+/// One JSON object per line; blank lines are skipped.
+pub struct JsonlReader<R: BufRead> {
+    lines: std::io::Lines<R>,
+    line: usize,
+    default_modality: String,
+}
+
+impl<R: BufRead> JsonlReader<R> {
+    pub fn new(input: R, default_modality: &str) -> Self {
+        JsonlReader { lines: input.lines(), line: 0, default_modality: default_modality.to_string() }
+    }
+}
+
+impl<R: BufRead> Iterator for JsonlReader<R> {
+    type Item = anyhow::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line += 1;
+            let line = match self.lines.next()? {
+                Ok(l) => l,
+                Err(e) => return Some(Err(e.into())),
+            };
+            if line.trim().is_empty() { continue; }
+            let parsed = serde_json::from_str::<Map<String, Value>>(&line)
+                .map_err(anyhow::Error::from)
+                .and_then(|obj| record_from_json(obj, &self.default_modality));
+            return Some(parsed.map_err(|e| anyhow::anyhow!("line {}: {}", self.line, e)));
+        }
+    }
+}
+
+/// CSV with a header row. Vector columns (`vector`, `vector_<modality>`, …)
+/// hold a JSON array or floats separated by spaces, commas or semicolons;
+/// `attributes` / `edges` hold JSON; unknown columns become attributes.
+pub struct CsvReader<R: Read> {
+    rows: csv::StringRecordsIntoIter<R>,
+    headers: Vec<String>,
+    row: usize,
+    default_modality: String,
+    first_id: Option<u64>,
+}
+
+impl<R: Read> CsvReader<R> {
+    pub fn new(input: R, default_modality: &str) -> anyhow::Result<Self> {
+        let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(input);
+        let headers = reader.headers()?.iter().map(|h| h.trim().to_string()).collect();
+        Ok(CsvReader { rows: reader.into_records(), headers, row: 1, default_modality: default_modality.to_string(), first_id: None })
+    }
+
+    /// Rows without an `id` get `first` for the first data row, `first + 1`
+    /// for the next, and so on.
+    pub fn with_row_ids(mut self, first: u64) -> Self {
+        self.first_id = Some(first);
+        self
+    }
+}
+
+fn csv_value(column: &str, raw: &str) -> anyhow::Result<Value> {
+    let raw = raw.trim();
+    let column = canonical(column);
+    if VECTOR_KEYS.contains(&column) || column.starts_with("vector_") {
+        if raw.starts_with('[') { return Ok(serde_json::from_str(raw)?); }
+        let floats = raw.split([',', ';', ' ']).filter(|s| !s.is_empty())
+            .map(|s| s.parse::<f32>().map_err(|e| anyhow::anyhow!("`{}`: {}", s, e)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        return Ok(floats.into());
+    }
+    if INT_FIELDS.contains(&column) || FLOAT_FIELDS.contains(&column) || JSON_FIELDS.contains(&column) {
+        return serde_json::from_str(raw).map_err(|e| anyhow::anyhow!("column `{}`: {}", column, e));
+    }
+    Ok(Value::String(raw.to_string()))
+}
+
+impl<R: Read> Iterator for CsvReader<R> {
+    type Item = anyhow::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let row = self.rows.next()?;
+        self.row += 1;
+        let parsed = row.map_err(anyhow::Error::from).and_then(|row| {
+            let mut obj = Map::new();
+            let mut extra = Map::new();
+            for (column, raw) in self.headers.iter().zip(row.iter()) {
+                if raw.trim().is_empty() { continue; }
+                let value = csv_value(column, raw)?;
+                if is_field(column) || VECTOR_KEYS.contains(&column.as_str()) || column.starts_with("vector_") {
+                    obj.insert(column.clone(), value);
+                } else {
+                    extra.insert(column.clone(), value);
+                }
+            }
+            obj.insert("metadata".into(), Value::Object(extra));
+            if let Some(first) = self.first_id {
+                obj.entry("id").or_insert_with(|| (first + self.row as u64 - 2).into());
+            }
+            record_from_json(obj, &self.default_modality)
+        });
+        Some(parsed.map_err(|e| anyhow::anyhow!("row {}: {}", self.row, e)))
+    }
+}
+
+#[cfg(feature = "arrow")]
+pub use columnar::ArrowReader;
+#[cfg(feature = "parquet")]
+pub use columnar::ParquetReader;
+
+#[cfg(feature = "arrow")]
+pub mod columnar {
+    use super::record_from_json;
+    use crate::Record;
+    use arrow::array::{
+        Array, FixedSizeListArray, Float32Array, Float64Array, Int32Array, Int64Array, LargeStringArray,
+        ListArray, StringArray, UInt32Array, UInt64Array, UInt8Array,
+    };
+    use arrow::datatypes::DataType;
+    use arrow::error::ArrowError;
+    use arrow::record_batch::RecordBatch;
+    use serde_json::{Map, Value};
+    use std::collections::VecDeque;
+
+    fn value_at(col: &dyn Array, row: usize) -> anyhow::Result<Value> {
+        if col.is_null(row) { return Ok(Value::Null); }
+        macro_rules! scalar {
+            ($ty:ty) => { col.as_any().downcast_ref::<$ty>().expect("type checked").value(row).into() };
+        }
+        Ok(match col.data_type() {
+            DataType::UInt8 => scalar!(UInt8Array),
+            DataType::UInt32 => scalar!(UInt32Array),
+            DataType::UInt64 => scalar!(UInt64Array),
+            DataType::Int32 => scalar!(Int32Array),
+            DataType::Int64 => scalar!(Int64Array),
+            DataType::Float32 => scalar!(Float32Array),
+            DataType::Float64 => scalar!(Float64Array),
+            DataType::Utf8 => scalar!(StringArray),
+            DataType::LargeUtf8 => scalar!(LargeStringArray),
+            DataType::List(_) => {
+                let list = col.as_any().downcast_ref::<ListArray>().expect("type checked");
+                floats(list.value(row).as_ref())?
+            }
+            DataType::FixedSizeList(_, _) => {
+                let list = col.as_any().downcast_ref::<FixedSizeListArray>().expect("type checked");
+                floats(list.value(row).as_ref())?
+            }
+            other => anyhow::bail!("unsupported column type {:?}", other),
+        })
+    }
+
+    fn floats(values: &dyn Array) -> anyhow::Result<Value> {
+        let v: Vec<f64> = match values.data_type() {
+            DataType::Float32 => values.as_any().downcast_ref::<Float32Array>().expect("type checked")
+                .iter().map(|x| x.unwrap_or(0.0) as f64).collect(),
+            DataType::Float64 => values.as_any().downcast_ref::<Float64Array>().expect("type checked")
+                .iter().map(|x| x.unwrap_or(0.0)).collect(),
+            other => anyhow::bail!("unsupported vector element type {:?}", other),
+        };
+        Ok(v.into())
+    }
+
+    /// The rows of one batch, columns matched by name.
+    pub fn from_batch(batch: &RecordBatch, default_modality: &str) -> anyhow::Result<Vec<Record>> {
+        let schema = batch.schema();
+        let mut out = Vec::with_capacity(batch.num_rows());
+        for row in 0..batch.num_rows() {
+            let mut obj = Map::new();
+            for (field, col) in schema.fields().iter().zip(batch.columns()) {
+                let mut v = value_at(col.as_ref(), row)?;
+                // the export layout stores these as JSON text
+                if matches!(field.name().as_str(), "attributes" | "edges" | "vectors" | "metadata" | "payload") {
+                    if let Value::String(s) = &v { v = serde_json::from_str(s)?; }
+                }
+                obj.insert(field.name().clone(), v);
+            }
+            out.push(record_from_json(obj, default_modality)?);
+        }
+        Ok(out)
+    }
+
+    // Yields the rows of a stream of record batches.
+    struct Rows<I> {
+        batches: I,
+        pending: VecDeque<Record>,
+        default_modality: String,
+    }
+
+    impl<I: Iterator<Item = Result<RecordBatch, ArrowError>>> Iterator for Rows<I> {
+        type Item = anyhow::Result<Record>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            while self.pending.is_empty() {
+                let batch = self.batches.next()?.map_err(anyhow::Error::from);
+                match batch.and_then(|b| from_batch(&b, &self.default_modality)) {
+                    Ok(rows) => self.pending.extend(rows),
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            self.pending.pop_front().map(Ok)
+        }
+    }
+
+    /// Arrow IPC file reader.
+    pub struct ArrowReader<R: std::io::Read + std::io::Seek>(Rows<arrow::ipc::reader::FileReader<R>>);
+
+    impl<R: std::io::Read + std::io::Seek> ArrowReader<R> {
+        pub fn new(input: R, default_modality: &str) -> anyhow::Result<Self> {
+            let batches = arrow::ipc::reader::FileReader::try_new(input, None)?;
+            Ok(ArrowReader(Rows { batches, pending: VecDeque::new(), default_modality: default_modality.to_string() }))
+        }
+    }
+
+    impl<R: std::io::Read + std::io::Seek> Iterator for ArrowReader<R> {
+        type Item = anyhow::Result<Record>;
+        fn next(&mut self) -> Option<Self::Item> { self.0.next() }
+    }
+
+    /// Parquet file reader.
+    #[cfg(feature = "parquet")]
+    pub struct ParquetReader(Rows<parquet::arrow::arrow_reader::ParquetRecordBatchReader>);
+
+    #[cfg(feature = "parquet")]
+    impl ParquetReader {
+        pub fn new(input: std::fs::File, default_modality: &str) -> anyhow::Result<Self> {
+            let batches = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(input)?.build()?;
+            Ok(ParquetReader(Rows { batches, pending: VecDeque::new(), default_modality: default_modality.to_string() }))
+        }
+    }
+
+    #[cfg(feature = "parquet")]
+    impl Iterator for ParquetReader {
+        type Item = anyhow::Result<Record>;
+        fn next(&mut self) -> Option<Self::Item> { self.0.next() }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ImportReport {
+    /// Records inserted.
+    pub records: usize,
+    /// Records left out under `OnDuplicate::Ignore`.
+    pub skipped: usize,
+    /// Records the dedup mode found already stored under another id.
+    pub deduplicated: usize,
+    pub batches: usize,
+}
+
+/// Insert every record from `records` into `db`, `batch_size` at a time
+/// (one parallel `add_batch` per modality per batch). `progress`, if given,
+/// is told the records read so far after each batch, out of the count
+/// `records` gives up front if it knows it exactly. Records whose id is
+/// taken are handled by the duplicate-id policy (see `open`), and repeats
+/// of stored records by the dedup mode (see `dedup`, which compares a
+/// record's first vector by modality name). Stops at the
+/// first bad row; earlier batches stay inserted. Does not save.
+pub fn import<I>(db: &DB, records: I, batch_size: usize, progress: Option<&mut ProgressFn>) -> anyhow::Result<ImportReport>
+where
+    I: IntoIterator<Item = anyhow::Result<Record>>,
+{
+    let mut records = records.into_iter().fuse();
+    let total = exact_len(&records);
+    insert_batches(db, std::iter::from_fn(|| next_batch(&mut records, batch_size)), total, progress)
+}
+
+/// `import`, with `records` read on a thread of their own up to
+/// `READ_AHEAD` batches ahead of the one being inserted, so that parsing
+/// rows overlaps building the index. Same batches, report and errors.
+pub fn import_pipelined<I>(db: &DB, records: I, batch_size: usize,
+                           progress: Option<&mut ProgressFn>) -> anyhow::Result<ImportReport>
+where
+    I: IntoIterator<Item = anyhow::Result<Record>>,
+    I::IntoIter: Send,
+{
+    let mut records = records.into_iter().fuse();
+    let total = exact_len(&records);
+    std::thread::scope(|scope| {
+        let (sender, batches) = mpsc::sync_channel(READ_AHEAD);
+        scope.spawn(move || {
+            // a send fails once the inserting side has stopped
+            while let Some(batch) = next_batch(&mut records, batch_size) {
+                if sender.send(batch).is_err() { return; }
+            }
+        });
+        insert_batches(db, batches, total, progress)
+    })
+}
+
+// The record count `records` promises, if it knows it exactly.
+fn exact_len(records: &impl Iterator) -> Option<usize> {
+    match records.size_hint() {
+        (lower, Some(upper)) if lower == upper => Some(upper),
+        _ => None,
+    }
+}
+
+// The next `batch_size` records (fewer at the end), None once there are
+// none, or the first bad row's error.
+fn next_batch(records: &mut impl Iterator<Item = anyhow::Result<Record>>,
+              batch_size: usize) -> Option<anyhow::Result<Vec<Record>>> {
+    let batch: anyhow::Result<Vec<Record>> = records.take(batch_size.max(1)).collect();
+    match batch {
+        Ok(batch) if batch.is_empty() => None,
+        batch => Some(batch),
+    }
+}
+
+// Insert `batches` in turn, reporting progress after each; the first error
+// ends the import.
+fn insert_batches(db: &DB, batches: impl IntoIterator<Item = anyhow::Result<Vec<Record>>>, total: Option<usize>,
+                  progress: Option<&mut ProgressFn>) -> anyhow::Result<ImportReport> {
+    let mut report = ImportReport::default();
+    let mut reporter = Reporter::new("import", progress);
+    for batch in batches {
+        let batch = batch?;
+        let first = report.records + report.skipped + report.deduplicated + 1;
+        let (skipped, deduplicated) = insert_batch(db, &batch).map_err(|e| {
+            anyhow::anyhow!("records {}..{}: {}", first, first + batch.len() - 1, e)
+        })?;
+        report.records += batch.len() - skipped - deduplicated;
+        report.skipped += skipped;
+        report.deduplicated += deduplicated;
+        report.batches += 1;
+        reporter.report(report.records + report.skipped + report.deduplicated, total);
+    }
+    Ok(report)
+}
+
+// One modality's share of a batch, in `DB::add_batch` argument form.
+#[derive(Default)]
+struct Columns {
+    ids: Vec<u64>,
+    vecs: Vec<Vec<f32>>,
+    metas: Vec<Metadata>,
+}
+
+// Returns the number of records the duplicate-id policy and the dedup
+// mode left out.
+pub(crate) fn insert_batch(db: &DB, batch: &[Record]) -> anyhow::Result<(usize, usize)> {
+    let ids: Vec<u64> = batch.iter().map(|r| r.id).collect();
+    let keep = db.admit_all(&ids)?;
+    let batch: Vec<&Record> = batch.iter().zip(&keep).filter(|(_, &k)| k).map(|(r, _)| r).collect();
+    if db.dedup().0 != Dedup::Off {
+        return Ok((ids.len() - batch.len(), insert_one_by_one(db, &batch)?));
+    }
+    let mut by_modality: BTreeMap<&str, Columns> = BTreeMap::new();
+    for r in &batch {
+        if r.vectors.is_empty() {
+            db.put_metadata(r.id, &r.metadata)?;
+        }
+        for (modality, v) in &r.vectors {
+            let cols = by_modality.entry(modality).or_default();
+            cols.ids.push(r.id);
+            cols.vecs.push(v.clone());
+            cols.metas.push(r.metadata.clone());
+        }
+    }
+    for (modality, cols) in by_modality {
+        db.write_batch(&cols.ids, &cols.vecs, &cols.metas, modality)?;
+    }
+    for r in &batch {
+        for (name, v) in &r.sparse {
+            db.set_sparse(r.id, name, v)?;
+        }
+    }
+    Ok((ids.len() - batch.len(), 0))
+}
+
+// `insert_batch` with a dedup mode set: each record is checked against the
+// store, earlier records of the batch included, before it is written.
+// Returns the number of duplicates.
+fn insert_one_by_one(db: &DB, batch: &[&Record]) -> anyhow::Result<usize> {
+    let mut duplicates = 0;
+    for r in batch {
+        let first = r.vectors.iter().next().map(|(m, v)| (m.as_str(), v.as_slice()));
+        if db.deduplicate(r.id, first, &r.metadata)?.is_some() {
+            duplicates += 1;
+            continue;
+        }
+        if r.vectors.is_empty() {
+            db.put_metadata(r.id, &r.metadata)?;
+        }
+        for (modality, v) in &r.vectors {
+            db.write_record(r.id, v, &r.metadata, modality)?;
+        }
+        for (name, v) in &r.sparse {
+            db.set_sparse(r.id, name, v)?;
+        }
+    }
+    Ok(duplicates)
+}
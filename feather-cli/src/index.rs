@@ -0,0 +1,72 @@
+//! Optional secondary indexes on `source` (hash) and `timestamp` (ordered).
+//!
+//! The core always indexes namespace, entity and attributes; these two are
+//! switched on per file because they cost memory on every record and only
+//! pay off for selective filters. With an index on, a search constrained by
+//! that field (`--source-filter`, `--after` / `--before`, or the matching
+//! terms of a `--filter` expression) can resolve its candidates from the
+//! index and rank them exactly, instead of traversing the whole vector graph
+//! and testing each record's metadata; `planner` decides which is cheaper. A
+//! timestamp range matching too many records for that to help is left to the
+//! graph traversal.
+
+use crate::DB;
+use std::collections::BTreeMap;
+
+/// Property listing the fields with an index switched on (comma-separated).
+pub(crate) const PROPERTY_KEY: &str = "indexes";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexField {
+    Source,
+    Timestamp,
+}
+
+impl IndexField {
+    pub fn name(self) -> &'static str {
+        match self {
+            IndexField::Source => "source",
+            IndexField::Timestamp => "timestamp",
+        }
+    }
+}
+
+/// Constraints applied inside the index scan (see `DB::knn_within`), where
+/// the core can answer them from its secondary indexes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Prefilter {
+    /// Inclusive `(after, before)` range of Unix seconds.
+    pub time_range: Option<(i64, i64)>,
+    /// Exact source.
+    pub source: Option<String>,
+    /// Exact attribute values, answered from the core's attribute index.
+    pub attributes: BTreeMap<String, String>,
+    /// Keep archived records, which the core otherwise leaves out.
+    pub include_archived: bool,
+    /// Measure every record rather than traverse the vector graph.
+    pub exact: bool,
+    /// Measure every record on this many leading components, then the
+    /// nearest in full (see `SearchOptions::truncate_dim`).
+    pub truncate_dim: Option<usize>,
+    /// Apply the constraints while traversing the graph even where an index
+    /// could list the records they admit (see `planner`).
+    pub post_filter: bool,
+}
+
+impl DB {
+    /// Switch the index on `field` on or off for the whole file (all
+    /// collections). Switching on builds it from the current records; the
+    /// choice persists on `save()`.
+    pub fn set_index(&self, field: IndexField, enabled: bool) -> anyhow::Result<()> {
+        self.writable()?;
+        self.handle.set_index(field.name(), enabled)
+    }
+
+    /// Fields whose index is switched on.
+    pub fn indexes(&self) -> Vec<IndexField> {
+        let list = self.property(PROPERTY_KEY).map(|raw| String::from_utf8_lossy(&raw).into_owned());
+        [IndexField::Source, IndexField::Timestamp].into_iter()
+            .filter(|f| list.as_deref().is_some_and(|l| l.split(',').any(|n| n == f.name())))
+            .collect()
+    }
+}
@@ -0,0 +1,114 @@
+//! Storing a whole document as overlapping, linked chunks (`feather ingest`).
+//!
+//! The text is cut into chunks of at most `size` characters, each starting
+//! `overlap` characters before the previous one ended so that a sentence
+//! cut at a boundary is whole in one of them. Cuts fall on whitespace
+//! where the second half of a chunk has any. Every chunk is embedded with
+//! the file's embedder (see `embed`) and stored under consecutive ids with
+//! the document as its source and its position as attributes, and each
+//! chunk is linked to the one before it.
+
+use crate::{Inserted, DB};
+
+/// Edge from each chunk to the chunk before it.
+pub const FOLLOWS: &str = "follows";
+
+/// Attribute holding a chunk's offset in the document, in characters.
+pub const OFFSET_ATTRIBUTE: &str = "offset";
+
+/// Attribute holding a chunk's position among the document's chunks, from 0.
+pub const CHUNK_ATTRIBUTE: &str = "chunk";
+
+/// A piece of a document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chunk {
+    /// Where the text starts in the document, in characters.
+    pub offset: usize,
+    pub text: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct IngestReport {
+    /// Id of the first chunk; the rest follow it.
+    pub first_id: u64,
+    /// Chunks the document was cut into.
+    pub chunks: usize,
+    /// Chunks written.
+    pub written: usize,
+    /// Chunks the duplicate-id policy or the dedup mode left out.
+    pub skipped: usize,
+}
+
+/// Cut `text` into chunks of at most `size` characters, overlapping by
+/// `overlap`, with surrounding whitespace trimmed and blank ones dropped.
+pub fn chunk(text: &str, size: usize, overlap: usize) -> anyhow::Result<Vec<Chunk>> {
+    anyhow::ensure!(size > 0, "chunk size must be positive");
+    anyhow::ensure!(overlap < size, "overlap must be smaller than the chunk size");
+    let chars: Vec<char> = text.chars().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        let mut end = (start + size).min(chars.len());
+        if end < chars.len() {
+            if let Some(space) = (start + size / 2..end).rev().find(|&i| chars[i].is_whitespace()) {
+                end = space + 1;
+            }
+        }
+        let piece = &chars[start..end];
+        let lead = piece.iter().take_while(|c| c.is_whitespace()).count();
+        let text: String = piece[lead..].iter().collect::<String>().trim_end().to_string();
+        if !text.is_empty() {
+            chunks.push(Chunk { offset: start + lead, text });
+        }
+        if end == chars.len() { break; }
+        let mut next = end.saturating_sub(overlap).max(start + 1);
+        // start the overlap on a word if one begins inside it
+        if let Some(word) = (next..end).find(|&i| i > 0 && chars[i - 1].is_whitespace()) {
+            next = word;
+        }
+        start = next;
+    }
+    Ok(chunks)
+}
+
+impl DB {
+    /// Chunk `text`, embed the chunks with this file's embedder and store
+    /// them from id `first_id` on, with `source` as their source. Each
+    /// chunk links to the previous one (`FOLLOWS`); where the duplicate-id
+    /// policy or dedup mode leaves a chunk out, the next links to the
+    /// record kept in its place.
+    pub fn ingest(&self, text: &str, source: &str, first_id: u64, size: usize,
+                  overlap: usize) -> anyhow::Result<IngestReport> {
+        let chunks = chunk(text, size, overlap)?;
+        let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
+        let vectors = if texts.is_empty() { Vec::new() } else { self.embed(&texts)? };
+        let mut report = IngestReport { first_id, chunks: chunks.len(), ..IngestReport::default() };
+        let mut previous = None;
+        for (i, (chunk, vector)) in chunks.iter().zip(&vectors).enumerate() {
+            let id = first_id + i as u64;
+            let mut insert = self.insert(id, vector)
+                .source(source)
+                .content(&chunk.text)
+                .attribute(OFFSET_ATTRIBUTE, &chunk.offset.to_string())
+                .attribute(CHUNK_ATTRIBUTE, &i.to_string());
+            if let Some(previous) = previous {
+                insert = insert.link(previous, FOLLOWS, 1.0);
+            }
+            previous = Some(match insert.execute()? {
+                Inserted::Written => {
+                    report.written += 1;
+                    id
+                }
+                Inserted::Kept => {
+                    report.skipped += 1;
+                    id
+                }
+                Inserted::DuplicateOf(existing) => {
+                    report.skipped += 1;
+                    existing
+                }
+            });
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,214 @@
+//! Inserting one record field by field (`DB::insert`).
+//!
+//! `add_with_meta` takes every field positionally and `add_with_metadata` a
+//! whole `Metadata`; the builder lets a caller name only the fields it has:
+//!
+//! ```ignore
+//! db.insert(7, &vec).importance(0.8).source("slack").content("...").link_to(42).execute()?;
+//! ```
+//!
+//! Nothing is written until `execute`, which checks the links and parents
+//! first so that a bad one leaves no half-inserted record behind.
+
+use crate::metadata::KEY_ATTRIBUTE;
+use crate::{decay, graph, lineage, ContextType, Edge, Metadata, SparseVector, Visibility, DB};
+
+/// What `Insert::execute` did.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Inserted {
+    /// The record was written.
+    Written,
+    /// The duplicate-id policy kept the existing record with this id.
+    Kept,
+    /// The dedup mode found the same record under this other id, and
+    /// skipped the insert or merged it there.
+    DuplicateOf(u64),
+}
+
+/// A pending insert; see `DB::insert`.
+#[must_use = "nothing is inserted until `execute` is called"]
+pub struct Insert<'a> {
+    db: &'a DB,
+    id: u64,
+    vector: &'a [f32],
+    modality: String,
+    timestamp: Option<i64>,
+    meta: Metadata,
+    vectors: Vec<(String, &'a [f32])>,
+    sparse: Vec<(String, SparseVector)>,
+}
+
+impl DB {
+    /// Start inserting (or replacing) record `id` with `vector` in the
+    /// `text` modality. Unset fields take the `Metadata` defaults, except
+    /// the timestamp, which defaults to now.
+    pub fn insert<'a>(&'a self, id: u64, vector: &'a [f32]) -> Insert<'a> {
+        Insert {
+            db: self,
+            id,
+            vector,
+            modality: "text".to_string(),
+            timestamp: None,
+            meta: Metadata::default(),
+            vectors: Vec::new(),
+            sparse: Vec::new(),
+        }
+    }
+}
+
+impl<'a> Insert<'a> {
+    /// Modality the vector given to `insert` goes into.
+    pub fn modality(mut self, modality: &str) -> Self {
+        self.modality = modality.to_string();
+        self
+    }
+
+    /// Unix seconds.
+    pub fn timestamp(mut self, timestamp: i64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    pub fn importance(mut self, importance: f32) -> Self {
+        self.meta.importance = importance;
+        self
+    }
+
+    pub fn confidence(mut self, confidence: f32) -> Self {
+        self.meta.confidence = confidence;
+        self
+    }
+
+    pub fn context_type(mut self, context_type: ContextType) -> Self {
+        self.meta.context_type = context_type;
+        self
+    }
+
+    pub fn source(mut self, source: &str) -> Self {
+        self.meta.source = source.to_string();
+        self
+    }
+
+    pub fn content(mut self, content: &str) -> Self {
+        self.meta.content = content.to_string();
+        self
+    }
+
+    pub fn namespace(mut self, namespace: &str) -> Self {
+        self.meta.namespace_id = namespace.to_string();
+        self
+    }
+
+    pub fn entity(mut self, entity: &str) -> Self {
+        self.meta.entity_id = entity.to_string();
+        self
+    }
+
+    /// Scope the record to a session (see `Metadata::session`).
+    pub fn session(mut self, session: &str) -> Self {
+        self.meta.set_session(Some(session));
+        self
+    }
+
+    /// The label that owns the record (see the `access` module).
+    pub fn owner(mut self, owner: &str) -> Self {
+        self.meta.set_owner(Some(owner));
+        self
+    }
+
+    /// Who may see the record, if it has an owner.
+    pub fn visibility(mut self, visibility: Visibility) -> Self {
+        self.meta.set_visibility(visibility);
+        self
+    }
+
+    /// The string key the record is known by (see the `keys` module).
+    /// `execute` fails if another live record has it.
+    pub fn key(mut self, key: &str) -> Self {
+        self.meta.attributes.insert(KEY_ATTRIBUTE.to_string(), key.to_string());
+        self
+    }
+
+    pub fn attribute(mut self, key: &str, value: &str) -> Self {
+        self.meta.attributes.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// The free-form JSON object (see `Metadata::json`).
+    pub fn json(mut self, object: &serde_json::Map<String, serde_json::Value>) -> Self {
+        self.meta.set_json(object);
+        self
+    }
+
+    /// Forget the record this many seconds after its timestamp.
+    pub fn ttl(mut self, seconds: i64) -> Self {
+        self.meta.ttl = seconds;
+        self
+    }
+
+    /// Link to the existing record `target` (`related_to`, weight 1).
+    pub fn link_to(self, target: u64) -> Self {
+        self.link(target, graph::DEFAULT_REL_TYPE, 1.0)
+    }
+
+    /// Link to the existing record `target` with a typed, weighted edge; a
+    /// second link of the same type to the same target replaces the first.
+    pub fn link(mut self, target: u64, rel_type: &str, weight: f32) -> Self {
+        self.meta.edges.retain(|e| e.target != target || e.rel_type != rel_type);
+        self.meta.edges.push(Edge { target, rel_type: rel_type.to_string(), weight });
+        self
+    }
+
+    /// Record that this record was derived from the existing record `parent`.
+    pub fn derived_from(self, parent: u64) -> Self {
+        self.link(parent, lineage::DERIVED_FROM, 1.0)
+    }
+
+    /// Another named vector of the record.
+    pub fn vector(mut self, modality: &str, vector: &'a [f32]) -> Self {
+        self.vectors.push((modality.to_string(), vector));
+        self
+    }
+
+    /// A sparse vector of the record, under `name`.
+    pub fn sparse(mut self, name: &str, vector: SparseVector) -> Self {
+        self.sparse.push((name.to_string(), vector));
+        self
+    }
+
+    /// Write the record, subject to the duplicate-id policy (see `open`)
+    /// and the dedup mode (see `dedup`). Fails, writing nothing, if a link
+    /// target or parent does not exist, the key names another record, or a
+    /// field is out of range; fails on a dimension mismatch like
+    /// `add_with_metadata`.
+    pub fn execute(self) -> anyhow::Result<Inserted> {
+        let Insert { db, id, vector, modality, timestamp, mut meta, vectors, sparse } = self;
+        anyhow::ensure!(meta.ttl >= 0, "ttl must not be negative");
+        for edge in &meta.edges {
+            graph::check_edge(&edge.rel_type, edge.weight)?;
+            anyhow::ensure!(edge.target != id || edge.rel_type != lineage::DERIVED_FROM,
+                            "record {} cannot be derived from itself", id);
+            anyhow::ensure!(edge.target == id || db.get_metadata(edge.target).is_some_and(|m| !m.is_forgotten()),
+                            "no record {}", edge.target);
+        }
+        if let Some((name, _)) = vectors.iter().find(|(name, _)| *name == modality) {
+            anyhow::bail!("vector '{}' given twice", name);
+        }
+        if let Some(key) = meta.attributes.get(KEY_ATTRIBUTE) {
+            db.check_key(key, id)?;
+        }
+        if !db.admit(id)? { return Ok(Inserted::Kept); }
+        meta.timestamp = timestamp.unwrap_or_else(decay::now);
+        if let Some(existing) = db.deduplicate(id, Some((&modality, vector)), &meta)? {
+            return Ok(Inserted::DuplicateOf(existing));
+        }
+        db.write_record(id, vector, &meta, &modality)?;
+        for (name, vector) in &vectors {
+            db.set_vector(id, name, vector)?;
+        }
+        for (name, vector) in &sparse {
+            db.set_sparse(id, name, vector)?;
+        }
+        Ok(Inserted::Written)
+    }
+}
@@ -0,0 +1,68 @@
+//! Records known by a string key, such as a document id or a UUID
+//! (`DB::add_keyed`, `DB::id_for_key`, `feather add/get/delete --key`).
+//!
+//! Pipelines that key their data by strings need not keep their own map to
+//! u64 ids. A keyed record still has an id, allocated when its key is first
+//! added (see `autoid`); adding the key again replaces that record, subject
+//! to the duplicate-id policy. Within a collection a key names at most one
+//! live record, which `add_keyed` and `Insert::key` check.
+//!
+//! The key is kept in the `_key` attribute, so it travels with exports,
+//! merges and forks like any other, and is looked up through the core's
+//! attribute index.
+
+use crate::metadata::KEY_ATTRIBUTE;
+use crate::{Metadata, DB};
+
+impl DB {
+    /// The live record known by `key`, if any.
+    pub fn id_for_key(&self, key: &str) -> Option<u64> {
+        if key.is_empty() { return None; }
+        self.handle.ids_with_attribute(KEY_ATTRIBUTE, key).into_iter()
+            .filter_map(|iid| self.xid(iid))
+            .find(|&id| self.get_metadata(id).is_some_and(|m| !m.is_forgotten() && m.key() == Some(key)))
+    }
+
+    /// The key record `id` is known by, if it has one.
+    pub fn key_of(&self, id: u64) -> Option<String> {
+        self.get_metadata(id)?.key().map(str::to_string)
+    }
+
+    /// Insert or replace the record known by `key` in the `text` modality,
+    /// and return its id: the one `key` already names, else a newly
+    /// allocated one. Subject to the duplicate-id policy and the dedup mode
+    /// like `add_with_metadata`; when the dedup mode finds the record stored
+    /// under another id, that id is returned instead.
+    pub fn add_keyed(&self, key: &str, vec: &[f32], meta: &Metadata) -> anyhow::Result<u64> {
+        anyhow::ensure!(!key.is_empty(), "a key must not be empty");
+        let id = match self.id_for_key(key) {
+            Some(id) => id,
+            None => self.allocate_id()?,
+        };
+        let mut meta = meta.clone();
+        meta.attributes.insert(KEY_ATTRIBUTE.to_string(), key.to_string());
+        if !self.admit(id)? { return Ok(id); }
+        if let Some(existing) = self.deduplicate(id, Some(("text", vec)), &meta)? {
+            return Ok(existing);
+        }
+        self.write_record(id, vec, &meta, "text")?;
+        Ok(id)
+    }
+
+    /// Forget the record known by `key`; returns its id, or None if no live
+    /// record has the key.
+    pub fn forget_key(&self, key: &str) -> anyhow::Result<Option<u64>> {
+        let Some(id) = self.id_for_key(key) else { return Ok(None) };
+        self.forget(id)?;
+        Ok(Some(id))
+    }
+
+    // Fail if `key` names a live record other than `id`.
+    pub(crate) fn check_key(&self, key: &str, id: u64) -> anyhow::Result<()> {
+        anyhow::ensure!(!key.is_empty(), "a key must not be empty");
+        match self.id_for_key(key) {
+            Some(other) if other != id => anyhow::bail!("key '{}' already names record {}", key, other),
+            _ => Ok(()),
+        }
+    }
+}
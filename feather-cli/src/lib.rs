@@ -1,98 +1,1505 @@
-use std::ffi::{c_void, c_char};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::{c_void, c_char, CStr, CString};
 use std::path::Path;
+use std::rc::Rc;
 
-#[repr(C)]
-pub struct DB(*mut c_void);  // ← `pub`
+pub mod access;
+pub mod analysis;
+pub mod ann_index;
+pub mod archive;
+pub mod audit;
+pub mod autoid;
+pub mod autosave;
+pub mod backup;
+pub mod batch;
+pub mod bench;
+pub mod bootstrap;
+pub mod budget;
+pub mod centroid;
+pub mod cluster;
+pub mod collection;
+pub mod compress;
+pub mod consolidate;
+pub mod config;
+pub mod context_type;
+pub mod decay;
+pub mod dedup;
+pub mod device;
+pub mod doctor;
+pub mod drift;
+pub mod dupes;
+pub mod embed;
+pub mod error;
+pub mod eval;
+pub mod explain;
+pub mod export;
+pub mod feed;
+pub mod filter;
+pub mod fork;
+pub mod fsck;
+pub mod fusion;
+pub mod graph;
+pub mod hamming;
+pub mod import;
+pub mod index;
+pub mod keys;
+pub mod ingest;
+pub mod insert;
+pub mod limits;
+pub mod lineage;
+pub mod lock;
+pub mod maintenance;
+pub mod mcp;
+pub mod merge;
+pub mod metadata;
+pub mod metrics;
+pub mod migrate;
+pub mod model;
+pub mod normalize;
+pub mod open;
+pub mod planner;
+pub mod progress;
+pub mod projection;
+pub mod prompt;
+pub mod radius;
+pub mod record;
+#[cfg(feature = "arrow")]
+pub mod record_batch;
+pub mod reembed;
+pub mod replicate;
+pub mod rerank;
+pub mod repl;
+pub mod scan;
+pub mod scoring;
+pub mod search;
+pub mod seed;
+pub mod serve;
+pub mod session;
+pub mod shard;
+pub mod shutdown;
+pub mod snapshots;
+pub mod sources;
+pub mod sparse;
+pub mod stream;
+pub mod subgraph;
+pub mod tenants;
+pub mod trace;
+pub mod tune;
+pub mod txn;
+pub mod vectors;
+pub mod webhook;
+
+pub use access::{Access, Tokens, Visibility};
+pub use analysis::Outlier;
+pub use ann_index::{IndexFile, IndexFormat};
+pub use autosave::AutoSave;
+pub use bootstrap::{BootstrapReport, CheckReport};
+pub use budget::Budget;
+pub use cluster::Cluster;
+pub use compress::Compression;
+pub use consolidate::{Consolidation, Summarizer};
+pub use context_type::ContextType;
+pub use decay::{Decay, DecayReport};
+pub use dedup::{Dedup, OnMatch};
+pub use device::Device;
+pub use drift::{DistributionStats, DriftReport};
+pub use dupes::Duplicates;
+pub use embed::EmbeddingProvider;
+pub use error::{DimensionMismatch, DuplicateId, Locked, ModelMismatch, Poisoned, ReadOnly, VersionConflict};
+pub use explain::Explanation;
+pub use export::{JsonlWriter, RecordWriter};
+pub use filter::Filter;
+pub use graph::{Link, Neighbor};
+pub use import::{CsvReader, ImportReport, JsonlReader};
+pub use index::IndexField;
+pub use ingest::IngestReport;
+pub use insert::{Insert, Inserted};
+pub use limits::{Limits, Rate};
+pub use lineage::Lineage;
+pub use maintenance::{Maintenance, MaintenanceReport};
+pub use merge::{ForkMergeReport, ForkStrategy, MergePolicy, MergeReport};
+pub use metadata::{Edge, Metadata};
+pub use open::{OnDuplicate, OpenOptions};
+pub use planner::{Plan, QueryPlan};
+pub use progress::{Progress, ProgressFn};
+pub use projection::Projection;
+pub use prompt::{Selected, Selection};
+pub use record::Record;
+pub use rerank::Reranker;
+pub use scan::{ScanPage, SortBy};
+pub use scoring::ScoringPolicy;
+pub use search::SearchOptions;
+pub use sparse::SparseVector;
+pub use stream::SearchIter;
+pub use subgraph::Subgraph;
+pub use txn::Transaction;
+
+use collection::Scope;
+use index::Prefilter;
+use metadata::{CMetadata, RawMetadata};
+use progress::{ProgressCb, Reporter};
+use trace::{Level, Span};
+
+/// A handle on a feather file, or on one named collection inside it (see
+/// `DB::collection`). Ids and modality names are always collection-local.
+pub struct DB {
+    ptr: *mut c_void,
+    handle: Rc<Handle>,
+    // None = the default collection
+    scope: Option<Scope>,
+}
+
+// The core DB plus wrapper state, shared by every collection handle of one
+// file. Modality keys are internal (`<collection>::<modality>`) names.
+struct Handle {
+    ptr: *mut c_void,
+    // modality → projection applied to every vector/query entering it
+    projections: RefCell<HashMap<String, Projection>>,
+    // modality → running stats of the queries searched against it (drift)
+    query_stats: RefCell<HashMap<String, DistributionStats>>,
+    query_stats_dirty: Cell<bool>,
+    // collection name → id-space index
+    collections: RefCell<BTreeMap<String, u16>>,
+    // the snapshot this file is a fork of; None for an ordinary store
+    fork: Option<fork::Base>,
+    on_duplicate: Cell<OnDuplicate>,
+    dedup: Cell<(Dedup, OnMatch)>,
+    // content hash → internal ids, built on first use (see `dedup`)
+    content_index: RefCell<Option<HashMap<u64, Vec<u64>>>>,
+    // unit-normalize vectors and queries (see `normalize`)
+    normalize: Cell<bool>,
+    // store vectors as bits, compared by Hamming distance (see `hamming`)
+    binary: Cell<bool>,
+    // turns text into vectors (see `embed`)
+    embedder: RefCell<Option<Rc<dyn embed::EmbeddingProvider>>>,
+    // this process's hold on the backing file (see `lock`); None in memory
+    lock: RefCell<Option<lock::FileLock>>,
+    // opened with `OpenOptions::read_only`: detached, and mutations refused
+    read_only: Cell<bool>,
+    // a transaction failed part-way: detached, and mutations refused (see `txn`)
+    poisoned: Cell<bool>,
+    // every shard of a sharded store, `ptr` being the first (see `shard`);
+    // empty for a single file
+    shards: Vec<*mut c_void>,
+    // the change log beside the file (see `audit`)
+    audit: RefCell<audit::Log>,
+    // channels the changes are sent on (see `feed`)
+    subscribers: RefCell<feed::Subscribers>,
+    // when to save without being asked, and the changes since the last save
+    autosave: RefCell<autosave::State>,
+    // housekeeping without being asked (see `maintenance`)
+    maintenance: RefCell<maintenance::State>,
+    // what the indexes are built from (see `seed`)
+    seed: Cell<Option<u64>>,
+}
 
 extern "C" {
+    fn feather_last_error() -> *const c_char;
     fn feather_open(path: *const c_char, dim: usize) -> *mut c_void;
+    fn feather_open_in_memory(dim: usize) -> *mut c_void;
+    fn feather_persist_to(db: *mut c_void, path: *const c_char) -> i32;
+    fn feather_is_in_memory(db: *mut c_void) -> i32;
+    fn feather_detach(db: *mut c_void);
     fn feather_add(db: *mut c_void, id: u64, vec: *const f32, len: usize);
     fn feather_add_with_meta(db: *mut c_void, id: u64, vec: *const f32, len: usize,
                               timestamp: i64, importance: f32, context_type: u8,
                               source: *const c_char, content: *const c_char, modality: *const c_char);
     fn feather_link(db: *mut c_void, from_id: u64, to_id: u64);
+    fn feather_link_typed(db: *mut c_void, from_id: u64, to_id: u64, rel_type: *const c_char, weight: f32);
+    fn feather_unlink(db: *mut c_void, from_id: u64, to_id: u64, rel_type: *const c_char) -> usize;
     fn feather_touch(db: *mut c_void, id: u64);
+    fn feather_forget(db: *mut c_void, id: u64);
     fn feather_search(db: *mut c_void, query: *const f32, len: usize, k: usize,
                       out_ids: *mut u64, out_dists: *mut f32, modality: *const c_char);
     fn feather_search_with_filter(db: *mut c_void, query: *const f32, len: usize, k: usize,
                                    type_filter: u8, source_filter: *const c_char,
                                    out_ids: *mut u64, out_dists: *mut f32, modality: *const c_char);
     fn feather_save(db: *mut c_void);
+    fn feather_compact(db: *mut c_void, cb: Option<ProgressCb>, ctx: *mut c_void) -> usize;
+    fn feather_forget_expired(db: *mut c_void) -> usize;
     fn feather_close(db: *mut c_void);
+    fn feather_dim(db: *mut c_void, modality: *const c_char) -> usize;
+    fn feather_register_modality(db: *mut c_void, modality: *const c_char, dim: usize) -> i32;
+    fn feather_get_all_ids(db: *mut c_void, modality: *const c_char, out: *mut u64, cap: usize) -> usize;
+    fn feather_get_vector(db: *mut c_void, id: u64, modality: *const c_char,
+                          out: *mut f32, cap: usize) -> usize;
+    fn feather_set_property(db: *mut c_void, key: *const c_char, val: *const c_char, len: usize);
+    fn feather_get_property(db: *mut c_void, key: *const c_char, out: *mut c_char, cap: usize) -> i64;
+    fn feather_remove_property(db: *mut c_void, key: *const c_char) -> i32;
+    fn feather_reproject(db: *mut c_void, modality: *const c_char, matrix: *const f32,
+                         bias: *const f32, in_dim: usize, out_dim: usize,
+                         cb: Option<ProgressCb>, ctx: *mut c_void) -> i64;
+    fn feather_knn(db: *mut c_void, query: *const f32, len: usize, k: usize, modality: *const c_char,
+                   time_range: *const i64, source: *const c_char, attributes: *const *const c_char,
+                   attribute_count: usize, include_archived: i32, exact: i32, truncate_dim: usize,
+                   shortlist: usize, post_filter: i32, out_ids: *mut u64, out_dists: *mut f32) -> i64;
+    fn feather_set_index(db: *mut c_void, field: *const c_char, enabled: i32) -> i32;
+    fn feather_set_ef(db: *mut c_void, ef: usize, modality: *const c_char) -> i32;
+    fn feather_warm(db: *mut c_void) -> i64;
+    fn feather_bm25(db: *mut c_void, query: *const c_char, k: usize, include_archived: i32,
+                    out_ids: *mut u64, out_scores: *mut f32) -> i64;
+    fn feather_set_attribute(db: *mut c_void, id: u64, key: *const c_char, value: *const c_char) -> i32;
+    fn feather_get_metadata(db: *mut c_void, id: u64) -> *const RawMetadata;
+    fn feather_metadata_free(meta: *const RawMetadata);
+    fn feather_put_metadata(db: *mut c_void, id: u64, meta: *const RawMetadata);
+    fn feather_add_with_metadata(db: *mut c_void, id: u64, vec: *const f32, len: usize,
+                                 meta: *const RawMetadata, modality: *const c_char) -> i32;
+    fn feather_add_batch(db: *mut c_void, ids: *const u64, vecs: *const f32, n: usize, dim: usize,
+                         metas: *const *const RawMetadata, modality: *const c_char) -> i32;
+    fn feather_all_ids(db: *mut c_void, out: *mut u64, cap: usize) -> usize;
+    fn feather_ids_with_attribute(db: *mut c_void, key: *const c_char, value: *const c_char,
+                                  out: *mut u64, cap: usize) -> usize;
+    fn feather_modality_names(db: *mut c_void, out: *mut c_char, cap: usize) -> usize;
+    fn feather_set_sparse(db: *mut c_void, id: u64, name: *const c_char, dims: *const u32,
+                          weights: *const f32, nnz: usize) -> i32;
+    fn feather_get_sparse(db: *mut c_void, id: u64, name: *const c_char, out_dims: *mut u32,
+                          out_weights: *mut f32, cap: usize) -> usize;
+    fn feather_sparse_search(db: *mut c_void, name: *const c_char, dims: *const u32, weights: *const f32,
+                             nnz: usize, k: usize, include_archived: i32, out_ids: *mut u64, out_scores: *mut f32) -> i64;
+    fn feather_sparse_names(db: *mut c_void, out: *mut c_char, cap: usize) -> usize;
+    fn feather_get_incoming(db: *mut c_void, id: u64, out: *mut u64, cap: usize) -> usize;
+}
+
+// Borrow an optional C string as a (possibly null) pointer. The CString must
+// outlive the FFI call, so never `map_or` it by value — that drops it first.
+fn opt_ptr(s: &Option<CString>) -> *const c_char {
+    s.as_ref().map_or(std::ptr::null(), |s| s.as_ptr())
+}
+
+// Split a buffer of NUL-terminated names.
+fn split_names(buf: &[u8]) -> Vec<String> {
+    buf.split(|&b| b == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .collect()
+}
+
+// The modality index names of one core.
+fn modality_names(core: *mut c_void) -> Vec<String> {
+    let n = unsafe { feather_modality_names(core, std::ptr::null_mut(), 0) };
+    let mut buf = vec![0u8; n];
+    unsafe { feather_modality_names(core, buf.as_mut_ptr().cast(), n) };
+    split_names(&buf)
+}
+
+// Every name in `lists`, once, in order of first appearance.
+fn union(lists: impl Iterator<Item = Vec<String>>) -> Vec<String> {
+    let mut names = Vec::new();
+    for name in lists.flatten() {
+        if !names.contains(&name) { names.push(name); }
+    }
+    names
+}
+
+fn c_str(s: &str) -> anyhow::Result<CString> {
+    CString::new(s).map_err(|_| anyhow::anyhow!("string contains a NUL byte: {:?}", s))
+}
+
+// A `Prefilter`'s constraints as the core takes them: a time range (or
+// null), a source (or null) and alternating attribute keys and values.
+struct CPrefilter {
+    time_range: Option<[i64; 2]>,
+    source: Option<CString>,
+    attributes: Vec<*const c_char>,
+    _strings: Vec<CString>,
+}
+
+impl CPrefilter {
+    fn new(prefilter: &Prefilter) -> anyhow::Result<CPrefilter> {
+        let strings = prefilter.attributes.iter()
+            .flat_map(|(key, value)| [c_str(key), c_str(value)])
+            .collect::<anyhow::Result<Vec<CString>>>()?;
+        Ok(CPrefilter {
+            time_range: prefilter.time_range.map(|(after, before)| [after, before]),
+            source: prefilter.source.as_deref().map(c_str).transpose()?,
+            attributes: strings.iter().map(|s| s.as_ptr()).collect(),
+            _strings: strings,
+        })
+    }
+
+    fn range(&self) -> *const i64 {
+        self.time_range.as_ref().map_or(std::ptr::null(), |r| r.as_ptr())
+    }
+}
+
+fn last_error() -> anyhow::Error {
+    let msg = unsafe { CStr::from_ptr(feather_last_error()) };
+    anyhow::anyhow!("{}", msg.to_string_lossy())
+}
+
+impl Handle {
+    // Wrap a core DB, restoring the wrapper state kept in its properties.
+    fn new(ptr: *mut c_void) -> anyhow::Result<Self> {
+        let mut handle = Handle {
+            ptr,
+            projections: RefCell::new(HashMap::new()),
+            query_stats: RefCell::new(HashMap::new()),
+            query_stats_dirty: Cell::new(false),
+            collections: RefCell::new(BTreeMap::new()),
+            fork: None,
+            on_duplicate: Cell::new(OnDuplicate::default()),
+            dedup: Cell::new((Dedup::default(), OnMatch::default())),
+            content_index: RefCell::new(None),
+            normalize: Cell::new(false),
+            binary: Cell::new(false),
+            embedder: RefCell::new(None),
+            lock: RefCell::new(None),
+            read_only: Cell::new(false),
+            poisoned: Cell::new(false),
+            shards: Vec::new(),
+            audit: RefCell::new(audit::Log::default()),
+            subscribers: RefCell::new(feed::Subscribers::default()),
+            autosave: RefCell::new(autosave::State::default()),
+            maintenance: RefCell::new(maintenance::State::default()),
+            seed: Cell::new(None),
+        };
+        let corrupt = |key: &str| anyhow::anyhow!("the '{}' property does not decode", key);
+        if let Some(raw) = handle.property(projection::PROPERTY_KEY) {
+            handle.projections.replace(projection::decode(&raw).ok_or_else(|| corrupt(projection::PROPERTY_KEY))?);
+        }
+        if let Some(raw) = handle.property(drift::PROPERTY_KEY) {
+            // a corrupt accumulator only loses drift history; start afresh
+            handle.query_stats.replace(drift::decode(&raw).unwrap_or_default());
+        }
+        handle.normalize.set(handle.property(normalize::PROPERTY_KEY).is_some());
+        handle.binary.set(handle.property(hamming::PROPERTY_KEY).is_some_and(|v| hamming::is_hamming(&v)));
+        if let Some(raw) = handle.property(collection::PROPERTY_KEY) {
+            handle.collections.replace(collection::decode(&raw).ok_or_else(|| corrupt(collection::PROPERTY_KEY))?);
+        }
+        if let Some(raw) = handle.property(fork::PROPERTY_KEY) {
+            let path = handle.path();
+            handle.fork = Some(fork::Base::open(&raw, handle.own_dim(None), path.as_deref().map(Path::new))?);
+        }
+        Ok(handle)
+    }
+
+    fn set_property(&self, key: &str, value: &[u8]) {
+        let Ok(c_key) = CString::new(key) else { return };
+        unsafe { feather_set_property(self.ptr, c_key.as_ptr(), value.as_ptr().cast(), value.len()) }
+    }
+
+    fn property(&self, key: &str) -> Option<Vec<u8>> {
+        let c_key = CString::new(key).ok()?;
+        let n = unsafe { feather_get_property(self.ptr, c_key.as_ptr(), std::ptr::null_mut(), 0) };
+        let mut buf = vec![0u8; usize::try_from(n).ok()?];
+        unsafe { feather_get_property(self.ptr, c_key.as_ptr(), buf.as_mut_ptr().cast(), buf.len()) };
+        Some(buf)
+    }
+
+    // Write in-memory wrapper state into the properties before a save.
+    fn flush_properties(&self) {
+        if self.query_stats_dirty.replace(false) {
+            self.set_property(drift::PROPERTY_KEY, &drift::encode(&self.query_stats.borrow()));
+        }
+        if let Some(base) = self.fork.as_ref().filter(|b| b.dirty.replace(false)) {
+            self.set_property(fork::PROPERTY_KEY, &base.encode());
+        }
+    }
+
+    // The primitives below see this file only — all its shards, if it has
+    // them; the overlay-aware versions that also read through to a fork's
+    // base live in `fork`.
+
+    // Every internal id, regardless of collection.
+    fn own_all_ids(&self) -> Vec<u64> {
+        self.cores().iter().flat_map(|&core| {
+            let n = unsafe { feather_all_ids(core, std::ptr::null_mut(), 0) };
+            let mut ids = vec![0u64; n];
+            let n = unsafe { feather_all_ids(core, ids.as_mut_ptr(), n) };
+            ids.truncate(n);
+            ids
+        }).collect()
+    }
+
+    // Internal ids of the live records whose attribute `key` is `value`,
+    // regardless of collection.
+    fn own_ids_with_attribute(&self, key: &str, value: &str) -> Vec<u64> {
+        let (Ok(c_key), Ok(c_value)) = (CString::new(key), CString::new(value)) else { return Vec::new() };
+        self.cores().iter().flat_map(|&core| {
+            let n = unsafe { feather_ids_with_attribute(core, c_key.as_ptr(), c_value.as_ptr(), std::ptr::null_mut(), 0) };
+            let mut ids = vec![0u64; n];
+            let n = unsafe { feather_ids_with_attribute(core, c_key.as_ptr(), c_value.as_ptr(), ids.as_mut_ptr(), n) };
+            ids.truncate(n);
+            ids
+        }).collect()
+    }
+
+    // Every modality index name, regardless of collection.
+    fn own_modalities(&self) -> Vec<String> {
+        union(self.cores().iter().map(|&core| modality_names(core)))
+    }
+
+    // Every sparse vector set name, regardless of collection.
+    fn own_sparse_names(&self) -> Vec<String> {
+        union(self.cores().iter().map(|&core| {
+            let n = unsafe { feather_sparse_names(core, std::ptr::null_mut(), 0) };
+            let mut buf = vec![0u8; n];
+            unsafe { feather_sparse_names(core, buf.as_mut_ptr().cast(), n) };
+            split_names(&buf)
+        }))
+    }
+
+    // Metadata by internal id, edges internal too.
+    fn own_meta(&self, id: u64) -> Option<Metadata> {
+        let raw = unsafe { feather_get_metadata(self.core(id), id) };
+        if raw.is_null() { return None; }
+        let meta = unsafe { Metadata::from_raw(&*raw) };
+        unsafe { feather_metadata_free(raw) };
+        Some(meta)
+    }
+
+    // Ids of the records whose metadata holds an edge to `id`, per the core's
+    // reverse index (which may still list forgotten ones).
+    fn own_incoming(&self, id: u64) -> Vec<u64> {
+        self.cores().iter().flat_map(|&core| {
+            let n = unsafe { feather_get_incoming(core, id, std::ptr::null_mut(), 0) };
+            let mut ids = vec![0u64; n];
+            let n = unsafe { feather_get_incoming(core, id, ids.as_mut_ptr(), n) };
+            ids.truncate(n);
+            ids
+        }).collect()
+    }
+
+    fn own_dim(&self, modality: Option<&str>) -> usize {
+        // a shard that holds no vectors of the modality yet has no dim for it
+        let core = match self.cores() {
+            [core] => *core,
+            cores => {
+                let name = modality.unwrap_or("text");
+                cores.iter().copied().find(|&c| modality_names(c).iter().any(|m| m == name)).unwrap_or(self.ptr)
+            }
+        };
+        let c_modality = modality.and_then(|m| CString::new(m).ok());
+        unsafe { feather_dim(core, opt_ptr(&c_modality)) }
+    }
+
+    fn own_ids(&self, modality: Option<&str>) -> Vec<u64> {
+        let c_modality = modality.and_then(|m| CString::new(m).ok());
+        self.cores().iter().flat_map(|&core| {
+            let n = unsafe { feather_get_all_ids(core, opt_ptr(&c_modality), std::ptr::null_mut(), 0) };
+            let mut ids = vec![0u64; n];
+            let n = unsafe { feather_get_all_ids(core, opt_ptr(&c_modality), ids.as_mut_ptr(), n) };
+            ids.truncate(n);
+            ids
+        }).collect()
+    }
+
+    fn own_vector(&self, id: u64, modality: Option<&str>) -> Option<Vec<f32>> {
+        let c_modality = modality.and_then(|m| CString::new(m).ok());
+        let core = self.core(id);
+        let mut vec = vec![0f32; self.own_dim(modality)];
+        let n = unsafe {
+            feather_get_vector(core, id, opt_ptr(&c_modality), vec.as_mut_ptr(), vec.len())
+        };
+        if n == 0 { return None; }
+        if n > vec.len() {
+            vec.resize(n, 0.0);
+            unsafe { feather_get_vector(core, id, opt_ptr(&c_modality), vec.as_mut_ptr(), n) };
+        }
+        vec.truncate(n);
+        Some(vec)
+    }
+
+    // Scored search as (id, score) pairs; always `k` of them, unfilled
+    // slots being (0, 0.0). Bumps the recall counts of the hits.
+    fn own_search(&self, query: &[f32], k: usize, modality: Option<&str>,
+                  type_filter: Option<u8>, source_filter: Option<&str>) -> Vec<(u64, f32)> {
+        let mut ids = vec![0u64; k];
+        let mut dists = vec![0f32; k];
+        let c_modality = modality.and_then(|m| CString::new(m).ok());
+        if type_filter.is_none() && source_filter.is_none() {
+            unsafe {
+                feather_search(
+                    self.ptr, query.as_ptr(), query.len(), k,
+                    ids.as_mut_ptr(), dists.as_mut_ptr(),
+                    opt_ptr(&c_modality)
+                )
+            };
+        } else {
+            let c_source = source_filter.and_then(|s| CString::new(s).ok());
+            unsafe {
+                feather_search_with_filter(
+                    self.ptr, query.as_ptr(), query.len(), k,
+                    type_filter.unwrap_or(context_type::ANY_CODE),
+                    opt_ptr(&c_source),
+                    ids.as_mut_ptr(), dists.as_mut_ptr(),
+                    opt_ptr(&c_modality)
+                )
+            };
+        }
+        ids.into_iter().zip(dists).collect()
+    }
+
+    fn own_knn(&self, query: &[f32], k: usize, modality: &str,
+               prefilter: &Prefilter) -> anyhow::Result<Vec<(u64, f32)>> {
+        let c_modality = c_str(modality)?;
+        let c_prefilter = CPrefilter::new(prefilter)?;
+        let shortlist = k.saturating_mul(search::TRUNCATED_CANDIDATE_FACTOR);
+        let mut hits = Vec::new();
+        for &core in self.cores() {
+            let mut ids = vec![0u64; k];
+            let mut dists = vec![0f32; k];
+            let n = unsafe {
+                feather_knn(core, query.as_ptr(), query.len(), k, c_modality.as_ptr(),
+                            c_prefilter.range(), opt_ptr(&c_prefilter.source), c_prefilter.attributes.as_ptr(),
+                            prefilter.attributes.len(), prefilter.include_archived as i32, prefilter.exact as i32,
+                            prefilter.truncate_dim.unwrap_or(0), shortlist, prefilter.post_filter as i32,
+                            ids.as_mut_ptr(), dists.as_mut_ptr())
+            };
+            if n < 0 { return Err(last_error()); }
+            hits.extend(ids.into_iter().zip(dists).take(n as usize));
+        }
+        hits.sort_by(|a, b| a.1.total_cmp(&b.1));
+        hits.truncate(k);
+        Ok(hits)
+    }
+
+    fn own_bm25(&self, text: &str, k: usize, include_archived: bool) -> anyhow::Result<Vec<(u64, f32)>> {
+        let c_text = c_str(text)?;
+        let mut hits = Vec::new();
+        for &core in self.cores() {
+            let mut ids = vec![0u64; k];
+            let mut scores = vec![0f32; k];
+            let n = unsafe {
+                feather_bm25(core, c_text.as_ptr(), k, include_archived as i32, ids.as_mut_ptr(), scores.as_mut_ptr())
+            };
+            if n < 0 { return Err(last_error()); }
+            hits.extend(ids.into_iter().zip(scores).take(n as usize));
+        }
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1));
+        hits.truncate(k);
+        Ok(hits)
+    }
+
+    fn own_sparse(&self, id: u64, name: &str) -> Option<SparseVector> {
+        let c_name = CString::new(name).ok()?;
+        let core = self.core(id);
+        let n = unsafe { feather_get_sparse(core, id, c_name.as_ptr(), std::ptr::null_mut(), std::ptr::null_mut(), 0) };
+        if n == 0 { return None; }
+        let (mut indices, mut values) = (vec![0u32; n], vec![0f32; n]);
+        let n = unsafe { feather_get_sparse(core, id, c_name.as_ptr(), indices.as_mut_ptr(), values.as_mut_ptr(), n) };
+        indices.truncate(n);
+        values.truncate(n);
+        Some(SparseVector { indices, values })
+    }
+
+    fn own_sparse_knn(&self, query: &SparseVector, k: usize, name: &str,
+                      include_archived: bool) -> anyhow::Result<Vec<(u64, f32)>> {
+        let c_name = c_str(name)?;
+        let mut hits = Vec::new();
+        for &core in self.cores() {
+            let mut ids = vec![0u64; k];
+            let mut scores = vec![0f32; k];
+            let n = unsafe {
+                feather_sparse_search(core, c_name.as_ptr(), query.indices.as_ptr(), query.values.as_ptr(),
+                                      query.len(), k, include_archived as i32, ids.as_mut_ptr(), scores.as_mut_ptr())
+            };
+            if n < 0 { return Err(last_error()); }
+            hits.extend(ids.into_iter().zip(scores).take(n as usize));
+        }
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1));
+        hits.truncate(k);
+        Ok(hits)
+    }
+
+    fn set_index(&self, field: &str, enabled: bool) -> anyhow::Result<()> {
+        let c_field = c_str(field)?;
+        for &core in self.cores() {
+            if unsafe { feather_set_index(core, c_field.as_ptr(), enabled as i32) } != 0 { return Err(last_error()); }
+        }
+        Ok(())
+    }
+
+    // HNSW search beam width of one modality or all, the fork's base too.
+    fn set_ef(&self, ef: usize, modality: Option<&str>) -> anyhow::Result<()> {
+        let c_modality = modality.map(c_str).transpose()?;
+        // an unknown modality is an error unless some shard holds it
+        let failed = self.cores().iter().filter(|&&core| unsafe { feather_set_ef(core, ef, opt_ptr(&c_modality)) } != 0).count();
+        if failed == self.cores().len() { return Err(last_error()); }
+        if let Some(base) = &self.fork { base.set_ef(ef, modality); }
+        Ok(())
+    }
+
+    // Read every page of this file's cores, and of a fork's base, once;
+    // the bytes read.
+    fn warm(&self) -> anyhow::Result<u64> {
+        let mut bytes = 0;
+        for &core in self.cores() {
+            let n = unsafe { feather_warm(core) };
+            bytes += u64::try_from(n).map_err(|_| last_error())?;
+        }
+        if let Some(base) = &self.fork { bytes += base.warm()?; }
+        Ok(bytes)
+    }
+
+    // Whether an internal modality name belongs to a registered collection.
+    fn in_collection(&self, modality: &str) -> bool {
+        modality.split_once(collection::MODALITY_SEP)
+            .is_some_and(|(name, _)| self.collections.borrow().contains_key(name))
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        self.flush_properties();   // the core saves on close
+        for &core in self.cores() {
+            unsafe { feather_close(core) }
+        }
+    }
 }
 
 impl DB {
+    /// Open (or create) the store at `path`, locked for writing (see
+    /// `lock`); None if that fails, and `OpenOptions::open` says why.
+    /// `OpenOptions` can also open without creating.
     pub fn open(path: &Path, dim: usize) -> Option<Self> {
-        let c_path = std::ffi::CString::new(path.to_str()?).ok()?;
+        OpenOptions::new().dim(dim).create(true).open(path).ok()
+    }
+
+    // `open` without taking the lock.
+    pub(crate) fn open_unlocked(path: &Path, dim: usize) -> anyhow::Result<Self> {
+        let mut span = Span::new(Level::Info, "feather::open");
+        span.record_str("path", &path.to_string_lossy());
+        let c_path = c_str(path.to_str().ok_or_else(|| anyhow::anyhow!("path is not UTF-8: {:?}", path))?)?;
         let ptr = unsafe { feather_open(c_path.as_ptr(), dim) };
-        if ptr.is_null() { None } else { Some(DB(ptr)) }
+        anyhow::ensure!(!ptr.is_null(), "Open failed: {:?}: {}", path, last_error());
+        let db = Self::wrap(ptr).map_err(|e| anyhow::anyhow!("cannot open {:?}: {:#}", path, e))?;
+        if span.is_enabled() {
+            db.record_stats(&mut span);
+        }
+        Ok(db)
     }
 
-    pub fn add(&self, id: u64, vec: &[f32]) {
-        unsafe { feather_add(self.0, id, vec.as_ptr(), vec.len()) }
+    // Record and vector counts of the store, per modality, on `span`.
+    fn record_stats(&self, span: &mut Span) {
+        span.record("records", self.all_ids().len());
+        for modality in self.modalities() {
+            span.record(&format!("{}.vectors", modality), self.ids(&modality).len());
+            span.record(&format!("{}.dim", modality), self.dim(&modality));
+        }
     }
 
-    pub fn add_with_meta(&self, id: u64, vec: &[f32], timestamp: i64, importance: f32, context_type: u8, 
-                         source: Option<&str>, content: Option<&str>, modality: Option<&str>) {
-        let c_source = source.and_then(|s| std::ffi::CString::new(s).ok());
-        let c_content = content.and_then(|s| std::ffi::CString::new(s).ok());
-        let c_modality = modality.and_then(|s| std::ffi::CString::new(s).ok());
-        
-        unsafe {
-            feather_add_with_meta(
-                self.0, id, vec.as_ptr(), vec.len(),
-                timestamp, importance, context_type,
-                c_source.map_or(std::ptr::null(), |s| s.as_ptr()),
-                c_content.map_or(std::ptr::null(), |s| s.as_ptr()),
-                c_modality.map_or(std::ptr::null(), |s| s.as_ptr())
-            )
+    /// An ephemeral store with no backing file — for tests, short-lived
+    /// sessions and caches. Nothing touches the filesystem (`save` is a no-op)
+    /// until `persist_to` gives it a path. `OpenOptions::open_in_memory`
+    /// makes one with a duplicate-id policy, dedup mode or normalization.
+    pub fn in_memory(dim: usize) -> Self {
+        let ptr = unsafe { feather_open_in_memory(dim) };
+        assert!(!ptr.is_null(), "could not allocate an in-memory store");
+        Self::wrap(ptr).expect("an empty store has no properties to decode")
+    }
+
+    /// Write the whole store (every collection) to `path` and keep it as the
+    /// backing file from now on; later writes are WAL-logged there. On a
+    /// file-backed store this is a "save as".
+    pub fn persist_to(&self, path: &Path) -> anyhow::Result<()> {
+        self.writable()?;
+        self.unsharded("persisted to one file")?;
+        let path = path.to_str().ok_or_else(|| anyhow::anyhow!("path is not UTF-8: {:?}", path))?;
+        let c_path = c_str(path)?;
+        let lock = lock::FileLock::acquire(Path::new(path), lock::LockMode::Exclusive)?;
+        self.handle.flush_properties();
+        if unsafe { feather_persist_to(self.ptr, c_path.as_ptr()) } != 0 { return Err(last_error()); }
+        self.handle.lock.replace(Some(lock));
+        self.attach_audit(Path::new(path))
+    }
+
+    /// Whether this handle was opened with `OpenOptions::read_only(true)`.
+    pub fn is_read_only(&self) -> bool { self.handle.read_only.get() }
+
+    // Fail with `ReadOnly` on a read-only handle; every mutation checks.
+    pub(crate) fn writable(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(!self.is_read_only(), ReadOnly);
+        anyhow::ensure!(!self.handle.poisoned.get(), Poisoned);
+        Ok(())
+    }
+
+    /// True while the store has no backing file.
+    pub fn is_in_memory(&self) -> bool {
+        unsafe { feather_is_in_memory(self.ptr) != 0 }
+    }
+
+    fn wrap(ptr: *mut c_void) -> anyhow::Result<Self> {
+        Ok(DB { ptr, handle: Rc::new(Handle::new(ptr)?), scope: None })
+    }
+
+    /// A handle on the named collection of this file, registering it if it
+    /// does not exist yet. Collections have independent id spaces (ids up to
+    /// `collection::MAX_ID`) and their own modality indexes; the handle
+    /// supports the full `DB` API and shares the file with this one. Once a
+    /// collection is registered, the default one refuses ids above
+    /// `collection::MAX_ID` too.
+    pub fn collection(&self, name: &str) -> anyhow::Result<DB> {
+        collection::validate_name(name)?;
+        let existing = self.handle.collections.borrow().get(name).copied();
+        let index = match existing {
+            Some(index) => index,
+            None => {
+                self.writable()?;
+                // skip indexes already taken by raw 64-bit ids in the file
+                let mut used: HashSet<u64> = self.handle.all_ids().iter()
+                    .map(|id| id >> collection::ID_BITS)
+                    .collect();
+                used.extend(self.handle.collections.borrow().values().map(|&i| i as u64));
+                let index = (1..=u16::MAX).find(|i| !used.contains(&(*i as u64)))
+                    .ok_or_else(|| anyhow::anyhow!("no free collection slot"))?;
+                let mut collections = self.handle.collections.borrow_mut();
+                collections.insert(name.to_string(), index);
+                self.handle.set_property(collection::PROPERTY_KEY, &collection::encode(&collections));
+                index
+            }
+        };
+        Ok(DB { ptr: self.ptr, handle: Rc::clone(&self.handle), scope: Some(Scope { name: name.to_string(), index }) })
+    }
+
+    pub fn has_collection(&self, name: &str) -> bool {
+        self.handle.collections.borrow().contains_key(name)
+    }
+
+    /// Names of the registered collections, sorted.
+    pub fn collections(&self) -> Vec<String> {
+        self.handle.collections.borrow().keys().cloned().collect()
+    }
+
+    /// The collection this handle is scoped to; None for the default one.
+    pub fn collection_name(&self) -> Option<&str> {
+        self.scope.as_ref().map(|s| s.name.as_str())
+    }
+
+    // Internal id of a collection-local id. Unscoped, an id above
+    // `collection::MAX_ID` is refused once a collection is registered: its
+    // top bits could name that collection's records.
+    fn iid(&self, id: u64) -> anyhow::Result<u64> {
+        match &self.scope {
+            Some(s) => s.internal_id(id),
+            None => {
+                anyhow::ensure!(id <= collection::MAX_ID || self.handle.collections.borrow().is_empty(),
+                                "id {} is too large for a file with collections (max {})", id, collection::MAX_ID);
+                Ok(id)
+            }
         }
     }
 
-    pub fn link(&self, from_id: u64, to_id: u64) {
-        unsafe { feather_link(self.0, from_id, to_id) }
+    // Collection-local id of an internal id, if it belongs to this handle.
+    fn xid(&self, internal: u64) -> Option<u64> {
+        match &self.scope {
+            Some(s) => s.external_id(internal),
+            None => {
+                let index = internal >> collection::ID_BITS;
+                let taken = index != 0
+                    && self.handle.collections.borrow().values().any(|&i| i as u64 == index);
+                (!taken).then_some(internal)
+            }
+        }
     }
 
-    pub fn touch(&self, id: u64) {
-        unsafe { feather_touch(self.0, id) }
+    // Internal name of a modality (None = the core's default, "text").
+    fn mname<'a>(&self, modality: Option<&'a str>) -> Option<Cow<'a, str>> {
+        match &self.scope {
+            Some(s) => Some(Cow::Owned(s.modality(modality.unwrap_or("text")))),
+            None => modality.map(Cow::Borrowed),
+        }
     }
 
-    pub fn search(&self, query: &[f32], k: usize, modality: Option<&str>) -> (Vec<u64>, Vec<f32>) {
-        let mut ids = vec![0u64; k];
-        let mut dists = vec![0f32; k];
-        let c_modality = modality.and_then(|s| std::ffi::CString::new(s).ok());
-        unsafe {
-            feather_search(
-                self.0, query.as_ptr(), query.len(), k, 
-                ids.as_mut_ptr(), dists.as_mut_ptr(),
-                c_modality.map_or(std::ptr::null(), |s| s.as_ptr())
-            )
+    // `meta` as written over the record with internal id `iid`: edge
+    // targets mapped to internal ids, and the version after the stored one.
+    fn meta_in(&self, iid: u64, meta: &Metadata) -> anyhow::Result<Metadata> {
+        let mut meta = meta.clone();
+        if self.scope.is_some() {
+            for edge in &mut meta.edges { edge.target = self.iid(edge.target)?; }
+        }
+        meta.set_version(self.stored_version(iid) + 1);
+        Ok(meta)
+    }
+
+    // Version of the record with internal id `iid`, forgotten or not; 0 if
+    // there is none.
+    fn stored_version(&self, iid: u64) -> u64 {
+        self.handle.meta(iid).map_or(0, |m| m.version())
+    }
+
+    // Count a change the core made to record `iid` in place, rather than by
+    // writing metadata from `meta_in`; `before` is its version until then.
+    fn stamp(&self, iid: u64, before: u64) {
+        let key = CString::new(metadata::VERSION_ATTRIBUTE).expect("no NUL");
+        let value = CString::new((before + 1).to_string()).expect("digits");
+        unsafe { feather_set_attribute(self.handle.core(iid), iid, key.as_ptr(), value.as_ptr()) };
+    }
+
+    fn observe_query(&self, modality: Option<&str>, query: &[f32]) {
+        self.handle.query_stats.borrow_mut()
+            .entry(modality.unwrap_or("text").to_string())
+            .or_default()
+            .observe(query);
+        self.handle.query_stats_dirty.set(true);
+    }
+
+    // Map a vector entering `modality` (internal name) through its
+    // projection, if it is in the projection's input space (anything else
+    // passes through unchanged), then scale it to unit length if the file
+    // normalizes, or to 0/1 if it holds bits.
+    fn project<'a>(&self, modality: Option<&str>, vec: &'a [f32]) -> Cow<'a, [f32]> {
+        let projected = match self.handle.projections.borrow().get(modality.unwrap_or("text")) {
+            Some(p) if vec.len() == p.in_dim() => Cow::Owned(p.apply(vec)),
+            _ => Cow::Borrowed(vec),
         };
-        (ids, dists)
+        if self.handle.binary.get() { return Cow::Owned(hamming::binarize(&projected)); }
+        if !self.handle.normalize.get() { return projected; }
+        normalize::unit(&projected).map_or(projected, Cow::Owned)
     }
 
-    pub fn search_with_filter(&self, query: &[f32], k: usize, type_filter: Option<u8>, 
-                               source_filter: Option<&str>, modality: Option<&str>) -> (Vec<u64>, Vec<f32>) {
-        let mut ids = vec![0u64; k];
-        let mut dists = vec![0f32; k];
-        let c_source = source_filter.and_then(|s| std::ffi::CString::new(s).ok());
-        let c_modality = modality.and_then(|s| std::ffi::CString::new(s).ok());
-        
+    // Reject a vector (already projected) that does not fit the index of
+    // `modality` (internal name). A modality with no index yet takes any
+    // dimension; the first vector sets it, unless `register_modality` has.
+    fn check_dim(&self, modality: Option<&str>, vec: &[f32]) -> Result<(), DimensionMismatch> {
+        let modality = modality.unwrap_or("text");
+        if !self.handle.modalities().iter().any(|m| m == modality) { return Ok(()); }
+        let expected = self.handle.dim(Some(modality));
+        if vec.len() == expected { Ok(()) } else { Err(DimensionMismatch { expected, got: vec.len() }) }
+    }
+
+    /// Insert or replace a record in the `text` modality, subject to the
+    /// duplicate-id policy (see `open`) and the dedup mode (see `dedup`).
+    /// Fails with `DimensionMismatch` if `vec` does not fit the modality.
+    pub fn add(&self, id: u64, vec: &[f32]) -> anyhow::Result<()> {
+        let mut span = Span::new(Level::Trace, "feather::add");
+        span.record("id", id).record_str("modality", "text");
+        self.writable()?;
+        if self.scope.is_some() {
+            return self.add_with_meta(id, vec, 0, 1.0, ContextType::default(), None, None, None);
+        }
+        let id = self.iid(id)?;
+        if !self.admit(id)? || self.deduplicate(id, Some(("text", vec)), &Metadata::default())?.is_some() {
+            return Ok(());
+        }
+        let vec = self.project(None, vec);
+        self.check_dim(None, &vec)?;
+        let before = self.stored_version(id);
+        unsafe { feather_add(self.handle.core(id), id, vec.as_ptr(), vec.len()) };
+        self.stamp(id, before);
+        self.changed(audit::Op::Add, id, None, None);
+        Ok(())
+    }
+
+    /// `add` with the common metadata fields and a modality.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_with_meta(&self, id: u64, vec: &[f32], timestamp: i64, importance: f32, context_type: ContextType,
+                         source: Option<&str>, content: Option<&str>, modality: Option<&str>) -> anyhow::Result<()> {
+        let mut span = Span::new(Level::Trace, "feather::add");
+        span.record("id", id).record_str("modality", modality.unwrap_or("text"));
+        self.writable()?;
+        if !self.admit(id)? { return Ok(()); }
+        if self.dedup().0 != Dedup::Off {
+            let meta = Metadata {
+                timestamp, importance, context_type,
+                source: source.unwrap_or_default().to_string(),
+                content: content.unwrap_or_default().to_string(),
+                ..Metadata::default()
+            };
+            if self.deduplicate(id, Some((modality.unwrap_or("text"), vec)), &meta)?.is_some() { return Ok(()); }
+        }
+        let id = self.iid(id)?;
+        self.handle.note_content(id, content.unwrap_or_default());
+        let modality = self.mname(modality);
+        let vec = self.project(modality.as_deref(), vec);
+        self.check_dim(modality.as_deref(), &vec)?;
+        let c_source = source.and_then(|s| CString::new(s).ok());
+        let c_content = content.and_then(|s| CString::new(s).ok());
+        let c_modality = modality.and_then(|s| CString::new(s.as_ref()).ok());
+        let before = self.stored_version(id);
+
         unsafe {
-            feather_search_with_filter(
-                self.0, query.as_ptr(), query.len(), k,
-                type_filter.unwrap_or(255),
-                c_source.map_or(std::ptr::null(), |s| s.as_ptr()),
-                ids.as_mut_ptr(), dists.as_mut_ptr(),
-                c_modality.map_or(std::ptr::null(), |s| s.as_ptr())
+            feather_add_with_meta(
+                self.handle.core(id), id, vec.as_ptr(), vec.len(),
+                timestamp, importance, context_type.code(),
+                opt_ptr(&c_source),
+                opt_ptr(&c_content),
+                opt_ptr(&c_modality)
             )
         };
-        (ids, dists)
+        self.stamp(id, before);
+        self.changed(audit::Op::Add, id, None, None);
+        Ok(())
     }
 
-    pub fn save(&self) { unsafe { feather_save(self.0) } }
-}
+    /// Insert or replace a record with full metadata, subject to the
+    /// duplicate-id policy and the dedup mode. Fails with
+    /// `DimensionMismatch` if `vec` does not fit the modality.
+    pub fn add_with_metadata(&self, id: u64, vec: &[f32], meta: &Metadata, modality: &str) -> anyhow::Result<()> {
+        self.writable()?;
+        if !self.admit(id)? || self.deduplicate(id, Some((modality, vec)), meta)?.is_some() { return Ok(()); }
+        self.write_record(id, vec, meta, modality)
+    }
+
+    // `add_with_metadata` regardless of the duplicate-id policy and the
+    // dedup mode.
+    pub(crate) fn write_record(&self, id: u64, vec: &[f32], meta: &Metadata, modality: &str) -> anyhow::Result<()> {
+        self.write_logged(audit::Op::Add, id, vec, meta, modality)
+    }
+
+    // `write_record`, logged as `op`.
+    fn write_logged(&self, op: audit::Op, id: u64, vec: &[f32], meta: &Metadata, modality: &str) -> anyhow::Result<()> {
+        let mut span = Span::new(Level::Trace, "feather::add");
+        span.record("id", id).record_str("modality", modality);
+        self.writable()?;
+        let id = self.iid(id)?;
+        let modality = self.mname(Some(modality)).expect("named");
+        let vec = self.project(Some(&modality), vec);
+        self.check_dim(Some(&modality), &vec)?;
+        let c_meta = CMetadata::new(&self.meta_in(id, meta)?)?;
+        let c_modality = c_str(&modality)?;
+        let rc = unsafe {
+            feather_add_with_metadata(self.handle.core(id), id, vec.as_ptr(), vec.len(), c_meta.raw(), c_modality.as_ptr())
+        };
+        if rc != 0 { return Err(last_error()); }
+        self.handle.note_content(id, &meta.content);
+        self.changed(op, id, None, None);
+        Ok(())
+    }
+
+    /// Insert or replace many records of one modality at once; the index is
+    /// built in parallel. All vectors must share one dimension. Subject to
+    /// the duplicate-id policy, an id repeated within the batch counting as
+    /// a duplicate; under `OnDuplicate::Error` nothing is inserted if any id
+    /// is refused. With a dedup mode set, records are written one at a time
+    /// so that each is checked against those before it.
+    pub fn add_batch(&self, ids: &[u64], vecs: &[Vec<f32>], metas: &[Metadata], modality: &str) -> anyhow::Result<()> {
+        anyhow::ensure!(ids.len() == vecs.len() && ids.len() == metas.len(),
+                        "add_batch: {} ids, {} vectors, {} metadata", ids.len(), vecs.len(), metas.len());
+        let mut span = Span::new(Level::Debug, "feather::add");
+        span.record("records", ids.len()).record_str("modality", modality);
+        self.writable()?;
+        let keep = self.admit_all(ids)?;
+        if self.dedup().0 != Dedup::Off {
+            for (i, _) in keep.iter().enumerate().filter(|(_, &k)| k) {
+                if self.deduplicate(ids[i], Some((modality, &vecs[i])), &metas[i])?.is_none() {
+                    self.write_record(ids[i], &vecs[i], &metas[i], modality)?;
+                }
+            }
+            return Ok(());
+        }
+        if keep.iter().all(|&k| k) {
+            return self.write_batch(ids, vecs, metas, modality);
+        }
+        let (mut kept_ids, mut kept_vecs, mut kept_metas) = (Vec::new(), Vec::new(), Vec::new());
+        for (i, _) in keep.iter().enumerate().filter(|(_, &k)| k) {
+            kept_ids.push(ids[i]);
+            kept_vecs.push(vecs[i].clone());
+            kept_metas.push(metas[i].clone());
+        }
+        self.write_batch(&kept_ids, &kept_vecs, &kept_metas, modality)
+    }
+
+    // `add_batch` regardless of the duplicate-id policy.
+    pub(crate) fn write_batch(&self, ids: &[u64], vecs: &[Vec<f32>], metas: &[Metadata], modality: &str) -> anyhow::Result<()> {
+        self.writable()?;
+        if ids.is_empty() { return Ok(()); }
+        let modality = self.mname(Some(modality)).expect("named");
+        let mut flat = Vec::new();
+        let mut dim = None;
+        for (id, v) in ids.iter().zip(vecs) {
+            let v = self.project(Some(&modality), v);
+            let d = *dim.get_or_insert(v.len());
+            anyhow::ensure!(v.len() == d, "record {}: dim {} differs from batch dim {}", id, v.len(), d);
+            flat.extend_from_slice(&v);
+        }
+        self.write_flat(ids, &flat, dim.unwrap_or(0), metas, &modality)
+    }
+
+    // `write_batch` for vectors already projected into `modality` (internal
+    // name) and laid end to end, `dim` values each.
+    pub(crate) fn write_flat(&self, ids: &[u64], flat: &[f32], dim: usize, metas: &[Metadata], modality: &str) -> anyhow::Result<()> {
+        self.writable()?;
+        if ids.is_empty() { return Ok(()); }
+        anyhow::ensure!(flat.len() == ids.len() * dim, "{} values for {} records of dim {}", flat.len(), ids.len(), dim);
+        self.check_dim(Some(modality), &flat[..dim])?;
+        let ids = ids.iter().map(|&id| self.iid(id)).collect::<anyhow::Result<Vec<_>>>()?;
+        let c_metas = ids.iter().zip(metas)
+            .map(|(&id, m)| CMetadata::new(&self.meta_in(id, m)?))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let c_modality = c_str(modality)?;
+        let add = |core, ids: &[u64], flat: &[f32], raws: &[*const RawMetadata]| {
+            let rc = unsafe {
+                feather_add_batch(core, ids.as_ptr(), flat.as_ptr(), ids.len(), dim, raws.as_ptr(), c_modality.as_ptr())
+            };
+            if rc != 0 { Err(last_error()) } else { Ok(()) }
+        };
+        let cores = self.handle.cores();
+        if cores.len() == 1 {
+            add(cores[0], &ids, flat, &c_metas.iter().map(CMetadata::raw).collect::<Vec<_>>())?;
+        } else {
+            // one call per shard, with the records that shard holds
+            let mut parts: Vec<(Vec<u64>, Vec<f32>, Vec<*const RawMetadata>)> = vec![Default::default(); cores.len()];
+            for (i, (&id, meta)) in ids.iter().zip(&c_metas).enumerate() {
+                let part = &mut parts[shard::of(id, cores.len())];
+                part.0.push(id);
+                part.1.extend_from_slice(&flat[i * dim..(i + 1) * dim]);
+                part.2.push(meta.raw());
+            }
+            for (&core, (ids, flat, raws)) in cores.iter().zip(&parts).filter(|(_, p)| !p.0.is_empty()) {
+                add(core, ids, flat, raws)?;
+            }
+        }
+        for (&id, meta) in ids.iter().zip(metas) {
+            self.handle.note_content(id, &meta.content);
+            self.handle.changed(audit::Op::Add, id, None, None);
+        }
+        self.maintain_if_due();
+        Ok(())
+    }
+
+    /// A record's metadata. On a collection handle, edges leading out of the
+    /// collection are left out.
+    pub fn get_metadata(&self, id: u64) -> Option<Metadata> {
+        let mut meta = self.handle.meta(self.iid(id).ok()?)?;
+        if self.scope.is_some() {
+            meta.edges.retain_mut(|e| match self.xid(e.target) {
+                Some(t) => { e.target = t; true }
+                None => false,
+            });
+        }
+        Some(meta)
+    }
+
+    /// Replace a record's metadata, edges included, counting a new version
+    /// of it. Vectors are untouched.
+    pub fn put_metadata(&self, id: u64, meta: &Metadata) -> anyhow::Result<()> {
+        self.writable()?;
+        let id = self.iid(id)?;
+        let c_meta = CMetadata::new(&self.meta_in(id, meta)?)?;
+        unsafe { feather_put_metadata(self.handle.core(id), id, c_meta.raw()) };
+        self.handle.note_content(id, &meta.content);
+        self.changed(audit::Op::Update, id, None, None);
+        Ok(())
+    }
+
+    /// The version of the live record `id` (see `Metadata::version`), or
+    /// None if there is no such record.
+    pub fn version(&self, id: u64) -> Option<u64> {
+        self.get_metadata(id).filter(|m| !m.is_forgotten()).map(|m| m.version())
+    }
+
+    // Fail with `VersionConflict` unless `id` is live at version `expected`.
+    fn expect_version(&self, id: u64, expected: u64) -> anyhow::Result<()> {
+        let actual = self.version(id);
+        anyhow::ensure!(actual == Some(expected), VersionConflict { id, expected, actual });
+        Ok(())
+    }
+
+    /// `put_metadata` if the record is still at version `expected`, as read
+    /// from `get_metadata`; otherwise fails with `VersionConflict`, changing
+    /// nothing, because someone else changed or forgot it since. Returns
+    /// the record's new version.
+    pub fn put_metadata_if(&self, id: u64, meta: &Metadata, expected: u64) -> anyhow::Result<u64> {
+        self.writable()?;
+        self.expect_version(id, expected)?;
+        self.put_metadata(id, meta)?;
+        Ok(expected + 1)
+    }
+
+    /// `forget` if the record is still at version `expected`; otherwise
+    /// fails with `VersionConflict`.
+    pub fn forget_if(&self, id: u64, expected: u64) -> anyhow::Result<()> {
+        self.writable()?;
+        self.expect_version(id, expected)?;
+        self.forget(id)
+    }
+
+    /// Every record id, whichever modalities hold its vectors.
+    pub fn all_ids(&self) -> Vec<u64> {
+        self.handle.all_ids().into_iter().filter_map(|id| self.xid(id)).collect()
+    }
+
+    /// Names of the modality indexes present in this DB (or collection).
+    pub fn modalities(&self) -> Vec<String> {
+        self.scoped_names(self.handle.modalities())
+    }
+
+    // The internal modality (or sparse set) names that belong to this
+    // handle, as it calls them.
+    fn scoped_names(&self, all: Vec<String>) -> Vec<String> {
+        match &self.scope {
+            Some(s) => {
+                let prefix = s.modality("");
+                all.iter().filter_map(|m| m.strip_prefix(&prefix).map(str::to_string)).collect()
+            }
+            None => all.into_iter().filter(|m| !self.handle.in_collection(m)).collect(),
+        }
+    }
+
+    /// Fails on a read-only handle, and for an id above
+    /// `collection::MAX_ID` on a collection handle or in a file with
+    /// collections.
+    pub fn link(&self, from_id: u64, to_id: u64) -> anyhow::Result<()> {
+        self.writable()?;
+        let (from_id, to_id) = (self.iid(from_id)?, self.iid(to_id)?);
+        let before = self.stored_version(from_id);
+        self.handle.copy_up(from_id);
+        unsafe { feather_link(self.handle.core(from_id), from_id, to_id) }
+        self.stamp(from_id, before);
+        self.changed(audit::Op::Link, from_id, Some(to_id), None);
+        Ok(())
+    }
+
+    /// Fails on a read-only handle, whose searches count no recalls, and
+    /// for an `id` above `collection::MAX_ID` on a collection handle or in a
+    /// file with collections.
+    pub fn touch(&self, id: u64) -> anyhow::Result<()> {
+        self.writable()?;
+        self.count_recall(self.iid(id)?);
+        Ok(())
+    }
+
+    // Count the hits of a search as recalled, as `touch` does; those of a
+    // read-only handle count none.
+    pub(crate) fn recalled(&self, ids: impl IntoIterator<Item = u64>) {
+        if self.is_read_only() { return; }
+        for id in ids {
+            if let Ok(id) = self.iid(id) { self.count_recall(id); }
+        }
+    }
+
+    fn count_recall(&self, internal: u64) {
+        self.handle.copy_up(internal);
+        unsafe { feather_touch(self.handle.core(internal), internal) }
+    }
+
+    /// Mark a live record as re-used: its timestamp becomes `timestamp`, so
+    /// recency ranks it as new, and it counts as recalled.
+    pub fn refresh(&self, id: u64, timestamp: i64) -> anyhow::Result<()> {
+        let mut meta = self.get_metadata(id).filter(|m| !m.is_forgotten())
+            .ok_or_else(|| anyhow::anyhow!("no record {}", id))?;
+        meta.timestamp = timestamp;
+        self.put_metadata(id, &meta)?;
+        self.touch(id)
+    }
 
-impl Drop for DB {
-    fn drop(&mut self) { unsafe { feather_close(self.0) } }
+    /// Soft-delete a record: it leaves search and export at once, and
+    /// `compact()` reclaims it. Forgetting an unknown id is a no-op.
+    pub fn forget(&self, id: u64) -> anyhow::Result<()> {
+        self.writable()?;
+        self.handle.forget(self.iid(id)?);
+        self.maintain_if_due();
+        Ok(())
+    }
+
+    /// Set the HNSW search beam width of `modality` (every modality of the
+    /// file if None): higher finds the true neighbours more often but
+    /// searches slower. Starts at the file's tuned value (`set_tuned_ef`),
+    /// else `search::DEFAULT_EF`; not persisted.
+    pub fn set_ef(&self, ef: usize, modality: Option<&str>) -> anyhow::Result<()> {
+        anyhow::ensure!(ef > 0, "ef must be positive");
+        match modality {
+            Some(_) => self.handle.set_ef(ef, self.mname(modality).as_deref()),
+            None => self.handle.set_ef(ef, None),
+        }
+    }
+
+    /// Read the whole loaded store once — vectors, graph links and records,
+    /// a fork's base too — and build the content index dedup checks use,
+    /// if dedup is on. The core holds the store in memory from the open,
+    /// but after an idle spell the OS may have paged it out, and the first
+    /// queries would fault it back in page by page. Returns the bytes read.
+    pub fn warm(&self) -> anyhow::Result<u64> {
+        let mut span = Span::new(Level::Info, "feather::warm");
+        let bytes = self.handle.warm()?;
+        if self.dedup().0 == Dedup::Content {
+            self.handle.with_content_index(|_| ());
+        }
+        span.record("bytes", bytes);
+        Ok(bytes)
+    }
+
+    /// Fails with `DimensionMismatch` if `query` does not fit the modality.
+    pub fn search(&self, query: &[f32], k: usize, modality: Option<&str>) -> anyhow::Result<(Vec<u64>, Vec<f32>)> {
+        let modality = self.mname(modality);
+        let query = self.project(modality.as_deref(), query);
+        self.check_dim(modality.as_deref(), &query)?;
+        self.observe_query(modality.as_deref(), &query);
+        let mut span = Span::new(Level::Debug, "feather::search");
+        span.record_str("modality", modality.as_deref().unwrap_or("text")).record("k", k);
+        let hits: (Vec<u64>, Vec<f32>) = self.handle.search(&query, k, modality.as_deref(), None, None)
+            .into_iter()
+            .map(|(id, score)| (self.xid(id).unwrap_or(0), score))
+            .unzip();
+        span.record("hits", hits.0.len());
+        Ok(hits)
+    }
+
+    /// Fails with `DimensionMismatch` if `query` does not fit the modality.
+    pub fn search_with_filter(&self, query: &[f32], k: usize, type_filter: Option<ContextType>,
+                              source_filter: Option<&str>, modality: Option<&str>)
+                              -> anyhow::Result<(Vec<u64>, Vec<f32>)> {
+        let modality = self.mname(modality);
+        let query = self.project(modality.as_deref(), query);
+        self.check_dim(modality.as_deref(), &query)?;
+        self.observe_query(modality.as_deref(), &query);
+        let mut span = Span::new(Level::Debug, "feather::search");
+        span.record_str("modality", modality.as_deref().unwrap_or("text")).record("k", k).record("filtered", true);
+        let type_filter = Some(type_filter.map_or(context_type::ANY_CODE, ContextType::code));
+        let hits: (Vec<u64>, Vec<f32>) = self.handle.search(&query, k, modality.as_deref(), type_filter, source_filter)
+            .into_iter()
+            .map(|(id, score)| (self.xid(id).unwrap_or(0), score))
+            .unzip();
+        span.record("hits", hits.0.len());
+        Ok(hits)
+    }
+
+    /// Raw nearest neighbours as `(id, squared L2 distance)`, nearest first.
+    /// Unlike `search`, hits are not scored and their recall counts are not
+    /// bumped — use this for analytics passes over the store.
+    pub fn knn(&self, query: &[f32], k: usize, modality: &str) -> anyhow::Result<Vec<(u64, f32)>> {
+        self.knn_within(query, k, modality, &Prefilter::default())
+    }
+
+    // `knn` restricted, during the index scan, to records `prefilter` accepts.
+    pub(crate) fn knn_within(&self, query: &[f32], k: usize, modality: &str,
+                             prefilter: &Prefilter) -> anyhow::Result<Vec<(u64, f32)>> {
+        let modality = self.mname(Some(modality)).expect("named");
+        let query = self.project(Some(&modality), query);
+        self.check_dim(Some(&modality), &query)?;
+        let mut span = Span::new(Level::Debug, "feather::search");
+        span.record_str("modality", &modality).record("k", k).record("knn", true);
+        let hits: Vec<(u64, f32)> = self.handle.knn(&query, k, &modality, prefilter)?
+            .into_iter()
+            .filter_map(|(id, d)| Some((self.xid(id)?, d)))
+            .collect();
+        span.record("hits", hits.len());
+        Ok(hits)
+    }
+
+    /// Records whose content best matches the keywords of `text`, as
+    /// `(id, BM25 score)`, best first. Like `knn`, hits are not counted as
+    /// recalled.
+    pub fn bm25(&self, text: &str, k: usize) -> anyhow::Result<Vec<(u64, f32)>> {
+        self.bm25_within(text, k, false)
+    }
+
+    // `bm25`, archived records included if `include_archived`.
+    pub(crate) fn bm25_within(&self, text: &str, k: usize, include_archived: bool) -> anyhow::Result<Vec<(u64, f32)>> {
+        let mut span = Span::new(Level::Debug, "feather::search");
+        span.record("k", k).record("bm25", true);
+        // the keyword index spans the file; fetch until k hits are in scope
+        let mut fetch = k;
+        loop {
+            let hits = self.handle.bm25(text, fetch, include_archived)?;
+            let exhausted = hits.len() < fetch;
+            let mut hits: Vec<(u64, f32)> = hits.into_iter()
+                .filter_map(|(id, score)| Some((self.xid(id)?, score)))
+                .collect();
+            if hits.len() >= k || exhausted {
+                hits.truncate(k);
+                span.record("hits", hits.len());
+                return Ok(hits);
+            }
+            fetch = fetch.saturating_mul(2);
+        }
+    }
+
+    /// `bm25` as a search: the hits count as recalled.
+    pub fn keyword_search(&self, text: &str, k: usize) -> anyhow::Result<Vec<(u64, f32)>> {
+        let hits = self.bm25(text, k)?;
+        self.recalled(hits.iter().map(|&(id, _)| id));
+        Ok(hits)
+    }
+
+    /// Set a string attribute on an existing record. Returns false if `id`
+    /// has no metadata. The version attribute is not settable.
+    pub fn set_attribute(&self, id: u64, key: &str, value: &str) -> anyhow::Result<bool> {
+        self.writable()?;
+        anyhow::ensure!(key != metadata::VERSION_ATTRIBUTE, "attribute {} is set by the store", key);
+        let id = self.iid(id)?;
+        let (c_key, c_value) = (c_str(key)?, c_str(value)?);
+        let before = self.stored_version(id);
+        self.handle.copy_up(id);
+        let set = unsafe { feather_set_attribute(self.handle.core(id), id, c_key.as_ptr(), c_value.as_ptr()) != 0 };
+        if set {
+            self.stamp(id, before);
+            self.changed(audit::Op::Update, id, None, None);
+        }
+        Ok(set)
+    }
+
+    /// Write the store to its file, first evicting what exceeds its budget
+    /// (`set_budget`); a no-op in memory. Fails on a read-only handle.
+    pub fn save(&self) -> anyhow::Result<()> {
+        self.writable()?;
+        self.enforce_budget();
+        let mut span = Span::new(Level::Info, "feather::save");
+        if span.is_enabled() {
+            self.record_stats(&mut span);
+        }
+        self.handle.checkpoint();
+        Ok(())
+    }
+
+    /// Rebuild every index without soft-deleted records and drop their
+    /// metadata, across all collections of the file. Returns the number of
+    /// dead records removed; call `save()` afterwards to rewrite the file and
+    /// reclaim the disk space. Fails on a read-only handle.
+    pub fn compact(&self) -> anyhow::Result<usize> { self.compact_with_progress(None) }
+
+    /// `compact`, reporting index elements visited out of all of them.
+    pub fn compact_with_progress(&self, progress: Option<&mut ProgressFn>) -> anyhow::Result<usize> {
+        self.writable()?;
+        Ok(self.handle.compact(progress))
+    }
+
+    /// Forget every record whose time-to-live has run out (`ttl > 0` and
+    /// `timestamp + ttl` in the past), across all collections of the file.
+    /// Forgotten records leave search at once; `compact()` reclaims them.
+    /// Returns the number of records expired. Fails on a read-only handle,
+    /// where expired records stay searchable.
+    pub fn expire(&self) -> anyhow::Result<usize> {
+        self.writable()?;
+        Ok(self.handle.expire())
+    }
+
+    /// Stored vector dimension of `modality` (the open() default if empty).
+    pub fn dim(&self, modality: &str) -> usize {
+        self.handle.dim(self.mname(Some(modality)).as_deref())
+    }
+
+    /// Create the index of `modality` with dimension `dim` before its first
+    /// vector, so that every add and search is checked against it, e.g.
+    /// `register_modality("image", 512)` next to 768-dim text. The index
+    /// is kept in the file from the next `save()`. Registering a modality
+    /// again with the same dim is a no-op; with another, an error.
+    pub fn register_modality(&self, modality: &str, dim: usize) -> anyhow::Result<()> {
+        anyhow::ensure!(!modality.is_empty() && !modality.contains(collection::MODALITY_SEP),
+                        "invalid modality name {:?}", modality);
+        anyhow::ensure!(dim > 0, "modality '{}': dim must be positive", modality);
+        if self.modalities().iter().any(|m| m == modality) {
+            let existing = self.dim(modality);
+            anyhow::ensure!(existing == dim, "modality '{}' already has dim {}", modality, existing);
+            return Ok(());
+        }
+        self.writable()?;
+        let c_modality = c_str(&self.mname(Some(modality)).expect("named"))?;
+        for &core in self.handle.cores() {
+            if unsafe { feather_register_modality(core, c_modality.as_ptr(), dim) } != 0 { return Err(last_error()); }
+        }
+        Ok(())
+    }
+
+    /// Every id with a vector in `modality`.
+    pub fn ids(&self, modality: &str) -> Vec<u64> {
+        self.handle.ids(self.mname(Some(modality)).as_deref())
+            .into_iter()
+            .filter_map(|id| self.xid(id))
+            .collect()
+    }
+
+    /// The stored (already projected) vector for `id` in `modality`.
+    pub fn get_vector(&self, id: u64, modality: &str) -> Option<Vec<f32>> {
+        self.handle.vector(self.iid(id).ok()?, self.mname(Some(modality)).as_deref())
+    }
+
+    /// Give the existing record `id` a vector in `modality` (a named vector,
+    /// e.g. "summary" next to "full_text"), replacing the one it had there.
+    /// Its metadata and its vectors in other modalities are left alone.
+    pub fn set_vector(&self, id: u64, modality: &str, vec: &[f32]) -> anyhow::Result<()> {
+        let meta = self.get_metadata(id).filter(|m| !m.is_forgotten())
+            .ok_or_else(|| anyhow::anyhow!("no record {}", id))?;
+        self.write_logged(audit::Op::Update, id, vec, &meta, modality)
+    }
+
+    /// Set a property persisted in the file header on the next `save()`.
+    /// Properties are per file, shared by all collections. Fails on a
+    /// read-only handle.
+    pub fn set_property(&self, key: &str, value: &[u8]) -> anyhow::Result<()> {
+        self.writable()?;
+        self.handle.set_property(key, value);
+        Ok(())
+    }
+
+    pub fn property(&self, key: &str) -> Option<Vec<u8>> {
+        self.handle.property(key)
+    }
+
+    /// Remove a property on the next `save()`; false if there was none.
+    /// Fails on a read-only handle.
+    pub fn remove_property(&self, key: &str) -> anyhow::Result<bool> {
+        self.writable()?;
+        let c_key = c_str(key)?;
+        Ok(unsafe { feather_remove_property(self.ptr, c_key.as_ptr()) != 0 })
+    }
+
+    /// The projection applied to vectors entering `modality`, if any.
+    pub fn projection(&self, modality: &str) -> Option<Projection> {
+        let modality = self.mname(Some(modality)).expect("named");
+        self.handle.projections.borrow().get(modality.as_ref()).cloned()
+    }
+
+    /// Project every stored vector of `modality` through `proj` and record it
+    /// so future inserts and queries in the original space are projected
+    /// too. Projections compose: redimming twice maps the original input
+    /// space straight to the latest one. In a file that normalizes, the
+    /// projected vectors are normalized again. Checkpoints the file. Returns
+    /// the number of vectors rewritten.
+    pub fn reproject(&self, modality: &str, proj: Projection) -> anyhow::Result<usize> {
+        self.reproject_with_progress(modality, proj, None)
+    }
+
+    /// `reproject`, reporting vectors added to the new index.
+    pub fn reproject_with_progress(&self, modality: &str, proj: Projection,
+                                   progress: Option<&mut ProgressFn>) -> anyhow::Result<usize> {
+        self.writable()?;
+        anyhow::ensure!(self.handle.fork.is_none(), "cannot reproject a fork: its base is read-only");
+        let stored = self.dim(modality);
+        anyhow::ensure!(proj.in_dim() == stored,
+                        "projection expects dim {}, but '{}' stores dim {}", proj.in_dim(), modality, stored);
+        let modality = self.mname(Some(modality)).expect("named");
+        let c_modality = c_str(&modality)?;
+        let (matrix, bias) = proj.to_affine();
+        let mut reporter = Reporter::new("reproject", progress);
+        let (cb, ctx) = reporter.as_c();
+        let mut n = 0;
+        for &core in self.handle.cores() {
+            let rewritten = unsafe {
+                feather_reproject(core, c_modality.as_ptr(), matrix.as_ptr(), bias.as_ptr(),
+                                  proj.in_dim(), proj.out_dim(), cb, ctx)
+            };
+            if rewritten < 0 { return Err(last_error()); }
+            n += rewritten;
+        }
+
+        {
+            let mut projections = self.handle.projections.borrow_mut();
+            let combined = match projections.get(modality.as_ref()) {
+                Some(prev) => prev.then(&proj),
+                None => proj,
+            };
+            projections.insert(modality.to_string(), combined);
+            self.set_property(projection::PROPERTY_KEY, &projection::encode(&projections))?;
+        }
+        if self.normalizes() {
+            self.handle.normalize_stored(&modality)?;
+        }
+        self.save()?;
+        Ok(n as usize)
+    }
+
+    /// Running statistics of the queries searched against `modality` since
+    /// the last `reset_drift`, if any.
+    pub fn query_stats(&self, modality: &str) -> Option<DistributionStats> {
+        let modality = self.mname(Some(modality)).expect("named");
+        self.handle.query_stats.borrow().get(modality.as_ref()).filter(|s| s.count > 0).cloned()
+    }
+
+    /// Forget every query observed in this DB (or collection), starting a
+    /// new drift window.
+    pub fn reset_drift(&self) {
+        let mut stats = self.handle.query_stats.borrow_mut();
+        match &self.scope {
+            Some(s) => {
+                let prefix = s.modality("");
+                stats.retain(|m, _| !m.starts_with(&prefix));
+            }
+            None => stats.retain(|m, _| self.handle.in_collection(m)),
+        }
+        self.handle.query_stats_dirty.set(true);
+    }
 }
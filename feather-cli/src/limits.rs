@@ -0,0 +1,156 @@
+//! Keeping one client from starving the others (`feather serve
+//! --rate-limit`, `--max-searches`, `--search-budget`).
+//!
+//! - A rate limit gives each client a token bucket: `burst` requests at
+//!   once, refilled at `per_second`. A client is its bearer token when the
+//!   server checks tokens, else its IP address. A request over it is answered 429,
+//!   with `Retry-After` saying when the next would be let in.
+//! - `max_searches` bounds the searches admitted and not yet answered;
+//!   beyond it, a search is answered 503 at once rather than queued.
+//! - A search budget bounds the time one search may take. The hits come a
+//!   page at a time (see `SearchIter::until`), and once the budget is spent no further
+//!   page is ranked: the reply holds the hits found so far, the best ones
+//!   of the full ranking in order, flagged `"partial": true`. A request
+//!   may ask for a tighter budget (`budget_ms`), never a looser one. A page
+//!   already started runs to its end, so a budget is a bound on starting
+//!   work, not a hard deadline.
+
+use crate::serve::{Request, Response};
+use crate::{Access, Tokens};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Clients whose buckets are kept before the full ones are dropped.
+const MAX_CLIENTS: usize = 10_000;
+
+/// What a server lets its clients do; the default limits nothing.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Limits {
+    /// Requests each client may make.
+    pub rate: Option<Rate>,
+    /// Most searches admitted and not yet answered.
+    pub max_searches: Option<usize>,
+    /// Longest a search may keep ranking.
+    pub search_budget: Option<Duration>,
+}
+
+/// A request rate: `burst` at once, `per_second` sustained.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rate {
+    pub per_second: f64,
+    pub burst: f64,
+}
+
+impl Rate {
+    /// A rate such as "20/s", "600/min" or "20" (per second), with a burst
+    /// of one second's worth, and at least one request.
+    pub fn parse(s: &str) -> anyhow::Result<Self> {
+        let (count, per) = s.split_once('/').unwrap_or((s, "s"));
+        let seconds = match per {
+            "s" | "sec" => 1.0,
+            "m" | "min" => 60.0,
+            "h" | "hour" => 3600.0,
+            _ => anyhow::bail!("unknown rate unit '{}' (s, min, hour)", per),
+        };
+        let count: f64 = count.trim().parse().map_err(|_| anyhow::anyhow!("bad rate '{}': expected e.g. 20/s", s))?;
+        anyhow::ensure!(count > 0.0 && count.is_finite(), "a rate must be positive");
+        let per_second = count / seconds;
+        Ok(Rate { per_second, burst: per_second.max(1.0) })
+    }
+}
+
+impl fmt::Display for Rate {
+    /// As `parse` reads it back.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{}/s", self.per_second) }
+}
+
+// A client's token bucket.
+struct Bucket {
+    tokens: f64,
+    at: Instant,
+}
+
+// Where the limits are enforced, shared by the threads reading requests.
+pub(crate) struct Gate {
+    tokens: Option<Tokens>,
+    limits: Limits,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    searches: AtomicUsize,
+}
+
+impl Gate {
+    pub(crate) fn new(tokens: Option<Tokens>, limits: Limits) -> Self {
+        Gate { tokens, limits, buckets: Mutex::new(HashMap::new()), searches: AtomicUsize::new(0) }
+    }
+
+    // The access `request`, from `peer`, is answered with, or the refusal:
+    // 401 without a known token, 429 over the rate, 503 past the searches
+    // in flight. An admitted search holds a slot until `release`d.
+    pub(crate) fn admit(&self, request: &Request, peer: Option<IpAddr>) -> Result<Access, Response> {
+        if is_probe(request) { return Ok(Access::Labels(Vec::new())); }
+        let access = match &self.tokens {
+            None => Access::All,
+            Some(tokens) => request.token.as_deref().and_then(|token| tokens.get(token)).cloned()
+                .ok_or_else(|| Response::error(401, "a bearer token is required: Authorization: Bearer <token>"))?,
+        };
+        if let Some(rate) = self.limits.rate {
+            // a token names the client only once it was checked: one
+            // made up anew for each request must not buy a fresh bucket
+            let client = match (&self.tokens, &request.token, peer) {
+                (Some(_), Some(token), _) => format!("token:{}", token),
+                (_, _, Some(ip)) => ip.to_string(),
+                (_, _, None) => String::new(),
+            };
+            self.take(&client, rate)?;
+        }
+        if is_search(request) {
+            let max = self.limits.max_searches.unwrap_or(usize::MAX);
+            let admitted = self.searches.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| (n < max).then_some(n + 1));
+            if admitted.is_err() {
+                return Err(Response::error(503, format!("{} searches are in flight; try again shortly", max)));
+            }
+        }
+        Ok(access)
+    }
+
+    // Free the slot of an admitted search, once answered.
+    pub(crate) fn release(&self) {
+        self.searches.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    // Take one token from `client`'s bucket, or say when there will be one.
+    fn take(&self, client: &str, rate: Rate) -> Result<(), Response> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if buckets.len() >= MAX_CLIENTS && !buckets.contains_key(client) {
+            // a full bucket is what a new client would get anyway
+            buckets.retain(|_, b| b.tokens + now.duration_since(b.at).as_secs_f64() * rate.per_second < rate.burst);
+        }
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket { tokens: rate.burst, at: now });
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.at).as_secs_f64() * rate.per_second).min(rate.burst);
+        bucket.at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        let wait = ((1.0 - bucket.tokens) / rate.per_second).ceil().max(1.0) as u64;
+        let mut refusal = Response::error(429, "rate limit exceeded");
+        refusal.body["retry_after"] = wait.into();
+        Err(refusal)
+    }
+}
+
+// Whether `request` is `GET /healthz` or `/readyz` (see `doctor`), which
+// take no token and count against no rate.
+pub(crate) fn is_probe(request: &Request) -> bool {
+    matches!(request.path.as_str(), "/healthz" | "/readyz")
+}
+
+// Whether `request` is a search, on a store of its own or of a data dir.
+pub(crate) fn is_search(request: &Request) -> bool {
+    request.path.trim_end_matches('/').ends_with("/search")
+}
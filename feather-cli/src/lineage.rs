@@ -0,0 +1,67 @@
+//! Record provenance: which records a memory was derived from.
+//!
+//! Summaries, re-embeddings and consolidations record their parents as
+//! `derived_from` edges, so the origin of any memory can be audited by
+//! walking them back (`DB::lineage`, `feather lineage`).
+
+use crate::{Edge, Metadata, DB};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Edge type linking a derived record to each of its parents.
+pub const DERIVED_FROM: &str = "derived_from";
+
+/// A record and, recursively, the records it was derived from.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Lineage {
+    pub id: u64,
+    /// None if the record no longer exists or was forgotten.
+    pub metadata: Option<Metadata>,
+    pub parents: Vec<Lineage>,
+    /// Already expanded elsewhere in the tree (a shared ancestor, or a
+    /// cycle); its parents are not repeated here.
+    pub repeated: bool,
+}
+
+impl Metadata {
+    /// Ids of the records this one was derived from.
+    pub fn derived_from(&self) -> Vec<u64> {
+        self.edges.iter().filter(|e| e.rel_type == DERIVED_FROM).map(|e| e.target).collect()
+    }
+}
+
+impl DB {
+    /// Record that `id` was derived from `parents`, which must exist. Parents
+    /// already recorded are kept; the rest are added.
+    pub fn add_derived_from(&self, id: u64, parents: &[u64]) -> anyhow::Result<()> {
+        let mut meta = self.get_metadata(id).ok_or_else(|| anyhow::anyhow!("no record {}", id))?;
+        for &parent in parents {
+            anyhow::ensure!(parent != id, "record {} cannot be derived from itself", id);
+            anyhow::ensure!(self.get_metadata(parent).is_some_and(|m| !m.is_forgotten()),
+                            "parent record {} does not exist", parent);
+            if !meta.derived_from().contains(&parent) {
+                meta.edges.push(Edge { target: parent, rel_type: DERIVED_FROM.to_string(), weight: 1.0 });
+            }
+        }
+        self.put_metadata(id, &meta)
+    }
+
+    /// The ancestry tree of `id` along `derived_from` edges, or None if the
+    /// record does not exist.
+    pub fn lineage(&self, id: u64) -> Option<Lineage> {
+        self.get_metadata(id).filter(|m| !m.is_forgotten())?;
+        Some(self.lineage_from(id, &mut HashSet::new()))
+    }
+
+    fn lineage_from(&self, id: u64, seen: &mut HashSet<u64>) -> Lineage {
+        let metadata = self.get_metadata(id).filter(|m| !m.is_forgotten());
+        if !seen.insert(id) {
+            return Lineage { id, metadata, parents: Vec::new(), repeated: true };
+        }
+        let parents = metadata.as_ref().map(Metadata::derived_from).unwrap_or_default()
+            .into_iter()
+            .map(|parent| self.lineage_from(parent, seen))
+            .collect();
+        Lineage { id, metadata, parents, repeated: false }
+    }
+}
@@ -0,0 +1,84 @@
+//! Advisory locking of a store's file across processes, so that two
+//! writers — two CLI invocations, or an agent and the CLI — cannot
+//! interleave saves and corrupt it.
+//!
+//! The lock is taken on a `<file>.lock` next to the store (saves replace
+//! the store itself by renaming over it), exclusive for a writer and
+//! shared among readers, and is held until the handle closes. The holder
+//! writes its pid in it for the `Locked` error others get. The operating
+//! system drops the lock when the holder exits, however it exits; the
+//! `.lock` file stays behind and is reused.
+//!
+//! Advisory: a process that does not take the lock is not kept out.
+//!
+//! A reader whose `.lock` cannot be created — the store sits on a
+//! read-only filesystem or directory — opens it read-only if it exists,
+//! and otherwise goes without a lock: no writer can get at the file
+//! there either.
+
+use crate::error::Locked;
+use std::fs::{File, TryLockError};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockMode {
+    /// Many readers at once, no writer.
+    Shared,
+    /// One writer, no one else.
+    Exclusive,
+}
+
+/// A held lock; released when dropped.
+#[derive(Debug)]
+pub struct FileLock {
+    // the lock lives as long as the open file; None for a reader that
+    // could not create one
+    _file: Option<File>,
+}
+
+/// The lock file of the store at `path`.
+pub fn lock_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".lock");
+    PathBuf::from(name)
+}
+
+impl FileLock {
+    /// Lock the store at `path`, failing with `Locked` at once rather than
+    /// waiting if another process holds it in a conflicting mode.
+    pub fn acquire(path: &Path, mode: LockMode) -> anyhow::Result<FileLock> {
+        let lock_path = lock_path(path);
+        let (mut file, writable) = match File::options().read(true).write(true).create(true).truncate(false).open(&lock_path) {
+            Ok(file) => (file, true),
+            Err(e) if mode == LockMode::Shared => {
+                match File::open(&lock_path) {
+                    Ok(file) => (file, false),
+                    Err(_) if !lock_path.exists() => return Ok(FileLock { _file: None }),
+                    Err(_) => anyhow::bail!("cannot open the lock file {:?}: {}", lock_path, e),
+                }
+            }
+            Err(e) => anyhow::bail!("cannot open the lock file {:?}: {}", lock_path, e),
+        };
+        let locked = match mode {
+            LockMode::Shared => file.try_lock_shared(),
+            LockMode::Exclusive => file.try_lock(),
+        };
+        match locked {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let mut pid = String::new();
+                let _ = file.read_to_string(&mut pid);
+                return Err(Locked { path: path.to_path_buf(), pid: pid.trim().parse().ok() }.into());
+            }
+            Err(TryLockError::Error(e)) => anyhow::bail!("cannot lock {:?}: {}", lock_path, e),
+        }
+        // best effort: the pid is only for the error message
+        if writable {
+            let _ = file.set_len(0)
+                .and_then(|_| file.rewind())
+                .and_then(|_| write!(file, "{}", std::process::id()));
+        }
+        Ok(FileLock { _file: Some(file) })
+    }
+}
@@ -1,92 +1,3081 @@
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
-use feather_db_cli::DB;
-use ndarray::Array1;
+use clap::{ArgGroup, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use feather_db_cli::filter::{Field, Op, Value};
+use feather_db_cli::config::{Config, Metric};
+use feather_db_cli::fsck::FsckReport;
+use feather_db_cli::migrate::Source;
+use feather_db_cli::progress::Bar;
+use feather_db_cli::{Access, Budget, Compression, CsvReader, Decay, Dedup, Device, EmbeddingProvider, Explanation, Filter, ForkStrategy, IndexField, IndexFile, IndexFormat, Inserted, JsonlReader, JsonlWriter, Lineage, MergePolicy, Metadata, Neighbor, OnDuplicate, OnMatch, OpenOptions, Progress, Projection, Rate, ReadOnly, RecordWriter, ScoringPolicy, SearchOptions, SortBy, SparseVector, Subgraph, Visibility, DB};
+use std::collections::HashMap;
+use ndarray::{Array1, Array2};
 
+// Commands not given a database use `FEATHER_DB`, else `db` in
+// ~/.config/feather/config.toml, which also holds defaults for k, the metric
+// of new files and the embedder (see `feather_db_cli::config`).
 #[derive(Parser)]
 #[command(name = "feather")]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Work inside this named collection of the file (independent id space)
+    #[arg(long, global = true)]
+    collection: Option<String>,
+    /// Unit-normalize every vector and query from now on (the file remembers)
+    #[arg(long, global = true)]
+    normalize: bool,
+    /// Open the database for reading only, alongside other readers; changes
+    /// are refused
+    #[arg(long, global = true, conflicts_with = "normalize")]
+    read_only: bool,
+    /// Who is making the changes, as the audit log records them (see `feather history`)
+    #[arg(long, global = true)]
+    actor: Option<String>,
+    /// Build the indexes from this seed, on one thread, so the same store gives
+    /// bit-identical indexes and rankings on every run (slower to open)
+    #[arg(long, global = true)]
+    index_seed: Option<u64>,
+    /// Embedding model that turns --text into a vector: a local Model2Vec
+    /// directory, or with --embed-api the model's name
+    #[arg(long, global = true)]
+    embed_model: Option<String>,
+    /// OpenAI-compatible API to embed --text with, e.g. https://api.openai.com/v1
+    /// (key from FEATHER_EMBED_API_KEY or OPENAI_API_KEY)
+    #[arg(long, global = true, requires = "embed_model")]
+    embed_api: Option<String>,
+    /// Embedding model the vectors and queries come from, e.g. text-embedding-3-small: a
+    /// new store records it, and one that records another refuses the command (text
+    /// embedded with --embed-model declares that model)
+    #[arg(long, global = true)]
+    model: Option<String>,
+    /// How search, get, stats and list print: text, one JSON document, or
+    /// JSON lines (goes before the command: `feather --format json search ...`)
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
 }
 
 #[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
 enum Commands {
-    New { path: PathBuf, #[arg(long)] dim: usize },
+    /// Create an empty store of vectors of --dim values
+    New {
+        path: PathBuf,
+        #[arg(long)] dim: usize,
+        /// Split the store into this many shard files in the directory PATH
+        #[arg(long)] shards: Option<usize>,
+        /// Pack the file's metadata, or all of it, when saving: none, metadata or all
+        #[arg(long, default_value = "none")] compress: Compression,
+        /// How vectors compare: l2, cosine (as --normalize) or hamming (binary vectors, a bit per dimension)
+        #[arg(long)] metric: Option<Metric>,
+    },
+    /// Add one record: a vector, with its content and metadata
     Add { 
         db: PathBuf, 
-        id: u64, 
-        #[arg(short)] npy: PathBuf,
+        /// Record id; without one (or --key) the store assigns the next free id and prints it
+        id: Option<u64>, 
+        /// String key to know the record by, e.g. a document id or UUID; adding it again replaces the record
+        #[arg(long, conflicts_with = "id")] key: Option<String>,
+        /// Vector file: .npy, .npz[:NAME] or .safetensors[:NAME]
+        #[arg(short, required_unless_present_any = ["text", "stdin"])] npy: Option<PathBuf>,
+        /// Text to store as the content and embed as the vector (needs --embed-model)
+        #[arg(long, conflicts_with_all = ["npy", "content"])] text: Option<String>,
+        /// Read the vector from stdin as raw little-endian float32
+        #[arg(long, conflicts_with_all = ["npy", "text"])] stdin: bool,
+        /// Values the --stdin vector must have
+        #[arg(long, requires = "stdin")] dim: Option<usize>,
         #[arg(long)] timestamp: Option<i64>,
         #[arg(long, default_value_t = 1.0)] importance: f32,
-        #[arg(long, default_value_t = 0)] context_type: u8,
+        /// Kind of record: semantic, episodic, procedural, tool_output, a
+        /// name registered with `feather context-types`, or a code
+        #[arg(long, default_value = "semantic")] context_type: String,
         #[arg(long)] source: Option<String>,
         #[arg(long)] content: Option<String>,
-        #[arg(long, default_value = "text")] modality: String,
+        /// Name of the vector -n holds
+        #[arg(long, visible_alias = "vector-name", default_value = "text")] modality: String,
+        /// A further named vector for the record, e.g. summary=summary.npy (repeatable)
+        #[arg(long = "vector", value_name = "NAME=FILE", value_parser = named_path)] vectors: Vec<(String, PathBuf)>,
+        /// Forget the record this many seconds after its timestamp
+        #[arg(long)] ttl_seconds: Option<i64>,
+        /// Ids of the records this one was derived from (e.g. a summary's sources)
+        #[arg(long, value_delimiter = ',')] derived_from: Vec<u64>,
+        /// Free-form JSON object stored with the record, e.g. '{"project": "atlas"}'
+        #[arg(long, value_parser = json_object)] meta: Option<JsonObject>,
+        /// Session (e.g. conversation) the record is scratch memory of; without, it is durable
+        #[arg(long)] session: Option<String>,
+        /// Label (e.g. user or tenant) that owns the record; `feather serve --access-file`
+        /// shows it only to tokens acting for it
+        #[arg(long)] owner: Option<String>,
+        /// Who may see the owned record: private (its owner) or public (everyone)
+        #[arg(long, value_parser = Visibility::parse, requires = "owner")] visibility: Option<Visibility>,
+        /// Sparse vector stored with the record, e.g. "12:0.5,873:1.2" or '{"12": 0.5}'
+        #[arg(long)] sparse: Option<SparseVector>,
+        /// Sparse vector set --sparse is stored in
+        #[arg(long, default_value = feather_db_cli::sparse::DEFAULT_NAME, requires = "sparse")]
+        sparse_name: String,
+        /// What to do if the id already has a record
+        #[arg(long, value_enum, default_value = "overwrite")] on_duplicate: DuplicatePolicy,
+        /// Skip the record if another one has the same content or vector
+        #[arg(long, value_enum, default_value = "off")] dedup: DedupMode,
+        /// Largest L2 distance at which `--dedup vector` calls two vectors the same
+        #[arg(long, default_value_t = 0.01)] dedup_epsilon: f32,
+        /// Merge a duplicate's metadata into the existing record instead of dropping it
+        #[arg(long)] dedup_merge: bool,
+    },
+    /// Add many records at once: a 2-D array with one vector per row, or a
+    /// directory of <id>.npy files
+    AddBatch {
+        db: PathBuf,
+        /// .npy, .npz[:NAME] or .safetensors[:NAME] matrix, or a directory
+        #[arg(long, required_unless_present = "stdin")] npy: Option<PathBuf>,
+        /// Read the vectors from stdin as raw little-endian float32, --dim values each
+        #[arg(long, conflicts_with = "npy", requires = "dim")] stdin: bool,
+        #[arg(long, requires = "stdin")] dim: Option<usize>,
+        /// 1-D .npy of integer ids, one per row [default: the metadata's ids,
+        /// else counting up from one past the largest id in use]
+        #[arg(long)] ids: Option<PathBuf>,
+        /// JSONL whose i-th line describes row i, in any shape `feather import` reads
+        #[arg(long)] meta: Option<PathBuf>,
+        /// Modality the vectors go into
+        #[arg(long, visible_alias = "vector-name", default_value = "text")] modality: String,
+        #[arg(long, default_value_t = feather_db_cli::import::DEFAULT_BATCH_SIZE)] batch_size: usize,
+        /// What to do with rows whose id already has a record
+        #[arg(long, value_enum, default_value = "overwrite")] on_duplicate: DuplicatePolicy,
+        /// Skip rows whose content or vector is already stored under another id
+        #[arg(long, value_enum, default_value = "off")] dedup: DedupMode,
+        /// Largest L2 distance at which `--dedup vector` calls two vectors the same
+        #[arg(long, default_value_t = 0.01)] dedup_epsilon: f32,
+        /// Merge a duplicate's metadata into the existing record instead of dropping it
+        #[arg(long)] dedup_merge: bool,
     },
+    /// Link one record to another, or every pair in a CSV
     Link {
+        db: PathBuf,
+        #[arg(required_unless_present = "file")] from: Option<u64>,
+        #[arg(required_unless_present = "file")] to: Option<u64>,
+        /// Make every link of this CSV instead: from, to[, rel_type][, weight]
+        #[arg(long, conflicts_with_all = ["from", "to"])] file: Option<PathBuf>,
+        /// Edge type, e.g. caused_by, follows, refines
+        #[arg(long = "type", default_value = feather_db_cli::graph::DEFAULT_REL_TYPE)] rel_type: String,
+        /// Edge weight; scales graph-boosted search along this link
+        #[arg(long, default_value_t = 1.0)] weight: f32,
+    },
+    /// Forget a record: it leaves search at once; `feather vacuum` reclaims the space
+    Delete {
+        db: PathBuf,
+        #[arg(required_unless_present = "key")] id: Option<u64>,
+        /// Delete the record known by this key instead
+        #[arg(long, conflicts_with = "id")] key: Option<String>,
+        /// Only if the record is still at this version, as `feather get` showed it
+        #[arg(long, value_name = "VERSION")] if_version: Option<u64>,
+    },
+    /// Archive a record: it keeps everything but leaves search until restored;
+    /// without an id, list the archived records
+    Archive {
+        db: PathBuf,
+        id: Option<u64>,
+    },
+    /// Bring an archived record back into search; with --from, rebuild DB, which must not
+    /// exist, from backups instead
+    Restore {
+        db: PathBuf,
+        #[arg(required_unless_present = "from")] id: Option<u64>,
+        /// A full backup (or snapshot), then the incremental backups taken since it, in order
+        #[arg(long, num_args = 1.., value_name = "BACKUP", conflicts_with = "id")] from: Vec<PathBuf>,
+    },
+    /// Change fields of a record's metadata; its vectors stay as they are
+    #[command(group(ArgGroup::new("changes").required(true).multiple(true)))]
+    Update {
+        db: PathBuf,
+        id: u64,
+        #[arg(long, group = "changes")] importance: Option<f32>,
+        #[arg(long, group = "changes")] confidence: Option<f32>,
+        /// A context type name or code
+        #[arg(long, group = "changes")] context_type: Option<String>,
+        #[arg(long, group = "changes")] source: Option<String>,
+        /// New content; the vectors are not re-embedded
+        #[arg(long, group = "changes")] content: Option<String>,
+        /// Unix seconds, YYYY-MM-DD, or e.g. 2d ago
+        #[arg(long, group = "changes", value_parser = time_point)] timestamp: Option<i64>,
+        /// Forget the record this many seconds after its timestamp (0: never)
+        #[arg(long, group = "changes")] ttl_seconds: Option<i64>,
+        /// Replace the free-form JSON object ('{}' removes it)
+        #[arg(long, group = "changes", value_parser = json_object)] meta: Option<JsonObject>,
+        /// Move the record into this session ('' makes it durable)
+        #[arg(long, group = "changes")] session: Option<String>,
+        /// Set an attribute, e.g. status=done (repeatable)
+        #[arg(long = "attribute", group = "changes", value_name = "KEY=VALUE", value_parser = key_value)]
+        attributes: Vec<(String, String)>,
+        /// Only if the record is still at this version, as `feather get` showed it
+        #[arg(long, value_name = "VERSION")] if_version: Option<u64>,
+    },
+    /// Mark a record as re-used: its timestamp becomes now and it counts as recalled
+    Touch {
+        db: PathBuf,
+        id: u64,
+    },
+    /// Remove the links from one record to another
+    Unlink {
+        db: PathBuf,
+        from: u64,
+        to: u64,
+        /// Only remove the link of this edge type
+        #[arg(long = "type")] rel_type: Option<String>,
+    },
+    /// Walk the links of a record, both ways, up to --depth hops
+    Links {
+        db: PathBuf,
+        id: u64,
+        #[arg(long, default_value_t = 1)] depth: usize,
+        /// Print the records reached as JSON
+        #[arg(long)] json: bool,
+    },
+    /// Show the shortest chain of links between two records, followed either way
+    Path {
         db: PathBuf,
         from: u64,
         to: u64,
+        /// Most links to follow
+        #[arg(long, default_value_t = feather_db_cli::subgraph::DEFAULT_MAX_DEPTH)] max_depth: usize,
+        /// Print it as a Graphviz DOT graph
+        #[arg(long)] dot: bool,
+    },
+    /// Show the records within --depth links of some records, and every link among them
+    Subgraph {
+        db: PathBuf,
+        #[arg(required = true, value_delimiter = ',')] ids: Vec<u64>,
+        #[arg(long, default_value_t = 1)] depth: usize,
+        /// Print it as a Graphviz DOT graph
+        #[arg(long)] dot: bool,
+    },
+    /// Show the records a memory was derived from, recursively
+    Lineage {
+        db: PathBuf,
+        id: u64,
+        /// Print the tree as JSON
+        #[arg(long)] json: bool,
     },
+    /// Print the k records nearest a query vector or text, best first
     Search { 
         db: PathBuf, 
-        #[arg(short)] npy: PathBuf, 
-        #[arg(long, default_value_t = 5)] k: usize,
-        #[arg(long)] type_filter: Option<u8>,
+        /// Query vector file: .npy, .npz[:NAME] or .safetensors[:NAME]
+        #[arg(short, required_unless_present_any = ["text", "sparse", "stdin", "like", "vector_expr"])] npy: Option<PathBuf>,
+        /// Read the query vector from stdin as raw little-endian float32
+        #[arg(long, conflicts_with = "npy")] stdin: bool,
+        /// Values the --stdin query must have
+        #[arg(long, requires = "stdin")] dim: Option<usize>,
+        /// Query by the centroid of these records' vectors ("more like these"); they are left out of the hits
+        #[arg(long, value_name = "IDS", value_delimiter = ',',
+              conflicts_with_all = ["npy", "stdin", "half_life"])]
+        like: Vec<u64>,
+        /// Query by a sum of records' vectors, e.g. "12 - 7 + 30" or "0.5*12 + 0.5*30"; they are left out of the hits
+        #[arg(long, value_name = "EXPR", conflicts_with_all = ["npy", "stdin", "like", "half_life"])]
+        vector_expr: Option<String>,
+        /// Hits to return [default: `k` in the config, else 5]
+        #[arg(long, visible_alias = "limit")] k: Option<usize>,
+        /// Skip this many of the best hits, to page through the results with --limit
+        #[arg(long, default_value_t = 0, conflicts_with = "half_life")]
+        offset: usize,
+        /// Only records of this kind (a context type name or code)
+        #[arg(long)] type_filter: Option<String>,
+        #[arg(long)] source_filter: Option<String>,
+        /// Which of the records' named vectors -n is matched against
+        #[arg(long, visible_alias = "vector-name", default_value = "text")] modality: String,
+        /// Rank by similarity × importance decayed with this half-life (e.g. 30d)
+        #[arg(long, value_parser = duration, conflicts_with_all = ["type_filter", "source_filter"])]
+        half_life: Option<f64>,
+        /// Blend in recency: 0 = similarity only, 1 = similarity × exp(-age/tau)
+        #[arg(long, conflicts_with = "half_life")]
+        recency_weight: Option<f32>,
+        /// Recency time constant for --recency-weight (e.g. 7d)
+        #[arg(long, value_parser = duration, default_value = "7d", requires = "recency_weight")]
+        tau: f64,
+        /// Re-rank a larger candidate pool for diversity (maximal marginal relevance)
+        #[arg(long, conflicts_with = "half_life")]
+        mmr: bool,
+        /// MMR trade-off: 1 = pure relevance, lower = more diverse
+        #[arg(long, default_value_t = 0.6, requires = "mmr")]
+        lambda: f32,
+        /// Only return hits scoring at least this (scores are 1 / (1 + distance))
+        #[arg(long)]
+        min_score: Option<f32>,
+        /// Only records stamped at or after this (Unix seconds, YYYY-MM-DD, or e.g. 7d ago)
+        #[arg(long, value_parser = time_point, conflicts_with = "half_life")]
+        after: Option<i64>,
+        /// Only records stamped at or before this (same forms as --after)
+        #[arg(long, value_parser = time_point, conflicts_with = "half_life")]
+        before: Option<i64>,
+        /// Only records at least this important
+        #[arg(long, conflicts_with = "half_life")]
+        min_importance: Option<f32>,
+        /// Only records at most this important
+        #[arg(long, conflicts_with = "half_life")]
+        max_importance: Option<f32>,
+        /// Metadata filter, e.g. "context_type in (1,2) and source != 'slack' and importance > 0.5"
+        #[arg(long, conflicts_with = "half_life")]
+        filter: Option<Filter>,
+        /// Only records of this session
+        #[arg(long, conflicts_with = "half_life")]
+        session: Option<String>,
+        /// Leave out the records of this session, e.g. the current conversation's
+        #[arg(long, conflicts_with = "half_life")]
+        exclude_session: Option<String>,
+        /// Leave out the records from this source (repeatable)
+        #[arg(long = "exclude-source", value_name = "SOURCE", conflicts_with = "half_life")]
+        exclude_sources: Vec<String>,
+        /// Leave out the records of this kind, a context type name or code (repeatable)
+        #[arg(long = "exclude-type", value_name = "TYPE", conflicts_with = "half_life")]
+        exclude_types: Vec<String>,
+        /// Leave out these records, e.g. the ones already in the prompt (repeatable, or comma-separated)
+        #[arg(long = "exclude-id", value_name = "ID", value_delimiter = ',', conflicts_with = "half_life")]
+        exclude_ids: Vec<u64>,
+        /// Consider archived records as well
+        #[arg(long, conflicts_with = "half_life")]
+        include_archived: bool,
+        /// Only consider what a caller acting for these labels may see: the records they
+        /// own, public ones and unowned ones (repeatable, or comma-separated)
+        #[arg(long = "as-owner", value_name = "LABEL", value_delimiter = ',', conflicts_with = "half_life")]
+        as_owners: Vec<String>,
+        /// Measure the query against every record rather than search the approximate index
+        #[arg(long, conflicts_with = "half_life")]
+        exact: bool,
+        /// Rank every record by the first N dimensions only, then re-score the best candidates
+        /// in full (for Matryoshka embeddings)
+        #[arg(long, value_name = "N", conflicts_with_all = ["exact", "half_life"])]
+        truncate_dim: Option<usize>,
+        /// Keywords to match against record content (BM25); without -n, rank by keywords
+        /// alone, or with --embed-model by the text's embedding
+        #[arg(long, conflicts_with = "half_life")]
+        text: Option<String>,
+        /// Sparse query vector (e.g. "12:0.5,873:1.2"), ranked by dot product; without -n, rank by it alone
+        #[arg(long, conflicts_with = "half_life")]
+        sparse: Option<SparseVector>,
+        /// Sparse vector set --sparse is matched against
+        #[arg(long, default_value = feather_db_cli::sparse::DEFAULT_NAME, requires = "sparse")]
+        sparse_name: String,
+        /// Fuse the --text keyword and --sparse rankings with the -n vector ranking
+        #[arg(long)]
+        hybrid: bool,
+        /// Share of hybrid relevance given to the keyword match (0..=1)
+        #[arg(long, default_value_t = feather_db_cli::search::DEFAULT_TEXT_WEIGHT, requires_all = ["hybrid", "text"])]
+        text_weight: f32,
+        /// Share of hybrid relevance given to the sparse match (0..=1)
+        #[arg(long, default_value_t = feather_db_cli::search::DEFAULT_SPARSE_WEIGHT, requires_all = ["hybrid", "sparse"])]
+        sparse_weight: f32,
+        /// Boost memories linked to the hits: share of activation passed along each link (0..=1)
+        #[arg(long, conflicts_with = "half_life")]
+        graph_boost: Option<f32>,
+        /// Links spreading activation travels from the hits
+        #[arg(long, default_value_t = feather_db_cli::search::DEFAULT_HOPS, requires = "graph_boost")]
+        hops: usize,
+        /// Keep at most N hits from any one source, e.g. chunks of one document
+        #[arg(long, value_name = "N", conflicts_with = "half_life")]
+        max_per_source: Option<usize>,
+        /// Follow each hit with its linked records that have a vector in this modality (repeatable)
+        #[arg(long = "include-linked", value_name = "MODALITY", conflicts_with = "half_life")]
+        include_linked: Vec<String>,
+        /// Score by a weighted mean, e.g. "similarity=0.6,importance=0.2,recency=0.2,graph=0"
+        /// [default: the store's, see `feather scoring`]
+        #[arg(long, conflicts_with_all = ["half_life", "recency_weight", "graph_boost"])]
+        scoring: Option<ScoringPolicy>,
+        /// Break each hit's score into the signals behind it, and list the candidates dropped and why
+        #[arg(long, conflicts_with = "half_life")]
+        explain: bool,
+        /// Another query vector file to rank by, e.g. a reformulation (repeatable); its
+        /// ranking and -n's are fused by reciprocal rank fusion
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["half_life", "explain"])]
+        fuse: Vec<PathBuf>,
+        /// Write the hits, with their records and scores, to this Arrow IPC file instead of printing them
+        #[arg(long, value_name = "FILE", conflicts_with = "explain")]
+        arrow: Option<PathBuf>,
+        /// Print each hit's whole content, not just its start
+        #[arg(long)]
+        show_content: bool,
+        /// Print each hit's importance, type, recall count, attributes and JSON object
+        #[arg(long)]
+        show_meta: bool,
+        /// Search the store as it was then, from its last snapshot by that time
+        /// (Unix seconds, YYYY-MM-DD or "7d ago"; see `feather snapshots`)
+        #[arg(long, value_parser = time_point)]
+        as_of: Option<i64>,
+    },
+    /// Print every record within a distance of a query vector, nearest first, rather than the best k
+    Near {
+        db: PathBuf,
+        /// Query vector file: .npy, .npz[:NAME] or .safetensors[:NAME]
+        #[arg(short)] npy: PathBuf,
+        /// Largest L2 distance from the query a record may lie at
+        #[arg(long)] radius: f32,
+        /// Which of the records' named vectors -n is matched against
+        #[arg(long, visible_alias = "vector-name", default_value = "text")] modality: String,
+        /// Print each record's full content
+        #[arg(long)] show_content: bool,
+        /// Print each record's importance, type, recall count, attributes and JSON object
+        #[arg(long)] show_meta: bool,
+    },
+    /// The records that fit a prompt's token budget, packed by score per token
+    Context {
+        db: PathBuf,
+        /// Query vector file: .npy, .npz[:NAME] or .safetensors[:NAME]
+        #[arg(short, required_unless_present = "text")] npy: Option<PathBuf>,
+        /// Keywords ranked along with -n (hybrid); without -n, embedded with --embed-model as the query
+        #[arg(long)] text: Option<String>,
+        /// Tokens the records' content may take in all, at about 4 characters a token
+        #[arg(long)] budget: usize,
+        /// Which of the records' named vectors -n is matched against
+        #[arg(long, visible_alias = "vector-name", default_value = "text")] modality: String,
+        /// Print only the records' content, one `- content` line each, ready for a prompt
+        #[arg(long, conflicts_with_all = ["show_content", "show_meta"])] lines: bool,
+        /// Print each record's full content
+        #[arg(long)] show_content: bool,
+        /// Print each record's importance, type, recall count, attributes and JSON object
+        #[arg(long)] show_meta: bool,
+    },
+    /// Print one record: its metadata, vectors and links
+    Get {
+        db: PathBuf,
+        #[arg(required_unless_present = "key")] id: Option<u64>,
+        /// Print the record known by this key instead
+        #[arg(long, conflicts_with = "id")] key: Option<String>,
+    },
+    /// List records in id order, a page at a time
+    Scan {
+        db: PathBuf,
+        /// Start after this id (the cursor printed at the end of the previous page)
+        #[arg(long)] cursor: Option<u64>,
+        #[arg(long, default_value_t = 20)] limit: usize,
+        /// Metadata filter, as for search
+        #[arg(long)] filter: Option<Filter>,
+        /// Print the page as JSON
+        #[arg(long)] json: bool,
+        /// Write the page's records to this Arrow IPC file; the cursor is still printed
+        #[arg(long, value_name = "FILE", conflicts_with = "json")] arrow: Option<PathBuf>,
+    },
+    /// Browse records, newest, most important or most recalled first
+    List {
+        db: PathBuf,
+        #[arg(long, value_enum, default_value_t = ListOrder::Recency)] sort: ListOrder,
+        #[arg(long, default_value_t = 20)] limit: usize,
+        /// Skip this many records, to page through the list with --limit
+        #[arg(long, default_value_t = 0)] offset: usize,
+        /// Only records of this kind (a context type name or code)
+        #[arg(long)] type_filter: Option<String>,
         #[arg(long)] source_filter: Option<String>,
+        /// Only records stamped at or after this (Unix seconds, YYYY-MM-DD, or e.g. 7d ago)
+        #[arg(long, value_parser = time_point)] after: Option<i64>,
+        /// Only records stamped at or before this (same forms as --after)
+        #[arg(long, value_parser = time_point)] before: Option<i64>,
+        /// Metadata filter, as for search
+        #[arg(long)] filter: Option<Filter>,
+    },
+    /// The newest records, with no query vector needed
+    Recent {
+        db: PathBuf,
+        #[arg(long, default_value_t = 10)] k: usize,
+        /// Only records of this kind (a context type name or code)
+        #[arg(long)] type_filter: Option<String>,
+        /// Only records from this source
+        #[arg(long)] source: Option<String>,
+        /// Metadata filter, as for search
+        #[arg(long)] filter: Option<Filter>,
+    },
+    /// Rewrite the file without forgotten records, reclaiming their space
+    Vacuum {
+        db: PathBuf,
+    },
+    /// Check that a store is fit to use: permissions, lock, disk space, WAL size, index
+    /// health and the config file, each with what to do about a problem
+    Doctor {
+        db: PathBuf,
+    },
+    /// Check the file and its WAL for damage: header, sections, HNSW graphs,
+    /// vectors without records, dangling links
+    Fsck {
+        db: PathBuf,
+        /// Fix what can be fixed: forget broken records, drop dangling links
+        /// and bad WAL entries, rebuild broken graphs
+        #[arg(long)] repair: bool,
+    },
+    /// Fade importance by inactivity: halve it every half-life (e.g. 30d)
+    Decay {
+        db: PathBuf,
+        #[arg(long, value_parser = duration)] half_life: f64,
+        /// Never decay importance below this
+        #[arg(long, default_value_t = 0.0)] floor: f32,
+        /// Report what would change without writing
+        #[arg(long)] dry_run: bool,
+    },
+    /// Forget records whose ttl has run out (every command also does this on open)
+    Expire {
+        db: PathBuf,
+    },
+    /// Run a maintenance pass: expire, decay if asked, compact once enough of an index
+    /// is deleted, checkpoint
+    Maintain {
+        db: PathBuf,
+        /// Also decay importance with this half-life (e.g. 30d), as `feather decay` does
+        #[arg(long, value_parser = duration)] half_life: Option<f64>,
+        /// Compact once this share of an index's vectors are deleted
+        #[arg(long, default_value_t = feather_db_cli::maintenance::DEFAULT_COMPACT_RATIO)] compact_ratio: f32,
+    },
+    /// Branch a store into a new file that shares its data copy-on-write
+    Fork {
+        db: PathBuf,
+        path: PathBuf,
+    },
+    /// Project a modality's vectors to fewer dims (PCA, OPQ or truncation), in place
+    Redim {
+        db: PathBuf,
+        #[arg(long)] to: usize,
+        #[arg(long, value_enum, default_value_t = RedimMethod::Pca)] method: RedimMethod,
+        #[arg(long, default_value = "text")] modality: String,
+        /// Vectors sampled to fit the PCA basis
+        #[arg(long, default_value_t = 10_000)] sample: usize,
+        /// Product-quantizer subspaces the OPQ rotation is fitted for
+        #[arg(long, default_value_t = 8)] subspaces: usize,
+    },
+    /// Serve the store as JSON over HTTP (/add, /search, /get/{id}, /delete/{id}), with Prometheus /metrics
+    Serve {
+        #[arg(required_unless_present = "data_dir")] db: Option<PathBuf>,
+        /// Serve every store in DIR, each as /db/{name}/..., with PUT and DELETE /db/{name}
+        /// to create and drop them
+        #[arg(long, value_name = "DIR", conflicts_with_all = ["db", "follow", "replicate_to", "webhooks", "warm"])]
+        data_dir: Option<PathBuf>,
+        #[arg(long, default_value = "127.0.0.1:8080")] http: String,
+        /// Stream every write to the read replica listening at ADDR (repeatable); the
+        /// replica must share --api-key-file
+        #[arg(long = "replicate-to", value_name = "ADDR", requires = "api_key_file")] replicate_to: Vec<String>,
+        /// Be a read replica: keep DB a copy of the primary that connects to [HOST:]PORT
+        /// (127.0.0.1 unless HOST is given) with a key of --api-key-file
+        #[arg(long, value_name = "[HOST:]PORT", conflicts_with = "replicate_to", requires = "api_key_file")]
+        follow: Option<String>,
+        /// Read the whole store into memory before taking requests, so the
+        /// first queries do not pay for it
+        #[arg(long, conflicts_with = "follow")] warm: bool,
+        /// POST the records each request adds or deletes, as JSON, to URL (repeatable)
+        #[arg(long = "webhook", value_name = "URL", conflicts_with = "follow")] webhooks: Vec<String>,
+        /// Answer only requests with a bearer token listed in FILE, one `TOKEN LABEL[,LABEL...]`
+        /// per line; each sees and writes only what its labels may (see `feather add --owner`)
+        #[arg(long, value_name = "FILE")] access_file: Option<PathBuf>,
+        /// Answer only requests with a bearer token listed in FILE, one API key per line;
+        /// each key sees and writes everything
+        #[arg(long, value_name = "FILE")] api_key_file: Option<PathBuf>,
+        /// Serve HTTPS with this PEM certificate chain, through the feather-https binary
+        #[arg(long, value_name = "FILE", requires = "tls_key", conflicts_with = "follow")] tls_cert: Option<PathBuf>,
+        /// The PEM private key of --tls-cert
+        #[arg(long, value_name = "FILE", requires = "tls_cert")] tls_key: Option<PathBuf>,
+        /// Requests each client (its token, else its IP) may make, e.g. 20/s or 600/min;
+        /// more are answered 429
+        #[arg(long, value_name = "RATE", value_parser = Rate::parse)] rate_limit: Option<Rate>,
+        /// Searches waiting to be answered at once; more are answered 503
+        #[arg(long, value_name = "N")] max_searches: Option<usize>,
+        /// Stop ranking a search after MS milliseconds, replying with the hits found so far
+        /// flagged as partial
+        #[arg(long, value_name = "MS")] search_budget: Option<u64>,
+        /// Maintain the store every INTERVAL (e.g. 10m), between requests or while idle:
+        /// expire, compact, checkpoint (see `feather maintain`)
+        #[arg(long, value_name = "INTERVAL", value_parser = duration, conflicts_with_all = ["data_dir", "follow"])]
+        maintenance: Option<f64>,
+        /// Also decay importance with this half-life on each maintenance pass
+        #[arg(long, value_name = "HALF_LIFE", value_parser = duration, requires = "maintenance")]
+        maintenance_half_life: Option<f64>,
+    },
+    /// Keep the store open and run add, search, get, link and stats commands interactively
+    Repl { db: PathBuf },
+    /// Serve the store as long-term memory to an MCP client over stdio
+    Mcp { db: PathBuf },
+    /// Write a copy of the store with one modality's vectors reduced to fewer dims
+    Reduce {
+        db: PathBuf,
+        #[arg(long)] dim: usize,
+        #[arg(short)] out: PathBuf,
+        #[arg(long, value_enum, default_value_t = RedimMethod::Pca)] method: RedimMethod,
+        #[arg(long, default_value = "text")] modality: String,
+        /// Vectors sampled to fit the PCA basis
+        #[arg(long, default_value_t = 10_000)] sample: usize,
+        /// Product-quantizer subspaces the OPQ rotation is fitted for
+        #[arg(long, default_value_t = 8)] subspaces: usize,
+    },
+    /// List the records whose vectors sit far from their nearest neighbours
+    Outliers {
+        db: PathBuf,
+        #[arg(long, default_value_t = 10)] k: usize,
+        #[arg(long, default_value_t = 3.0)] threshold: f32,
+        #[arg(long, default_value = "text")] modality: String,
+        /// Tag each outlier with the attribute quarantine=outlier
+        #[arg(long)] quarantine: bool,
+    },
+    /// Copy the records and links of other stores into this one
+    Merge {
+        dst: PathBuf,
+        #[arg(required = true)] srcs: Vec<PathBuf>,
+        /// What to do when a source id already exists in the destination
+        #[arg(long, value_enum, default_value_t = OnConflict::Skip)] on_conflict: OnConflict,
+    },
+    /// Merge a fork's changes back into the store it was forked from
+    MergeFork {
+        db: PathBuf,
+        fork: PathBuf,
+        /// How to settle records changed on both sides
+        #[arg(long, value_enum, default_value_t = ForkMergeStrategy::NewestWins)] strategy: ForkMergeStrategy,
+        /// Where `manual` writes the fork's side of each conflict, as JSONL
+        /// for `feather import` (default: <fork>.conflicts.jsonl)
+        #[arg(long)] conflicts: Option<PathBuf>,
+    },
+    /// Show the record count, each modality with its dims, and query drift
+    Stats {
+        db: PathBuf,
+        /// Relative centroid shift that counts as query drift
+        #[arg(long, default_value_t = feather_db_cli::drift::DEFAULT_THRESHOLD)] drift_threshold: f32,
+        /// Start a new drift observation window after reporting
+        #[arg(long)] reset_drift: bool,
+    },
+    /// List the context type names, or name a custom code
+    ContextTypes {
+        db: PathBuf,
+        /// Register NAME for a custom code, e.g. decision=10
+        #[arg(long, value_parser = named_code)] add: Vec<(String, u8)>,
+    },
+    /// List the modalities, or register one ahead of its first vector
+    Modalities {
+        db: PathBuf,
+        /// Create the index of modality NAME with this dimension, e.g. image=512
+        #[arg(long, value_parser = named_dim)] add: Vec<(String, usize)>,
+    },
+    /// Show, set or clear the store's default scoring policy for ranked search
+    Scoring {
+        db: PathBuf,
+        /// Make this the default, e.g. "similarity=0.7,importance=0.3"
+        #[arg(long, conflicts_with = "clear")] set: Option<ScoringPolicy>,
+        /// Go back to ranking by similarity and the search options
+        #[arg(long)] clear: bool,
+    },
+    /// Fold clusters of near-duplicate records into one new record each,
+    /// derived from them; the originals are marked superseded
+    Consolidate {
+        db: PathBuf,
+        /// Cosine similarity to the cluster's leader at which records count
+        /// as near duplicates
+        #[arg(long, default_value_t = feather_db_cli::consolidate::DEFAULT_THRESHOLD)] threshold: f32,
+        #[arg(long, default_value = "text")] modality: String,
+        /// Shell command writing the new record's content: it reads the
+        /// members' contents as a JSON array on stdin (default: the leader's)
+        #[arg(long, value_name = "CMD")] summarize: Option<String>,
+        /// Forget the superseded records too
+        #[arg(long)] forget: bool,
+        /// List the clusters without writing anything
+        #[arg(long, conflicts_with_all = ["summarize", "forget"])] dry_run: bool,
+    },
+    /// Report groups of near-duplicate records: the same content, or
+    /// near-identical vectors
+    Dupes {
+        db: PathBuf,
+        /// Cosine similarity at which records count as duplicates
+        #[arg(long, default_value_t = feather_db_cli::dupes::DEFAULT_THRESHOLD)] threshold: f32,
+        #[arg(long, default_value = "text")] modality: String,
+        /// Forget every copy but the oldest of each group
+        #[arg(long)] delete: bool,
+    },
+    /// Group records into k clusters by their vectors, label each record
+    /// with its cluster, and show the records nearest each cluster's centre
+    Cluster {
+        db: PathBuf,
+        #[arg(long, default_value_t = 20)] k: usize,
+        #[arg(long, default_value = "text")] modality: String,
+        /// Lloyd rounds at most
+        #[arg(long, default_value_t = feather_db_cli::cluster::DEFAULT_ITERATIONS)] iterations: usize,
+        /// Records shown per cluster
+        #[arg(long, default_value_t = 3)] show: usize,
+        /// Find the clusters without labelling the records
+        #[arg(long)] dry_run: bool,
+    },
+    /// List the sessions with scratch records, or forget one's records
+    Sessions {
+        db: PathBuf,
+        /// Forget every record of this session
+        #[arg(long, value_name = "SESSION")] forget: Option<String>,
+    },
+    /// List the sources records come from, with their record counts
+    Sources {
+        db: PathBuf,
+    },
+    /// Move every record of one source to another, e.g. to fix a typo
+    RenameSource {
+        db: PathBuf,
+        from: String,
+        to: String,
+    },
+    /// Show how the store, or one record, changed: every add, update, delete
+    /// and link the audit log holds, oldest first
+    History {
+        db: PathBuf,
+        id: Option<u64>,
+        /// Start logging changes to DB.audit, for every later writer
+        #[arg(long, conflicts_with_all = ["id", "disable"])] enable: bool,
+        /// Stop logging changes; the log is kept
+        #[arg(long, conflicts_with = "id")] disable: bool,
+    },
+    /// Show the snapshots kept of the store, or how many every save keeps
+    Snapshots {
+        db: PathBuf,
+        /// Keep a snapshot of every save from now on, the newest N; 0 stops
+        #[arg(long, value_name = "N")] keep: Option<usize>,
+    },
+    /// Back the store up to OUT: a checkpointed copy, or with --incremental what
+    /// changed since an earlier backup
+    Backup {
+        db: PathBuf,
+        out: PathBuf,
+        /// Write only the changes since --since
+        #[arg(long, requires = "since")] incremental: bool,
+        /// The id an earlier backup printed (the last full one's, or the newest), or a
+        /// snapshot's, as `feather snapshots` lists it
+        #[arg(long, value_name = "ID", requires = "incremental")] since: Option<i64>,
+    },
+    /// Show, set or lift the store's memory budget; over it, saves evict the
+    /// records least worth keeping (low importance, long idle, rarely recalled)
+    Budget {
+        db: PathBuf,
+        /// Most live records to keep
+        #[arg(long, conflicts_with = "clear")] max_records: Option<usize>,
+        /// Most bytes of live data to keep, e.g. 500MB
+        #[arg(long, value_parser = feather_db_cli::budget::parse_bytes, conflicts_with = "clear")]
+        max_bytes: Option<u64>,
+        /// Lift the caps
+        #[arg(long)] clear: bool,
+    },
+    /// Show or switch the optional secondary indexes on source and timestamp
+    Index {
+        db: PathBuf,
+        /// Build and keep an index on this field
+        #[arg(long, value_enum)] add: Vec<IndexedField>,
+        /// Drop the index on this field
+        #[arg(long, value_enum)] drop: Vec<IndexedField>,
+    },
+    /// Add the records of a JSONL, CSV, Parquet or Arrow file, or of another vector database
+    Import {
+        db: PathBuf,
+        /// The file to read; with --from, the Qdrant server's URL or the
+        /// Chroma persistent directory
+        file: PathBuf,
+        /// Input format; inferred from the file extension when omitted
+        #[arg(long, value_enum)] format: Option<ImportFormat>,
+        /// Move a collection over from another vector database instead
+        #[arg(long, value_enum, conflicts_with = "format")] from: Option<MigrateFrom>,
+        /// The collection to read with --from [default: the --collection
+        /// imported into]
+        #[arg(long, requires = "from")] source_collection: Option<String>,
+        /// Modality for rows carrying a bare `vector` / `embedding` field
+        #[arg(long, default_value = "text")] modality: String,
+        #[arg(long, default_value_t = feather_db_cli::import::DEFAULT_BATCH_SIZE)] batch_size: usize,
+        /// What to do with rows whose id already has a record
+        #[arg(long, value_enum, default_value = "overwrite")] on_duplicate: DuplicatePolicy,
+        /// Skip rows whose content or first vector is already stored under another id
+        #[arg(long, value_enum, default_value = "off")] dedup: DedupMode,
+        /// Largest L2 distance at which `--dedup vector` calls two vectors the same
+        #[arg(long, default_value_t = 0.01)] dedup_epsilon: f32,
+        /// Merge a duplicate's metadata into the existing record instead of dropping it
+        #[arg(long)] dedup_merge: bool,
+        /// CSV of links to make once the records are in: from, to[, rel_type][, weight]
+        #[arg(long)] links: Option<PathBuf>,
+    },
+    /// Store a document as overlapping chunks, embedded with --embed-model and linked in order
+    Ingest {
+        db: PathBuf,
+        #[arg(long)] file: PathBuf,
+        /// Most characters per chunk
+        #[arg(long, default_value_t = 512)] chunk_size: usize,
+        /// Characters each chunk repeats from the end of the one before
+        #[arg(long, default_value_t = 64)] overlap: usize,
+        /// Id of the first chunk [default: one past the largest id in use]
+        #[arg(long)] start_id: Option<u64>,
+        /// Source recorded on the chunks [default: the file's path]
+        #[arg(long)] source: Option<String>,
+    },
+    /// Copy the store to OUT with every record's content embedded by another model, under
+    /// the same ids, metadata and links; records without content are reported and left out
+    Reembed {
+        db: PathBuf,
+        #[arg(short, value_name = "OUT")] out: PathBuf,
+        /// File naming the new embedder as the config file does (embed_model, and
+        /// embed_api for an API) [default: --embed-model and --embed-api]
+        #[arg(long, value_name = "CFG")] provider: Option<PathBuf>,
+        /// Modality whose vectors are replaced; the others are copied
+        #[arg(long, default_value = "text")] modality: String,
+        /// Records embedded per call to the embedder
+        #[arg(long, default_value_t = feather_db_cli::reembed::DEFAULT_BATCH)] batch_size: usize,
+    },
+    /// Build a new store from a vector array plus optional metadata and links CSVs
+    Bootstrap {
+        db: PathBuf,
+        /// 2-D array, one vector per row: .npy, .npz[:NAME] or .safetensors[:NAME]
+        #[arg(long)] vectors: PathBuf,
+        /// CSV describing row i of the array on its i-th row; rows without an
+        /// `id` column get id i + 1
+        #[arg(long)] meta: Option<PathBuf>,
+        /// CSV of links: from, to[, rel_type][, weight]
+        #[arg(long)] links: Option<PathBuf>,
+        #[arg(long, default_value = "text")] modality: String,
+        #[arg(long, default_value_t = feather_db_cli::bootstrap::DEFAULT_BATCH_SIZE)] batch_size: usize,
+    },
+    /// Measure insert throughput, search latency and recall versus brute
+    /// force in a scratch store, per HNSW search beam width (ef)
+    Bench {
+        /// Vectors to insert (random ones)
+        #[arg(short = 'n', long, default_value_t = feather_db_cli::bench::DEFAULT_COUNT, conflicts_with = "vectors")]
+        count: usize,
+        /// Dimension of the random vectors
+        #[arg(long, default_value_t = 128, conflicts_with = "vectors")]
+        dim: usize,
+        /// Real vectors instead, one per row (.npy, .npz[:NAME] or .safetensors[:NAME]);
+        /// the last --queries rows are held out as the queries
+        #[arg(long)] vectors: Option<PathBuf>,
+        /// Queries to run per ef
+        #[arg(long, default_value_t = feather_db_cli::bench::DEFAULT_QUERIES)]
+        queries: usize,
+        #[arg(short, default_value_t = 10)] k: usize,
+        /// Search beam widths to compare, comma-separated
+        #[arg(long, value_delimiter = ',', default_values_t = [10, 50, 100, 200])]
+        ef: Vec<usize>,
+        /// Seed of the random vectors
+        #[arg(long, default_value_t = 42)] seed: u64,
+        #[arg(long, default_value_t = feather_db_cli::bootstrap::DEFAULT_BATCH_SIZE)] batch_size: usize,
+    },
+    /// Answer many query vectors at once by an exact scan, on the CPU or a
+    /// GPU; one line of hits per query
+    SearchBatch {
+        db: PathBuf,
+        /// Query vectors, one per row (.npy, .npz[:NAME] or .safetensors[:NAME])
+        #[arg(long)] queries: PathBuf,
+        #[arg(short, default_value_t = 10)] k: usize,
+        #[arg(long, default_value = "text")] modality: String,
+        /// Where to measure: gpu needs a build with --features
+        /// experimental-gpu and a CUDA device, else runs on the CPU
+        #[arg(long, value_enum, default_value = "cpu")] device: DeviceArg,
+        /// Metadata filter, as for search
+        #[arg(long)] filter: Option<Filter>,
+        /// Only return hits scoring at least this
+        #[arg(long)] min_score: Option<f32>,
+    },
+    /// Measure recall@k and search latency of a store on its own data,
+    /// against ground truth or an exact brute-force scan, per ef
+    Eval {
+        db: PathBuf,
+        /// Query vectors, one per row (.npy, .npz[:NAME] or .safetensors[:NAME])
+        #[arg(long)] queries: PathBuf,
+        /// .npy of each query's true nearest ids, nearest first, one row per
+        /// query; without it the store's vectors are scanned exactly
+        #[arg(long)] ground_truth: Option<PathBuf>,
+        #[arg(short, default_value_t = 10)] k: usize,
+        /// Search beam widths to compare, comma-separated [default: the
+        /// modality's tuned ef, else 50]
+        #[arg(long, value_delimiter = ',')] ef: Vec<usize>,
+        #[arg(long, default_value = "text")] modality: String,
+    },
+    /// Find the smallest ef reaching a target recall on a sample of the
+    /// store's vectors and store it as the modality's default
+    Tune {
+        db: PathBuf,
+        #[arg(long, default_value_t = 0.95)] target_recall: f64,
+        #[arg(short, default_value_t = 10)] k: usize,
+        /// Vectors to query with
+        #[arg(long, default_value_t = feather_db_cli::tune::DEFAULT_SAMPLE)] sample: usize,
+        #[arg(long, default_value = "text")] modality: String,
+        /// Report the ef without storing it
+        #[arg(long)] dry_run: bool,
+        /// Forget the stored ef instead
+        #[arg(long, conflicts_with_all = ["target_recall", "k", "sample", "dry_run"])] clear: bool,
+    },
+    /// Write every record, with its vectors and metadata, as JSONL, Parquet or Arrow
+    Export {
+        db: PathBuf,
+        #[arg(long, value_enum, default_value_t = ExportFormat::Jsonl)] format: ExportFormat,
+        #[arg(short)] out: PathBuf,
+        /// Also write every link here, as the CSV `feather import --links` reads
+        #[arg(long)] links: Option<PathBuf>,
+    },
+    /// Add the vectors of an hnswlib or FAISS index file, with metadata by
+    /// id from an optional sidecar file
+    ImportIndex {
+        db: PathBuf,
+        file: PathBuf,
+        #[arg(long, value_enum)] format: IndexFileFormat,
+        /// Metadata rows by id: JSONL, or CSV with an `id` column
+        #[arg(long)] meta: Option<PathBuf>,
+        /// Modality the vectors go into
+        #[arg(long, default_value = "text")] modality: String,
+        #[arg(long, default_value_t = feather_db_cli::import::DEFAULT_BATCH_SIZE)] batch_size: usize,
+    },
+    /// Write a modality's vectors as an hnswlib or FAISS index file
+    ExportIndex {
+        db: PathBuf,
+        #[arg(long, value_enum)] format: IndexFileFormat,
+        #[arg(short)] out: PathBuf,
+        /// Also write the records' metadata here, as JSONL by id
+        #[arg(long)] meta: Option<PathBuf>,
         #[arg(long, default_value = "text")] modality: String,
     },
 }
 
-fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
-    match cli.command {
-        Commands::New { path, dim } => {
-            DB::open(&path, dim).ok_or_else(|| anyhow::anyhow!("Failed to create DB"))?;
-            println!("Created: {:?}", path);
-        }
-        Commands::Add { db, id, npy, timestamp, importance, context_type, source, content, modality } => {
-            let arr: Array1<f32> = ndarray_npy::read_npy(&npy)?;
-            let dim = arr.len();
-            let db = DB::open(&db, dim).ok_or_else(|| anyhow::anyhow!("Open failed"))?;
-            
-            let ts = timestamp.unwrap_or_else(|| {
-                std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs() as i64
-            });
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+    Ndjson,
+}
 
-            db.add_with_meta(
-                id, arr.as_slice().unwrap(), 
-                ts, importance, context_type, 
-                source.as_deref(), content.as_deref(), Some(&modality)
-            );
-            db.save();
-            println!("Added ID {} to modality '{}'", id, modality);
-        }
-        Commands::Link { db, from, to } => {
-            let db = DB::open(&db, 0).ok_or_else(|| anyhow::anyhow!("Open failed"))?;
-            db.link(from, to);
-            db.save();
-            println!("Linked {} -> {}", from, to);
-        }
-        Commands::Search { db, npy, k, type_filter, source_filter, modality } => {
-            let arr: Array1<f32> = ndarray_npy::read_npy(&npy)?;
-            let dim = arr.len();
-            let db = DB::open(&db, dim).ok_or_else(|| anyhow::anyhow!("Open failed"))?;
-            
-            let (ids, dists) = if type_filter.is_some() || source_filter.is_some() {
-                db.search_with_filter(arr.as_slice().unwrap(), k, type_filter, source_filter.as_deref(), Some(&modality))
-            } else {
-                db.search(arr.as_slice().unwrap(), k, Some(&modality))
-            };
+#[derive(Clone, Copy, ValueEnum)]
+enum ImportFormat {
+    Jsonl,
+    Csv,
+    Parquet,
+    Arrow,
+}
 
-            for (id, dist) in ids.iter().zip(dists.iter()) {
-                if *id != 0 || *dist != 0.0 {
-                    println!("ID: {}  Score: {:.4}", id, dist);
-                }
+#[derive(Clone, Copy, ValueEnum)]
+enum MigrateFrom {
+    Qdrant,
+    Chroma,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    Jsonl,
+    Parquet,
+    Arrow,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum IndexFileFormat {
+    Hnswlib,
+    Faiss,
+}
+
+impl IndexFileFormat {
+    fn format(self) -> IndexFormat {
+        match self {
+            IndexFileFormat::Hnswlib => IndexFormat::Hnswlib,
+            IndexFileFormat::Faiss => IndexFormat::Faiss,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OnConflict {
+    Skip,
+    Overwrite,
+    Remap,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DeviceArg {
+    Cpu,
+    Gpu,
+}
+
+impl DeviceArg {
+    fn device(self) -> Device {
+        match self {
+            DeviceArg::Cpu => Device::Cpu,
+            DeviceArg::Gpu => Device::Gpu,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DuplicatePolicy {
+    Error,
+    Overwrite,
+    Ignore,
+}
+
+impl DuplicatePolicy {
+    fn policy(self) -> OnDuplicate {
+        match self {
+            DuplicatePolicy::Error => OnDuplicate::Error,
+            DuplicatePolicy::Overwrite => OnDuplicate::Overwrite,
+            DuplicatePolicy::Ignore => OnDuplicate::Ignore,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DedupMode {
+    Off,
+    Content,
+    Vector,
+}
+
+impl DedupMode {
+    fn apply(self, db: &DB, epsilon: f32, merge: bool) -> anyhow::Result<()> {
+        let dedup = match self {
+            DedupMode::Off => Dedup::Off,
+            DedupMode::Content => Dedup::Content,
+            DedupMode::Vector => Dedup::Vector { epsilon },
+        };
+        db.set_dedup(dedup, if merge { OnMatch::Merge } else { OnMatch::Skip })
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ForkMergeStrategy {
+    NewestWins,
+    Manual,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum IndexedField {
+    Source,
+    Timestamp,
+}
+
+impl IndexedField {
+    fn field(self) -> IndexField {
+        match self {
+            IndexedField::Source => IndexField::Source,
+            IndexedField::Timestamp => IndexField::Timestamp,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ListOrder {
+    Recency,
+    Importance,
+    Recalls,
+    Id,
+}
+
+impl ListOrder {
+    fn sort(self) -> SortBy {
+        match self {
+            ListOrder::Recency => SortBy::Recency,
+            ListOrder::Importance => SortBy::Importance,
+            ListOrder::Recalls => SortBy::Recalls,
+            ListOrder::Id => SortBy::Id,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum RedimMethod {
+    Pca,
+    Opq,
+    Truncate,
+}
+
+// Fit the projection of `modality` down to `to` dims, on up to `sample` of
+// its stored vectors.
+fn fit_projection(db: &DB, modality: &str, method: RedimMethod, to: usize, sample: usize,
+                  subspaces: usize, bar: &mut Bar) -> anyhow::Result<Projection> {
+    let from = db.dim(modality);
+    if let RedimMethod::Truncate = method {
+        return Projection::truncate(from, to);
+    }
+    let ids = db.ids(modality);
+    let step = ids.len().div_ceil(sample.max(1)).max(1);
+    let total = ids.len().div_ceil(step);
+    let samples: Vec<Vec<f32>> = ids.iter().step_by(step).enumerate()
+        .filter_map(|(i, &id)| {
+            bar.update(Progress { stage: "sample", done: i + 1, total: Some(total) });
+            db.get_vector(id, modality)
+        })
+        .collect();
+    let mut report = |p: Progress| bar.update(p);
+    match method {
+        RedimMethod::Opq => Projection::fit_opq_with_progress(&samples, to, subspaces, Some(&mut report)),
+        _ => Projection::fit_pca_with_progress(&samples, to, Some(&mut report)),
+    }
+}
+
+fn duration(s: &str) -> Result<f64, String> {
+    feather_db_cli::decay::parse_duration(s).map_err(|e| e.to_string())
+}
+
+type JsonObject = serde_json::Map<String, serde_json::Value>;
+
+fn json_object(s: &str) -> Result<JsonObject, String> {
+    match serde_json::from_str(s).map_err(|e| e.to_string())? {
+        serde_json::Value::Object(object) => Ok(object),
+        _ => Err("expected a JSON object".to_string()),
+    }
+}
+
+fn named_path(s: &str) -> Result<(String, PathBuf), String> {
+    match s.split_once('=') {
+        Some((name, path)) if !name.is_empty() && !path.is_empty() => Ok((name.to_string(), PathBuf::from(path))),
+        _ => Err("expected NAME=PATH".to_string()),
+    }
+}
+
+fn key_value(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err("expected KEY=VALUE".to_string()),
+    }
+}
+
+fn named_code(s: &str) -> Result<(String, u8), String> {
+    match s.split_once('=') {
+        Some((name, code)) if !name.is_empty() => {
+            Ok((name.to_string(), code.parse().map_err(|_| format!("bad code `{}`", code))?))
+        }
+        _ => Err("expected NAME=CODE".to_string()),
+    }
+}
+
+fn named_dim(s: &str) -> Result<(String, usize), String> {
+    match s.split_once('=') {
+        Some((name, dim)) if !name.is_empty() => {
+            Ok((name.to_string(), dim.parse().map_err(|_| format!("bad dim `{}`", dim))?))
+        }
+        _ => Err("expected NAME=DIM".to_string()),
+    }
+}
+
+fn time_point(s: &str) -> Result<i64, String> {
+    feather_db_cli::decay::parse_time(s, feather_db_cli::decay::now()).map_err(|e| e.to_string())
+}
+
+// Open `path`, scoped to `collection` if given. Only `create` makes a file,
+// or registers a collection, that does not exist yet. Expired records are
+// swept first, so no command ever sees them, unless `options` opens
+// read-only.
+fn open(path: &Path, dim: usize, collection: Option<&str>, options: &OpenOptions, create: bool) -> anyhow::Result<DB> {
+    let mut options = options.clone().dim(dim).create(create);
+    if let Some(name) = collection {
+        options = options.collection(name);
+    }
+    let db = options.open(path)?;
+    if !db.is_read_only() { db.expire()?; }
+    Ok(db)
+}
+
+// Bytes on disk of a store file, or of every shard file of a sharded store.
+fn store_size(path: &Path) -> u64 {
+    let files = if path.is_dir() { feather_db_cli::shard::files(path) } else { vec![path.to_path_buf()] };
+    files.iter().filter_map(|f| std::fs::metadata(f).ok()).map(|m| m.len()).sum()
+}
+
+// The embedder --embed-model and --embed-api name.
+fn embedder(model: Option<&str>, api: Option<&str>) -> anyhow::Result<Box<dyn EmbeddingProvider>> {
+    let Some(model) = model else { anyhow::bail!("--text needs --embed-model to turn it into a vector, or -n") };
+    if let Some(api) = api {
+        return Ok(Box::new(feather_db_cli::embed::remote::ApiEmbedder::new(api, model)));
+    }
+    #[cfg(feature = "local-embed")]
+    {
+        Ok(Box::new(feather_db_cli::embed::local::StaticEmbedder::load(Path::new(model))?))
+    }
+    #[cfg(not(feature = "local-embed"))]
+    {
+        anyhow::bail!("local embedding is not built in; rebuild with `--features local-embed`, or use --embed-api")
+    }
+}
+
+// One vector of raw float32 from stdin, of `dim` values if given.
+fn stdin_vector(dim: Option<usize>) -> anyhow::Result<Vec<f32>> {
+    let arr = feather_db_cli::vectors::read_raw(std::io::stdin().lock(), dim)?;
+    anyhow::ensure!(arr.nrows() == 1, "--stdin holds {} vectors of dim {}; expected one", arr.nrows(), arr.ncols());
+    Ok(arr.into_raw_vec())
+}
+
+// The name a store records for the embedding model `model`: a local model's
+// directory name, so the same model read from elsewhere matches.
+fn model_name(model: &str, api: Option<&str>) -> String {
+    match api {
+        Some(_) => model.to_string(),
+        None => Path::new(model).file_name().map_or(model.to_string(), |name| name.to_string_lossy().into_owned()),
+    }
+}
+
+// The embedding of `text` by the embedder the global options name.
+fn embed_text(model: Option<&str>, api: Option<&str>, text: &str) -> anyhow::Result<Vec<f32>> {
+    Ok(embedder(model, api)?.embed(&[text])?.remove(0))
+}
+
+// Write the record batch `$batch` evaluates to (a Result) to an Arrow IPC
+// file at `$path`; without the `arrow` feature `$batch` is left out and
+// this is an error.
+macro_rules! write_arrow {
+    ($path:expr, $batch:expr) => {{
+        #[cfg(feature = "arrow")]
+        let written: anyhow::Result<()> = (|| {
+            let out = std::io::BufWriter::new(std::fs::File::create($path)?);
+            feather_db_cli::record_batch::write_ipc(out, &$batch?)
+        })();
+        #[cfg(not(feature = "arrow"))]
+        let written: anyhow::Result<()> =
+            Err(anyhow::anyhow!("can't write {:?}: Arrow output is not built in; rebuild with `--features arrow`", $path));
+        written
+    }};
+}
+
+// Print `value` for --format json (pretty) or ndjson (compact, an array one
+// element per line).
+fn print_json(format: OutputFormat, value: &serde_json::Value) -> anyhow::Result<()> {
+    match (format, value) {
+        (OutputFormat::Ndjson, serde_json::Value::Array(items)) => {
+            for item in items {
+                println!("{}", item);
+            }
+        }
+        (OutputFormat::Ndjson, value) => println!("{}", value),
+        _ => println!("{}", serde_json::to_string_pretty(value)?),
+    }
+    Ok(())
+}
+
+// A record's content, shortened for one line of a tree.
+fn content_label(meta: Option<&Metadata>) -> String {
+    match meta {
+        None => "(missing)".to_string(),
+        Some(m) => {
+            let content: String = m.content.chars().take(60).collect();
+            let more = if m.content.chars().count() > 60 { "…" } else { "" };
+            format!("{:?}{}", content, more)
+        }
+    }
+}
+
+// The browsing filters of `list` and `recent`, as one metadata filter.
+fn list_filter(db: &DB, filter: Option<Filter>, type_filter: Option<&str>, source: Option<String>,
+               after: Option<i64>, before: Option<i64>) -> anyhow::Result<Option<Filter>> {
+    let mut conditions: Vec<Filter> = filter.into_iter().collect();
+    if let Some(kind) = type_filter {
+        let code = f64::from(db.context_type(kind)?.code());
+        conditions.push(Filter::Compare(Field::ContextType, Op::Eq, Value::Number(code)));
+    }
+    if let Some(source) = source {
+        conditions.push(Filter::Compare(Field::Source, Op::Eq, Value::Text(source)));
+    }
+    if let Some(after) = after {
+        conditions.push(Filter::Compare(Field::Timestamp, Op::Ge, Value::Number(after as f64)));
+    }
+    if let Some(before) = before {
+        conditions.push(Filter::Compare(Field::Timestamp, Op::Le, Value::Number(before as f64)));
+    }
+    Ok(conditions.into_iter().reduce(|a, b| Filter::And(Box::new(a), Box::new(b))))
+}
+
+// Records as `list` and `recent` show them: a table, or JSON.
+fn print_records(db: &DB, format: OutputFormat, records: &[(u64, Metadata)]) -> anyhow::Result<()> {
+    if format != OutputFormat::Text {
+        let records: Vec<serde_json::Value> = records.iter()
+            .map(|(id, meta)| serde_json::json!({ "id": id, "metadata": meta }))
+            .collect();
+        return print_json(format, &serde_json::Value::Array(records));
+    }
+    println!("{:>8}  {:<16}  {:>10}  {:>7}  {:<12}  {:<16}  CONTENT", "ID", "TIMESTAMP", "IMPORTANCE", "RECALLS",
+             "TYPE", "SOURCE");
+    for (id, meta) in records {
+        println!("{:>8}  {:<16}  {:>10.2}  {:>7}  {:<12}  {:<16}  {}", id, feather_db_cli::decay::format_time(meta.timestamp),
+                 meta.importance, meta.recall_count, db.context_type_name(meta.context_type), meta.source,
+                 content_label(Some(meta)));
+    }
+    Ok(())
+}
+
+// The id a command's `id` argument or `--key` names.
+fn record_id(db: &DB, id: Option<u64>, key: Option<&str>) -> anyhow::Result<u64> {
+    match (id, key) {
+        (Some(id), _) => Ok(id),
+        (None, Some(key)) => db.id_for_key(key).ok_or_else(|| anyhow::anyhow!("no record with key '{}'", key)),
+        (None, None) => anyhow::bail!("give an id or --key"),
+    }
+}
+
+// A search hit: id, any key, score, time, source and the start of the
+// content on one line, then with `full_content` the whole content and with
+// `meta` the rest of the metadata, indented.
+// `label` names what `score` is: a score, or a distance.
+fn print_hit(db: &DB, id: u64, label: &str, score: f32, m: &Metadata, full_content: bool, meta: bool) {
+    let source = if m.source.is_empty() { String::new() } else { format!("  {}", m.source) };
+    let content = if full_content || m.content.is_empty() { String::new() } else { format!("  {}", content_label(Some(m))) };
+    let key = m.key().map(|k| format!("  Key: {}", k)).unwrap_or_default();
+    println!("ID: {}{}  {}: {:.4}  {}{}{}", id, key, label, score, feather_db_cli::decay::format_time(m.timestamp), source, content);
+    if full_content {
+        for line in m.content.lines() {
+            println!("    {}", line);
+        }
+    }
+    if meta {
+        let session = m.session().map(|s| format!("  session {}", s)).unwrap_or_default();
+        let archived = if m.is_archived() { "  archived" } else { "" };
+        let owner = m.owner().map(|o| format!("  owner {} ({})", o, m.visibility())).unwrap_or_default();
+        println!("    importance {}  type {}  recalled {}×  confidence {}{}{}{}", m.importance,
+                 db.context_type_name(m.context_type), m.recall_count, m.confidence, session, archived, owner);
+        let hidden = [feather_db_cli::metadata::JSON_ATTRIBUTE, feather_db_cli::metadata::VERSION_ATTRIBUTE,
+                      feather_db_cli::metadata::SESSION_ATTRIBUTE, feather_db_cli::metadata::ARCHIVED_ATTRIBUTE,
+                      feather_db_cli::metadata::KEY_ATTRIBUTE, feather_db_cli::metadata::OWNER_ATTRIBUTE,
+                      feather_db_cli::metadata::VISIBILITY_ATTRIBUTE];
+        let attributes: Vec<String> = m.attributes.iter()
+            .filter(|(k, _)| !hidden.contains(&k.as_str()))
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect();
+        if !attributes.is_empty() {
+            println!("    attributes {}", attributes.join(", "));
+        }
+        if let Some(json) = m.json() {
+            println!("    meta {}", serde_json::Value::Object(json));
+        }
+    }
+}
+
+// One line per record, parents indented below their child.
+// Problems `feather fsck` lists before summing up the rest.
+const FSCK_SHOWN: usize = 20;
+
+// The command line, with the default database slotted in as the command's
+// first argument when that is what it lacks.
+fn parse_args(default_db: Option<PathBuf>) -> clap::ArgMatches {
+    let args: Vec<OsString> = std::env::args_os().collect();
+    let err = match Cli::command().try_get_matches_from(&args) {
+        Ok(matches) => return matches,
+        Err(e) => e,
+    };
+    if let (clap::error::ErrorKind::MissingRequiredArgument, Some(db), Some(at)) = (err.kind(), default_db, subcommand_at(&args)) {
+        let mut with_db = args.clone();
+        with_db.insert(at + 1, db.into_os_string());
+        match Cli::command().try_get_matches_from(&with_db) {
+            Ok(matches) => return matches,
+            // still short of arguments: say which, past the database
+            Err(e) if e.kind() == clap::error::ErrorKind::MissingRequiredArgument => e.exit(),
+            Err(_) => {}
+        }
+    }
+    err.exit()
+}
+
+// Position of the subcommand name, past the options before it.
+fn subcommand_at(args: &[OsString]) -> Option<usize> {
+    let cli = Cli::command();
+    let mut i = 1;
+    while let Some(arg) = args.get(i) {
+        let arg = arg.to_str()?;
+        if let Some(long) = arg.strip_prefix("--") {
+            let takes_value = cli.get_arguments().any(|a| a.get_long() == Some(long) && a.get_action().takes_values());
+            i += if takes_value { 2 } else { 1 };
+        } else if arg.starts_with('-') {
+            i += 1;
+        } else {
+            return cli.find_subcommand(arg).map(|_| i);
+        }
+    }
+    None
+}
+
+fn print_fsck(path: &Path, report: &FsckReport) {
+    match report.compression {
+        Compression::None => println!("File:     {:?} (format v{})", path, report.version),
+        packed => println!("File:     {:?} (format v{}, {} packed)", path, report.version, packed.name()),
+    }
+    println!("Records:  {}", report.records);
+    for (name, dim, vectors) in &report.modalities {
+        println!("Modality '{}': {} vectors, dim {}", name, vectors, dim);
+    }
+    println!("WAL:      {} entries", report.wal_entries);
+    if report.is_ok() {
+        println!("No problems found");
+        return;
+    }
+    println!("Problems: {}", report.problems.len());
+    for problem in report.problems.iter().take(FSCK_SHOWN) {
+        println!("  {}", problem);
+    }
+    if report.problems.len() > FSCK_SHOWN {
+        println!("  ... and {} more", report.problems.len() - FSCK_SHOWN);
+    }
+}
+
+fn print_explanation(format: OutputFormat, explanation: &Explanation) -> anyhow::Result<()> {
+    if format != OutputFormat::Text {
+        return print_json(format, &serde_json::json!({
+            "hits": explanation.hits,
+            "rejected": explanation.rejected.iter()
+                .map(|(id, reason)| serde_json::json!({ "id": id, "reason": reason }))
+                .collect::<Vec<_>>(),
+            "plan": explanation.plan,
+        }));
+    }
+    println!("Plan: {}", explanation.plan.plan);
+    if let Some(e) = &explanation.plan.estimate {
+        let listed = e.index_rows.map_or("no index applies".to_string(), |rows| format!("indexes list ~{}", rows));
+        println!("  {} vectors, {}; {} of {} sampled admitted, ~{} in all", e.vectors, listed, e.admitted,
+                 e.sampled, e.matches);
+        println!("  cost in distances: pre-filter {}  post-filter ~{}", e.pre_filter_cost, e.post_filter_cost);
+    }
+    let opt = |v: Option<f32>| v.map_or("-".to_string(), |v| format!("{:.4}", v));
+    for hit in &explanation.hits {
+        println!("ID: {}  Score: {:.4}{}", hit.id, hit.score, if hit.linked { "  (linked)" } else { "" });
+        println!("  distance {}  similarity {:.4}  keyword {}  sparse {}  relevance {:.4}",
+                 opt(hit.distance), hit.similarity, opt(hit.keyword), opt(hit.sparse), hit.relevance);
+        println!("  recency {:.4}  importance {:.4}  usage {:.4}  graph {:.4}  reranker {}",
+                 hit.recency, hit.importance, hit.usage, hit.graph, opt(hit.reranker));
+    }
+    if !explanation.rejected.is_empty() {
+        println!("Dropped:");
+        for (id, reason) in &explanation.rejected {
+            println!("  {}: {}", id, reason);
+        }
+    }
+    Ok(())
+}
+
+fn print_lineage(node: &Lineage, lead: &str, indent: &str) {
+    let label = content_label(node.metadata.as_ref());
+    println!("{}{}  {}{}", lead, node.id, label, if node.repeated { "  (see above)" } else { "" });
+    for (i, parent) in node.parents.iter().enumerate() {
+        let last = i + 1 == node.parents.len();
+        print_lineage(parent,
+                      &format!("{}{}", indent, if last { "└─ " } else { "├─ " }),
+                      &format!("{}{}", indent, if last { "   " } else { "│  " }));
+    }
+}
+
+// The records `id` was first reached from, indented below it: `->` marks a
+// link `id` holds, `<-` one pointing at it.
+fn print_neighbors(db: &DB, id: u64, reached: &HashMap<u64, Vec<&Neighbor>>, indent: &str) {
+    let children = reached.get(&id).map_or(&[][..], Vec::as_slice);
+    for (i, n) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        let arrow = if n.via.from == id { "->" } else { "<-" };
+        println!("{}{}{} {}  {} {:.2}  {}", indent, if last { "└─ " } else { "├─ " }, arrow, n.id,
+                 n.via.rel_type, n.via.weight, content_label(db.get_metadata(n.id).as_ref()));
+        print_neighbors(db, n.id, reached, &format!("{}{}", indent, if last { "   " } else { "│  " }));
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let config = match Config::load() {
+        Ok(config) => config,
+        // `feather doctor` reports a broken config file rather than stop at it
+        Err(_) if std::env::args().any(|arg| arg == "doctor") => Config::default(),
+        Err(e) => return Err(e),
+    };
+    let matches = parse_args(config.default_db());
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let db_path = matches.subcommand()
+        .and_then(|(_, m)| ["db", "path"].into_iter().find_map(|id| m.try_get_one::<PathBuf>(id).ok().flatten()));
+    let defaults = config.for_db(db_path.map(PathBuf::as_path));
+    let collection = cli.collection.as_deref();
+    // the configured metric is for files the command creates
+    let creating = db_path.is_some_and(|p| !p.exists());
+    let normalize = cli.normalize || (defaults.metric == Some(Metric::Cosine) && creating);
+    let mut options = OpenOptions::new().normalize(normalize).read_only(cli.read_only);
+    if defaults.metric == Some(Metric::Hamming) && creating { options = options.metric(Metric::Hamming); }
+    if let Some(actor) = &cli.actor { options = options.actor(actor); }
+    if let Some(seed) = cli.index_seed { options = options.seed(seed); }
+    // an --embed-model on the command line comes with its own --embed-api or none
+    let (embed_model, embed_api) = match cli.embed_model.as_deref() {
+        Some(model) => (Some(model), cli.embed_api.as_deref()),
+        None => (defaults.embed_model.as_deref(), defaults.embed_api.as_deref()),
+    };
+    // the options before --model, for reading a store written by another model
+    let undeclared = options.clone();
+    if let Some(model) = &cli.model { options = options.model(model); }
+    // commands that embed text declare the embedder's model, unless --model did
+    let embedding = match (&cli.model, embed_model) {
+        (None, Some(model)) => options.clone().model(&model_name(model, embed_api)),
+        _ => options.clone(),
+    };
+    let format = cli.format;
+    match cli.command {
+        Commands::New { path, dim, shards, compress, metric } => {
+            let mut options = options.clone().shards(shards.unwrap_or(0)).compression(compress);
+            if let Some(metric) = metric { options = options.metric(metric); }
+            let db = open(&path, dim, collection, &options, true)?;
+            let mut notes = Vec::new();
+            if let Some(name) = collection { notes.push(format!("collection '{}'", name)); }
+            if db.shard_count() > 1 { notes.push(format!("{} shards", db.shard_count())); }
+            if compress != Compression::None { notes.push(format!("{} packed", compress.name())); }
+            if db.metric() == Metric::Hamming { notes.push("hamming, a bit per dimension".to_string()); }
+            match notes.is_empty() {
+                true => println!("Created: {:?}", path),
+                false => println!("Created: {:?} ({})", path, notes.join(", ")),
+            }
+        }
+        Commands::Add { db, id, key, npy, text, stdin, dim, timestamp, importance, context_type, source, content, modality, vectors,
+                        ttl_seconds, derived_from, meta, session, owner, visibility, sparse, sparse_name, on_duplicate, dedup,
+                        dedup_epsilon, dedup_merge } => {
+            let arr: Array1<f32> = match &npy {
+                Some(npy) => feather_db_cli::vectors::read_vector(npy)?.into(),
+                None if stdin => stdin_vector(dim)?.into(),
+                None => embed_text(embed_model, embed_api, text.as_deref().unwrap_or_default())?.into(),
+            };
+            let content = content.or(text);
+            let named = vectors.into_iter()
+                .map(|(name, path)| Ok((name, Array1::from(feather_db_cli::vectors::read_vector(&path)?))))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let options = if npy.is_none() && !stdin { &embedding } else { &options };
+            // the store must exist (`feather new`); a collection is registered on first use
+            let db = open(&db, 0, None, options, false)?;
+            let db = match collection { Some(name) => db.collection(name)?, None => db };
+            db.set_on_duplicate(on_duplicate.policy());
+            dedup.apply(&db, dedup_epsilon, dedup_merge)?;
+            anyhow::ensure!(ttl_seconds.is_none_or(|ttl| ttl > 0), "--ttl-seconds must be positive");
+            let id = match (id, &key) {
+                (Some(id), _) => id,
+                (None, Some(key)) => db.id_for_key(key).map_or_else(|| db.allocate_id(), Ok)?,
+                (None, None) => db.allocate_id()?,
+            };
+
+            let mut insert = db.insert(id, arr.as_slice().unwrap())
+                .modality(&modality)
+                .importance(importance)
+                .context_type(db.context_type(&context_type)?)
+                .ttl(ttl_seconds.unwrap_or(0));
+            if let Some(ts) = timestamp { insert = insert.timestamp(ts); }
+            if let Some(key) = &key { insert = insert.key(key); }
+            if let Some(source) = &source { insert = insert.source(source); }
+            if let Some(content) = &content { insert = insert.content(content); }
+            if let Some(json) = &meta { insert = insert.json(json); }
+            if let Some(session) = &session { insert = insert.session(session); }
+            if let Some(owner) = &owner { insert = insert.owner(owner); }
+            if let Some(visibility) = visibility { insert = insert.visibility(visibility); }
+            for &parent in &derived_from { insert = insert.derived_from(parent); }
+            for (name, vec) in &named { insert = insert.vector(name, vec.as_slice().unwrap()); }
+            if let Some(sparse) = sparse { insert = insert.sparse(&sparse_name, sparse); }
+            match insert.execute()? {
+                Inserted::Written => {}
+                Inserted::Kept => {
+                    println!("ID {} already exists; left unchanged", id);
+                    return Ok(());
+                }
+                Inserted::DuplicateOf(existing) if dedup_merge => {
+                    db.save()?;
+                    println!("Same as ID {}; merged into it", existing);
+                    return Ok(());
+                }
+                Inserted::DuplicateOf(existing) => {
+                    println!("Same as ID {}; nothing added", existing);
+                    return Ok(());
+                }
+            }
+            db.save()?;
+            if named.is_empty() {
+                println!("Added ID {} to modality '{}'", id, modality);
+            } else {
+                let names: Vec<String> = std::iter::once(&modality).chain(named.iter().map(|(n, _)| n))
+                    .map(|n| format!("'{}'", n))
+                    .collect();
+                println!("Added ID {} with vectors {}", id, names.join(", "));
+            }
+        }
+        Commands::AddBatch { db, npy, stdin: _, dim, ids, meta, modality, batch_size, on_duplicate, dedup, dedup_epsilon,
+                             dedup_merge } => {
+            let (ids, arr): (Option<Vec<u64>>, Array2<f32>) = match npy {
+                Some(dir) if dir.is_dir() => {
+                    anyhow::ensure!(ids.is_none(), "--ids does not go with a directory; its file names are the ids");
+                    let (ids, arr) = feather_db_cli::batch::read_dir(&dir)?;
+                    (Some(ids), arr)
+                }
+                npy => {
+                    let arr = match npy {
+                        Some(npy) => feather_db_cli::vectors::read_matrix(&npy)?,
+                        None => feather_db_cli::vectors::read_raw(std::io::stdin().lock(), dim)?,
+                    };
+                    (ids.as_deref().map(feather_db_cli::batch::read_ids).transpose()?, arr)
+                }
+            };
+            let db = open(&db, arr.ncols(), collection, &options, true)?;
+            db.set_on_duplicate(on_duplicate.policy());
+            dedup.apply(&db, dedup_epsilon, dedup_merge)?;
+            let meta = meta.map(|path| std::fs::File::open(&path).map(std::io::BufReader::new)
+                .map_err(|e| anyhow::anyhow!("{:?}: {}", path, e))).transpose()?;
+            let first_id = db.all_ids().into_iter().max().map_or(0, |max| max + 1);
+            let records = feather_db_cli::batch::records(arr.view(), ids.as_deref(), meta, first_id, &modality,
+                                                         feather_db_cli::decay::now())?;
+            let mut bar = Bar::new();
+            let result = feather_db_cli::import::import_pipelined(&db, records.into_iter().map(Ok), batch_size,
+                                                                  Some(&mut |p| bar.update(p)));
+            bar.finish();
+            db.save()?;
+            let report = result?;
+            println!("Added {} records to modality '{}' in {} batches", report.records, modality, report.batches);
+            if report.skipped > 0 {
+                println!("Skipped {} records whose id already existed", report.skipped);
+            }
+            if report.deduplicated > 0 {
+                let verb = if dedup_merge { "Merged" } else { "Skipped" };
+                println!("{} {} records already stored under another id", verb, report.deduplicated);
+            }
+        }
+        Commands::Link { db, from, to, file, rel_type, weight } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            match (from, to, file) {
+                (Some(from), Some(to), None) => {
+                    db.link_with(from, to, &rel_type, weight)?;
+                    db.save()?;
+                    println!("Linked {} -> {} ({}, weight {})", from, to, rel_type, weight);
+                }
+                (_, _, Some(file)) => {
+                    let links = feather_db_cli::bootstrap::read_links(std::fs::File::open(&file)?)?;
+                    let made = db.link_batch_with(&links)?;
+                    db.save()?;
+                    println!("Applied {} links from {:?}, {} new or reweighted", links.len(), file, made);
+                }
+                _ => unreachable!("clap requires both ids or --file"),
+            }
+        }
+        Commands::Delete { db, id, key, if_version } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            let id = record_id(&db, id, key.as_deref())?;
+            match if_version {
+                Some(expected) => db.forget_if(id, expected)?,
+                None => {
+                    anyhow::ensure!(db.version(id).is_some(), "no record {}", id);
+                    db.forget(id)?;
+                }
+            }
+            db.save()?;
+            println!("Deleted ID {}; run `feather vacuum` to reclaim the space", id);
+        }
+        Commands::Archive { db, id: Some(id) } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            if db.archive(id)? {
+                db.save()?;
+                println!("Archived ID {}; `feather restore` brings it back", id);
+            } else {
+                println!("ID {} is already archived", id);
+            }
+        }
+        Commands::Archive { db, id: None } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            let archived = db.archived();
+            if format != OutputFormat::Text {
+                let archived: Vec<serde_json::Value> = archived.iter()
+                    .map(|&id| serde_json::json!({ "id": id, "archived_at": db.get_metadata(id).and_then(|m| m.archived_at()) }))
+                    .collect();
+                return print_json(format, &serde_json::Value::Array(archived));
+            }
+            if archived.is_empty() { println!("No archived records"); }
+            for id in archived {
+                let Some(m) = db.get_metadata(id) else { continue };
+                println!("{}  archived {}  {:?}", id, feather_db_cli::decay::format_time(m.archived_at().unwrap_or(0)),
+                         m.content);
+            }
+        }
+        Commands::Restore { db: path, id: None, from } => {
+            let (base, deltas) = from.split_first().expect("required");
+            let at = feather_db_cli::backup::restore(base, deltas, &path)?;
+            if format != OutputFormat::Text {
+                return print_json(format, &serde_json::json!({"path": path, "deltas": deltas.len(), "snapshot": at}));
+            }
+            match at {
+                Some(at) => println!("Restored {:?} from {:?} and {} incremental backup(s), as of backup {} ({})",
+                                     path, base, deltas.len(), at, feather_db_cli::decay::format_time(at)),
+                None => println!("Restored {:?} from {:?}", path, base),
+            }
+        }
+        Commands::Restore { db, id: Some(id), .. } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            if db.restore(id)? {
+                db.save()?;
+                println!("Restored ID {}", id);
+            } else {
+                println!("ID {} is not archived", id);
+            }
+        }
+        Commands::Update { db, id, importance, confidence, context_type, source, content, timestamp, ttl_seconds,
+                           meta, session, attributes, if_version } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            let mut m = db.get_metadata(id).filter(|m| !m.is_forgotten())
+                .ok_or_else(|| anyhow::anyhow!("no record {}", id))?;
+            anyhow::ensure!(ttl_seconds.is_none_or(|ttl| ttl >= 0), "--ttl-seconds must not be negative");
+            if let Some(importance) = importance { m.importance = importance; }
+            if let Some(confidence) = confidence { m.confidence = confidence; }
+            if let Some(kind) = &context_type { m.context_type = db.context_type(kind)?; }
+            if let Some(source) = source { m.source = source; }
+            if let Some(content) = content { m.content = content; }
+            if let Some(timestamp) = timestamp { m.timestamp = timestamp; }
+            if let Some(ttl) = ttl_seconds { m.ttl = ttl; }
+            if let Some(json) = &meta { m.set_json(json); }
+            if let Some(session) = &session { m.set_session(Some(session)); }
+            m.attributes.extend(attributes);
+            let version = match if_version {
+                Some(expected) => db.put_metadata_if(id, &m, expected)?,
+                None => {
+                    db.put_metadata(id, &m)?;
+                    m.version() + 1
+                }
+            };
+            db.save()?;
+            println!("Updated ID {} (version {})", id, version);
+        }
+        Commands::Touch { db, id } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            db.refresh(id, feather_db_cli::decay::now())?;
+            db.save()?;
+            println!("Touched ID {}", id);
+        }
+        Commands::Unlink { db, from, to, rel_type } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            let removed = match &rel_type {
+                Some(t) => usize::from(db.unlink_type(from, to, t)?),
+                None => db.unlink(from, to)?,
+            };
+            anyhow::ensure!(removed > 0, "no link {} -> {}{}", from, to,
+                            rel_type.map(|t| format!(" of type {}", t)).unwrap_or_default());
+            db.save()?;
+            println!("Unlinked {} -> {} ({} edge{})", from, to, removed, if removed == 1 { "" } else { "s" });
+        }
+        Commands::Links { db, id, depth, json } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            let meta = db.get_metadata(id).filter(|m| !m.is_forgotten())
+                .ok_or_else(|| anyhow::anyhow!("no record {}", id))?;
+            let neighbors = db.neighbors(id, depth);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&neighbors)?);
+            } else {
+                let mut reached: HashMap<u64, Vec<&Neighbor>> = HashMap::new();
+                for n in &neighbors {
+                    reached.entry(n.via.other(n.id)).or_default().push(n);
+                }
+                println!("{}  {}", id, content_label(Some(&meta)));
+                print_neighbors(&db, id, &reached, "");
+            }
+        }
+        Commands::Path { db, from, to, max_depth, dot } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            let path = db.path(from, to, max_depth)?
+                .ok_or_else(|| anyhow::anyhow!("no path from {} to {} within {} links", from, to, max_depth))?;
+            if dot {
+                print!("{}", db.to_dot(&Subgraph::of_path(from, &path), &[from, to]));
+            } else if format != OutputFormat::Text {
+                print_json(format, &serde_json::to_value(&path)?)?;
+            } else {
+                println!("{}  {}", from, content_label(db.get_metadata(from).as_ref()));
+                let mut at = from;
+                for link in &path {
+                    let arrow = if link.from == at { "->" } else { "<-" };
+                    at = link.other(at);
+                    println!("  {} {}  {} {:.2}  {}", arrow, at, link.rel_type, link.weight,
+                             content_label(db.get_metadata(at).as_ref()));
+                }
+            }
+        }
+        Commands::Subgraph { db, ids, depth, dot } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            let graph = db.subgraph(&ids, depth)?;
+            if dot {
+                print!("{}", db.to_dot(&graph, &ids));
+            } else if format != OutputFormat::Text {
+                print_json(format, &serde_json::to_value(&graph)?)?;
+            } else {
+                println!("{} records, {} links", graph.nodes.len(), graph.links.len());
+                for &id in &graph.nodes {
+                    println!("{}  {}", id, content_label(db.get_metadata(id).as_ref()));
+                }
+                for link in &graph.links {
+                    println!("{} -> {}  {} {:.2}", link.from, link.to, link.rel_type, link.weight);
+                }
+            }
+        }
+        Commands::Lineage { db, id, json } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            let lineage = db.lineage(id).ok_or_else(|| anyhow::anyhow!("no record {}", id))?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&lineage)?);
+            } else {
+                print_lineage(&lineage, "", "");
+            }
+        }
+        Commands::Search { db, npy, stdin, dim, like, vector_expr, k, offset, type_filter, source_filter, modality, half_life,
+                            recency_weight, tau, mmr, lambda, min_score, after, before, min_importance, max_importance, filter, session,
+                            exclude_session, exclude_sources, exclude_types, exclude_ids, include_archived, as_owners, exact, truncate_dim, text, sparse, sparse_name, hybrid, text_weight, sparse_weight, graph_boost, hops,
+                            max_per_source, include_linked, scoring, explain, fuse, arrow, show_content, show_meta, as_of } => {
+            let k = k.or(defaults.k).unwrap_or(feather_db_cli::search::DEFAULT_K);
+            // with --embed-model and no -n, --text is embedded as the query
+            // vector; it ranks keywords too only with --hybrid
+            let embedded = npy.is_none() && !stdin && like.is_empty() && vector_expr.is_none() && embed_model.is_some();
+            let arr: Option<Array1<f32>> = match (npy, &text) {
+                (Some(npy), _) => Some(feather_db_cli::vectors::read_vector(&npy)?.into()),
+                (None, _) if stdin => Some(stdin_vector(dim)?.into()),
+                (None, Some(text)) if embedded => Some(embed_text(embed_model, embed_api, text)?.into()),
+                (None, _) => None,
+            };
+            let text = if embedded && !hybrid { None } else { text };
+            let options = if embedded { &embedding } else { &options };
+            let options = match as_of {
+                Some(at) => options.clone().as_of(at),
+                None => options.clone(),
+            };
+            let db = open(&db, arr.as_ref().map_or(0, |a| a.len()), collection, &options, false)?;
+            // --like and --vector-expr read their query from the store
+            let terms = vector_expr.as_deref().map(feather_db_cli::centroid::parse_terms).transpose()?.unwrap_or_default();
+            let arr = match arr {
+                None if !like.is_empty() => Some(db.centroid(&like, &modality)?.into()),
+                None if !terms.is_empty() => Some(db.combine(&terms, &modality)?.into()),
+                arr => arr,
+            };
+            let mut exclude_ids = exclude_ids;
+            exclude_ids.extend(like.iter().chain(terms.iter().map(|(_, id)| id)));
+            let type_filter = type_filter.map(|t| db.context_type(&t)).transpose()?;
+            let hits = match arr.as_ref().map(|a| a.as_slice().unwrap()) {
+                None => {
+                    anyhow::ensure!(recency_weight.is_none() && !mmr && after.is_none() && before.is_none()
+                                    && min_importance.is_none() && max_importance.is_none() && filter.is_none()
+                                    && session.is_none() && exclude_session.is_none() && !include_archived && as_owners.is_empty() && !exact
+                                    && truncate_dim.is_none()
+                                    && exclude_sources.is_empty() && exclude_types.is_empty() && exclude_ids.is_empty() && offset == 0 && !hybrid && graph_boost.is_none() && max_per_source.is_none() && include_linked.is_empty()
+                                    && scoring.is_none() && !explain && fuse.is_empty()
+                                    && type_filter.is_none() && source_filter.is_none(),
+                                    "keyword- or sparse-only search takes no ranking, filter or paging options; add -n and --hybrid");
+                    match (&text, &sparse) {
+                        (Some(text), None) => db.keyword_search(text, k)?,
+                        (None, Some(sparse)) => db.sparse_search(sparse, k, &sparse_name)?,
+                        _ => anyhow::bail!("add -n and --hybrid to combine --text with --sparse"),
+                    }
+                }
+                Some(_) if (text.is_some() || sparse.is_some()) && !hybrid => {
+                    anyhow::bail!("add --hybrid to combine --text or --sparse with -n")
+                }
+                Some(_) if hybrid && text.is_none() && sparse.is_none() => {
+                    anyhow::bail!("--hybrid needs --text or --sparse")
+                }
+                Some(query) => if let Some(half_life) = half_life {
+                    let decay = Decay::new(half_life, 0.0)?;
+                    db.search_decayed(query, k, &modality, &decay)?
+                } else {
+                    let time_range = (after.is_some() || before.is_some())
+                        .then(|| (after.unwrap_or(i64::MIN), before.unwrap_or(i64::MAX)));
+                    let importance_range = (min_importance.is_some() || max_importance.is_some())
+                        .then(|| (min_importance.unwrap_or(f32::NEG_INFINITY), max_importance.unwrap_or(f32::INFINITY)));
+                    // --type-filter and --source-filter are shorthands for --filter comparisons
+                    let filter = [
+                        filter,
+                        type_filter.map(|t| Filter::Compare(Field::ContextType, Op::Eq, Value::Number(t.code() as f64))),
+                        source_filter.map(|s| Filter::Compare(Field::Source, Op::Eq, Value::Text(s))),
+                    ].into_iter().flatten().reduce(|a, b| Filter::And(Box::new(a), Box::new(b)));
+                    let options = SearchOptions {
+                        recency_weight: recency_weight.unwrap_or(0.0),
+                        tau,
+                        mmr_lambda: mmr.then_some(lambda),
+                        min_score,
+                        time_range,
+                        importance_range,
+                        filter,
+                        session,
+                        exclude_session,
+                        exclude_sources,
+                        exclude_types: exclude_types.iter().map(|t| db.context_type(t)).collect::<anyhow::Result<_>>()?,
+                        exclude_ids,
+                        include_archived,
+                        access: (!as_owners.is_empty()).then_some(Access::Labels(as_owners)),
+                        exact,
+                        truncate_dim,
+                        text,
+                        text_weight,
+                        sparse,
+                        sparse_name,
+                        sparse_weight,
+                        graph_boost: graph_boost.unwrap_or(0.0),
+                        hops,
+                        offset,
+                        max_per_source,
+                        linked_modalities: include_linked,
+                        reranker: None,
+                        scoring,
+                        device: Device::Cpu,
+                    };
+                    if explain {
+                        return print_explanation(format, &db.explain_search(query, k, &modality, &options)?);
+                    }
+                    if !fuse.is_empty() {
+                        let others = fuse.iter().map(|f| feather_db_cli::vectors::read_vector(f)).collect::<anyhow::Result<Vec<_>>>()?;
+                        let queries: Vec<&[f32]> = std::iter::once(query).chain(others.iter().map(Vec::as_slice)).collect();
+                        db.search_fused_with_options(&queries, k, &modality, &options)?
+                    } else {
+                        db.search_with_options(query, k, &modality, &options)?
+                    }
+                },
+            };
+
+            let hits = hits.into_iter().filter(|&(_, score)| min_score.is_none_or(|min| score >= min));
+            if let Some(path) = arrow {
+                let hits: Vec<(u64, f32)> = hits.collect();
+                write_arrow!(&path, db.hits_batch(&hits))?;
+                println!("Wrote {} hit(s) to {:?}", hits.len(), path);
+                return Ok(());
+            }
+            if format != OutputFormat::Text {
+                let hits: Vec<serde_json::Value> = hits
+                    .map(|(id, score)| serde_json::json!({ "id": id, "key": db.key_of(id), "score": score, "metadata": db.get_metadata(id) }))
+                    .collect();
+                print_json(format, &serde_json::Value::Array(hits))?;
+                return Ok(());
+            }
+            for (id, score) in hits {
+                let Some(m) = db.get_metadata(id) else {
+                    println!("ID: {}  Score: {:.4}", id, score);
+                    continue;
+                };
+                print_hit(&db, id, "Score", score, &m, show_content, show_meta);
+            }
+        }
+        Commands::Near { db, npy, radius, modality, show_content, show_meta } => {
+            let query = feather_db_cli::vectors::read_vector(&npy)?;
+            let db = open(&db, query.len(), collection, &options, false)?;
+            let hits = db.search_radius(&query, radius, &modality)?;
+            if format != OutputFormat::Text {
+                let hits: Vec<serde_json::Value> = hits.into_iter()
+                    .map(|(id, distance)| serde_json::json!({ "id": id, "distance": distance, "metadata": db.get_metadata(id) }))
+                    .collect();
+                print_json(format, &serde_json::Value::Array(hits))?;
+                return Ok(());
+            }
+            for &(id, distance) in &hits {
+                match db.get_metadata(id) {
+                    Some(m) => print_hit(&db, id, "Distance", distance, &m, show_content, show_meta),
+                    None => println!("ID: {}  Distance: {:.4}", id, distance),
+                }
+            }
+            println!("{} record(s) within {} of the query in modality '{}'", hits.len(), radius, modality);
+        }
+        Commands::Context { db, npy, text, budget, modality, lines, show_content, show_meta } => {
+            let options = if npy.is_none() { &embedding } else { &options };
+            let (query, text) = match (npy, text) {
+                (Some(npy), text) => (feather_db_cli::vectors::read_vector(&npy)?, text),
+                (None, Some(text)) if embed_model.is_some() => (embed_text(embed_model, embed_api, &text)?, None),
+                (None, _) => anyhow::bail!("--text without -n needs --embed-model to embed it"),
+            };
+            let db = open(&db, query.len(), collection, options, false)?;
+            let selection = db.select_for_context(&query, budget, &modality, &SearchOptions { text, ..Default::default() })?;
+            if format != OutputFormat::Text {
+                let selected: Vec<serde_json::Value> = selection.selected.iter()
+                    .map(|s| serde_json::json!({ "id": s.id, "score": s.score, "tokens": s.tokens, "metadata": db.get_metadata(s.id) }))
+                    .collect();
+                print_json(format, &serde_json::json!({
+                    "selected": selected, "tokens": selection.tokens, "left_out": selection.left_out, "expired": selection.expired,
+                }))?;
+                return Ok(());
+            }
+            for chosen in &selection.selected {
+                let Some(m) = db.get_metadata(chosen.id) else { continue };
+                match lines {
+                    true => println!("- {}", m.content.replace('\n', " ")),
+                    false => print_hit(&db, chosen.id, "Score", chosen.score, &m, show_content, show_meta),
+                }
+            }
+            if !lines {
+                println!("{} record(s), {} of {} tokens; {} left out, {} expired", selection.selected.len(),
+                         selection.tokens, budget, selection.left_out, selection.expired);
+            }
+        }
+        Commands::Get { db, id, key } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            let id = record_id(&db, id, key.as_deref())?;
+            let record = db.record(id).filter(|r| !r.metadata.is_forgotten())
+                .ok_or_else(|| anyhow::anyhow!("no record {}", id))?;
+            if format != OutputFormat::Text {
+                print_json(format, &serde_json::to_value(&record)?)?;
+                return Ok(());
+            }
+            let m = &record.metadata;
+            println!("ID: {}", id);
+            if let Some(key) = m.key() { println!("Key: {}", key); }
+            println!("Content: {:?}", m.content);
+            if !m.source.is_empty() { println!("Source: {}", m.source); }
+            println!("Timestamp: {}  Importance: {}  Type: {}  Version: {}", m.timestamp, m.importance,
+                     db.context_type_name(m.context_type), m.version());
+            match m.last_recalled_at {
+                0 => println!("Recalled: {}×", m.recall_count),
+                last => println!("Recalled: {}×, last {}", m.recall_count, feather_db_cli::decay::format_time(last as i64)),
+            }
+            if let Some(session) = m.session() { println!("Session: {}", session); }
+            if let Some(at) = m.archived_at() { println!("Archived: {}", feather_db_cli::decay::format_time(at)); }
+            if let Some(owner) = m.owner() { println!("Owner: {} ({})", owner, m.visibility()); }
+            let hidden = [feather_db_cli::metadata::VERSION_ATTRIBUTE, feather_db_cli::metadata::SESSION_ATTRIBUTE,
+                          feather_db_cli::metadata::ARCHIVED_ATTRIBUTE, feather_db_cli::metadata::KEY_ATTRIBUTE,
+                          feather_db_cli::metadata::OWNER_ATTRIBUTE, feather_db_cli::metadata::VISIBILITY_ATTRIBUTE];
+            let attributes: Vec<String> = m.attributes.iter()
+                .filter(|(k, _)| !hidden.contains(&k.as_str()))
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect();
+            if !attributes.is_empty() { println!("Attributes: {}", attributes.join(", ")); }
+            let vectors: Vec<String> = record.vectors.iter().map(|(name, v)| format!("{} (dim {})", name, v.len())).collect();
+            println!("Vectors: {}", vectors.join(", "));
+            for edge in &m.edges {
+                println!("Link: -> {}  {} {:.2}", edge.target, edge.rel_type, edge.weight);
+            }
+        }
+        Commands::Scan { db, cursor, limit, filter, json, arrow } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            let page = db.scan(cursor, limit, filter.as_ref())?;
+            if let Some(path) = arrow {
+                write_arrow!(&path, db.records_batch(&page.ids))?;
+                println!("Wrote {} record(s) to {:?}", page.ids.len(), path);
+                if let Some(next) = page.next_cursor {
+                    println!("More: --cursor {}", next);
+                }
+            } else if format != OutputFormat::Text {
+                let records: Vec<serde_json::Value> = page.ids.iter()
+                    .map(|&id| serde_json::json!({ "id": id, "metadata": db.get_metadata(id) }))
+                    .collect();
+                let value = match format {
+                    OutputFormat::Ndjson => serde_json::Value::Array(records),
+                    _ => serde_json::json!({ "records": records, "next_cursor": page.next_cursor }),
+                };
+                print_json(format, &value)?;
+            } else if json {
+                println!("{}", serde_json::to_string_pretty(&page)?);
+            } else {
+                for &id in &page.ids {
+                    println!("ID: {}  {}", id, content_label(db.get_metadata(id).as_ref()));
+                }
+                if let Some(next) = page.next_cursor {
+                    println!("More: --cursor {}", next);
+                }
+            }
+        }
+        Commands::List { db, sort, limit, offset, type_filter, source_filter, after, before, filter } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            let filter = list_filter(&db, filter, type_filter.as_deref(), source_filter, after, before)?;
+            print_records(&db, format, &db.list(sort.sort(), offset, limit, filter.as_ref()))?;
+        }
+        Commands::Recent { db, k, type_filter, source, filter } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            let filter = list_filter(&db, filter, type_filter.as_deref(), source, None, None)?;
+            print_records(&db, format, &db.recent(k, filter.as_ref()))?;
+        }
+        Commands::Fsck { db, repair } => {
+            // the whole file, every collection included; every shard of a
+            // sharded store
+            let sharded = db.is_dir();
+            let mut reports = match sharded {
+                true => feather_db_cli::fsck::check_shards(&db)?,
+                false => vec![(db.clone(), feather_db_cli::fsck::check(&db)?)],
+            };
+            let count = |reports: &[(PathBuf, FsckReport)]| reports.iter().map(|(_, r)| r.problems.len()).sum::<usize>();
+            let print = |reports: &[(PathBuf, FsckReport)]| for (i, (file, report)) in reports.iter().enumerate() {
+                if i > 0 { println!(); }
+                print_fsck(file, report);
+            };
+            print(&reports);
+            let before = count(&reports);
+            if repair && before > 0 {
+                anyhow::ensure!(!cli.read_only, ReadOnly);
+                reports = match sharded {
+                    true => feather_db_cli::fsck::repair_shards(&db)?,
+                    false => vec![(db.clone(), feather_db_cli::fsck::repair(&db)?)],
+                };
+                println!();
+                println!("Repaired {} of {} problems", before.saturating_sub(count(&reports)), before);
+                print(&reports);
+            }
+            let hint = !repair && reports.iter().any(|(_, r)| r.problems.iter().any(|p| p.is_repairable()));
+            anyhow::ensure!(count(&reports) == 0, "{:?} has {} problems{}", db, count(&reports),
+                            if hint { "; --repair fixes all but damage" } else { "" });
+        }
+        Commands::Doctor { db } => {
+            let findings = feather_db_cli::doctor::diagnose(&db);
+            if format != OutputFormat::Text {
+                print_json(format, &serde_json::to_value(&findings)?)?;
+            } else {
+                let width = findings.iter().map(|f| f.check.len()).max().unwrap_or(0);
+                for finding in &findings {
+                    println!("{:<4}  {:<width$}  {}", finding.status, finding.check, finding.detail);
+                    if let Some(hint) = &finding.hint {
+                        println!("{:<4}  {:<width$}  -> {}", "", "", hint);
+                    }
+                }
+            }
+            let failed = findings.iter().filter(|f| f.status == feather_db_cli::doctor::Status::Fail).count();
+            anyhow::ensure!(failed == 0, "{:?} failed {} check(s)", db, failed);
+        }
+        Commands::Vacuum { db } => {
+            let before = store_size(&db);
+            // compaction always covers the whole file, every collection included
+            let handle = open(&db, 0, collection, &options, false)?;
+            let mut bar = Bar::new();
+            let removed = handle.compact_with_progress(Some(&mut |p| bar.update(p)))?;
+            bar.finish();
+            handle.save()?;
+            drop(handle);
+            let after = store_size(&db);
+            println!("Vacuumed {:?}: removed {} dead records, {} -> {} bytes",
+                     db, removed, before, after);
+        }
+        Commands::Decay { db, half_life, floor, dry_run } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            let decay = Decay::new(half_life, floor)?;
+            let report = feather_db_cli::decay::apply(&db, &decay, feather_db_cli::decay::now(), dry_run)?;
+            if !dry_run {
+                db.save()?;
+            }
+            println!("{} {} of {} records (half-life {}s, floor {})",
+                     if dry_run { "Would decay" } else { "Decayed" },
+                     report.decayed, report.records, half_life, floor);
+        }
+        Commands::Expire { db: path } => {
+            // expiry is per record, so the sweep covers every collection
+            let db = options.open(&path)?;
+            let expired = db.expire()?;
+            db.save()?;
+            println!("Expired {} record(s) in {:?}; run `feather vacuum` to reclaim the space", expired, path);
+        }
+        Commands::Maintain { db: path, half_life, compact_ratio } => {
+            // expiry, compaction and checkpoints cover every collection
+            let db = options.open(&path)?;
+            anyhow::ensure!(!db.is_read_only(), ReadOnly);
+            let mut policy = feather_db_cli::Maintenance { compact_ratio, ..feather_db_cli::Maintenance::every(std::time::Duration::MAX) };
+            if let Some(half_life) = half_life {
+                policy = policy.decay(Decay::new(half_life, 0.0)?);
+            }
+            db.set_maintenance(Some(policy))?;
+            let report = db.maintain()?;
+            if format != OutputFormat::Text {
+                return print_json(format, &serde_json::to_value(&report)?);
+            }
+            println!("Maintained {:?}: expired {}, decayed {}, compacted {} record(s){}", path, report.expired,
+                     report.decayed, report.compacted, if report.checkpointed { "; checkpointed" } else { "" });
+        }
+        Commands::Fork { db: path, path: fork_path } => {
+            // a fork covers the whole file, every collection included
+            let db = options.open(&path)?;
+            let fork = db.fork(&fork_path)?;
+            println!("Forked {:?} into {:?} (shared snapshot {:?})",
+                     path, fork_path, fork.fork_base().unwrap_or_default());
+        }
+        Commands::Redim { db, to, method, modality, sample, subspaces } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            let from = db.dim(&modality);
+            let mut bar = Bar::new();
+            let proj = fit_projection(&db, &modality, method, to, sample, subspaces, &mut bar)?;
+            let n = db.reproject_with_progress(&modality, proj, Some(&mut |p| bar.update(p)))?;
+            bar.finish();
+            println!("Reprojected {} vectors in modality '{}': {} -> {} dims", n, modality, from, to);
+        }
+        Commands::Serve { db: path, data_dir, http, replicate_to, follow, warm, webhooks, access_file, api_key_file, tls_cert,
+                          tls_key, rate_limit, max_searches, search_budget, maintenance, maintenance_half_life } => {
+            if let (Some(cert), Some(key)) = (&tls_cert, &tls_key) {
+                // TLS lives in its own crate, so this one needs no TLS stack
+                let mut https = std::process::Command::new("feather-https");
+                match (&path, &data_dir) {
+                    (_, Some(dir)) => https.arg("--data-dir").arg(dir),
+                    (path, None) => https.arg(path.as_ref().expect("required without --data-dir")),
+                };
+                https.args(["--http", &http]).arg("--tls-cert").arg(cert).arg("--tls-key").arg(key);
+                if let Some(file) = &api_key_file { https.arg("--api-key-file").arg(file); }
+                if let Some(file) = &access_file { https.arg("--access-file").arg(file); }
+                if let Some(name) = collection { https.args(["--collection", name]); }
+                for addr in &replicate_to { https.args(["--replicate-to", addr]); }
+                for url in &webhooks { https.args(["--webhook", url]); }
+                if warm { https.arg("--warm"); }
+                if let Some(rate) = rate_limit { https.args(["--rate-limit", &rate.to_string()]); }
+                if let Some(n) = max_searches { https.args(["--max-searches", &n.to_string()]); }
+                if let Some(ms) = search_budget { https.args(["--search-budget", &ms.to_string()]); }
+                if let Some(secs) = maintenance { https.args(["--maintenance", &secs.to_string()]); }
+                if let Some(secs) = maintenance_half_life { https.args(["--maintenance-half-life", &secs.to_string()]); }
+                let status = https.status()
+                    .map_err(|e| anyhow::anyhow!("--tls-cert needs feather-https on the PATH (cargo install --path feather-https): {}", e))?;
+                std::process::exit(status.code().unwrap_or(1));
+            }
+            // serving returns once either asks it to stop, and the stores
+            // close, saved, as it does
+            feather_db_cli::shutdown::on_signals()?;
+            let bind = |addr: &str| std::net::TcpListener::bind(addr)
+                .map_err(|e| anyhow::anyhow!("cannot listen on {}: {}", addr, e));
+            let tokens = feather_db_cli::Tokens::load_files(api_key_file.as_deref(), access_file.as_deref())?;
+            if let Some(tokens) = &tokens {
+                println!("Answering only requests with one of {} token(s)", tokens.len());
+            }
+            anyhow::ensure!(max_searches != Some(0), "--max-searches must be at least 1");
+            let limits = feather_db_cli::Limits {
+                rate: rate_limit,
+                max_searches,
+                search_budget: search_budget.map(std::time::Duration::from_millis),
+            };
+            if let Some(dir) = data_dir {
+                anyhow::ensure!(collection.is_none(), "a data dir serves whole stores: drop --collection");
+                let listener = bind(&http)?;
+                println!("Serving the stores in {:?} on http://{}/db/{{name}}", dir, listener.local_addr()?);
+                feather_db_cli::tenants::serve(&dir, &listener, tokens.as_ref(), &limits)?;
+                println!("Stopped; the stores in {:?} are saved", dir);
+                return Ok(());
+            }
+            let path = path.expect("required without --data-dir");
+            if let Some(follow) = follow {
+                anyhow::ensure!(collection.is_none(), "a replica serves the whole file: drop --collection");
+                let follow = match follow.parse::<u16>() {
+                    Ok(port) => format!("127.0.0.1:{}", port),
+                    Err(_) => follow,
+                };
+                let secret = feather_db_cli::replicate::Secret::load(api_key_file.as_deref().expect("required by --follow"))?;
+                let (replication, listener) = (bind(&follow)?, bind(&http)?);
+                println!("Replica {:?} of the primary at {}, serving on http://{}", path,
+                         replication.local_addr()?, listener.local_addr()?);
+                feather_db_cli::replicate::follow(&path, replication, &listener, &secret, tokens.as_ref(), &limits)?;
+                println!("Stopped; {:?} is saved", path);
+                return Ok(());
+            }
+            let db = open(&path, 0, collection, &options, true)?;
+            if let Some(secs) = maintenance {
+                let mut policy = feather_db_cli::Maintenance::every(std::time::Duration::from_secs_f64(secs));
+                if let Some(half_life) = maintenance_half_life {
+                    policy = policy.decay(Decay::new(half_life, 0.0)?);
+                }
+                db.set_maintenance(Some(policy))?;
+                println!("Maintaining the store every {}s", secs);
+            }
+            if warm {
+                let start = std::time::Instant::now();
+                let bytes = db.warm()?;
+                println!("Warmed {:.1} MB in {:.2}s", bytes as f64 / 1e6, start.elapsed().as_secs_f64());
+            }
+            let listener = bind(&http)?;
+            println!("Serving {:?} on http://{}", path, listener.local_addr()?);
+            let hooks = match webhooks.is_empty() {
+                true => None,
+                false => Some(feather_db_cli::webhook::Webhooks::new(&db, &webhooks)?),
+            };
+            for url in &webhooks {
+                println!("Posting adds and deletes to {}", url);
+            }
+            let mut primary = match replicate_to.is_empty() {
+                true => None,
+                false => {
+                    let file = api_key_file.as_deref().expect("required by --replicate-to");
+                    Some(feather_db_cli::replicate::Primary::new(&db, &replicate_to, &feather_db_cli::replicate::Secret::load(file)?)?)
+                }
+            };
+            if let Some(primary) = &primary {
+                for addr in &replicate_to {
+                    let state = if primary.connected().contains(&addr.as_str()) { "connected" } else { "not reachable yet" };
+                    println!("Replicating to {} ({})", addr, state);
+                }
+            }
+            feather_db_cli::serve::serve_with(&db, &listener, primary.as_mut(), hooks.as_ref(), tokens.as_ref(), &limits)?;
+            drop((primary, hooks, db));
+            println!("Stopped; {:?} is saved", path);
+        }
+        Commands::Repl { db: path } => {
+            feather_db_cli::shutdown::on_signals()?;
+            let db = open(&path, 0, collection, &embedding, true)?;
+            if embed_model.is_some() {
+                db.set_embedder(embedder(embed_model, embed_api)?);
+            }
+            let stdin = std::io::stdin();
+            let prompt = std::io::IsTerminal::is_terminal(&stdin);
+            if prompt {
+                println!("{:?}: {} records. Type `help` for commands, `quit` to leave.", path, db.all_ids().len());
+            }
+            feather_db_cli::repl::run(&db, stdin.lock(), std::io::stdout().lock(), prompt)?;
+        }
+        Commands::Mcp { db: path } => {
+            feather_db_cli::shutdown::on_signals()?;
+            let db = open(&path, 0, collection, &options, true)?;
+            feather_db_cli::mcp::serve(&db, std::io::stdin().lock(), std::io::stdout().lock())?;
+        }
+        Commands::Reduce { db: path, dim, out, method, modality, sample, subspaces } => {
+            anyhow::ensure!(!out.exists(), "{:?} already exists", out);
+            let db = open(&path, 0, collection, &options, false)?;
+            anyhow::ensure!(db.fork_base().is_none(), "cannot reduce a fork; merge it first");
+            let from = db.dim(&modality);
+            let mut bar = Bar::new();
+            let proj = fit_projection(&db, &modality, method, dim, sample, subspaces, &mut bar)?;
+            // save the whole store as `out`, then reproject the copy
+            db.persist_to(&out)?;
+            let n = db.reproject_with_progress(&modality, proj, Some(&mut |p| bar.update(p)))?;
+            bar.finish();
+            println!("Wrote {:?}: {} vectors in modality '{}' reduced from {} to {} dims",
+                     out, n, modality, from, dim);
+        }
+        Commands::Outliers { db, k, threshold, modality, quarantine } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            let found = feather_db_cli::analysis::outliers(&db, &modality, k, threshold)?;
+            for o in &found {
+                println!("ID: {}  kNN distance: {:.4}  z: {:.2}", o.id, o.knn_distance, o.z_score);
+                if quarantine {
+                    db.set_attribute(o.id, "quarantine", "outlier")?;
+                }
+            }
+            if quarantine && !found.is_empty() {
+                db.save()?;
+            }
+            println!("{} outlier(s) in modality '{}' (k={}, threshold={})",
+                     found.len(), modality, k, threshold);
+        }
+        Commands::Merge { dst, srcs, on_conflict } => {
+            let policy = match on_conflict {
+                OnConflict::Skip => MergePolicy::Skip,
+                OnConflict::Overwrite => MergePolicy::Overwrite,
+                OnConflict::Remap => MergePolicy::Remap,
+            };
+            let dst_db = open(&dst, 0, collection, &options, true)?;
+            for src in &srcs {
+                anyhow::ensure!(src.exists(), "source {:?} does not exist", src);
+                let src_db = open(src, 0, collection, &options, false)?;
+                let report = feather_db_cli::merge::merge_into(&dst_db, &src_db, policy)?;
+                let mut remapped: Vec<_> = report.remapped.iter().collect();
+                remapped.sort();
+                for (from, to) in remapped {
+                    println!("  remapped {} -> {}", from, to);
+                }
+                println!("Merged {:?}: {} copied, {} skipped, {} overwritten, {} remapped",
+                         src, report.copied, report.skipped, report.overwritten, report.remapped.len());
+            }
+            dst_db.save()?;
+        }
+        Commands::MergeFork { db, fork, strategy, conflicts } => {
+            let strategy = match strategy {
+                ForkMergeStrategy::NewestWins => ForkStrategy::NewestWins,
+                ForkMergeStrategy::Manual => ForkStrategy::Manual,
+            };
+            anyhow::ensure!(fork.exists(), "fork {:?} does not exist", fork);
+            let dst_db = options.open(&db)?;
+            let fork_db = options.open(&fork)?;
+            // merge the collection asked for, or every one the fork has
+            let scopes: Vec<Option<String>> = match collection {
+                Some(name) => vec![Some(name.to_string())],
+                None => std::iter::once(None).chain(fork_db.collections().into_iter().map(Some)).collect(),
+            };
+            let conflicts_path = conflicts.unwrap_or_else(|| {
+                let mut p = fork.clone().into_os_string();
+                p.push(".conflicts.jsonl");
+                PathBuf::from(p)
+            });
+            let mut writer = None;
+            for scope in scopes {
+                let scoped;
+                let (dst, src) = match &scope {
+                    Some(name) => {
+                        scoped = (dst_db.collection(name)?, fork_db.collection(name)?);
+                        (&scoped.0, &scoped.1)
+                    }
+                    None => (&dst_db, &fork_db),
+                };
+                let report = feather_db_cli::merge::merge_fork(dst, src, strategy)?;
+                let label = scope.as_deref().map_or(String::new(), |n| format!(" (collection '{}')", n));
+                println!("Merged {:?}{}: {} applied, {} forgotten, {} recall updates, {} resolved, {} conflicts",
+                         fork, label, report.applied, report.forgotten, report.recalls, report.resolved,
+                         report.conflicts.len());
+                for (id, record) in &report.conflicts {
+                    match record {
+                        Some(record) => {
+                            let w = match &mut writer {
+                                Some(w) => w,
+                                None => writer.insert(JsonlWriter::new(std::io::BufWriter::new(
+                                    std::fs::File::create(&conflicts_path)?))),
+                            };
+                            w.write(record)?;
+                            println!("  conflict: {} changed on both sides", id);
+                        }
+                        None => println!("  conflict: {} changed here but forgotten in the fork", id),
+                    }
+                }
+            }
+            if let Some(mut w) = writer {
+                w.finish()?;
+                println!("Wrote the fork's side of the conflicts to {:?}; `feather import` it to take them",
+                         conflicts_path);
+            }
+            dst_db.save()?;
+        }
+        Commands::Stats { db: path, drift_threshold, reset_drift } => {
+            let db = open(&path, 0, collection, &options, false)?;
+            let mut modalities = db.modalities();
+            modalities.sort();
+            if format != OutputFormat::Text {
+                let modalities: Vec<serde_json::Value> = modalities.iter().map(|modality| {
+                    let drift = feather_db_cli::drift::report(&db, modality).map(|r| serde_json::json!({
+                        "queries": r.queries,
+                        "centroid_shift": r.centroid_shift,
+                        "query_norm_mean": r.query_norm_mean,
+                        "query_norm_std": r.query_norm_std,
+                        "stored_norm_mean": r.stored_norm_mean,
+                        "stored_norm_std": r.stored_norm_std,
+                        "drifted": r.drifted(drift_threshold),
+                    }));
+                    serde_json::json!({ "name": modality, "vectors": db.ids(modality).len(),
+                                        "dim": db.dim(modality), "drift": drift })
+                }).collect();
+                print_json(format, &serde_json::json!({
+                    "database": path,
+                    "collection": db.collection_name(),
+                    "collections": db.collections(),
+                    "shards": db.shard_count(),
+                    "compression": db.compression().name(),
+                    "metric": db.metric().name(),
+                    "model": db.model(),
+                    "records": db.all_ids().len(),
+                    "modalities": modalities,
+                    "indexes": db.indexes().into_iter().map(IndexField::name).collect::<Vec<_>>(),
+                }))?;
+                if reset_drift {
+                    db.reset_drift();
+                }
+                return Ok(());
+            }
+            println!("Database: {:?}", path);
+            match db.collection_name() {
+                Some(name) => println!("Collection: '{}'", name),
+                None if !db.collections().is_empty() => println!("Collections: {}", db.collections().join(", ")),
+                None => {}
+            }
+            if db.shard_count() > 1 {
+                println!("Shards:   {}", db.shard_count());
+            }
+            if db.compression() != Compression::None {
+                println!("Packed:   {}", db.compression().name());
+            }
+            if db.metric() != Metric::L2 {
+                println!("Metric:   {}", db.metric().name());
+            }
+            if let Some(model) = db.model() {
+                println!("Model:    {}", model);
+            }
+            println!("Records:  {}", db.all_ids().len());
+            for modality in &modalities {
+                println!("Modality '{}': {} vectors, dim {}", modality, db.ids(modality).len(), db.dim(modality));
+                let Some(r) = feather_db_cli::drift::report(&db, modality) else { continue };
+                println!("  drift: {} queries, centroid shift {:.3}, query norm {:.3}±{:.3} vs stored {:.3}±{:.3}",
+                         r.queries, r.centroid_shift, r.query_norm_mean, r.query_norm_std,
+                         r.stored_norm_mean, r.stored_norm_std);
+                if r.drifted(drift_threshold) {
+                    println!("  ALERT: query distribution has drifted from the stored vectors \
+                              (embedding model or traffic changed?)");
+                }
+            }
+            let indexes = db.indexes();
+            if !indexes.is_empty() {
+                println!("Indexes:  {}", indexes.iter().map(|f| f.name()).collect::<Vec<_>>().join(", "));
+            }
+            if reset_drift {
+                db.reset_drift();
+            }
+        }
+        Commands::ContextTypes { db: path, add } => {
+            let db = open(&path, 0, collection, &options, false)?;
+            for (name, code) in &add {
+                db.register_context_type(name, *code)?;
+            }
+            if !add.is_empty() {
+                db.save()?;
+            }
+            for (name, kind) in db.context_types() {
+                println!("{:>3}  {}", kind.code(), name);
+            }
+        }
+        Commands::Modalities { db: path, add } => {
+            let db = open(&path, 0, collection, &options, false)?;
+            for (name, dim) in &add {
+                db.register_modality(name, *dim)?;
+            }
+            if !add.is_empty() {
+                db.save()?;
+            }
+            let mut modalities = db.modalities();
+            modalities.sort();
+            for modality in &modalities {
+                println!("Modality '{}': {} vectors, dim {}", modality, db.ids(modality).len(), db.dim(modality));
+            }
+        }
+        Commands::Scoring { db: path, set, clear } => {
+            let db = open(&path, 0, collection, &options, false)?;
+            if set.is_some() || clear {
+                db.set_scoring_policy(set)?;
+                db.save()?;
+            }
+            match db.scoring_policy() {
+                Some(policy) => println!("Scoring: {}", policy),
+                None => println!("Scoring: similarity (no default policy)"),
+            }
+        }
+        Commands::Consolidate { db: path, threshold, modality, summarize, forget, dry_run } => {
+            let db = open(&path, 0, collection, &options, false)?;
+            let command = summarize.map(|command| move |members: &[Metadata]| -> anyhow::Result<String> {
+                use std::io::Write;
+                let contents: Vec<&str> = members.iter().map(|m| m.content.as_str()).collect();
+                let mut child = std::process::Command::new("sh").arg("-c").arg(&command)
+                    .stdin(std::process::Stdio::piped())
+                    .stdout(std::process::Stdio::piped())
+                    .spawn()
+                    .map_err(|e| anyhow::anyhow!("cannot run `{}`: {}", command, e))?;
+                child.stdin.take().expect("piped").write_all(serde_json::to_string(&contents)?.as_bytes())?;
+                let output = child.wait_with_output()?;
+                anyhow::ensure!(output.status.success(), "`{}` failed ({})", command, output.status);
+                Ok(String::from_utf8(output.stdout)?.trim_end().to_string())
+            });
+            let summarizer = command.as_ref().map(|c| c as &dyn feather_db_cli::Summarizer);
+            let done = db.consolidate(&modality, threshold, summarizer, dry_run)?;
+            if forget {
+                for member in done.iter().flat_map(|c| &c.members) {
+                    db.forget(*member)?;
+                }
+            }
+            if !dry_run { db.save()?; }
+            if format != OutputFormat::Text {
+                return print_json(format, &serde_json::to_value(&done)?);
+            }
+            for c in &done {
+                let members: Vec<String> = c.members.iter().map(u64::to_string).collect();
+                println!("{} {} into {}", if dry_run { "Would fold" } else { "Folded" }, members.join(", "), c.id);
+            }
+            let folded: usize = done.iter().map(|c| c.members.len()).sum();
+            println!("{} {} record(s) into {}", if dry_run { "Would consolidate" } else { "Consolidated" },
+                     folded, done.len());
+        }
+        Commands::Dupes { db: path, threshold, modality, delete } => {
+            let db = open(&path, 0, collection, &options, false)?;
+            let found = db.dupes(&modality, threshold)?;
+            if delete {
+                for id in found.iter().flat_map(|d| d.newer()) {
+                    db.forget(*id)?;
+                }
+                db.save()?;
+            }
+            if format != OutputFormat::Text {
+                return print_json(format, &serde_json::to_value(&found)?);
+            }
+            for d in &found {
+                let newer: Vec<String> = d.newer().iter().map(u64::to_string).collect();
+                println!("{} {}: {}{}", if delete { "Kept" } else { "Keep" }, d.members[0], newer.join(", "),
+                         if d.same_content { "  (same content)" } else { "" });
+            }
+            let copies: usize = found.iter().map(|d| d.newer().len()).sum();
+            println!("{} {} newer cop{} in {} group(s) (threshold={})", if delete { "Forgot" } else { "Found" },
+                     copies, if copies == 1 { "y" } else { "ies" }, found.len(), threshold);
+        }
+        Commands::Cluster { db: path, k, modality, iterations, show, dry_run } => {
+            let db = open(&path, 0, collection, &options, false)?;
+            let clusters = db.cluster(&modality, k, iterations, dry_run)?;
+            if !dry_run { db.save()?; }
+            if format != OutputFormat::Text {
+                let clusters: Vec<serde_json::Value> = clusters.iter()
+                    .map(|c| serde_json::json!({
+                        "label": c.label,
+                        "size": c.members.len(),
+                        "spread": c.spread,
+                        "representatives": &c.members[..show.min(c.members.len())],
+                    }))
+                    .collect();
+                return print_json(format, &serde_json::Value::Array(clusters));
+            }
+            for c in &clusters {
+                println!("Cluster {}: {} record(s), spread {:.4}", c.label, c.members.len(), c.spread);
+                for &id in c.members.iter().take(show) {
+                    println!("  {}  {}", id, content_label(db.get_metadata(id).as_ref()));
+                }
+            }
+            let records: usize = clusters.iter().map(|c| c.members.len()).sum();
+            println!("{} {} record(s) of modality '{}' into {} cluster(s)",
+                     if dry_run { "Would label" } else { "Labelled" }, records, modality, clusters.len());
+        }
+        Commands::Sessions { db: path, forget } => {
+            let db = open(&path, 0, collection, &options, false)?;
+            if let Some(session) = &forget {
+                let forgotten = db.forget_session(session)?;
+                db.save()?;
+                println!("Forgot {} record(s) of session {}", forgotten, session);
+                return Ok(());
+            }
+            let sessions = db.sessions();
+            if format != OutputFormat::Text {
+                let sessions: Vec<serde_json::Value> = sessions.iter()
+                    .map(|(session, records)| serde_json::json!({ "session": session, "records": records }))
+                    .collect();
+                return print_json(format, &serde_json::Value::Array(sessions));
+            }
+            if sessions.is_empty() { println!("No sessions"); }
+            for (session, records) in &sessions {
+                println!("{}  {} record(s)", session, records);
+            }
+        }
+        Commands::Sources { db } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            let sources = db.sources();
+            if format != OutputFormat::Text {
+                let sources: Vec<serde_json::Value> = sources.iter()
+                    .map(|(source, records)| serde_json::json!({ "source": source, "records": records }))
+                    .collect();
+                return print_json(format, &serde_json::Value::Array(sources));
+            }
+            if sources.is_empty() { println!("No records"); }
+            for (source, records) in &sources {
+                let name = if source.is_empty() { "(none)" } else { source.as_str() };
+                println!("{}  {} record(s)", name, records);
+            }
+        }
+        Commands::RenameSource { db, from, to } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            let renamed = db.rename_source(&from, &to)?;
+            db.save()?;
+            println!("Moved {} record(s) from source '{}' to '{}'", renamed, from, to);
+        }
+        Commands::History { db: path, id, enable, disable } => {
+            let db = open(&path, 0, collection, &options, false)?;
+            if enable || disable {
+                db.set_audit(enable)?;
+                db.save()?;
+                match enable {
+                    true => println!("Logging changes to {:?}", feather_db_cli::audit::log_path(&path)),
+                    false => println!("Stopped logging changes"),
+                }
+                return Ok(());
+            }
+            let entries = db.history(id)?;
+            if format != OutputFormat::Text {
+                return print_json(format, &serde_json::to_value(&entries)?);
+            }
+            if entries.is_empty() {
+                println!("{}", if db.audits() { "No changes logged" } else { "No changes logged; `feather history DB --enable` starts" });
+            }
+            for e in &entries {
+                let mut line = format!("{}  {:<6}  {}", feather_db_cli::decay::format_time(e.at), e.op.name(), e.id);
+                if let Some(target) = e.target { line += &format!(" -> {}", target); }
+                if let Some(rel_type) = &e.rel_type { line += &format!(" ({})", rel_type); }
+                if let Some(actor) = &e.actor { line += &format!("  by {}", actor); }
+                println!("{}", line);
+            }
+        }
+        Commands::Snapshots { db: path, keep } => {
+            let db = open(&path, 0, collection, &options, false)?;
+            if let Some(keep) = keep {
+                db.set_snapshots(keep)?;
+                db.save()?;
+            }
+            let snapshots = feather_db_cli::snapshots::list(&path)?;
+            if format != OutputFormat::Text {
+                let taken: Vec<_> = snapshots.iter()
+                    .map(|(at, file)| serde_json::json!({"at": at, "path": file, "bytes": std::fs::metadata(file).map_or(0, |m| m.len())}))
+                    .collect();
+                return print_json(format, &serde_json::json!({"keep": db.snapshots(), "snapshots": taken}));
+            }
+            match db.snapshots() {
+                0 => println!("Snapshots: off"),
+                keep => println!("Snapshots: the newest {} saves", keep),
+            }
+            for (at, file) in &snapshots {
+                println!("{}  {}  {:.1} MB", at, feather_db_cli::decay::format_time(*at), store_size(file) as f64 / 1e6);
+            }
+        }
+        Commands::Backup { db: path, out, incremental: _, since } => {
+            let report = match since {
+                Some(since) => feather_db_cli::backup::backup_since(&path, since, &out)?,
+                None => feather_db_cli::backup::backup(&path, &out)?,
+            };
+            if format != OutputFormat::Text {
+                return print_json(format, &serde_json::to_value(&report)?);
+            }
+            match report.since {
+                Some(since) => println!("Backed up {:?} to {:?}: {} change(s) since {}, {:.1} MB",
+                                        path, out, report.entries, since, report.bytes as f64 / 1e6),
+                None => println!("Backed up {:?} to {:?}: {:.1} MB", path, out, report.bytes as f64 / 1e6),
+            }
+            println!("Backup {} ({}); take the next incremental backup --since {}",
+                     report.id, feather_db_cli::decay::format_time(report.id), report.id);
+        }
+        Commands::Budget { db: path, max_records, max_bytes, clear } => {
+            let db = open(&path, 0, collection, &options, false)?;
+            if max_records.is_some() || max_bytes.is_some() || clear {
+                db.set_budget((!clear).then_some(Budget { max_records, max_bytes }))?;
+                let evicted = db.enforce_budget();
+                db.save()?;
+                if evicted > 0 {
+                    println!("Evicted {} record(s); run `feather vacuum` to reclaim the space", evicted);
+                }
+            }
+            let usage = db.usage();
+            match db.budget() {
+                Some(budget) => println!("Budget: {}", budget),
+                None => println!("Budget: none"),
+            }
+            println!("Usage:  {} records, {:.1} MB", usage.records, usage.bytes as f64 / 1e6);
+        }
+        Commands::Index { db: path, add, drop } => {
+            let db = open(&path, 0, collection, &options, false)?;
+            for field in &add {
+                db.set_index(field.field(), true)?;
+            }
+            for field in &drop {
+                db.set_index(field.field(), false)?;
+            }
+            if !add.is_empty() || !drop.is_empty() {
+                db.save()?;
+            }
+            let indexes: Vec<&str> = db.indexes().into_iter().map(IndexField::name).collect();
+            println!("Indexes: {}", if indexes.is_empty() { "none".to_string() } else { indexes.join(", ") });
+        }
+        Commands::Import { db, file, format, from, source_collection, modality, batch_size, on_duplicate, dedup,
+                           dedup_epsilon, dedup_merge, links } => {
+            let links = links.map(|path| {
+                let input = std::fs::File::open(&path).map_err(|e| anyhow::anyhow!("{:?}: {}", path, e))?;
+                anyhow::Ok((feather_db_cli::bootstrap::read_links(input)?, path))
+            }).transpose()?;
+            let source = from.map(|from| {
+                let name = source_collection.or_else(|| collection.map(str::to_string))
+                    .ok_or_else(|| anyhow::anyhow!("name the collection to read with --collection or --source-collection"))?;
+                anyhow::Ok(match from {
+                    MigrateFrom::Qdrant => Source::Qdrant { url: file.to_string_lossy().into_owned(), collection: name },
+                    MigrateFrom::Chroma => Source::Chroma { dir: file.clone(), collection: name },
+                })
+            }).transpose()?;
+            let format = match format {
+                Some(f) => f,
+                None => match file.extension().and_then(|e| e.to_str()) {
+                    Some("csv") => ImportFormat::Csv,
+                    Some("parquet") => ImportFormat::Parquet,
+                    Some("arrow" | "ipc" | "feather") => ImportFormat::Arrow,
+                    _ => ImportFormat::Jsonl,
+                },
+            };
+            let db = open(&db, 0, collection, &options, true)?;
+            db.set_on_duplicate(on_duplicate.policy());
+            dedup.apply(&db, dedup_epsilon, dedup_merge)?;
+            let mut bar = Bar::new();
+            let result = if let Some(source) = source {
+                db.migrate(&source, &modality, batch_size, Some(&mut |p| bar.update(p)))
+            } else {
+                let input = || std::fs::File::open(&file);
+                let records: Box<dyn Iterator<Item = anyhow::Result<feather_db_cli::Record>> + Send> = match format {
+                    ImportFormat::Jsonl => Box::new(JsonlReader::new(std::io::BufReader::new(input()?), &modality)),
+                    ImportFormat::Csv => Box::new(CsvReader::new(input()?, &modality)?),
+                    #[cfg(feature = "parquet")]
+                    ImportFormat::Parquet => Box::new(feather_db_cli::import::ParquetReader::new(input()?, &modality)?),
+                    #[cfg(feature = "arrow")]
+                    ImportFormat::Arrow => Box::new(feather_db_cli::import::ArrowReader::new(input()?, &modality)?),
+                    #[allow(unreachable_patterns)]
+                    other => {
+                        let name = other.to_possible_value().expect("no skipped variants").get_name().to_string();
+                        anyhow::bail!("{} import is not built in; rebuild with `--features {}`", name, name)
+                    }
+                };
+                feather_db_cli::import::import_pipelined(&db, records, batch_size, Some(&mut |p| bar.update(p)))
+            };
+            bar.finish();
+            // keep what made it in before a bad row
+            db.save()?;
+            let report = result?;
+            println!("Imported {} records from {:?} in {} batches", report.records, file, report.batches);
+            if let Some((links, path)) = links {
+                let made = db.link_batch_with(&links)?;
+                db.save()?;
+                println!("Applied {} links from {:?}, {} new or reweighted", links.len(), path, made);
+            }
+            if report.skipped > 0 {
+                println!("Skipped {} records whose id already existed", report.skipped);
+            }
+            if report.deduplicated > 0 {
+                let verb = if dedup_merge { "Merged" } else { "Skipped" };
+                println!("{} {} records already stored under another id", verb, report.deduplicated);
+            }
+        }
+        Commands::Ingest { db: path, file, chunk_size, overlap, start_id, source } => {
+            let text = std::fs::read_to_string(&file).map_err(|e| anyhow::anyhow!("{:?}: {}", file, e))?;
+            let embedder = embedder(embed_model, embed_api)?;
+            let db = open(&path, 0, collection, &embedding, true)?;
+            db.set_embedder(embedder);
+            let first_id = start_id.unwrap_or_else(|| db.all_ids().into_iter().max().map_or(0, |max| max + 1));
+            let source = source.unwrap_or_else(|| file.display().to_string());
+            let report = db.ingest(&text, &source, first_id, chunk_size, overlap)?;
+            db.save()?;
+            let last_id = first_id + report.chunks.saturating_sub(1) as u64;
+            print!("Ingested {:?} as {} chunks (IDs {}..={})", file, report.chunks, first_id, last_id);
+            if report.skipped > 0 { print!(", {} already stored", report.skipped); }
+            println!();
+        }
+        Commands::Reembed { db: path, out, provider, modality, batch_size } => {
+            let (model, api) = match &provider {
+                Some(file) => {
+                    let text = std::fs::read_to_string(file).map_err(|e| anyhow::anyhow!("{:?}: {}", file, e))?;
+                    let named = Config::parse(&text).map_err(|e| anyhow::anyhow!("{:?}: {}", file, e))?.defaults;
+                    let model = named.embed_model.ok_or_else(|| anyhow::anyhow!("{:?} names no embed_model", file))?;
+                    (model, named.embed_api)
+                }
+                None => (embed_model.ok_or_else(|| anyhow::anyhow!("reembed needs --provider, or --embed-model"))?.to_string(),
+                         embed_api.map(str::to_string)),
+            };
+            let embedder = embedder(Some(&model), api.as_deref())?;
+            let name = cli.model.clone().unwrap_or_else(|| model_name(&model, api.as_deref()));
+            let db = open(&path, 0, None, &undeclared.clone().read_only(true), false)?;
+            let mut bar = Bar::new();
+            let report = feather_db_cli::reembed::reembed(&db, &embedder, &out, &modality, Some(&name), batch_size,
+                                                          Some(&mut |p| bar.update(p)))?;
+            bar.finish();
+            if format != OutputFormat::Text {
+                return print_json(format, &serde_json::to_value(&report)?);
+            }
+            println!("Re-embedded {} record(s) of {:?} into {:?} with {} ({} dims)", report.records, path, out, name, report.dim);
+            if !report.unmigrated.is_empty() {
+                println!("Left out {} record(s) without content to embed:", report.unmigrated.len());
+                for r in &report.unmigrated {
+                    match &r.collection {
+                        Some(name) => println!("  {} in collection '{}'", r.id, name),
+                        None => println!("  {}", r.id),
+                    }
+                }
+            }
+            if report.links_dropped > 0 {
+                println!("Dropped {} link(s) to records left out", report.links_dropped);
+            }
+        }
+        Commands::Bootstrap { db, vectors, meta, links, modality, batch_size } => {
+            let arr = feather_db_cli::vectors::read_matrix(&vectors)?;
+            let meta = match meta {
+                Some(path) => Some(CsvReader::new(std::fs::File::open(path)?, &modality)?.with_row_ids(1)),
+                None => None,
+            };
+            let links = match links {
+                Some(path) => feather_db_cli::bootstrap::read_links(std::fs::File::open(path)?)?,
+                None => Vec::new(),
+            };
+            let db = open(&db, arr.ncols(), collection, &options, true)?;
+            let mut bar = Bar::new();
+            let result = feather_db_cli::bootstrap::bootstrap(&db, arr.view(), meta, &links, &modality, batch_size,
+                                                              Some(&mut |p| bar.update(p)));
+            bar.finish();
+            let report = result?;
+            db.save()?;
+            println!("Bootstrapped {} records with {} links in {} batches", report.records, report.links, report.batches);
+            let check = &report.check;
+            println!("Check: {} records, {} missing, {} dangling links, {}/{} lookups found",
+                     check.records, check.missing.len(), check.dangling.len(),
+                     check.lookups - check.misses.len(), check.lookups);
+            anyhow::ensure!(check.is_ok(), "check failed; first problem ids: missing {:?}, dangling {:?}, lookup misses {:?}",
+                            &check.missing[..check.missing.len().min(5)],
+                            &check.dangling[..check.dangling.len().min(5)],
+                            &check.misses[..check.misses.len().min(5)]);
+        }
+        Commands::Bench { count, dim, vectors, queries, k, ef, seed, batch_size } => {
+            let (data, query_rows) = match vectors {
+                Some(path) => {
+                    let mut data = feather_db_cli::vectors::read_matrix(&path)?;
+                    anyhow::ensure!(data.nrows() > queries, "{:?} has {} vectors; need more than --queries {}",
+                                    path, data.nrows(), queries);
+                    let queries = data.slice(ndarray::s![data.nrows() - queries.., ..]).to_owned();
+                    data = data.slice(ndarray::s![..data.nrows() - queries.nrows(), ..]).to_owned();
+                    (data, queries)
+                }
+                None => (feather_db_cli::bench::random_vectors(count, dim, seed),
+                         feather_db_cli::bench::random_vectors(queries, dim, seed.wrapping_add(1))),
+            };
+            let dir = std::env::temp_dir().join(format!("feather-bench-{}", std::process::id()));
+            std::fs::create_dir_all(&dir)?;
+            let result = OpenOptions::new().dim(data.ncols()).create(true).open(&dir.join("bench.feather"))
+                .and_then(|db| {
+                    eprintln!("Inserting {} vectors of dim {}...", data.nrows(), data.ncols());
+                    feather_db_cli::bench::run(&db, data.view(), query_rows.view(), k, &ef, batch_size)
+                });
+            let _ = std::fs::remove_dir_all(&dir);
+            let report = result?;
+            if format != OutputFormat::Text {
+                let runs: Vec<serde_json::Value> = report.runs.iter().map(|r| serde_json::json!({
+                    "ef": r.ef,
+                    "p50_ms": r.p50.as_secs_f64() * 1e3,
+                    "p95_ms": r.p95.as_secs_f64() * 1e3,
+                    "p99_ms": r.p99.as_secs_f64() * 1e3,
+                    "mean_ms": r.mean.as_secs_f64() * 1e3,
+                    "qps": r.qps,
+                    "recall": r.recall,
+                })).collect();
+                return print_json(format, &serde_json::json!({
+                    "vectors": report.inserted,
+                    "dim": query_rows.ncols(),
+                    "queries": report.queries,
+                    "k": report.k,
+                    "insert_secs": report.insert_time.as_secs_f64(),
+                    "inserts_per_sec": report.inserts_per_sec(),
+                    "brute_force_ms": report.brute_force.as_secs_f64() * 1e3,
+                    "runs": runs,
+                }));
+            }
+            let ms = |d: std::time::Duration| d.as_secs_f64() * 1e3;
+            println!("Vectors:  {} of dim {}", report.inserted, query_rows.ncols());
+            println!("Insert:   {:.2}s ({:.0} vectors/s)", report.insert_time.as_secs_f64(), report.inserts_per_sec());
+            println!("Queries:  {}, k = {}; brute force {:.3} ms/query", report.queries, report.k, ms(report.brute_force));
+            println!();
+            println!("{:>6}  {:>9}  {:>9}  {:>9}  {:>9}  {:>8}", "ef", "p50 ms", "p95 ms", "p99 ms", "QPS", "recall");
+            for r in &report.runs {
+                println!("{:>6}  {:>9.3}  {:>9.3}  {:>9.3}  {:>9.0}  {:>8.4}",
+                         r.ef, ms(r.p50), ms(r.p95), ms(r.p99), r.qps, r.recall);
+            }
+        }
+        Commands::SearchBatch { db: path, queries, k, modality, device, filter, min_score } => {
+            let db = open(&path, 0, collection, &options, false)?;
+            let query_rows = feather_db_cli::vectors::read_matrix(&queries)?;
+            if matches!(device, DeviceArg::Gpu) && !feather_db_cli::device::gpu_available() {
+                eprintln!("No GPU available; measuring on the CPU");
+            }
+            let options = SearchOptions { filter, min_score, ..SearchOptions::default() }.device(device.device());
+            let start = std::time::Instant::now();
+            let hits = db.search_batch(query_rows.view(), k, &modality, &options)?;
+            let elapsed = start.elapsed();
+            if format != OutputFormat::Text {
+                let rows: Vec<serde_json::Value> = hits.iter().map(|hits| serde_json::json!(
+                    hits.iter().map(|&(id, score)| serde_json::json!({ "id": id, "score": score })).collect::<Vec<_>>()
+                )).collect();
+                return print_json(format, &serde_json::Value::Array(rows));
+            }
+            for (i, hits) in hits.iter().enumerate() {
+                let hits: Vec<String> = hits.iter().map(|(id, score)| format!("{}:{:.4}", id, score)).collect();
+                println!("{}\t{}", i, hits.join(" "));
+            }
+            eprintln!("{} queries in {:.1} ms", hits.len(), elapsed.as_secs_f64() * 1e3);
+        }
+        Commands::Eval { db: path, queries, ground_truth, k, ef, modality } => {
+            let db = open(&path, 0, collection, &options, false)?;
+            let query_rows = feather_db_cli::vectors::read_matrix(&queries)?;
+            let truth = ground_truth.as_deref().map(feather_db_cli::vectors::read_ids).transpose()?;
+            let ef = if ef.is_empty() {
+                vec![db.tuned_ef(&modality).unwrap_or(feather_db_cli::search::DEFAULT_EF)]
+            } else {
+                ef
+            };
+            let report = feather_db_cli::eval::run(&db, query_rows.view(), truth.as_deref(), k, &modality, &ef)?;
+            if format != OutputFormat::Text {
+                let runs: Vec<serde_json::Value> = report.runs.iter().map(|r| serde_json::json!({
+                    "ef": r.ef,
+                    "p50_ms": r.p50.as_secs_f64() * 1e3,
+                    "p95_ms": r.p95.as_secs_f64() * 1e3,
+                    "p99_ms": r.p99.as_secs_f64() * 1e3,
+                    "mean_ms": r.mean.as_secs_f64() * 1e3,
+                    "qps": r.qps,
+                    "recall": r.recall,
+                })).collect();
+                return print_json(format, &serde_json::json!({
+                    "queries": report.queries,
+                    "k": report.k,
+                    "ground_truth": ground_truth.is_some(),
+                    "brute_force_ms": report.brute_force.map(|(_, t)| t.as_secs_f64() * 1e3),
+                    "runs": runs,
+                }));
+            }
+            let ms = |d: std::time::Duration| d.as_secs_f64() * 1e3;
+            match report.brute_force {
+                Some((n, t)) => println!("Queries:  {}, k = {}; exact over {} vectors, {:.3} ms/query",
+                                         report.queries, report.k, n, ms(t)),
+                None => println!("Queries:  {}, k = {}; ground truth {:?}", report.queries, report.k,
+                                 ground_truth.as_deref().unwrap_or(Path::new(""))),
+            }
+            println!();
+            println!("{:>6}  {:>9}  {:>9}  {:>9}  {:>9}  {:>8}", "ef", "p50 ms", "p95 ms", "p99 ms", "QPS",
+                     format!("recall@{}", report.k));
+            for r in &report.runs {
+                println!("{:>6}  {:>9.3}  {:>9.3}  {:>9.3}  {:>9.0}  {:>8.4}",
+                         r.ef, ms(r.p50), ms(r.p95), ms(r.p99), r.qps, r.recall);
+            }
+        }
+        Commands::Tune { db: path, target_recall, k, sample, modality, dry_run, clear } => {
+            let db = open(&path, 0, collection, &options, false)?;
+            if clear {
+                db.set_tuned_ef(&modality, None)?;
+                db.save()?;
+                println!("Cleared the tuned ef of '{}'; searches use ef {} from the next open",
+                         modality, feather_db_cli::search::DEFAULT_EF);
+                return Ok(());
+            }
+            eprintln!("Tuning '{}' for recall@{} >= {}...", modality, k, target_recall);
+            let report = db.tune(&modality, target_recall, k, sample)?;
+            let store = report.ef.filter(|_| !dry_run);
+            if let Some(ef) = store {
+                db.set_tuned_ef(&modality, Some(ef))?;
+                db.save()?;
+            }
+            if format != OutputFormat::Text {
+                let runs: Vec<serde_json::Value> = report.runs.iter().map(|r| serde_json::json!({
+                    "ef": r.ef,
+                    "p50_ms": r.p50.as_secs_f64() * 1e3,
+                    "p95_ms": r.p95.as_secs_f64() * 1e3,
+                    "p99_ms": r.p99.as_secs_f64() * 1e3,
+                    "mean_ms": r.mean.as_secs_f64() * 1e3,
+                    "qps": r.qps,
+                    "recall": r.recall,
+                })).collect();
+                print_json(format, &serde_json::json!({
+                    "modality": modality,
+                    "vectors": report.vectors,
+                    "queries": report.queries,
+                    "k": report.k,
+                    "target_recall": target_recall,
+                    "ef": report.ef,
+                    "stored": store.is_some(),
+                    "runs": runs,
+                }))?;
+            } else {
+                let ms = |d: std::time::Duration| d.as_secs_f64() * 1e3;
+                println!("Queries:  {} of {} vectors, k = {}", report.queries, report.vectors, report.k);
+                println!();
+                println!("{:>6}  {:>9}  {:>9}  {:>9}  {:>9}  {:>8}", "ef", "p50 ms", "p95 ms", "p99 ms", "QPS",
+                         format!("recall@{}", report.k));
+                for r in &report.runs {
+                    println!("{:>6}  {:>9.3}  {:>9.3}  {:>9.3}  {:>9.0}  {:>8.4}",
+                             r.ef, ms(r.p50), ms(r.p95), ms(r.p99), r.qps, r.recall);
+                }
+                println!();
+                match (report.ef, store) {
+                    (Some(ef), Some(_)) => println!("Tuned: ef {} stored as the default of '{}'", ef, modality),
+                    (Some(ef), None) => println!("Tuned: ef {} (dry run, not stored)", ef),
+                    (None, _) => {}
+                }
+            }
+            if report.ef.is_none() {
+                let best = report.runs.iter().map(|r| r.recall).fold(0.0, f64::max);
+                anyhow::bail!("no ef up to {} reaches recall {} (best {:.4}); nothing stored",
+                              feather_db_cli::tune::CANDIDATE_EFS[feather_db_cli::tune::CANDIDATE_EFS.len() - 1],
+                              target_recall, best);
+            }
+        }
+        Commands::Export { db, format, out, links } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            let create = || std::fs::File::create(&out).map(std::io::BufWriter::new);
+            let mut modalities = db.modalities();
+            modalities.sort();
+            let mut writer: Box<dyn RecordWriter> = match format {
+                ExportFormat::Jsonl => Box::new(JsonlWriter::new(create()?)),
+                #[cfg(feature = "parquet")]
+                ExportFormat::Parquet => Box::new(feather_db_cli::export::ParquetWriter::new(create()?, modalities)?),
+                #[cfg(feature = "arrow")]
+                ExportFormat::Arrow => Box::new(feather_db_cli::export::ArrowWriter::new(create()?, modalities)?),
+                #[allow(unreachable_patterns)]
+                other => {
+                    let name = other.to_possible_value().expect("no skipped variants").get_name().to_string();
+                    anyhow::bail!("{} export is not built in; rebuild with `--features {}`", name, name)
+                }
+            };
+            let n = feather_db_cli::export::export(&db, writer.as_mut())?;
+            println!("Exported {} records to {:?}", n, out);
+            if let Some(path) = links {
+                let n = feather_db_cli::export::write_links(&db, std::fs::File::create(&path).map(std::io::BufWriter::new)?)?;
+                println!("Exported {} links to {:?}", n, path);
+            }
+        }
+        Commands::ImportIndex { db, file, format, meta, modality, batch_size } => {
+            let index = IndexFile::read(&file, format.format())?;
+            let open_meta = |path: &PathBuf| std::fs::File::open(path).map_err(|e| anyhow::anyhow!("{:?}: {}", path, e));
+            let rows: Option<Box<dyn Iterator<Item = anyhow::Result<feather_db_cli::Record>>>> = match &meta {
+                Some(path) if path.extension().is_some_and(|e| e == "csv") => {
+                    Some(Box::new(CsvReader::new(open_meta(path)?, &modality)?))
+                }
+                Some(path) => Some(Box::new(JsonlReader::new(std::io::BufReader::new(open_meta(path)?), &modality))),
+                None => None,
+            };
+            let inner_product = index.inner_product;
+            let records = index.records(&modality, rows, feather_db_cli::decay::now())?;
+            let db = open(&db, 0, collection, &options, true)?;
+            let mut bar = Bar::new();
+            let result = feather_db_cli::import::import_pipelined(&db, records.into_iter().map(Ok), batch_size,
+                                                                  Some(&mut |p| bar.update(p)));
+            bar.finish();
+            db.save()?;
+            let report = result?;
+            println!("Imported {} vectors from {:?} into modality '{}'", report.records, file, modality);
+            if report.skipped > 0 {
+                println!("Skipped {} records whose id already existed", report.skipped);
+            }
+            if inner_product {
+                println!("Note: the index ranks by inner product and feather by L2 distance; \
+                          the rankings agree only for unit-length vectors, which `--normalize` makes them");
+            }
+        }
+        Commands::ExportIndex { db, format, out, meta, modality } => {
+            let db = open(&db, 0, collection, &options, false)?;
+            let ids = db.export_index(&out, format.format(), &modality)?;
+            println!("Exported {} vectors of modality '{}' to {:?}", ids.len(), modality, out);
+            if let Some(path) = meta {
+                let file = std::fs::File::create(&path).map_err(|e| anyhow::anyhow!("{:?}: {}", path, e))?;
+                feather_db_cli::ann_index::write_metadata(&db, &ids, &mut std::io::BufWriter::new(file))?;
+                println!("Wrote their metadata to {:?}", path);
             }
         }
     }
@@ -0,0 +1,157 @@
+//! Housekeeping a store does on its own (`OpenOptions::maintenance`,
+//! `DB::maintain`, `feather serve --maintenance`).
+//!
+//! A maintenance pass forgets the records past their time-to-live (see
+//! `DB::expire`), bakes importance decay into every collection if the
+//! policy has a `Decay` (see `decay::apply`), compacts the indexes once
+//! soft-deleted vectors make up `compact_ratio` of one, and checkpoints the
+//! file if anything is unsaved, so the WAL stays short.
+//!
+//! `DB::maintain` runs a pass now. With a `Maintenance` policy a handle also
+//! runs one every `interval` without being asked. A `DB` is not `Send`, so
+//! the pass does not run on a thread of its own but on the handle's, at the
+//! first change once the interval has passed, as an auto-save does (see
+//! `autosave`); `feather serve` also wakes every `interval` when no request
+//! comes, so an idle server keeps up. No pass runs on a read-only handle or
+//! while a transaction holds changes.
+
+use crate::*;
+use std::time::{Duration, Instant};
+
+/// Share of soft-deleted vectors in an index past which a pass compacts.
+pub const DEFAULT_COMPACT_RATIO: f32 = 0.2;
+
+extern "C" {
+    fn feather_dead_share(db: *mut c_void) -> f32;
+}
+
+/// How often a handle maintains itself, and what a pass does.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Maintenance {
+    /// Time between passes.
+    pub interval: Duration,
+    /// Importance decay baked in by each pass; None = none.
+    pub decay: Option<Decay>,
+    /// Compact once this share of an index's vectors are soft-deleted,
+    /// within 0..=1; 1 compacts only an index of nothing but deleted ones.
+    pub compact_ratio: f32,
+}
+
+impl Maintenance {
+    /// A pass every `interval`, without decay.
+    pub fn every(interval: Duration) -> Self {
+        Maintenance { interval, decay: None, compact_ratio: DEFAULT_COMPACT_RATIO }
+    }
+
+    /// This policy, baking `decay` in on each pass.
+    pub fn decay(mut self, decay: Decay) -> Self {
+        self.decay = Some(decay);
+        self
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(!self.interval.is_zero(), "the maintenance interval must be positive");
+        anyhow::ensure!((0.0..=1.0).contains(&self.compact_ratio), "the compaction ratio must be within 0..=1");
+        Ok(())
+    }
+}
+
+/// What a maintenance pass did.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize)]
+pub struct MaintenanceReport {
+    /// Records forgotten for outliving their time-to-live.
+    pub expired: usize,
+    /// Records whose importance decayed.
+    pub decayed: usize,
+    /// Dead records compaction removed.
+    pub compacted: usize,
+    /// Whether the file was checkpointed.
+    pub checkpointed: bool,
+}
+
+// A handle's policy, when it last ran a pass, and whether one is running.
+pub(crate) struct State {
+    policy: Option<Maintenance>,
+    last: Instant,
+    running: bool,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State { policy: None, last: Instant::now(), running: false }
+    }
+}
+
+impl DB {
+    /// The maintenance policy of this file's handles; None = none.
+    pub fn maintenance(&self) -> Option<Maintenance> { self.handle.maintenance.borrow().policy }
+
+    /// Change the maintenance policy for every handle on this file
+    /// (collections included); the first pass comes an interval from now.
+    /// Not persisted.
+    pub fn set_maintenance(&self, policy: Option<Maintenance>) -> anyhow::Result<()> {
+        if let Some(policy) = &policy { policy.validate()?; }
+        let mut state = self.handle.maintenance.borrow_mut();
+        state.policy = policy;
+        state.last = Instant::now();
+        Ok(())
+    }
+
+    /// Run a maintenance pass now (see the module docs), by the policy if
+    /// there is one, else expiring, compacting at `DEFAULT_COMPACT_RATIO`
+    /// and checkpointing.
+    pub fn maintain(&self) -> anyhow::Result<MaintenanceReport> {
+        self.writable()?;
+        anyhow::ensure!(!self.handle.holds_changes(), "cannot maintain the store while a transaction is open");
+        let policy = self.maintenance().unwrap_or(Maintenance::every(Duration::MAX));
+        self.handle.maintenance.borrow_mut().running = true;
+        let report = self.pass(&policy);
+        let mut state = self.handle.maintenance.borrow_mut();
+        state.running = false;
+        state.last = Instant::now();
+        report
+    }
+
+    // Log a change (see `Handle::changed`), then maintain if it is time.
+    pub(crate) fn changed(&self, op: audit::Op, iid: u64, target: Option<u64>, rel_type: Option<&str>) {
+        self.handle.changed(op, iid, target, rel_type);
+        self.maintain_if_due();
+    }
+
+    // Run a pass if the policy says one is due.
+    pub(crate) fn maintain_if_due(&self) {
+        let due = {
+            let state = self.handle.maintenance.borrow();
+            !state.running && state.policy.is_some_and(|p| state.last.elapsed() >= p.interval)
+        };
+        if !due || self.is_read_only() || self.handle.holds_changes() { return; }
+        if let Err(e) = self.maintain() {
+            // the change that came first was made; a failed pass must not undo it
+            Span::new(Level::Warn, "feather::maintenance").record_str("error", &e.to_string());
+        }
+    }
+
+    fn pass(&self, policy: &Maintenance) -> anyhow::Result<MaintenanceReport> {
+        let mut span = Span::new(Level::Info, "feather::maintenance");
+        let mut report = MaintenanceReport { expired: self.handle.expire(), ..MaintenanceReport::default() };
+        if let Some(decay) = &policy.decay {
+            let root = DB { ptr: self.handle.ptr, handle: Rc::clone(&self.handle), scope: None };
+            let now = decay::now();
+            report.decayed += decay::apply(&root, decay, now, false)?.decayed;
+            for name in root.collections() {
+                report.decayed += decay::apply(&root.collection(&name)?, decay, now, false)?.decayed;
+            }
+        }
+        let dead = self.handle.cores().iter().map(|&core| unsafe { feather_dead_share(core) }).fold(0.0, f32::max);
+        if dead > 0.0 && dead >= policy.compact_ratio {
+            report.compacted = self.handle.compact(None);
+        }
+        if self.unsaved_changes() > 0 || report.compacted > 0 {
+            self.handle.checkpoint();
+            report.checkpointed = true;
+        }
+        span.record("expired", report.expired).record("decayed", report.decayed)
+            .record("compacted", report.compacted).record("checkpointed", report.checkpointed);
+        Ok(report)
+    }
+}
@@ -0,0 +1,227 @@
+//! `feather mcp`: one store as long-term memory for an MCP (Model Context
+//! Protocol) client, over stdio.
+//!
+//! Messages are JSON-RPC 2.0, one per line. The server answers
+//! `initialize`, `ping`, `tools/list` and `tools/call`, and offers three
+//! tools:
+//!
+//! - `remember {content, importance?, source?, vector?}` stores a memory
+//!   under the next free id and replies with that id. Without a vector the
+//!   memory is found by its keywords alone.
+//! - `recall {query, k?, vector?}` replies with the best `k` memories for
+//!   the keywords of `query` (BM25), or for `vector` ranked with `query` as
+//!   hybrid keywords if one is given; recalled memories count as recalled.
+//! - `forget {id}` forgets a memory.
+//!
+//! A tool that fails replies with `isError` set, as the protocol asks, so
+//! the model sees why. Writes reach the WAL at once; the file is
+//! checkpointed when the client closes stdin, or on SIGINT or SIGTERM.
+
+use crate::{decay, shutdown, Inserted, Metadata, SearchOptions, DB};
+use serde_json::{json, Map, Value};
+use std::io::{BufRead, Write};
+
+/// Protocol revisions understood, oldest first; a client asking for another
+/// is offered the newest.
+pub const PROTOCOL_VERSIONS: [&str; 3] = ["2024-11-05", "2025-03-26", "2025-06-18"];
+
+/// Memories `recall` returns when the call names no `k`.
+const DEFAULT_K: usize = 5;
+
+// JSON-RPC error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Answer messages read from `input` on `output` until `input` ends or the
+/// process is asked to stop (see `shutdown`), then checkpoint the file.
+pub fn serve(db: &DB, input: impl BufRead, mut output: impl Write) -> anyhow::Result<()> {
+    for line in shutdown::lines(input) {
+        let line = line?;
+        if line.trim().is_empty() { continue; }
+        let reply = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => handle(db, &message),
+            Err(e) => Some(error(Value::Null, PARSE_ERROR, format!("not JSON: {}", e))),
+        };
+        if let Some(reply) = reply {
+            writeln!(output, "{}", reply)?;
+            output.flush()?;
+        }
+    }
+    db.save()?;
+    Ok(())
+}
+
+/// Answer one JSON-RPC message; None for a notification, which gets no reply.
+pub fn handle(db: &DB, message: &Value) -> Option<Value> {
+    let id = message.get("id").cloned();
+    let Some(method) = message.get("method").and_then(Value::as_str) else {
+        return id.map(|id| error(id, INVALID_REQUEST, "no method"));
+    };
+    // notifications (`notifications/initialized` and the like) need nothing
+    let id = id?;
+    let params = message.get("params").cloned().unwrap_or_else(|| json!({}));
+    let result = match method {
+        "initialize" => initialize(&params),
+        "ping" => json!({}),
+        "tools/list" => json!({ "tools": tools() }),
+        "tools/call" => {
+            let Some(name) = params.get("name").and_then(Value::as_str) else {
+                return Some(error(id, INVALID_PARAMS, "tools/call takes a tool `name`"));
+            };
+            let arguments = match params.get("arguments").cloned().unwrap_or_else(|| json!({})) {
+                Value::Object(arguments) => arguments,
+                _ => return Some(error(id, INVALID_PARAMS, "`arguments` must be an object")),
+            };
+            let outcome = match name {
+                "remember" => remember(db, arguments),
+                "recall" => recall(db, arguments),
+                "forget" => forget(db, arguments),
+                _ => return Some(error(id, INVALID_PARAMS, format!("no tool `{}`", name))),
+            };
+            let (text, failed) = match outcome {
+                Ok(text) => (text, false),
+                Err(e) => (format!("{:#}", e), true),
+            };
+            json!({ "content": [{ "type": "text", "text": text }], "isError": failed })
+        }
+        _ => return Some(error(id, METHOD_NOT_FOUND, format!("no method `{}`", method))),
+    };
+    Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+}
+
+fn error(id: Value, code: i64, message: impl std::fmt::Display) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message.to_string() } })
+}
+
+fn initialize(params: &Value) -> Value {
+    let asked = params.get("protocolVersion").and_then(Value::as_str);
+    let version = asked
+        .filter(|v| PROTOCOL_VERSIONS.contains(v))
+        .unwrap_or(PROTOCOL_VERSIONS[PROTOCOL_VERSIONS.len() - 1]);
+    json!({
+        "protocolVersion": version,
+        "capabilities": { "tools": {} },
+        "serverInfo": { "name": "feather", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+fn tools() -> Value {
+    let vector = json!({
+        "type": "array",
+        "items": { "type": "number" },
+        "description": "Embedding of the text, if the client has one; it must match the store's dimension",
+    });
+    json!([
+        {
+            "name": "remember",
+            "description": "Store a memory for later recall. Replies with its id.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "content": { "type": "string", "description": "The text to remember" },
+                    "importance": { "type": "number", "description": "How much it matters, 0 to 1 (default 1)" },
+                    "source": { "type": "string", "description": "Where it came from" },
+                    "vector": vector,
+                },
+                "required": ["content"],
+            },
+        },
+        {
+            "name": "recall",
+            "description": "Find the stored memories that best match a query, best first, with their ids.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "Keywords to look for" },
+                    "k": { "type": "integer", "minimum": 1, "description": "Most memories to return (default 5)" },
+                    "vector": vector,
+                },
+                "required": ["query"],
+            },
+        },
+        {
+            "name": "forget",
+            "description": "Forget a stored memory by its id.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "id": { "type": "integer", "minimum": 0 } },
+                "required": ["id"],
+            },
+        },
+    ])
+}
+
+// The argument `key` of a tool call, removed from it; unknown arguments are
+// left for `done` to reject.
+fn take<T: serde::de::DeserializeOwned>(args: &mut Map<String, Value>, key: &str) -> anyhow::Result<Option<T>> {
+    args.remove(key)
+        .filter(|v| !v.is_null())
+        .map(|v| serde_json::from_value(v).map_err(|e| anyhow::anyhow!("bad `{}`: {}", key, e)))
+        .transpose()
+}
+
+fn done(args: &Map<String, Value>) -> anyhow::Result<()> {
+    match args.keys().next() {
+        Some(key) => anyhow::bail!("unknown argument `{}`", key),
+        None => Ok(()),
+    }
+}
+
+fn remember(db: &DB, mut args: Map<String, Value>) -> anyhow::Result<String> {
+    let content: String = take(&mut args, "content")?.ok_or_else(|| anyhow::anyhow!("`content` is required"))?;
+    anyhow::ensure!(!content.trim().is_empty(), "`content` must not be empty");
+    let importance = take::<f32>(&mut args, "importance")?.unwrap_or(1.0);
+    anyhow::ensure!((0.0..=1.0).contains(&importance), "`importance` must be between 0 and 1");
+    let source = take::<String>(&mut args, "source")?.unwrap_or_default();
+    let vector = take::<Vec<f32>>(&mut args, "vector")?;
+    done(&args)?;
+    let id = db.all_ids().into_iter().max().map_or(0, |max| max + 1);
+    let existing = match &vector {
+        Some(vector) => match db.insert(id, vector).content(&content).importance(importance).source(&source).execute()? {
+            Inserted::DuplicateOf(existing) => Some(existing),
+            _ => None,
+        },
+        None => {
+            let meta = Metadata { timestamp: decay::now(), importance, source, content, ..Metadata::default() };
+            let existing = db.deduplicate(id, None, &meta)?;
+            if existing.is_none() { db.put_metadata(id, &meta)?; }
+            existing
+        }
+    };
+    Ok(match existing {
+        Some(existing) => format!("Already remembered as memory {}.", existing),
+        None => format!("Remembered as memory {}.", id),
+    })
+}
+
+fn recall(db: &DB, mut args: Map<String, Value>) -> anyhow::Result<String> {
+    let query: String = take(&mut args, "query")?.ok_or_else(|| anyhow::anyhow!("`query` is required"))?;
+    let k = take::<usize>(&mut args, "k")?.unwrap_or(DEFAULT_K);
+    anyhow::ensure!(k > 0, "`k` must be at least 1");
+    let vector = take::<Vec<f32>>(&mut args, "vector")?;
+    done(&args)?;
+    let hits = match vector {
+        Some(vector) => {
+            let options = SearchOptions { text: Some(query), ..SearchOptions::default() };
+            db.search_with_options(&vector, k, "text", &options)?
+        }
+        None => db.keyword_search(&query, k)?,
+    };
+    let lines: Vec<String> = hits.into_iter()
+        .filter_map(|(id, score)| {
+            let meta = db.get_metadata(id).filter(|m| !m.is_forgotten())?;
+            Some(format!("[{}] ({:.3}) {}", id, score, meta.content))
+        })
+        .collect();
+    Ok(if lines.is_empty() { "No memories match.".to_string() } else { lines.join("\n") })
+}
+
+fn forget(db: &DB, mut args: Map<String, Value>) -> anyhow::Result<String> {
+    let id: u64 = take(&mut args, "id")?.ok_or_else(|| anyhow::anyhow!("`id` is required"))?;
+    done(&args)?;
+    anyhow::ensure!(db.get_metadata(id).is_some_and(|m| !m.is_forgotten()), "no memory {}", id);
+    db.forget(id)?;
+    Ok(format!("Forgot memory {}.", id))
+}
@@ -0,0 +1,230 @@
+//! Consolidating one store into another (`feather merge`), and merging a
+//! fork back into the store it was taken from (`feather merge-fork`).
+
+use crate::{Edge, Record, DB};
+use std::collections::{HashMap, HashSet};
+
+/// What to do when a source record's id already exists in the destination.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Keep the destination's record; edges pointing at the id resolve to it.
+    Skip,
+    /// Replace the destination's record with the source's.
+    Overwrite,
+    /// Copy the source record under a fresh id (edges are rewritten).
+    Remap,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MergeReport {
+    pub copied: usize,
+    pub skipped: usize,
+    pub overwritten: usize,
+    /// Source id → destination id, for records copied under a fresh id.
+    pub remapped: HashMap<u64, u64>,
+}
+
+/// Copy every record of `src` (vectors in all modalities, sparse vectors,
+/// metadata, and links) into `dst`, resolving id collisions per `policy`.
+/// Does not save.
+pub fn merge_into(dst: &DB, src: &DB, policy: MergePolicy) -> anyhow::Result<MergeReport> {
+    let existing: HashSet<u64> = dst.all_ids().into_iter().collect();
+    let src_ids = src.all_ids();
+    let mut next_id = existing.iter().chain(&src_ids).max().map_or(1, |m| m + 1);
+
+    // Pass 1: decide where each source record lands.
+    let mut report = MergeReport::default();
+    let mut targets = Vec::with_capacity(src_ids.len());
+    for &id in &src_ids {
+        if !existing.contains(&id) {
+            targets.push((id, id));
+            continue;
+        }
+        match policy {
+            MergePolicy::Skip => report.skipped += 1,
+            MergePolicy::Overwrite => {
+                report.overwritten += 1;
+                targets.push((id, id));
+            }
+            MergePolicy::Remap => {
+                report.remapped.insert(id, next_id);
+                targets.push((id, next_id));
+                next_id += 1;
+            }
+        }
+    }
+
+    // Pass 2: copy vectors, then metadata with edges rewritten to the new ids,
+    // then sparse vectors.
+    let modalities = src.modalities();
+    let sparse_names = src.sparse_names();
+    for (src_id, dst_id) in targets {
+        let Some(mut meta) = src.get_metadata(src_id) else { continue };
+        for edge in &mut meta.edges {
+            if let Some(&new) = report.remapped.get(&edge.target) { edge.target = new; }
+        }
+        for modality in &modalities {
+            if let Some(vec) = src.get_vector(src_id, modality) {
+                dst.write_record(dst_id, &vec, &meta, modality)?;
+            }
+        }
+        dst.put_metadata(dst_id, &meta)?;
+        for name in &sparse_names {
+            if let Some(v) = src.get_sparse(src_id, name) {
+                dst.set_sparse(dst_id, name, &v)?;
+            }
+        }
+        report.copied += 1;
+    }
+    Ok(report)
+}
+
+/// How `merge_fork` settles a record changed on both sides.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ForkStrategy {
+    /// Keep the side with the later activity (timestamp or last recall); a
+    /// deletion loses to a modification. Links are merged either way.
+    NewestWins,
+    /// Leave the destination's record alone and report the conflict.
+    Manual,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ForkMergeReport {
+    /// Fork changes applied to records the destination had not changed.
+    pub applied: usize,
+    /// Records the fork forgot, forgotten in the destination too.
+    pub forgotten: usize,
+    /// Records whose only change in the fork was being recalled; the recall
+    /// counts are added to the destination's.
+    pub recalls: usize,
+    /// Conflicts settled by `NewestWins`.
+    pub resolved: usize,
+    /// Conflicts left for the caller under `Manual`: the fork's version of
+    /// each record, None where the fork forgot it.
+    pub conflicts: Vec<(u64, Option<Record>)>,
+}
+
+/// Merge the changes made in `fork` since it was taken back into `dst`, the
+/// store it was forked from (which may have moved on since). This is a
+/// three-way merge against the fork's snapshot: changes made on one side
+/// only are applied, and records changed on both sides are settled per
+/// `strategy`. Merging the same fork again only re-counts its recalls.
+/// Does not save.
+pub fn merge_fork(dst: &DB, fork: &DB, strategy: ForkStrategy) -> anyhow::Result<ForkMergeReport> {
+    let ancestor = fork.snapshot().ok_or_else(|| anyhow::anyhow!("the source store is not a fork"))?;
+    let live = |db: &DB, id| db.record(id).filter(|r| !r.metadata.is_forgotten());
+    let mut report = ForkMergeReport::default();
+    for id in fork.changed_ids() {
+        let (base, theirs, ours) = (live(&ancestor, id), live(fork, id), live(dst, id));
+        if same(&theirs, &base) {
+            // only recalled in the fork: carry the recalls over
+            if let (Some(t), Some(b), Some(mut o)) = (theirs, base, ours) {
+                let recalls = t.metadata.recall_count.saturating_sub(b.metadata.recall_count);
+                if recalls > 0 {
+                    o.metadata.recall_count += recalls;
+                    o.metadata.last_recalled_at = o.metadata.last_recalled_at.max(t.metadata.last_recalled_at);
+                    dst.put_metadata(id, &o.metadata)?;
+                    report.recalls += 1;
+                }
+            }
+            continue;
+        }
+        if same(&ours, &theirs) { continue; }   // both sides made the same change
+        if same(&ours, &base) {
+            match theirs {
+                Some(_) => report.applied += 1,
+                None => report.forgotten += 1,
+            }
+            apply(dst, id, theirs)?;
+            continue;
+        }
+        if let (Some(o), Some(t)) = (&ours, &theirs) {
+            if same_but_edges(o, t) {
+                // only the links diverged, which always merge cleanly
+                let mut o = o.clone();
+                o.metadata.edges = merge_edges(&base, &ours, &theirs);
+                apply(dst, id, Some(o))?;
+                report.applied += 1;
+                continue;
+            }
+        }
+        match strategy {
+            ForkStrategy::Manual => report.conflicts.push((id, theirs)),
+            ForkStrategy::NewestWins => {
+                let edges = merge_edges(&base, &ours, &theirs);
+                let theirs_wins = match (&ours, &theirs) {
+                    (Some(o), Some(t)) => activity(t) >= activity(o),
+                    (None, _) => theirs.is_some(),
+                    (Some(_), None) => false,
+                };
+                let mut winner = if theirs_wins { theirs } else { ours };
+                if let Some(w) = &mut winner { w.metadata.edges = edges; }
+                apply(dst, id, winner)?;
+                report.resolved += 1;
+            }
+        }
+    }
+    Ok(report)
+}
+
+// Write `record` over `dst`'s copy of `id`, or forget it if None.
+fn apply(dst: &DB, id: u64, record: Option<Record>) -> anyhow::Result<()> {
+    match record {
+        Some(record) => {
+            for (modality, vec) in &record.vectors {
+                dst.write_record(id, vec, &record.metadata, modality)?;
+            }
+            dst.put_metadata(id, &record.metadata)?;
+            for (name, v) in &record.sparse {
+                dst.set_sparse(id, name, v)?;
+            }
+            Ok(())
+        }
+        None => dst.forget(id),
+    }
+}
+
+// Record equality, ignoring the recall bookkeeping that every search bumps.
+fn same(a: &Option<Record>, b: &Option<Record>) -> bool {
+    let strip = |r: &Record| {
+        let mut r = r.clone();
+        r.metadata.recall_count = 0;
+        r.metadata.last_recalled_at = 0;
+        r
+    };
+    match (a, b) {
+        (Some(a), Some(b)) => strip(a) == strip(b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn same_but_edges(a: &Record, b: &Record) -> bool {
+    let strip = |r: &Record| {
+        let mut r = r.clone();
+        r.metadata.edges.clear();
+        Some(r)
+    };
+    same(&strip(a), &strip(b))
+}
+
+fn activity(r: &Record) -> i64 {
+    r.metadata.timestamp.max(r.metadata.last_recalled_at as i64)
+}
+
+// Three-way merge of a record's links: `ours`, plus the links the fork added,
+// minus the ones it removed.
+fn merge_edges(base: &Option<Record>, ours: &Option<Record>, theirs: &Option<Record>) -> Vec<Edge> {
+    let edges = |r: &Option<Record>| r.as_ref().map(|r| r.metadata.edges.clone()).unwrap_or_default();
+    let key = |e: &Edge| (e.target, e.rel_type.clone());
+    let (base, theirs) = (edges(base), edges(theirs));
+    let removed: HashSet<_> = base.iter().map(key).filter(|k| !theirs.iter().any(|e| key(e) == *k)).collect();
+    let mut merged: Vec<Edge> = edges(ours).into_iter().filter(|e| !removed.contains(&key(e))).collect();
+    for edge in theirs {
+        if !base.iter().any(|e| key(e) == key(&edge)) && !merged.iter().any(|e| key(e) == key(&edge)) {
+            merged.push(edge);
+        }
+    }
+    merged
+}
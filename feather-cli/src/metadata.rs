@@ -0,0 +1,304 @@
+//! Owned record metadata and its C view (`FeatherMetadata` in feather_core.cpp).
+
+use crate::{ContextType, Visibility};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::{c_char, CStr, CString};
+
+/// A typed, weighted graph edge to another record.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Edge {
+    pub target: u64,
+    #[serde(default = "default_rel_type")]
+    pub rel_type: String,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+}
+
+fn default_rel_type() -> String { "related_to".to_string() }
+fn default_weight() -> f32 { 1.0 }
+
+/// Everything the core stores about a record besides its vectors.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Metadata {
+    pub timestamp: i64,
+    pub importance: f32,
+    pub context_type: ContextType,
+    pub source: String,
+    pub content: String,
+    pub tags_json: String,
+    pub recall_count: u32,
+    pub last_recalled_at: u64,
+    pub namespace_id: String,
+    pub entity_id: String,
+    pub attributes: BTreeMap<String, String>,
+    pub edges: Vec<Edge>,
+    /// Seconds-to-live from `timestamp`; 0 = never expires.
+    pub ttl: i64,
+    pub confidence: f32,
+}
+
+impl Default for Metadata {
+    fn default() -> Self {
+        Metadata {
+            timestamp: 0,
+            importance: 1.0,
+            context_type: ContextType::default(),
+            source: String::new(),
+            content: String::new(),
+            tags_json: String::new(),
+            recall_count: 0,
+            last_recalled_at: 0,
+            namespace_id: String::new(),
+            entity_id: String::new(),
+            attributes: BTreeMap::new(),
+            edges: Vec::new(),
+            ttl: 0,
+            confidence: 1.0,
+        }
+    }
+}
+
+/// `source` the core gives a forgotten (soft-deleted or expired) record.
+pub const FORGOTTEN_SOURCE: &str = "_forgotten";
+
+/// Attribute holding a record's free-form JSON object (`Metadata::json`).
+pub const JSON_ATTRIBUTE: &str = "_meta";
+
+/// Attribute holding a record's version (`Metadata::version`), which the
+/// store sets on every write; a value callers give it is ignored.
+pub const VERSION_ATTRIBUTE: &str = "_version";
+
+/// Attribute holding the session a record belongs to (`Metadata::session`).
+pub const SESSION_ATTRIBUTE: &str = "_session";
+
+/// Attribute holding when a record was archived (`Metadata::archived_at`).
+pub const ARCHIVED_ATTRIBUTE: &str = "_archived";
+
+/// Attribute holding a record's string key (`Metadata::key`).
+pub const KEY_ATTRIBUTE: &str = "_key";
+
+/// Attribute holding the label that owns a record (`Metadata::owner`).
+pub const OWNER_ATTRIBUTE: &str = "_owner";
+
+/// Attribute holding who may see a record with an owner
+/// (`Metadata::visibility`).
+pub const VISIBILITY_ATTRIBUTE: &str = "_visibility";
+
+impl Metadata {
+    /// True once the record was forgotten; only its node shell remains.
+    pub fn is_forgotten(&self) -> bool { self.source == FORGOTTEN_SOURCE }
+
+    /// True once its time-to-live has run out at `now` (Unix seconds);
+    /// `DB::expire` forgets such records, and until it does they are still
+    /// found.
+    pub fn is_expired(&self, now: i64) -> bool { self.ttl > 0 && now > self.timestamp + self.ttl }
+
+    /// The record's free-form JSON object, if it has one.
+    pub fn json(&self) -> Option<serde_json::Map<String, serde_json::Value>> {
+        serde_json::from_str(self.attributes.get(JSON_ATTRIBUTE)?).ok()
+    }
+
+    /// Attach a free-form JSON object, replacing any previous one; an empty
+    /// object removes it.
+    pub fn set_json(&mut self, object: &serde_json::Map<String, serde_json::Value>) {
+        if object.is_empty() {
+            self.attributes.remove(JSON_ATTRIBUTE);
+        } else {
+            let json = serde_json::Value::Object(object.clone()).to_string();
+            self.attributes.insert(JSON_ATTRIBUTE.to_string(), json);
+        }
+    }
+
+    /// How many times the record was written: 1 once added, one more with
+    /// each change to it, forgetting aside. 0 for metadata not read from a
+    /// store. Compaction drops the versions of the records it reclaims, so
+    /// an id added again after that starts over at 1.
+    pub fn version(&self) -> u64 {
+        self.attributes.get(VERSION_ATTRIBUTE).and_then(|v| v.parse().ok()).unwrap_or(0)
+    }
+
+    /// The session — e.g. the conversation — the record was written in,
+    /// if it was scoped to one (see the `session` module).
+    pub fn session(&self) -> Option<&str> {
+        self.attributes.get(SESSION_ATTRIBUTE).map(String::as_str).filter(|s| !s.is_empty())
+    }
+
+    /// Scope the record to `session`, or with None make it durable.
+    pub fn set_session(&mut self, session: Option<&str>) {
+        match session.filter(|s| !s.is_empty()) {
+            Some(session) => { self.attributes.insert(SESSION_ATTRIBUTE.to_string(), session.to_string()); }
+            None => { self.attributes.remove(SESSION_ATTRIBUTE); }
+        }
+    }
+
+    /// The string key the record is known by, if it has one (see the
+    /// `keys` module).
+    pub fn key(&self) -> Option<&str> {
+        self.attributes.get(KEY_ATTRIBUTE).map(String::as_str).filter(|k| !k.is_empty())
+    }
+
+    /// The label that owns the record, if one does (see the `access`
+    /// module).
+    pub fn owner(&self) -> Option<&str> {
+        self.attributes.get(OWNER_ATTRIBUTE).map(String::as_str).filter(|o| !o.is_empty())
+    }
+
+    /// Give the record to `owner`, or with None to no one.
+    pub fn set_owner(&mut self, owner: Option<&str>) {
+        match owner.filter(|o| !o.is_empty()) {
+            Some(owner) => { self.attributes.insert(OWNER_ATTRIBUTE.to_string(), owner.to_string()); }
+            None => { self.attributes.remove(OWNER_ATTRIBUTE); }
+        }
+    }
+
+    /// Who may see the record, if it has an owner; anything but "public"
+    /// reads as private.
+    pub fn visibility(&self) -> Visibility {
+        match self.attributes.get(VISIBILITY_ATTRIBUTE).map(String::as_str) {
+            Some("public") => Visibility::Public,
+            _ => Visibility::Private,
+        }
+    }
+
+    pub fn set_visibility(&mut self, visibility: Visibility) {
+        match visibility {
+            Visibility::Private => { self.attributes.remove(VISIBILITY_ATTRIBUTE); }
+            Visibility::Public => { self.attributes.insert(VISIBILITY_ATTRIBUTE.to_string(), visibility.name().to_string()); }
+        }
+    }
+
+    /// When the record was archived, in Unix seconds, if it is (see the
+    /// `archive` module).
+    pub fn archived_at(&self) -> Option<i64> {
+        self.attributes.get(ARCHIVED_ATTRIBUTE).map(|t| t.parse().unwrap_or(0))
+    }
+
+    /// True while the record is archived: kept, but out of searches.
+    pub fn is_archived(&self) -> bool { self.attributes.contains_key(ARCHIVED_ATTRIBUTE) }
+
+    pub(crate) fn set_version(&mut self, version: u64) {
+        self.attributes.insert(VERSION_ATTRIBUTE.to_string(), version.to_string());
+    }
+}
+
+#[repr(C)]
+pub(crate) struct RawMetadata {
+    timestamp: i64,
+    importance: f32,
+    context_type: u8,
+    recall_count: u32,
+    last_recalled_at: u64,
+    ttl: i64,
+    confidence: f32,
+    source: *const c_char,
+    content: *const c_char,
+    tags_json: *const c_char,
+    namespace_id: *const c_char,
+    entity_id: *const c_char,
+    attr_keys: *const *const c_char,
+    attr_values: *const *const c_char,
+    attr_count: usize,
+    edge_targets: *const u64,
+    edge_types: *const *const c_char,
+    edge_weights: *const f32,
+    edge_count: usize,
+}
+
+unsafe fn owned(p: *const c_char) -> String {
+    if p.is_null() { String::new() } else { CStr::from_ptr(p).to_string_lossy().into_owned() }
+}
+
+unsafe fn slice<'a, T>(p: *const T, n: usize) -> &'a [T] {
+    if n == 0 { &[] } else { std::slice::from_raw_parts(p, n) }
+}
+
+impl Metadata {
+    /// Copy out of a core-owned view.
+    pub(crate) unsafe fn from_raw(raw: &RawMetadata) -> Self {
+        let keys = slice(raw.attr_keys, raw.attr_count);
+        let values = slice(raw.attr_values, raw.attr_count);
+        let targets = slice(raw.edge_targets, raw.edge_count);
+        let types = slice(raw.edge_types, raw.edge_count);
+        let weights = slice(raw.edge_weights, raw.edge_count);
+        Metadata {
+            timestamp: raw.timestamp,
+            importance: raw.importance,
+            context_type: raw.context_type.into(),
+            source: owned(raw.source),
+            content: owned(raw.content),
+            tags_json: owned(raw.tags_json),
+            recall_count: raw.recall_count,
+            last_recalled_at: raw.last_recalled_at,
+            namespace_id: owned(raw.namespace_id),
+            entity_id: owned(raw.entity_id),
+            attributes: keys.iter().zip(values).map(|(k, v)| (owned(*k), owned(*v))).collect(),
+            edges: targets.iter().zip(types).zip(weights)
+                .map(|((&target, t), &weight)| Edge { target, rel_type: owned(*t), weight })
+                .collect(),
+            ttl: raw.ttl,
+            confidence: raw.confidence,
+        }
+    }
+}
+
+/// A `RawMetadata` borrowing from C strings it owns; keep it alive for the
+/// duration of the FFI call that reads `raw()`.
+pub(crate) struct CMetadata {
+    raw: RawMetadata,
+    _strings: Vec<CString>,
+    _ptrs: [Vec<*const c_char>; 3],
+    _targets: Vec<u64>,
+    _weights: Vec<f32>,
+}
+
+impl CMetadata {
+    pub(crate) fn new(m: &Metadata) -> anyhow::Result<Self> {
+        let mut strings = Vec::new();
+        let mut intern = |s: &str| -> anyhow::Result<*const c_char> {
+            let c = crate::c_str(s)?;
+            let p = c.as_ptr();   // heap buffer: stable when the CString moves
+            strings.push(c);
+            Ok(p)
+        };
+        let source = intern(&m.source)?;
+        let content = intern(&m.content)?;
+        let tags_json = intern(&m.tags_json)?;
+        let namespace_id = intern(&m.namespace_id)?;
+        let entity_id = intern(&m.entity_id)?;
+        let mut keys = Vec::with_capacity(m.attributes.len());
+        let mut values = Vec::with_capacity(m.attributes.len());
+        for (k, v) in &m.attributes {
+            keys.push(intern(k)?);
+            values.push(intern(v)?);
+        }
+        let types = m.edges.iter().map(|e| intern(&e.rel_type)).collect::<anyhow::Result<Vec<_>>>()?;
+        let targets: Vec<u64> = m.edges.iter().map(|e| e.target).collect();
+        let weights: Vec<f32> = m.edges.iter().map(|e| e.weight).collect();
+        let raw = RawMetadata {
+            timestamp: m.timestamp,
+            importance: m.importance,
+            context_type: m.context_type.code(),
+            recall_count: m.recall_count,
+            last_recalled_at: m.last_recalled_at,
+            ttl: m.ttl,
+            confidence: m.confidence,
+            source,
+            content,
+            tags_json,
+            namespace_id,
+            entity_id,
+            attr_keys: keys.as_ptr(),
+            attr_values: values.as_ptr(),
+            attr_count: keys.len(),
+            edge_targets: targets.as_ptr(),
+            edge_types: types.as_ptr(),
+            edge_weights: weights.as_ptr(),
+            edge_count: targets.len(),
+        };
+        Ok(CMetadata { raw, _strings: strings, _ptrs: [keys, values, types], _targets: targets, _weights: weights })
+    }
+
+    pub(crate) fn raw(&self) -> *const RawMetadata { &self.raw }
+}
@@ -0,0 +1,157 @@
+//! What `feather serve` exposes at `GET /metrics`, in the Prometheus text
+//! format, so the store can be monitored like any other backend:
+//!
+//! - `feather_http_requests_total{endpoint, status}`
+//! - `feather_request_duration_seconds{endpoint}`: a latency histogram
+//! - `feather_inserts_total{outcome}`: rows `/add` added, skipped or
+//!   deduplicated; `feather_deletes_total`
+//! - `feather_records`, and per modality `feather_index_vectors` and
+//!   `feather_index_dimensions`, read from the store at scrape time
+//!
+//! Serving a data dir (see `tenants`), the requests of every store are
+//! counted together, by the endpoint under `/db/{name}`, and the gauges
+//! are given for each store open, labelled `db`.
+//!
+//! Counters start at zero with the server.
+
+use crate::serve::Response;
+use crate::DB;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Content type of the exposition.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Upper bounds of the latency buckets, in seconds.
+pub const BUCKETS: [f64; 12] = [0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5];
+
+// Fields of an `/add` reply, the outcomes feather_inserts_total counts.
+const INSERT_OUTCOMES: [&str; 3] = ["added", "skipped", "deduplicated"];
+
+#[derive(Clone, Debug, Default)]
+struct Histogram {
+    /// Observations per bucket, not cumulative; the last one is +Inf.
+    counts: [u64; BUCKETS.len() + 1],
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        let bucket = BUCKETS.iter().position(|&le| seconds <= le).unwrap_or(BUCKETS.len());
+        self.counts[bucket] += 1;
+        self.sum += seconds;
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {
+    requests: BTreeMap<(&'static str, u16), u64>,
+    latency: BTreeMap<&'static str, Histogram>,
+    inserts: [u64; INSERT_OUTCOMES.len()],
+    deletes: u64,
+}
+
+impl Metrics {
+    /// Count a request to `path` answered with `response` in `elapsed`.
+    pub fn observe(&mut self, path: &str, response: &Response, elapsed: Duration) {
+        let endpoint = endpoint(path);
+        *self.requests.entry((endpoint, response.status)).or_default() += 1;
+        self.latency.entry(endpoint).or_default().observe(elapsed.as_secs_f64());
+        if response.status != 200 { return; }
+        match endpoint {
+            "/add" => for (n, outcome) in self.inserts.iter_mut().zip(INSERT_OUTCOMES) {
+                *n += response.body[outcome].as_u64().unwrap_or(0);
+            },
+            "/delete" => self.deletes += 1,
+            _ => {}
+        }
+    }
+
+    /// The exposition: the counters so far, and the gauges of `db` now.
+    pub fn render(&self, db: &DB) -> String {
+        self.exposition(&[(None, db)])
+    }
+
+    /// `render`, with the gauges of each of `stores`, labelled with its name.
+    pub fn render_stores(&self, stores: &[(&str, &DB)]) -> String {
+        let stores: Vec<(Option<&str>, &DB)> = stores.iter().map(|&(name, db)| (Some(name), db)).collect();
+        self.exposition(&stores)
+    }
+
+    fn exposition(&self, stores: &[(Option<&str>, &DB)]) -> String {
+        let mut out = String::new();
+        header(&mut out, "feather_http_requests_total", "counter", "HTTP requests answered, by endpoint and status.");
+        for ((endpoint, status), n) in &self.requests {
+            let _ = writeln!(out, "feather_http_requests_total{{endpoint=\"{}\",status=\"{}\"}} {}", endpoint, status, n);
+        }
+        header(&mut out, "feather_request_duration_seconds", "histogram", "Time to answer a request, by endpoint.");
+        for (endpoint, hist) in &self.latency {
+            let mut cumulative = 0;
+            for (i, n) in hist.counts.iter().enumerate() {
+                cumulative += n;
+                let le = BUCKETS.get(i).map_or("+Inf".to_string(), f64::to_string);
+                let _ = writeln!(out, "feather_request_duration_seconds_bucket{{endpoint=\"{}\",le=\"{}\"}} {}", endpoint, le, cumulative);
+            }
+            let _ = writeln!(out, "feather_request_duration_seconds_sum{{endpoint=\"{}\"}} {}", endpoint, hist.sum);
+            let _ = writeln!(out, "feather_request_duration_seconds_count{{endpoint=\"{}\"}} {}", endpoint, cumulative);
+        }
+        header(&mut out, "feather_inserts_total", "counter", "Rows sent to /add, by what became of them.");
+        for (n, outcome) in self.inserts.iter().zip(INSERT_OUTCOMES) {
+            let _ = writeln!(out, "feather_inserts_total{{outcome=\"{}\"}} {}", outcome, n);
+        }
+        header(&mut out, "feather_deletes_total", "counter", "Records forgotten through /delete.");
+        let _ = writeln!(out, "feather_deletes_total {}", self.deletes);
+
+        header(&mut out, "feather_records", "gauge", "Records in the store, forgotten ones included until compacted.");
+        for &(name, db) in stores {
+            let _ = writeln!(out, "feather_records{} {}", labels(name, None), db.all_ids().len());
+        }
+        let modalities: Vec<Vec<String>> = stores.iter().map(|(_, db)| {
+            let mut modalities = db.modalities();
+            modalities.sort();
+            modalities
+        }).collect();
+        header(&mut out, "feather_index_vectors", "gauge", "Vectors in a modality's index.");
+        for (&(name, db), modalities) in stores.iter().zip(&modalities) {
+            for modality in modalities {
+                let _ = writeln!(out, "feather_index_vectors{} {}", labels(name, Some(modality)), db.ids(modality).len());
+            }
+        }
+        header(&mut out, "feather_index_dimensions", "gauge", "Dimension of a modality's vectors.");
+        for (&(name, db), modalities) in stores.iter().zip(&modalities) {
+            for modality in modalities {
+                let _ = writeln!(out, "feather_index_dimensions{} {}", labels(name, Some(modality)), db.dim(modality));
+            }
+        }
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+}
+
+// The labels of a gauge of the store named `db`, if it has a name, and of
+// `modality`.
+fn labels(db: Option<&str>, modality: Option<&str>) -> String {
+    let labels: Vec<String> = [db.map(|db| format!("db={:?}", db)), modality.map(|m| format!("modality={:?}", m))]
+        .into_iter().flatten().collect();
+    if labels.is_empty() { String::new() } else { format!("{{{}}}", labels.join(",")) }
+}
+
+// The endpoint label of `path`: its route without the id, so that the
+// label takes few values.
+fn endpoint(path: &str) -> &'static str {
+    let route = path.trim_end_matches('/');
+    let route = route.rsplit_once('/').filter(|(r, _)| !r.is_empty()).map_or(route, |(r, _)| r);
+    match route {
+        "/add" => "/add",
+        "/search" => "/search",
+        "/get" => "/get",
+        "/delete" => "/delete",
+        "/metrics" => "/metrics",
+        "/db" => "/db",
+        _ => "other",
+    }
+}
@@ -0,0 +1,124 @@
+//! Moving a collection over from another vector database (`DB::migrate`,
+//! `feather import --from qdrant|chroma`).
+//!
+//! Qdrant is read over its REST API, a page of points at a time, payloads
+//! and vectors included (see `qdrant`). Chroma is read from the directory
+//! of a `PersistentClient` (see `chroma`). Either way, nothing is embedded
+//! again.
+//!
+//! A point's payload (Qdrant) or metadata (Chroma) maps onto feather's as a
+//! `payload` object does for `feather import`: feather metadata fields such
+//! as `content`, `source`, `timestamp` and `importance` by name, any other
+//! key to a string attribute. A Chroma document becomes the `content`. An
+//! unnamed vector lands in the default modality, a named one in the
+//! modality of its name. A point whose id is not an unsigned integer (a
+//! UUID, any Chroma id that is not a number) is added under its id as a key
+//! (see `keys`), so migrating again replaces the records rather than adding
+//! them twice; allocated ids step over the numeric ids of the migration,
+//! which come first (Qdrant lists them before UUIDs, and Chroma's records
+//! are read in the same order). Points without a timestamp are stamped with
+//! the time of the migration.
+
+pub mod chroma;
+mod pickle;
+pub mod qdrant;
+
+use crate::import::{self, ImportReport};
+use crate::metadata::KEY_ATTRIBUTE;
+use crate::{ProgressFn, Record, SparseVector, DB};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+
+/// Where a migration reads from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// A collection of the Qdrant server at `url`, e.g. `http://localhost:6333`.
+    Qdrant { url: String, collection: String },
+    /// A collection of the Chroma persistent directory `dir`.
+    Chroma { dir: PathBuf, collection: String },
+}
+
+/// A point's id in the database it comes from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PointId {
+    Num(u64),
+    Key(String),
+}
+
+impl PointId {
+    /// `Num` if `id` is an unsigned integer, else `Key`.
+    pub fn parse(id: &str) -> Self {
+        id.parse().map_or_else(|_| PointId::Key(id.to_string()), PointId::Num)
+    }
+}
+
+/// One point read from another database.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Point {
+    pub id: PointId,
+    /// The unnamed vector, empty if there is none.
+    pub vector: Vec<f32>,
+    /// Named dense vectors, by name.
+    pub named: BTreeMap<String, Vec<f32>>,
+    /// Named sparse vectors, by name.
+    pub sparse: BTreeMap<String, SparseVector>,
+    pub payload: Map<String, Value>,
+}
+
+impl Point {
+    pub fn new(id: PointId) -> Self {
+        Point { id, vector: Vec::new(), named: BTreeMap::new(), sparse: BTreeMap::new(), payload: Map::new() }
+    }
+}
+
+impl DB {
+    /// Insert every point of `source`'s collection, `batch_size` at a
+    /// time, unnamed vectors in `modality` (see the module docs). Like
+    /// `import::import`, records whose id is taken are handled by the
+    /// duplicate-id policy, and repeats by the dedup mode; it stops at the
+    /// first error, earlier batches inserted. Does not save.
+    pub fn migrate(&self, source: &Source, modality: &str, batch_size: usize,
+                   progress: Option<&mut ProgressFn>) -> anyhow::Result<ImportReport> {
+        self.writable()?;
+        let points: Box<dyn Iterator<Item = anyhow::Result<Point>>> = match source {
+            Source::Qdrant { url, collection } => Box::new(qdrant::Scroll::new(url, collection)),
+            Source::Chroma { dir, collection } => Box::new(chroma::read(dir, collection)?.into_iter().map(Ok)),
+        };
+        let timestamp = crate::decay::now();
+        let mut numeric = HashSet::new();
+        let records = points.map(|point| point.and_then(|point| self.migrated(point, modality, timestamp, &mut numeric)));
+        import::import(self, records, batch_size, progress)
+    }
+
+    // `point` as a record, keyed points under the id their key already
+    // has, else a newly allocated one not among the `numeric` ids seen so
+    // far, which may not be stored yet.
+    fn migrated(&self, point: Point, modality: &str, timestamp: i64, numeric: &mut HashSet<u64>) -> anyhow::Result<Record> {
+        let id = match &point.id {
+            PointId::Num(id) => {
+                numeric.insert(*id);
+                *id
+            }
+            PointId::Key(key) => match self.id_for_key(key) {
+                Some(id) => id,
+                None => loop {
+                    let id = self.allocate_id()?;
+                    if !numeric.contains(&id) { break id; }
+                },
+            },
+        };
+        let mut row = Map::new();
+        row.insert("id".into(), id.into());
+        row.insert("payload".into(), Value::Object(point.payload));
+        let mut record = import::record_from_json(row, modality).map_err(|e| anyhow::anyhow!("point {:?}: {}", point.id, e))?;
+        if let PointId::Key(key) = point.id {
+            record.metadata.attributes.insert(KEY_ATTRIBUTE.to_string(), key);
+        }
+        if record.metadata.timestamp == 0 { record.metadata.timestamp = timestamp; }
+        if !point.vector.is_empty() { record.vectors.insert(modality.to_string(), point.vector); }
+        record.vectors.extend(point.named);
+        record.sparse.extend(point.sparse);
+        Ok(record)
+    }
+}
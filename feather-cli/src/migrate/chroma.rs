@@ -0,0 +1,154 @@
+//! Reading a collection from a Chroma persistent directory (the `path` of
+//! `chromadb.PersistentClient`), as Chroma 0.4 and 0.5 lay it out.
+//!
+//! `chroma.sqlite3` holds each record's id, document and metadata, in the
+//! collection's metadata segment; it is read through the `sqlite3` binary,
+//! as HTTP goes through `curl`. The vectors are in the collection's vector
+//! segment, a directory named by the segment's id: hnswlib's header and
+//! level-0 data (`header.bin`, `data_level0.bin`), and a pickle mapping
+//! hnswlib labels to record ids (`index_metadata.pickle`). Vectors Chroma
+//! has not written to the segment yet are still in the write-ahead queue
+//! of `chroma.sqlite3`, and are taken from there, the latest write of each
+//! record winning. Only records the metadata segment still has are read,
+//! so deleted ones are left out.
+
+use super::pickle::{self, Pickled};
+use super::{Point, PointId};
+use crate::ann_index;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+/// Metadata key Chroma keeps a record's document under.
+pub const DOCUMENT_KEY: &str = "chroma:document";
+
+// Write-ahead queue operations.
+const DELETE: i64 = 3;
+
+/// The records of `collection` in the Chroma directory `dir`: those with
+/// numeric ids, then the others, each in the order they were added.
+pub fn read(dir: &Path, collection: &str) -> anyhow::Result<Vec<Point>> {
+    let db = dir.join("chroma.sqlite3");
+    anyhow::ensure!(db.is_file(), "{:?}: not a Chroma directory (no chroma.sqlite3)", dir);
+    let found = query(&db, &format!("SELECT id FROM collections WHERE name = {}", literal(collection)))?;
+    let id = found.first().and_then(|row| row["id"].as_str())
+        .ok_or_else(|| anyhow::anyhow!("{:?}: no collection '{}'", dir, collection))?.to_string();
+
+    let mut points: Vec<Point> = Vec::new();
+    let mut records: Vec<String> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let rows = query(&db, &format!(
+        "SELECT e.embedding_id AS id, m.key, m.string_value, m.int_value, m.float_value, m.bool_value \
+         FROM embeddings e JOIN segments s ON s.id = e.segment_id \
+         LEFT JOIN embedding_metadata m ON m.id = e.id \
+         WHERE s.collection = {} AND s.scope = 'METADATA' ORDER BY e.id", literal(&id)))?;
+    for mut row in rows {
+        let Some(record) = row["id"].as_str().map(str::to_string) else { continue };
+        let at = *index.entry(record.clone()).or_insert_with(|| {
+            points.push(Point::new(PointId::parse(&record)));
+            records.push(record.clone());
+            points.len() - 1
+        });
+        let Some(key) = row["key"].as_str().map(str::to_string) else { continue };
+        let value = match (row["string_value"].take(), row["int_value"].take(), row["float_value"].take(), &row["bool_value"]) {
+            (s @ Value::String(_), _, _, _) => s,
+            (_, _, _, b) if !b.is_null() => Value::Bool(b.as_i64().is_some_and(|b| b != 0)),
+            (_, i @ Value::Number(_), _, _) => i,
+            (_, _, f, _) => f,
+        };
+        let key = if key == DOCUMENT_KEY { "content".to_string() } else { key };
+        points[at].payload.insert(key, value);
+    }
+
+    let segment = query(&db, &format!("SELECT id FROM segments WHERE collection = {} AND scope = 'VECTOR'", literal(&id)))?;
+    let mut vectors: HashMap<String, Vec<f32>> = HashMap::new();
+    let mut persisted_to = 0;
+    if let Some(dir) = segment.first().and_then(|row| row["id"].as_str()).map(|segment| dir.join(segment)) {
+        if dir.join("header.bin").is_file() {
+            persisted_to = read_segment(&dir, &mut vectors)?;
+        }
+    }
+    // writes the segment has not taken in yet
+    let queued = query(&db, &format!(
+        "SELECT id, operation, hex(vector) AS vector, encoding FROM embeddings_queue \
+         WHERE topic LIKE {} AND seq_id > {} ORDER BY seq_id", literal(&format!("%{}", id)), persisted_to))?;
+    for row in queued {
+        let Some(record) = row["id"].as_str() else { continue };
+        if row["operation"].as_i64() == Some(DELETE) {
+            vectors.remove(record);
+            continue;
+        }
+        let Some(hex) = row["vector"].as_str().filter(|hex| !hex.is_empty()) else { continue };
+        anyhow::ensure!(row["encoding"].as_str().is_none_or(|e| e == "FLOAT32"),
+                        "record {}: vector encoding {} is not supported", record, row["encoding"]);
+        vectors.insert(record.to_string(), float32s(hex)?);
+    }
+
+    for (point, record) in points.iter_mut().zip(&records) {
+        point.vector = vectors.remove(record)
+            .ok_or_else(|| anyhow::anyhow!("{:?}: record '{}' has no vector", dir, record))?;
+    }
+    // numeric ids first, as `migrate` expects
+    points.sort_by_key(|point| matches!(point.id, PointId::Key(_)));
+    Ok(points)
+}
+
+// Read the vectors of the HNSW segment in `dir` into `vectors`, by record
+// id; returns the last write-ahead sequence number the segment took in.
+fn read_segment(dir: &Path, vectors: &mut HashMap<String, Vec<f32>>) -> anyhow::Result<i64> {
+    let file = |name: &str| {
+        let path = dir.join(name);
+        std::fs::read(&path).map_err(|e| anyhow::anyhow!("{:?}: {}", path, e))
+    };
+    let meta = pickle::read(&file("index_metadata.pickle")?)
+        .map_err(|e| anyhow::anyhow!("{:?}: {:#}", dir.join("index_metadata.pickle"), e))?;
+    let mut labels: HashMap<i64, &str> = HashMap::new();
+    if let Some(Pickled::Dict(items)) = meta.get("label_to_id") {
+        for (label, id) in items {
+            if let (Some(label), Some(id)) = (label.as_int(), id.as_str()) { labels.insert(label, id); }
+        }
+    }
+    let mut bytes = file("header.bin")?;
+    bytes.extend(file("data_level0.bin")?);
+    let index = ann_index::read_hnswlib(&bytes).map_err(|e| anyhow::anyhow!("{:?}: {:#}", dir, e))?;
+    for (label, vector) in index.ids.iter().zip(index.vectors.chunks_exact(index.dim.max(1))) {
+        if let Some(id) = labels.get(&(*label as i64)) {
+            vectors.insert(id.to_string(), vector.to_vec());
+        }
+    }
+    Ok(meta.get("max_seq_id").and_then(Pickled::as_int).unwrap_or(0))
+}
+
+// The rows `sql` selects from the SQLite file `db`, as JSON objects.
+fn query(db: &Path, sql: &str) -> anyhow::Result<Vec<Map<String, Value>>> {
+    let out = Command::new("sqlite3")
+        .args(["-readonly", "-json"])
+        .arg(db)
+        .arg(sql)
+        .output()
+        .map_err(|e| anyhow::anyhow!("--from chroma needs sqlite3 on the PATH: {}", e))?;
+    anyhow::ensure!(out.status.success(), "{:?}: {}", db, String::from_utf8_lossy(&out.stderr).trim());
+    // no rows print nothing at all
+    if out.stdout.iter().all(u8::is_ascii_whitespace) { return Ok(Vec::new()); }
+    serde_json::from_slice(&out.stdout).map_err(|e| anyhow::anyhow!("{:?}: unreadable sqlite3 output: {}", db, e))
+}
+
+// `s` as an SQL string literal.
+fn literal(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+// Little-endian float32s from their hex digits.
+fn float32s(hex: &str) -> anyhow::Result<Vec<f32>> {
+    anyhow::ensure!(hex.len().is_multiple_of(8), "vector of {} hex digits", hex.len());
+    (0..hex.len()).step_by(8)
+        .map(|i| {
+            let mut bytes = [0u8; 4];
+            for (j, b) in bytes.iter_mut().enumerate() {
+                *b = u8::from_str_radix(&hex[i + 2 * j..i + 2 * j + 2], 16)?;
+            }
+            Ok(f32::from_le_bytes(bytes))
+        })
+        .collect()
+}
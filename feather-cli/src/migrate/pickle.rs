@@ -0,0 +1,237 @@
+//! Just enough of Python's pickle format to read the index metadata Chroma
+//! keeps beside its HNSW files: protocols 2 to 5, made of `None`, bools,
+//! ints, floats, strings, bytes, tuples, lists, sets, dicts and plain
+//! objects. An object reads as its state (usually its `__dict__`), and the
+//! class it was made from is not looked up. Memoized containers are copied
+//! rather than shared, which matters only to pickles that change a
+//! container after referring to it twice.
+
+/// A value read from a pickle.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pickled {
+    None,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bytes(Vec<u8>),
+    /// Tuples, lists and sets.
+    List(Vec<Pickled>),
+    Dict(Vec<(Pickled, Pickled)>),
+    /// An instance, as the state it was built with; `None` before `BUILD`.
+    Object(Box<Pickled>),
+    /// A class or function, as `module.name`.
+    Global(String),
+}
+
+impl Pickled {
+    /// The value under the string `key`, for a dict or an object whose state is one.
+    pub fn get(&self, key: &str) -> Option<&Pickled> {
+        match self {
+            Pickled::Dict(items) => items.iter().find(|(k, _)| *k == Pickled::Str(key.to_string())).map(|(_, v)| v),
+            Pickled::Object(state) => state.get(key),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Pickled::Int(i) => Some(*i),
+            Pickled::Bool(b) => Some(*b as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Pickled::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+// What the stack holds: values, and the marks that group them.
+enum Slot {
+    Mark,
+    Value(Pickled),
+}
+
+/// The value pickled in `bytes`.
+pub fn read(bytes: &[u8]) -> anyhow::Result<Pickled> {
+    let mut input = bytes;
+    let mut stack: Vec<Slot> = Vec::new();
+    let mut memo: Vec<Pickled> = Vec::new();
+    loop {
+        let op = take(&mut input, 1)?[0];
+        match op {
+            0x80 => { take(&mut input, 1)?; }                       // PROTO
+            0x95 => { take(&mut input, 8)?; }                       // FRAME
+            b'.' => return pop(&mut stack),                          // STOP
+            b'(' => stack.push(Slot::Mark),
+            b'N' => push(&mut stack, Pickled::None),
+            0x88 => push(&mut stack, Pickled::Bool(true)),
+            0x89 => push(&mut stack, Pickled::Bool(false)),
+            b'K' => { let n = take(&mut input, 1)?[0]; push(&mut stack, Pickled::Int(n.into())) }
+            b'M' => { let n = u16::from_le_bytes(array(&mut input)?); push(&mut stack, Pickled::Int(n.into())) }
+            b'J' => { let n = i32::from_le_bytes(array(&mut input)?); push(&mut stack, Pickled::Int(n.into())) }
+            0x8a | 0x8b => {                                         // LONG1, LONG4
+                let len = if op == 0x8a { take(&mut input, 1)?[0] as usize } else { u32::from_le_bytes(array(&mut input)?) as usize };
+                push(&mut stack, Pickled::Int(long(take(&mut input, len)?)?));
+            }
+            b'G' => push(&mut stack, Pickled::Float(f64::from_be_bytes(array(&mut input)?))),
+            0x8c | b'X' | 0x8d => {                                  // SHORT_BINUNICODE, BINUNICODE, BINUNICODE8
+                let len = length(&mut input, op == 0x8c, op == 0x8d)?;
+                let s = std::str::from_utf8(take(&mut input, len)?)?;
+                push(&mut stack, Pickled::Str(s.to_string()));
+            }
+            b'C' | b'B' | 0x8e => {                                  // SHORT_BINBYTES, BINBYTES, BINBYTES8
+                let len = length(&mut input, op == b'C', op == 0x8e)?;
+                push(&mut stack, Pickled::Bytes(take(&mut input, len)?.to_vec()));
+            }
+            b')' => push(&mut stack, Pickled::List(Vec::new())),
+            0x85..=0x87 => {                                         // TUPLE1..3
+                let n = (op - 0x84) as usize;
+                let at = stack.len().checked_sub(n).ok_or_else(underflow)?;
+                let items = stack.split_off(at).into_iter().map(value).collect::<anyhow::Result<_>>()?;
+                push(&mut stack, Pickled::List(items));
+            }
+            b't' | 0x91 => {                                         // TUPLE, FROZENSET
+                let items = since_mark(&mut stack)?;
+                push(&mut stack, Pickled::List(items));
+            }
+            b']' | 0x8f => push(&mut stack, Pickled::List(Vec::new())), // EMPTY_LIST, EMPTY_SET
+            b'a' => { let item = pop(&mut stack)?; list(&mut stack)?.push(item) }
+            b'e' | 0x90 => { let items = since_mark(&mut stack)?; list(&mut stack)?.extend(items) } // APPENDS, ADDITEMS
+            b'}' => push(&mut stack, Pickled::Dict(Vec::new())),
+            b's' => {
+                let v = pop(&mut stack)?;
+                let k = pop(&mut stack)?;
+                dict(&mut stack)?.push((k, v));
+            }
+            b'u' => {
+                let items = since_mark(&mut stack)?;
+                anyhow::ensure!(items.len().is_multiple_of(2), "pickle: odd SETITEMS");
+                let mut items = items.into_iter();
+                let target = dict(&mut stack)?;
+                while let (Some(k), Some(v)) = (items.next(), items.next()) { target.push((k, v)); }
+            }
+            0x93 => {                                                // STACK_GLOBAL
+                let name = pop(&mut stack)?;
+                let module = pop(&mut stack)?;
+                let (Some(module), Some(name)) = (module.as_str(), name.as_str()) else { anyhow::bail!("pickle: bad global") };
+                push(&mut stack, Pickled::Global(format!("{}.{}", module, name)));
+            }
+            b'c' => {                                                // GLOBAL
+                let module = line(&mut input)?;
+                let name = line(&mut input)?;
+                push(&mut stack, Pickled::Global(format!("{}.{}", module, name)));
+            }
+            0x81 | b'R' => {                                         // NEWOBJ, REDUCE
+                pop(&mut stack)?;
+                pop(&mut stack)?;
+                push(&mut stack, Pickled::Object(Box::new(Pickled::None)));
+            }
+            0x92 => {                                                // NEWOBJ_EX
+                for _ in 0..3 { pop(&mut stack)?; }
+                push(&mut stack, Pickled::Object(Box::new(Pickled::None)));
+            }
+            b'b' => {                                                // BUILD
+                let state = pop(&mut stack)?;
+                match stack.last_mut() {
+                    Some(Slot::Value(Pickled::Object(old))) => **old = state,
+                    _ => anyhow::bail!("pickle: BUILD without an object"),
+                }
+            }
+            0x94 => {                                                // MEMOIZE
+                let Some(Slot::Value(top)) = stack.last() else { return Err(underflow()) };
+                memo.push(top.clone());
+            }
+            b'q' | b'r' => {                                         // BINPUT, LONG_BINPUT
+                let at = if op == b'q' { take(&mut input, 1)?[0] as usize } else { u32::from_le_bytes(array(&mut input)?) as usize };
+                let Some(Slot::Value(top)) = stack.last() else { return Err(underflow()) };
+                if memo.len() <= at { memo.resize(at + 1, Pickled::None); }
+                memo[at] = top.clone();
+            }
+            b'h' | b'j' => {                                         // BINGET, LONG_BINGET
+                let at = if op == b'h' { take(&mut input, 1)?[0] as usize } else { u32::from_le_bytes(array(&mut input)?) as usize };
+                let value = memo.get(at).cloned().ok_or_else(|| anyhow::anyhow!("pickle: no memo {}", at))?;
+                push(&mut stack, value);
+            }
+            other => anyhow::bail!("pickle: unsupported opcode {:#04x}", other),
+        }
+    }
+}
+
+fn underflow() -> anyhow::Error { anyhow::anyhow!("pickle: stack underflow") }
+
+fn push(stack: &mut Vec<Slot>, value: Pickled) { stack.push(Slot::Value(value)) }
+
+fn value(slot: Slot) -> anyhow::Result<Pickled> {
+    match slot {
+        Slot::Value(v) => Ok(v),
+        Slot::Mark => anyhow::bail!("pickle: unexpected mark"),
+    }
+}
+
+fn pop(stack: &mut Vec<Slot>) -> anyhow::Result<Pickled> {
+    value(stack.pop().ok_or_else(underflow)?)
+}
+
+fn since_mark(stack: &mut Vec<Slot>) -> anyhow::Result<Vec<Pickled>> {
+    let at = stack.iter().rposition(|s| matches!(s, Slot::Mark)).ok_or_else(|| anyhow::anyhow!("pickle: no mark"))?;
+    let items = stack.split_off(at + 1).into_iter().map(value).collect();
+    stack.pop();
+    items
+}
+
+fn list(stack: &mut [Slot]) -> anyhow::Result<&mut Vec<Pickled>> {
+    match stack.last_mut() {
+        Some(Slot::Value(Pickled::List(items))) => Ok(items),
+        _ => anyhow::bail!("pickle: append to a non-list"),
+    }
+}
+
+fn dict(stack: &mut [Slot]) -> anyhow::Result<&mut Vec<(Pickled, Pickled)>> {
+    match stack.last_mut() {
+        Some(Slot::Value(Pickled::Dict(items))) => Ok(items),
+        _ => anyhow::bail!("pickle: set an item of a non-dict"),
+    }
+}
+
+fn take<'a>(input: &mut &'a [u8], n: usize) -> anyhow::Result<&'a [u8]> {
+    anyhow::ensure!(input.len() >= n, "pickle cut short");
+    let (head, rest) = input.split_at(n);
+    *input = rest;
+    Ok(head)
+}
+
+fn array<const N: usize>(input: &mut &[u8]) -> anyhow::Result<[u8; N]> {
+    Ok(take(input, N)?.try_into().expect("N bytes"))
+}
+
+// The length before a string or bytes: one byte if `short`, eight if
+// `long`, else four.
+fn length(input: &mut &[u8], short: bool, long: bool) -> anyhow::Result<usize> {
+    Ok(match (short, long) {
+        (true, _) => take(input, 1)?[0] as usize,
+        (_, true) => u64::from_le_bytes(array(input)?) as usize,
+        _ => u32::from_le_bytes(array(input)?) as usize,
+    })
+}
+
+// A little-endian two's complement integer of up to eight bytes.
+fn long(bytes: &[u8]) -> anyhow::Result<i64> {
+    anyhow::ensure!(bytes.len() <= 8, "pickle: integer too large");
+    if bytes.is_empty() { return Ok(0); }
+    let fill = if bytes[bytes.len() - 1] & 0x80 != 0 { 0xff } else { 0 };
+    let mut full = [fill; 8];
+    full[..bytes.len()].copy_from_slice(bytes);
+    Ok(i64::from_le_bytes(full))
+}
+
+fn line(input: &mut &[u8]) -> anyhow::Result<String> {
+    let end = input.iter().position(|&b| b == b'\n').ok_or_else(|| anyhow::anyhow!("pickle cut short"))?;
+    let text = std::str::from_utf8(&input[..end])?.to_string();
+    *input = &input[end + 1..];
+    Ok(text)
+}
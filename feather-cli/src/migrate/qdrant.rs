@@ -0,0 +1,125 @@
+//! Reading a Qdrant collection over its REST API.
+//!
+//! Points come from `POST /collections/<name>/points/scroll`, `PAGE` at a
+//! time, with payloads and vectors. Requests go through the `curl` binary,
+//! as `--embed-api` requests do, so an `https://` server works; the API key
+//! of `QDRANT_API_KEY`, if set, is sent as the `api-key` header on curl's
+//! stdin, never on its command line.
+
+use super::{Point, PointId};
+use crate::embed::remote::quote;
+use crate::SparseVector;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Environment variable read for the API key.
+pub const KEY_VAR: &str = "QDRANT_API_KEY";
+
+/// Points asked for per request.
+pub const PAGE: usize = 256;
+
+/// The points of one collection, in Qdrant's order, read a page at a time.
+pub struct Scroll {
+    url: String,
+    key: Option<String>,
+    buffered: VecDeque<Value>,
+    // None before the first page; Value::Null once the last has been read
+    offset: Option<Value>,
+}
+
+impl Scroll {
+    /// The points of `collection` on the server at `url`.
+    pub fn new(url: &str, collection: &str) -> Self {
+        Scroll {
+            url: format!("{}/collections/{}/points/scroll", url.trim_end_matches('/'), collection),
+            key: std::env::var(KEY_VAR).ok().filter(|k| !k.is_empty()),
+            buffered: VecDeque::new(),
+            offset: None,
+        }
+    }
+
+    // Read the next page into `buffered`.
+    fn fetch(&mut self) -> anyhow::Result<()> {
+        let mut body = json!({ "limit": PAGE, "with_payload": true, "with_vector": true });
+        if let Some(offset) = &self.offset { body["offset"] = offset.clone(); }
+        let mut config = format!("url = {}\nrequest = POST\nheader = \"Content-Type: application/json\"\n",
+                                 quote(&self.url));
+        if let Some(key) = &self.key {
+            config += &format!("header = {}\n", quote(&format!("api-key: {}", key)));
+        }
+        config += &format!("data-binary = {}\n", quote(&body.to_string()));
+        let mut curl = Command::new("curl")
+            .args(["--silent", "--show-error", "--config", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("--from qdrant needs curl on the PATH: {}", e))?;
+        curl.stdin.take().expect("piped").write_all(config.as_bytes())?;
+        let out = curl.wait_with_output()?;
+        anyhow::ensure!(out.status.success(), "{}: {}", self.url, String::from_utf8_lossy(&out.stderr).trim());
+        let mut reply: Value = serde_json::from_slice(&out.stdout)
+            .map_err(|_| anyhow::anyhow!("{}: reply is not JSON: {}", self.url,
+                                         String::from_utf8_lossy(&out.stdout).chars().take(200).collect::<String>()))?;
+        if let Some(error) = reply["status"].get("error") {
+            anyhow::bail!("{}: {}", self.url, error.as_str().map_or_else(|| error.to_string(), str::to_string));
+        }
+        let result = reply["result"].take();
+        let Value::Array(points) = result["points"].clone() else {
+            anyhow::bail!("{}: reply has no `result.points` array", self.url);
+        };
+        self.buffered.extend(points);
+        self.offset = Some(result["next_page_offset"].clone());
+        Ok(())
+    }
+}
+
+impl Iterator for Scroll {
+    type Item = anyhow::Result<Point>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.buffered.is_empty() {
+            if self.offset.as_ref().is_some_and(Value::is_null) { return None; }
+            if let Err(e) = self.fetch() {
+                self.offset = Some(Value::Null);
+                return Some(Err(e));
+            }
+        }
+        self.buffered.pop_front().map(point)
+    }
+}
+
+// One point of a scroll reply.
+fn point(mut value: Value) -> anyhow::Result<Point> {
+    let id = match value["id"].take() {
+        Value::Number(n) => n.as_u64().map(PointId::Num),
+        Value::String(s) => Some(PointId::Key(s)),
+        _ => None,
+    }.ok_or_else(|| anyhow::anyhow!("point without a valid id: {}", value))?;
+    let mut point = Point::new(id);
+    if let Value::Object(payload) = value["payload"].take() { point.payload = payload; }
+    match value["vector"].take() {
+        Value::Null => {}
+        Value::Object(named) => {
+            for (name, v) in named {
+                if v.get("indices").is_some() {
+                    let sparse: SparseVector = serde_json::from_value(v)
+                        .map_err(|e| anyhow::anyhow!("point {:?}: sparse vector '{}': {}", point.id, name, e))?;
+                    point.sparse.insert(name, sparse);
+                } else {
+                    let dense: Vec<f32> = serde_json::from_value(v)
+                        .map_err(|_| anyhow::anyhow!("point {:?}: vector '{}' is not a dense or sparse vector \
+                                                      (multivectors are not supported)", point.id, name))?;
+                    point.named.insert(name, dense);
+                }
+            }
+        }
+        v => {
+            point.vector = serde_json::from_value(v)
+                .map_err(|_| anyhow::anyhow!("point {:?}: vector is not an array of numbers", point.id))?;
+        }
+    }
+    Ok(point)
+}
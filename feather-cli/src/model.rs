@@ -0,0 +1,50 @@
+//! The embedding model a store's vectors come from (`OpenOptions::model`,
+//! `feather --model`).
+//!
+//! Vectors from different models share nothing but their length, so a
+//! store that mixes them, or is searched with a query from another model,
+//! returns hits that look fine and mean nothing. A store can record the
+//! model its vectors come from, a name as the provider gives it (e.g.
+//! `text-embedding-3-small`), with a version or hash if wanted. It is a
+//! property of the file, all collections and shards, which a fork inherits.
+//!
+//! A handle opened with `OpenOptions::model` declares the model its
+//! vectors and queries come from: a new store records it, as does one that
+//! records none yet if the handle can write, and one that records another
+//! model is refused with `ModelMismatch`. A handle that declares nothing is
+//! not checked.
+
+use crate::*;
+
+/// Property holding the model's name.
+pub(crate) const PROPERTY_KEY: &str = "model";
+
+impl DB {
+    /// The embedding model this store records; None if it records none.
+    pub fn model(&self) -> Option<String> {
+        self.property(PROPERTY_KEY).map(|raw| String::from_utf8_lossy(&raw).into_owned())
+    }
+
+    /// Record the embedding model this store's vectors come from, replacing
+    /// any recorded; None stops recording one. Saved with the file.
+    pub fn set_model(&self, model: Option<&str>) -> anyhow::Result<()> {
+        self.writable()?;
+        match model {
+            Some(model) => {
+                anyhow::ensure!(!model.trim().is_empty(), "the model name must not be empty");
+                self.set_property(PROPERTY_KEY, model.as_bytes())?;
+            }
+            None => { self.remove_property(PROPERTY_KEY)?; }
+        }
+        Ok(())
+    }
+
+    /// Fail with `ModelMismatch` if this store records an embedding model
+    /// other than `model`; a store that records none takes any.
+    pub fn check_model(&self, model: &str) -> Result<(), ModelMismatch> {
+        match self.model() {
+            Some(recorded) if recorded != model => Err(ModelMismatch { expected: recorded, got: model.to_string() }),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! Unit-length vectors for cosine-style retrieval (`--normalize`).
+//!
+//! With normalization on, every vector entering the file and every query is
+//! scaled to unit L2 norm after any projection, so the squared L2 distances
+//! the index ranks by order hits as cosine similarity would. It is a
+//! property of the file: once on, every later open follows it, whatever the
+//! caller passes. Zero vectors are left as they are.
+
+use crate::*;
+
+/// Property set while normalization is on.
+pub(crate) const PROPERTY_KEY: &str = "normalize";
+
+// Norms this close to 1 count as unit already.
+const TOLERANCE: f32 = 1e-6;
+
+/// `vec` scaled to unit L2 norm; None if it is unit already or zero.
+pub(crate) fn unit(vec: &[f32]) -> Option<Vec<f32>> {
+    let norm = vec.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 || (norm - 1.0).abs() <= TOLERANCE { return None; }
+    Some(vec.iter().map(|x| x / norm).collect())
+}
+
+impl DB {
+    /// Whether vectors and queries entering this file are unit-normalized.
+    pub fn normalizes(&self) -> bool { self.handle.normalize.get() }
+
+    /// Turn normalization on or off for the whole file (all collections).
+    /// Turning it on rewrites every stored vector that is not unit length
+    /// and checkpoints the file; turning it off leaves stored vectors as
+    /// they are. Returns the number of vectors rewritten.
+    pub fn set_normalize(&self, on: bool) -> anyhow::Result<usize> {
+        if on == self.normalizes() { return Ok(0); }
+        self.writable()?;
+        if !on {
+            self.handle.normalize.set(false);
+            self.remove_property(PROPERTY_KEY)?;
+            return Ok(0);
+        }
+        anyhow::ensure!(self.handle.fork.is_none(), "cannot normalize a fork: its base is read-only");
+        anyhow::ensure!(!self.handle.binary.get(), "cannot normalize a store of bits (metric hamming)");
+        let mut n = 0;
+        for modality in self.handle.modalities() {
+            n += self.handle.normalize_stored(&modality)?;
+        }
+        self.handle.normalize.set(true);
+        self.set_property(PROPERTY_KEY, b"1")?;
+        self.save()?;
+        Ok(n)
+    }
+}
+
+impl Handle {
+    // Rewrite every stored vector of `modality` (internal name) that is not
+    // unit length; returns how many were.
+    pub(crate) fn normalize_stored(&self, modality: &str) -> anyhow::Result<usize> {
+        let c_modality = c_str(modality)?;
+        let mut n = 0;
+        for id in self.ids(Some(modality)) {
+            let Some(vec) = self.vector(id, Some(modality)).and_then(|v| unit(&v)) else { continue };
+            let Some(meta) = self.meta(id) else { continue };
+            let c_meta = CMetadata::new(&meta)?;
+            let rc = unsafe {
+                feather_add_with_metadata(self.core(id), id, vec.as_ptr(), vec.len(), c_meta.raw(), c_modality.as_ptr())
+            };
+            if rc != 0 { return Err(last_error()); }
+            n += 1;
+        }
+        Ok(n)
+    }
+}
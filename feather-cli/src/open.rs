@@ -0,0 +1,343 @@
+//! Options fixed when a store is opened (`OpenOptions`).
+//!
+//! Like `std::fs::OpenOptions`, opening does not create the file unless
+//! asked to: `open` fails with "no database at PATH" on a missing file
+//! without `create(true)`, and `create_new(true)` fails on one that exists
+//! rather than opening it. `DB::open` is the shorthand that creates.
+//!
+//! The duplicate-id policy decides what an insert of an id that already
+//! has a live record does: replace it (the default, and what the core does
+//! on its own), fail with `DuplicateId`, or leave the existing record alone.
+//! It applies to `add`, `add_with_meta`, `add_with_metadata`, `add_batch`,
+//! `insert` and `import`, record by record: a record's second modality is
+//! not a duplicate when both arrive in one `import` row, but adding it
+//! later through `add_with_metadata` is (use `set_vector` instead). Merges
+//! follow their own `MergePolicy`.
+//!
+//! A read-only open takes the lock shared, so any number of readers — a
+//! `feather serve` and CLI queries, say — can have one file open at once,
+//! while a writer waits for all of them to close. The file and its WAL are
+//! only ever read: every mutation fails with `ReadOnly` (`touch`, `link`,
+//! `save`, `compact` and `expire` do nothing), searches count no recalls,
+//! and records past their time-to-live are not expired.
+//!
+//! A directory is opened as a sharded store (see `shard`); `shards(n)`
+//! creates one of `n` shard files, where a path that does not exist yet
+//! would otherwise become a single file.
+
+use crate::config::Metric;
+use crate::lock::{FileLock, LockMode};
+use crate::{feather_detach, shard, snapshots, AutoSave, Compression, Dedup, DuplicateId, Maintenance, OnMatch, DB};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// What inserting an id that already has a live record does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnDuplicate {
+    /// Fail with `DuplicateId`.
+    Error,
+    /// Replace the record, metadata included.
+    #[default]
+    Overwrite,
+    /// Keep the existing record and drop the insert without an error.
+    Ignore,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct OpenOptions {
+    dim: usize,
+    on_duplicate: OnDuplicate,
+    dedup: (Dedup, OnMatch),
+    normalize: bool,
+    hamming: bool,
+    read_only: bool,
+    create: bool,
+    create_new: bool,
+    collection: Option<String>,
+    shards: usize,
+    compression: Compression,
+    actor: Option<String>,
+    auto_save: AutoSave,
+    maintenance: Option<Maintenance>,
+    as_of: Option<i64>,
+    seed: Option<u64>,
+    model: Option<String>,
+}
+
+impl OpenOptions {
+    pub fn new() -> Self { OpenOptions::default() }
+
+    /// Dimension of the `text` modality for a new file; as for `DB::open`.
+    pub fn dim(mut self, dim: usize) -> Self {
+        self.dim = dim;
+        self
+    }
+
+    /// Create the file if it does not exist.
+    pub fn create(mut self, create: bool) -> Self {
+        self.create = create;
+        self
+    }
+
+    /// Create the file, failing if it exists already; `create` is then
+    /// implied.
+    pub fn create_new(mut self, create_new: bool) -> Self {
+        self.create_new = create_new;
+        self
+    }
+
+    /// Split a new store into `n` shard files in the directory `path`
+    /// (see `shard`); an existing one must have `n`. 0, the default, makes
+    /// a single file and opens a directory with whatever shards it has.
+    pub fn shards(mut self, n: usize) -> Self {
+        self.shards = n;
+        self
+    }
+
+    /// What a new store's file is packed with (see `compress`); an
+    /// existing store keeps its own setting.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// How vectors compare: `Metric::Cosine` is `normalize(true)`;
+    /// `Metric::Hamming` makes a new store hold bits (see `hamming`), and
+    /// an existing one must already.
+    pub fn metric(mut self, metric: Metric) -> Self {
+        self.hamming = metric == Metric::Hamming;
+        match metric {
+            Metric::Hamming => self,
+            other => self.normalize(other == Metric::Cosine),
+        }
+    }
+
+    /// Return a handle on the named collection of the file rather than on
+    /// the default one. It must exist unless `create` is set, which
+    /// registers it.
+    pub fn collection(mut self, name: &str) -> Self {
+        self.collection = Some(name.to_string());
+        self
+    }
+
+    pub fn on_duplicate(mut self, policy: OnDuplicate) -> Self {
+        self.on_duplicate = policy;
+        self
+    }
+
+    /// Turn on unit normalization of vectors and queries (see
+    /// `normalize`). The file remembers it, so `false` leaves a file that
+    /// normalizes as it is.
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Open an existing file for reading only, alongside other readers;
+    /// see the module docs.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Name who makes this handle's changes in the audit log (see
+    /// `audit`).
+    pub fn actor(mut self, actor: &str) -> Self {
+        self.actor = Some(actor.to_string());
+        self
+    }
+
+    /// Save every so many changes or so often (see `autosave`).
+    pub fn auto_save(mut self, policy: AutoSave) -> Self {
+        self.auto_save = policy;
+        self
+    }
+
+    /// Expire, decay, compact and checkpoint every so often (see
+    /// `maintenance`).
+    pub fn maintenance(mut self, policy: Maintenance) -> Self {
+        self.maintenance = Some(policy);
+        self
+    }
+
+    /// Open the store as it was at `at` (Unix seconds) instead: its last
+    /// snapshot taken by then, read-only (see `snapshots`).
+    pub fn as_of(mut self, at: i64) -> Self {
+        self.as_of = Some(at);
+        self
+    }
+
+    /// Build the indexes from `seed`, on one thread, so that the same
+    /// records give the same indexes and rankings on every run (see
+    /// `seed`).
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Declare the embedding model this handle's vectors and queries come
+    /// from; a store that records another is refused with `ModelMismatch`
+    /// (see `model`).
+    pub fn model(mut self, model: &str) -> Self {
+        self.model = Some(model.to_string());
+        self
+    }
+
+    /// See `dedup`.
+    pub fn dedup(mut self, dedup: Dedup, on_match: OnMatch) -> Self {
+        self.dedup = (dedup, on_match);
+        self
+    }
+
+    /// Open the store at `path`, locked for writing; fails with `Locked` if
+    /// another process has it open (see `lock`). Read-only, the file must
+    /// exist whatever `create` says, and only a writer holding it makes
+    /// this fail. With `as_of`, opens the snapshot instead, read-only.
+    pub fn open(&self, path: &Path) -> anyhow::Result<DB> {
+        anyhow::ensure!(!(self.hamming && self.normalize), "binary vectors cannot be normalized");
+        // refused before a new store's file is made
+        anyhow::ensure!(path.exists() || self.compression == Compression::None || cfg!(feature = "zstd"),
+                        "cannot pack {:?}: this build has no zstd (rebuild with the `zstd` feature)", path);
+        if let Some(at) = self.as_of {
+            let snapshot = snapshots::at(path, at)?;
+            return OpenOptions { as_of: None, ..self.clone() }.open_read_only(&snapshot);
+        }
+        if path.is_dir() || (self.shards > 0 && !path.exists()) {
+            return self.open_sharded(path);
+        }
+        anyhow::ensure!(self.shards == 0 || !path.is_file(), "{:?} is a single-file store, not a sharded one", path);
+        if self.read_only {
+            return self.open_read_only(path);
+        }
+        let create = self.create || self.create_new;
+        anyhow::ensure!(create || path.is_file(), "no database at {:?}", path);
+        let lock = FileLock::acquire(path, LockMode::Exclusive)?;
+        // checked again under the lock, against a writer creating it meanwhile
+        anyhow::ensure!(!(self.create_new && path.exists()), "{:?} already exists", path);
+        let new = !path.exists();
+        let db = DB::open_unlocked(path, self.dim)?;
+        db.handle.lock.replace(Some(lock));
+        self.apply(db, Some(path), create, new)
+    }
+
+    /// An ephemeral store with these options, as `DB::in_memory` makes:
+    /// nothing touches the filesystem until `persist_to` gives it a path.
+    pub fn open_in_memory(&self) -> anyhow::Result<DB> {
+        anyhow::ensure!(!self.read_only, "an in-memory store cannot be read-only");
+        anyhow::ensure!(!(self.hamming && self.normalize), "binary vectors cannot be normalized");
+        self.apply(DB::in_memory(self.dim), None, true, true)
+    }
+
+    // Set the policies of these options on a newly opened `db` (and the
+    // compression, if the store is `new`), and scope it to the collection
+    // asked for, registering it if `create`. `path` is None in memory.
+    fn apply(&self, db: DB, path: Option<&Path>, create: bool, new: bool) -> anyhow::Result<DB> {
+        if let Some(seed) = self.seed {
+            db.set_seed(seed)?;
+        }
+        db.set_on_duplicate(self.on_duplicate);
+        if new {
+            db.set_compression(self.compression)?;
+        }
+        db.set_dedup(self.dedup.0, self.dedup.1)?;
+        db.set_auto_save(self.auto_save);
+        if self.maintenance.is_some() {
+            db.set_maintenance(self.maintenance)?;
+        }
+        if self.normalize {
+            db.set_normalize(true)?;
+        }
+        if self.hamming && db.metric() != Metric::Hamming {
+            anyhow::ensure!(new, "{:?} does not hold binary vectors; the metric is fixed when a store is created",
+                            path.unwrap_or(Path::new("memory")));
+            db.set_hamming()?;
+        }
+        if let Some(model) = &self.model {
+            match db.model() {
+                None if !db.is_read_only() => db.set_model(Some(model))?,
+                _ => db.check_model(model)?,
+            }
+        }
+        db.apply_tuned_ef();
+        db.set_actor(self.actor.as_deref());
+        if let Some(path) = path {
+            db.attach_audit(path)?;
+        }
+        match &self.collection {
+            None => Ok(db),
+            Some(name) => {
+                anyhow::ensure!(create || db.has_collection(name), "no collection '{}' in {:?}", name,
+                                path.unwrap_or(Path::new("memory")));
+                db.collection(name)
+            }
+        }
+    }
+
+    fn open_read_only(&self, path: &Path) -> anyhow::Result<DB> {
+        anyhow::ensure!(path.is_file(), "no database at {:?}", path);
+        anyhow::ensure!(!self.normalize, "cannot turn on normalization read-only");
+        let lock = FileLock::acquire(path, LockMode::Shared)?;
+        let db = DB::open_unlocked(path, self.dim)?;
+        // the core keeps what it loaded but forgets the file, so nothing,
+        // not even closing, writes to it
+        unsafe { feather_detach(db.ptr) };
+        db.handle.lock.replace(Some(lock));
+        db.handle.read_only.set(true);
+        self.apply(db, Some(path), false, false)
+    }
+
+    fn open_sharded(&self, dir: &Path) -> anyhow::Result<DB> {
+        anyhow::ensure!(!(self.read_only && self.normalize), "cannot turn on normalization read-only");
+        let create = !self.read_only && (self.create || self.create_new);
+        let mode = if self.read_only { LockMode::Shared } else { LockMode::Exclusive };
+        let lock = FileLock::acquire(dir, mode)?;
+        let new = shard::files(dir).is_empty();
+        anyhow::ensure!(!self.create_new || new, "{:?} already exists", dir);
+        let db = DB::open_shards(dir, self.dim, self.shards, create)?;
+        if self.read_only {
+            for &core in db.handle.cores() {
+                unsafe { feather_detach(core) };
+            }
+            db.handle.read_only.set(true);
+        }
+        db.handle.lock.replace(Some(lock));
+        self.apply(db, Some(dir), create, new)
+    }
+}
+
+impl DB {
+    /// The duplicate-id policy of this file's handles.
+    pub fn on_duplicate(&self) -> OnDuplicate { self.handle.on_duplicate.get() }
+
+    /// Change the duplicate-id policy, for every handle on this file
+    /// (collections included). Not persisted.
+    pub fn set_on_duplicate(&self, policy: OnDuplicate) {
+        self.handle.on_duplicate.set(policy)
+    }
+
+    // Whether an insert of `id` goes ahead under the policy: false to skip
+    // it, an error to refuse it.
+    pub(crate) fn admit(&self, id: u64) -> anyhow::Result<bool> {
+        let policy = self.on_duplicate();
+        if policy == OnDuplicate::Overwrite || self.get_metadata(id).is_none_or(|m| m.is_forgotten()) {
+            return Ok(true);
+        }
+        anyhow::ensure!(policy == OnDuplicate::Ignore, DuplicateId { id });
+        Ok(false)
+    }
+
+    // `admit` for each of `ids`, an id repeated within them counting as a
+    // duplicate of its first occurrence.
+    pub(crate) fn admit_all(&self, ids: &[u64]) -> anyhow::Result<Vec<bool>> {
+        let policy = self.on_duplicate();
+        if policy == OnDuplicate::Overwrite { return Ok(vec![true; ids.len()]); }
+        let mut seen = HashSet::new();
+        ids.iter()
+            .map(|&id| {
+                if seen.insert(id) { return self.admit(id); }
+                anyhow::ensure!(policy == OnDuplicate::Ignore, DuplicateId { id });
+                Ok(false)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,181 @@
+//! Choosing how a filtered search finds its candidates (shown by
+//! `DB::explain_search`, `feather search --explain`).
+//!
+//! A filter can be applied two ways. Pre-filtering lists the records it
+//! admits from the secondary indexes (see `index`), or with no index on its
+//! terms checks every record, and measures each against the query exactly.
+//! Post-filtering traverses the vector graph as an unfiltered search does,
+//! passing over records the filter rejects and fetching more while too few
+//! are left. The first costs a distance per record listed, however few hits
+//! are wanted; the second about `GRAPH_COST` per candidate fetched, and the
+//! rarer the matches, the more candidates it takes to find enough.
+//!
+//! Before a filtered search the planner gathers statistics of its filter:
+//! how many records the indexed terms narrow it to (the index sets' shares
+//! of all records multiplied, as if independent), and which of an even
+//! sample of up to `SAMPLE` vectors per index the search would admit. It
+//! then runs whichever plan should compute fewer distances. Searches without
+//! a filter traverse the graph (`Plan::Ann`); exact and truncated searches
+//! measure every record as asked (`Plan::Scan`).
+
+use crate::index::Prefilter;
+use crate::*;
+use serde::Serialize;
+use std::fmt;
+
+/// Vectors sampled per index to estimate a filter's selectivity.
+pub const SAMPLE: usize = 256;
+
+/// Distances a graph traversal computes per candidate it returns, about
+/// the links of a node in the bottom layer.
+pub const GRAPH_COST: usize = 32;
+
+extern "C" {
+    fn feather_filter_stats(db: *mut c_void, modality: *const c_char, time_range: *const i64,
+                            source: *const c_char, attributes: *const *const c_char, attribute_count: usize,
+                            include_archived: i32, sample: usize, out_stats: *mut u64, out_ids: *mut u64,
+                            cap: usize) -> i64;
+}
+
+/// How a search finds its candidates.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Plan {
+    /// Traverse the vector graph; there is nothing to filter.
+    #[default]
+    Ann,
+    /// List the records the filter admits and measure each.
+    PreFilter,
+    /// Traverse the vector graph, passing over records the filter rejects.
+    PostFilter,
+    /// Measure every record (`SearchOptions::exact`, `truncate_dim`).
+    Scan,
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Plan::Ann => "ann",
+            Plan::PreFilter => "pre-filter",
+            Plan::PostFilter => "post-filter",
+            Plan::Scan => "scan",
+        })
+    }
+}
+
+/// A filter's statistics, and the cost of each plan they predict.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Estimate {
+    /// Live vectors in the modality.
+    pub vectors: usize,
+    /// Records the secondary indexes narrow the filter to; None if no index
+    /// applies to it.
+    pub index_rows: Option<usize>,
+    /// Vectors sampled.
+    pub sampled: usize,
+    /// Sampled vectors the search admits.
+    pub admitted: usize,
+    /// Vectors expected to match, scaled up from the sample.
+    pub matches: usize,
+    /// Distances pre-filtering computes.
+    pub pre_filter_cost: usize,
+    /// Distances post-filtering computes, about.
+    pub post_filter_cost: usize,
+}
+
+/// The plan a search ran, and what it was chosen on.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct QueryPlan {
+    pub plan: Plan,
+    /// None unless the search is filtered and could run either way.
+    pub estimate: Option<Estimate>,
+}
+
+impl QueryPlan {
+    // Make `prefilter` run the plan.
+    pub(crate) fn apply(&self, prefilter: &mut Prefilter) {
+        match (self.plan, &self.estimate) {
+            (Plan::PreFilter, Some(e)) if e.index_rows.is_none() => prefilter.exact = true,
+            (Plan::PostFilter, _) => prefilter.post_filter = true,
+            _ => {}
+        }
+    }
+}
+
+// What the cores report of a filter over one modality.
+#[derive(Default)]
+pub(crate) struct FilterStats {
+    vectors: usize,
+    index_rows: Option<usize>,
+    sampled: usize,
+    // internal ids of the sampled vectors the core-side terms admit
+    matched: Vec<u64>,
+}
+
+impl Handle {
+    // `prefilter`'s statistics over `modality` in every core, the base's too.
+    pub(crate) fn filter_stats(&self, modality: &str, prefilter: &Prefilter) -> anyhow::Result<FilterStats> {
+        let c_modality = c_str(modality)?;
+        let c_prefilter = CPrefilter::new(prefilter)?;
+        let mut stats = FilterStats::default();
+        for &core in self.cores() {
+            let mut counts = [0u64; 4];
+            let mut ids = vec![0u64; SAMPLE];
+            let n = unsafe {
+                feather_filter_stats(core, c_modality.as_ptr(), c_prefilter.range(), opt_ptr(&c_prefilter.source),
+                                     c_prefilter.attributes.as_ptr(), prefilter.attributes.len(),
+                                     prefilter.include_archived as i32, SAMPLE, counts.as_mut_ptr(),
+                                     ids.as_mut_ptr(), ids.len())
+            };
+            if n < 0 { return Err(last_error()); }
+            stats.vectors += counts[0] as usize;
+            if counts[1] != 0 { stats.index_rows = Some(stats.index_rows.unwrap_or(0) + counts[2] as usize); }
+            stats.sampled += counts[3] as usize;
+            stats.matched.extend(ids.into_iter().take(n as usize));
+        }
+        if let Some(base) = &self.fork {
+            let theirs = base.handle.filter_stats(modality, prefilter)?;
+            stats.vectors += theirs.vectors;
+            stats.index_rows = match (stats.index_rows, theirs.index_rows) {
+                (None, None) => None,
+                (ours, theirs) => Some(ours.unwrap_or(0) + theirs.unwrap_or(0)),
+            };
+            stats.sampled += theirs.sampled;
+            stats.matched.extend(theirs.matched);
+        }
+        Ok(stats)
+    }
+}
+
+impl DB {
+    // How a search for `candidates` hits in `modality` should run, given
+    // `prefilter`, the part of `options` the index scan enforces.
+    pub(crate) fn plan(&self, candidates: usize, modality: &str, options: &SearchOptions,
+                       prefilter: &Prefilter) -> anyhow::Result<QueryPlan> {
+        if options.exact || options.truncate_dim.is_some() {
+            return Ok(QueryPlan { plan: Plan::Scan, estimate: None });
+        }
+        if !options.filters() { return Ok(QueryPlan::default()); }
+        let internal = self.mname(Some(modality)).expect("named");
+        let stats = self.handle.filter_stats(&internal, prefilter)?;
+        let admitted = stats.matched.iter()
+            .filter(|&&id| self.xid(id).is_some_and(|id| self.admitted(id, options).is_some()))
+            .count();
+        let sampled = stats.sampled.max(1) as f64;
+        let matches = (admitted as f64 / sampled * stats.vectors as f64).round() as usize;
+        // matches too rare to show in the sample: take half a sampled vector's share
+        let share = (admitted as f64).max(0.5) / sampled;
+        let fetched = (candidates as f64 / share).min(stats.vectors as f64);
+        let estimate = Estimate {
+            vectors: stats.vectors,
+            index_rows: stats.index_rows,
+            sampled: stats.sampled,
+            admitted,
+            matches,
+            pre_filter_cost: stats.index_rows.unwrap_or(stats.vectors),
+            post_filter_cost: (fetched * GRAPH_COST as f64).round() as usize,
+        };
+        let plan = if estimate.pre_filter_cost <= estimate.post_filter_cost { Plan::PreFilter } else { Plan::PostFilter };
+        Ok(QueryPlan { plan, estimate: Some(estimate) })
+    }
+}
@@ -0,0 +1,126 @@
+//! Progress of long operations — bulk import, compaction, fitting a
+//! projection, reprojection — which can take minutes on large stores.
+//!
+//! Each takes an optional `ProgressFn`, called every so often and once at
+//! the end with a `Progress`. `Bar` draws those on a terminal, as the CLI
+//! does.
+
+use std::ffi::c_void;
+use std::io::{IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+/// Where a long operation has got to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+    /// What is being done: "import", "compact", "sample", "fit",
+    /// "reproject" or "reembed".
+    pub stage: &'static str,
+    pub done: usize,
+    /// Units in all, if known up front (an import from a stream is not).
+    pub total: Option<usize>,
+}
+
+/// Callback a long operation reports its `Progress` to.
+pub type ProgressFn<'a> = dyn FnMut(Progress) + 'a;
+
+/// Reports `(done, total)` under one stage to an optional `ProgressFn`.
+pub(crate) struct Reporter<'a, 'b> {
+    stage: &'static str,
+    progress: Option<&'a mut ProgressFn<'b>>,
+}
+
+impl<'a, 'b> Reporter<'a, 'b> {
+    pub(crate) fn new(stage: &'static str, progress: Option<&'a mut ProgressFn<'b>>) -> Self {
+        Reporter { stage, progress }
+    }
+
+    pub(crate) fn report(&mut self, done: usize, total: Option<usize>) {
+        if let Some(progress) = &mut self.progress {
+            progress(Progress { stage: self.stage, done, total });
+        }
+    }
+
+    /// The core's callback and its context, for the FFI calls that take one;
+    /// a null callback if there is no `ProgressFn`. Valid while `self` is.
+    pub(crate) fn as_c(&mut self) -> (Option<ProgressCb>, *mut c_void) {
+        match self.progress {
+            Some(_) => (Some(forward), (self as *mut Self).cast()),
+            None => (None, std::ptr::null_mut()),
+        }
+    }
+}
+
+/// `feather_progress_cb`: the core's progress callback.
+pub(crate) type ProgressCb = unsafe extern "C" fn(ctx: *mut c_void, done: usize, total: usize);
+
+unsafe extern "C" fn forward(ctx: *mut c_void, done: usize, total: usize) {
+    // ctx is the Reporter that `as_c` gave out, still borrowed by the caller
+    let reporter = unsafe { &mut *ctx.cast::<Reporter>() };
+    reporter.report(done, Some(total));
+}
+
+// Redraw no more often than this.
+const REDRAW_EVERY: Duration = Duration::from_millis(100);
+
+const BAR_WIDTH: usize = 30;
+
+/// A progress bar on stderr: stage, bar, percentage, count, rate and time
+/// left when the total is known; stage, count and rate when not. Draws
+/// nothing unless stderr is a terminal.
+pub struct Bar {
+    enabled: bool,
+    stage: Option<&'static str>,
+    start: Instant,
+    drawn: Option<Instant>,
+}
+
+impl Default for Bar {
+    fn default() -> Self { Bar::new() }
+}
+
+impl Bar {
+    pub fn new() -> Self {
+        Bar { enabled: std::io::stderr().is_terminal(), stage: None, start: Instant::now(), drawn: None }
+    }
+
+    pub fn update(&mut self, p: Progress) {
+        if !self.enabled { return; }
+        if self.stage != Some(p.stage) {
+            // a new stage starts on a line of its own
+            self.finish();
+            self.stage = Some(p.stage);
+            self.start = Instant::now();
+        }
+        let complete = p.total == Some(p.done);
+        if !complete && self.drawn.is_some_and(|t| t.elapsed() < REDRAW_EVERY) { return; }
+        self.drawn = Some(Instant::now());
+
+        let secs = self.start.elapsed().as_secs_f64();
+        let rate = p.done as f64 / secs.max(f64::EPSILON);
+        let line = match p.total {
+            Some(total) => {
+                let frac = if total == 0 { 1.0 } else { (p.done as f64 / total as f64).min(1.0) };
+                let filled = (frac * BAR_WIDTH as f64).round() as usize;
+                let left = if rate > 0.0 { (total.saturating_sub(p.done)) as f64 / rate } else { 0.0 };
+                format!("{:<9} [{}{}] {:>3.0}% {}/{} ({:.0}/s, {:.0}s left)", p.stage,
+                        "#".repeat(filled), "-".repeat(BAR_WIDTH - filled), frac * 100.0,
+                        p.done, total, rate, left)
+            }
+            None => format!("{:<9} {} ({:.0}/s)", p.stage, p.done, rate),
+        };
+        let mut err = std::io::stderr().lock();
+        let _ = write!(err, "\r{}\x1b[K", line);
+        let _ = err.flush();
+    }
+
+    /// End the current line, if anything was drawn on it.
+    pub fn finish(&mut self) {
+        if self.drawn.take().is_some() {
+            eprintln!();
+        }
+    }
+}
+
+impl Drop for Bar {
+    fn drop(&mut self) { self.finish(); }
+}
@@ -0,0 +1,323 @@
+//! Linear projections applied to stored vectors and to every future insert
+//! and query (`feather redim`). Persisted per modality in the DB properties.
+
+use crate::progress::Reporter;
+use crate::ProgressFn;
+use ndarray::{Array1, Array2, Axis};
+use std::collections::HashMap;
+
+/// Property key holding every modality's projection.
+pub(crate) const PROPERTY_KEY: &str = "projections";
+
+/// Subspace-iteration rounds for PCA; converges well past what retrieval needs.
+const PCA_ITERS: usize = 24;
+
+/// Rotation/codebook alternations for OPQ, Lloyd rounds per codebook, and
+/// centroids per subspace (one byte per code).
+const OPQ_ITERS: usize = 8;
+const KMEANS_ITERS: usize = 4;
+const OPQ_CENTROIDS: usize = 256;
+
+/// Newton–Schulz rounds for the orthogonal polar factor.
+const POLAR_ITERS: usize = 40;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Projection {
+    /// Keep the first `out_dim` components.
+    Truncate { in_dim: usize, out_dim: usize },
+    /// `out = matrix · v + bias`, `matrix` being `out_dim × in_dim` row-major.
+    Affine { in_dim: usize, out_dim: usize, matrix: Vec<f32>, bias: Vec<f32> },
+}
+
+impl Projection {
+    pub fn truncate(in_dim: usize, out_dim: usize) -> anyhow::Result<Self> {
+        anyhow::ensure!(out_dim > 0 && out_dim < in_dim,
+                        "target dim {} must be in 1..{}", out_dim, in_dim);
+        Ok(Projection::Truncate { in_dim, out_dim })
+    }
+
+    /// Fit a PCA projection onto the top `out_dim` principal components of
+    /// `samples`, ordered by explained variance and centered on their mean.
+    pub fn fit_pca(samples: &[Vec<f32>], out_dim: usize) -> anyhow::Result<Self> {
+        Projection::fit_pca_with_progress(samples, out_dim, None)
+    }
+
+    /// `fit_pca`, reporting iterations done under the stage "fit".
+    pub fn fit_pca_with_progress(samples: &[Vec<f32>], out_dim: usize,
+                                 progress: Option<&mut ProgressFn>) -> anyhow::Result<Self> {
+        Projection::pca(samples, out_dim, &mut Reporter::new("fit", progress), PCA_ITERS)
+    }
+
+    // `fit_pca`, its iterations reported as the first of `total`.
+    fn pca(samples: &[Vec<f32>], out_dim: usize, reporter: &mut Reporter, total: usize) -> anyhow::Result<Self> {
+        let n = samples.len();
+        anyhow::ensure!(n >= 2, "PCA needs at least 2 vectors, got {}", n);
+        let in_dim = samples[0].len();
+        anyhow::ensure!(out_dim > 0 && out_dim < in_dim,
+                        "target dim {} must be in 1..{}", out_dim, in_dim);
+        anyhow::ensure!(out_dim <= n, "PCA to {} dims needs at least {} vectors, got {}",
+                        out_dim, out_dim, n);
+
+        let mut x = Array2::<f32>::zeros((n, in_dim));
+        for (i, v) in samples.iter().enumerate() {
+            anyhow::ensure!(v.len() == in_dim, "sample {} has dim {}, expected {}", i, v.len(), in_dim);
+            x.row_mut(i).assign(&Array1::from(v.clone()));
+        }
+        let mean = x.mean_axis(Axis(0)).expect("n >= 2");
+        x -= &mean;
+        let cov = x.t().dot(&x) / (n - 1) as f32;
+        reporter.report(0, Some(total));
+
+        // Deterministic start so the same data always yields the same basis.
+        let mut seed = 0x9E37_79B9_7F4A_7C15u64;
+        let mut q = Array2::<f32>::from_shape_fn((in_dim, out_dim), |_| {
+            seed ^= seed << 13; seed ^= seed >> 7; seed ^= seed << 17;
+            (seed >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+        });
+        orthonormalize(&mut q);
+        for i in 0..PCA_ITERS {
+            q = cov.dot(&q);
+            orthonormalize(&mut q);
+            reporter.report(i + 1, Some(total));
+        }
+
+        // Order components by variance explained (Rayleigh quotient).
+        let cq = cov.dot(&q);
+        let mut order: Vec<(usize, f32)> = (0..out_dim)
+            .map(|j| (j, q.column(j).dot(&cq.column(j))))
+            .collect();
+        order.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let mut matrix = Vec::with_capacity(out_dim * in_dim);
+        let mut bias = Vec::with_capacity(out_dim);
+        for (j, _) in order {
+            let col = q.column(j);
+            matrix.extend(col.iter().copied());
+            bias.push(-col.dot(&mean));
+        }
+        Ok(Projection::Affine { in_dim, out_dim, matrix, bias })
+    }
+
+    /// PCA to `out_dim`, followed by the OPQ rotation (non-parametric OPQ,
+    /// Ge et al.) that minimises the error of a product quantizer with
+    /// `subspaces` equal subspaces. The rotation keeps distances, so search
+    /// ranks as after plain PCA, but variance is spread evenly across the
+    /// subspaces, which suits quantized storage.
+    pub fn fit_opq(samples: &[Vec<f32>], out_dim: usize, subspaces: usize) -> anyhow::Result<Self> {
+        Projection::fit_opq_with_progress(samples, out_dim, subspaces, None)
+    }
+
+    /// `fit_opq`, reporting iterations done under the stage "fit".
+    pub fn fit_opq_with_progress(samples: &[Vec<f32>], out_dim: usize, subspaces: usize,
+                                 progress: Option<&mut ProgressFn>) -> anyhow::Result<Self> {
+        anyhow::ensure!(subspaces > 0 && out_dim.is_multiple_of(subspaces),
+                        "target dim {} must split into {} equal subspaces", out_dim, subspaces);
+        let total = PCA_ITERS + OPQ_ITERS;
+        let mut reporter = Reporter::new("fit", progress);
+        let pca = Projection::pca(samples, out_dim, &mut reporter, total)?;
+        let x = Array2::from_shape_vec((samples.len(), out_dim),
+                                       samples.iter().flat_map(|v| pca.apply(v)).collect())
+            .expect("shape");
+        let centroids = OPQ_CENTROIDS.min(samples.len());
+        let mut r = Array2::<f32>::eye(out_dim);
+        for i in 0..OPQ_ITERS {
+            reporter.report(PCA_ITERS + i, Some(total));
+            let y_hat = pq_reconstruct(&x.dot(&r), subspaces, centroids);
+            // Procrustes: the orthogonal R minimising |XR - Ŷ| is the polar
+            // factor of XᵀŶ; keep the last one if it has none (rank-deficient)
+            match polar(&x.t().dot(&y_hat)) {
+                Some(next) => r = next,
+                None => break,
+            }
+        }
+        reporter.report(total, Some(total));
+        // rows are samples, so a vector v maps to Rᵀv
+        let rotation = Projection::Affine {
+            in_dim: out_dim,
+            out_dim,
+            matrix: r.t().iter().copied().collect(),
+            bias: vec![0.0; out_dim],
+        };
+        Ok(pca.then(&rotation))
+    }
+
+    pub fn in_dim(&self) -> usize {
+        match self { Projection::Truncate { in_dim, .. } | Projection::Affine { in_dim, .. } => *in_dim }
+    }
+
+    pub fn out_dim(&self) -> usize {
+        match self { Projection::Truncate { out_dim, .. } | Projection::Affine { out_dim, .. } => *out_dim }
+    }
+
+    pub fn method(&self) -> &'static str {
+        match self { Projection::Truncate { .. } => "truncate", Projection::Affine { .. } => "affine" }
+    }
+
+    pub fn apply(&self, v: &[f32]) -> Vec<f32> {
+        match self {
+            Projection::Truncate { out_dim, .. } => v[..*out_dim].to_vec(),
+            Projection::Affine { in_dim, matrix, bias, .. } => matrix
+                .chunks_exact(*in_dim)
+                .zip(bias)
+                .map(|(row, b)| row.iter().zip(v).map(|(m, x)| m * x).sum::<f32>() + b)
+                .collect(),
+        }
+    }
+
+    /// The matrix/bias form of this projection.
+    pub fn to_affine(&self) -> (Vec<f32>, Vec<f32>) {
+        match self {
+            Projection::Truncate { in_dim, out_dim } => {
+                let mut matrix = vec![0f32; out_dim * in_dim];
+                for r in 0..*out_dim { matrix[r * in_dim + r] = 1.0; }
+                (matrix, vec![0f32; *out_dim])
+            }
+            Projection::Affine { matrix, bias, .. } => (matrix.clone(), bias.clone()),
+        }
+    }
+
+    /// `self` followed by `next`, as one projection from `self.in_dim()`.
+    pub fn then(&self, next: &Projection) -> Projection {
+        if let (Projection::Truncate { in_dim, .. }, Projection::Truncate { out_dim, .. }) = (self, next) {
+            return Projection::Truncate { in_dim: *in_dim, out_dim: *out_dim };
+        }
+        let (m1, b1) = self.to_affine();
+        let (m2, b2) = next.to_affine();
+        let (d0, d1, d2) = (self.in_dim(), self.out_dim(), next.out_dim());
+        let m1 = Array2::from_shape_vec((d1, d0), m1).expect("shape");
+        let m2 = Array2::from_shape_vec((d2, d1), m2).expect("shape");
+        let bias = m2.dot(&Array1::from(b1)) + Array1::from(b2);
+        Projection::Affine {
+            in_dim: d0,
+            out_dim: d2,
+            matrix: m2.dot(&m1).iter().copied().collect(),
+            bias: bias.to_vec(),
+        }
+    }
+}
+
+// Modified Gram–Schmidt over the columns; degenerate columns are zeroed.
+fn orthonormalize(q: &mut Array2<f32>) {
+    for j in 0..q.ncols() {
+        for i in 0..j {
+            let dot = q.column(i).dot(&q.column(j));
+            let prev = q.column(i).to_owned();
+            q.column_mut(j).scaled_add(-dot, &prev);
+        }
+        let norm = q.column(j).dot(&q.column(j)).sqrt();
+        let mut col = q.column_mut(j);
+        if norm > 1e-12 { col /= norm; } else { col.fill(0.0); }
+    }
+}
+
+// Each row of `y` with every subspace replaced by its nearest centroid of a
+// k-means codebook fitted on that subspace.
+fn pq_reconstruct(y: &Array2<f32>, subspaces: usize, centroids: usize) -> Array2<f32> {
+    let (n, dim) = y.dim();
+    let width = dim / subspaces;
+    let mut out = Array2::<f32>::zeros((n, dim));
+    for s in 0..subspaces {
+        let cols = s * width..(s + 1) * width;
+        let sub: Vec<f32> = y.slice(ndarray::s![.., cols.clone()]).iter().copied().collect();
+        // evenly spaced samples as the starting codebook, for determinism
+        let mut codebook: Vec<f32> = (0..centroids)
+            .flat_map(|c| sub[c * n / centroids * width..][..width].to_vec())
+            .collect();
+        let mut assign = vec![0usize; n];
+        for round in 0..=KMEANS_ITERS {
+            for (i, row) in sub.chunks_exact(width).enumerate() {
+                assign[i] = codebook.chunks_exact(width)
+                    .map(|c| c.iter().zip(row).map(|(a, b)| (a - b) * (a - b)).sum::<f32>())
+                    .enumerate()
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .map_or(0, |(c, _)| c);
+            }
+            if round == KMEANS_ITERS { break; }
+            let mut sums = vec![0f32; centroids * width];
+            let mut counts = vec![0usize; centroids];
+            for (i, row) in sub.chunks_exact(width).enumerate() {
+                for (s, x) in sums[assign[i] * width..][..width].iter_mut().zip(row) { *s += x; }
+                counts[assign[i]] += 1;
+            }
+            for (c, &count) in counts.iter().enumerate().filter(|(_, &count)| count > 0) {
+                for (k, s) in codebook[c * width..][..width].iter_mut().zip(&sums[c * width..]) {
+                    *k = s / count as f32;
+                }
+            }
+        }
+        for (i, &c) in assign.iter().enumerate() {
+            for (j, col) in cols.clone().enumerate() {
+                out[[i, col]] = codebook[c * width + j];
+            }
+        }
+    }
+    out
+}
+
+// The orthogonal factor U·Vᵀ of `m` = U·Σ·Vᵀ, by Newton–Schulz iteration;
+// None if `m` is too close to singular for it to converge.
+fn polar(m: &Array2<f32>) -> Option<Array2<f32>> {
+    let norm = m.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm <= f32::EPSILON { return None; }
+    let mut z = m / norm;
+    for _ in 0..POLAR_ITERS {
+        z = &z * 1.5 - z.dot(&z.t()).dot(&z) * 0.5;
+    }
+    let error = (z.t().dot(&z) - Array2::<f32>::eye(z.ncols())).iter().map(|x| x.abs()).fold(0.0, f32::max);
+    (error < 1e-3).then_some(z)
+}
+
+pub(crate) fn encode(map: &HashMap<String, Projection>) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend((map.len() as u32).to_le_bytes());
+    for (name, p) in map {
+        out.extend((name.len() as u16).to_le_bytes());
+        out.extend(name.as_bytes());
+        out.push(matches!(p, Projection::Affine { .. }) as u8);
+        out.extend((p.in_dim() as u32).to_le_bytes());
+        out.extend((p.out_dim() as u32).to_le_bytes());
+        if let Projection::Affine { matrix, bias, .. } = p {
+            for x in matrix.iter().chain(bias) { out.extend(x.to_le_bytes()); }
+        }
+    }
+    out
+}
+
+pub(crate) fn decode(bytes: &[u8]) -> Option<HashMap<String, Projection>> {
+    let mut r = Reader(bytes);
+    let count = r.u32()?;
+    let mut map = HashMap::new();
+    for _ in 0..count {
+        let name_len = r.u16()? as usize;
+        let name = String::from_utf8(r.take(name_len)?.to_vec()).ok()?;
+        let affine = r.take(1)?[0] != 0;
+        let in_dim = r.u32()? as usize;
+        let out_dim = r.u32()? as usize;
+        let p = if affine {
+            let matrix = r.f32s(out_dim.checked_mul(in_dim)?)?;
+            let bias = r.f32s(out_dim)?;
+            Projection::Affine { in_dim, out_dim, matrix, bias }
+        } else {
+            Projection::Truncate { in_dim, out_dim }
+        };
+        map.insert(name, p);
+    }
+    Some(map)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n { return None; }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Some(head)
+    }
+    fn u16(&mut self) -> Option<u16> { Some(u16::from_le_bytes(self.take(2)?.try_into().ok()?)) }
+    fn u32(&mut self) -> Option<u32> { Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?)) }
+    fn f32s(&mut self, n: usize) -> Option<Vec<f32>> {
+        let raw = self.take(n.checked_mul(4)?)?;
+        Some(raw.chunks_exact(4).map(|c| f32::from_le_bytes(c.try_into().unwrap())).collect())
+    }
+}
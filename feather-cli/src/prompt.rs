@@ -0,0 +1,110 @@
+//! The memories that fit in a prompt (`DB::select_for_context`, `feather
+//! context`).
+//!
+//! An agent building a prompt has a token budget, not a k: ten hits may
+//! overflow it or leave most of it unused. `select_for_context` ranks
+//! candidates as `search_with_options` would, then packs them greedily by
+//! score per token, best first, skipping any that no longer fit, until the
+//! budget is spent. A short, relevant record thus goes in before a long one
+//! scoring a little higher.
+//!
+//! A record costs `estimate_tokens` of its content, about one token per
+//! `CHARS_PER_TOKEN` characters; records without content are left out, as
+//! are those whose time-to-live has run out though `expire()` has not yet
+//! forgotten them. Candidates are fetched from `CANDIDATES` on, doubling
+//! until they hold `CANDIDATE_FACTOR` times the budget, the ranking runs
+//! out or `MAX_CANDIDATES` are ranked. Only the records chosen count as
+//! recalled.
+
+use crate::rerank::Query;
+use crate::{decay, SearchOptions, DB};
+use serde::Serialize;
+
+/// Characters of content counted as one token.
+pub const CHARS_PER_TOKEN: usize = 4;
+
+/// Candidates ranked first.
+pub const CANDIDATES: usize = 64;
+
+/// Most candidates ranked.
+pub const MAX_CANDIDATES: usize = 4096;
+
+/// Tokens of candidates sought, as a multiple of the budget.
+pub const CANDIDATE_FACTOR: usize = 3;
+
+/// A record chosen for the prompt.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Selected {
+    pub id: u64,
+    pub score: f32,
+    /// Its estimated cost (`estimate_tokens`).
+    pub tokens: usize,
+}
+
+/// What `select_for_context` chose.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Selection {
+    /// The records chosen, best score first.
+    pub selected: Vec<Selected>,
+    /// Their tokens in all, at most the budget.
+    pub tokens: usize,
+    /// Candidates that did not fit.
+    pub left_out: usize,
+    /// Candidates left out because their time-to-live has run out.
+    pub expired: usize,
+}
+
+/// Tokens `text` is estimated to take in a prompt: one per
+/// `CHARS_PER_TOKEN` characters, rounded up.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+impl DB {
+    /// The records worth putting in a prompt of `token_budget` tokens for
+    /// `query` in `modality`, ranked as `options` say (see the module docs).
+    pub fn select_for_context(&self, query: &[f32], token_budget: usize, modality: &str,
+                              options: &SearchOptions) -> anyhow::Result<Selection> {
+        anyhow::ensure!(token_budget > 0, "the token budget must be positive");
+        let query = Query { vector: query, text: options.text.as_deref() };
+        let now = decay::now();
+        let wanted = token_budget.saturating_mul(CANDIDATE_FACTOR);
+        let mut k = CANDIDATES;
+        let (candidates, expired) = loop {
+            let hits = self.ranked(query, k, modality, options, None)?;
+            let ranked_all = hits.len() < k;
+            let mut candidates = Vec::with_capacity(hits.len());
+            let mut expired = 0;
+            for (id, score) in hits {
+                let Some(meta) = self.get_metadata(id) else { continue };
+                if meta.is_expired(now) {
+                    expired += 1;
+                } else if !meta.content.is_empty() {
+                    candidates.push(Selected { id, score, tokens: estimate_tokens(&meta.content) });
+                }
+            }
+            let tokens: usize = candidates.iter().map(|c| c.tokens).sum();
+            if ranked_all || tokens >= wanted || k >= MAX_CANDIDATES { break (candidates, expired); }
+            k = (k * 2).min(MAX_CANDIDATES);
+        };
+        let internal = self.mname(Some(modality)).expect("named");
+        self.observe_query(Some(&internal), &self.project(Some(&internal), query.vector));
+
+        let mut by_density = candidates;
+        by_density.sort_by(|a, b| (b.score / b.tokens as f32).total_cmp(&(a.score / a.tokens as f32))
+            .then(b.score.total_cmp(&a.score))
+            .then(a.id.cmp(&b.id)));
+        let mut selection = Selection { expired, ..Selection::default() };
+        for candidate in by_density {
+            if selection.tokens + candidate.tokens > token_budget {
+                selection.left_out += 1;
+                continue;
+            }
+            selection.tokens += candidate.tokens;
+            selection.selected.push(candidate);
+        }
+        selection.selected.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+        self.recalled(selection.selected.iter().map(|chosen| chosen.id));
+        Ok(selection)
+    }
+}
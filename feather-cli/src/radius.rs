@@ -0,0 +1,39 @@
+//! Every record near a query (`DB::search_radius`, `feather near`).
+//!
+//! A search returns the best k hits however far off they are. "Everything
+//! related to this", or everything a record duplicates, wants a distance
+//! instead: `search_radius` returns every live record whose vector lies
+//! within `max_distance` of the query, nearest first. It fetches nearest
+//! neighbours in batches that start at `FIRST_FETCH` and double until one
+//! lies beyond the radius.
+//!
+//! Distances are L2, as `Dedup::Vector`'s epsilon is. Like `knn`, hits
+//! are not scored or counted as recalled, and archived records are left
+//! out.
+
+use crate::DB;
+
+/// Neighbours fetched first; each further batch is twice the last.
+pub const FIRST_FETCH: usize = 32;
+
+impl DB {
+    /// Every live record whose vector in `modality` lies within
+    /// `max_distance` (L2) of `query`, as `(id, distance)`, nearest first.
+    pub fn search_radius(&self, query: &[f32], max_distance: f32, modality: &str) -> anyhow::Result<Vec<(u64, f32)>> {
+        anyhow::ensure!(max_distance >= 0.0 && max_distance.is_finite(), "radius must be a non-negative distance");
+        // knn measures squared distances
+        let limit = max_distance * max_distance;
+        let mut fetch = FIRST_FETCH;
+        loop {
+            let hits = self.knn(query, fetch, modality)?;
+            if hits.len() < fetch || hits.last().is_some_and(|&(_, d)| d > limit) {
+                return Ok(hits.into_iter()
+                    .take_while(|&(_, d)| d <= limit)
+                    .filter(|&(id, _)| self.get_metadata(id).is_some_and(|m| !m.is_forgotten()))
+                    .map(|(id, d)| (id, d.sqrt()))
+                    .collect());
+            }
+            fetch = fetch.saturating_mul(2);
+        }
+    }
+}
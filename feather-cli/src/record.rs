@@ -0,0 +1,34 @@
+//! A whole record — id, vectors in every modality, sparse vectors, and
+//! metadata — as moved between stores and files by export/import.
+
+use crate::{Metadata, SparseVector, DB};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub id: u64,
+    /// modality → vector
+    #[serde(default)]
+    pub vectors: BTreeMap<String, Vec<f32>>,
+    /// name → sparse vector
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sparse: BTreeMap<String, SparseVector>,
+    #[serde(flatten)]
+    pub metadata: Metadata,
+}
+
+impl DB {
+    /// The full record for `id` (vectors from every modality, sparse vectors
+    /// from every set), or None if it has no metadata.
+    pub fn record(&self, id: u64) -> Option<Record> {
+        let metadata = self.get_metadata(id)?;
+        let vectors = self.modalities().into_iter()
+            .filter_map(|m| self.get_vector(id, &m).map(|v| (m, v)))
+            .collect();
+        let sparse = self.sparse_names().into_iter()
+            .filter_map(|name| self.get_sparse(id, &name).map(|v| (name, v)))
+            .collect();
+        Some(Record { id, vectors, sparse, metadata })
+    }
+}
@@ -0,0 +1,168 @@
+//! Arrow record batches into and out of a store without a detour through
+//! rows (`DB::add_record_batch`, `DB::records_batch`, `DB::hits_batch`;
+//! the `arrow` feature).
+//!
+//! `add_record_batch` reads the columns `feather import` does: `id`, a
+//! `vector` / `embedding` / `values` column for the default modality or a
+//! `vector_<modality>` column per modality, and any metadata columns. A
+//! vector column of `FixedSizeList<Float32>`, or of `List<Float32>` whose
+//! rows share one length, goes to the index as the buffer Arrow already
+//! holds. Batches that have to be taken a record at a time (null or ragged
+//! vectors, ids the duplicate-id policy turns away, a dedup mode) are
+//! inserted the way `import` inserts rows instead.
+//!
+//! Batches going out are in the `feather export` layout; search hits get a
+//! `score` column after `id`.
+
+use crate::export::columnar::{schema, to_batch};
+use crate::import::{self, columnar::from_batch, ImportReport, VECTOR_KEYS};
+use crate::{Dedup, Metadata, Record, DB};
+use arrow::array::{Array, ArrayRef, FixedSizeListArray, Float32Array, Float64Array, ListArray};
+use arrow::datatypes::{DataType, Field, FieldRef, Schema};
+use arrow::record_batch::RecordBatch;
+use std::borrow::Cow;
+use std::io::Write;
+use std::sync::Arc;
+
+impl DB {
+    /// Insert every row of `batch`, vectors in an unnamed column going to
+    /// `default_modality`. Same duplicate-id policy, dedup mode and report
+    /// as `import` over the batch's rows. Does not save.
+    pub fn add_record_batch(&self, batch: &RecordBatch, default_modality: &str) -> anyhow::Result<ImportReport> {
+        self.writable()?;
+        let schema = batch.schema();
+        let (mut vectors, mut others) = (Vec::new(), Vec::new());
+        for (i, field) in schema.fields().iter().enumerate() {
+            match vector_modality(field.name(), default_modality) {
+                Some(m) => vectors.push((m, batch.column(i).as_ref())),
+                None => others.push(i),
+            }
+        }
+        let rows = from_batch(&batch.project(&others)?, default_modality)?;
+        let ids: Vec<u64> = rows.iter().map(|r| r.id).collect();
+        let columns: Option<Vec<_>> = vectors.iter()
+            .map(|&(m, col)| flat(col).map(|(values, dim)| (m, values, dim)))
+            .collect();
+        let distinct = vectors.iter().enumerate().all(|(i, (m, _))| vectors[..i].iter().all(|(n, _)| n != m));
+        let whole = self.dedup().0 == Dedup::Off && distinct && rows.iter().all(|r| r.vectors.is_empty())
+            && self.admit_all(&ids)?.iter().all(|&k| k);
+        let columns = match columns {
+            Some(columns) if whole => columns,
+            _ => {
+                let (skipped, deduplicated) = import::insert_batch(self, &from_batch(batch, default_modality)?)?;
+                return Ok(ImportReport {
+                    records: rows.len() - skipped - deduplicated, skipped, deduplicated, batches: 1,
+                });
+            }
+        };
+        let metas: Vec<Metadata> = rows.iter().map(|r| r.metadata.clone()).collect();
+        for (modality, values, dim) in columns {
+            let modality = self.mname(Some(modality)).expect("named");
+            let (values, dim) = self.project_flat(&modality, values, dim);
+            self.write_flat(&ids, &values, dim, &metas, &modality)?;
+        }
+        for r in &rows {
+            if vectors.is_empty() {
+                self.put_metadata(r.id, &r.metadata)?;
+            }
+            for (name, v) in &r.sparse {
+                self.set_sparse(r.id, name, v)?;
+            }
+        }
+        Ok(ImportReport { records: rows.len(), batches: 1, ..ImportReport::default() })
+    }
+
+    // `values` (rows of `dim`) through the projection of `modality`
+    // (internal name) and normalized, where the file does either, with the
+    // dimension they come out at.
+    fn project_flat<'a>(&self, modality: &str, values: Cow<'a, [f32]>, dim: usize) -> (Cow<'a, [f32]>, usize) {
+        if !self.handle.normalize.get() && !self.handle.projections.borrow().contains_key(modality) {
+            return (values, dim);
+        }
+        let mut out = Vec::with_capacity(values.len());
+        let mut out_dim = dim;
+        for v in values.chunks_exact(dim) {
+            let v = self.project(Some(modality), v);
+            out_dim = v.len();
+            out.extend_from_slice(&v);
+        }
+        (Cow::Owned(out), out_dim)
+    }
+
+    /// The records `ids` name, in that order, as one batch in the
+    /// `feather export` layout. Ids with no record are left out.
+    pub fn records_batch(&self, ids: &[u64]) -> anyhow::Result<RecordBatch> {
+        let rows: Vec<Record> = ids.iter().filter_map(|&id| self.record(id)).collect();
+        let modalities = self.sorted_modalities();
+        to_batch(&schema(&modalities), &modalities, &rows)
+    }
+
+    /// Search hits as `records_batch` does their records, with a `score`
+    /// column after `id`.
+    pub fn hits_batch(&self, hits: &[(u64, f32)]) -> anyhow::Result<RecordBatch> {
+        let (rows, scores): (Vec<Record>, Vec<f32>) = hits.iter()
+            .filter_map(|&(id, score)| Some((self.record(id)?, score)))
+            .unzip();
+        let modalities = self.sorted_modalities();
+        let batch = to_batch(&schema(&modalities), &modalities, &rows)?;
+        let mut fields: Vec<FieldRef> = batch.schema().fields().iter().cloned().collect();
+        fields.insert(1, Arc::new(Field::new("score", DataType::Float32, false)));
+        let mut columns = batch.columns().to_vec();
+        columns.insert(1, Arc::new(Float32Array::from(scores)) as ArrayRef);
+        Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)?)
+    }
+
+    fn sorted_modalities(&self) -> Vec<String> {
+        let mut modalities = self.modalities();
+        modalities.sort();
+        modalities
+    }
+}
+
+/// Write `batch` to `out` as an Arrow IPC file.
+pub fn write_ipc(out: impl Write, batch: &RecordBatch) -> anyhow::Result<()> {
+    let mut writer = arrow::ipc::writer::FileWriter::try_new(out, &batch.schema())?;
+    writer.write(batch)?;
+    writer.finish()?;
+    Ok(())
+}
+
+// The modality a column of this name holds vectors of, if it does.
+fn vector_modality<'a>(column: &'a str, default_modality: &'a str) -> Option<&'a str> {
+    if VECTOR_KEYS.contains(&column) { return Some(default_modality); }
+    column.strip_prefix("vector_")
+}
+
+// A vector column's values end to end, and their dimension, if every row
+// holds a vector of one nonzero length: float32 values as Arrow holds them,
+// float64 ones narrowed.
+fn flat(col: &dyn Array) -> Option<(Cow<'_, [f32]>, usize)> {
+    if col.is_empty() || col.null_count() > 0 { return None; }
+    let (values, start, dim) = match col.data_type() {
+        DataType::FixedSizeList(_, dim) => {
+            let list = col.as_any().downcast_ref::<FixedSizeListArray>()?;
+            (list.values(), list.value_offset(0) as usize, *dim as usize)
+        }
+        DataType::List(_) => {
+            let list = col.as_any().downcast_ref::<ListArray>()?;
+            let offsets = list.value_offsets();
+            let dim = (offsets[1] - offsets[0]) as usize;
+            if offsets.windows(2).any(|w| (w[1] - w[0]) as usize != dim) { return None; }
+            (list.values(), offsets[0] as usize, dim)
+        }
+        _ => return None,
+    };
+    let end = start + col.len() * dim;
+    if dim == 0 || values.null_count() > 0 || values.len() < end { return None; }
+    match values.data_type() {
+        DataType::Float32 => {
+            let values = values.as_any().downcast_ref::<Float32Array>()?;
+            Some((Cow::Borrowed(&values.values()[start..end]), dim))
+        }
+        DataType::Float64 => {
+            let values = values.as_any().downcast_ref::<Float64Array>()?;
+            Some((Cow::Owned(values.values()[start..end].iter().map(|&x| x as f32).collect()), dim))
+        }
+        _ => None,
+    }
+}
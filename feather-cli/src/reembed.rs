@@ -0,0 +1,141 @@
+//! Moving a store to another embedding model (`reembed`, `feather
+//! reembed`).
+//!
+//! Vectors from one model cannot be searched with queries from another, so
+//! changing models means embedding every record again. `reembed` reads the
+//! store record by record, in every collection, embeds each record's
+//! `content` with the new model a batch at a time, and writes a new store
+//! under the same ids, with the same metadata and links. The embedding
+//! replaces the record's vector in one modality (`text` unless asked);
+//! its other vectors and its sparse vectors are copied as they are.
+//!
+//! A record without content has nothing to embed, so it is left out and
+//! reported, and links to it are dropped with it. The new store keeps the
+//! old one's metric, collections, context types, secondary indexes, budget,
+//! scoring, decay and id counters, and records the new model if named (see
+//! `model`); not its projections, query statistics, tuned search effort,
+//! snapshots or audit log, which belong to the old vectors or the old file.
+//! The old store is only read.
+
+use crate::embed::EmbeddingProvider;
+use crate::progress::Reporter;
+use crate::*;
+use std::path::Path;
+
+/// Records embedded per call to the provider.
+pub const DEFAULT_BATCH: usize = 64;
+
+// Settings of the whole file carried over as they are.
+const KEPT: [&str; 4] = [context_type::PROPERTY_KEY, index::PROPERTY_KEY, budget::PROPERTY_KEY, scoring::PROPERTY_KEY];
+
+// Settings of each collection carried over, under `<key>::<collection>` in
+// a named one.
+const KEPT_PER_COLLECTION: [&str; 2] = [autoid::PROPERTY_KEY, decay::PROPERTY_KEY];
+
+/// A record `reembed` left out for having no content.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct Unmigrated {
+    /// None for the default collection.
+    pub collection: Option<String>,
+    pub id: u64,
+}
+
+/// What `reembed` wrote.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize)]
+pub struct ReembedReport {
+    /// Records written to the new store, each embedded anew.
+    pub records: usize,
+    /// Dimension of the new embeddings.
+    pub dim: usize,
+    /// Links dropped for pointing at a record left out.
+    pub links_dropped: usize,
+    /// Records left out for having no content, by collection and id.
+    pub unmigrated: Vec<Unmigrated>,
+}
+
+/// Write to `out`, which must not exist yet, a copy of `db` whose
+/// `modality` vectors are `embedder`'s embeddings of each record's content,
+/// `batch` records per call (see the module docs). `model` names the new
+/// model for the copy to record. `progress` is told the records read so far
+/// after each batch. A copy that fails is removed.
+pub fn reembed(db: &DB, embedder: &dyn EmbeddingProvider, out: &Path, modality: &str, model: Option<&str>,
+               batch: usize, progress: Option<&mut ProgressFn>) -> anyhow::Result<ReembedReport> {
+    anyhow::ensure!(!out.exists(), "{:?} exists already; re-embed into a new path", out);
+    let mut options = OpenOptions::new().create_new(true).metric(db.metric());
+    if let Some(model) = model { options = options.model(model); }
+    let copy = options.open(out)?;
+    let written = write(db, &copy, embedder, modality, batch, progress)
+        .and_then(|report| copy.save().map(|()| report));
+    if written.is_err() {
+        drop(copy);
+        for leftover in [out.to_path_buf(), crate::lock::lock_path(out), suffixed(out, ".wal")] {
+            let _ = std::fs::remove_file(leftover);
+        }
+    }
+    written
+}
+
+fn write(db: &DB, copy: &DB, embedder: &dyn EmbeddingProvider, modality: &str, batch: usize,
+         progress: Option<&mut ProgressFn>) -> anyhow::Result<ReembedReport> {
+    let root = || DB { ptr: db.handle.ptr, handle: Rc::clone(&db.handle), scope: None };
+    for key in KEPT {
+        if let Some(value) = db.property(key) { copy.set_property(key, &value)?; }
+    }
+    let mut scopes = vec![(None, root(), DB { ptr: copy.ptr, handle: Rc::clone(&copy.handle), scope: None })];
+    for name in db.collections() {
+        scopes.push((Some(name.clone()), root().collection(&name)?, copy.collection(&name)?));
+    }
+    let total = scopes.iter().map(|(_, from, _)| from.all_ids().len()).sum();
+    let mut reporter = Reporter::new("reembed", progress);
+    let mut report = ReembedReport::default();
+    let mut done = 0;
+    for (name, from, to) in &scopes {
+        for key in KEPT_PER_COLLECTION {
+            let key = match name {
+                Some(name) => format!("{}{}{}", key, collection::MODALITY_SEP, name),
+                None => key.to_string(),
+            };
+            if let Some(value) = db.property(&key) { copy.set_property(&key, &value)?; }
+        }
+        let mut ids = from.all_ids();
+        ids.sort_unstable();
+        let left_out: HashSet<u64> = ids.iter().copied()
+            .filter(|&id| from.get_metadata(id).is_some_and(|m| !m.is_forgotten() && m.content.trim().is_empty()))
+            .collect();
+        report.unmigrated.extend(ids.iter().filter(|id| left_out.contains(id))
+            .map(|&id| Unmigrated { collection: name.clone(), id }));
+        for chunk in ids.chunks(batch.max(1)) {
+            let mut records: Vec<Record> = chunk.iter()
+                .filter(|id| !left_out.contains(id))
+                .filter_map(|&id| from.record(id))
+                .filter(|r| !r.metadata.is_forgotten())
+                .collect();
+            if !records.is_empty() {
+                let texts: Vec<&str> = records.iter().map(|r| r.metadata.content.as_str()).collect();
+                let vectors = embedder.embed(&texts)?;
+                anyhow::ensure!(vectors.len() == records.len(), "the embedder returned {} vectors for {} texts",
+                                vectors.len(), records.len());
+                for (record, vector) in records.iter_mut().zip(vectors) {
+                    report.dim = vector.len();
+                    record.vectors.insert(modality.to_string(), vector);
+                    let before = record.metadata.edges.len();
+                    record.metadata.edges.retain(|e| !left_out.contains(&e.target));
+                    report.links_dropped += before - record.metadata.edges.len();
+                }
+                import::insert_batch(to, &records).map_err(|e| {
+                    anyhow::anyhow!("records {}..={}: {}", records[0].id, records[records.len() - 1].id, e)
+                })?;
+                report.records += records.len();
+            }
+            done += chunk.len();
+            reporter.report(done, Some(total));
+        }
+    }
+    Ok(report)
+}
+
+fn suffixed(path: &Path, suffix: &str) -> std::path::PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    name.into()
+}
@@ -0,0 +1,190 @@
+//! `feather repl`: one open store, many commands.
+//!
+//! Every other `feather` command opens and loads the file anew; the REPL
+//! keeps it open between commands, one per line:
+//!
+//! ```text
+//! add <id> <vector> [content...]    vector: 0.1,0.2,... or a vector file
+//! add <id> <text...>                with an embedder (--embed-model): embed the text
+//! search [-k N] <vector | text...>  text: embedded, else keywords (BM25)
+//! get <id>
+//! link <from> <to> [type] [weight]
+//! forget <id>
+//! stats
+//! save
+//! help
+//! quit
+//! ```
+//!
+//! Words may be double-quoted. A failing command prints its error and the
+//! session goes on. Writes reach the WAL at once; the file is checkpointed
+//! on `save` and when the session ends, Ctrl-C included.
+
+use crate::{graph, shutdown, vectors, Inserted, SearchOptions, DB};
+use std::io::{BufRead, Write};
+
+/// Hits `search` returns without `-k`.
+pub const DEFAULT_K: usize = 5;
+
+const HELP: &str = "\
+add <id> <vector> [content...]    vector: 0.1,0.2,... or a .npy/.npz/.safetensors file
+add <id> <text...>                with --embed-model: embed the text as the vector
+search [-k N] <vector | text...>  text: embedded with --embed-model, else keywords (BM25)
+get <id>                          the record as JSON
+link <from> <to> [type] [weight]
+forget <id>
+stats
+save                              checkpoint the file
+quit";
+
+/// Run commands from `input` until it ends, says `quit` or the process is
+/// asked to stop (see `shutdown`), writing replies to `output`; with
+/// `prompt`, a prompt goes before each line. Checkpoints the file at the
+/// end.
+pub fn run(db: &DB, input: impl BufRead, mut output: impl Write, prompt: bool) -> anyhow::Result<()> {
+    let mut lines = shutdown::lines(input);
+    loop {
+        if prompt {
+            write!(output, "feather> ")?;
+            output.flush()?;
+        }
+        let Some(line) = lines.next().transpose()? else { break };
+        let words = match split(&line) {
+            Ok(words) => words,
+            Err(e) => {
+                writeln!(output, "error: {}", e)?;
+                continue;
+            }
+        };
+        let Some((command, args)) = words.split_first() else { continue };
+        if matches!(command.as_str(), "quit" | "exit") { break; }
+        match execute(db, command, args) {
+            Ok(reply) if reply.is_empty() => {}
+            Ok(reply) => writeln!(output, "{}", reply)?,
+            Err(e) => writeln!(output, "error: {:#}", e)?,
+        }
+    }
+    db.save()?;
+    Ok(())
+}
+
+/// Run one command; returns what to print.
+pub fn execute(db: &DB, command: &str, args: &[String]) -> anyhow::Result<String> {
+    match command {
+        "add" => add(db, args),
+        "search" => search(db, args),
+        "get" => {
+            let [id] = args else { anyhow::bail!("usage: get <id>") };
+            let id = parse_id(id)?;
+            let record = db.record(id).filter(|r| !r.metadata.is_forgotten())
+                .ok_or_else(|| anyhow::anyhow!("no record {}", id))?;
+            Ok(serde_json::to_string(&record)?)
+        }
+        "link" => {
+            let (from, to, rel_type, weight) = match args {
+                [from, to] => (from, to, graph::DEFAULT_REL_TYPE, "1"),
+                [from, to, rel_type] => (from, to, rel_type.as_str(), "1"),
+                [from, to, rel_type, weight] => (from, to, rel_type.as_str(), weight.as_str()),
+                _ => anyhow::bail!("usage: link <from> <to> [type] [weight]"),
+            };
+            let (from, to) = (parse_id(from)?, parse_id(to)?);
+            let weight: f32 = weight.parse().map_err(|_| anyhow::anyhow!("bad weight `{}`", weight))?;
+            db.link_with(from, to, rel_type, weight)?;
+            Ok(format!("Linked {} -> {} ({}, weight {})", from, to, rel_type, weight))
+        }
+        "forget" => {
+            let [id] = args else { anyhow::bail!("usage: forget <id>") };
+            let id = parse_id(id)?;
+            anyhow::ensure!(db.get_metadata(id).is_some_and(|m| !m.is_forgotten()), "no record {}", id);
+            db.forget(id)?;
+            Ok(format!("Forgot ID {}", id))
+        }
+        "stats" => {
+            let mut lines = vec![format!("Records:  {}", db.all_ids().len())];
+            let mut modalities = db.modalities();
+            modalities.sort();
+            for modality in &modalities {
+                lines.push(format!("Modality '{}': {} vectors, dim {}", modality, db.ids(modality).len(), db.dim(modality)));
+            }
+            Ok(lines.join("\n"))
+        }
+        "save" => {
+            db.save()?;
+            Ok("Saved".to_string())
+        }
+        "help" => Ok(HELP.to_string()),
+        _ => anyhow::bail!("unknown command `{}`; try `help`", command),
+    }
+}
+
+fn add(db: &DB, args: &[String]) -> anyhow::Result<String> {
+    let [id, first, rest @ ..] = args else { anyhow::bail!("usage: add <id> <vector> [content...]") };
+    let id = parse_id(id)?;
+    let (vector, content) = match vector(first)? {
+        Some(vector) => (vector, rest.join(" ")),
+        None => {
+            anyhow::ensure!(db.embedder().is_some(), "`{}` is not a vector; set an embedder to add text", first);
+            let text = args[1..].join(" ");
+            (db.embed(&[&text])?.remove(0), text)
+        }
+    };
+    Ok(match db.insert(id, &vector).content(&content).execute()? {
+        Inserted::Written => format!("Added ID {}", id),
+        Inserted::Kept => format!("ID {} already exists; left unchanged", id),
+        Inserted::DuplicateOf(existing) => format!("Same as ID {}; nothing added", existing),
+    })
+}
+
+fn search(db: &DB, args: &[String]) -> anyhow::Result<String> {
+    let (k, query) = match args {
+        [flag, k, query @ ..] if flag == "-k" => (k.parse().map_err(|_| anyhow::anyhow!("bad k `{}`", k))?, query),
+        query => (DEFAULT_K, query),
+    };
+    let [first, ..] = query else { anyhow::bail!("usage: search [-k N] <vector | text...>") };
+    let hits = match vector(first)? {
+        Some(vector) => db.search_with_options(&vector, k, "text", &SearchOptions::default())?,
+        None if db.embedder().is_some() => db.search_text(&query.join(" "), k, "text", &SearchOptions::default())?,
+        None => db.keyword_search(&query.join(" "), k)?,
+    };
+    let lines: Vec<String> = hits.into_iter()
+        .map(|(id, score)| {
+            let content = db.get_metadata(id).map(|m| m.content).unwrap_or_default();
+            format!("ID: {}  Score: {:.4}  {}", id, score, content)
+        })
+        .collect();
+    Ok(if lines.is_empty() { "No hits".to_string() } else { lines.join("\n") })
+}
+
+fn parse_id(word: &str) -> anyhow::Result<u64> {
+    word.parse().map_err(|_| anyhow::anyhow!("bad id `{}`", word))
+}
+
+// `word` as a vector: comma-separated numbers (a trailing comma for just
+// one), or a vector file; None if it is neither (so it is text).
+fn vector(word: &str) -> anyhow::Result<Option<Vec<f32>>> {
+    if vectors::is_file(word) {
+        return vectors::read_vector(std::path::Path::new(word)).map(Some);
+    }
+    if !word.contains(',') { return Ok(None); }
+    Ok(word.strip_suffix(',').unwrap_or(word).split(',').map(|x| x.trim().parse::<f32>().ok()).collect())
+}
+
+// Split a line into words at whitespace, keeping "double-quoted" runs whole.
+fn split(line: &str) -> anyhow::Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                word.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    anyhow::ensure!(!quoted, "unclosed quote");
+    words.extend(word);
+    Ok(words)
+}
@@ -0,0 +1,405 @@
+//! Streaming replication: one writer, `feather serve --replicate-to`, ships
+//! its WAL to read replicas, `feather serve --follow`, which keep a local
+//! copy of the store and answer reads from it.
+//!
+//! The primary connects to each replica and sends it a snapshot — the
+//! whole file, just checkpointed — then the WAL entries of every write as
+//! it is made. A replica that drops off is reconnected, at most every
+//! `RECONNECT_EVERY`, and sent a fresh snapshot. A replica logs what it
+//! applies to its own WAL, so a restart resumes from where it was, and
+//! refuses writes. Header properties (projections, context types, field
+//! indexes) only travel with snapshots.
+//!
+//! A replica installs whatever snapshot a primary sends, so primaries prove
+//! a shared secret first: the keys of an API key file (see `Secret`), the
+//! same file on both sides. The secret crosses the wire as it is, like the
+//! store after it, so keep replication on a trusted network or a tunnel.
+//!
+//! On the wire, a primary greets with `MAGIC`, a little-endian u16 length
+//! and that many bytes of key; the replica answers `ACCEPTED`, or hangs up.
+//! Then a frame is a kind byte (`b'S'` snapshot, `b'W'` WAL entries), a
+//! little-endian u64 length, at most `MAX_FRAME`, and that many bytes.
+//! Connections and drops log under the `feather::replicate` target (see
+//! `trace`).
+
+use crate::limits::Gate;
+use crate::lock::{FileLock, LockMode};
+use crate::metrics::Metrics;
+use crate::serve::{self, Response};
+use crate::shutdown;
+use crate::trace::{Level, Span};
+use crate::{access, Limits, Tokens, DB};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+extern "C" {
+    fn feather_apply_wal(db: *mut std::ffi::c_void, data: *const std::ffi::c_char, len: usize) -> i32;
+}
+
+/// Greeting a primary opens each connection with.
+pub const MAGIC: &[u8; 8] = b"FTHRREP1";
+
+/// A replica's answer to a primary that proved the secret.
+pub const ACCEPTED: u8 = b'+';
+
+/// Longest frame a replica takes; a snapshot is the whole file.
+pub const MAX_FRAME: u64 = 16 << 30;
+
+/// Least time between two attempts to reach a replica.
+pub const RECONNECT_EVERY: Duration = Duration::from_secs(5);
+
+const SNAPSHOT: u8 = b'S';
+const WAL: u8 = b'W';
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+// A primary that takes longer than this to greet and prove the secret is
+// hung up on.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// A replica that takes longer than this to accept a frame is dropped.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// How long a replica waits for a frame before looking for requests again.
+const POLL: Duration = Duration::from_millis(10);
+
+/// What a primary and its replicas share: the keys of an API key file (see
+/// `Tokens::load_api_keys`). A primary presents the first; a replica takes
+/// any.
+#[derive(Clone, Debug)]
+pub struct Secret {
+    keys: Vec<String>,
+}
+
+impl Secret {
+    /// The keys of the API key file at `path`.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        Ok(Secret { keys: access::read_api_keys(path)? })
+    }
+
+    fn key(&self) -> &str { &self.keys[0] }
+
+    // Whether `key` is one of the keys, compared in constant time.
+    fn accepts(&self, key: &[u8]) -> bool {
+        self.keys.iter().fold(false, |found, k| {
+            let same = k.len() == key.len() && k.bytes().zip(key).fold(0, |d, (a, b)| d | (a ^ b)) == 0;
+            found | same
+        })
+    }
+}
+
+/// The primary's side: the replicas it streams to, and how much of its WAL
+/// they have.
+pub struct Primary {
+    file: PathBuf,
+    secret: Secret,
+    wal: PathBuf,
+    // bytes of the WAL sent to every connected replica
+    shipped: u64,
+    replicas: Vec<Replica>,
+}
+
+struct Replica {
+    addr: String,
+    stream: Option<TcpStream>,
+    tried: Option<Instant>,
+}
+
+impl Replica {
+    // Send one frame, dropping the connection if that fails.
+    fn send(&mut self, kind: u8, payload: &[u8]) {
+        let Some(stream) = &mut self.stream else { return };
+        let sent = Ok(())
+            .and_then(|_| match payload.len() as u64 <= MAX_FRAME {
+                true => Ok(()),
+                false => Err(std::io::Error::other(format!("a frame of {} bytes is over the limit", payload.len()))),
+            })
+            .and_then(|_| stream.write_all(&[kind]))
+            .and_then(|_| stream.write_all(&(payload.len() as u64).to_le_bytes()))
+            .and_then(|_| stream.write_all(payload));
+        if let Err(e) = sent {
+            Span::new(Level::Warn, "feather::replicate").record_str("replica", &self.addr)
+                .record_str("dropped", &e.to_string());
+            // worth one try at once: a restarted replica is likely back
+            self.stream = None;
+            self.tried = None;
+        }
+    }
+
+    fn connect(&mut self, secret: &Secret) -> std::io::Result<()> {
+        self.tried = Some(Instant::now());
+        let addr = self.addr.to_socket_addrs()?.next()
+            .ok_or_else(|| std::io::Error::other(format!("{} resolves to no address", self.addr)))?;
+        let mut stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let key = secret.key().as_bytes();
+        stream.write_all(MAGIC)?;
+        stream.write_all(&(key.len() as u16).to_le_bytes())?;
+        stream.write_all(key)?;
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        let mut answer = [0];
+        if stream.read_exact(&mut answer).is_err() || answer[0] != ACCEPTED {
+            return Err(std::io::Error::other("the replica refused the secret"));
+        }
+        self.stream = Some(stream);
+        Ok(())
+    }
+}
+
+impl Primary {
+    /// Replicate `db`, a store backed by a file, to the replicas listening
+    /// at `addrs`, which must share `secret`. Replicas that are not up yet
+    /// are no failure: they are tried again as requests come.
+    pub fn new(db: &DB, addrs: &[String], secret: &Secret) -> anyhow::Result<Self> {
+        db.writable()?;
+        anyhow::ensure!(db.handle.fork.is_none(), "a fork cannot be replicated");
+        db.unsharded("replicated")?;
+        let file = PathBuf::from(db.handle.path()
+            .ok_or_else(|| anyhow::anyhow!("only a store backed by a file can be replicated"))?);
+        let mut wal = file.clone().into_os_string();
+        wal.push(".wal");
+        let replicas = addrs.iter().map(|addr| Replica { addr: addr.clone(), stream: None, tried: None }).collect();
+        let mut primary = Primary { file, secret: secret.clone(), wal: wal.into(), shipped: 0, replicas };
+        primary.connect(db)?;
+        Ok(primary)
+    }
+
+    /// Addresses of the replicas connected now.
+    pub fn connected(&self) -> Vec<&str> {
+        self.replicas.iter().filter(|r| r.stream.is_some()).map(|r| r.addr.as_str()).collect()
+    }
+
+    /// Send the replicas what the WAL logged since the last call, and
+    /// reconnect those that dropped off.
+    pub fn ship(&mut self, db: &DB) -> anyhow::Result<()> {
+        self.flush()?;
+        self.connect(db)
+    }
+
+    /// `ship`, checkpointing the file (which empties the WAL) in between.
+    pub fn checkpoint(&mut self, db: &DB) -> anyhow::Result<()> {
+        self.flush()?;
+        db.save()?;
+        self.shipped = 0;
+        self.connect(db)
+    }
+
+    // Send the connected replicas the WAL past `shipped`.
+    fn flush(&mut self) -> anyhow::Result<()> {
+        let len = match std::fs::metadata(&self.wal) {
+            Ok(meta) => meta.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(anyhow::anyhow!("cannot read {:?}: {}", self.wal, e)),
+        };
+        if len < self.shipped {
+            // checkpointed behind our back: start the replicas over
+            for replica in &mut self.replicas { replica.stream = None; replica.tried = None; }
+            self.shipped = 0;
+            return Ok(());
+        }
+        if len == self.shipped { return Ok(()); }
+        let mut file = File::open(&self.wal)?;
+        file.seek(SeekFrom::Start(self.shipped))?;
+        let mut entries = Vec::new();
+        file.read_to_end(&mut entries)?;
+        for replica in &mut self.replicas {
+            replica.send(WAL, &entries);
+        }
+        self.shipped += entries.len() as u64;
+        Ok(())
+    }
+
+    // Connect to the replicas that are not, as often as `RECONNECT_EVERY`
+    // allows, and send those that answer a snapshot.
+    fn connect(&mut self, db: &DB) -> anyhow::Result<()> {
+        let mut fresh = Vec::new();
+        for (i, replica) in self.replicas.iter_mut().enumerate() {
+            if replica.stream.is_some() || replica.tried.is_some_and(|t| t.elapsed() < RECONNECT_EVERY) { continue; }
+            let mut span = Span::new(Level::Info, "feather::replicate");
+            span.record_str("replica", &replica.addr);
+            match replica.connect(&self.secret) {
+                Ok(()) => fresh.push(i),
+                Err(e) => { span.record_str("unreachable", &e.to_string()); }
+            }
+        }
+        if fresh.is_empty() { return Ok(()); }
+        // the others have the WAL so far, which the checkpoint empties
+        db.save()?;
+        self.shipped = 0;
+        let snapshot = std::fs::read(&self.file)
+            .map_err(|e| anyhow::anyhow!("cannot read {:?}: {}", self.file, e))?;
+        for i in fresh {
+            self.replicas[i].send(SNAPSHOT, &snapshot);
+        }
+        Ok(())
+    }
+}
+
+enum Frame {
+    Snapshot(Vec<u8>),
+    Wal(Vec<u8>),
+}
+
+/// Keep the store at `path` a read replica of the primary that connects to
+/// `replication` and proves `secret`, answering requests on `http` as
+/// `serve` does, with `tokens` if given, within `limits`; writes fail with
+/// `ReadOnly`. Every request sees the writes received before it. A replica
+/// with no file yet answers 503 until its first snapshot. Returns, saved,
+/// once the process is asked to stop (see `shutdown`).
+pub fn follow(path: &Path, replication: TcpListener, http: &TcpListener, secret: &Secret, tokens: Option<&Tokens>,
+              limits: &Limits) -> anyhow::Result<()> {
+    let _lock = FileLock::acquire(path, LockMode::Exclusive)?;
+    let mut db = if path.is_file() { Some(open_replica(path)?) } else { None };
+    let (frames, received) = mpsc::channel();
+    let secret = secret.clone();
+    std::thread::spawn(move || receive(replication, &secret, frames));
+    http.set_nonblocking(true)?;
+    let mut metrics = Metrics::default();
+    let gate = Gate::new(tokens.cloned(), limits.clone());
+    let mut applied = 0;
+    loop {
+        if shutdown::requested() {
+            if let Some(db) = &db { unsafe { crate::feather_save(db.ptr) }; }
+            return Ok(());
+        }
+        match http.accept() {
+            Ok((mut stream, _)) => {
+                stream.set_nonblocking(false)?;
+                stream.set_read_timeout(Some(serve::READ_TIMEOUT))?;
+                applied += catch_up(path, &mut db, &received, None)?;
+                match &db {
+                    Some(db) => { serve::answer_one(db, &mut stream, &gate, &mut metrics, limits.search_budget); }
+                    None => {
+                        let refusal = Response::error(503, "waiting for a snapshot from the primary");
+                        if let Some(request) = serve::read_request(&mut stream) {
+                            serve::reply(&mut stream, &serve::health(&request).unwrap_or(refusal));
+                        }
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => match received.recv_timeout(POLL) {
+                Ok(frame) => applied += catch_up(path, &mut db, &received, Some(frame))?,
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => anyhow::bail!("replication listener failed"),
+            },
+            Err(e) => return Err(e.into()),
+        }
+        if applied >= serve::CHECKPOINT_EVERY {
+            if let Some(db) = &db { unsafe { crate::feather_save(db.ptr) }; }
+            applied = 0;
+        }
+    }
+}
+
+// Open the replica's file: read-only to its users, though the replication
+// stream still writes to it.
+fn open_replica(path: &Path) -> anyhow::Result<DB> {
+    let db = DB::open_unlocked(path, 0)?;
+    db.handle.read_only.set(true);
+    Ok(db)
+}
+
+// Apply `first` and every frame waiting after it, the WAL entries in one go.
+// Returns the number of WAL frames applied.
+fn catch_up(path: &Path, db: &mut Option<DB>, received: &Receiver<Frame>, first: Option<Frame>) -> anyhow::Result<usize> {
+    let mut entries = Vec::new();
+    let mut count = 0;
+    for frame in first.into_iter().chain(received.try_iter()) {
+        match frame {
+            Frame::Snapshot(file) => {
+                entries.clear();
+                count = 0;
+                install(path, db, &file)?;
+            }
+            // entries before the first snapshot belong to no known state
+            Frame::Wal(_) if db.is_none() => {}
+            Frame::Wal(bytes) => {
+                entries.extend_from_slice(&bytes);
+                count += 1;
+            }
+        }
+    }
+    if let (Some(db), false) = (db.as_ref(), entries.is_empty()) {
+        if unsafe { feather_apply_wal(db.ptr, entries.as_ptr().cast(), entries.len()) } != 0 {
+            return Err(crate::last_error());
+        }
+    }
+    Ok(count)
+}
+
+// Replace the replica's file with `snapshot` and reopen it.
+fn install(path: &Path, db: &mut Option<DB>, snapshot: &[u8]) -> anyhow::Result<()> {
+    let mut span = Span::new(Level::Info, "feather::replicate");
+    span.record("snapshot_bytes", snapshot.len());
+    if let Some(old) = db.take() {
+        // superseded: closing it must not write it back
+        unsafe { crate::feather_detach(old.ptr) };
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".snapshot");
+    let tmp = PathBuf::from(tmp);
+    let mut file = File::create(&tmp)?;
+    file.write_all(snapshot)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    let mut wal = path.as_os_str().to_owned();
+    wal.push(".wal");
+    match std::fs::remove_file(PathBuf::from(wal)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    *db = Some(open_replica(path)?);
+    Ok(())
+}
+
+// Accept primaries one after another, passing on the frames of those that
+// prove `secret`.
+fn receive(listener: TcpListener, secret: &Secret, frames: Sender<Frame>) {
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else { continue };
+        if !handshake(&mut stream, secret) {
+            Span::new(Level::Warn, "feather::replicate")
+                .record_str("refused", &stream.peer_addr().map_or_else(|_| "?".to_string(), |a| a.to_string()));
+            continue;
+        }
+        let mut reader = BufReader::new(stream);
+        while let Some(frame) = read_frame(&mut reader) {
+            if frames.send(frame).is_err() { return; }
+        }
+    }
+}
+
+// Read a primary's greeting and key, and accept it if the key is one of
+// `secret`'s.
+fn handshake(stream: &mut TcpStream, secret: &Secret) -> bool {
+    let mut greeting = [0; MAGIC.len() + 2];
+    let greeted = stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)).is_ok()
+        && stream.read_exact(&mut greeting).is_ok()
+        && greeting[..MAGIC.len()] == *MAGIC;
+    if !greeted { return false; }
+    let mut key = vec![0; u16::from_le_bytes([greeting[MAGIC.len()], greeting[MAGIC.len() + 1]]) as usize];
+    stream.read_exact(&mut key).is_ok()
+        && secret.accepts(&key)
+        && stream.write_all(&[ACCEPTED]).is_ok()
+        // frames come only as the primary writes, however far apart
+        && stream.set_read_timeout(None).is_ok()
+}
+
+fn read_frame(reader: &mut impl Read) -> Option<Frame> {
+    let mut head = [0; 9];
+    reader.read_exact(&mut head).ok()?;
+    let len = u64::from_le_bytes(head[1..].try_into().expect("8 bytes"));
+    if len > MAX_FRAME { return None; }
+    let mut payload = Vec::new();
+    reader.take(len).read_to_end(&mut payload).ok()?;
+    if payload.len() as u64 != len { return None; }
+    match head[0] {
+        SNAPSHOT => Some(Frame::Snapshot(payload)),
+        WAL => Some(Frame::Wal(payload)),
+        _ => None,
+    }
+}
@@ -0,0 +1,73 @@
+//! A re-ranking hook for search (`SearchOptions::reranker`).
+//!
+//! A `Reranker` sees the best candidates a search found — `CANDIDATE_FACTOR`
+//! times as many as it returns, scored and filtered as usual, with their
+//! metadata — and puts them in its own order before the k hits are picked.
+//! A cross-encoder scoring content against the query text, or business
+//! rules such as pinning a source, can raise precision that way without
+//! touching the scoring code; a closure will do:
+//!
+//! ```ignore
+//! let options = SearchOptions::default().reranker(|query: &Query, mut hits: Vec<Candidate>| {
+//!     let text = query.text.unwrap_or_default();
+//!     for hit in &mut hits { hit.score = model.score(text, &hit.metadata.content)?; }
+//!     hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+//!     Ok(hits)
+//! });
+//! let hits = db.search_text("why did deploys fail?", 5, "text", &options)?;
+//! ```
+//!
+//! A reranker may drop candidates but not add any. MMR, paging and
+//! `linked_modalities` then work on its order and scores.
+
+use crate::Metadata;
+use std::fmt;
+
+/// A search hit offered to a `Reranker`.
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    pub id: u64,
+    /// The search's score for it: relevance × recency, plus activation.
+    pub score: f32,
+    pub metadata: Metadata,
+}
+
+/// What a search was asked.
+#[derive(Clone, Copy, Debug)]
+pub struct Query<'a> {
+    /// The query vector, as given.
+    pub vector: &'a [f32],
+    /// The query text: what `search_text` embedded, else the keywords of
+    /// `SearchOptions::text`.
+    pub text: Option<&'a str>,
+}
+
+/// Re-orders a search's candidates.
+pub trait Reranker {
+    /// `candidates`, best first by the search, in the order (and with the
+    /// scores) the hits should have; a candidate left out is dropped.
+    fn rerank(&self, query: &Query, candidates: Vec<Candidate>) -> anyhow::Result<Vec<Candidate>>;
+}
+
+impl<F> Reranker for F
+where
+    F: Fn(&Query, Vec<Candidate>) -> anyhow::Result<Vec<Candidate>>,
+{
+    fn rerank(&self, query: &Query, candidates: Vec<Candidate>) -> anyhow::Result<Vec<Candidate>> {
+        self(query, candidates)
+    }
+}
+
+// So that `SearchOptions` keeps its derives: rerankers are equal only if
+// they are the same one.
+impl fmt::Debug for dyn Reranker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Reranker")
+    }
+}
+
+impl PartialEq for dyn Reranker {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(self, other)
+    }
+}
@@ -0,0 +1,87 @@
+//! Paging through every record in id order (`DB::scan`, `feather scan`),
+//! and browsing them by recency or importance (`DB::list`, `feather list`).
+//!
+//! A scan page ends with a cursor, the last id it holds; the next page
+//! starts after it. Because the cursor is an id rather than a position,
+//! records added or forgotten between two calls neither shift nor repeat
+//! the pages that follow. A listing is paged by offset instead, so it is
+//! for reading, not for walking a store that is being written to.
+
+use crate::{Filter, Metadata, DB};
+use serde::Serialize;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ScanPage {
+    /// Live records, in increasing id order.
+    pub ids: Vec<u64>,
+    /// Pass as `after` for the next page; None on the last one.
+    pub next_cursor: Option<u64>,
+}
+
+impl DB {
+    /// Up to `limit` (at least 1) live records with an id above `after`
+    /// (from the start if None) and metadata matching `filter`, if given.
+    /// Scanned records do not count as recalled.
+    pub fn scan(&self, after: Option<u64>, limit: usize, filter: Option<&Filter>) -> anyhow::Result<ScanPage> {
+        anyhow::ensure!(limit > 0, "a page holds at least one record");
+        let mut ids = self.all_ids();
+        ids.retain(|&id| after.is_none_or(|after| id > after));
+        ids.sort_unstable();
+        let mut page = ScanPage::default();
+        for id in ids {
+            let Some(meta) = self.get_metadata(id).filter(|m| !m.is_forgotten()) else { continue };
+            if filter.is_some_and(|f| !f.matches(&meta)) { continue; }
+            if page.ids.len() == limit {
+                page.next_cursor = page.ids.last().copied();
+                break;
+            }
+            page.ids.push(id);
+        }
+        Ok(page)
+    }
+}
+
+/// Order `DB::list` returns records in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortBy {
+    /// Newest timestamp first.
+    #[default]
+    Recency,
+    /// Most important first, newest first among equals.
+    Importance,
+    /// Most recalled by searches first, most recently recalled first among
+    /// equals.
+    Recalls,
+    /// Increasing id.
+    Id,
+}
+
+impl DB {
+    /// Live records matching `filter`, if given, sorted by `sort`: the
+    /// `limit` of them that follow the first `offset`. Listed records do not
+    /// count as recalled.
+    pub fn list(&self, sort: SortBy, offset: usize, limit: usize,
+                filter: Option<&Filter>) -> Vec<(u64, Metadata)> {
+        let mut records: Vec<(u64, Metadata)> = self.all_ids().into_iter()
+            .filter_map(|id| Some((id, self.get_metadata(id)?)))
+            .filter(|(_, meta)| !meta.is_forgotten() && filter.is_none_or(|f| f.matches(meta)))
+            .collect();
+        match sort {
+            SortBy::Recency => records.sort_by(|(a, x), (b, y)| y.timestamp.cmp(&x.timestamp).then(b.cmp(a))),
+            SortBy::Importance => records.sort_by(|(a, x), (b, y)| {
+                y.importance.total_cmp(&x.importance).then(y.timestamp.cmp(&x.timestamp)).then(b.cmp(a))
+            }),
+            SortBy::Recalls => records.sort_by(|(a, x), (b, y)| {
+                y.recall_count.cmp(&x.recall_count).then(y.last_recalled_at.cmp(&x.last_recalled_at)).then(b.cmp(a))
+            }),
+            SortBy::Id => records.sort_by_key(|&(id, _)| id),
+        }
+        records.into_iter().skip(offset).take(limit).collect()
+    }
+
+    /// The `k` newest live records matching `filter`, if given: `list` by
+    /// recency, for pulling the latest memories without a query vector.
+    pub fn recent(&self, k: usize, filter: Option<&Filter>) -> Vec<(u64, Metadata)> {
+        self.list(SortBy::Recency, 0, k, filter)
+    }
+}
@@ -0,0 +1,125 @@
+//! Composite scoring of search hits (`SearchOptions::scoring`).
+//!
+//! By default `search_with_options` scores a hit by its similarity, scaled
+//! by recency and raised by spreading activation as the options say. A
+//! `ScoringPolicy` replaces that with a weighted mean of five signals, each
+//! within 0..=1 for a typical record:
+//!
+//! - similarity: `1 / (1 + d)`, fused with the keyword and sparse matches
+//!   as hybrid search does;
+//! - importance: the record's stored importance;
+//! - recency: `exp(-age / tau)`, 1 for a record without a timestamp;
+//! - usage: how often searches have returned the record, `n / (n +
+//!   USAGE_HALF)` after n recalls, so memories that keep proving useful
+//!   strengthen over time;
+//! - graph proximity: the activation reaching the record along links from
+//!   the other hits' similarities, over `hops` hops at full strength,
+//!   capped at 1. Records reached only through links join the hits.
+//!
+//! A policy is set per query, or stored in the file as the default for
+//! every search that sets no scoring of its own — no policy, recency
+//! weight or graph boost (`DB::set_scoring_policy`, for the whole file
+//! and all of its collections). Plain `search` keeps the core's ranking.
+//! The text form, used by the property and the CLI, is
+//! `similarity=0.6,importance=0.2,recency=0.2,usage=0,graph=0`.
+
+use crate::DB;
+use std::fmt;
+use std::str::FromStr;
+
+/// Property key holding the file's default policy, in its text form.
+pub(crate) const PROPERTY_KEY: &str = "scoring";
+
+/// Recalls at which the usage signal reaches one half.
+pub const USAGE_HALF: f32 = 10.0;
+
+/// Weights of the signals a hit's score is the weighted mean of.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScoringPolicy {
+    pub similarity: f32,
+    pub importance: f32,
+    pub recency: f32,
+    pub usage: f32,
+    pub graph: f32,
+}
+
+impl Default for ScoringPolicy {
+    /// Similarity alone.
+    fn default() -> Self {
+        ScoringPolicy { similarity: 1.0, importance: 0.0, recency: 0.0, usage: 0.0, graph: 0.0 }
+    }
+}
+
+impl ScoringPolicy {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let weights = [self.similarity, self.importance, self.recency, self.usage, self.graph];
+        anyhow::ensure!(weights.iter().all(|w| w.is_finite() && *w >= 0.0), "scoring weights must not be negative");
+        anyhow::ensure!(weights.iter().sum::<f32>() > 0.0, "scoring weights are all 0");
+        Ok(())
+    }
+
+    /// The weighted mean of the signals.
+    pub fn score(&self, similarity: f32, importance: f32, recency: f32, usage: f32, graph: f32) -> f32 {
+        let total = self.similarity + self.importance + self.recency + self.usage + self.graph;
+        (self.similarity * similarity + self.importance * importance + self.recency * recency
+            + self.usage * usage + self.graph * graph) / total
+    }
+}
+
+/// The usage signal of a record recalled `recall_count` times.
+pub fn usage(recall_count: u32) -> f32 {
+    recall_count as f32 / (recall_count as f32 + USAGE_HALF)
+}
+
+impl fmt::Display for ScoringPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "similarity={},importance={},recency={},usage={},graph={}",
+               self.similarity, self.importance, self.recency, self.usage, self.graph)
+    }
+}
+
+impl FromStr for ScoringPolicy {
+    type Err = String;
+
+    /// `NAME=WEIGHT` pairs separated by commas; a signal left out weighs 0.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut policy = ScoringPolicy { similarity: 0.0, importance: 0.0, recency: 0.0, usage: 0.0, graph: 0.0 };
+        for pair in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (name, weight) = pair.split_once('=').ok_or_else(|| format!("expected NAME=WEIGHT, got `{}`", pair))?;
+            let weight: f32 = weight.trim().parse().map_err(|_| format!("bad weight `{}`", weight))?;
+            let slot = match name.trim() {
+                "similarity" => &mut policy.similarity,
+                "importance" => &mut policy.importance,
+                "recency" => &mut policy.recency,
+                "usage" => &mut policy.usage,
+                "graph" => &mut policy.graph,
+                other => return Err(format!("unknown signal `{}` (expected similarity, importance, recency, usage or graph)", other)),
+            };
+            *slot = weight;
+        }
+        policy.validate().map_err(|e| e.to_string())?;
+        Ok(policy)
+    }
+}
+
+impl DB {
+    /// The file's default scoring policy, if one is set.
+    pub fn scoring_policy(&self) -> Option<ScoringPolicy> {
+        let raw = self.property(PROPERTY_KEY)?;
+        String::from_utf8_lossy(&raw).parse().ok()
+    }
+
+    /// Make `policy` the file's default (see the module docs), or with
+    /// None go back to the options' own scoring; persists on `save()`.
+    pub fn set_scoring_policy(&self, policy: Option<ScoringPolicy>) -> anyhow::Result<()> {
+        self.writable()?;
+        match policy {
+            Some(policy) => {
+                policy.validate()?;
+                self.set_property(PROPERTY_KEY, policy.to_string().as_bytes())?;
+            }
+            None => { self.remove_property(PROPERTY_KEY)?; }
+        }
+        Ok(())
+    }
+}
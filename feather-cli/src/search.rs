@@ -0,0 +1,659 @@
+//! Search options beyond plain vector similarity.
+//!
+//! `DB::search_with_options` ranks candidates by similarity (`1 / (1 + d)`,
+//! as `search` scores them) times an optional recency factor, so recent
+//! context can outrank stale but similar memories. Optionally the ranked
+//! pool is then re-ranked by maximal marginal relevance (MMR), which trades
+//! relevance against similarity to the hits already picked so the top k are
+//! not near-duplicates of each other. A time range restricts candidates to
+//! records stamped within it, inside the index scan rather than afterwards,
+//! so a narrow window still yields up to k hits. A metadata `Filter` is
+//! applied to the candidates in Rust; the pool grows until enough match.
+//! An importance range is applied the same way.
+//! The filter's `source = '…'`, timestamp and exact attribute terms are
+//! also handed to the index scan, where the core's attribute index or an
+//! optional secondary index (see `index`) can answer them directly.
+//! `session` keeps to one session's records the same way, and
+//! `exclude_session` leaves one out (see the `session` module). Archived
+//! records are left out by the core unless `include_archived` is set (see
+//! the `archive` module). `exclude_ids`, `exclude_sources` and
+//! `exclude_types` leave records out after the scan, like the filter, and
+//! so does `access`, to the records a caller may see (see the `access`
+//! module).
+//! With `exact`, the scan measures every record rather than traversing the
+//! approximate vector graph. With `truncate_dim`, for Matryoshka (MRL)
+//! embeddings whose leading components carry most of the meaning, it
+//! measures every record on that many leading components only, a fraction
+//! of the work, then re-measures the best `TRUNCATED_CANDIDATE_FACTOR` per
+//! hit in full, so hits score by the full vectors.
+//!
+//! With a keyword `text`, search is hybrid: the BM25 hits over record
+//! content join the vector candidates, and each candidate's relevance is
+//! `(1 - text_weight) * similarity + text_weight * bm25 / best bm25`, so a
+//! record matching the keywords can surface even when its vector is not
+//! among the nearest. A `sparse` query fuses the same way: its best
+//! dot-product hits join the candidates and `sparse_weight` of relevance goes
+//! to `dot / best dot`, the similarity keeping what the two shares leave.
+//!
+//! With a `graph_boost`, the scored hits then seed spreading activation over
+//! the link graph: each hop passes `graph_boost` times a record's activation,
+//! times the link's weight, on to every record linked to it, either way, for
+//! `hops` hops, and each record's score grows by the activation it receives.
+//! A memory closely associated with good hits is recalled even if it
+//! matches the query poorly itself, or not at all.
+//!
+//! `max_per_source` keeps at most that many hits from any one source, so
+//! the chunks of one document do not crowd out the rest: once the hits are
+//! ranked, a hit is dropped when better ones from its source already fill
+//! the quota, and the pool grows until enough are left. Records without a
+//! source are never dropped, and linked records do not count.
+//!
+//! An `offset` pages through the ranking: the search ranks `offset + k`
+//! hits and returns the last k, ties broken by id so that consecutive pages
+//! neither repeat nor skip a hit while the store is unchanged.
+//!
+//! A `scoring` policy (see `scoring`) replaces the recency factor and
+//! spreading activation with a weighted mean of similarity, importance,
+//! recency and graph proximity; the file can store one as its default.
+//!
+//! A `reranker` (see `rerank`) then gets the best candidates and sets
+//! their final order.
+//!
+//! With `linked_modalities`, each of the k hits is followed by the records
+//! linked to it that have a vector in one of those modalities — the
+//! screenshot attached to a note found by its text — so a memory spread
+//! over several modalities comes back together. They score the hit's score
+//! times the link's weight and do not count against k.
+//!
+//! `DB::explain_search` ranks the same way and breaks each hit's score into
+//! these signals (see `explain`).
+
+use crate::index::Prefilter;
+use crate::metadata::SESSION_ATTRIBUTE;
+use crate::explain::{HitExplanation, Rejection, Trace};
+use crate::rerank::{Candidate, Query, Reranker};
+use crate::scoring::{self, ScoringPolicy};
+use crate::{decay, sparse, Access, ContextType, Device, Filter, SparseVector, DB};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// Candidates fetched per requested hit before re-ranking in Rust.
+pub(crate) const CANDIDATE_FACTOR: usize = 3;
+
+/// Candidates of a truncated first pass re-measured in full per hit.
+pub const TRUNCATED_CANDIDATE_FACTOR: usize = 10;
+
+/// Default number of hits a search returns.
+pub const DEFAULT_K: usize = 5;
+
+/// Default recency time constant: one week.
+pub const DEFAULT_TAU: f64 = 7.0 * 86_400.0;
+
+/// Default share of hybrid relevance given to the keyword match.
+pub const DEFAULT_TEXT_WEIGHT: f32 = 0.5;
+
+/// Default share of hybrid relevance given to the sparse match.
+pub const DEFAULT_SPARSE_WEIGHT: f32 = 0.5;
+
+/// Default number of hops spreading activation travels.
+pub const DEFAULT_HOPS: usize = 2;
+
+/// HNSW search beam width an index starts with (`DB::set_ef`).
+pub const DEFAULT_EF: usize = 50;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchOptions {
+    /// How much recency counts, within 0..=1: 0 ranks by similarity alone,
+    /// 1 by `similarity * exp(-age / tau)`.
+    pub recency_weight: f32,
+    /// Recency time constant in seconds: a record `tau` old keeps 1/e of
+    /// its recency.
+    pub tau: f64,
+    /// MMR re-ranking with this lambda, within 0..=1: 1 is pure relevance,
+    /// lower values favour diversity. None = no re-ranking.
+    pub mmr_lambda: Option<f32>,
+    /// Drop hits scoring below this, so an irrelevant store yields fewer
+    /// than k hits (possibly none) rather than junk.
+    pub min_score: Option<f32>,
+    /// Only consider records whose timestamp lies within this inclusive
+    /// `(after, before)` range of Unix seconds.
+    pub time_range: Option<(i64, i64)>,
+    /// Only consider records whose importance lies within this inclusive
+    /// `(min, max)` range.
+    pub importance_range: Option<(f32, f32)>,
+    /// Only consider records whose metadata matches this expression.
+    pub filter: Option<Filter>,
+    /// Only consider records of this session (`Metadata::session`).
+    pub session: Option<String>,
+    /// Leave out the records of this session, e.g. the current
+    /// conversation's scratch memory, which the caller already holds.
+    pub exclude_session: Option<String>,
+    /// Leave out the records from these sources.
+    pub exclude_sources: Vec<String>,
+    /// Leave out the records of these context types.
+    pub exclude_types: Vec<ContextType>,
+    /// Leave out these records, e.g. the memories already in the agent's
+    /// prompt.
+    pub exclude_ids: Vec<u64>,
+    /// Consider archived records as well (see the `archive` module).
+    pub include_archived: bool,
+    /// Only consider the records this caller may see (see the `access`
+    /// module). None = every record.
+    pub access: Option<Access>,
+    /// Measure the query against every record instead of searching the
+    /// approximate index: slower, but the true nearest neighbours, to check
+    /// the index's results or for a query that must not miss.
+    pub exact: bool,
+    /// Measure every record on the first this many dimensions of the
+    /// query and its vector, then the best candidates in full, rather than
+    /// search the approximate index. None = every dimension.
+    pub truncate_dim: Option<usize>,
+    /// Keywords to match against record content (BM25), fused with the
+    /// vector ranking. None = vector similarity alone.
+    pub text: Option<String>,
+    /// Share of relevance given to the keyword match, within 0..=1.
+    pub text_weight: f32,
+    /// Sparse query (e.g. SPLADE term weights) matched by dot product
+    /// against the sparse vectors under `sparse_name`, fused with the vector
+    /// ranking. None = no sparse match.
+    pub sparse: Option<SparseVector>,
+    pub sparse_name: String,
+    /// Share of relevance given to the sparse match, within 0..=1; with
+    /// `text` as well, the two shares may not add up to more than 1.
+    pub sparse_weight: f32,
+    /// Share of activation passed along each link per hop, within 0..=1.
+    /// 0 = no spreading activation.
+    pub graph_boost: f32,
+    /// Hops spreading activation travels from the hits.
+    pub hops: usize,
+    /// Skip this many of the best hits: k hits from `offset` on are the
+    /// page after the first `offset`.
+    pub offset: usize,
+    /// Keep at most this many hits from one source. None = no limit.
+    pub max_per_source: Option<usize>,
+    /// Follow each hit with its linked records that have a vector in one of
+    /// these modalities. Empty = hits alone.
+    pub linked_modalities: Vec<String>,
+    /// Re-orders the best candidates before the hits are picked. None =
+    /// the search's own order.
+    pub reranker: Option<Rc<dyn Reranker>>,
+    /// Score hits by this weighted mean instead; it excludes
+    /// `recency_weight` and `graph_boost`. None = the file's default
+    /// policy if it has one and they are unset, else the above.
+    pub scoring: Option<ScoringPolicy>,
+    /// Where `search_batch` measures queries against vectors (see the
+    /// `device` module); other searches run on the CPU.
+    pub device: Device,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            recency_weight: 0.0, tau: DEFAULT_TAU, mmr_lambda: None, min_score: None, time_range: None,
+            importance_range: None, filter: None, session: None, exclude_session: None,
+            exclude_sources: Vec::new(), exclude_types: Vec::new(), exclude_ids: Vec::new(), include_archived: false, access: None, exact: false,
+            truncate_dim: None,
+            text: None, text_weight: DEFAULT_TEXT_WEIGHT,
+            sparse: None, sparse_name: sparse::DEFAULT_NAME.to_string(), sparse_weight: DEFAULT_SPARSE_WEIGHT,
+            graph_boost: 0.0, hops: DEFAULT_HOPS, offset: 0, max_per_source: None, linked_modalities: Vec::new(),
+            reranker: None, scoring: None, device: Device::Cpu,
+        }
+    }
+}
+
+impl SearchOptions {
+    /// These options, following each hit with its linked records in
+    /// `modalities`, e.g. `&["image"]`.
+    pub fn include_linked_modalities(mut self, modalities: &[&str]) -> Self {
+        self.linked_modalities = modalities.iter().map(|m| m.to_string()).collect();
+        self
+    }
+
+    /// These options, with a first pass over the first `dims` dimensions
+    /// only (see the module docs).
+    pub fn truncate_dim(mut self, dims: usize) -> Self {
+        self.truncate_dim = Some(dims);
+        self
+    }
+
+    /// These options, with at most `n` hits from any one source.
+    pub fn max_per_source(mut self, n: usize) -> Self {
+        self.max_per_source = Some(n);
+        self
+    }
+
+    /// These options, with `search_batch` running on `device`.
+    pub fn device(mut self, device: Device) -> Self {
+        self.device = device;
+        self
+    }
+
+    /// These options, with `reranker` setting the order of the hits.
+    pub fn reranker(mut self, reranker: impl Reranker + 'static) -> Self {
+        self.reranker = Some(Rc::new(reranker));
+        self
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        anyhow::ensure!((0.0..=1.0).contains(&self.recency_weight), "recency weight must be within 0..=1");
+        anyhow::ensure!(self.tau > 0.0 && self.tau.is_finite(), "tau must be positive");
+        anyhow::ensure!(self.mmr_lambda.is_none_or(|l| (0.0..=1.0).contains(&l)), "MMR lambda must be within 0..=1");
+        anyhow::ensure!(self.time_range.is_none_or(|(after, before)| after <= before),
+                        "time range ends before it starts");
+        anyhow::ensure!(self.importance_range.is_none_or(|(min, max)| min <= max),
+                        "importance range ends before it starts");
+        anyhow::ensure!(self.truncate_dim != Some(0), "truncated dimensions must be at least 1");
+        anyhow::ensure!(!(self.exact && self.truncate_dim.is_some()), "an exact search measures every dimension");
+        anyhow::ensure!(self.max_per_source != Some(0), "at most 0 hits per source leaves no hits");
+        anyhow::ensure!((0.0..=1.0).contains(&self.text_weight), "text weight must be within 0..=1");
+        anyhow::ensure!((0.0..=1.0).contains(&self.sparse_weight), "sparse weight must be within 0..=1");
+        anyhow::ensure!(self.text.is_none() || self.sparse.is_none() || self.text_weight + self.sparse_weight <= 1.0,
+                        "text and sparse weights add up to more than 1");
+        anyhow::ensure!((0.0..=1.0).contains(&self.graph_boost), "graph boost must be within 0..=1");
+        anyhow::ensure!(self.graph_boost == 0.0 || self.hops > 0, "spreading activation needs at least one hop");
+        if let Some(policy) = &self.scoring {
+            policy.validate()?;
+            anyhow::ensure!(self.recency_weight == 0.0 && self.graph_boost == 0.0,
+                            "a scoring policy replaces the recency weight and graph boost");
+            anyhow::ensure!(policy.graph == 0.0 || self.hops > 0, "graph proximity needs at least one hop");
+        }
+        Ok(())
+    }
+
+    // Whether the options drop any records, so a search may have to fetch
+    // past the nearest to fill its hits.
+    pub(crate) fn filters(&self) -> bool {
+        self.filter.is_some() || self.time_range.is_some() || self.importance_range.is_some()
+            || self.session.is_some() || self.exclude_session.is_some() || self.excludes() || self.access.is_some()
+    }
+
+    // Whether any records are left out by id, source or type.
+    fn excludes(&self) -> bool {
+        !self.exclude_ids.is_empty() || !self.exclude_sources.is_empty() || !self.exclude_types.is_empty()
+    }
+
+    // What the index scan can enforce: the time range and session, narrowed
+    // by the filter's top-level terms.
+    fn prefilter(&self) -> Prefilter {
+        let mut prefilter = Prefilter {
+            time_range: self.time_range, include_archived: self.include_archived, exact: self.exact,
+            truncate_dim: self.truncate_dim, ..Prefilter::default()
+        };
+        if let Some(session) = &self.session {
+            prefilter.attributes.insert(SESSION_ATTRIBUTE.to_string(), session.clone());
+        }
+        if let Some(filter) = &self.filter {
+            filter.narrow(&mut prefilter);
+        }
+        prefilter
+    }
+
+    /// Multiplier on the similarity of a record stamped `timestamp`. Records
+    /// without a timestamp are not penalised.
+    pub fn recency(&self, timestamp: i64, now: i64) -> f32 {
+        if self.recency_weight == 0.0 || timestamp <= 0 { return 1.0; }
+        let age = (now - timestamp).max(0) as f64;
+        let w = self.recency_weight as f64;
+        ((1.0 - w) + w * (-age / self.tau).exp()) as f32
+    }
+}
+
+impl DB {
+    /// Nearest records to `query` ranked by similarity × recency, best first,
+    /// as `(id, score)`; with MMR, in pick order (scores stay the relevance
+    /// scores). Returns the k hits after the first `options.offset`. Like
+    /// `search`, the returned hits count as recalled and the query feeds
+    /// drift stats.
+    pub fn search_with_options(&self, query: &[f32], k: usize, modality: &str,
+                               options: &SearchOptions) -> anyhow::Result<Vec<(u64, f32)>> {
+        self.search_query(Query { vector: query, text: options.text.as_deref() }, k, modality, options)
+    }
+
+    // `search_with_options`, telling a reranker the query text too.
+    pub(crate) fn search_query(&self, query: Query, k: usize, modality: &str,
+                               options: &SearchOptions) -> anyhow::Result<Vec<(u64, f32)>> {
+        let hits = self.ranked(query, k, modality, options, None)?;
+        let internal = self.mname(Some(modality)).expect("named");
+        self.observe_query(Some(&internal), &self.project(Some(&internal), query.vector));
+        self.recalled(hits.iter().map(|&(id, _)| id));
+        Ok(hits)
+    }
+
+    // The ranking of `search_query`, without its side effects; with a
+    // `trace`, noting how each candidate scored and why any were dropped.
+    pub(crate) fn ranked(&self, query: Query, k: usize, modality: &str, options: &SearchOptions,
+                         mut trace: Option<&mut Trace>) -> anyhow::Result<Vec<(u64, f32)>> {
+        options.validate()?;
+        let Query { vector: query, text } = query;
+        let k = k.saturating_add(options.offset);
+        let internal = self.mname(Some(modality)).expect("named");
+        let projected = self.project(Some(&internal), query);
+        self.check_dim(Some(&internal), &projected)?;
+        let now = decay::now();
+        let policy = options.scoring.or_else(|| {
+            (options.recency_weight == 0.0 && options.graph_boost == 0.0).then(|| self.scoring_policy()).flatten()
+        });
+        let reranked = options.recency_weight > 0.0 || options.mmr_lambda.is_some() || options.reranker.is_some()
+            || policy.is_some_and(|p| p != ScoringPolicy::default());
+        let candidates = if reranked { k.saturating_mul(CANDIDATE_FACTOR) } else { k };
+        let mut prefilter = options.prefilter();
+        let plan = self.plan(candidates, modality, options, &prefilter)?;
+        plan.apply(&mut prefilter);
+        if let Some(trace) = trace.as_deref_mut() { trace.plan = plan; }
+        let mut fetch = candidates;
+        let mut hits = loop {
+            let found = self.knn_within(query, fetch, modality, &prefilter)?;
+            let keyword = match &options.text {
+                Some(text) => self.bm25_within(text, fetch, options.include_archived)?,
+                None => Vec::new(),
+            };
+            let sparse = match &options.sparse {
+                Some(q) => self.sparse_knn_within(q, fetch, &options.sparse_name, options.include_archived)?,
+                None => Vec::new(),
+            };
+            let exhausted = found.len() < fetch && keyword.len() < fetch && sparse.len() < fetch;
+            if let Some(trace) = trace.as_deref_mut() { trace.clear(); }
+            let hits: Vec<(u64, f32)> = self.relevance(query, modality, found, keyword, sparse, options)
+                .into_iter()
+                .filter_map(|mut hit| {
+                    let timestamp = match self.admission(hit.id, options) {
+                        Ok(timestamp) => timestamp,
+                        Err(reason) => {
+                            if let Some(trace) = trace.as_deref_mut() { trace.reject(hit.id, reason); }
+                            return None;
+                        }
+                    };
+                    hit.recency = if policy.is_some() { 1.0 } else { options.recency(timestamp, now) };
+                    let score = hit.recency * hit.relevance;
+                    let id = hit.id;
+                    if let Some(trace) = trace.as_deref_mut() { trace.note(hit); }
+                    Some((id, score))
+                })
+                .collect();
+            // without a filter or a per-source limit, fetching further only adds worse hits
+            let kept = match options.max_per_source {
+                Some(_) => self.cap_per_source(hits.clone(), options, None).len(),
+                None => hits.len(),
+            };
+            if !(options.filters() || options.max_per_source.is_some()) || kept >= candidates || exhausted {
+                break hits;
+            }
+            fetch = fetch.saturating_mul(2);
+        };
+        if let Some(policy) = &policy {
+            hits = self.compose(hits, policy, options, now, trace.as_deref_mut());
+        } else if options.graph_boost > 0.0 {
+            let before: HashMap<u64, f32> = hits.iter().copied().collect();
+            hits = self.spread(hits, options);
+            if let Some(trace) = trace.as_deref_mut() {
+                for &(id, score) in &hits {
+                    trace.entry(id).graph = score - before.get(&id).copied().unwrap_or(0.0);
+                }
+            }
+        }
+        hits.retain(|&(id, score)| {
+            let kept = options.min_score.is_none_or(|min| score >= min);
+            if !kept { if let Some(trace) = trace.as_deref_mut() { trace.reject(id, Rejection::MinScore); } }
+            kept
+        });
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        if let Some(reranker) = &options.reranker {
+            hits.truncate(candidates);
+            hits = self.rerank(reranker.as_ref(), &Query { vector: query, text }, hits)?;
+            if let Some(trace) = trace.as_deref_mut() {
+                for &(id, score) in &hits { trace.entry(id).reranker = Some(score); }
+            }
+        }
+        hits = self.cap_per_source(hits, options, trace.as_deref_mut());
+        match options.mmr_lambda {
+            Some(lambda) => hits = self.mmr(hits, k, modality, lambda),
+            None => hits.truncate(k),
+        }
+        hits.drain(..options.offset.min(hits.len()));
+        if !options.linked_modalities.is_empty() {
+            let ranked: HashSet<u64> = hits.iter().map(|&(id, _)| id).collect();
+            hits = self.with_linked(hits, options);
+            if let Some(trace) = trace {
+                for &(id, _) in hits.iter().filter(|(id, _)| !ranked.contains(id)) {
+                    trace.entry(id).linked = true;
+                }
+            }
+        }
+        Ok(hits)
+    }
+}
+
+impl DB {
+    // `hits` without those that rank below `options.max_per_source` others
+    // from their source, in order.
+    pub(crate) fn cap_per_source(&self, hits: Vec<(u64, f32)>, options: &SearchOptions,
+                                 mut trace: Option<&mut Trace>) -> Vec<(u64, f32)> {
+        let Some(most) = options.max_per_source else { return hits };
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        hits.into_iter()
+            .filter(|&(id, _)| {
+                let source = self.get_metadata(id).map(|m| m.source).unwrap_or_default();
+                if source.is_empty() { return true; }
+                let count = seen.entry(source).or_default();
+                *count += 1;
+                let kept = *count <= most;
+                if !kept { if let Some(trace) = trace.as_deref_mut() { trace.reject(id, Rejection::PerSource); } }
+                kept
+            })
+            .collect()
+    }
+
+    // The timestamp of `id` if it is live and passes the options' time range
+    // and filter.
+    pub(crate) fn admitted(&self, id: u64, options: &SearchOptions) -> Option<i64> {
+        self.admission(id, options).ok()
+    }
+
+    // `admitted`, saying why not.
+    fn admission(&self, id: u64, options: &SearchOptions) -> Result<i64, Rejection> {
+        let meta = self.get_metadata(id).filter(|m| !m.is_forgotten()).ok_or(Rejection::Forgotten)?;
+        // graph hits bypass the index scan's time range and archive check
+        if !options.include_archived && meta.is_archived() { return Err(Rejection::Archived); }
+        if options.exclude_ids.contains(&id) || options.exclude_sources.contains(&meta.source)
+            || options.exclude_types.iter().any(|t| t.code() == meta.context_type.code()) {
+            return Err(Rejection::Excluded);
+        }
+        if options.time_range.is_some_and(|(after, before)| !(after..=before).contains(&meta.timestamp)) {
+            return Err(Rejection::TimeRange);
+        }
+        if options.importance_range.is_some_and(|(min, max)| !(min..=max).contains(&meta.importance)) {
+            return Err(Rejection::Importance);
+        }
+        if options.session.as_deref().is_some_and(|s| meta.session() != Some(s))
+            || options.exclude_session.as_deref().is_some_and(|s| meta.session() == Some(s)) {
+            return Err(Rejection::Session);
+        }
+        if options.filter.as_ref().is_some_and(|f| !f.matches(&meta)) { return Err(Rejection::Filter); }
+        if options.access.as_ref().is_some_and(|a| !a.sees(&meta)) { return Err(Rejection::Access); }
+        Ok(meta.timestamp)
+    }
+
+    // Each hit followed by the records linked to it, either way, that have a
+    // vector in one of the options' linked modalities and that the options
+    // admit, best first at the hit's score × the link's weight. A record
+    // already listed is not repeated.
+    fn with_linked(&self, hits: Vec<(u64, f32)>, options: &SearchOptions) -> Vec<(u64, f32)> {
+        let mut listed: HashSet<u64> = hits.iter().map(|&(id, _)| id).collect();
+        let mut out = Vec::with_capacity(hits.len());
+        for (id, score) in hits {
+            out.push((id, score));
+            let mut linked: Vec<(u64, f32)> = self.links(id).into_iter()
+                .map(|link| (link.other(id), score * link.weight))
+                .filter(|&(other, _)| !listed.contains(&other))
+                .filter(|&(other, _)| options.linked_modalities.iter().any(|m| self.get_vector(other, m).is_some()))
+                .filter(|&(other, _)| self.admitted(other, options).is_some())
+                .collect();
+            linked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+            for (other, score) in linked {
+                if listed.insert(other) { out.push((other, score)); }
+            }
+        }
+        out
+    }
+
+    // Score the hits `(id, similarity)` by `policy`, adding the records
+    // their links reach when graph proximity counts.
+    fn compose(&self, hits: Vec<(u64, f32)>, policy: &ScoringPolicy, options: &SearchOptions,
+               now: i64, mut trace: Option<&mut Trace>) -> Vec<(u64, f32)> {
+        let similarity: HashMap<u64, f32> = hits.iter().copied().collect();
+        let mut proximity = HashMap::new();
+        if policy.graph > 0.0 {
+            let full = SearchOptions { graph_boost: 1.0, ..options.clone() };
+            for (id, score) in self.spread(hits, &full) {
+                let received = score - similarity.get(&id).copied().unwrap_or(0.0);
+                proximity.insert(id, received.clamp(0.0, 1.0));
+            }
+        }
+        let ids: HashSet<u64> = similarity.keys().chain(proximity.keys()).copied().collect();
+        ids.into_iter()
+            .filter_map(|id| {
+                let meta = self.get_metadata(id)?;
+                let age = (now - meta.timestamp).max(0) as f64;
+                let recency = if meta.timestamp <= 0 { 1.0 } else { (-age / options.tau).exp() as f32 };
+                let graph = proximity.get(&id).copied().unwrap_or(0.0);
+                if let Some(trace) = trace.as_deref_mut() {
+                    let hit = trace.entry(id);
+                    hit.recency = recency;
+                    hit.graph = graph;
+                }
+                let usage = scoring::usage(meta.recall_count);
+                Some((id, policy.score(similarity.get(&id).copied().unwrap_or(0.0), meta.importance, recency, usage, graph)))
+            })
+            .collect()
+    }
+
+    // `hits` in the order `reranker` gives them, which must be among them.
+    fn rerank(&self, reranker: &dyn Reranker, query: &Query, hits: Vec<(u64, f32)>)
+              -> anyhow::Result<Vec<(u64, f32)>> {
+        let offered: HashSet<u64> = hits.iter().map(|&(id, _)| id).collect();
+        let candidates = hits.into_iter()
+            .filter_map(|(id, score)| Some(Candidate { id, score, metadata: self.get_metadata(id)? }))
+            .collect();
+        let reranked = reranker.rerank(query, candidates)?;
+        let mut seen = HashSet::new();
+        for c in &reranked {
+            anyhow::ensure!(offered.contains(&c.id), "reranker returned {}, which is not a candidate", c.id);
+            anyhow::ensure!(seen.insert(c.id), "reranker returned {} twice", c.id);
+        }
+        Ok(reranked.into_iter().map(|c| (c.id, c.score)).collect())
+    }
+
+    // Spreading activation from the scored `seeds`: every hop passes
+    // `graph_boost` × link weight of each record's newly received activation
+    // to its linked records, and each record's score grows by all it
+    // receives.
+    // Records reached only through links join if the options admit them.
+    fn spread(&self, seeds: Vec<(u64, f32)>, options: &SearchOptions) -> Vec<(u64, f32)> {
+        let mut scores: HashMap<u64, f32> = seeds.iter().copied().collect();
+        let mut rejected: HashSet<u64> = HashSet::new();
+        let mut frontier = seeds;
+        for _ in 0..options.hops {
+            let mut received: HashMap<u64, f32> = HashMap::new();
+            for (id, activation) in frontier {
+                for link in self.links(id) {
+                    *received.entry(link.other(id)).or_default() += options.graph_boost * link.weight * activation;
+                }
+            }
+            received.retain(|&id, _| {
+                if scores.contains_key(&id) { return true; }
+                if rejected.contains(&id) { return false; }
+                let admitted = self.admitted(id, options).is_some();
+                if !admitted { rejected.insert(id); }
+                admitted
+            });
+            for (&id, &activation) in &received {
+                *scores.entry(id).or_default() += activation;
+            }
+            frontier = received.into_iter().collect();
+        }
+        scores.into_iter().collect()
+    }
+
+    // Relevance of each candidate: similarity `1 / (1 + d)` for the vector
+    // hits `(id, d)`, fused with the keyword hits `(id, bm25)` when
+    // `options.text` is set and the sparse hits `(id, dot)` when
+    // `options.sparse` is. A keyword or sparse hit outside the vector hits
+    // gets the similarity of its stored vector (0 without one); a candidate
+    // outside the sparse hits, the dot product of its stored sparse vector.
+    // Each comes with the signals that made it up.
+    fn relevance(&self, query: &[f32], modality: &str, vector: Vec<(u64, f32)>, keyword: Vec<(u64, f32)>,
+                 sparse: Vec<(u64, f32)>, options: &SearchOptions) -> Vec<HitExplanation> {
+        let similarity = |dist: f32| 1.0 / (1.0 + dist);
+        let vector_hit = |id: u64, dist: f32| HitExplanation {
+            id, distance: Some(dist), similarity: similarity(dist), relevance: similarity(dist), recency: 1.0,
+            ..HitExplanation::default()
+        };
+        if options.text.is_none() && options.sparse.is_none() {
+            return vector.into_iter().map(|(id, dist)| vector_hit(id, dist)).collect();
+        }
+        let projected = self.project(self.mname(Some(modality)).as_deref(), query);
+        let stored_hit = |id: u64| {
+            let dist = self.get_vector(id, modality).filter(|v| v.len() == projected.len())
+                .map(|v| v.iter().zip(projected.iter()).map(|(a, b)| (a - b) * (a - b)).sum::<f32>());
+            HitExplanation { id, distance: dist, similarity: dist.map_or(0.0, similarity), recency: 1.0,
+                             ..HitExplanation::default() }
+        };
+        let normalize = |score: f32, best: f32| if best > 0.0 { score / best } else { 0.0 };
+        let mut pool: HashMap<u64, HitExplanation> = vector.into_iter()
+            .map(|(id, dist)| (id, vector_hit(id, dist)))
+            .collect();
+        let best = keyword.first().map_or(0.0, |&(_, score)| score);
+        for (id, score) in keyword {
+            pool.entry(id).or_insert_with(|| stored_hit(id)).keyword = Some(normalize(score, best));
+        }
+        let best = sparse.first().map_or(0.0, |&(_, score)| score);
+        let sparse: HashMap<u64, f32> = sparse.into_iter().collect();
+        if let Some(q) = &options.sparse {
+            for &id in sparse.keys() {
+                pool.entry(id).or_insert_with(|| stored_hit(id));
+            }
+            for (id, hit) in pool.iter_mut() {
+                let dot = sparse.get(id).copied().or_else(|| {
+                    self.get_sparse(*id, &options.sparse_name).map(|v| v.dot(q))
+                });
+                hit.sparse = Some(normalize(dot.unwrap_or(0.0), best).max(0.0));
+            }
+        }
+        let tw = if options.text.is_some() { options.text_weight } else { 0.0 };
+        let sw = if options.sparse.is_some() { options.sparse_weight } else { 0.0 };
+        pool.into_values()
+            .map(|mut hit| {
+                hit.relevance = (1.0 - tw - sw) * hit.similarity + tw * hit.keyword.unwrap_or(0.0)
+                    + sw * hit.sparse.unwrap_or(0.0);
+                hit
+            })
+            .collect()
+    }
+
+    // Greedy MMR over `pool` (id, relevance), best first: each pick maximises
+    // `lambda * relevance - (1 - lambda) * max cosine to the picks so far`.
+    fn mmr(&self, pool: Vec<(u64, f32)>, k: usize, modality: &str, lambda: f32) -> Vec<(u64, f32)> {
+        let mut pool: Vec<(u64, f32, Vec<f32>)> = pool.into_iter()
+            .filter_map(|(id, score)| Some((id, score, self.get_vector(id, modality)?)))
+            .collect();
+        let mut picked: Vec<(u64, f32, Vec<f32>)> = Vec::with_capacity(k.min(pool.len()));
+        while picked.len() < k && !pool.is_empty() {
+            let value = |(_, score, vec): &(u64, f32, Vec<f32>)| {
+                let redundancy = picked.iter().map(|p| cosine(vec, &p.2)).fold(0.0f32, f32::max);
+                lambda * score - (1.0 - lambda) * redundancy
+            };
+            let best = (0..pool.len())
+                .max_by(|&a, &b| value(&pool[a]).total_cmp(&value(&pool[b])))
+                .expect("pool is not empty");
+            picked.push(pool.swap_remove(best));
+        }
+        picked.into_iter().map(|(id, score, _)| (id, score)).collect()
+    }
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denom = norm(a) * norm(b);
+    if denom == 0.0 { 0.0 } else { dot / denom }
+}
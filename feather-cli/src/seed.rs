@@ -0,0 +1,47 @@
+//! Reproducible indexes and rankings (`OpenOptions::seed`, `feather
+//! --seed`).
+//!
+//! An HNSW index draws each vector's level at random, and a batch of more
+//! than a few hundred vectors is indexed on several threads, so the same
+//! records can make a slightly different graph, and a search return
+//! slightly different hits, from one run to the next. With a seed set,
+//! every index draws its levels from it and is built on one thread, in the
+//! order the vectors are written. An index loaded as saved keeps its graph
+//! and draws its next levels from the seed; one built on several threads
+//! at open (a file saved without its graph) is rebuilt at once, its
+//! vectors in id order. The same file, writes and seed then give
+//! bit-identical indexes and rankings on every run, for test suites and
+//! evaluations. `cluster` seeds its k-means++ from it too.
+//!
+//! A seeded store indexes batches on one thread, so it loads them slower,
+//! and a rebuild makes its open slower. The seed is not kept in the file.
+
+use crate::*;
+
+extern "C" {
+    fn feather_set_seed(db: *mut c_void, seed: u64) -> i32;
+}
+
+impl Handle {
+    // Seed every core, and a fork's base.
+    pub(crate) fn set_seed(&self, seed: u64) -> anyhow::Result<()> {
+        for &core in self.cores() {
+            if unsafe { feather_set_seed(core, seed) } != 0 { return Err(last_error()); }
+        }
+        if let Some(base) = &self.fork { base.set_seed(seed)?; }
+        self.seed.set(Some(seed));
+        Ok(())
+    }
+}
+
+impl DB {
+    /// The seed the indexes are built from, if one was set.
+    pub fn seed(&self) -> Option<u64> { self.handle.seed.get() }
+
+    /// Build every index from `seed`, on one thread, rebuilding those
+    /// already loaded (see the module docs). For every handle on this file;
+    /// not persisted.
+    pub fn set_seed(&self, seed: u64) -> anyhow::Result<()> {
+        self.handle.set_seed(seed)
+    }
+}
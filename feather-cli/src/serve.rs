@@ -0,0 +1,750 @@
+//! `feather serve`: one store over HTTP, as JSON, so agents written in any
+//! language can use it over localhost without FFI.
+//!
+//! - `POST /add` takes one row, or an array of rows, shaped like a line of
+//!   `feather import` JSONL; replies `{"added", "skipped", "deduplicated"}`.
+//! - `POST /search` takes `{"vector": [...], "k": 10}` plus, optionally,
+//!   `offset`, `max_per_source`, `modality`, `filter` (as `--filter` takes
+//!   it), `session`, `exclude_session`, `exclude_sources`, `exclude_types`
+//!   (codes), `exclude_ids`, `include_archived`, `exact`, `truncate_dim`,
+//!   `text` (hybrid keywords), `min_score`, `min_importance`,
+//!   `max_importance` and `budget_ms`;
+//!   replies `{"hits": [{"id", "score"}], "partial"}`, `partial` true when
+//!   the time budget ran out first (see `limits`). With `"stream": true` it
+//!   replies NDJSON instead, one `{"id", "score"}` line per hit written as
+//!   it is ranked (see `stream`); a failure part-way ends the stream with
+//!   an `{"error"}` line, a budget that ran out with `{"partial": true}`. A
+//!   row's `session_id` scopes it to a session.
+//! - `GET /get/{id}` replies with the record as `feather export` writes it;
+//!   its version is the `_version` attribute.
+//! - `DELETE /delete/{id}` forgets the record; replies `{"deleted": id}`.
+//!   A body of `{"if_version": n}` makes it conditional: a record no longer
+//!   at version `n` is left alone, with status 409.
+//! - `GET /metrics` replies with request counts and latencies, insert
+//!   counts and index sizes for Prometheus (see `metrics`).
+//! - `GET /healthz` replies `{"status": "ok"}` while the process is up, and
+//!   `GET /readyz` `{"ready", "checks"}`, 503 when it cannot take requests;
+//!   neither takes a token (see `doctor`).
+//!
+//! With `Tokens` (`feather serve --api-key-file` / `--access-file`), every
+//! request must carry one as `Authorization: Bearer <token>`, or is
+//! answered 401. Tokens and limits are checked on the request's line and
+//! headers, which may take `MAX_HEAD` bytes; its body, up to `MAX_BODY`,
+//! is read only once the request is let in. An API key may do anything;
+//! an access file's token acts for its labels (see `access`): searches see
+//! only what those labels may, `/get` and `/delete` answer 404 for a
+//! record they may not see and 403 for one they see but do not own, and
+//! `/add` rows are owned by the token's first label unless they name
+//! another of its own.
+//!
+//! Failures reply `{"error": "..."}` with a 4xx or 5xx status. Each
+//! connection is read on a thread of its own, up to `MAX_CONNECTIONS` open
+//! at once, one request per connection; the requests are then answered one
+//! at a time on the calling thread (a `DB` is not `Send`). `Limits` can
+//! bound each client's request rate, the searches waiting and the time one
+//! search takes (see `limits`). Writes reach the WAL at once; the file is
+//! checkpointed every `CHECKPOINT_EVERY` writes. `serve_replicated` also
+//! streams them to read replicas (see `replicate`), and `serve_with` can
+//! post the records each write adds or deletes to webhooks (see `webhook`).
+//! A store with a `Maintenance` policy is maintained between requests, and
+//! every interval while none come (see `maintenance`). A connection that
+//! fails, or a request whose replication fails, is logged and the server
+//! goes on; only a listener that fails stops it.
+//! `serve_connections` answers connections of any kind, such as the TLS
+//! streams of the `feather-https` crate, which `feather serve --tls-cert`
+//! runs. `tenants` serves a directory of stores the same way. After
+//! `shutdown::on_signals`, SIGINT or SIGTERM stops a server cleanly.
+
+use crate::doctor::{self, Finding, Status};
+use crate::limits::{self, Gate};
+use crate::metrics::{self, Metrics};
+use crate::replicate::Primary;
+use crate::shutdown;
+use crate::trace::{Level, Span};
+use crate::webhook::Webhooks;
+use crate::{import, Access, ContextType, Filter, Limits, SearchOptions, Tokens, VersionConflict, DB};
+use serde_json::{json, Map, Value};
+use std::cell::RefCell;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Largest request body accepted.
+pub const MAX_BODY: usize = 64 << 20;
+
+/// Largest request line and headers accepted, together.
+pub const MAX_HEAD: usize = 16 << 10;
+
+/// Writes between two checkpoints of the file.
+pub const CHECKPOINT_EVERY: usize = 1000;
+
+/// Hits returned by `/search` when the request names no `k`.
+const DEFAULT_K: usize = 10;
+
+/// A client that stalls longer than this loses its connection.
+pub const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Connections open at once; one more is answered 503 unread.
+pub const MAX_CONNECTIONS: usize = 256;
+
+// How long to wait after a connection that could not be accepted.
+const ACCEPT_PAUSE: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl Response {
+    pub(crate) fn ok(body: Value) -> Self { Response { status: 200, body } }
+
+    pub(crate) fn error(status: u16, message: impl std::fmt::Display) -> Self {
+        Response { status, body: json!({ "error": message.to_string() }) }
+    }
+}
+
+/// A connection `serve_connections` can answer: a stream that can move to
+/// the thread reading it.
+pub trait Connection: Read + Write + Send + 'static {
+    /// The client's address, which rate limits count a request without a
+    /// token by.
+    fn peer(&self) -> Option<IpAddr>;
+}
+
+impl Connection for TcpStream {
+    fn peer(&self) -> Option<IpAddr> { self.peer_addr().ok().map(|addr| addr.ip()) }
+}
+
+/// Answer connections on `listener` until it fails.
+pub fn serve(db: &DB, listener: &TcpListener) -> anyhow::Result<()> {
+    serve_with(db, listener, None, None, None, &Limits::default())
+}
+
+/// `serve`, streaming every write to the read replicas of `primary`.
+pub fn serve_replicated(db: &DB, listener: &TcpListener, primary: &mut Primary) -> anyhow::Result<()> {
+    serve_with(db, listener, Some(primary), None, None, &Limits::default())
+}
+
+/// `serve`, streaming writes to `primary`'s replicas, posting the records
+/// they add or delete to `webhooks`, and answering only requests with one
+/// of `tokens`, each if given, within `limits`.
+pub fn serve_with(db: &DB, listener: &TcpListener, primary: Option<&mut Primary>, webhooks: Option<&Webhooks>,
+                  tokens: Option<&Tokens>, limits: &Limits) -> anyhow::Result<()> {
+    serve_connections(db, accept(listener)?, primary, webhooks, tokens, limits)
+}
+
+/// The connections accepted on `listener`, their reads timing out after
+/// `READ_TIMEOUT`, until the process is asked to stop (see `shutdown`).
+pub fn accept(listener: &TcpListener) -> std::io::Result<impl Iterator<Item = std::io::Result<TcpStream>> + Send + 'static> {
+    let listener = listener.try_clone()?;
+    let mut local = listener.local_addr()?;
+    if local.ip().is_unspecified() {
+        local.set_ip(match local.ip() {
+            IpAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+            IpAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+        });
+    }
+    // a connection of its own wakes the accept waiting for one
+    shutdown::on_request(move || { let _ = TcpStream::connect_timeout(&local, Duration::from_secs(1)); });
+    Ok(std::iter::from_fn(move || {
+        let accepted = listener.accept();
+        if shutdown::requested() { return None; }
+        Some(accepted.and_then(|(stream, _)| {
+            stream.set_read_timeout(Some(READ_TIMEOUT))?;
+            Ok(stream)
+        }))
+    }))
+}
+
+/// `serve_with`, answering each connection `connections` yields, such as
+/// TLS streams over a listener's; their reads should time out after
+/// `READ_TIMEOUT`. `connections` is drained on a thread of its own; an
+/// error it yields is logged and skipped unless it says the listener
+/// itself failed (an invalid, unconnected or unsupported socket), which
+/// stops the server, as does its end after a stop was asked for (see
+/// `shutdown`), having answered the requests read by then.
+pub fn serve_connections<S: Connection>(db: &DB, connections: impl IntoIterator<Item = std::io::Result<S>, IntoIter: Send + 'static>,
+                                        primary: Option<&mut Primary>, webhooks: Option<&Webhooks>,
+                                        tokens: Option<&Tokens>, limits: &Limits) -> anyhow::Result<()> {
+    let mut writes = 0;
+    let primary = RefCell::new(primary);
+    let tick = db.maintenance().map(|policy| policy.interval);
+    let idle = || {
+        db.maintain_if_due();
+        match primary.borrow_mut().as_deref_mut() {
+            Some(primary) => primary.ship(db),
+            None => Ok(()),
+        }
+    };
+    dispatch(connections, tokens, limits, tick, idle, |stream, request, access, metrics| {
+        let wrote = answer(db, stream, &request, access, metrics, limits.search_budget);
+        if let Some(webhooks) = webhooks {
+            webhooks.notify(db);
+        }
+        writes += wrote as usize;
+        let checkpoint = wrote && writes % CHECKPOINT_EVERY == 0;
+        match primary.borrow_mut().as_deref_mut() {
+            Some(primary) if checkpoint => primary.checkpoint(db)?,
+            // after any request: a write that failed part-way logged some
+            Some(primary) => primary.ship(db)?,
+            None if checkpoint => db.save()?,
+            None => {}
+        }
+        Ok(())
+    })
+}
+
+// Read each of `connections` on a thread of its own, and hand every
+// request let in by `tokens` and `limits` to `answer` on the calling
+// thread, with its connection, access and the server's metrics. With a
+// `tick`, `idle` runs after each request and whenever none has come for a
+// tick. Errors of `answer` and `idle` are logged; stops only when the
+// listener fails.
+pub(crate) fn dispatch<S: Connection>(connections: impl IntoIterator<Item = std::io::Result<S>, IntoIter: Send + 'static>,
+                                      tokens: Option<&Tokens>, limits: &Limits, tick: Option<Duration>,
+                                      mut idle: impl FnMut() -> anyhow::Result<()>,
+                                      mut answer: impl FnMut(&mut S, Request, &Access, &mut Metrics) -> anyhow::Result<()>)
+                                      -> anyhow::Result<()> {
+    let gate = Arc::new(Gate::new(tokens.cloned(), limits.clone()));
+    let (jobs, queue) = mpsc::channel();
+    let (connections, intake_gate) = (connections.into_iter(), gate.clone());
+    std::thread::spawn(move || intake(connections, intake_gate, jobs));
+    let mut metrics = Metrics::default();
+    loop {
+        let job = match tick.map(|tick| queue.recv_timeout(tick)) {
+            None => match queue.recv() {
+                Ok(job) => job,
+                Err(_) => break,
+            },
+            Some(Ok(job)) => job,
+            Some(Err(RecvTimeoutError::Timeout)) => {
+                logged(idle());
+                continue;
+            }
+            Some(Err(RecvTimeoutError::Disconnected)) => break,
+        };
+        match job {
+            Job::Answer(mut open, request, access) => {
+                let search = limits::is_search(&request);
+                let answered = answer(&mut open.stream, request, &access, &mut metrics);
+                if search { gate.release(); }
+                logged(answered);
+            }
+            Job::Refused(path, refusal) => metrics.observe(&path, &refusal, Duration::ZERO),
+            Job::Failed(e) => return Err(e.into()),
+            Job::Stop => break,
+        }
+        if tick.is_some() { logged(idle()); }
+    }
+    Ok(())
+}
+
+// Log the error of a request, or of the work between them, that the
+// server outlives.
+fn logged(result: anyhow::Result<()>) {
+    if let Err(e) = result {
+        Span::new(Level::Warn, "feather::serve").record_str("error", &e.to_string());
+    }
+}
+
+// Whether `e`, yielded in place of a connection, is the listener's own
+// failure rather than one connection's, such as a client hanging up
+// before it was accepted or the process running out of descriptors.
+fn listener_failed(e: &std::io::Error) -> bool {
+    matches!(e.kind(), ErrorKind::InvalidInput | ErrorKind::NotConnected | ErrorKind::Unsupported)
+}
+
+// What a thread reading a connection hands the thread holding the store.
+enum Job<S> {
+    // A request to answer, with the access it was admitted with.
+    Answer(Open<S>, Request, Access),
+    // A request refused already, to count in the metrics.
+    Refused(String, Response),
+    // The listener failed.
+    Failed(std::io::Error),
+    // The process was asked to stop: connections still being read are left.
+    Stop,
+}
+
+// A connection, counted among the open ones until dropped.
+struct Open<S> {
+    stream: S,
+    count: Arc<AtomicUsize>,
+}
+
+impl<S> Drop for Open<S> {
+    fn drop(&mut self) {
+        self.count.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Read each of `connections` on a thread of its own, handing the request
+// on to `jobs` once it is past `gate`.
+fn intake<S: Connection>(connections: impl Iterator<Item = std::io::Result<S>>, gate: Arc<Gate>, jobs: Sender<Job<S>>) {
+    let count = Arc::new(AtomicUsize::new(0));
+    for stream in connections {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) if listener_failed(&e) => {
+                let _ = jobs.send(Job::Failed(e));
+                return;
+            }
+            Err(e) => {
+                Span::new(Level::Warn, "feather::serve").record_str("error", &e.to_string());
+                // give descriptors run short time to come back
+                std::thread::sleep(ACCEPT_PAUSE);
+                continue;
+            }
+        };
+        if count.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            count.fetch_sub(1, Ordering::SeqCst);
+            reply(&mut stream, &Response::error(503, format!("{} connections are open; try again shortly", MAX_CONNECTIONS)));
+            continue;
+        }
+        let mut open = Open { stream, count: count.clone() };
+        let (gate, jobs) = (gate.clone(), jobs.clone());
+        std::thread::spawn(move || {
+            let Some(mut request) = read_request(&mut open.stream) else { return };
+            if let Some(response) = health(&request) {
+                reply(&mut open.stream, &response);
+                return;
+            }
+            let job = match gate.admit(&request, open.stream.peer()) {
+                Ok(access) if read_body(&mut open.stream, &mut request).is_some() => Job::Answer(open, request, access),
+                Ok(_) => {
+                    if limits::is_search(&request) { gate.release(); }
+                    return;
+                }
+                Err(refusal) => {
+                    reply(&mut open.stream, &refusal);
+                    Job::Refused(request.path, refusal)
+                }
+            };
+            // the store's thread is gone only when serving failed
+            let _ = jobs.send(job);
+        });
+    }
+    if shutdown::requested() {
+        let _ = jobs.send(Job::Stop);
+    }
+}
+
+// Read one request off `stream`, let it past `gate` and answer it, all on
+// the calling thread; true if it was a write that succeeded.
+pub(crate) fn answer_one(db: &DB, stream: &mut impl Connection, gate: &Gate, metrics: &mut Metrics,
+                         budget: Option<Duration>) -> bool {
+    let Some(mut request) = read_request(stream) else { return false };
+    if let Some(response) = health(&request) {
+        reply(stream, &response);
+        return false;
+    }
+    match gate.admit(&request, stream.peer()) {
+        Ok(access) => {
+            let wrote = read_body(stream, &mut request).is_some()
+                && answer(db, stream, &request, &access, metrics, budget);
+            if limits::is_search(&request) { gate.release(); }
+            wrote
+        }
+        Err(refusal) => {
+            metrics.observe(&request.path, &refusal, Duration::ZERO);
+            reply(stream, &refusal);
+            false
+        }
+    }
+}
+
+// Answer `request`, admitted with `access`, on `stream`, searching within
+// `budget`; true if it was a write that succeeded.
+pub(crate) fn answer(db: &DB, stream: &mut impl Write, request: &Request, access: &Access, metrics: &mut Metrics,
+          budget: Option<Duration>) -> bool {
+    let Request { method, path, body, .. } = request;
+    if method == "GET" && path == "/metrics" {
+        respond(stream, 200, metrics::CONTENT_TYPE, &metrics.render(db));
+        return false;
+    }
+    if path == "/readyz" {
+        let response = match method.as_str() {
+            "GET" => ready(&db.readiness()),
+            _ => Response::error(405, "/readyz takes GET"),
+        };
+        metrics.observe(path, &response, Duration::ZERO);
+        reply(stream, &response);
+        return false;
+    }
+    let start = Instant::now();
+    let response = match body {
+        Ok(body) if method == "POST" && path == "/search" && streamed(body) => match stream_search(db, access, stream, body, budget) {
+            Some(refusal) => refusal,
+            None => {
+                metrics.observe(path, &Response::ok(Value::Null), start.elapsed());
+                return false;
+            }
+        },
+        Ok(body) => route(db, access, method, path, body, budget),
+        Err(refusal) => refusal.clone(),
+    };
+    metrics.observe(path, &response, start.elapsed());
+    reply(stream, &response);
+    response.status == 200 && method != "GET" && path != "/search"
+}
+
+// The answer to `GET /healthz`, given as soon as it is read, or None if
+// `request` is something else (see `doctor`).
+pub(crate) fn health(request: &Request) -> Option<Response> {
+    (request.path == "/healthz").then(|| match request.method.as_str() {
+        "GET" => Response::ok(json!({ "status": "ok" })),
+        _ => Response::error(405, "/healthz takes GET"),
+    })
+}
+
+// The answer to `GET /readyz`: 503 if any of `findings` failed.
+pub(crate) fn ready(findings: &[Finding]) -> Response {
+    let ready = doctor::worst(findings) < Status::Fail;
+    let checks: Map<String, Value> = findings.iter()
+        .map(|f| (f.check.to_string(), json!({ "status": f.status, "detail": f.detail })))
+        .collect();
+    Response { status: if ready { 200 } else { 503 }, body: json!({ "ready": ready, "checks": checks }) }
+}
+
+// One request read off a connection.
+pub(crate) struct Request {
+    pub method: String,
+    /// Without any query string.
+    pub path: String,
+    /// The bearer token of its `Authorization` header, if it has one.
+    pub token: Option<String>,
+    /// Its `Content-Length`.
+    pub length: usize,
+    /// The body, or the error to answer the request with. Until
+    /// `read_body`, only what came in along with the head.
+    pub body: Result<Vec<u8>, Response>,
+}
+
+// Read the line and headers of one request off `stream`, at most
+// `MAX_HEAD` bytes; None if the client went away or sent no HTTP. The
+// body is left to `read_body`, once the request is let in.
+pub(crate) fn read_request(stream: &mut impl Read) -> Option<Request> {
+    let mut head = Vec::new();
+    let mut chunk = [0; 4096];
+    let end = loop {
+        if let Some(end) = head_end(&head) { break end; }
+        if head.len() > MAX_HEAD { break head.len(); }
+        match stream.read(&mut chunk).ok()? {
+            0 if head.is_empty() => return None,
+            0 => break head.len(),
+            n => head.extend_from_slice(&chunk[..n]),
+        }
+    };
+    if end > MAX_HEAD {
+        let refusal = Response::error(431, format!("request line and headers over {} bytes", MAX_HEAD));
+        return Some(Request { method: String::new(), path: String::new(), token: None, length: 0, body: Err(refusal) });
+    }
+    let text = String::from_utf8_lossy(&head[..end]);
+    let mut lines = text.lines();
+    let mut parts = lines.next()?.split_whitespace();
+    let (method, target) = (parts.next()?.to_string(), parts.next()?);
+    let path = target.split('?').next().unwrap_or_default().to_string();
+    let mut length = 0;
+    let mut token = None;
+    for header in lines {
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().ok()?;
+            } else if name.trim().eq_ignore_ascii_case("authorization") {
+                token = value.trim().split_once(' ')
+                    .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+                    .map(|(_, t)| t.trim().to_string());
+            }
+        }
+    }
+    if length > MAX_BODY {
+        let refusal = Response::error(413, format!("request body over {} bytes", MAX_BODY));
+        return Some(Request { method, path, token, length, body: Err(refusal) });
+    }
+    let mut body = head.split_off(end);
+    body.truncate(length);
+    Some(Request { method, path, token, length, body: Ok(body) })
+}
+
+// Where the head in `bytes` ends, past the blank line that ends it.
+fn head_end(bytes: &[u8]) -> Option<usize> {
+    let at = bytes.windows(2).position(|w| w == b"\n\n").map(|i| i + 2);
+    let crlf = bytes.windows(4).position(|w| w == b"\r\n\r\n").map(|i| i + 4);
+    at.into_iter().chain(crlf).min()
+}
+
+// Read the rest of `request`'s body off `stream`; None if the client went
+// away first. The buffer grows with what arrives, not with what the
+// request claims.
+pub(crate) fn read_body(stream: &mut impl Read, request: &mut Request) -> Option<()> {
+    if let Ok(body) = &mut request.body {
+        let missing = request.length - body.len();
+        stream.take(missing as u64).read_to_end(body).ok()?;
+        if body.len() < request.length { return None; }
+    }
+    Some(())
+}
+
+// Write `response` as JSON; a 429 also says when to retry in its headers.
+pub(crate) fn reply(stream: &mut impl Write, response: &Response) {
+    let retry = response.body["retry_after"].as_u64().map(|s| format!("Retry-After: {}\r\n", s)).unwrap_or_default();
+    send(stream, response.status, "application/json", &retry, &response.body.to_string());
+}
+
+pub(crate) fn respond(stream: &mut impl Write, status: u16, content_type: &str, body: &str) {
+    send(stream, status, content_type, "", body)
+}
+
+// Write a reply with the extra `headers`, each ending in CRLF.
+fn send(stream: &mut impl Write, status: u16, content_type: &str, headers: &str, body: &str) {
+    let head = format!("HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n",
+                       status, reason(status), content_type, body.len(), headers);
+    // a client that hung up is its own problem
+    let _ = stream.write_all(head.as_bytes()).and_then(|_| stream.write_all(body.as_bytes()));
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        503 => "Service Unavailable",
+        _ => "Error",
+    }
+}
+
+/// Answer one request: `method` and `path` as in the request line (without
+/// any query string), `body` the raw request body.
+pub fn handle(db: &DB, method: &str, path: &str, body: &[u8]) -> Response {
+    handle_as(db, &Access::All, method, path, body)
+}
+
+/// `handle`, for a caller with `access` (see the `access` module).
+pub fn handle_as(db: &DB, access: &Access, method: &str, path: &str, body: &[u8]) -> Response {
+    route(db, access, method, path, body, None)
+}
+
+// `handle_as`, searching within `budget` if given.
+fn route(db: &DB, access: &Access, method: &str, path: &str, body: &[u8], budget: Option<Duration>) -> Response {
+    let (route, id) = match path.trim_end_matches('/').rsplit_once('/') {
+        Some((route @ ("/get" | "/delete"), id)) => match id.parse::<u64>() {
+            Ok(id) => (route, Some(id)),
+            Err(_) => return Response::error(400, format!("bad id `{}`", id)),
+        },
+        _ => (path, None),
+    };
+    let allowed = match route {
+        "/add" | "/search" => "POST",
+        "/get" => "GET",
+        "/delete" => "DELETE",
+        _ => return Response::error(404, format!("no endpoint {}", path)),
+    };
+    if id.is_none() && allowed != "POST" {
+        return Response::error(404, format!("{} takes an id: {}/{{id}}", route, route));
+    }
+    if method != allowed {
+        return Response::error(405, format!("{} takes {}", route, allowed));
+    }
+    let result = match (route, id) {
+        ("/get", Some(id)) => return get(db, access, id),
+        ("/delete", Some(id)) => return delete(db, access, id, body),
+        ("/add", _) => parse(body).and_then(|body| add(db, access, body)),
+        _ => parse(body).and_then(|body| search(db, access, body, budget)),
+    };
+    match result {
+        Ok(body) => Response::ok(body),
+        Err(e) if e.is::<Forbidden>() => Response::error(403, e),
+        Err(e) => Response::error(400, format!("{:#}", e)),
+    }
+}
+
+fn parse(body: &[u8]) -> anyhow::Result<Value> {
+    serde_json::from_slice(body).map_err(|e| anyhow::anyhow!("body is not JSON: {}", e))
+}
+
+// A write the caller's access does not allow.
+#[derive(Debug)]
+struct Forbidden(String);
+
+impl std::fmt::Display for Forbidden {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { f.write_str(&self.0) }
+}
+
+impl std::error::Error for Forbidden {}
+
+fn add(db: &DB, access: &Access, body: Value) -> anyhow::Result<Value> {
+    let rows = match body {
+        Value::Array(rows) => rows,
+        row => vec![row],
+    };
+    // every row is checked before any is written
+    let records = rows.into_iter().map(|row| {
+        let Value::Object(obj) = row else { anyhow::bail!("each row must be a JSON object") };
+        let mut record = import::record_from_json(obj, "text")?;
+        access.claim(&mut record.metadata).map_err(|e| Forbidden(e.to_string()))?;
+        // replacing a record takes owning it
+        if let Some(old) = db.get_metadata(record.id).filter(|m| !m.is_forgotten()) {
+            if !access.owns(&old) { return Err(Forbidden(format!("cannot replace record {}", record.id)).into()); }
+        }
+        Ok(record)
+    }).collect::<anyhow::Result<Vec<_>>>()?;
+    let report = import::import(db, records.into_iter().map(Ok), import::DEFAULT_BATCH_SIZE, None)?;
+    Ok(json!({ "added": report.records, "skipped": report.skipped, "deduplicated": report.deduplicated }))
+}
+
+// A `/search` request: the query vector, k, modality, options and time
+// budget.
+struct SearchRequest {
+    vector: Vec<f32>,
+    k: usize,
+    modality: String,
+    options: SearchOptions,
+    budget: Option<Duration>,
+}
+
+fn search(db: &DB, access: &Access, body: Value, budget: Option<Duration>) -> anyhow::Result<Value> {
+    let SearchRequest { vector, k, modality, options, budget } = search_request(body, access, budget)?;
+    let (hits, partial) = match budget {
+        None => (db.search_with_options(&vector, k, &modality, &options)?, false),
+        Some(budget) => {
+            let mut hits = db.search_iter(&vector, &modality, &options).until(Instant::now() + budget);
+            let found = hits.by_ref().take(k).collect::<anyhow::Result<Vec<_>>>()?;
+            (found, hits.partial())
+        }
+    };
+    let hits: Vec<Value> = hits.into_iter().map(|(id, score)| json!({ "id": id, "score": score })).collect();
+    Ok(json!({ "hits": hits, "partial": partial }))
+}
+
+// The request in `body`, searching within the server's `budget` or the
+// tighter one the request asks for.
+fn search_request(body: Value, access: &Access, budget: Option<Duration>) -> anyhow::Result<SearchRequest> {
+    let Value::Object(mut body) = body else { anyhow::bail!("expected a JSON object") };
+    let vector: Vec<f32> = serde_json::from_value(body.remove("vector").unwrap_or_default())
+        .map_err(|_| anyhow::anyhow!("`vector` must be an array of numbers"))?;
+    let k = take::<usize>(&mut body, "k")?.unwrap_or(DEFAULT_K);
+    let modality = take::<String>(&mut body, "modality")?.unwrap_or_else(|| "text".to_string());
+    let min_importance = take::<f32>(&mut body, "min_importance")?;
+    let max_importance = take::<f32>(&mut body, "max_importance")?;
+    let options = SearchOptions {
+        offset: take(&mut body, "offset")?.unwrap_or(0),
+        max_per_source: take(&mut body, "max_per_source")?,
+        importance_range: (min_importance.is_some() || max_importance.is_some())
+            .then(|| (min_importance.unwrap_or(f32::NEG_INFINITY), max_importance.unwrap_or(f32::INFINITY))),
+        filter: take::<String>(&mut body, "filter")?.map(|f| Filter::parse(&f)).transpose()?,
+        session: take(&mut body, "session")?,
+        exclude_session: take(&mut body, "exclude_session")?,
+        exclude_sources: take(&mut body, "exclude_sources")?.unwrap_or_default(),
+        exclude_types: take::<Vec<u8>>(&mut body, "exclude_types")?.unwrap_or_default()
+            .into_iter().map(ContextType::from).collect(),
+        exclude_ids: take(&mut body, "exclude_ids")?.unwrap_or_default(),
+        include_archived: take(&mut body, "include_archived")?.unwrap_or(false),
+        access: (*access != Access::All).then(|| access.clone()),
+        exact: take(&mut body, "exact")?.unwrap_or(false),
+        truncate_dim: take(&mut body, "truncate_dim")?,
+        text: take(&mut body, "text")?,
+        min_score: take(&mut body, "min_score")?,
+        ..SearchOptions::default()
+    };
+    let budget = match (take(&mut body, "budget_ms")?.map(Duration::from_millis), budget) {
+        (Some(asked), Some(most)) => Some(asked.min(most)),
+        (asked, most) => asked.or(most),
+    };
+    // `answer` has routed a streamed search already
+    take::<bool>(&mut body, "stream")?;
+    if let Some(key) = body.keys().next() {
+        anyhow::bail!("unknown search field `{}`", key);
+    }
+    Ok(SearchRequest { vector, k, modality, options, budget })
+}
+
+// Whether a `/search` body asks for NDJSON.
+fn streamed(body: &[u8]) -> bool {
+    serde_json::from_slice::<Value>(body).is_ok_and(|body| body["stream"] == Value::Bool(true))
+}
+
+// Answer a streamed `/search` on `stream`, hit by hit, within `budget`;
+// None once the reply is written, else the error to reply with.
+fn stream_search(db: &DB, access: &Access, stream: &mut impl Write, body: &[u8], budget: Option<Duration>) -> Option<Response> {
+    let request = match parse(body).and_then(|body| search_request(body, access, budget)) {
+        Ok(request) => request,
+        Err(e) => return Some(Response::error(400, format!("{:#}", e))),
+    };
+    let mut hits = db.search_iter(&request.vector, &request.modality, &request.options);
+    if let Some(budget) = request.budget {
+        hits = hits.until(Instant::now() + budget);
+    }
+    let mut ranked = hits.by_ref().take(request.k).peekable();
+    // a request the first page fails on still gets a status saying so
+    if let Some(Err(e)) = ranked.peek() {
+        return Some(Response::error(400, format!("{:#}", e)));
+    }
+    let head = "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nConnection: close\r\n\r\n";
+    if stream.write_all(head.as_bytes()).is_err() { return None; }
+    for hit in ranked {
+        let line = match hit {
+            Ok((id, score)) => json!({ "id": id, "score": score }),
+            Err(e) => json!({ "error": format!("{:#}", e) }),
+        };
+        // a client that hung up wants no more
+        if stream.write_all(format!("{}\n", line).as_bytes()).is_err() { return None; }
+    }
+    if hits.partial() {
+        let _ = stream.write_all(format!("{}\n", json!({ "partial": true })).as_bytes());
+    }
+    None
+}
+
+// The optional field `key` of a request, removed from it.
+fn take<T: serde::de::DeserializeOwned>(body: &mut Map<String, Value>, key: &str) -> anyhow::Result<Option<T>> {
+    body.remove(key)
+        .filter(|v| !v.is_null())
+        .map(|v| serde_json::from_value(v).map_err(|e| anyhow::anyhow!("bad `{}`: {}", key, e)))
+        .transpose()
+}
+
+fn get(db: &DB, access: &Access, id: u64) -> Response {
+    match db.record(id).filter(|r| !r.metadata.is_forgotten() && access.sees(&r.metadata)) {
+        Some(record) => Response::ok(serde_json::to_value(record).expect("records serialize")),
+        None => Response::error(404, format!("no record {}", id)),
+    }
+}
+
+// The `if_version` of a `/delete` body, which may be empty.
+fn if_version(body: &[u8]) -> anyhow::Result<Option<u64>> {
+    if body.is_empty() { return Ok(None); }
+    let Value::Object(mut body) = parse(body)? else { anyhow::bail!("expected a JSON object") };
+    take(&mut body, "if_version")
+}
+
+fn delete(db: &DB, access: &Access, id: u64, body: &[u8]) -> Response {
+    let if_version = match if_version(body) {
+        Ok(v) => v,
+        Err(e) => return Response::error(400, format!("{:#}", e)),
+    };
+    if let Some(meta) = db.get_metadata(id).filter(|m| !m.is_forgotten()) {
+        if !access.sees(&meta) { return Response::error(404, format!("no record {}", id)); }
+        if !access.owns(&meta) { return Response::error(403, format!("cannot delete record {}", id)); }
+    }
+    let result = match if_version {
+        Some(expected) => db.forget_if(id, expected),
+        None if db.version(id).is_none() => return Response::error(404, format!("no record {}", id)),
+        None => db.forget(id),
+    };
+    match result {
+        Ok(()) => Response::ok(json!({ "deleted": id })),
+        Err(e) if e.is::<VersionConflict>() => Response::error(409, e),
+        Err(e) => Response::error(400, e),
+    }
+}
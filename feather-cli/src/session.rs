@@ -0,0 +1,54 @@
+//! Scoping records to a session (`feather add --session`, `feather search
+//! --session` / `--exclude-session`, `feather sessions`).
+//!
+//! An agent keeps two kinds of memory in one file: scratch notes that only
+//! matter within the current conversation, and durable knowledge. A record
+//! written with a session id (`Metadata::session`) belongs to that session;
+//! one without is durable. Searches can keep to one session
+//! (`SearchOptions::session`, or `session = '…'` in a filter), answered from
+//! the core's attribute index rather than by testing every candidate, or
+//! leave one out (`SearchOptions::exclude_session`) — typically the current
+//! conversation, whose notes the agent already holds.
+//!
+//! When a session ends, its records can be made durable one by one
+//! (`set_session(id, None)`) and the rest forgotten together
+//! (`forget_session`). The session id is kept in the `_session` attribute,
+//! so it travels with exports, merges and forks like any other.
+
+use crate::DB;
+use std::collections::BTreeMap;
+
+impl DB {
+    /// The sessions with live records, and how many each has.
+    pub fn sessions(&self) -> BTreeMap<String, usize> {
+        let mut sessions = BTreeMap::new();
+        for id in self.all_ids() {
+            let Some(meta) = self.get_metadata(id).filter(|m| !m.is_forgotten()) else { continue };
+            if let Some(session) = meta.session() {
+                *sessions.entry(session.to_string()).or_default() += 1;
+            }
+        }
+        sessions
+    }
+
+    /// Move record `id` into `session`, or with None make it durable.
+    pub fn set_session(&self, id: u64, session: Option<&str>) -> anyhow::Result<()> {
+        let mut meta = self.get_metadata(id).filter(|m| !m.is_forgotten())
+            .ok_or_else(|| anyhow::anyhow!("no record {}", id))?;
+        if meta.session() == session.filter(|s| !s.is_empty()) { return Ok(()); }
+        meta.set_session(session);
+        self.put_metadata(id, &meta)
+    }
+
+    /// Forget every live record of `session`; returns how many.
+    pub fn forget_session(&self, session: &str) -> anyhow::Result<usize> {
+        self.writable()?;
+        let ids: Vec<u64> = self.all_ids().into_iter()
+            .filter(|&id| self.get_metadata(id).is_some_and(|m| !m.is_forgotten() && m.session() == Some(session)))
+            .collect();
+        for &id in &ids {
+            self.forget(id)?;
+        }
+        Ok(ids.len())
+    }
+}
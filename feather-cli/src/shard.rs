@@ -0,0 +1,152 @@
+//! Sharded stores: one store split across the files of a directory,
+//! `shard-0.feather` to `shard-<N-1>.feather`, each record held by the
+//! shard a hash of its id picks.
+//!
+//! A sharded store is opened like a file — `OpenOptions::shards` creates
+//! one, and opening a directory finds its shards — and used through the
+//! same `DB` API. Writes and lookups by id go to the record's shard alone;
+//! searches and listings fan out to every shard and merge what comes back.
+//! Links may cross shards. The shard count is fixed when the store is
+//! created, and the whole directory is locked as one store (`DIR.lock`).
+//!
+//! Each shard is an ordinary store file with its own WAL, which `fsck`
+//! checks one at a time. The wrapper state kept in properties —
+//! projections, collections, drift, normalization — lives in shard 0.
+//!
+//! What does not carry over: a transaction is atomic within each shard but
+//! not across them; BM25 scores a shard's hits by that shard's term
+//! statistics; and a sharded store cannot be forked, replicated or
+//! persisted elsewhere.
+
+use crate::*;
+use std::path::PathBuf;
+
+/// Property key marking a file as shard `i` of `n`.
+pub(crate) const PROPERTY_KEY: &str = "shard";
+
+/// Most shards a store can be split into.
+pub const MAX_SHARDS: usize = 256;
+
+/// The shard, of `count`, that holds internal id `id`.
+pub fn of(id: u64, count: usize) -> usize {
+    // splitmix64's finalizer: consecutive ids spread evenly
+    let mut z = id.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    ((z ^ (z >> 31)) % count as u64) as usize
+}
+
+/// Shard file `index` of the store in `dir`.
+pub fn file(dir: &Path, index: usize) -> PathBuf {
+    dir.join(format!("shard-{}.feather", index))
+}
+
+/// The shard files of the store in `dir`, in order; none if it is not one.
+pub fn files(dir: &Path) -> Vec<PathBuf> {
+    (0..).map(|i| file(dir, i)).take_while(|p| p.is_file()).collect()
+}
+
+fn encode(index: usize, count: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(8);
+    out.extend((index as u32).to_le_bytes());
+    out.extend((count as u32).to_le_bytes());
+    out
+}
+
+fn decode(bytes: &[u8]) -> Option<(usize, usize)> {
+    let index = u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?) as usize;
+    let count = u32::from_le_bytes(bytes.get(4..8)?.try_into().ok()?) as usize;
+    Some((index, count))
+}
+
+impl Handle {
+    // Every core of the store: its shards, or the one file.
+    pub(crate) fn cores(&self) -> &[*mut c_void] {
+        if self.shards.is_empty() { std::slice::from_ref(&self.ptr) } else { &self.shards }
+    }
+
+    // The core that holds (or is to hold) internal id `id`.
+    pub(crate) fn core(&self, id: u64) -> *mut c_void {
+        if self.shards.is_empty() { self.ptr } else { self.shards[of(id, self.shards.len())] }
+    }
+
+    pub(crate) fn is_sharded(&self) -> bool { !self.shards.is_empty() }
+}
+
+impl DB {
+    /// Number of shard files the store is split into; 1 for a single file.
+    pub fn shard_count(&self) -> usize { self.handle.cores().len() }
+
+    // Fail on a sharded store, for what works on one file only.
+    pub(crate) fn unsharded(&self, what: &str) -> anyhow::Result<()> {
+        anyhow::ensure!(!self.handle.is_sharded(), "a sharded store cannot be {}", what);
+        Ok(())
+    }
+
+    // Open the shards of the store in `dir` without taking the lock.
+    // `count` is how many it must have, 0 for whatever it has; with
+    // `create`, a directory without shards gets `count` new ones.
+    pub(crate) fn open_shards(dir: &Path, dim: usize, count: usize, create: bool) -> anyhow::Result<DB> {
+        let mut span = Span::new(Level::Info, "feather::open");
+        span.record_str("path", &dir.to_string_lossy());
+        let found = files(dir).len();
+        let (count, new) = match (found, count) {
+            (0, 0) => anyhow::bail!("no sharded store in {:?}", dir),
+            (0, n) => {
+                anyhow::ensure!(create, "no database at {:?}", dir);
+                anyhow::ensure!(n <= MAX_SHARDS, "at most {} shards", MAX_SHARDS);
+                std::fs::create_dir_all(dir)?;
+                (n, true)
+            }
+            (found, 0) => (found, false),
+            (found, n) => {
+                anyhow::ensure!(found == n, "{:?} holds {} shards, not {}", dir, found, n);
+                (n, false)
+            }
+        };
+
+        let mut cores = Vec::with_capacity(count);
+        let close = |cores: &[*mut c_void]| for &core in cores { unsafe { feather_close(core) } };
+        for index in 0..count {
+            let path = file(dir, index);
+            let c_path = c_str(path.to_str().ok_or_else(|| anyhow::anyhow!("path is not UTF-8: {:?}", path))?)?;
+            let core = unsafe { feather_open(c_path.as_ptr(), dim) };
+            if core.is_null() {
+                close(&cores);
+                anyhow::bail!("Open failed: {:?}: {}", path, crate::last_error());
+            }
+            cores.push(core);
+            let c_key = c_str(PROPERTY_KEY)?;
+            if new {
+                let marker = encode(index, count);
+                unsafe {
+                    feather_set_property(core, c_key.as_ptr(), marker.as_ptr().cast(), marker.len());
+                    feather_save(core);
+                }
+                continue;
+            }
+            let n = unsafe { feather_get_property(core, c_key.as_ptr(), std::ptr::null_mut(), 0) };
+            let mut buf = vec![0u8; usize::try_from(n).unwrap_or(0)];
+            unsafe { feather_get_property(core, c_key.as_ptr(), buf.as_mut_ptr().cast(), buf.len()) };
+            if n < 0 || decode(&buf) != Some((index, count)) {
+                close(&cores);
+                anyhow::bail!("{:?} is not shard {} of {}", path, index, count);
+            }
+        }
+
+        let mut handle = match Handle::new(cores[0]) {
+            Ok(handle) => handle,
+            Err(e) => {
+                close(&cores);
+                anyhow::bail!("cannot open {:?}: {:#}", file(dir, 0), e);
+            }
+        };
+        handle.shards = cores;
+        let db = DB { ptr: handle.ptr, handle: Rc::new(handle), scope: None };
+        if span.is_enabled() {
+            span.record("shards", count);
+            db.record_stats(&mut span);
+        }
+        Ok(db)
+    }
+}
@@ -0,0 +1,178 @@
+//! Stopping cleanly on SIGINT and SIGTERM (`feather serve`, `feather repl`,
+//! `feather mcp`).
+//!
+//! Every change reaches the WAL as it is made, but a process killed
+//! mid-way leaves the settings kept in properties unsaved, a WAL the next
+//! open has to replay, and a server's last requests unanswered. Once
+//! `on_signals` has run, SIGINT or SIGTERM instead asks the process to stop
+//! (`requested`): a server stops taking connections, answers the requests
+//! it has read and returns, and the REPL and MCP loops return at their next
+//! read, or as soon as the command they are running ends. Each saves as it
+//! returns, and the caller then drops its handles, which checkpoints each
+//! store and lets go of its lock. A second signal while stopping kills the
+//! process at once, as if none had been caught.
+//!
+//! `request` asks the same from within the process, e.g. to stop a server
+//! run on another thread. Catching signals is for Unix; elsewhere
+//! `on_signals` does nothing.
+
+use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+// Whether a stop has been asked for.
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+// What to run when it is, such as waking a thread blocked on accept.
+static WAKERS: Mutex<Vec<Box<dyn FnOnce() + Send>>> = Mutex::new(Vec::new());
+
+/// Whether the process has been asked to stop.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Ask the process to stop, as a signal would once `on_signals` has run.
+pub fn request() {
+    REQUESTED.store(true, Ordering::SeqCst);
+    wake();
+}
+
+// Run the wakers registered so far.
+fn wake() {
+    let wakers = std::mem::take(&mut *WAKERS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+    for wake in wakers {
+        wake();
+    }
+}
+
+// Run `wake` once a stop is asked for, or now if it has been.
+pub(crate) fn on_request(wake: impl FnOnce() + Send + 'static) {
+    let mut wakers = WAKERS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if requested() {
+        drop(wakers);
+        wake();
+    } else {
+        wakers.push(Box::new(wake));
+    }
+}
+
+/// Have SIGINT and SIGTERM ask the process to stop rather than kill it
+/// (see the module docs). Only the first call does anything.
+pub fn on_signals() -> anyhow::Result<()> {
+    static INSTALLED: AtomicBool = AtomicBool::new(false);
+    if INSTALLED.swap(true, Ordering::SeqCst) { return Ok(()); }
+    #[cfg(unix)]
+    signals::install().map_err(|e| anyhow::anyhow!("cannot catch signals: {}", e))?;
+    Ok(())
+}
+
+#[cfg(unix)]
+mod signals {
+    use std::fs::File;
+    use std::io::{self, Read};
+    use std::os::fd::FromRawFd;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    // The write end of the pipe that wakes the watching thread.
+    static PIPE: AtomicI32 = AtomicI32::new(-1);
+
+    const SIGNALS: [libc::c_int; 2] = [libc::SIGINT, libc::SIGTERM];
+
+    // Only what is async-signal-safe: the flag, the pipe, and `signal`.
+    extern "C" fn caught(_: libc::c_int) {
+        super::REQUESTED.store(true, Ordering::SeqCst);
+        let byte = 1u8;
+        unsafe {
+            for signal in SIGNALS {
+                libc::signal(signal, libc::SIG_DFL);
+            }
+            libc::write(PIPE.load(Ordering::SeqCst), &byte as *const u8 as *const libc::c_void, 1);
+        }
+    }
+
+    pub(super) fn install() -> io::Result<()> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        PIPE.store(fds[1], Ordering::SeqCst);
+        let mut pipe = unsafe { File::from_raw_fd(fds[0]) };
+        unsafe {
+            let mut set: libc::sigset_t = std::mem::zeroed();
+            let mut old: libc::sigset_t = std::mem::zeroed();
+            libc::sigemptyset(&mut set);
+            for signal in SIGNALS {
+                libc::sigaddset(&mut set, signal);
+            }
+            // the watcher is born with the signals blocked, so they land on
+            // the threads that may be waiting on a read
+            libc::pthread_sigmask(libc::SIG_BLOCK, &set, &mut old);
+            std::thread::spawn(move || {
+                let mut byte = [0u8];
+                if pipe.read(&mut byte).is_ok_and(|n| n == 1) {
+                    super::wake();
+                }
+            });
+            libc::pthread_sigmask(libc::SIG_SETMASK, &old, std::ptr::null_mut());
+            for signal in SIGNALS {
+                let mut action: libc::sigaction = std::mem::zeroed();
+                action.sa_sigaction = caught as *const () as libc::sighandler_t;
+                // no SA_RESTART: a read waiting for input returns, to see
+                // that it should stop
+                action.sa_flags = 0;
+                libc::sigemptyset(&mut action.sa_mask);
+                if libc::sigaction(signal, &action, std::ptr::null_mut()) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The lines of `input`, as `BufRead::lines` yields them, ending early once
+/// a stop is asked for, even while waiting for one.
+pub fn lines<B: BufRead>(input: B) -> Lines<B> {
+    Lines { input }
+}
+
+/// See `lines`.
+pub struct Lines<B> {
+    input: B,
+}
+
+impl<B: BufRead> Iterator for Lines<B> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = Vec::new();
+        loop {
+            if requested() { return None; }
+            let available = match self.input.fill_buf() {
+                Ok(available) => available,
+                // a signal: see whether it asks to stop
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Some(Err(e)),
+            };
+            if available.is_empty() { break; }
+            match available.iter().position(|&b| b == b'\n') {
+                Some(end) => {
+                    line.extend_from_slice(&available[..=end]);
+                    self.input.consume(end + 1);
+                    break;
+                }
+                None => {
+                    let n = available.len();
+                    line.extend_from_slice(available);
+                    self.input.consume(n);
+                }
+            }
+        }
+        if line.is_empty() { return None; }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+            if line.last() == Some(&b'\r') { line.pop(); }
+        }
+        Some(String::from_utf8(line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
+    }
+}
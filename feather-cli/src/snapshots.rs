@@ -0,0 +1,133 @@
+//! Past states of a store, kept for time travel (`DB::set_snapshots`,
+//! `DB::open_at`, `feather search --as-of`, `feather snapshots`).
+//!
+//! With snapshots on, every `save()` keeps the state it wrote: once the
+//! file is on disk it is hard-linked as `<store>.snapshots/<unix
+//! seconds>.feather`. A save writes a new file and renames it over the old
+//! one, so the link copies nothing, and holds only the space the next save
+//! would have freed (on a filesystem without hard links it is a copy). A
+//! second save within the same second replaces that second's snapshot, and
+//! beyond `keep` snapshots the oldest are removed.
+//!
+//! `open_at(path, t)` opens, read-only, the last snapshot taken at or
+//! before `t`: what the store held at its last save by then. So a change
+//! shows in the past from the save after it on; saving more often (see
+//! `OpenOptions::auto_save`) makes the history finer. Asking for a time
+//! before the first snapshot kept is an error. The number to keep is in
+//! the file's properties, so every later writer takes snapshots too; the
+//! save made when the last handle closes is not one of them. Sharded
+//! stores keep none.
+
+use crate::trace::{Level, Span};
+use crate::{Handle, OpenOptions, DB};
+use std::path::{Path, PathBuf};
+
+/// Property holding how many snapshots to keep, while snapshots are on.
+pub(crate) const PROPERTY_KEY: &str = "snapshots";
+
+/// Extension of a snapshot file.
+const EXTENSION: &str = "feather";
+
+/// The directory holding the snapshots of the store at `path`.
+pub fn dir(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".snapshots");
+    PathBuf::from(name)
+}
+
+/// The snapshots of the store at `path`, as (time taken, file), oldest
+/// first.
+pub fn list(path: &Path) -> anyhow::Result<Vec<(i64, PathBuf)>> {
+    let dir = dir(path);
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => anyhow::bail!("cannot read {:?}: {}", dir, e),
+    };
+    let mut snapshots = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_none_or(|e| e != EXTENSION) { continue; }
+        let Some(at) = path.file_stem().and_then(|s| s.to_str()).and_then(|s| s.parse::<i64>().ok()) else { continue };
+        snapshots.push((at, path));
+    }
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+/// The snapshot of the store at `path` that holds its state as of `at`:
+/// the last one taken at or before it.
+pub fn at(path: &Path, at: i64) -> anyhow::Result<PathBuf> {
+    anyhow::ensure!(!path.is_dir(), "a sharded store keeps no snapshots");
+    let snapshots = list(path)?;
+    let Some(first) = snapshots.first() else {
+        anyhow::bail!("{:?} has no snapshots; turn them on with `feather snapshots --keep N`", path);
+    };
+    anyhow::ensure!(at >= first.0, "{:?} has no snapshot from before {}; the first was taken at {}",
+                    path, crate::decay::format_time(at), crate::decay::format_time(first.0));
+    Ok(snapshots.into_iter().rev().find(|(taken, _)| *taken <= at).expect("first is at or before").1)
+}
+
+impl Handle {
+    // Keep the state the last save wrote, if snapshots are on.
+    pub(crate) fn snapshot_saved(&self) {
+        let Some(keep) = self.property(PROPERTY_KEY).and_then(|raw| Some(u32::from_le_bytes(raw.try_into().ok()?))) else {
+            return;
+        };
+        if keep == 0 || !self.shards.is_empty() { return; }
+        let Some(path) = self.path() else { return };
+        if let Err(e) = take(Path::new(&path), keep as usize) {
+            Span::new(Level::Warn, "feather::snapshots").record_str("error", &e.to_string());
+        }
+    }
+}
+
+// Link the file at `path` into its snapshots, and remove all but the
+// newest `keep`.
+fn take(path: &Path, keep: usize) -> anyhow::Result<()> {
+    let dir = dir(path);
+    std::fs::create_dir_all(&dir).map_err(|e| anyhow::anyhow!("cannot create {:?}: {}", dir, e))?;
+    let snapshot = dir.join(format!("{}.{}", crate::decay::now(), EXTENSION));
+    // this second's snapshot is replaced by the newer state
+    let _ = std::fs::remove_file(&snapshot);
+    if std::fs::hard_link(path, &snapshot).is_err() {
+        std::fs::copy(path, &snapshot).map_err(|e| anyhow::anyhow!("cannot snapshot {:?}: {}", path, e))?;
+    }
+    let snapshots = list(path)?;
+    for (_, old) in &snapshots[..snapshots.len().saturating_sub(keep)] {
+        std::fs::remove_file(old).map_err(|e| anyhow::anyhow!("cannot remove {:?}: {}", old, e))?;
+        let _ = std::fs::remove_file(crate::lock::lock_path(old));
+    }
+    Ok(())
+}
+
+impl DB {
+    /// How many snapshots every save keeps (see the module docs); 0 when
+    /// snapshots are off.
+    pub fn snapshots(&self) -> usize {
+        self.property(PROPERTY_KEY)
+            .and_then(|raw| Some(u32::from_le_bytes(raw.try_into().ok()?)))
+            .map_or(0, |keep| keep as usize)
+    }
+
+    /// Keep a snapshot of every save from now on, the newest `keep` of
+    /// them; 0 turns snapshots off, leaving those already taken. Saved
+    /// with the file, like any property.
+    pub fn set_snapshots(&self, keep: usize) -> anyhow::Result<()> {
+        self.writable()?;
+        self.unsharded("snapshotted")?;
+        if keep == 0 {
+            self.remove_property(PROPERTY_KEY)?;
+        } else {
+            let keep = u32::try_from(keep).map_err(|_| anyhow::anyhow!("cannot keep {} snapshots", keep))?;
+            self.set_property(PROPERTY_KEY, &keep.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// The store at `path` as it was at `at` (Unix seconds), read-only:
+    /// its last snapshot taken by then (see the module docs).
+    pub fn open_at(path: &Path, at: i64) -> anyhow::Result<DB> {
+        OpenOptions::new().as_of(at).open(path)
+    }
+}
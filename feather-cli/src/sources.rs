@@ -0,0 +1,66 @@
+//! The sources records come from (`DB::sources`, `DB::rename_source`,
+//! `feather sources`, `feather rename-source`).
+//!
+//! `source` is a free-form string on each record, so a typo in an ingest
+//! job leaves a second spelling behind. `sources` lists the spellings in
+//! use with their live record counts; `rename_source` moves every record of
+//! one to another, merging the two if the new name is already in use. The
+//! core renames in a single pass, touching only the source index and
+//! logging each record to the WAL, so a rename costs one pass however many
+//! records it moves. A rename relabels records rather than rewriting them:
+//! their versions stay as they were.
+
+use crate::*;
+use std::collections::BTreeMap;
+
+extern "C" {
+    fn feather_rename_source(db: *mut c_void, ids: *const u64, n: usize, from: *const c_char, to: *const c_char,
+                             out: *mut u64, cap: usize) -> usize;
+}
+
+impl DB {
+    /// The sources of live records, and how many records each has.
+    pub fn sources(&self) -> BTreeMap<String, usize> {
+        let mut sources = BTreeMap::new();
+        for id in self.all_ids() {
+            let Some(meta) = self.get_metadata(id).filter(|m| !m.is_forgotten()) else { continue };
+            *sources.entry(meta.source).or_default() += 1;
+        }
+        sources
+    }
+
+    /// Give every live record from source `from` the source `to`; returns
+    /// how many records were renamed. On a fork, renamed records of the
+    /// base are copied into the fork like any other write to them.
+    pub fn rename_source(&self, from: &str, to: &str) -> anyhow::Result<usize> {
+        self.writable()?;
+        anyhow::ensure!(from != to, "'{}' is already the name", from);
+        if self.handle.fork.is_some() {
+            let mut n = 0;
+            for id in self.all_ids() {
+                let Some(mut meta) = self.get_metadata(id).filter(|m| !m.is_forgotten() && m.source == from) else { continue };
+                meta.source = to.to_string();
+                self.put_metadata(id, &meta)?;
+                n += 1;
+            }
+            return Ok(n);
+        }
+        let ids: Vec<u64> = self.handle.all_ids().into_iter().filter(|&iid| self.xid(iid).is_some()).collect();
+        let (c_from, c_to) = (c_str(from)?, c_str(to)?);
+        let mut n = 0;
+        for &core in self.handle.cores() {
+            let mut renamed = vec![0u64; ids.len()];
+            let count = unsafe {
+                feather_rename_source(core, ids.as_ptr(), ids.len(), c_from.as_ptr(), c_to.as_ptr(),
+                                      renamed.as_mut_ptr(), renamed.len())
+            };
+            renamed.truncate(count);
+            for iid in renamed {
+                self.handle.changed(audit::Op::Update, iid, None, None);
+            }
+            n += count;
+        }
+        self.maintain_if_due();
+        Ok(n)
+    }
+}
@@ -0,0 +1,171 @@
+//! Sparse vectors: `(dimension, weight)` pairs such as SPLADE term weights.
+//!
+//! A record can carry sparse vectors next to its dense ones, each under a
+//! name (`sparse` unless told otherwise) the way dense vectors live under a
+//! modality. The core keeps an inverted index per name and ranks records by
+//! the dot product of their vector with the query; `search_with_options`
+//! fuses that ranking with the dense one (see `SearchOptions::sparse`).
+
+use crate::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Name sparse vectors are stored under unless told otherwise.
+pub const DEFAULT_NAME: &str = "sparse";
+
+/// Dimensions in increasing order, each with a non-zero weight.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Parts")]
+pub struct SparseVector {
+    pub indices: Vec<u32>,
+    pub values: Vec<f32>,
+}
+
+// The serialized form, checked on the way in.
+#[derive(Deserialize)]
+struct Parts {
+    indices: Vec<u32>,
+    values: Vec<f32>,
+}
+
+impl TryFrom<Parts> for SparseVector {
+    type Error = anyhow::Error;
+
+    fn try_from(parts: Parts) -> anyhow::Result<Self> {
+        anyhow::ensure!(parts.indices.len() == parts.values.len(),
+                        "{} indices but {} values", parts.indices.len(), parts.values.len());
+        SparseVector::new(parts.indices.into_iter().zip(parts.values).collect())
+    }
+}
+
+impl SparseVector {
+    /// A vector from `(dimension, weight)` pairs in any order. Zero weights
+    /// are dropped; a repeated dimension or a non-finite weight is an error.
+    pub fn new(mut pairs: Vec<(u32, f32)>) -> anyhow::Result<Self> {
+        if let Some(&(dim, w)) = pairs.iter().find(|(_, w)| !w.is_finite()) {
+            anyhow::bail!("dimension {}: weight {} is not finite", dim, w);
+        }
+        pairs.retain(|&(_, w)| w != 0.0);
+        pairs.sort_by_key(|&(dim, _)| dim);
+        if let Some(pair) = pairs.windows(2).find(|p| p[0].0 == p[1].0) {
+            anyhow::bail!("dimension {} given twice", pair[0].0);
+        }
+        let (indices, values) = pairs.into_iter().unzip();
+        Ok(SparseVector { indices, values })
+    }
+
+    /// Parse `dim:weight` pairs separated by commas or whitespace
+    /// (`12:0.5,873:1.2`), or a JSON object: `{"12": 0.5, "873": 1.2}` or
+    /// `{"indices": [12, 873], "values": [0.5, 1.2]}`.
+    pub fn parse(s: &str) -> anyhow::Result<Self> {
+        let s = s.trim();
+        if s.starts_with('{') {
+            let value: serde_json::Value = serde_json::from_str(s)?;
+            if value.get("indices").is_some() {
+                return Ok(serde_json::from_value(value)?);
+            }
+            let weights: BTreeMap<String, f32> = serde_json::from_value(value)?;
+            let pairs = weights.into_iter()
+                .map(|(dim, w)| Ok((dim.trim().parse().map_err(|_| anyhow::anyhow!("bad dimension `{}`", dim))?, w)))
+                .collect::<anyhow::Result<_>>()?;
+            return SparseVector::new(pairs);
+        }
+        let pairs = s.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|p| !p.is_empty())
+            .map(|pair| {
+                let (dim, w) = pair.split_once(':')
+                    .ok_or_else(|| anyhow::anyhow!("expected `dim:weight`, got `{}`", pair))?;
+                let dim = dim.parse().map_err(|_| anyhow::anyhow!("bad dimension `{}`", dim))?;
+                let w = w.parse().map_err(|_| anyhow::anyhow!("bad weight `{}`", w))?;
+                Ok((dim, w))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        SparseVector::new(pairs)
+    }
+
+    pub fn len(&self) -> usize { self.indices.len() }
+
+    pub fn is_empty(&self) -> bool { self.indices.is_empty() }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, f32)> + '_ {
+        self.indices.iter().copied().zip(self.values.iter().copied())
+    }
+
+    pub fn dot(&self, other: &SparseVector) -> f32 {
+        let (mut i, mut j, mut sum) = (0, 0, 0.0);
+        while i < self.len() && j < other.len() {
+            match self.indices[i].cmp(&other.indices[j]) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    sum += self.values[i] * other.values[j];
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        sum
+    }
+}
+
+impl std::str::FromStr for SparseVector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> { SparseVector::parse(s) }
+}
+
+impl DB {
+    /// Attach `vector` to the existing record `id` under `name`, replacing
+    /// the one it had there; an empty vector removes it.
+    pub fn set_sparse(&self, id: u64, name: &str, vector: &SparseVector) -> anyhow::Result<()> {
+        self.writable()?;
+        let internal = self.iid(id)?;
+        let name = self.mname(Some(name)).expect("named");
+        let c_name = c_str(&name)?;
+        let before = self.stored_version(internal);
+        self.handle.copy_up(internal);
+        let set = unsafe {
+            feather_set_sparse(self.handle.core(internal), internal, c_name.as_ptr(), vector.indices.as_ptr(),
+                               vector.values.as_ptr(), vector.len())
+        };
+        anyhow::ensure!(set != 0, "no record {}", id);
+        self.stamp(internal, before);
+        self.changed(audit::Op::Update, internal, None, None);
+        Ok(())
+    }
+
+    /// The sparse vector of `id` under `name`, if it has one.
+    pub fn get_sparse(&self, id: u64, name: &str) -> Option<SparseVector> {
+        self.handle.sparse(self.iid(id).ok()?, &self.mname(Some(name))?)
+    }
+
+    /// Records whose sparse vector under `name` has the largest dot product
+    /// with `query`, as `(id, score)`, best first; records sharing no
+    /// dimension with the query are left out. Like `knn`, hits are not
+    /// counted as recalled.
+    pub fn sparse_knn(&self, query: &SparseVector, k: usize, name: &str) -> anyhow::Result<Vec<(u64, f32)>> {
+        self.sparse_knn_within(query, k, name, false)
+    }
+
+    // `sparse_knn`, archived records included if `include_archived`.
+    pub(crate) fn sparse_knn_within(&self, query: &SparseVector, k: usize, name: &str,
+                                    include_archived: bool) -> anyhow::Result<Vec<(u64, f32)>> {
+        let name = self.mname(Some(name)).expect("named");
+        Ok(self.handle.sparse_knn(query, k, &name, include_archived)?
+            .into_iter()
+            .filter_map(|(id, score)| Some((self.xid(id)?, score)))
+            .collect())
+    }
+
+    /// `sparse_knn` as a search: the hits count as recalled.
+    pub fn sparse_search(&self, query: &SparseVector, k: usize, name: &str) -> anyhow::Result<Vec<(u64, f32)>> {
+        let hits = self.sparse_knn(query, k, name)?;
+        self.recalled(hits.iter().map(|&(id, _)| id));
+        Ok(hits)
+    }
+
+    /// Names of the sparse vector sets in this DB (or collection).
+    pub fn sparse_names(&self) -> Vec<String> {
+        self.scoped_names(self.handle.sparse_names())
+    }
+}
@@ -0,0 +1,111 @@
+//! Search results a page at a time (`DB::search_iter`, `/search` with
+//! `"stream": true`).
+//!
+//! `search_with_options` ranks and returns all k hits at once. For a large
+//! k, or a filter that has to look through much of the store, a caller that
+//! may stop early — at the first hit over a token budget, say — pays for
+//! the whole list. `SearchIter` yields the same ranking hit by hit, fetching
+//! it in pages that start at `FIRST_PAGE` hits and double up to `MAX_PAGE`:
+//! each page is ranked afresh from the hits already yielded on, so what is
+//! ranked stays within about twice what was consumed. It has no k;
+//! `take(k)` bounds it.
+//!
+//! `until(deadline)` stops it from ranking a page once `deadline` has
+//! passed: the hits already ranked are still yielded, and then it ends,
+//! `partial()` saying it ended early. What it yielded is then the best of
+//! the full ranking, in order, just fewer of them.
+//!
+//! Each hit counts as recalled when it is yielded. A record the index
+//! ranks differently from one page to the next is yielded once, at its
+//! first place.
+
+use crate::rerank::Query;
+use crate::{SearchOptions, DB};
+use std::collections::{HashSet, VecDeque};
+use std::time::Instant;
+
+/// Hits ranked for the first page.
+pub const FIRST_PAGE: usize = 16;
+
+/// Most hits ranked for one page.
+pub const MAX_PAGE: usize = 4096;
+
+/// The hits of a search, best first, as `(id, score)`; see the module
+/// docs. A failed page is yielded as an error and ends the iteration.
+pub struct SearchIter<'a> {
+    db: &'a DB,
+    query: Vec<f32>,
+    modality: String,
+    options: SearchOptions,
+    page: usize,
+    pending: VecDeque<(u64, f32)>,
+    seen: HashSet<u64>,
+    done: bool,
+    deadline: Option<Instant>,
+    partial: bool,
+}
+
+impl DB {
+    /// `search_with_options` as an iterator, from `options.offset` on, that
+    /// ranks only as far as it is consumed.
+    pub fn search_iter(&self, query: &[f32], modality: &str, options: &SearchOptions) -> SearchIter<'_> {
+        let internal = self.mname(Some(modality)).expect("named");
+        self.observe_query(Some(&internal), &self.project(Some(&internal), query));
+        SearchIter {
+            db: self,
+            query: query.to_vec(),
+            modality: modality.to_string(),
+            options: options.clone(),
+            page: FIRST_PAGE,
+            pending: VecDeque::new(),
+            seen: HashSet::new(),
+            done: false,
+            deadline: None,
+            partial: false,
+        }
+    }
+}
+
+impl SearchIter<'_> {
+    /// Rank no page once `deadline` has passed (see the module docs).
+    pub fn until(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Whether the deadline ended the hits before the ranking did.
+    pub fn partial(&self) -> bool { self.partial }
+
+    // Rank the next page into `pending`.
+    fn fetch(&mut self) -> anyhow::Result<()> {
+        let query = Query { vector: &self.query, text: self.options.text.as_deref() };
+        let hits = self.db.ranked(query, self.page, &self.modality, &self.options, None)?;
+        self.done = hits.len() < self.page;
+        self.options.offset += hits.len();
+        self.page = (self.page * 2).min(MAX_PAGE);
+        self.pending.extend(hits.into_iter().filter(|(id, _)| !self.seen.contains(id)));
+        Ok(())
+    }
+}
+
+impl Iterator for SearchIter<'_> {
+    type Item = anyhow::Result<(u64, f32)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() {
+            if self.done { return None; }
+            if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                self.partial = true;
+                return None;
+            }
+            if let Err(e) = self.fetch() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+        let (id, score) = self.pending.pop_front()?;
+        self.seen.insert(id);
+        self.db.recalled([id]);
+        Some(Ok((id, score)))
+    }
+}
@@ -0,0 +1,133 @@
+//! How memories are associated: the shortest chain of links between two
+//! records (`DB::path`, `feather path`) and the records around a few, with
+//! every link among them (`DB::subgraph`, `feather subgraph`).
+//!
+//! Both walk links in either direction, as `DB::neighbors` does, and only
+//! between live records. `DB::to_dot` draws what they find as a Graphviz
+//! DOT graph, each record labelled with its id and the start of its
+//! content.
+
+use crate::*;
+use serde::Serialize;
+use std::collections::{BTreeSet, VecDeque};
+
+/// Most links `feather path` follows unless told otherwise.
+pub const DEFAULT_MAX_DEPTH: usize = 6;
+
+/// Characters of content in a DOT label.
+const LABEL_CHARS: usize = 40;
+
+/// Records and links between them.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Subgraph {
+    /// The records, by id.
+    pub nodes: Vec<u64>,
+    /// Links between two of them, by `from`, then as the record holds them.
+    pub links: Vec<Link>,
+}
+
+impl Subgraph {
+    /// The records and links of a path from `start`, as `DB::path` returns
+    /// it, in path order.
+    pub fn of_path(start: u64, path: &[Link]) -> Subgraph {
+        let mut nodes = vec![start];
+        for link in path {
+            let last = *nodes.last().expect("starts with one");
+            nodes.push(link.other(last));
+        }
+        Subgraph { nodes, links: path.to_vec() }
+    }
+}
+
+impl DB {
+    // The live record `id`'s metadata, or an error naming it.
+    fn live_metadata(&self, id: u64) -> anyhow::Result<Metadata> {
+        self.get_metadata(id).filter(|m| !m.is_forgotten()).ok_or_else(|| anyhow::anyhow!("no record {}", id))
+    }
+
+    /// The links of a shortest chain from `from` to `to`, at most
+    /// `max_depth` of them, each as stored, so it may point back along the
+    /// chain. Empty if `from == to`; None if no chain is that short. Among
+    /// equally short chains, the one reached first by following each
+    /// record's links in `DB::links` order.
+    pub fn path(&self, from: u64, to: u64, max_depth: usize) -> anyhow::Result<Option<Vec<Link>>> {
+        self.live_metadata(from)?;
+        self.live_metadata(to)?;
+        if from == to { return Ok(Some(Vec::new())); }
+        // each record reached -> the link it was first reached by
+        let mut via: HashMap<u64, Link> = HashMap::new();
+        let mut queue = VecDeque::from([(from, 0)]);
+        while let Some((node, hops)) = queue.pop_front() {
+            if hops == max_depth { continue; }
+            for link in self.links(node) {
+                let next = link.other(node);
+                if next == from || via.contains_key(&next) { continue; }
+                via.insert(next, link);
+                if next == to {
+                    let mut path = Vec::new();
+                    let mut at = to;
+                    while at != from {
+                        let link = via.remove(&at).expect("reached");
+                        at = link.other(at);
+                        path.push(link);
+                    }
+                    path.reverse();
+                    return Ok(Some(path));
+                }
+                queue.push_back((next, hops + 1));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The live records within `depth` links of any of `ids`, these
+    /// included, and every link between two of them. Depth 0 gives just the
+    /// links among `ids`.
+    pub fn subgraph(&self, ids: &[u64], depth: usize) -> anyhow::Result<Subgraph> {
+        let mut seen = BTreeSet::new();
+        let mut queue = VecDeque::new();
+        for &id in ids {
+            self.live_metadata(id)?;
+            if seen.insert(id) { queue.push_back((id, 0)); }
+        }
+        while let Some((node, hops)) = queue.pop_front() {
+            if hops == depth { continue; }
+            for link in self.links(node) {
+                let next = link.other(node);
+                if seen.insert(next) { queue.push_back((next, hops + 1)); }
+            }
+        }
+        let mut links = Vec::new();
+        for &id in &seen {
+            let meta = self.live_metadata(id)?;
+            links.extend(meta.edges.into_iter()
+                .filter(|e| seen.contains(&e.target))
+                .map(|e| Link { from: id, to: e.target, rel_type: e.rel_type, weight: e.weight }));
+        }
+        Ok(Subgraph { nodes: seen.into_iter().collect(), links })
+    }
+
+    /// `graph` as a Graphviz DOT digraph, the records of `marked` drawn in
+    /// bold and each link labelled with its type and weight.
+    pub fn to_dot(&self, graph: &Subgraph, marked: &[u64]) -> String {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"));
+        let mut dot = String::from("digraph memories {\n  node [shape=box];\n");
+        for &id in &graph.nodes {
+            let content = self.get_metadata(id).map(|m| m.content).unwrap_or_default();
+            let mut label = format!("{}", id);
+            if !content.is_empty() {
+                let short: String = content.chars().take(LABEL_CHARS).collect();
+                let more = if content.chars().count() > LABEL_CHARS { "…" } else { "" };
+                label = format!("{}: {}{}", id, short, more);
+            }
+            let style = if marked.contains(&id) { ", style=bold" } else { "" };
+            dot.push_str(&format!("  {} [label={}{}];\n", id, quote(&label), style));
+        }
+        for link in &graph.links {
+            let label = format!("{} {:.2}", link.rel_type, link.weight);
+            dot.push_str(&format!("  {} -> {} [label={}];\n", link.from, link.to, quote(&label)));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
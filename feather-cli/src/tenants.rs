@@ -0,0 +1,252 @@
+//! Many stores served from one process (`feather serve --data-dir`), so
+//! one daemon can hold the memory of a whole fleet of agents.
+//!
+//! A data dir holds one store per name, as `<name>.feather`; a name is 1
+//! to `MAX_NAME` letters, digits, `-` and `_`. Every endpoint of `serve` is
+//! there for each store under `/db/{name}` (`/db/{name}/search`,
+//! `/db/{name}/get/{id}`, ...), and:
+//!
+//! - `GET /db` lists the stores: `{"databases": [name, ...]}`.
+//! - `PUT /db/{name}` creates an empty store; replies `{"created": name}`,
+//!   or 409 if there is one already.
+//! - `DELETE /db/{name}` closes the store and removes it, with its WAL,
+//!   lock, audit log, snapshots and backup states; replies
+//!   `{"dropped": name}`. A store another process has open is answered
+//!   409, and left alone.
+//! - `GET /metrics` covers every store (see `metrics`), and `GET /readyz`
+//!   the room to save them all.
+//!
+//! Stores are created only by `PUT`: a request to a store that does not
+//! exist is answered 404. Tokens and limits apply across all of them, and
+//! with tokens, creating and dropping a store takes an API key. A store is
+//! opened at its first request and kept open, and checkpointed every
+//! `CHECKPOINT_EVERY` writes of its own.
+
+use crate::lock::{self, FileLock, LockMode};
+use crate::metrics::{self, Metrics};
+use crate::serve::{self, Connection, Request, Response, CHECKPOINT_EVERY};
+use crate::trace::{Level, Span};
+use crate::doctor::{self, Finding};
+use crate::{audit, backup, snapshots, Access, Limits, Locked, OpenOptions, Tokens, DB};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Extension of a store file in a data dir.
+pub const EXTENSION: &str = "feather";
+
+/// Longest store name.
+pub const MAX_NAME: usize = 64;
+
+/// The stores of a data dir, and those of them open.
+pub struct DataDir {
+    dir: PathBuf,
+    open: BTreeMap<String, Store>,
+}
+
+// An open store, and its writes since it was last checkpointed.
+struct Store {
+    db: DB,
+    writes: usize,
+}
+
+impl DataDir {
+    /// The data dir at `dir`, created if missing.
+    pub fn new(dir: &Path) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(dir).map_err(|e| anyhow::anyhow!("cannot create {:?}: {}", dir, e))?;
+        Ok(DataDir { dir: dir.to_path_buf(), open: BTreeMap::new() })
+    }
+
+    /// The names of its stores, sorted.
+    pub fn names(&self) -> anyhow::Result<Vec<String>> {
+        let entries = std::fs::read_dir(&self.dir).map_err(|e| anyhow::anyhow!("cannot read {:?}: {}", self.dir, e))?;
+        let mut names = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if !path.is_file() || path.extension().is_none_or(|e| e != EXTENSION) { continue; }
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()) else { continue };
+            if valid(name) { names.push(name.to_string()); }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// The file of the store `name`; fails if the name is not valid.
+    pub fn path(&self, name: &str) -> anyhow::Result<PathBuf> {
+        anyhow::ensure!(valid(name), "bad database name '{}': 1 to {} letters, digits, '-' and '_'", name, MAX_NAME);
+        Ok(self.dir.join(format!("{}.{}", name, EXTENSION)))
+    }
+
+    /// The store `name`, opened if it is not yet; None if there is none.
+    pub fn get(&mut self, name: &str) -> anyhow::Result<Option<&DB>> {
+        Ok(self.store(name)?.map(|store| &store.db))
+    }
+
+    fn store(&mut self, name: &str) -> anyhow::Result<Option<&mut Store>> {
+        if !self.open.contains_key(name) {
+            let path = self.path(name)?;
+            if !path.is_file() { return Ok(None); }
+            let db = OpenOptions::new().open(&path)?;
+            db.expire()?;
+            self.open.insert(name.to_string(), Store { db, writes: 0 });
+        }
+        Ok(self.open.get_mut(name))
+    }
+
+    /// Create the store `name`, empty; fails if there is one.
+    pub fn create(&mut self, name: &str) -> anyhow::Result<&DB> {
+        let path = self.path(name)?;
+        let db = OpenOptions::new().create_new(true).open(&path)?;
+        // on disk at once, so that it is listed
+        db.save()?;
+        let store = self.open.entry(name.to_string()).insert_entry(Store { db, writes: 0 });
+        Ok(&store.into_mut().db)
+    }
+
+    /// Close the store `name` and remove its files; false if there was
+    /// none. Fails with `Locked` if another process has it open.
+    pub fn remove(&mut self, name: &str) -> anyhow::Result<bool> {
+        let path = self.path(name)?;
+        // closing saves it, and lets go of the lock
+        self.open.remove(name);
+        if !path.is_file() { return Ok(false); }
+        let _lock = FileLock::acquire(&path, LockMode::Exclusive)?;
+        let mut wal = path.as_os_str().to_owned();
+        wal.push(".wal");
+        for file in [path.clone(), PathBuf::from(wal), audit::log_path(&path), lock::lock_path(&path)] {
+            match std::fs::remove_file(&file) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => anyhow::bail!("cannot remove {:?}: {}", file, e),
+                _ => {}
+            }
+        }
+        for kept in [snapshots::dir(&path), backup::dir(&path)] {
+            if kept.is_dir() {
+                std::fs::remove_dir_all(&kept).map_err(|e| anyhow::anyhow!("cannot remove {:?}: {}", kept, e))?;
+            }
+        }
+        Ok(true)
+    }
+
+    // Answer `request`, admitted with `access`, on `stream`, searching
+    // within `budget`.
+    fn answer(&mut self, stream: &mut impl Write, request: Request, access: &Access, metrics: &mut Metrics,
+              budget: Option<Duration>) {
+        let start = Instant::now();
+        let path = request.path.trim_end_matches('/').to_string();
+        if request.method == "GET" && path == "/metrics" {
+            let stores: Vec<(&str, &DB)> = self.open.iter().map(|(name, store)| (name.as_str(), &store.db)).collect();
+            serve::respond(stream, 200, metrics::CONTENT_TYPE, &metrics.render_stores(&stores));
+            return;
+        }
+        if request.path == "/readyz" {
+            let response = match request.method.as_str() {
+                "GET" => serve::ready(&self.readiness()),
+                _ => Response::error(405, "/readyz takes GET"),
+            };
+            metrics.observe(&request.path, &response, start.elapsed());
+            serve::reply(stream, &response);
+            return;
+        }
+        let response = match path.strip_prefix("/db").map(|rest| rest.trim_start_matches('/')) {
+            Some("") if request.method == "GET" => match self.names() {
+                Ok(names) => Response::ok(json!({ "databases": names })),
+                Err(e) => Response::error(400, format!("{:#}", e)),
+            },
+            Some("") => Response::error(405, "/db takes GET"),
+            Some(rest) if path.starts_with("/db/") => match rest.split_once('/') {
+                Some((name, route)) => {
+                    let request = Request { path: format!("/{}", route), ..request };
+                    return self.answer_store(name, stream, request, access, metrics, budget);
+                }
+                None => self.manage(&request.method, rest, access),
+            },
+            _ => Response::error(404, format!("no endpoint {}; the stores are under /db/{{name}}", request.path)),
+        };
+        metrics.observe(&request.path, &response, start.elapsed());
+        serve::reply(stream, &response);
+    }
+
+    // `doctor::readiness` of the dir and the stores in it.
+    fn readiness(&self) -> Vec<Finding> {
+        let mut files = vec![self.dir.clone()];
+        files.extend(self.names().unwrap_or_default().iter().filter_map(|name| self.path(name).ok()));
+        doctor::readiness(&files)
+    }
+
+    // Answer `request`, its path within the store `name`.
+    fn answer_store(&mut self, name: &str, stream: &mut impl Write, request: Request, access: &Access,
+                    metrics: &mut Metrics, budget: Option<Duration>) {
+        let refusal = match self.store(name) {
+            Ok(Some(store)) => {
+                if serve::answer(&store.db, stream, &request, access, metrics, budget) {
+                    store.writes += 1;
+                    if store.writes >= CHECKPOINT_EVERY {
+                        if let Err(e) = store.db.save() {
+                            Span::new(Level::Warn, "feather::serve").record_str("error", &format!("{}: {}", name, e));
+                        }
+                        store.writes = 0;
+                    }
+                }
+                return;
+            }
+            Ok(None) => Response::error(404, format!("no database '{}'", name)),
+            Err(e) if e.is::<Locked>() => Response::error(409, e),
+            Err(e) => Response::error(400, format!("{:#}", e)),
+        };
+        metrics.observe(&request.path, &refusal, Duration::ZERO);
+        serve::reply(stream, &refusal);
+    }
+
+    // Create or drop the store `name`, as `method` says.
+    fn manage(&mut self, method: &str, name: &str, access: &Access) -> Response {
+        if method != "PUT" && method != "DELETE" {
+            return Response::error(405, "/db/{name} takes PUT or DELETE");
+        }
+        if *access != Access::All {
+            return Response::error(403, "creating and dropping databases takes an API key");
+        }
+        let path = match self.path(name) {
+            Ok(path) => path,
+            Err(e) => return Response::error(400, e),
+        };
+        let result = match method {
+            "PUT" if path.exists() => return Response::error(409, format!("database '{}' exists already", name)),
+            "PUT" => self.create(name).map(|_| json!({ "created": name })),
+            _ => match self.remove(name) {
+                Ok(false) => return Response::error(404, format!("no database '{}'", name)),
+                removed => removed.map(|_| json!({ "dropped": name })),
+            },
+        };
+        match result {
+            Ok(body) => Response::ok(body),
+            Err(e) if e.is::<Locked>() => Response::error(409, e),
+            Err(e) => Response::error(400, format!("{:#}", e)),
+        }
+    }
+}
+
+// Whether `name` may name a store.
+fn valid(name: &str) -> bool {
+    (1..=MAX_NAME).contains(&name.len()) && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// Answer connections on `listener` for the stores of `dir`, answering
+/// only requests with one of `tokens` if given, within `limits`, until it
+/// fails.
+pub fn serve(dir: &Path, listener: &TcpListener, tokens: Option<&Tokens>, limits: &Limits) -> anyhow::Result<()> {
+    serve_connections(dir, serve::accept(listener)?, tokens, limits)
+}
+
+/// `serve`, answering each connection `connections` yields, as
+/// `serve::serve_connections` does.
+pub fn serve_connections<S: Connection>(dir: &Path, connections: impl IntoIterator<Item = std::io::Result<S>, IntoIter: Send + 'static>,
+                                        tokens: Option<&Tokens>, limits: &Limits) -> anyhow::Result<()> {
+    let mut stores = DataDir::new(dir)?;
+    serve::dispatch(connections, tokens, limits, None, || Ok(()), |stream, request, access, metrics| {
+        stores.answer(stream, request, access, metrics, limits.search_budget);
+        Ok(())
+    })
+}
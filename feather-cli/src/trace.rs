@@ -0,0 +1,171 @@
+//! Timed spans around the store's expensive operations — open, save, add,
+//! search — written as structured lines on stderr, for diagnosing slow
+//! queries and long saves in production.
+//!
+//! Off unless `RUST_LOG` asks for them. It takes the usual comma-separated
+//! directives, each a level or `target=level`, the longest matching target
+//! winning:
+//!
+//! ```text
+//! RUST_LOG=info                          # opens and saves
+//! RUST_LOG=feather=debug                 # ... and batches and searches
+//! RUST_LOG=info,feather::search=trace    # opens, saves, and every search
+//! ```
+//!
+//! Targets are `feather::open`, `feather::save`, `feather::add`,
+//! `feather::search` and `feather::replicate`. Opens, saves and replica
+//! connections log at `info`, dropped replicas at `warn`, batch inserts
+//! and searches at `debug`, single inserts at `trace`. A line gives the
+//! time, level, target, the span's fields and its duration:
+//!
+//! ```text
+//! 2026-10-15T09:12:03.120Z DEBUG feather::search: modality="text" k=10 hits=10 elapsed_ms=0.412
+//! ```
+
+use std::fmt::{Display, Write as _};
+use std::io::Write as _;
+use std::sync::OnceLock;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Variable holding the filter directives.
+pub const FILTER_VAR: &str = "RUST_LOG";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    fn name(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+}
+
+// The most verbose level each target logs at; 0 for off.
+#[derive(Debug, Default)]
+struct Filter {
+    default: u8,
+    /// `(target, level)`, longest target first.
+    targets: Vec<(String, u8)>,
+}
+
+impl Filter {
+    // Directives that do not parse are skipped, as env_logger does.
+    fn parse(spec: &str) -> Filter {
+        let mut filter = Filter::default();
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    if let Some(level) = level_code(level) {
+                        filter.targets.push((target.trim().to_string(), level));
+                    }
+                }
+                // a bare target turns everything on for it
+                None => match level_code(directive) {
+                    Some(level) => filter.default = level,
+                    None => filter.targets.push((directive.to_string(), Level::Trace as u8)),
+                },
+            }
+        }
+        filter.targets.sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+        filter
+    }
+
+    fn enabled(&self, target: &str, level: Level) -> bool {
+        let max = self.targets.iter()
+            .find(|(t, _)| target.strip_prefix(t.as_str()).is_some_and(|rest| rest.is_empty() || rest.starts_with("::")))
+            .map_or(self.default, |&(_, l)| l);
+        level as u8 <= max
+    }
+}
+
+fn level_code(s: &str) -> Option<u8> {
+    Some(match s.trim().to_ascii_lowercase().as_str() {
+        "off" => 0,
+        "error" => Level::Error as u8,
+        "warn" => Level::Warn as u8,
+        "info" => Level::Info as u8,
+        "debug" => Level::Debug as u8,
+        "trace" => Level::Trace as u8,
+        _ => return None,
+    })
+}
+
+fn filter() -> &'static Filter {
+    static FILTER: OnceLock<Filter> = OnceLock::new();
+    FILTER.get_or_init(|| Filter::parse(&std::env::var(FILTER_VAR).unwrap_or_default()))
+}
+
+/// Whether `RUST_LOG` lets `target` log at `level`.
+pub fn enabled(target: &str, level: Level) -> bool {
+    filter().enabled(target, level)
+}
+
+/// An operation being timed. Its line, with the fields recorded on it and
+/// the time since `Span::new`, is written when it is dropped; a span
+/// `RUST_LOG` filters out records nothing and costs next to nothing.
+pub struct Span {
+    // None when filtered out
+    inner: Option<Inner>,
+}
+
+struct Inner {
+    target: &'static str,
+    level: Level,
+    start: Instant,
+    fields: String,
+}
+
+impl Span {
+    pub fn new(level: Level, target: &'static str) -> Span {
+        let inner = enabled(target, level)
+            .then(|| Inner { target, level, start: Instant::now(), fields: String::new() });
+        Span { inner }
+    }
+
+    pub fn is_enabled(&self) -> bool { self.inner.is_some() }
+
+    /// Add `key=value` to the line.
+    pub fn record(&mut self, key: &str, value: impl Display) -> &mut Self {
+        if let Some(inner) = &mut self.inner {
+            let _ = write!(inner.fields, "{}={} ", key, value);
+        }
+        self
+    }
+
+    /// Add `key="value"` to the line, quoted and escaped.
+    pub fn record_str(&mut self, key: &str, value: &str) -> &mut Self {
+        if let Some(inner) = &mut self.inner {
+            let _ = write!(inner.fields, "{}={:?} ", key, value);
+        }
+        self
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let Some(inner) = self.inner.take() else { return };
+        let elapsed = inner.start.elapsed().as_secs_f64() * 1000.0;
+        let line = format!("{} {:>5} {}: {}elapsed_ms={:.3}\n",
+                           timestamp(), inner.level.name(), inner.target, inner.fields, elapsed);
+        // one write per line, so that threads do not interleave
+        let _ = std::io::stderr().lock().write_all(line.as_bytes());
+    }
+}
+
+// The current time as RFC 3339 UTC with milliseconds.
+fn timestamp() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let minute = crate::decay::format_time(now.as_secs() as i64).replace(' ', "T");
+    format!("{}:{:02}.{:03}Z", minute, now.as_secs() % 60, now.subsec_millis())
+}
@@ -0,0 +1,158 @@
+//! Picking a modality's search beam width for a target recall (`feather
+//! tune`).
+//!
+//! `tune` takes an even sample of the modality's own vectors as queries,
+//! finds each one's exact k nearest neighbours among the others by a scan,
+//! and answers it from the HNSW index at growing beam widths (`ef`) until
+//! the mean recall@k reaches the target. The smallest `ef` that does is
+//! stored in the file as the modality's default: every later open searches
+//! with it, until `DB::set_ef` overrides it for a session.
+//!
+//! Stored vectors sit among their neighbours, so they are easier queries
+//! than fresh ones and the recall reached can run a little above what real
+//! traffic sees; `feather eval` checks it on held-out queries.
+//!
+//! `ef` is the only search-time setting the index has. A projection
+//! (`feather redim`) rewrites the stored vectors, so it is left to the user
+//! and measured with `feather eval`.
+
+use crate::bench::{self, EfRun};
+use crate::DB;
+use std::collections::{BTreeMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// Property key holding the tuned beam widths, one `MODALITY=EF` line per
+/// modality (internal names).
+pub(crate) const PROPERTY_KEY: &str = "ef";
+
+/// Vectors `tune` queries with unless told otherwise.
+pub const DEFAULT_SAMPLE: usize = 100;
+
+/// Beam widths `tune` tries, in order.
+pub const CANDIDATE_EFS: [usize; 9] = [8, 16, 32, 64, 128, 256, 512, 1024, 2048];
+
+#[derive(Clone, Debug, Default)]
+pub struct TuneReport {
+    pub queries: usize,
+    pub k: usize,
+    /// Vectors the exact neighbours were found among.
+    pub vectors: usize,
+    /// One run per beam width tried, the last reaching the target if any
+    /// did.
+    pub runs: Vec<EfRun>,
+    /// The smallest beam width reaching the target recall.
+    pub ef: Option<usize>,
+}
+
+impl DB {
+    /// Find the smallest beam width at which searches of `modality` reach
+    /// `target_recall` at `k` (see the module docs), over `sample` of its
+    /// vectors. Nothing is stored; `set_tuned_ef` does that. `modality` is
+    /// left searching at the last `ef` tried.
+    pub fn tune(&self, modality: &str, target_recall: f64, k: usize, sample: usize) -> anyhow::Result<TuneReport> {
+        anyhow::ensure!(target_recall > 0.0 && target_recall <= 1.0, "target recall must be in (0, 1]");
+        anyhow::ensure!(k > 0, "k must be positive");
+        anyhow::ensure!(sample > 0, "sample must be positive");
+        let vectors: Vec<(u64, Vec<f32>)> = self.ids(modality).into_iter()
+            .filter_map(|id| Some((id, self.get_vector(id, modality)?)))
+            .collect();
+        anyhow::ensure!(vectors.len() > k, "modality '{}' has {} vectors; need more than k = {}",
+                        modality, vectors.len(), k);
+        let step = (vectors.len() / sample).max(1);
+        let queries: Vec<&(u64, Vec<f32>)> = vectors.iter().step_by(step).take(sample).collect();
+        let truth: Vec<HashSet<u64>> = queries.iter().map(|(id, query)| neighbours(&vectors, *id, query, k)).collect();
+        let mut report = TuneReport { queries: queries.len(), k, vectors: vectors.len(), ..Default::default() };
+
+        for ef in CANDIDATE_EFS {
+            self.set_ef(ef, Some(modality))?;
+            let mut times = Vec::with_capacity(queries.len());
+            let mut found = 0;
+            for ((id, query), truth) in queries.iter().zip(&truth) {
+                let start = Instant::now();
+                let hits = self.knn(query, k + 1, modality)?;
+                times.push(start.elapsed());
+                found += hits.iter().filter(|(hit, _)| hit != id).take(k).filter(|(hit, _)| truth.contains(hit)).count();
+            }
+            let total: Duration = times.iter().sum();
+            times.sort();
+            let recall = found as f64 / truth.iter().map(|t| t.len()).sum::<usize>().max(1) as f64;
+            report.runs.push(EfRun {
+                ef,
+                p50: bench::percentile(&times, 50.0),
+                p95: bench::percentile(&times, 95.0),
+                p99: bench::percentile(&times, 99.0),
+                mean: total / times.len() as u32,
+                qps: times.len() as f64 / total.as_secs_f64().max(f64::EPSILON),
+                recall,
+            });
+            if recall >= target_recall {
+                report.ef = Some(ef);
+                break;
+            }
+        }
+        Ok(report)
+    }
+
+    /// The beam width stored for `modality` by `set_tuned_ef`, if any.
+    pub fn tuned_ef(&self, modality: &str) -> Option<usize> {
+        let name = self.mname(Some(modality))?;
+        decode(&self.property(PROPERTY_KEY)?).remove(name.as_ref())
+    }
+
+    /// Store `ef` as the default beam width of `modality`, applying it now
+    /// and on every later open; None goes back to `search::DEFAULT_EF` from
+    /// the next open. Persists on `save()`.
+    pub fn set_tuned_ef(&self, modality: &str, ef: Option<usize>) -> anyhow::Result<()> {
+        self.writable()?;
+        let name = self.mname(Some(modality)).expect("named").into_owned();
+        let mut tuned = self.property(PROPERTY_KEY).map(|raw| decode(&raw)).unwrap_or_default();
+        match ef {
+            Some(ef) => {
+                self.set_ef(ef, Some(modality))?;
+                tuned.insert(name, ef);
+            }
+            None => { tuned.remove(&name); }
+        }
+        if tuned.is_empty() {
+            self.remove_property(PROPERTY_KEY)?;
+        } else {
+            self.set_property(PROPERTY_KEY, encode(&tuned).as_bytes())?;
+        }
+        Ok(())
+    }
+
+    // Search every tuned modality of the file at its stored beam width.
+    pub(crate) fn apply_tuned_ef(&self) {
+        let Some(raw) = self.handle.property(PROPERTY_KEY) else { return };
+        for (modality, ef) in decode(&raw) {
+            // a tuned modality a shard lacks is not an error
+            let _ = self.handle.set_ef(ef, Some(&modality));
+        }
+    }
+}
+
+// Ids of the k vectors nearest `query` in L2, other than `id` itself.
+fn neighbours(vectors: &[(u64, Vec<f32>)], id: u64, query: &[f32], k: usize) -> HashSet<u64> {
+    let mut dists: Vec<(f32, u64)> = vectors.iter()
+        .filter(|(other, _)| *other != id)
+        .map(|(other, v)| (v.iter().zip(query).map(|(a, b)| (a - b) * (a - b)).sum(), *other))
+        .collect();
+    let k = k.min(dists.len());
+    if k == 0 { return HashSet::new(); }
+    dists.select_nth_unstable_by(k - 1, |a, b| a.0.total_cmp(&b.0));
+    dists[..k].iter().map(|&(_, id)| id).collect()
+}
+
+fn encode(tuned: &BTreeMap<String, usize>) -> String {
+    tuned.iter().map(|(modality, ef)| format!("{}={}\n", modality, ef)).collect()
+}
+
+// Lines that do not parse are skipped: they only lose a tuning.
+fn decode(raw: &[u8]) -> BTreeMap<String, usize> {
+    String::from_utf8_lossy(raw).lines()
+        .filter_map(|line| {
+            let (modality, ef) = line.rsplit_once('=')?;
+            Some((modality.to_string(), ef.parse().ok().filter(|&ef| ef > 0)?))
+        })
+        .collect()
+}
@@ -0,0 +1,191 @@
+//! Transactions (`DB::begin`): several adds, forgets and links that land
+//! together or not at all, so that a memory stored with its links is never
+//! left half-written by a crash.
+//!
+//! ```ignore
+//! let mut tx = db.begin();
+//! tx.add(7, &vec, &meta, "text").link(7, 3, "follows", 1.0);
+//! tx.commit()?;
+//! ```
+//!
+//! Operations are staged and nothing is visible until `commit`, which
+//! checks all of them against the store as the earlier ones would leave
+//! it — dimensions, the duplicate-id policy, link endpoints — before
+//! applying any. Their log entries reach the WAL as one entry, which a
+//! reopen replays whole or, torn by a crash, not at all. In a sharded store
+//! that is one entry per shard, so a crash mid-commit can keep some shards'
+//! part and lose the rest. Dropping a transaction, or `rollback`, discards
+//! it. The dedup mode does not apply to a transaction's adds.
+//!
+//! A commit that fails after applying some operations (only what the
+//! checks cannot foresee, such as a WAL that cannot be written) cannot take
+//! them back in memory. It poisons the handle instead: the handle lets go
+//! of the file, so not even closing saves the part applied (nor settings
+//! changed since the last save, which only a save writes), and refuses
+//! every later change with `Poisoned`. Reopen the store to go on.
+
+use crate::{graph, DimensionMismatch, DuplicateId, Handle, Metadata, OnDuplicate, DB};
+use std::collections::HashMap;
+
+extern "C" {
+    fn feather_begin(db: *mut std::ffi::c_void) -> i32;
+    fn feather_commit(db: *mut std::ffi::c_void) -> i32;
+    fn feather_rollback(db: *mut std::ffi::c_void);
+    fn feather_detach(db: *mut std::ffi::c_void);
+}
+
+enum Op {
+    Add { id: u64, vector: Vec<f32>, meta: Box<Metadata>, modality: String },
+    Forget(u64),
+    Link { from: u64, to: u64, rel_type: String, weight: f32 },
+}
+
+/// Staged changes to a store; see `DB::begin`.
+#[must_use = "a transaction is discarded unless `commit` is called"]
+pub struct Transaction<'a> {
+    db: &'a DB,
+    ops: Vec<Op>,
+}
+
+impl DB {
+    /// Start a transaction on this handle (and its collection, if scoped).
+    pub fn begin(&self) -> Transaction<'_> {
+        Transaction { db: self, ops: Vec::new() }
+    }
+}
+
+impl Transaction<'_> {
+    /// Insert or replace a record, as `add_with_metadata` does.
+    pub fn add(&mut self, id: u64, vector: &[f32], meta: &Metadata, modality: &str) -> &mut Self {
+        self.ops.push(Op::Add { id, vector: vector.to_vec(), meta: Box::new(meta.clone()), modality: modality.to_string() });
+        self
+    }
+
+    /// Soft-delete a record, as `DB::forget` does.
+    pub fn forget(&mut self, id: u64) -> &mut Self {
+        self.ops.push(Op::Forget(id));
+        self
+    }
+
+    /// Link two records, as `DB::link_with` does; either may be added
+    /// earlier in the transaction.
+    pub fn link(&mut self, from: u64, to: u64, rel_type: &str, weight: f32) -> &mut Self {
+        self.ops.push(Op::Link { from, to, rel_type: rel_type.to_string(), weight });
+        self
+    }
+
+    /// Operations staged so far.
+    pub fn len(&self) -> usize { self.ops.len() }
+
+    pub fn is_empty(&self) -> bool { self.ops.is_empty() }
+
+    /// Apply every staged operation and log them as one. Fails, changing
+    /// nothing, if any of them would fail; an add the `Ignore` policy
+    /// skips is no failure. Failing after applying some, it poisons the
+    /// handle (see the module docs).
+    pub fn commit(self) -> anyhow::Result<()> {
+        let Transaction { db, ops } = self;
+        db.writable()?;
+        let keep = check(db, &ops)?;
+        let cores = db.handle.cores();
+        for (i, &core) in cores.iter().enumerate() {
+            if unsafe { feather_begin(core) } != 0 {
+                cores[..i].iter().for_each(|&core| unsafe { feather_rollback(core) });
+                return Err(crate::last_error());
+            }
+        }
+        // logged as one too, once it commits
+        db.handle.hold_changes();
+        let applied = ops.iter().zip(keep).filter(|(_, keep)| *keep).try_for_each(|(op, _)| match op {
+            Op::Add { id, vector, meta, modality } => db.write_record(*id, vector, meta, modality),
+            Op::Forget(id) => db.forget(*id),
+            Op::Link { from, to, rel_type, weight } => db.link_with(*from, *to, rel_type, *weight),
+        });
+        if let Err(e) = applied {
+            // only what `check` could not foresee gets here
+            cores.iter().for_each(|&core| unsafe { feather_rollback(core) });
+            db.handle.poison();
+            return Err(e.context("transaction failed part-way: nothing reached the file; reopen the store"));
+        }
+        for (i, &core) in cores.iter().enumerate() {
+            if unsafe { feather_commit(core) } != 0 {
+                let e = crate::last_error();
+                cores[i + 1..].iter().for_each(|&core| unsafe { feather_rollback(core) });
+                db.handle.poison();
+                return Err(e.context(match i {
+                    0 => "transaction failed to commit: nothing reached the file; reopen the store",
+                    _ => "transaction failed to commit: some shards logged their part; reopen the store",
+                }));
+            }
+        }
+        db.handle.release_changes(true);
+        Ok(())
+    }
+
+    /// Discard the staged operations; the same as dropping the transaction.
+    pub fn rollback(self) {}
+}
+
+impl Handle {
+    // Let go of the file without saving what this handle holds, and refuse
+    // every later change (see the module docs).
+    fn poison(&self) {
+        self.release_changes(false);
+        for &core in self.cores() {
+            unsafe { feather_detach(core) };
+        }
+        self.poisoned.set(true);
+    }
+}
+
+// Check `ops` in order against the store as the ones before leave it.
+// Returns which to apply: false for an add the duplicate-id policy skips.
+fn check(db: &DB, ops: &[Op]) -> anyhow::Result<Vec<bool>> {
+    // ids the transaction adds (true) or forgets (false) so far
+    let mut live: HashMap<u64, bool> = HashMap::new();
+    // dimensions of modalities the transaction creates
+    let mut dims: HashMap<String, usize> = HashMap::new();
+    let is_live = |live: &HashMap<u64, bool>, id: u64| {
+        live.get(&id).copied().unwrap_or_else(|| db.get_metadata(id).is_some_and(|m| !m.is_forgotten()))
+    };
+    let mut keep = Vec::with_capacity(ops.len());
+    for op in ops {
+        match op {
+            Op::Add { id, vector, meta, modality } => {
+                db.iid(*id)?;
+                if is_live(&live, *id) && db.on_duplicate() != OnDuplicate::Overwrite {
+                    anyhow::ensure!(db.on_duplicate() == OnDuplicate::Ignore, DuplicateId { id: *id });
+                    keep.push(false);
+                    continue;
+                }
+                for edge in &meta.edges {
+                    graph::check_edge(&edge.rel_type, edge.weight)?;
+                    anyhow::ensure!(edge.target == *id || is_live(&live, edge.target), "no record {}", edge.target);
+                }
+                let name = db.mname(Some(modality)).expect("named").into_owned();
+                let vector = db.project(Some(&name), vector);
+                match dims.get(&name) {
+                    Some(&expected) => anyhow::ensure!(vector.len() == expected,
+                                                       DimensionMismatch { expected, got: vector.len() }),
+                    None => {
+                        db.check_dim(Some(&name), &vector)?;
+                        dims.insert(name, vector.len());
+                    }
+                }
+                live.insert(*id, true);
+            }
+            Op::Forget(id) => {
+                db.iid(*id)?;
+                live.insert(*id, false);
+            }
+            Op::Link { from, to, rel_type, weight } => {
+                graph::check_edge(rel_type, *weight)?;
+                for id in [from, to] {
+                    anyhow::ensure!(is_live(&live, *id), "no record {}", id);
+                }
+            }
+        }
+        keep.push(true);
+    }
+    Ok(keep)
+}
@@ -0,0 +1,187 @@
+//! Reading vectors in the formats embedding toolchains write.
+//!
+//! Wherever the CLI takes a vector file it takes any of:
+//!
+//! - `file.npy`: a float32 array;
+//! - `file.npz` or `file.npz:NAME`: array NAME of a NumPy archive, or its
+//!   only array;
+//! - `file.safetensors` or `file.safetensors:NAME`: tensor NAME (F32, F16
+//!   or BF16), or the file's only tensor.
+//!
+//! A vector is a 1-D array, or a 2-D one with a single row; a matrix is a
+//! 2-D array with one vector per row. `read_raw` takes little-endian
+//! float32 as it comes, e.g. piped into `--stdin`.
+
+use ndarray::{Array2, ArrayD, IxDyn, OwnedRepr};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+/// Extensions `read_vector` and `read_matrix` know.
+pub const EXTENSIONS: [&str; 3] = ["npy", "npz", "safetensors"];
+
+/// Whether `spec` names a vector file (by its extension), as opposed to,
+/// say, inline numbers or text.
+pub fn is_file(spec: &str) -> bool {
+    let (path, _) = split(spec);
+    Path::new(path).extension().and_then(|e| e.to_str()).is_some_and(|e| EXTENSIONS.contains(&e))
+}
+
+/// The vector in `spec` (see the module docs).
+pub fn read_vector(spec: &Path) -> anyhow::Result<Vec<f32>> {
+    let (shape, data) = read(spec)?;
+    match shape.as_slice() {
+        [_] | [1, _] => Ok(data),
+        _ => anyhow::bail!("{:?}: expected one vector, found an array of shape {:?}", spec, shape),
+    }
+}
+
+/// The vectors in `spec`, one per row (see the module docs).
+pub fn read_matrix(spec: &Path) -> anyhow::Result<Array2<f32>> {
+    let (shape, data) = read(spec)?;
+    let &[rows, dim] = shape.as_slice() else {
+        anyhow::bail!("{:?}: expected a 2-D array (one vector per row), found shape {:?}", spec, shape)
+    };
+    Ok(Array2::from_shape_vec((rows, dim), data)?)
+}
+
+/// The integers in the .npy file `path`, one row per query, e.g. the ids
+/// of its true nearest neighbours; int64, int32, uint64 or uint32. A
+/// negative entry (padding) comes back as None.
+pub fn read_ids(path: &Path) -> anyhow::Result<Vec<Vec<Option<u64>>>> {
+    let rows = |shape: &[usize], values: Vec<Option<u64>>| -> anyhow::Result<Vec<Vec<Option<u64>>>> {
+        let &[_, width] = shape else {
+            anyhow::bail!("{:?}: expected a 2-D array (one row per query), found shape {:?}", path, shape)
+        };
+        anyhow::ensure!(width > 0, "{:?}: rows are empty", path);
+        Ok(values.chunks(width).map(<[_]>::to_vec).collect())
+    };
+    if let Ok(array) = ndarray_npy::read_npy::<_, ArrayD<i64>>(path) {
+        return rows(array.shape(), array.iter().map(|&v| u64::try_from(v).ok()).collect());
+    }
+    if let Ok(array) = ndarray_npy::read_npy::<_, ArrayD<i32>>(path) {
+        return rows(array.shape(), array.iter().map(|&v| u64::try_from(v).ok()).collect());
+    }
+    if let Ok(array) = ndarray_npy::read_npy::<_, ArrayD<u32>>(path) {
+        return rows(array.shape(), array.iter().map(|&v| Some(u64::from(v))).collect());
+    }
+    let array: ArrayD<u64> = ndarray_npy::read_npy(path)
+        .map_err(|e| anyhow::anyhow!("{:?}: {} (arrays must be int64, int32, uint64 or uint32)", path, e))?;
+    rows(array.shape(), array.iter().map(|&v| Some(v)).collect())
+}
+
+/// Little-endian float32 from `input`: vectors of `dim` values, or, with no
+/// `dim`, everything as one vector.
+pub fn read_raw(mut input: impl Read, dim: Option<usize>) -> anyhow::Result<Array2<f32>> {
+    let mut raw = Vec::new();
+    input.read_to_end(&mut raw)?;
+    anyhow::ensure!(!raw.is_empty(), "no vector data on the input");
+    anyhow::ensure!(raw.len() % 4 == 0, "{} bytes of input is not a whole number of float32s", raw.len());
+    let values: Vec<f32> = raw.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect();
+    let dim = dim.unwrap_or(values.len());
+    anyhow::ensure!(dim > 0 && values.len().is_multiple_of(dim),
+                    "{} float32s on the input do not make vectors of dim {}", values.len(), dim);
+    Ok(Array2::from_shape_vec((values.len() / dim, dim), values)?)
+}
+
+// `file.npz:NAME` → ("file.npz", Some("NAME")); only archives take a name.
+fn split(spec: &str) -> (&str, Option<&str>) {
+    match spec.rsplit_once(':') {
+        Some((path, name)) if path.ends_with(".npz") || path.ends_with(".safetensors") => (path, Some(name)),
+        _ => (spec, None),
+    }
+}
+
+// The array `spec` names, as (shape, values in row-major order).
+fn read(spec: &Path) -> anyhow::Result<(Vec<usize>, Vec<f32>)> {
+    let spec_str = spec.to_str().ok_or_else(|| anyhow::anyhow!("{:?}: not a UTF-8 path", spec))?;
+    let (path, name) = split(spec_str);
+    let context = |e: &dyn std::fmt::Display| anyhow::anyhow!("{}: {}", path, e);
+    match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("npz") => {
+            let file = std::fs::File::open(path).map_err(|e| context(&e))?;
+            let mut npz = ndarray_npy::NpzReader::new(file).map_err(|e| context(&e))?;
+            let names = npz.names().map_err(|e| context(&e))?;
+            let entry = match name {
+                Some(name) => names.iter().find(|n| *n == name || n.strip_suffix(".npy") == Some(name))
+                    .ok_or_else(|| anyhow::anyhow!("{}: no array named '{}' (has {})", path, name, list(&names)))?,
+                None => match names.as_slice() {
+                    [only] => only,
+                    _ => anyhow::bail!("{}: holds {}; name one as {}:NAME", path, list(&names), path),
+                },
+            }.clone();
+            let array: ArrayD<f32> = npz.by_name::<OwnedRepr<f32>, IxDyn>(&entry)
+                .map_err(|e| anyhow::anyhow!("{}:{}: {} (arrays must be float32)", path, entry, e))?;
+            Ok((array.shape().to_vec(), array.iter().copied().collect()))
+        }
+        Some("safetensors") => {
+            let raw = std::fs::read(path).map_err(|e| context(&e))?;
+            read_safetensors(&raw, name).map_err(|e| context(&e))
+        }
+        _ => {
+            let array: ArrayD<f32> = ndarray_npy::read_npy(path)
+                .map_err(|e| anyhow::anyhow!("{}: {} (arrays must be float32)", path, e))?;
+            Ok((array.shape().to_vec(), array.iter().copied().collect()))
+        }
+    }
+}
+
+fn list(names: &[String]) -> String {
+    let names: Vec<&str> = names.iter().map(|n| n.strip_suffix(".npy").unwrap_or(n)).collect();
+    if names.is_empty() { "no arrays".to_string() } else { names.join(", ") }
+}
+
+/// Names of the tensors in a safetensors file.
+pub fn tensor_names(raw: &[u8]) -> anyhow::Result<Vec<String>> {
+    Ok(safetensors_header(raw)?.0.into_keys().filter(|name| name != "__metadata__").collect())
+}
+
+/// Tensor `name` of a safetensors file, or its only tensor, as (shape,
+/// values).
+pub fn read_safetensors(raw: &[u8], name: Option<&str>) -> anyhow::Result<(Vec<usize>, Vec<f32>)> {
+    let (header, data) = safetensors_header(raw)?;
+    let tensor = match name {
+        Some(name) => header.get(name).ok_or_else(|| anyhow::anyhow!("no tensor named '{}'", name))?,
+        None => {
+            let mut tensors = header.iter().filter(|(name, _)| *name != "__metadata__");
+            match (tensors.next(), tensors.next()) {
+                (Some((_, tensor)), None) => tensor,
+                _ => anyhow::bail!("holds several tensors; name one as FILE:NAME"),
+            }
+        }
+    };
+    let shape: Vec<usize> = serde_json::from_value(tensor["shape"].clone())?;
+    let offsets: [usize; 2] = serde_json::from_value(tensor["data_offsets"].clone())?;
+    let bytes = data.get(offsets[0]..offsets[1]).ok_or_else(|| anyhow::anyhow!("truncated tensor data"))?;
+    let values: Vec<f32> = match tensor["dtype"].as_str() {
+        Some("F32") => bytes.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect(),
+        Some("F16") => bytes.chunks_exact(2).map(|b| f16_to_f32(u16::from_le_bytes([b[0], b[1]]))).collect(),
+        Some("BF16") => bytes.chunks_exact(2).map(|b| f32::from_bits(u32::from(u16::from_le_bytes([b[0], b[1]])) << 16)).collect(),
+        other => anyhow::bail!("unsupported dtype {:?}; use F32, F16 or BF16", other),
+    };
+    anyhow::ensure!(values.len() == shape.iter().product::<usize>(), "tensor data does not match its shape");
+    Ok((shape, values))
+}
+
+// The JSON header of a safetensors file and the data after it.
+fn safetensors_header(raw: &[u8]) -> anyhow::Result<(HashMap<String, Value>, &[u8])> {
+    let bad = || anyhow::anyhow!("truncated or not safetensors");
+    let header_len = u64::from_le_bytes(raw.get(..8).ok_or_else(bad)?.try_into()?) as usize;
+    let end = header_len.checked_add(8).ok_or_else(bad)?;
+    let header = serde_json::from_slice(raw.get(8..end).ok_or_else(bad)?)
+        .map_err(|e| anyhow::anyhow!("bad header: {}", e))?;
+    Ok((header, &raw[end..]))
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = (bits >> 10) & 0x1f;
+    let frac = f32::from(bits & 0x3ff);
+    sign * match exp {
+        0 => frac * 2f32.powi(-24),
+        0x1f if frac == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + frac / 1024.0) * 2f32.powi(i32::from(exp) - 15),
+    }
+}
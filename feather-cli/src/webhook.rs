@@ -0,0 +1,130 @@
+//! Webhooks of `feather serve --webhook URL`: external pipelines — a
+//! summarizer, a notifier — told of new and deleted memories as they
+//! happen, rather than polling.
+//!
+//! After each request that added or deleted records, every webhook URL is
+//! sent one POST of
+//!
+//! ```text
+//! {"events": [{"event": "added", "id": 7, "at": 1718000000, "record": {...}},
+//!             {"event": "deleted", "id": 3, "at": 1718000000}]}
+//! ```
+//!
+//! in the order the changes were made. An added record comes as `/get`
+//! replies with it; `collection` and `actor` are there when the server has
+//! them (see `audit::Entry`). Updates and links are not sent.
+//!
+//! Deliveries run on a thread of their own, through the `curl` binary as
+//! `--embed-api` requests do, so a slow or unreachable endpoint never holds
+//! up a request. Each gets `DELIVERY_TIMEOUT`; one that fails is logged
+//! under the `feather::webhook` target (see `trace`) and not retried.
+
+use crate::audit::{Entry, Op};
+use crate::embed::remote::quote;
+use crate::trace::{Level, Span};
+use crate::DB;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Longest one delivery may take, connecting included.
+pub const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The webhooks of one served store.
+pub struct Webhooks {
+    changes: Receiver<Entry>,
+    // payloads for the delivery thread; None once dropping
+    queue: Option<Sender<String>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Webhooks {
+    /// Send the records added to and deleted from `db` from now on to each
+    /// of `urls` (http or https).
+    pub fn new(db: &DB, urls: &[String]) -> anyhow::Result<Self> {
+        anyhow::ensure!(!urls.is_empty(), "no webhook URLs");
+        for url in urls {
+            anyhow::ensure!(url.starts_with("http://") || url.starts_with("https://"),
+                            "webhook URL {:?} is not http or https", url);
+        }
+        Command::new("curl").arg("--version").stdout(Stdio::null()).status()
+            .map_err(|e| anyhow::anyhow!("--webhook needs curl on the PATH: {}", e))?;
+        let (queue, payloads) = mpsc::channel::<String>();
+        let urls = urls.to_vec();
+        let worker = std::thread::spawn(move || {
+            for payload in payloads {
+                for url in &urls {
+                    deliver(url, &payload);
+                }
+            }
+        });
+        Ok(Webhooks { changes: db.subscribe(), queue: Some(queue), worker: Some(worker) })
+    }
+
+    // Queue the adds and deletes made to `db` since the last call.
+    pub(crate) fn notify(&self, db: &DB) {
+        let events: Vec<Value> = self.changes.try_iter()
+            .filter_map(|change| {
+                let event = match change.op {
+                    Op::Add => "added",
+                    Op::Delete => "deleted",
+                    _ => return None,
+                };
+                let mut event = json!({ "event": event, "id": change.id, "at": change.at });
+                if let Some(collection) = &change.collection { event["collection"] = json!(collection); }
+                if let Some(actor) = &change.actor { event["actor"] = json!(actor); }
+                if change.op == Op::Add {
+                    // gone again if the same request deleted it
+                    if let Some(record) = db.record(change.id).filter(|r| !r.metadata.is_forgotten()) {
+                        event["record"] = serde_json::to_value(record).expect("records serialize");
+                    }
+                }
+                Some(event)
+            })
+            .collect();
+        if events.is_empty() { return; }
+        if let Some(queue) = &self.queue {
+            // the worker only stops once the queue is dropped
+            let _ = queue.send(json!({ "events": events }).to_string());
+        }
+    }
+}
+
+impl Drop for Webhooks {
+    // Deliver what is queued before the server goes.
+    fn drop(&mut self) {
+        self.queue = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+// POST `payload` to `url`, logging a failure.
+fn deliver(url: &str, payload: &str) {
+    let mut span = Span::new(Level::Debug, "feather::webhook");
+    span.record_str("url", url).record("bytes", payload.len());
+    let config = format!("url = {}\nrequest = POST\nheader = \"Content-Type: application/json\"\ndata-binary = {}\n",
+                         quote(url), quote(payload));
+    let result = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--output", "/dev/null", "--max-time"])
+        .arg(DELIVERY_TIMEOUT.as_secs().to_string())
+        .args(["--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .and_then(|mut curl| {
+            curl.stdin.take().expect("piped").write_all(config.as_bytes())?;
+            curl.wait_with_output()
+        });
+    let error = match result {
+        Ok(out) if out.status.success() => return,
+        Ok(out) => String::from_utf8_lossy(&out.stderr).trim().to_string(),
+        Err(e) => e.to_string(),
+    };
+    Span::new(Level::Warn, "feather::webhook").record_str("url", url).record_str("error", &error);
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use common::*;
+use feather_db_cli::{ContextType, SearchOptions, SparseVector, DB};
+
+// The ids each kind of search finds for record 2's vector, content and
+// sparse vector.
+fn found(db: &DB, options: &SearchOptions) -> [bool; 6] {
+    let query = vector(2);
+    let sparse = SparseVector::new(vec![(5, 1.0)]).unwrap();
+    [
+        db.search(&query, 3, Some("text")).unwrap().0.contains(&2),
+        db.search_with_filter(&query, 3, Some(ContextType::Semantic), None, Some("text")).unwrap().0.contains(&2),
+        db.search_with_options(&query, 3, "text", options).unwrap().iter().any(|&(id, _)| id == 2),
+        db.search_with_options(&query, 3, "text", &SearchOptions { exact: true, ..options.clone() })
+            .unwrap().iter().any(|&(id, _)| id == 2),
+        db.keyword_search("bravo", 3).unwrap().iter().any(|&(id, _)| id == 2),
+        db.sparse_search(&sparse, 3, "terms").unwrap().iter().any(|&(id, _)| id == 2),
+    ]
+}
+
+// The library leaves archived records out of every search unless asked,
+// whichever path the search takes; the C++ core itself does not.
+#[test]
+fn archived_records_are_left_out() {
+    let dir = Scratch::new("archive");
+    let path = dir.path("t.feather");
+    let db = create(&path);
+    for (id, word) in [(1, "alpha"), (2, "bravo"), (3, "charlie")] { add(&db, id, word); }
+    db.set_sparse(2, "terms", &SparseVector::new(vec![(5, 1.0)]).unwrap()).unwrap();
+    let include = SearchOptions { include_archived: true, ..SearchOptions::default() };
+    assert_eq!(found(&db, &SearchOptions::default()), [true; 6]);
+
+    assert!(db.archive(2).unwrap());
+    db.save().unwrap();
+    drop(db);
+    let db = reopen(&path);
+    assert_eq!(db.archived(), [2]);
+    assert_eq!(content(&db, 2).as_deref(), Some("bravo"));
+    assert_eq!(found(&db, &SearchOptions::default()), [false; 6]);
+    assert_eq!(found(&db, &include)[2..4], [true; 2]);
+
+    assert!(db.restore(2).unwrap());
+    assert_eq!(found(&db, &SearchOptions::default()), [true; 6]);
+}
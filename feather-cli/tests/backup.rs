@@ -0,0 +1,61 @@
+mod common;
+
+use common::*;
+use feather_db_cli::backup;
+
+// A full backup and deltas taken after it restore to the store as it was.
+#[test]
+fn deltas_restore_onto_the_full_backup() {
+    let dir = Scratch::new("backup-restore");
+    let path = dir.path("t.feather");
+    let db = create(&path);
+    add(&db, 1, "first");
+    drop(db);
+    let full = backup::backup(&path, &dir.path("full.feather")).unwrap();
+
+    let db = reopen(&path);
+    add(&db, 2, "second");
+    drop(db);
+    let mon = backup::backup_since(&path, full.id, &dir.path("mon.delta")).unwrap();
+    let db = reopen(&path);
+    add(&db, 3, "third");
+    db.forget(1).unwrap();
+    drop(db);
+    let tue = backup::backup_since(&path, mon.id, &dir.path("tue.delta")).unwrap();
+    assert!(full.id < mon.id && mon.id < tue.id);
+
+    let deltas = [dir.path("mon.delta"), dir.path("tue.delta")];
+    let at = backup::restore(&dir.path("full.feather"), &deltas, &dir.path("new.feather")).unwrap();
+    assert_eq!(at, Some(tue.id));
+    let restored = reopen(&dir.path("new.feather"));
+    assert_eq!(content(&restored, 2).as_deref(), Some("second"));
+    assert_eq!(content(&restored, 3).as_deref(), Some("third"));
+    assert!(restored.get_metadata(1).is_none_or(|m| m.is_forgotten()));
+
+    // out of order, a delta is refused and nothing is left behind
+    let out = dir.path("wrong.feather");
+    assert!(backup::restore(&dir.path("full.feather"), &deltas[1..], &out).is_err());
+    assert!(!out.exists());
+}
+
+// A backup after one whose id is not in the past, as when the clock is
+// set back, takes the next id rather than waiting for the clock.
+#[test]
+fn backup_ids_never_wait_for_the_clock() {
+    let dir = Scratch::new("backup-clock");
+    let path = dir.path("t.feather");
+    let db = create(&path);
+    add(&db, 1, "first");
+    drop(db);
+    let first = backup::backup(&path, &dir.path("a.feather")).unwrap();
+    // the state of a backup an hour ahead of the clock
+    let ahead = first.id + 3600;
+    std::fs::rename(dir.path("t.feather.backups").join(format!("{}.feather", first.id)),
+                    dir.path("t.feather.backups").join(format!("{}.feather", ahead))).unwrap();
+
+    let start = std::time::Instant::now();
+    let delta = backup::backup_since(&path, ahead, &dir.path("b.delta")).unwrap();
+    let again = backup::backup_since(&path, delta.id, &dir.path("c.delta")).unwrap();
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    assert_eq!((delta.id, again.id), (ahead + 1, ahead + 2));
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use common::*;
+
+// An unscoped id whose top bits name a collection must not reach that
+// collection's records.
+#[test]
+fn raw_ids_cannot_alias_a_collection() {
+    let dir = Scratch::new("collections-alias");
+    let path = dir.path("c.feather");
+    let db = create(&path);
+    let a = db.collection("a").unwrap();
+    add(&a, 5, "collection a");
+    let alias = 1 << feather_db_cli::collection::ID_BITS | 5;
+    let meta = feather_db_cli::Metadata { content: "raw".into(), ..Default::default() };
+    assert!(db.add_with_metadata(alias, &vector(1), &meta, "text").is_err());
+    assert!(db.add(alias, &vector(1)).is_err());
+    assert_eq!(db.get_metadata(alias), None);
+    // nor link or touch, which fail instead of panicking
+    assert!(db.link(alias, 5).is_err());
+    assert!(a.link(5, alias).is_err());
+    assert!(db.touch(alias).is_err());
+    assert!(a.get_metadata(5).unwrap().edges.is_empty());
+    add(&db, 5, "default");
+    db.save().unwrap();
+    drop((a, db));
+
+    let db = reopen(&path);
+    assert_eq!(content(&db.collection("a").unwrap(), 5).as_deref(), Some("collection a"));
+    assert_eq!(content(&db, 5).as_deref(), Some("default"));
+}
+
+// Registering a collection skips the index a raw id already uses.
+#[test]
+fn collections_skip_indexes_raw_ids_use() {
+    let dir = Scratch::new("collections-skip");
+    let path = dir.path("c.feather");
+    let db = create(&path);
+    let raw = 1 << feather_db_cli::collection::ID_BITS | 5;
+    add(&db, raw, "raw");
+    let a = db.collection("a").unwrap();
+    add(&a, 5, "collection a");
+    assert_eq!(content(&a, 5).as_deref(), Some("collection a"));
+    // still in the file, though the unscoped handle now refuses its id
+    assert!(db.all_ids().contains(&raw));
+    assert_eq!(content(&db, raw), None);
+}
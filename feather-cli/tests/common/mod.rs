@@ -0,0 +1,55 @@
+//! What the integration tests share: a scratch directory per test, and
+//! small records to fill stores with.
+
+#![allow(dead_code)]
+
+use feather_db_cli::{Metadata, OpenOptions, DB};
+use std::path::{Path, PathBuf};
+
+/// A directory of its own for one test, removed when dropped.
+pub struct Scratch(PathBuf);
+
+impl Scratch {
+    pub fn new(test: &str) -> Scratch {
+        let dir = std::env::temp_dir().join(format!("feather-test-{}-{}", std::process::id(), test));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("scratch directory");
+        Scratch(dir)
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf { self.0.join(name) }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+pub const DIM: usize = 4;
+
+/// A new store of `DIM`-value vectors at `path`.
+pub fn create(path: &Path) -> DB {
+    OpenOptions::new().create_new(true).dim(DIM).open(path).expect("create")
+}
+
+/// The store at `path`, which must exist.
+pub fn reopen(path: &Path) -> DB {
+    OpenOptions::new().open(path).expect("reopen")
+}
+
+/// A vector no two `seed`s share.
+pub fn vector(seed: u64) -> Vec<f32> {
+    (0..DIM).map(|i| ((seed * 7 + i as u64 * 3) % 11) as f32 + 1.0).collect()
+}
+
+/// Add a record whose content is `content`.
+pub fn add(db: &DB, id: u64, content: &str) {
+    let meta = Metadata { content: content.to_string(), ..Metadata::default() };
+    db.add_with_metadata(id, &vector(id), &meta, "text").expect("add");
+}
+
+/// The content of record `id`, if it is there.
+pub fn content(db: &DB, id: u64) -> Option<String> {
+    db.get_metadata(id).map(|m| m.content)
+}
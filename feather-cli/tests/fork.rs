@@ -0,0 +1,38 @@
+mod common;
+
+use common::*;
+
+// A fork and its base, moved together, still open as the fork.
+#[test]
+fn fork_moves_with_its_base() {
+    let dir = Scratch::new("fork-move");
+    let db = create(&dir.path("main.feather"));
+    add(&db, 1, "in the base");
+    let fork = db.fork(&dir.path("f.feather")).unwrap();
+    add(&fork, 2, "in the fork");
+    fork.save().unwrap();
+    drop((fork, db));
+
+    std::fs::create_dir(dir.path("moved")).unwrap();
+    for name in ["f.feather", "f.feather.base"] {
+        std::fs::rename(dir.path(name), dir.path("moved").join(name)).unwrap();
+    }
+    let fork = reopen(&dir.path("moved").join("f.feather"));
+    assert_eq!(content(&fork, 1).as_deref(), Some("in the base"));
+    assert_eq!(content(&fork, 2).as_deref(), Some("in the fork"));
+    assert_eq!(fork.fork_base(), Some(dir.path("moved").join("f.feather.base")));
+    drop(fork);
+    assert!(feather_db_cli::fsck::check(&dir.path("moved").join("f.feather")).unwrap().problems.is_empty());
+}
+
+// A fork without its base says so rather than failing to open blindly.
+#[test]
+fn fork_without_its_base_says_so() {
+    let dir = Scratch::new("fork-missing");
+    let db = create(&dir.path("main.feather"));
+    add(&db, 1, "in the base");
+    drop(db.fork(&dir.path("f.feather")).unwrap());
+    std::fs::remove_file(dir.path("f.feather.base")).unwrap();
+    let e = feather_db_cli::OpenOptions::new().open(&dir.path("f.feather")).err().expect("no base");
+    assert!(format!("{:#}", e).contains("base snapshot"), "{:#}", e);
+}
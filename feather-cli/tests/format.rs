@@ -0,0 +1,139 @@
+mod common;
+
+use common::*;
+use feather_db_cli::{fsck, Compression, OpenOptions, SparseVector};
+use std::path::Path;
+
+// The format version in a file's header.
+fn version(path: &Path) -> u32 {
+    let bytes = std::fs::read(path).unwrap();
+    u32::from_le_bytes(bytes[4..8].try_into().unwrap())
+}
+
+// Content long enough, and varied enough, to take several chunks to pack.
+fn text(seed: u64) -> String {
+    let mut x = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    (0..300).map(|_| {
+        x = x.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        ["alpha", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel"][(x >> 61) as usize]
+    }).collect::<Vec<_>>().join(" ")
+}
+
+fn fill(path: &Path, compression: Compression) {
+    let db = OpenOptions::new().create_new(true).dim(DIM).compression(compression).open(path).unwrap();
+    for id in 1..=200 { add(&db, id, &text(id)); }
+    db.set_sparse(7, "terms", &SparseVector::new(vec![(3, 0.5), (900, 1.5)]).unwrap()).unwrap();
+    db.save().unwrap();
+}
+
+#[cfg(feature = "zstd")]
+fn assert_whole(path: &Path) {
+    let db = reopen(path);
+    assert_eq!(db.all_ids().len(), 200);
+    for id in [1, 100, 200] {
+        assert_eq!(content(&db, id), Some(text(id)));
+        assert_eq!(db.get_vector(id, "text"), Some(vector(id)));
+    }
+    assert_eq!(db.sparse_knn(&SparseVector::new(vec![(900, 1.0)]).unwrap(), 1, "terms").unwrap()[0].0, 7);
+}
+
+// Packed stores come back whole, still packed, and written as v12.
+#[cfg(feature = "zstd")]
+#[test]
+fn packed_stores_reopen_whole() {
+    let dir = Scratch::new("format-packed");
+    fill(&dir.path("plain.feather"), Compression::None);
+    for (name, compression) in [("meta.feather", Compression::Metadata), ("all.feather", Compression::All)] {
+        let path = dir.path(name);
+        fill(&path, compression);
+        assert_eq!(version(&path), 12);
+        assert!(std::fs::metadata(&path).unwrap().len() < std::fs::metadata(dir.path("plain.feather")).unwrap().len());
+        assert_whole(&path);
+        assert_eq!(reopen(&path).compression(), compression);
+        let report = fsck::check(&path).unwrap();
+        assert_eq!((report.version, report.compression), (12, compression));
+        assert!(report.problems.is_empty(), "{:?}", report.problems);
+
+        // saved again, unpacked, it is v11
+        let db = reopen(&path);
+        db.set_compression(Compression::None).unwrap();
+        db.save().unwrap();
+        drop(db);
+        assert_eq!(version(&path), 11);
+        assert_whole(&path);
+    }
+}
+
+// An unpacked store is written as v11, which builds before packing read.
+#[test]
+fn unpacked_stores_stay_v11() {
+    let dir = Scratch::new("format-v11");
+    let path = dir.path("t.feather");
+    fill(&path, Compression::None);
+    assert_eq!(version(&path), 11);
+    let report = fsck::check(&path).unwrap();
+    assert_eq!((report.version, report.compression), (11, Compression::None));
+    assert!(report.problems.is_empty(), "{:?}", report.problems);
+}
+
+// A v10 file, which has no sparse section, loads and is saved as v11.
+#[test]
+fn v10_files_load() {
+    let dir = Scratch::new("format-v10");
+    let path = dir.path("t.feather");
+    let db = create(&path);
+    add(&db, 1, "from v10");
+    db.save().unwrap();
+    drop(db);
+    let mut bytes = std::fs::read(&path).unwrap();
+    // the empty sparse section: a zero count
+    assert_eq!(bytes.split_off(bytes.len() - 4), [0; 4]);
+    bytes[4..8].copy_from_slice(&10u32.to_le_bytes());
+    std::fs::write(&path, bytes).unwrap();
+
+    let db = reopen(&path);
+    assert_eq!(content(&db, 1).as_deref(), Some("from v10"));
+    add(&db, 2, "after");
+    db.save().unwrap();
+    drop(db);
+    assert_eq!(version(&path), 11);
+    assert_eq!(content(&reopen(&path), 2).as_deref(), Some("after"));
+}
+
+// A packed section that does not unpack fails the open, which leaves the
+// file alone, and fsck says so.
+#[cfg(feature = "zstd")]
+#[test]
+fn damaged_packed_sections_are_refused() {
+    let dir = Scratch::new("format-damaged");
+    let path = dir.path("t.feather");
+    fill(&path, Compression::All);
+    let mut bytes = std::fs::read(&path).unwrap();
+    let middle = bytes.len() / 2;
+    for b in &mut bytes[middle..middle + 64] { *b ^= 0x5a; }
+    std::fs::write(&path, &bytes).unwrap();
+
+    let error = OpenOptions::new().open(&path).err().expect("a damaged file does not open");
+    assert!(format!("{:#}", error).contains("packed section"), "{:#}", error);
+    // and is left as it was, not saved over half-read
+    assert_eq!(std::fs::read(&path).unwrap(), bytes);
+    let report = fsck::check(&path).unwrap();
+    assert!(report.problems.iter().any(|p| matches!(p, fsck::Problem::Damaged { .. })), "{:?}", report.problems);
+}
+
+// Without the `zstd` feature there is no codec: asking to pack fails at
+// once, with the reason, and leaves no file behind.
+#[cfg(not(feature = "zstd"))]
+#[test]
+fn packing_needs_zstd() {
+    let dir = Scratch::new("format-no-zstd");
+    for (name, compression) in [("meta.feather", Compression::Metadata), ("all.feather", Compression::All)] {
+        let error = OpenOptions::new().create(true).dim(DIM).compression(compression).open(&dir.path(name))
+            .err().expect("packing needs zstd");
+        assert!(format!("{:#}", error).contains("no zstd"), "{:#}", error);
+        assert!(!dir.path(name).exists());
+    }
+    let path = dir.path("t.feather");
+    let db = OpenOptions::new().create(true).dim(DIM).open(&path).unwrap();
+    assert!(db.set_compression(Compression::Metadata).is_err());
+}
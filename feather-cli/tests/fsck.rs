@@ -0,0 +1,83 @@
+mod common;
+
+use common::*;
+use feather_db_cli::fsck::{self, Problem};
+use std::path::{Path, PathBuf};
+
+fn wal(path: &Path) -> PathBuf {
+    let mut wal = path.as_os_str().to_owned();
+    wal.push(".wal");
+    PathBuf::from(wal)
+}
+
+// One WAL entry: op, id, payload length, payload.
+fn entry(op: u8, id: u64, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![op];
+    bytes.extend(id.to_le_bytes());
+    bytes.extend((payload.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+// A WAL link from `from` to `to`.
+fn link(from: u64, to: u64, rel_type: &str) -> Vec<u8> {
+    let mut payload = to.to_le_bytes().to_vec();
+    payload.push(rel_type.len() as u8);
+    payload.extend_from_slice(rel_type.as_bytes());
+    payload.extend(1.0f32.to_le_bytes());
+    entry(0x04, from, &payload)
+}
+
+// Repair drops the WAL entries replay would trip on and the links to
+// nothing, keeps the rest, and leaves a file that checks clean.
+#[test]
+fn repair_fixes_the_wal_and_links() {
+    let dir = Scratch::new("fsck-repair");
+    let path = dir.path("t.feather");
+    let db = create(&path);
+    for id in 1..=3 { add(&db, id, &format!("record {}", id)); }
+    db.save().unwrap();
+    drop(db);
+    let mut log = link(1, 2, "cites");
+    log.extend(entry(0x7f, 9, b"?"));
+    log.extend(link(1, 99, "cites"));
+    log.extend(&link(3, 1, "cites")[..7]);
+    std::fs::write(wal(&path), &log).unwrap();
+
+    let report = fsck::check(&path).unwrap();
+    assert_eq!(report.wal_entries, 3);
+    assert_eq!(report.problems.len(), 3, "{:?}", report.problems);
+    assert!(report.problems.iter().all(Problem::is_repairable));
+    assert!(report.problems.contains(&Problem::DanglingLink { from: 1, to: 99, rel_type: "cites".into() }));
+
+    let report = fsck::repair(&path).unwrap();
+    assert!(report.is_ok(), "{:?}", report.problems);
+    let db = reopen(&path);
+    assert_eq!(db.all_ids().len(), 3);
+    for id in 1..=3 { assert_eq!(content(&db, id), Some(format!("record {}", id))); }
+    let edges = db.get_metadata(1).unwrap().edges;
+    assert_eq!(edges.iter().map(|e| e.target).collect::<Vec<_>>(), [2]);
+    drop(db);
+    assert!(fsck::check(&path).unwrap().is_ok());
+}
+
+// A file that does not read is damage: repair refuses it and writes
+// nothing.
+#[test]
+fn repair_leaves_damage_alone() {
+    let dir = Scratch::new("fsck-damaged");
+    let path = dir.path("t.feather");
+    let db = create(&path);
+    for id in 1..=20 { add(&db, id, "some content"); }
+    db.save().unwrap();
+    drop(db);
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes.truncate(bytes.len() * 2 / 3);
+    std::fs::write(&path, &bytes).unwrap();
+
+    let report = fsck::check(&path).unwrap();
+    assert!(matches!(report.problems[..], [Problem::Damaged { .. }]), "{:?}", report.problems);
+    assert!(fsck::repair(&path).is_err());
+    assert_eq!(std::fs::read(&path).unwrap(), bytes);
+    assert!(!wal(&path).exists());
+}
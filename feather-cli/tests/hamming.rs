@@ -0,0 +1,40 @@
+mod common;
+
+use common::*;
+use feather_db_cli::config::Metric;
+use feather_db_cli::{Metadata, OpenOptions};
+
+const BITS: usize = 8;
+
+// `bits` of a byte, highest first, as 0/1 components.
+fn bits(byte: u8) -> Vec<f32> {
+    (0..BITS).map(|i| f32::from((byte >> (BITS - 1 - i)) & 1)).collect()
+}
+
+// A hamming store keeps its metric and its bits across a reopen, and ranks
+// by the number of bits that differ.
+#[test]
+fn hamming_stores_rank_by_bits_after_reopen() {
+    let dir = Scratch::new("hamming");
+    let path = dir.path("t.feather");
+    let db = OpenOptions::new().create_new(true).dim(BITS).metric(Metric::Hamming).open(&path).unwrap();
+    for (id, byte) in [(1, 0b1111_0000), (2, 0b0000_1111), (3, 0b1110_0000), (4, 0b1000_0001)] {
+        db.add_with_metadata(id, &bits(byte), &Metadata::default(), "text").unwrap();
+    }
+    // raw embeddings are binarized: above zero is a set bit
+    let raw = [0.7, -0.2, 0.1, 0.0, -1.0, 0.3, -0.5, -0.1];
+    db.add_with_metadata(5, &raw, &Metadata::default(), "text").unwrap();
+    db.save().unwrap();
+    drop(db);
+
+    let db = reopen(&path);
+    assert_eq!(db.metric(), Metric::Hamming);
+    assert_eq!(db.get_vector(5, "text"), Some(bits(0b1010_0100)));
+    // 0, 1, 3, 4 and 8 bits from the query
+    let (ids, scores) = db.search(&bits(0b1111_0000), 5, Some("text")).unwrap();
+    assert_eq!(ids, [1, 3, 5, 4, 2]);
+    let want = [0.0, 1.0, 3.0, 4.0, 8.0].map(|d: f32| 1.0 / (1.0 + d));
+    for (score, want) in scores.iter().zip(want) {
+        assert!((score - want).abs() < 1e-6, "{:?}", scores);
+    }
+}
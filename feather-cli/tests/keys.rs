@@ -0,0 +1,34 @@
+mod common;
+
+use common::*;
+use feather_db_cli::Metadata;
+
+fn meta(content: &str) -> Metadata {
+    Metadata { content: content.to_string(), ..Metadata::default() }
+}
+
+// Keys name the same records after a reopen: a replaced key keeps its id,
+// a forgotten one names nothing, and new keys get ids never used before.
+#[test]
+fn keys_survive_a_reopen() {
+    let dir = Scratch::new("keys");
+    let path = dir.path("t.feather");
+    let db = create(&path);
+    let a = db.add_keyed("doc-a", &vector(1), &meta("a, first")).unwrap();
+    let b = db.add_keyed("doc-b", &vector(2), &meta("b")).unwrap();
+    assert_ne!(a, b);
+    assert_eq!(db.add_keyed("doc-a", &vector(3), &meta("a, second")).unwrap(), a);
+    assert_eq!(db.forget_key("doc-b").unwrap(), Some(b));
+    db.save().unwrap();
+    drop(db);
+
+    let db = reopen(&path);
+    assert_eq!(db.id_for_key("doc-a"), Some(a));
+    assert_eq!(db.key_of(a).as_deref(), Some("doc-a"));
+    assert_eq!(content(&db, a).as_deref(), Some("a, second"));
+    assert_eq!(db.id_for_key("doc-b"), None);
+    let again = db.add_keyed("doc-b", &vector(2), &meta("b, again")).unwrap();
+    let c = db.add_keyed("doc-c", &vector(4), &meta("c")).unwrap();
+    assert!(![a, b].contains(&again) && ![a, b, again].contains(&c));
+    assert_eq!(db.id_for_key("doc-b"), Some(again));
+}
@@ -0,0 +1,33 @@
+mod common;
+
+use common::*;
+use feather_db_cli::{OpenOptions, ReadOnly, SearchOptions};
+
+// A read-only handle refuses every change with `ReadOnly`, rather than
+// quietly doing nothing, and its searches count no recalls.
+#[test]
+fn read_only_handles_refuse_changes() {
+    let dir = Scratch::new("read-only");
+    let path = dir.path("t.feather");
+    let db = create(&path);
+    for id in 1..=2 { add(&db, id, "kept"); }
+    db.save().unwrap();
+    drop(db);
+
+    let db = OpenOptions::new().read_only(true).open(&path).unwrap();
+    let refused = [
+        db.link(1, 2).err(),
+        db.touch(1).err(),
+        db.set_property("key", b"value").err(),
+        db.remove_property("key").err(),
+        db.compact().err(),
+        db.expire().err(),
+        db.save().err(),
+    ];
+    for error in refused {
+        assert!(error.expect("refused").is::<ReadOnly>());
+    }
+    db.search_with_options(&vector(1), 2, "text", &SearchOptions::default()).unwrap();
+    assert_eq!(db.get_metadata(1).unwrap().recall_count, 0);
+    assert!(db.get_metadata(1).unwrap().edges.is_empty());
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::*;
+use feather_db_cli::replicate::{self, Primary, Secret};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+// Whether `get` answers, within a few seconds, with a body containing
+// `expected`.
+fn eventually(http: &str, get: &str, expected: &str) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(10) {
+        if let Ok(mut stream) = TcpStream::connect(http) {
+            let request = format!("GET {} HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n", get);
+            let mut reply = String::new();
+            if stream.write_all(request.as_bytes()).is_ok() && stream.read_to_string(&mut reply).is_ok()
+                && reply.contains(expected) {
+                return true;
+            }
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    false
+}
+
+// Only a primary with the shared secret is taken, and within the frame
+// limit. Stopping is global to the process, so this is one test.
+#[test]
+fn a_replica_takes_only_its_primary() {
+    let dir = Scratch::new("replicate");
+    std::fs::write(dir.path("keys"), "right-key\n").unwrap();
+    std::fs::write(dir.path("other"), "wrong-key\n").unwrap();
+    let secret = Secret::load(&dir.path("keys")).unwrap();
+
+    let replication = TcpListener::bind("127.0.0.1:0").unwrap();
+    let http = TcpListener::bind("127.0.0.1:0").unwrap();
+    let (addr, http_addr) = (replication.local_addr().unwrap().to_string(), http.local_addr().unwrap().to_string());
+    let copy = dir.path("copy.feather");
+    let follower = {
+        let (copy, secret) = (copy.clone(), secret.clone());
+        std::thread::spawn(move || replicate::follow(&copy, replication, &http, &secret, None, &Default::default()))
+    };
+
+    let db = create(&dir.path("main.feather"));
+    add(&db, 1, "replicated");
+    let stranger = Primary::new(&db, std::slice::from_ref(&addr), &Secret::load(&dir.path("other")).unwrap()).unwrap();
+    assert!(stranger.connected().is_empty());
+    drop(stranger);
+
+    // the right key, then a frame too long to take: the replica hangs up
+    let mut forged = TcpStream::connect(&addr).unwrap();
+    forged.write_all(replicate::MAGIC).unwrap();
+    forged.write_all(&9u16.to_le_bytes()).unwrap();
+    forged.write_all(b"right-key").unwrap();
+    let mut answer = [0];
+    forged.read_exact(&mut answer).unwrap();
+    assert_eq!(answer[0], replicate::ACCEPTED);
+    forged.write_all(b"S").unwrap();
+    forged.write_all(&(replicate::MAX_FRAME + 1).to_le_bytes()).unwrap();
+    forged.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    assert_eq!(forged.read(&mut answer).unwrap(), 0);
+    assert!(!copy.exists());
+
+    let mut primary = Primary::new(&db, &[addr], &secret).unwrap();
+    assert_eq!(primary.connected().len(), 1);
+    add(&db, 2, "shipped");
+    primary.ship(&db).unwrap();
+    assert!(eventually(&http_addr, "/get/2", "shipped"));
+
+    feather_db_cli::shutdown::request();
+    follower.join().unwrap().unwrap();
+    drop((primary, db));
+    assert_eq!(content(&reopen(&copy), 1).as_deref(), Some("replicated"));
+}
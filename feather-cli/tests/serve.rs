@@ -0,0 +1,119 @@
+mod common;
+
+use common::*;
+use feather_db_cli::{serve, Limits, Rate, Tokens};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+
+// Serve a new store at `path` on a port of its own, for the rest of the
+// process; returns the address.
+fn server(path: PathBuf, tokens: Option<Tokens>, limits: Limits) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    std::thread::spawn(move || {
+        let db = create(&path);
+        add(&db, 1, "served");
+        serve::serve_with(&db, &listener, None, None, tokens.as_ref(), &limits)
+    });
+    addr
+}
+
+// Send `request` as is and read the reply to its end.
+fn send(addr: &str, request: &[u8]) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(request).unwrap();
+    let mut reply = String::new();
+    let _ = stream.read_to_string(&mut reply);
+    reply
+}
+
+// The status of `GET /get/1`, sent with `headers`.
+fn status(addr: &str, headers: &str) -> u16 {
+    let reply = send(addr, format!("GET /get/1 HTTP/1.1\r\nHost: x\r\n{}\r\n", headers).as_bytes());
+    reply.split_whitespace().nth(1).and_then(|s| s.parse().ok()).unwrap_or(0)
+}
+
+// Without tokens to check, a token is not who the client is: a new
+// made-up one on each request still counts against its address.
+#[test]
+fn made_up_tokens_do_not_dodge_the_rate_limit() {
+    let dir = Scratch::new("serve-rate");
+    let limits = Limits { rate: Some(Rate { per_second: 0.01, burst: 1.0 }), ..Limits::default() };
+    let addr = server(dir.path("t.feather"), None, limits);
+    assert_eq!(status(&addr, "Authorization: Bearer first\r\n"), 200);
+    for token in ["second", "third"] {
+        assert_eq!(status(&addr, &format!("Authorization: Bearer {}\r\n", token)), 429);
+    }
+}
+
+// With tokens, each one that checks out has a bucket of its own.
+#[test]
+fn checked_tokens_are_limited_apart() {
+    let dir = Scratch::new("serve-rate-keys");
+    std::fs::write(dir.path("keys"), "key-a\nkey-b\n").unwrap();
+    let tokens = Tokens::load_files(Some(&dir.path("keys")), None).unwrap();
+    let limits = Limits { rate: Some(Rate { per_second: 0.01, burst: 1.0 }), ..Limits::default() };
+    let addr = server(dir.path("t.feather"), tokens, limits);
+    assert_eq!(status(&addr, "Authorization: Bearer key-a\r\n"), 200);
+    assert_eq!(status(&addr, "Authorization: Bearer key-a\r\n"), 429);
+    assert_eq!(status(&addr, "Authorization: Bearer key-b\r\n"), 200);
+    assert_eq!(status(&addr, "Authorization: Bearer made-up\r\n"), 401);
+}
+
+// Tokens and limits are checked before the body is read: a client that
+// is not let in gets its answer without sending the body it announced.
+#[test]
+fn refusals_come_before_the_body() {
+    let dir = Scratch::new("serve-head");
+    std::fs::write(dir.path("keys"), "key-a\n").unwrap();
+    let tokens = Tokens::load_files(Some(&dir.path("keys")), None).unwrap();
+    let addr = server(dir.path("t.feather"), tokens, Limits::default());
+
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+    let head = format!("POST /add HTTP/1.1\r\nHost: x\r\nContent-Length: {}\r\n\r\n", serve::MAX_BODY);
+    stream.write_all(head.as_bytes()).unwrap();
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).unwrap();
+    assert!(reply.starts_with("HTTP/1.1 401"), "{}", reply);
+
+    // the scheme is matched in any case
+    assert_eq!(status(&addr, "Authorization: bearer key-a\r\n"), 200);
+    assert_eq!(status(&addr, "Authorization: BEARER key-a\r\n"), 200);
+    assert_eq!(status(&addr, "Authorization: Basic key-a\r\n"), 401);
+
+    // a head past its limit is refused, its token unread
+    let long = format!("GET /get/1 HTTP/1.1\r\nAuthorization: Bearer key-a\r\nX-Padding: {}\r\n\r\n",
+                       "x".repeat(serve::MAX_HEAD));
+    assert!(send(&addr, long.as_bytes()).starts_with("HTTP/1.1 401"));
+    let open = server(dir.path("open.feather"), None, Limits::default());
+    assert!(send(&open, long.as_bytes()).starts_with("HTTP/1.1 431"));
+}
+
+// A connection that fails to come in is one client's problem: the server
+// logs it and answers the next; only the listener failing stops it.
+#[test]
+fn failed_connections_do_not_stop_the_server() {
+    let dir = Scratch::new("serve-failed");
+    let db = create(&dir.path("t.feather"));
+    add(&db, 1, "served");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let (answered, done) = std::sync::mpsc::channel();
+    let client = std::thread::spawn(move || {
+        let status = status(&addr, "");
+        answered.send(()).unwrap();
+        status
+    });
+    let connections = [Err(ErrorKind::ConnectionAborted.into()), Err(ErrorKind::ConnectionReset.into())]
+        .into_iter()
+        .chain(serve::accept(&listener).unwrap().take(1))
+        .chain(std::iter::once_with(move || {
+            done.recv().unwrap();
+            Err(ErrorKind::InvalidInput.into())
+        }));
+    let served = serve::serve_connections(&db, connections, None, None, None, &Limits::default());
+    assert_eq!(client.join().unwrap(), 200);
+    assert_eq!(served.unwrap_err().downcast::<std::io::Error>().unwrap().kind(), ErrorKind::InvalidInput);
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use common::*;
+use feather_db_cli::{shard, OpenOptions};
+
+// A sharded store saved and reopened holds what it held, spread over the
+// same shards, with links between shards and searches across them intact.
+#[test]
+fn sharded_stores_reopen_whole() {
+    let dir = Scratch::new("shard-reopen");
+    let path = dir.path("store");
+    let db = OpenOptions::new().create(true).dim(DIM).shards(4).open(&path).unwrap();
+    for id in 1..=40 { add(&db, id, &format!("record {}", id)); }
+    let (from, to) = (1, (2..=40).find(|&id| shard::of(id, 4) != shard::of(1, 4)).unwrap());
+    db.link(from, to).unwrap();
+    db.save().unwrap();
+    drop(db);
+
+    assert_eq!(shard::files(&path).len(), 4);
+    let db = reopen(&path);
+    assert_eq!(db.shard_count(), 4);
+    let mut ids = db.all_ids();
+    ids.sort_unstable();
+    assert_eq!(ids, (1..=40).collect::<Vec<_>>());
+    for id in 1..=40 { assert_eq!(content(&db, id), Some(format!("record {}", id))); }
+    let edges = db.get_metadata(from).unwrap().edges;
+    assert_eq!(edges.iter().map(|e| e.target).collect::<Vec<_>>(), [to]);
+    // every shard answers a search
+    let (mut found, _) = db.search(&vector(3), 40, Some("text")).unwrap();
+    assert_eq!(db.get_vector(found[0], "text"), Some(vector(3)));
+    found.sort_unstable();
+    assert_eq!(found, ids);
+    drop(db);
+
+    // the count is fixed when the store is created
+    assert!(OpenOptions::new().shards(3).open(&path).is_err());
+}
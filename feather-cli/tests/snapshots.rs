@@ -0,0 +1,37 @@
+mod common;
+
+use common::*;
+use feather_db_cli::{snapshots, DB};
+
+// Each save keeps the state it wrote; reopened, the store keeps taking
+// snapshots, and each past time opens as the store was then.
+#[test]
+fn snapshots_reopen_as_the_store_was() {
+    let dir = Scratch::new("snapshots");
+    let path = dir.path("t.feather");
+    let db = create(&path);
+    db.set_snapshots(3).unwrap();
+    add(&db, 1, "first");
+    db.save().unwrap();
+    drop(db);
+    // back-date it, rather than wait a second for the next one
+    let [(taken, first)] = &snapshots::list(&path).unwrap()[..] else { panic!("one snapshot") };
+    let before = taken - 60;
+    std::fs::rename(first, snapshots::dir(&path).join(format!("{}.feather", before))).unwrap();
+
+    let db = reopen(&path);
+    assert_eq!(db.snapshots(), 3);
+    add(&db, 2, "second");
+    db.save().unwrap();
+    drop(db);
+    assert_eq!(snapshots::list(&path).unwrap().len(), 2);
+
+    let then = DB::open_at(&path, before + 30).unwrap();
+    assert!(then.is_read_only());
+    assert_eq!(content(&then, 1).as_deref(), Some("first"));
+    assert_eq!(content(&then, 2), None);
+    drop(then);
+    let now = DB::open_at(&path, i64::MAX).unwrap();
+    assert_eq!(content(&now, 2).as_deref(), Some("second"));
+    assert!(DB::open_at(&path, before - 1).is_err());
+}
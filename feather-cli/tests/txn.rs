@@ -0,0 +1,64 @@
+mod common;
+
+use common::*;
+use feather_db_cli::{Metadata, Poisoned};
+
+#[test]
+fn committed_transactions_survive_a_reopen() {
+    let dir = Scratch::new("txn-commit");
+    let path = dir.path("t.feather");
+    let db = create(&path);
+    add(&db, 1, "one");
+    add(&db, 3, "three");
+    let mut tx = db.begin();
+    tx.add(2, &vector(2), &Metadata { content: "two".into(), ..Metadata::default() }, "text")
+        .link(2, 1, "follows", 1.0)
+        .forget(3);
+    tx.commit().unwrap();
+    drop(db);
+    let db = reopen(&path);
+    assert_eq!(content(&db, 2).as_deref(), Some("two"));
+    assert_eq!(db.get_metadata(2).unwrap().edges[0].target, 1);
+    assert!(db.get_metadata(3).is_none_or(|m| m.is_forgotten()));
+}
+
+#[test]
+fn a_transaction_checked_out_changes_nothing() {
+    let dir = Scratch::new("txn-checked");
+    let path = dir.path("t.feather");
+    let db = create(&path);
+    add(&db, 1, "one");
+    let mut tx = db.begin();
+    tx.add(2, &vector(2), &Metadata::default(), "text").link(2, 99, "follows", 1.0);
+    assert!(tx.commit().is_err());
+    assert_eq!(db.get_metadata(2), None);
+    drop(db);
+    assert_eq!(content(&reopen(&path), 2), None);
+}
+
+// A commit that fails after applying its operations must not let closing
+// the handle save them.
+#[test]
+fn a_commit_failing_part_way_reaches_nothing() {
+    let dir = Scratch::new("txn-torn");
+    let path = dir.path("t.feather");
+    let db = create(&path);
+    add(&db, 1, "one");
+    db.save().unwrap();
+    // a directory where the WAL goes: the commit cannot append to it
+    let wal = dir.path("t.feather.wal");
+    std::fs::create_dir(&wal).unwrap();
+    let mut tx = db.begin();
+    tx.add(2, &vector(2), &Metadata { content: "two".into(), ..Metadata::default() }, "text").forget(1);
+    assert!(tx.commit().is_err());
+    let e = db.add_with_metadata(3, &vector(3), &Metadata::default(), "text").unwrap_err();
+    assert!(e.downcast_ref::<Poisoned>().is_some(), "{:#}", e);
+    drop(db);
+
+    std::fs::remove_dir(&wal).unwrap();
+    let db = reopen(&path);
+    assert_eq!(content(&db, 1).as_deref(), Some("one"));
+    assert!(!db.get_metadata(1).unwrap().is_forgotten());
+    assert_eq!(db.get_metadata(2), None);
+    assert_eq!(db.get_metadata(3), None);
+}
@@ -0,0 +1,280 @@
+//! gRPC front end for a Feather store: the `feather.v1.Feather` service of
+//! `proto/feather/v1/feather.proto`.
+//!
+//! A `DB` is not `Send`, so one worker thread owns the store and the RPC
+//! handlers hand it jobs over a channel. Jobs run one at a time, in arrival
+//! order, as under `feather serve`; the streaming RPCs save round trips,
+//! not store time. `InsertBatch` writes its stream `BATCH_SIZE` records at
+//! a time through `import`, so the duplicate-id policy and dedup mode apply
+//! as they do to `feather import`. Writes reach the WAL at once; the file is
+//! checkpointed every `serve::CHECKPOINT_EVERY` writes and on `save`.
+
+// `Status` is tonic's error type, large or not; the handlers return it as is.
+#![allow(clippy::result_large_err)]
+
+pub mod pb {
+    tonic::include_proto!("feather.v1");
+}
+
+use feather_db_cli::trace::{Level, Span};
+use feather_db_cli::{decay, import, serve, ContextType, Edge, Filter, Metadata, Record, SearchOptions, DB};
+use pb::feather_server::{Feather, FeatherServer};
+use std::pin::Pin;
+use std::sync::mpsc;
+use tokio::sync::oneshot;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::Stream;
+use tonic::{Request, Response, Status, Streaming};
+
+/// Records per insert job when `InsertBatch` streams them in.
+pub const BATCH_SIZE: usize = import::DEFAULT_BATCH_SIZE;
+
+/// Hits returned when a search asks for none.
+const DEFAULT_K: usize = 10;
+
+/// `SearchBatch` replies buffered ahead of a slow client.
+const REPLY_BUFFER: usize = 16;
+
+type Reply<T> = oneshot::Sender<Result<T, Status>>;
+
+enum Job {
+    Insert(Vec<Record>, Reply<pb::InsertReply>),
+    Search(pb::SearchRequest, Reply<pb::SearchReply>),
+    Get(u64, Reply<pb::Record>),
+    Delete(u64, Reply<pb::DeleteReply>),
+    Save(Reply<()>),
+}
+
+#[derive(Clone)]
+pub struct Service {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl Service {
+    /// Start the worker thread, which calls `open` for its store. Fails if
+    /// `open` does.
+    pub fn start(open: impl FnOnce() -> anyhow::Result<DB> + Send + 'static) -> anyhow::Result<Self> {
+        let (jobs, queue) = mpsc::channel();
+        let (ready, opened) = mpsc::channel();
+        std::thread::spawn(move || {
+            let db = match open() {
+                Ok(db) => db,
+                Err(e) => return ready.send(Err(e)).unwrap_or(()),
+            };
+            if ready.send(Ok(())).is_ok() {
+                work(&db, queue);
+            }
+        });
+        opened.recv().map_err(|_| anyhow::anyhow!("store worker died while opening"))??;
+        Ok(Service { jobs })
+    }
+
+    pub fn into_server(self) -> FeatherServer<Self> {
+        FeatherServer::new(self)
+    }
+
+    /// Checkpoint the file.
+    pub async fn save(&self) -> Result<(), Status> {
+        self.run(Job::Save).await
+    }
+
+    async fn run<T>(&self, job: impl FnOnce(Reply<T>) -> Job) -> Result<T, Status> {
+        let (reply, answer) = oneshot::channel();
+        self.jobs.send(job(reply)).map_err(|_| Status::unavailable("store worker stopped"))?;
+        answer.await.map_err(|_| Status::internal("store worker dropped the request"))?
+    }
+}
+
+// The worker loop: run jobs until every `Service` handle is gone.
+fn work(db: &DB, queue: mpsc::Receiver<Job>) {
+    let mut writes = 0;
+    for job in queue {
+        let wrote = match job {
+            Job::Insert(records, reply) => {
+                let result = insert(db, records);
+                let added = result.as_ref().map_or(0, |r| r.added as usize);
+                let _ = reply.send(result);
+                added
+            }
+            Job::Search(query, reply) => {
+                let _ = reply.send(search(db, query));
+                0
+            }
+            Job::Get(id, reply) => {
+                let _ = reply.send(live(db, id).map(|record| to_pb(&record)));
+                0
+            }
+            Job::Delete(id, reply) => {
+                let result = live(db, id)
+                    .and_then(|_| db.forget(id).map_err(invalid))
+                    .map(|()| pb::DeleteReply {});
+                let deleted = usize::from(result.is_ok());
+                let _ = reply.send(result);
+                deleted
+            }
+            Job::Save(reply) => {
+                let _ = reply.send(db.save().map_err(invalid));
+                0
+            }
+        };
+        if wrote > 0 && (writes + wrote) / serve::CHECKPOINT_EVERY > writes / serve::CHECKPOINT_EVERY {
+            if let Err(e) = db.save() {
+                Span::new(Level::Warn, "feather::grpc").record_str("error", &e.to_string());
+            }
+        }
+        writes += wrote;
+    }
+}
+
+fn invalid(e: anyhow::Error) -> Status {
+    Status::invalid_argument(format!("{:#}", e))
+}
+
+fn insert(db: &DB, records: Vec<Record>) -> Result<pb::InsertReply, Status> {
+    let report = import::import(db, records.into_iter().map(Ok), BATCH_SIZE, None).map_err(invalid)?;
+    Ok(pb::InsertReply {
+        added: report.records as u64,
+        skipped: report.skipped as u64,
+        deduplicated: report.deduplicated as u64,
+    })
+}
+
+fn search(db: &DB, query: pb::SearchRequest) -> Result<pb::SearchReply, Status> {
+    let filter = (!query.filter.is_empty()).then(|| Filter::parse(&query.filter)).transpose().map_err(invalid)?;
+    let options = SearchOptions {
+        offset: query.offset as usize,
+        filter,
+        text: (!query.text.is_empty()).then_some(query.text),
+        min_score: query.min_score,
+        ..SearchOptions::default()
+    };
+    let k = if query.k == 0 { DEFAULT_K } else { query.k as usize };
+    let modality = if query.modality.is_empty() { "text" } else { &query.modality };
+    let hits = db.search_with_options(&query.vector, k, modality, &options).map_err(invalid)?;
+    Ok(pb::SearchReply { hits: hits.into_iter().map(|(id, score)| pb::Hit { id, score }).collect() })
+}
+
+fn live(db: &DB, id: u64) -> Result<Record, Status> {
+    db.record(id)
+        .filter(|r| !r.metadata.is_forgotten())
+        .ok_or_else(|| Status::not_found(format!("no record {}", id)))
+}
+
+fn from_pb(record: pb::Record) -> Result<Record, Status> {
+    let context_type = u8::try_from(record.context_type)
+        .map_err(|_| Status::invalid_argument(format!("record {}: context_type over 255", record.id)))?;
+    Ok(Record {
+        id: record.id,
+        vectors: record.vectors.into_iter().map(|(modality, v)| (modality, v.values)).collect(),
+        sparse: Default::default(),
+        metadata: Metadata {
+            timestamp: record.timestamp.unwrap_or_else(decay::now),
+            importance: record.importance.unwrap_or(1.0),
+            confidence: record.confidence.unwrap_or(1.0),
+            context_type: ContextType::from(context_type),
+            source: record.source,
+            content: record.content,
+            namespace_id: record.namespace_id,
+            entity_id: record.entity_id,
+            attributes: record.attributes.into_iter().collect(),
+            edges: record.edges.into_iter()
+                .map(|e| Edge { target: e.target, rel_type: e.rel_type, weight: e.weight })
+                .collect(),
+            ttl: record.ttl,
+            ..Metadata::default()
+        },
+    })
+}
+
+fn to_pb(record: &Record) -> pb::Record {
+    let meta = &record.metadata;
+    pb::Record {
+        id: record.id,
+        vectors: record.vectors.iter()
+            .map(|(modality, v)| (modality.clone(), pb::Vector { values: v.clone() }))
+            .collect(),
+        timestamp: Some(meta.timestamp),
+        importance: Some(meta.importance),
+        confidence: Some(meta.confidence),
+        context_type: meta.context_type.code().into(),
+        source: meta.source.clone(),
+        content: meta.content.clone(),
+        namespace_id: meta.namespace_id.clone(),
+        entity_id: meta.entity_id.clone(),
+        attributes: meta.attributes.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        edges: meta.edges.iter()
+            .map(|e| pb::Edge { target: e.target, rel_type: e.rel_type.clone(), weight: e.weight })
+            .collect(),
+        ttl: meta.ttl,
+        recall_count: meta.recall_count,
+        last_recalled_at: meta.last_recalled_at,
+    }
+}
+
+type ReplyStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Feather for Service {
+    async fn insert(&self, request: Request<pb::Record>) -> Result<Response<pb::InsertReply>, Status> {
+        let record = from_pb(request.into_inner())?;
+        self.run(|reply| Job::Insert(vec![record], reply)).await.map(Response::new)
+    }
+
+    async fn insert_batch(&self, request: Request<Streaming<pb::Record>>) -> Result<Response<pb::InsertReply>, Status> {
+        let mut stream = request.into_inner();
+        let mut total = pb::InsertReply::default();
+        let mut batch = Vec::new();
+        loop {
+            let next = stream.message().await?;
+            if let Some(record) = next.map(from_pb).transpose()? {
+                batch.push(record);
+                if batch.len() < BATCH_SIZE { continue; }
+            }
+            let done = batch.len() < BATCH_SIZE;
+            if !batch.is_empty() {
+                let records = std::mem::take(&mut batch);
+                let report = self.run(|reply| Job::Insert(records, reply)).await?;
+                total.added += report.added;
+                total.skipped += report.skipped;
+                total.deduplicated += report.deduplicated;
+            }
+            if done { return Ok(Response::new(total)); }
+        }
+    }
+
+    async fn search(&self, request: Request<pb::SearchRequest>) -> Result<Response<pb::SearchReply>, Status> {
+        let query = request.into_inner();
+        self.run(|reply| Job::Search(query, reply)).await.map(Response::new)
+    }
+
+    type SearchBatchStream = ReplyStream<pb::SearchReply>;
+
+    async fn search_batch(&self, request: Request<Streaming<pb::SearchRequest>>)
+                          -> Result<Response<Self::SearchBatchStream>, Status> {
+        let mut queries = request.into_inner();
+        let service = self.clone();
+        let (replies, stream) = tokio::sync::mpsc::channel(REPLY_BUFFER);
+        tokio::spawn(async move {
+            loop {
+                let reply = match queries.message().await {
+                    Ok(Some(query)) => service.run(|reply| Job::Search(query, reply)).await,
+                    Ok(None) => return,
+                    Err(status) => Err(status),
+                };
+                let failed = reply.is_err();
+                if replies.send(reply).await.is_err() || failed { return; }
+            }
+        });
+        Ok(Response::new(Box::pin(ReceiverStream::new(stream))))
+    }
+
+    async fn get(&self, request: Request<pb::GetRequest>) -> Result<Response<pb::Record>, Status> {
+        let id = request.into_inner().id;
+        self.run(|reply| Job::Get(id, reply)).await.map(Response::new)
+    }
+
+    async fn delete(&self, request: Request<pb::DeleteRequest>) -> Result<Response<pb::DeleteReply>, Status> {
+        let id = request.into_inner().id;
+        self.run(|reply| Job::Delete(id, reply)).await.map(Response::new)
+    }
+}
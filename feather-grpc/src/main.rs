@@ -0,0 +1,37 @@
+use clap::Parser;
+use feather_db_cli::OpenOptions;
+use feather_grpc::Service;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+/// Serve a Feather store over gRPC (feather.v1.Feather)
+#[derive(Parser)]
+#[command(name = "feather-grpc", version)]
+struct Cli {
+    db: PathBuf,
+    #[arg(long, default_value = "127.0.0.1:50051")] addr: SocketAddr,
+    /// Serve this named collection of the file
+    #[arg(long)] collection: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let (path, collection) = (cli.db.clone(), cli.collection.clone());
+    let service = Service::start(move || {
+        let db = OpenOptions::new().open(&path)?;
+        db.expire()?;
+        match collection {
+            Some(name) => db.collection(&name),
+            None => Ok(db),
+        }
+    })?;
+    println!("Serving {:?} over gRPC on {}", cli.db, cli.addr);
+    let handle = service.clone();
+    tonic::transport::Server::builder()
+        .add_service(service.into_server())
+        .serve_with_shutdown(cli.addr, async { tokio::signal::ctrl_c().await.unwrap_or(()) })
+        .await?;
+    handle.save().await?;
+    Ok(())
+}
@@ -0,0 +1,91 @@
+//! TLS termination for `feather serve`: the same JSON endpoints, API keys
+//! and access tokens (see `feather_db_cli::serve`), over HTTPS, for one
+//! store or a data dir of them (see `feather_db_cli::tenants`).
+//!
+//! Each accepted connection is wrapped in a rustls server session and
+//! handed to `serve::serve_connections`, so requests are read and answered
+//! as over HTTP, under the same limits. The handshake happens on the first
+//! read, on the connection's own thread and under the same read timeout; a
+//! client that fails it is dropped like one that sends no HTTP. Each
+//! connection ends with a TLS close_notify.
+
+use feather_db_cli::replicate::Primary;
+use feather_db_cli::serve::Connection;
+use feather_db_cli::webhook::Webhooks;
+use feather_db_cli::{serve, tenants, Limits, Tokens, DB};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::net::{IpAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::Arc;
+
+/// A server configuration presenting the PEM certificate chain in `cert`,
+/// signed with the PEM private key (PKCS#8, PKCS#1 or SEC1) in `key`.
+pub fn config(cert: &Path, key: &Path) -> anyhow::Result<Arc<ServerConfig>> {
+    let open = |path: &Path| File::open(path).map(BufReader::new).map_err(|e| anyhow::anyhow!("cannot read {:?}: {}", path, e));
+    let certs: Vec<CertificateDer<'static>> = rustls_pemfile::certs(&mut open(cert)?)
+        .collect::<Result<_, _>>()
+        .map_err(|e| anyhow::anyhow!("{:?}: {}", cert, e))?;
+    anyhow::ensure!(!certs.is_empty(), "{:?} holds no PEM certificate", cert);
+    let private: PrivateKeyDer<'static> = rustls_pemfile::private_key(&mut open(key)?)
+        .map_err(|e| anyhow::anyhow!("{:?}: {}", key, e))?
+        .ok_or_else(|| anyhow::anyhow!("{:?} holds no PEM private key", key))?;
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, private)
+        .map_err(|e| anyhow::anyhow!("{:?} and {:?}: {}", cert, key, e))?;
+    Ok(Arc::new(config))
+}
+
+/// `serve::serve_with` over HTTPS: answer the connections on `listener`
+/// as `config` has TLS present itself, until the listener fails or the
+/// process is asked to stop (see `feather_db_cli::shutdown`).
+pub fn serve(db: &DB, listener: &TcpListener, config: Arc<ServerConfig>, primary: Option<&mut Primary>,
+             webhooks: Option<&Webhooks>, tokens: Option<&Tokens>, limits: &Limits) -> anyhow::Result<()> {
+    serve::serve_connections(db, accept(listener, config)?, primary, webhooks, tokens, limits)
+}
+
+/// `tenants::serve` over HTTPS: answer the connections on `listener` for
+/// the stores of `dir`.
+pub fn serve_dir(dir: &Path, listener: &TcpListener, config: Arc<ServerConfig>, tokens: Option<&Tokens>,
+                 limits: &Limits) -> anyhow::Result<()> {
+    tenants::serve_connections(dir, accept(listener, config)?, tokens, limits)
+}
+
+// The connections accepted on `listener`, each in a TLS session presenting
+// `config`.
+fn accept(listener: &TcpListener, config: Arc<ServerConfig>)
+          -> std::io::Result<impl Iterator<Item = std::io::Result<Tls>> + Send + 'static> {
+    let connections = serve::accept(listener)?;
+    Ok(connections.map(move |stream| {
+        let session = ServerConnection::new(config.clone()).map_err(std::io::Error::other)?;
+        Ok(Tls(StreamOwned::new(session, stream?)))
+    }))
+}
+
+/// One HTTPS connection, closed cleanly when dropped.
+pub struct Tls(StreamOwned<ServerConnection, TcpStream>);
+
+impl Read for Tls {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> { self.0.read(buf) }
+}
+
+impl Write for Tls {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> { self.0.write(buf) }
+
+    fn flush(&mut self) -> std::io::Result<()> { self.0.flush() }
+}
+
+impl Connection for Tls {
+    fn peer(&self) -> Option<IpAddr> { self.0.sock.peer_addr().ok().map(|addr| addr.ip()) }
+}
+
+impl Drop for Tls {
+    fn drop(&mut self) {
+        self.0.conn.send_close_notify();
+        // a client that hung up is its own problem
+        let _ = self.0.flush();
+    }
+}
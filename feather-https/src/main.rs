@@ -0,0 +1,106 @@
+use clap::Parser;
+use feather_db_cli::replicate::{Primary, Secret};
+use feather_db_cli::webhook::Webhooks;
+use feather_db_cli::{Decay, Limits, Maintenance, OpenOptions, Rate, Tokens};
+use std::path::PathBuf;
+
+/// Serve a Feather store as `feather serve` does, over HTTPS
+#[derive(Parser)]
+#[command(name = "feather-https", version)]
+struct Cli {
+    #[arg(required_unless_present = "data_dir")] db: Option<PathBuf>,
+    /// Serve every store in DIR, each as /db/{name}/...
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["db", "collection", "replicate_to", "webhooks", "warm"])]
+    data_dir: Option<PathBuf>,
+    #[arg(long, default_value = "127.0.0.1:8443")] http: String,
+    /// PEM certificate chain to present
+    #[arg(long, value_name = "FILE")] tls_cert: PathBuf,
+    /// PEM private key of --tls-cert
+    #[arg(long, value_name = "FILE")] tls_key: PathBuf,
+    /// Answer only requests with a bearer token listed in FILE, one API key per line
+    #[arg(long, value_name = "FILE")] api_key_file: Option<PathBuf>,
+    /// Answer only requests with a bearer token listed in FILE, one `TOKEN LABEL[,LABEL...]` per line
+    #[arg(long, value_name = "FILE")] access_file: Option<PathBuf>,
+    /// Serve this named collection of the file
+    #[arg(long)] collection: Option<String>,
+    /// Stream every write to the read replica listening at ADDR (repeatable); the
+    /// replica must share --api-key-file
+    #[arg(long = "replicate-to", value_name = "ADDR", requires = "api_key_file")] replicate_to: Vec<String>,
+    /// POST the records each request adds or deletes, as JSON, to URL (repeatable)
+    #[arg(long = "webhook", value_name = "URL")] webhooks: Vec<String>,
+    /// Read the whole store into memory before taking requests
+    #[arg(long)] warm: bool,
+    /// Requests each client may make, e.g. 20/s or 600/min; more are answered 429
+    #[arg(long, value_name = "RATE", value_parser = Rate::parse)] rate_limit: Option<Rate>,
+    /// Searches waiting to be answered at once; more are answered 503
+    #[arg(long, value_name = "N")] max_searches: Option<usize>,
+    /// Stop ranking a search after MS milliseconds, replying with the hits found so far
+    #[arg(long, value_name = "MS")] search_budget: Option<u64>,
+    /// Maintain the store every INTERVAL (e.g. 10m): expire, compact, checkpoint
+    #[arg(long, value_name = "INTERVAL", value_parser = duration, conflicts_with = "data_dir")] maintenance: Option<f64>,
+    /// Also decay importance with this half-life on each maintenance pass
+    #[arg(long, value_name = "HALF_LIFE", value_parser = duration, requires = "maintenance")]
+    maintenance_half_life: Option<f64>,
+}
+
+fn duration(s: &str) -> Result<f64, String> {
+    feather_db_cli::decay::parse_duration(s).map_err(|e| e.to_string())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let config = feather_https::config(&cli.tls_cert, &cli.tls_key)?;
+    let tokens = Tokens::load_files(cli.api_key_file.as_deref(), cli.access_file.as_deref())?;
+    if let Some(tokens) = &tokens {
+        println!("Answering only requests with one of {} token(s)", tokens.len());
+    }
+    let limits = Limits {
+        rate: cli.rate_limit,
+        max_searches: cli.max_searches,
+        search_budget: cli.search_budget.map(std::time::Duration::from_millis),
+    };
+    feather_db_cli::shutdown::on_signals()?;
+    let bind = |addr: &str| std::net::TcpListener::bind(addr).map_err(|e| anyhow::anyhow!("cannot listen on {}: {}", addr, e));
+    if let Some(dir) = &cli.data_dir {
+        let listener = bind(&cli.http)?;
+        println!("Serving the stores in {:?} on https://{}/db/{{name}}", dir, listener.local_addr()?);
+        feather_https::serve_dir(dir, &listener, config, tokens.as_ref(), &limits)?;
+        println!("Stopped; the stores in {:?} are saved", dir);
+        return Ok(());
+    }
+    let path = cli.db.expect("required without --data-dir");
+    let mut options = OpenOptions::new().create(true);
+    if let Some(name) = &cli.collection {
+        options = options.collection(name);
+    }
+    if let Some(secs) = cli.maintenance {
+        let mut policy = Maintenance::every(std::time::Duration::from_secs_f64(secs));
+        if let Some(half_life) = cli.maintenance_half_life {
+            policy = policy.decay(Decay::new(half_life, 0.0)?);
+        }
+        options = options.maintenance(policy);
+    }
+    let db = options.open(&path)?;
+    db.expire()?;
+    if cli.warm {
+        let bytes = db.warm()?;
+        println!("Warmed {:.1} MB", bytes as f64 / 1e6);
+    }
+    let listener = bind(&cli.http)?;
+    println!("Serving {:?} on https://{}", path, listener.local_addr()?);
+    let hooks = match cli.webhooks.is_empty() {
+        true => None,
+        false => Some(Webhooks::new(&db, &cli.webhooks)?),
+    };
+    let mut primary = match cli.replicate_to.is_empty() {
+        true => None,
+        false => {
+            let file = cli.api_key_file.as_deref().expect("required by --replicate-to");
+            Some(Primary::new(&db, &cli.replicate_to, &Secret::load(file)?)?)
+        }
+    };
+    feather_https::serve(&db, &listener, config, primary.as_mut(), hooks.as_ref(), tokens.as_ref(), &limits)?;
+    drop((primary, hooks, db));
+    println!("Stopped; {:?} is saved", path);
+    Ok(())
+}
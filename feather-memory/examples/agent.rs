@@ -0,0 +1,45 @@
+//! A toy agent loop on `MemoryStore`: remember facts, work inside a session,
+//! consolidate duplicates, and assemble prompt context.
+//!
+//!     cargo run --example agent
+//!
+//! Embeddings here are a bag-of-letters stand-in; a real agent would call
+//! its embedding model.
+
+use feather_memory::MemoryStore;
+
+const DIM: usize = 26;
+
+fn embed(text: &str) -> Vec<f32> {
+    let mut v = [0f32; DIM];
+    for c in text.chars().filter(char::is_ascii_alphabetic) {
+        v[(c.to_ascii_lowercase() as u8 - b'a') as usize] += 1.0;
+    }
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt().max(1.0);
+    v.iter().map(|x| x / norm).collect()
+}
+
+fn main() -> anyhow::Result<()> {
+    let memory = MemoryStore::in_memory(DIM);
+
+    let name = memory.remember("The user's name is Ada", &embed("The user's name is Ada"))?;
+    memory.pin(name)?;
+    for fact in ["Ada prefers dark roast coffee", "Ada prefers dark roast coffee",
+                 "Ada is allergic to peanuts", "The deploy runs every Friday"] {
+        memory.remember(fact, &embed(fact))?;
+    }
+
+    let session = memory.session("chat-42");
+    session.remember("Ada asked for a cafe nearby", &embed("Ada asked for a cafe nearby"))?;
+
+    let report = memory.consolidate()?;
+    println!("consolidated: {} of {} memories folded", report.merged, report.examined);
+
+    let query = embed("what coffee should I order for Ada");
+    println!("session recall: {:?}",
+             session.recall(&query, 3)?.iter().map(|m| m.content().to_string()).collect::<Vec<_>>());
+    println!("context:\n{}", memory.context(&query, 3)?.text);
+
+    println!("kept {} session memories", session.commit()?);
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! Consolidation: folding near-duplicate memories into one.
+//!
+//! Agents re-learn the same fact many times. Two memories whose similarity
+//! reaches `MemoryConfig::consolidate_threshold` are merged: the survivor —
+//! the pinned one, else the more important, else the older — keeps its
+//! content and absorbs the other's importance (the max), recall count (the
+//! sum), activity times and edges; the other is forgotten.
+
+use crate::{Memory, MemoryStore};
+
+/// Neighbours examined per memory.
+const NEIGHBOURS: usize = 8;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConsolidationReport {
+    /// Live memories examined.
+    pub examined: usize,
+    /// Memories folded into a near-duplicate and forgotten.
+    pub merged: usize,
+}
+
+impl MemoryStore {
+    /// Fold near-duplicate memories into one another.
+    pub fn consolidate(&self) -> anyhow::Result<ConsolidationReport> {
+        let (db, modality) = (self.db(), &self.config().modality);
+        let threshold = self.config().consolidate_threshold;
+        let mut report = ConsolidationReport::default();
+        for id in db.ids(modality) {
+            if self.get(id).is_none() { continue; }   // absorbed earlier in this pass
+            report.examined += 1;
+            let Some(vec) = db.get_vector(id, modality) else { continue };
+            for (other, dist) in db.knn(&vec, NEIGHBOURS, modality)? {
+                if other == id || 1.0 / (1.0 + dist) < threshold { continue; }
+                let (Some(a), Some(b)) = (self.get(id), self.get(other)) else { continue };
+                if a.is_pinned() && b.is_pinned() { continue; }
+                let (keep, drop) = if survives(&a, &b) { (a, b) } else { (b, a) };
+                self.absorb(keep, &drop)?;
+                report.merged += 1;
+                if drop.id == id { break; }
+            }
+        }
+        Ok(report)
+    }
+
+    fn absorb(&self, keep: Memory, drop: &Memory) -> anyhow::Result<()> {
+        let (mut meta, other) = (keep.metadata, &drop.metadata);
+        meta.importance = meta.importance.max(other.importance);
+        meta.recall_count = meta.recall_count.saturating_add(other.recall_count);
+        meta.last_recalled_at = meta.last_recalled_at.max(other.last_recalled_at);
+        meta.timestamp = meta.timestamp.max(other.timestamp);
+        // the longer-lived side wins; 0 = never expires
+        meta.ttl = if meta.ttl == 0 || other.ttl == 0 { 0 } else { meta.ttl.max(other.ttl) };
+        for edge in &other.edges {
+            let known = meta.edges.iter().any(|e| e.target == edge.target && e.rel_type == edge.rel_type);
+            if edge.target != keep.id && !known {
+                meta.edges.push(edge.clone());
+            }
+        }
+        self.db().put_metadata(keep.id, &meta)?;
+        self.forget(drop.id)
+    }
+}
+
+// Whether `a` survives a merge with `b`.
+fn survives(a: &Memory, b: &Memory) -> bool {
+    if a.is_pinned() != b.is_pinned() { return a.is_pinned(); }
+    match a.metadata.importance.total_cmp(&b.metadata.importance) {
+        std::cmp::Ordering::Equal => (a.metadata.timestamp, a.id) <= (b.metadata.timestamp, b.id),
+        order => order.is_gt(),
+    }
+}
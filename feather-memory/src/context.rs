@@ -0,0 +1,40 @@
+//! Context assembly: the memories worth putting in a prompt, as text.
+
+use crate::{Memory, MemoryStore};
+use std::collections::HashSet;
+
+/// An assembled context.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Context {
+    /// One `- <content>` line per memory.
+    pub text: String,
+    /// The memories in `text`, in order.
+    pub memories: Vec<Memory>,
+}
+
+impl MemoryStore {
+    /// The pinned memories (most important first), then the `k` memories
+    /// most relevant to `query`, one line each, within the store's
+    /// `context_budget` characters. Memories without content are skipped;
+    /// the first one that does not fit ends the context.
+    pub fn context(&self, query: &[f32], k: usize) -> anyhow::Result<Context> {
+        let budget = self.config().context_budget;
+        let pinned = self.pinned();
+        let pinned_ids: HashSet<u64> = pinned.iter().map(|m| m.id).collect();
+        let relevant = self.recall(query, k)?.into_iter().filter(|m| !pinned_ids.contains(&m.id));
+
+        let mut context = Context::default();
+        let mut used = 0;
+        for memory in pinned.into_iter().chain(relevant) {
+            let content = memory.content().trim();
+            if content.is_empty() { continue; }
+            let line = format!("- {}\n", content.replace('\n', " "));
+            let len = line.chars().count();
+            if used + len > budget { break; }
+            used += len;
+            context.text.push_str(&line);
+            context.memories.push(memory);
+        }
+        Ok(context)
+    }
+}
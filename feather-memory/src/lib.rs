@@ -0,0 +1,257 @@
+//! Agent memory on Feather.
+//!
+//! `MemoryStore` wraps a Feather `DB` and wires its low-level features into
+//! the few operations an agent needs, with defaults that work out of the box:
+//!
+//! * `remember` / `recall` — store a memory, find the relevant ones. Recall
+//!   ranks by similarity × importance, and importance fades with inactivity
+//!   (`MemoryConfig::decay`) unless the memory is used.
+//! * `pin` — keep a memory from fading or expiring, and put it at the top of
+//!   every assembled context.
+//! * `session` — working memory scoped to one conversation. It expires after
+//!   `MemoryConfig::session_ttl` unless the session is committed.
+//! * `consolidate` — fold near-duplicate memories into one.
+//! * `context` — the pinned and relevant memories as prompt-ready text within
+//!   a character budget.
+//! * `maintain` — the periodic sweep: expire, then bake in decay.
+//!
+//! Everything a `MemoryStore` writes is a plain Feather record, so the file
+//! stays readable by the `feather` CLI and every other binding; `db()` gives
+//! access to the full API.
+
+mod consolidate;
+mod context;
+mod session;
+
+pub use consolidate::ConsolidationReport;
+pub use context::Context;
+pub use feather_db_cli::{Decay, Metadata, DB};
+pub use session::Session;
+
+use std::cell::Cell;
+use std::path::Path;
+
+/// Attribute marking a pinned memory (`"true"`).
+pub const PINNED: &str = "pinned";
+/// Attribute holding the name of the session a memory belongs to.
+pub const SESSION: &str = "session";
+
+/// Candidates fetched per requested memory before re-ranking.
+const CANDIDATE_FACTOR: usize = 3;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemoryConfig {
+    /// How importance fades with inactivity; None = memories never fade.
+    pub decay: Option<Decay>,
+    /// Seconds a session memory lives unless its session is committed;
+    /// 0 = session memories never expire.
+    pub session_ttl: i64,
+    /// Similarity (`1 / (1 + distance)`) at or above which `consolidate`
+    /// folds two memories into one.
+    pub consolidate_threshold: f32,
+    /// Character budget of an assembled `context`.
+    pub context_budget: usize,
+    /// Modality memories are embedded under.
+    pub modality: String,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        MemoryConfig {
+            decay: Some(Decay { half_life: 30.0 * 86_400.0, floor: 0.05 }),
+            session_ttl: 86_400,
+            consolidate_threshold: 0.95,
+            context_budget: 4_000,
+            modality: "text".to_string(),
+        }
+    }
+}
+
+/// A recalled memory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Memory {
+    pub id: u64,
+    /// Similarity × (decayed) importance.
+    pub score: f32,
+    pub metadata: Metadata,
+}
+
+impl Memory {
+    pub fn content(&self) -> &str { &self.metadata.content }
+
+    pub fn is_pinned(&self) -> bool { is_pinned(&self.metadata) }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MaintenanceReport {
+    /// Memories whose ttl had run out.
+    pub expired: usize,
+    /// Memories whose stored importance was lowered by decay.
+    pub decayed: usize,
+}
+
+pub struct MemoryStore {
+    db: DB,
+    config: MemoryConfig,
+    next_id: Cell<u64>,
+}
+
+impl MemoryStore {
+    /// Open (or create) the memory file at `path`. Expired memories are swept
+    /// on open.
+    pub fn open(path: &Path, dim: usize) -> anyhow::Result<Self> {
+        let db = feather_db_cli::OpenOptions::new().dim(dim).create(true).open(path)?;
+        db.expire()?;
+        Ok(Self::wrap(db))
+    }
+
+    /// A memory with no backing file; see `DB::in_memory`.
+    pub fn in_memory(dim: usize) -> Self {
+        Self::wrap(DB::in_memory(dim))
+    }
+
+    /// Build on an already opened `DB` (or collection of one).
+    pub fn wrap(db: DB) -> Self {
+        let next_id = db.all_ids().into_iter().max().unwrap_or(0) + 1;
+        MemoryStore { db, config: MemoryConfig::default(), next_id: Cell::new(next_id) }
+    }
+
+    pub fn with_config(mut self, config: MemoryConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn config(&self) -> &MemoryConfig { &self.config }
+
+    /// The underlying store, for everything `MemoryStore` does not cover.
+    pub fn db(&self) -> &DB { &self.db }
+
+    /// Store `content` under `embedding` as a new memory, stamped now.
+    /// Returns its id.
+    pub fn remember(&self, content: &str, embedding: &[f32]) -> anyhow::Result<u64> {
+        self.remember_with(embedding, Metadata { content: content.to_string(), ..Metadata::default() })
+    }
+
+    /// Store a memory with full control over its metadata. A zero timestamp
+    /// is stamped with the current time.
+    pub fn remember_with(&self, embedding: &[f32], mut meta: Metadata) -> anyhow::Result<u64> {
+        if meta.timestamp == 0 {
+            meta.timestamp = feather_db_cli::decay::now();
+        }
+        let id = self.next_id.get();
+        self.db.add_with_metadata(id, embedding, &meta, &self.config.modality)?;
+        self.next_id.set(id + 1);
+        Ok(id)
+    }
+
+    /// The `k` memories most relevant to `query`, best first. Recalled
+    /// memories count as used, which restarts their decay.
+    pub fn recall(&self, query: &[f32], k: usize) -> anyhow::Result<Vec<Memory>> {
+        self.recall_where(query, k, |_| true)
+    }
+
+    /// One memory by id, unless it was forgotten.
+    pub fn get(&self, id: u64) -> Option<Memory> {
+        let metadata = self.db.get_metadata(id).filter(|m| !m.is_forgotten())?;
+        Some(Memory { id, score: metadata.importance, metadata })
+    }
+
+    pub fn forget(&self, id: u64) -> anyhow::Result<()> {
+        self.db.forget(id)
+    }
+
+    /// Keep `id` from fading or expiring. Returns false if there is no such
+    /// memory.
+    pub fn pin(&self, id: u64) -> anyhow::Result<bool> {
+        self.update(id, |meta| {
+            meta.attributes.insert(PINNED.to_string(), "true".to_string());
+            meta.ttl = 0;
+        })
+    }
+
+    /// Undo `pin`. Returns false if there is no such memory.
+    pub fn unpin(&self, id: u64) -> anyhow::Result<bool> {
+        self.update(id, |meta| { meta.attributes.remove(PINNED); })
+    }
+
+    /// Pinned memories, most important first.
+    pub fn pinned(&self) -> Vec<Memory> {
+        let mut pinned: Vec<Memory> = self.db.all_ids().into_iter()
+            .filter_map(|id| self.get(id))
+            .filter(Memory::is_pinned)
+            .collect();
+        pinned.sort_by(|a, b| b.metadata.importance.total_cmp(&a.metadata.importance));
+        pinned
+    }
+
+    /// Working memory scoped to the session `name`.
+    pub fn session(&self, name: &str) -> Session<'_> {
+        Session::new(self, name)
+    }
+
+    /// The periodic sweep: forget expired memories, then bake the decay
+    /// accrued so far into stored importances. Pinned memories keep theirs.
+    pub fn maintain(&self) -> anyhow::Result<MaintenanceReport> {
+        let mut report = MaintenanceReport { expired: self.db.expire()?, decayed: 0 };
+        if let Some(decay) = &self.config.decay {
+            let pinned: Vec<(u64, Metadata)> = self.pinned().into_iter().map(|m| (m.id, m.metadata)).collect();
+            let applied = feather_db_cli::decay::apply(&self.db, decay, feather_db_cli::decay::now(), false)?;
+            let mut restored = 0;
+            for (id, meta) in &pinned {
+                if self.db.get_metadata(*id).is_some_and(|m| m.importance < meta.importance) {
+                    restored += 1;
+                }
+                self.db.put_metadata(*id, meta)?;
+            }
+            report.decayed = applied.decayed - restored;
+        }
+        Ok(report)
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        self.db.save()
+    }
+
+    // `recall` over the memories `keep` accepts; the candidate pool grows
+    // until k of them are found or the store is exhausted.
+    pub(crate) fn recall_where(&self, query: &[f32], k: usize,
+                               keep: impl Fn(&Metadata) -> bool) -> anyhow::Result<Vec<Memory>> {
+        let since = feather_db_cli::decay::last_applied(&self.db);
+        let now = feather_db_cli::decay::now();
+        let mut fetch = k.saturating_mul(CANDIDATE_FACTOR);
+        let mut memories = loop {
+            let hits = self.db.knn(query, fetch, &self.config.modality)?;
+            let exhausted = hits.len() < fetch;
+            let memories: Vec<Memory> = hits.into_iter()
+                .filter_map(|(id, dist)| {
+                    let metadata = self.db.get_metadata(id).filter(|m| !m.is_forgotten() && keep(m))?;
+                    let importance = match &self.config.decay {
+                        Some(decay) if !is_pinned(&metadata) => decay.importance(&metadata, since, now),
+                        _ => metadata.importance,
+                    };
+                    Some(Memory { id, score: importance / (1.0 + dist), metadata })
+                })
+                .collect();
+            if memories.len() >= k || exhausted { break memories; }
+            fetch = fetch.saturating_mul(2);
+        };
+        memories.sort_by(|a, b| b.score.total_cmp(&a.score));
+        memories.truncate(k);
+        for memory in &memories {
+            self.db.touch(memory.id)?;
+        }
+        Ok(memories)
+    }
+
+    // Rewrite `id`'s metadata with `f`. False if there is no live memory `id`.
+    pub(crate) fn update(&self, id: u64, f: impl FnOnce(&mut Metadata)) -> anyhow::Result<bool> {
+        let Some(mut meta) = self.db.get_metadata(id).filter(|m| !m.is_forgotten()) else { return Ok(false) };
+        f(&mut meta);
+        self.db.put_metadata(id, &meta)?;
+        Ok(true)
+    }
+}
+
+fn is_pinned(meta: &Metadata) -> bool {
+    meta.attributes.get(PINNED).is_some_and(|v| v == "true")
+}
@@ -0,0 +1,77 @@
+//! Sessions: working memory scoped to one conversation or task.
+//!
+//! A session memory is an ordinary memory tagged with the `session`
+//! attribute and given the store's `session_ttl`, so an abandoned session
+//! cleans itself up. `commit` turns a session's memories into long-term ones
+//! (clears their ttl); `discard` forgets them right away. Store-wide `recall`
+//! sees session memories too; `Session::recall` sees only its own.
+
+use crate::{Memory, MemoryStore, Metadata, SESSION};
+
+pub struct Session<'a> {
+    store: &'a MemoryStore,
+    name: String,
+}
+
+impl<'a> Session<'a> {
+    pub(crate) fn new(store: &'a MemoryStore, name: &str) -> Self {
+        Session { store, name: name.to_string() }
+    }
+
+    pub fn name(&self) -> &str { &self.name }
+
+    /// Store `content` under `embedding` in this session. Returns its id.
+    pub fn remember(&self, content: &str, embedding: &[f32]) -> anyhow::Result<u64> {
+        self.remember_with(embedding, Metadata { content: content.to_string(), ..Metadata::default() })
+    }
+
+    /// `MemoryStore::remember_with`, tagged with this session. A memory
+    /// without a ttl of its own gets the store's `session_ttl`.
+    pub fn remember_with(&self, embedding: &[f32], mut meta: Metadata) -> anyhow::Result<u64> {
+        meta.attributes.insert(SESSION.to_string(), self.name.clone());
+        if meta.ttl == 0 {
+            meta.ttl = self.store.config().session_ttl;
+        }
+        self.store.remember_with(embedding, meta)
+    }
+
+    /// The `k` memories of this session most relevant to `query`.
+    pub fn recall(&self, query: &[f32], k: usize) -> anyhow::Result<Vec<Memory>> {
+        self.store.recall_where(query, k, |meta| self.owns(meta))
+    }
+
+    /// Ids of this session's live memories.
+    pub fn memories(&self) -> Vec<u64> {
+        let db = self.store.db();
+        db.all_ids().into_iter()
+            .filter(|&id| db.get_metadata(id).is_some_and(|m| !m.is_forgotten() && self.owns(&m)))
+            .collect()
+    }
+
+    /// Keep this session's memories for good: clear their ttl. They stay
+    /// tagged with the session. Returns how many were kept.
+    pub fn commit(&self) -> anyhow::Result<usize> {
+        let ids = self.memories();
+        for &id in &ids {
+            self.store.update(id, |meta| meta.ttl = 0)?;
+        }
+        Ok(ids.len())
+    }
+
+    /// Forget this session's memories now, pinned ones excepted. Returns how
+    /// many were forgotten.
+    pub fn discard(&self) -> anyhow::Result<usize> {
+        let mut forgotten = 0;
+        for id in self.memories() {
+            if self.store.get(id).is_some_and(|m| !m.is_pinned()) {
+                self.store.forget(id)?;
+                forgotten += 1;
+            }
+        }
+        Ok(forgotten)
+    }
+
+    fn owns(&self, meta: &Metadata) -> bool {
+        meta.attributes.get(SESSION).is_some_and(|s| *s == self.name)
+    }
+}
@@ -0,0 +1,288 @@
+//! Python bindings for a Feather store: the `feather_py` module.
+//!
+//! ```python
+//! from feather_py import Feather
+//!
+//! db = Feather.open("memory.feather", dim=768)
+//! db.add(1, embedding, content="The user prefers dark roast", source="chat")
+//! for hit in db.search(query, k=5):
+//!     print(hit.id, hit.score, hit.metadata.content)
+//! ```
+//!
+//! Vectors may be any sequence of numbers; a contiguous 1-D `float32` numpy
+//! array is read in place, without a copy. A `Feather` may only be used from
+//! the thread that opened it (a `DB` is not `Send`). Writes reach the WAL at
+//! once; `save` checkpoints the file.
+
+// pyo3 0.22's generated wrappers convert `PyErr` into itself.
+#![allow(clippy::useless_conversion)]
+
+use feather_db_cli::{AutoSave, Filter, Inserted, Metadata, OpenOptions, SearchOptions, SortBy, DB};
+use numpy::{PyReadonlyArray1, PyUntypedArrayMethods};
+use pyo3::exceptions::{PyOSError, PyValueError};
+use pyo3::prelude::*;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+fn invalid(e: anyhow::Error) -> PyErr {
+    PyValueError::new_err(format!("{:#}", e))
+}
+
+// A vector argument: borrowed from a float32 array when it can be, copied
+// out of anything else.
+enum Vector<'py> {
+    Array(PyReadonlyArray1<'py, f32>),
+    Owned(Vec<f32>),
+}
+
+impl<'py> Vector<'py> {
+    fn extract(obj: &Bound<'py, PyAny>) -> PyResult<Self> {
+        if let Ok(array) = obj.extract::<PyReadonlyArray1<'py, f32>>() {
+            if array.is_contiguous() {
+                return Ok(Vector::Array(array));
+            }
+        }
+        obj.extract().map(Vector::Owned)
+    }
+
+    fn as_slice(&self) -> &[f32] {
+        match self {
+            Vector::Array(array) => array.as_slice().expect("contiguous"),
+            Vector::Owned(vec) => vec,
+        }
+    }
+}
+
+/// A record's metadata, as `search` and `get` return it.
+#[pyclass(name = "Metadata", module = "feather_py", frozen, get_all)]
+#[derive(Clone)]
+pub struct PyMetadata {
+    timestamp: i64,
+    importance: f32,
+    confidence: f32,
+    context_type: String,
+    source: String,
+    content: String,
+    namespace: String,
+    entity: String,
+    attributes: BTreeMap<String, String>,
+    /// `(target, rel_type, weight)` per outgoing edge.
+    edges: Vec<(u64, String, f32)>,
+    ttl: i64,
+    recall_count: u32,
+    last_recalled_at: u64,
+}
+
+impl From<Metadata> for PyMetadata {
+    fn from(meta: Metadata) -> Self {
+        PyMetadata {
+            timestamp: meta.timestamp,
+            importance: meta.importance,
+            confidence: meta.confidence,
+            context_type: meta.context_type.to_string(),
+            source: meta.source,
+            content: meta.content,
+            namespace: meta.namespace_id,
+            entity: meta.entity_id,
+            attributes: meta.attributes,
+            edges: meta.edges.into_iter().map(|e| (e.target, e.rel_type, e.weight)).collect(),
+            ttl: meta.ttl,
+            recall_count: meta.recall_count,
+            last_recalled_at: meta.last_recalled_at,
+        }
+    }
+}
+
+#[pymethods]
+impl PyMetadata {
+    fn __repr__(&self) -> String {
+        format!("Metadata(content={:?}, source={:?}, importance={}, timestamp={})",
+                self.content, self.source, self.importance, self.timestamp)
+    }
+}
+
+/// One search result.
+#[pyclass(module = "feather_py", frozen, get_all)]
+#[derive(Clone)]
+pub struct Hit {
+    id: u64,
+    score: f32,
+    metadata: PyMetadata,
+}
+
+#[pymethods]
+impl Hit {
+    fn __repr__(&self) -> String {
+        format!("Hit(id={}, score={:.4}, content={:?})", self.id, self.score, self.metadata.content)
+    }
+}
+
+/// A record found by a metadata query.
+#[pyclass(module = "feather_py", frozen, get_all)]
+#[derive(Clone)]
+pub struct Record {
+    id: u64,
+    metadata: PyMetadata,
+}
+
+#[pymethods]
+impl Record {
+    fn __repr__(&self) -> String {
+        format!("Record(id={}, content={:?})", self.id, self.metadata.content)
+    }
+}
+
+fn sort_order(name: &str) -> PyResult<SortBy> {
+    match name {
+        "recency" | "timestamp" => Ok(SortBy::Recency),
+        "importance" => Ok(SortBy::Importance),
+        "recalls" => Ok(SortBy::Recalls),
+        "id" => Ok(SortBy::Id),
+        _ => Err(PyValueError::new_err(format!(
+            "sort_by must be 'recency', 'importance', 'recalls' or 'id', got {:?}", name))),
+    }
+}
+
+/// An open Feather store.
+#[pyclass(module = "feather_py", unsendable)]
+pub struct Feather {
+    db: DB,
+}
+
+#[pymethods]
+impl Feather {
+    /// Open the store at `path`, creating it if need be; `dim` is needed
+    /// only to create one. `collection` scopes the handle to one
+    /// collection; `normalize` turns on unit-length vectors for the file.
+    /// `save_every` (changes) and `save_interval` (seconds) save the store
+    /// without calling `save`.
+    #[staticmethod]
+    #[pyo3(signature = (path, dim = 0, *, collection = None, normalize = false, save_every = 0,
+                        save_interval = None))]
+    fn open(path: PathBuf, dim: usize, collection: Option<&str>, normalize: bool, save_every: usize,
+            save_interval: Option<f64>) -> PyResult<Self> {
+        let interval = save_interval.map(std::time::Duration::try_from_secs_f64).transpose()
+            .map_err(|e| PyValueError::new_err(format!("save_interval: {}", e)))?;
+        let db = OpenOptions::new()
+            .dim(dim)
+            .create(true)
+            .normalize(normalize)
+            .auto_save(AutoSave { every: save_every, interval })
+            .open(&path)
+            .map_err(|e| PyOSError::new_err(format!("{:#}", e)))?;
+        db.expire().map_err(invalid)?;
+        let db = match collection {
+            Some(name) => db.collection(name).map_err(invalid)?,
+            None => db,
+        };
+        Ok(Feather { db })
+    }
+
+    /// Add (or replace) record `id`, subject to the store's duplicate-id
+    /// policy and dedup mode. Returns the id the record is stored under:
+    /// `id`, or the existing record dedup found it repeats.
+    #[pyo3(signature = (id, vector, *, modality = "text", content = None, source = None,
+                        importance = None, confidence = None, timestamp = None,
+                        namespace = None, entity = None, attributes = None))]
+    #[allow(clippy::too_many_arguments)]
+    fn add(&self, id: u64, vector: &Bound<'_, PyAny>, modality: &str, content: Option<&str>,
+           source: Option<&str>, importance: Option<f32>, confidence: Option<f32>,
+           timestamp: Option<i64>, namespace: Option<&str>, entity: Option<&str>,
+           attributes: Option<BTreeMap<String, String>>) -> PyResult<u64> {
+        let vector = Vector::extract(vector)?;
+        let mut insert = self.db.insert(id, vector.as_slice()).modality(modality);
+        if let Some(content) = content { insert = insert.content(content); }
+        if let Some(source) = source { insert = insert.source(source); }
+        if let Some(importance) = importance { insert = insert.importance(importance); }
+        if let Some(confidence) = confidence { insert = insert.confidence(confidence); }
+        if let Some(timestamp) = timestamp { insert = insert.timestamp(timestamp); }
+        if let Some(namespace) = namespace { insert = insert.namespace(namespace); }
+        if let Some(entity) = entity { insert = insert.entity(entity); }
+        for (key, value) in attributes.iter().flatten() {
+            insert = insert.attribute(key, value);
+        }
+        Ok(match insert.execute().map_err(invalid)? {
+            Inserted::DuplicateOf(existing) => existing,
+            Inserted::Written | Inserted::Kept => id,
+        })
+    }
+
+    /// The `k` records nearest `query`, best first, with their metadata.
+    /// `filter` takes an expression as `feather search --filter` does;
+    /// `text` adds hybrid keyword ranking.
+    #[pyo3(signature = (query, k = 10, *, modality = "text", filter = None, text = None,
+                        offset = 0, min_score = None))]
+    #[allow(clippy::too_many_arguments)]
+    fn search(&self, query: &Bound<'_, PyAny>, k: usize, modality: &str, filter: Option<&str>,
+              text: Option<String>, offset: usize, min_score: Option<f32>) -> PyResult<Vec<Hit>> {
+        let query = Vector::extract(query)?;
+        let options = SearchOptions {
+            offset,
+            filter: filter.map(Filter::parse).transpose().map_err(invalid)?,
+            text,
+            min_score,
+            ..SearchOptions::default()
+        };
+        let hits = self.db.search_with_options(query.as_slice(), k, modality, &options).map_err(invalid)?;
+        Ok(hits.into_iter()
+            .filter_map(|(id, score)| {
+                let metadata = self.db.get_metadata(id)?.into();
+                Some(Hit { id, score, metadata })
+            })
+            .collect())
+    }
+
+    /// Live records matching `filter` (an expression, as for `search`),
+    /// with no query vector: sorted by `sort_by` — "recency" (newest
+    /// first, also "timestamp"), "importance", "recalls" or "id" — the
+    /// `limit` after the first `offset`.
+    #[pyo3(signature = (filter = None, *, sort_by = "recency", limit = 10, offset = 0))]
+    fn query_meta(&self, filter: Option<&str>, sort_by: &str, limit: usize, offset: usize) -> PyResult<Vec<Record>> {
+        let filter = filter.map(Filter::parse).transpose().map_err(invalid)?;
+        let records = self.db.list(sort_order(sort_by)?, offset, limit, filter.as_ref());
+        Ok(records.into_iter().map(|(id, meta)| Record { id, metadata: meta.into() }).collect())
+    }
+
+    /// The `k` newest live records matching `filter`.
+    #[pyo3(signature = (k = 10, *, filter = None))]
+    fn recent(&self, k: usize, filter: Option<&str>) -> PyResult<Vec<Record>> {
+        self.query_meta(filter, "recency", k, 0)
+    }
+
+    /// Record `id`'s metadata; None if there is no such live record.
+    fn get(&self, id: u64) -> Option<PyMetadata> {
+        self.db.get_metadata(id).filter(|m| !m.is_forgotten()).map(PyMetadata::from)
+    }
+
+    /// Forget record `id`; a no-op for an unknown id.
+    fn forget(&self, id: u64) -> PyResult<()> {
+        self.db.forget(id).map_err(invalid)
+    }
+
+    /// Dimension of a modality's vectors (the open-time `dim` while it
+    /// has none).
+    #[pyo3(signature = (modality = "text"))]
+    fn dim(&self, modality: &str) -> usize {
+        self.db.dim(modality)
+    }
+
+    /// Checkpoint the file.
+    fn save(&self) -> PyResult<()> {
+        self.db.save().map_err(|e| PyOSError::new_err(format!("{:#}", e)))
+    }
+
+    /// Sync the WAL to disk, so the changes made so far survive a crash,
+    /// without the cost of `save`.
+    fn flush(&self) -> PyResult<()> {
+        self.db.flush().map_err(|e| PyOSError::new_err(format!("{:#}", e)))
+    }
+}
+
+#[pymodule]
+fn feather_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Feather>()?;
+    m.add_class::<Hit>()?;
+    m.add_class::<PyMetadata>()?;
+    m.add_class::<Record>()?;
+    Ok(())
+}
@@ -1,4 +1,4 @@
-from .core import (DB, ContextType, Metadata, ScoringConfig,
+from .core import (DB, ContextType, Compression, Metadata, ScoringConfig,
                    Edge, IncomingEdge,
                    ContextNode, ContextEdge, ContextChainResult)
 from .filter import FilterBuilder
@@ -30,7 +30,7 @@ from .providers import (
 from .engine import ContextEngine
 
 __all__ = [
-    "DB", "ContextType", "Metadata", "ScoringConfig",
+    "DB", "ContextType", "Compression", "Metadata", "ScoringConfig",
     "Edge", "IncomingEdge",
     "ContextNode", "ContextEdge", "ContextChainResult",
     "FilterBuilder",
@@ -22,7 +22,13 @@ struct SearchFilter {
     std::optional<std::string> entity_id;
     std::optional<std::unordered_map<std::string, std::string>> attributes_match;
 
+    // With this set, archived records (ARCHIVED_ATTRIBUTE) never match. The
+    // core leaves them in by default; bindings that hide them set it.
+    bool exclude_archived = false;
+
     bool matches(const Metadata& meta) const {
+        if (exclude_archived && meta.attributes.count(ARCHIVED_ATTRIBUTE)) return false;
+
         if (types) {
             bool found = false;
             for (auto t : *types) {
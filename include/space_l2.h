@@ -1,5 +1,9 @@
 #pragma once
 #include "hnswlib.h"
+#include <cstring>
+#ifdef _MSC_VER
+#include <intrin.h>
+#endif
 
 namespace hnswlib {
 
@@ -299,6 +303,61 @@ class Int8L2Space : public SpaceInterface<float> {
     ~Int8L2Space() {}
 };
 
+// ── Binary (1 bit per dimension) Hamming space ───────────────────────────
+// Stores each vector as ceil(dim/64) uint64 words, bit i of the vector in bit
+// i%64 of word i/64 (32x smaller than float32). Distance is the number of
+// differing bits, popcount(a ^ b) summed over the words — which equals the
+// squared L2 of the same vectors written as 0.0/1.0 floats.
+// Param layout { size_t words; size_t dim } — words MUST be first so hnswlib's
+// getDataByLabel<uint64_t>() (which reads *(size_t*)param) returns the vector.
+struct HammingParams {
+    size_t words;
+    size_t dim;
+};
+
+static inline size_t
+Popcount64(uint64_t x) {
+#ifdef _MSC_VER
+    return static_cast<size_t>(__popcnt64(x));
+#else
+    return static_cast<size_t>(__builtin_popcountll(x));
+#endif
+}
+
+static float
+HammingDistance(const void *pa, const void *pb, const void *param_ptr) {
+    const HammingParams *p = static_cast<const HammingParams *>(param_ptr);
+    const char *a = static_cast<const char *>(pa);
+    const char *b = static_cast<const char *>(pb);
+    size_t acc = 0;
+    for (size_t i = 0; i < p->words; i++) {
+        uint64_t x, y;   // memcpy: a query blob need not be 8-byte aligned
+        std::memcpy(&x, a + i * sizeof(uint64_t), sizeof(uint64_t));
+        std::memcpy(&y, b + i * sizeof(uint64_t), sizeof(uint64_t));
+        acc += Popcount64(x ^ y);
+    }
+    return static_cast<float>(acc);
+}
+
+class HammingSpace : public SpaceInterface<float> {
+    HammingParams params_;
+    size_t data_size_;
+
+ public:
+    explicit HammingSpace(size_t dim) {
+        params_.words = (dim + 63) / 64;
+        params_.dim = dim;
+        data_size_ = params_.words * sizeof(uint64_t);
+    }
+
+    size_t get_data_size() { return data_size_; }
+    DISTFUNC<float> get_dist_func() { return HammingDistance; }
+    void *get_dist_func_param() { return &params_; }
+
+    ~HammingSpace() {}
+};
+
+
 static int
 L2SqrI4x(const void *__restrict pVect1, const void *__restrict pVect2, const void *__restrict qty_ptr) {
     size_t qty = *((size_t *) qty_ptr);
@@ -24,11 +24,20 @@ if _machine in ("x86_64", "amd64"):
         if _mode == "avx512":
             _simd_args += ["-DUSE_AVX512", "-mavx512f", "-mavx512dq"]
 
+# The core packs files with zstd (format v12): the system's libzstd, or the
+# one installed under ZSTD_DIR (its include/ and lib/).
+_zstd_dir = os.getenv("ZSTD_DIR")
+_zstd_include = [os.path.join(_zstd_dir, "include")] if _zstd_dir else []
+_zstd_lib = [os.path.join(_zstd_dir, "lib")] if _zstd_dir else []
+
 ext_modules = [
     Extension(
         "feather_db.core",
         ["bindings/feather.cpp", "src/metadata.cpp", "src/filter.cpp", "src/scoring.cpp"],
-        include_dirs=[pybind11.get_include(), "include"],
+        include_dirs=[pybind11.get_include(), "include"] + _zstd_include,
+        library_dirs=_zstd_lib,
+        libraries=["zstd"],
+        define_macros=[("FEATHER_ZSTD", None)],
         language="c++",
         extra_compile_args=["-O3", "-std=c++17"] + _simd_args,
         extra_link_args=extra_link_args,
@@ -0,0 +1,67 @@
+"""Test feather-api auth: scoped keys stay in their namespaces, and minting or
+revoking keys can never lock everyone out of /v1/admin."""
+import os, sys, tempfile
+
+_dir = tempfile.mkdtemp()
+os.environ["FEATHER_DATA_DIR"] = _dir
+os.environ["FEATHER_KEYS_FILE"] = os.path.join(_dir, "_keys.json")
+os.environ.pop("FEATHER_API_KEY", None)
+sys.path.insert(0, os.path.join(os.path.dirname(os.path.abspath(__file__)), "feather-api"))
+
+from fastapi.testclient import TestClient
+from app import main
+from app.auth import KeyStore
+
+fails = []
+def check(name, cond, extra=""):
+    print(f"  {'PASS' if cond else 'FAIL'}: {name} {extra}")
+    if not cond: fails.append(name)
+
+def mint(client, token=None, **body):
+    headers = {"X-API-Key": token} if token else {}
+    return client.post("/v1/admin/keys", json=body, headers=headers)
+
+with TestClient(main.app) as client:
+    print("0) dev mode: the first key minted must be an admin key")
+    r = mint(client, name="product", namespaces=["team_a_*"])
+    check("non-admin first key refused", r.status_code == 400, f"got {r.status_code}")
+    check("still dev mode", not main.KEYS.enabled)
+    r = mint(client, name="ops", admin=True)
+    check("admin first key minted", r.status_code == 201, f"got {r.status_code}")
+    admin = r.json()["token"]
+    check("auth now on", client.get("/v1/namespaces").status_code == 401)
+    check("admin reaches /v1/admin",
+          client.get("/v1/admin/keys", headers={"X-API-Key": admin}).status_code == 200)
+
+    print("1) a scoped key is held to its namespaces and kept out of /v1/admin")
+    r = mint(client, admin, name="product", namespaces=["team_a_*"])
+    check("scoped key minted", r.status_code == 201, f"got {r.status_code}")
+    scoped = {"X-API-Key": r.json()["token"]}
+    check("in scope passes auth",
+          client.get("/v1/team_a_x/records/1", headers=scoped).status_code == 404)
+    check("out of scope refused",
+          client.get("/v1/team_b/records/1", headers=scoped).status_code == 403)
+    check("/v1/admin refused", client.get("/v1/admin/keys", headers=scoped).status_code == 403)
+    check("minting refused", mint(client, scoped["X-API-Key"], name="x", admin=True).status_code == 403)
+    check("creating a namespace refused",
+          client.post("/v1/namespaces", json={"name": "team_a_y"}, headers=scoped).status_code == 403)
+
+    print("2) the last admin key cannot be revoked")
+    admin_id = next(k["id"] for k in client.get("/v1/admin/keys", headers={"X-API-Key": admin}).json()["keys"]
+                    if k["admin"])
+    r = client.delete(f"/v1/admin/keys/{admin_id}", headers={"X-API-Key": admin})
+    check("last admin kept", r.status_code == 400, f"got {r.status_code}")
+    second = mint(client, admin, name="ops-2", admin=True).json()["token"]
+    r = client.delete(f"/v1/admin/keys/{admin_id}", headers={"X-API-Key": second})
+    check("revoked once another admin exists", r.status_code == 200, f"got {r.status_code}")
+    check("the other still reaches /v1/admin",
+          client.get("/v1/admin/keys", headers={"X-API-Key": second}).status_code == 200)
+
+print("3) with a master key, non-admin keys may come first")
+store = KeyStore(os.path.join(_dir, "_master_keys.json"), master_key="secret")
+key, _ = store.create("product", ["team_a_*"])
+check("scoped first key minted", not key.admin and store.enabled)
+
+print()
+print("ALL PASS" if not fails else f"{len(fails)} FAILURES: {fails}")
+sys.exit(1 if fails else 0)
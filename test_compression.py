@@ -0,0 +1,94 @@
+"""Test: packed store files (file format v12, zstd).
+
+set_compression() packs the metadata section, or every section, on save.
+Asserts:
+  1) round-trip — content and search results IDENTICAL after save+reload,
+     for Compression.METADATA and Compression.ALL, and the file is smaller
+  2) a packed file is v12 and remembers its setting; saved unpacked it is
+     v11 again, so older builds still read it
+  3) a damaged packed section fails the open and leaves the file as it was
+"""
+import os, sys, glob, tempfile, importlib.util, numpy as np
+
+_tag = f"cpython-{sys.version_info.major}{sys.version_info.minor}"
+_cands = [p for p in glob.glob("feather_db/core*.so") if _tag in p]
+_so = (_cands or sorted(glob.glob("feather_db/core*.so")))[0]
+_spec = importlib.util.spec_from_file_location("core", _so)
+fc = importlib.util.module_from_spec(_spec); _spec.loader.exec_module(fc)
+
+DIM, N, K = 32, 3000, 10
+rng = np.random.default_rng(5)
+vecs = rng.standard_normal((N, DIM)).astype(np.float32)
+queries = rng.standard_normal((20, DIM)).astype(np.float32)
+words = ["alpha", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel"]
+texts = [" ".join(rng.choice(words, 60)) for _ in range(N)]
+fails = 0
+
+def check(cond, msg, extra=""):
+    global fails
+    print(f"  {'PASS' if cond else 'FAIL'}: {msg} {extra}")
+    if not cond: fails += 1
+
+def version(path):
+    with open(path, "rb") as fh:
+        fh.read(4); return int.from_bytes(fh.read(4), "little")
+
+def build(path, compression):
+    db = fc.DB.open(path, dim=DIM)
+    metas = []
+    for i in range(N):
+        m = fc.Metadata(); m.content = texts[i]; metas.append(m)
+    db.add_batch(ids=list(range(N)), vecs=vecs, metas=metas)
+    db.set_compression(compression)
+    db.save()
+    return db
+
+def topk(db, q):
+    return [r.id for r in db.search(q, k=K)]
+
+plain = tempfile.mktemp(suffix=".feather")
+db = build(plain, fc.Compression.NONE); del db
+check(version(plain) == 11, "unpacked file is v11", f"got v{version(plain)}")
+
+for compression in (fc.Compression.METADATA, fc.Compression.ALL):
+    print(f"1) round-trip with {compression}")
+    p = tempfile.mktemp(suffix=".feather")
+    db = build(p, compression)
+    before = [topk(db, q) for q in queries]
+    del db
+    check(version(p) == 12, "packed file is v12", f"got v{version(p)}")
+    check(os.path.getsize(p) < os.path.getsize(plain), "smaller than unpacked",
+          f"{os.path.getsize(p)} vs {os.path.getsize(plain)} bytes")
+    db2 = fc.DB.open(p, dim=DIM)
+    check(db2.compression() == compression, "setting kept in the file")
+    check(all(db2.get_metadata(i).content == texts[i] for i in (0, N // 2, N - 1)),
+          "content identical after reload")
+    after = [topk(db2, q) for q in queries]
+    check(after == before, "search identical after reload")
+
+    print("2) saved unpacked again")
+    db2.set_compression(fc.Compression.NONE)
+    db2.save()
+    del db2
+    check(version(p) == 11, "back to v11", f"got v{version(p)}")
+    check(fc.DB.open(p, dim=DIM).get_metadata(1).content == texts[1], "still whole")
+    for f in glob.glob(p + "*"): os.remove(f)
+
+print("3) damaged packed section")
+p = tempfile.mktemp(suffix=".feather")
+db = build(p, fc.Compression.ALL); del db
+with open(p, "rb") as fh: data = bytearray(fh.read())
+mid = len(data) // 2
+data[mid:mid + 64] = bytes(b ^ 0x5A for b in data[mid:mid + 64])
+with open(p, "wb") as fh: fh.write(data)
+try:
+    fc.DB.open(p, dim=DIM)
+    check(False, "damaged file refused")
+except RuntimeError as e:
+    check(True, "damaged file refused", f"({e})")
+with open(p, "rb") as fh:
+    check(fh.read() == bytes(data), "damaged file left as it was")
+for f in glob.glob(p + "*") + glob.glob(plain + "*"): os.remove(f)
+
+print("\n" + ("ALL PASS" if fails == 0 else f"{fails} FAILED"))
+sys.exit(1 if fails else 0)
@@ -56,7 +56,7 @@ qs = (centers[rng.integers(0, K, size=80)]
 # ── 1) persisted-graph fast path ────────────────────────────────────────
 with open(path, "rb") as fh:
     fh.read(4); ver = int.from_bytes(fh.read(4), "little")
-check("file format v9 (persisted graph)", ver == 9, f"got v{ver}")
+check("file format v11 (persisted graph since v9)", ver == 11, f"got v{ver}")
 print("persisted-graph reload ...")
 dbp, t_persist = timed_load(1)
 print(f"  persisted load: {t_persist*1000:8.1f} ms")
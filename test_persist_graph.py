@@ -52,10 +52,10 @@ print(f"1) round-trip: {N}x{DIM}, file {fsz:.1f}MB, reload {load_dt*1000:.0f}ms"
 identical = sum(1 for a, b in zip(before, after) if a == b)
 check(identical == len(queries), "all 50 queries identical after reload",
       f"{identical}/{len(queries)}")
-# version on disk should be 9
+# version on disk: the graph is persisted since v9; an unpacked store is v11
 with open(p, "rb") as fh:
     fh.read(4); ver = int.from_bytes(fh.read(4), "little")
-check(ver == 9, "file format v9", f"got v{ver}")
+check(ver == 11, "file format v11", f"got v{ver}")
 
 # ── compare to a pure-rebuild reload time (force fallback via a forget) ──
 print("2) fallback path: forgotten record disables graph-persist")